@@ -0,0 +1,237 @@
+use crate::engine::value::SqlValue;
+use crate::engine::{DbEngine, DbSession, InsertMode};
+use crate::util::batch::{row_sql_len, BatchLimit};
+use anyhow::{Context, Result};
+use std::time::{Duration, Instant};
+
+pub struct BenchOptions {
+    /// Scratch table created for each run and dropped afterwards
+    pub table: String,
+    /// Rows inserted per run
+    pub rows: usize,
+    pub batch_sizes: Vec<usize>,
+    pub insert_modes: Vec<InsertMode>,
+}
+
+/// Outcome of inserting the synthetic rows with one batch size and mode
+pub struct BenchResult {
+    pub insert_mode: InsertMode,
+    pub batch_rows: usize,
+    /// Time to insert and commit every row, or why the run failed
+    pub outcome: Result<Duration, String>,
+    /// Approximate SQL size of the rows, for a bytes/sec figure
+    pub bytes: usize,
+}
+
+impl BenchResult {
+    pub fn rows_per_sec(&self, rows: usize) -> Option<f64> {
+        let elapsed = self.outcome.as_ref().ok()?;
+        Some(rows as f64 / elapsed.as_secs_f64().max(1e-6))
+    }
+}
+
+pub async fn bench(engine: &dyn DbEngine, url: &str, opts: BenchOptions) -> Result<()> {
+    println!("Starting insert benchmark...");
+    println!(
+        "Inserting {} synthetic rows into '{}' per run",
+        opts.rows, opts.table
+    );
+
+    let results = run_benchmarks(engine, url, &opts).await?;
+
+    println!("\n═══════════════════════════════════════");
+    println!("Insert Benchmark");
+    println!("═══════════════════════════════════════");
+    println!(
+        "{:<10} {:>10} {:>12} {:>10}",
+        "Mode", "Batch", "Rows/sec", "MB/sec"
+    );
+    for result in &results {
+        match &result.outcome {
+            Ok(elapsed) => println!(
+                "{:<10} {:>10} {:>12.0} {:>10.2}",
+                result.insert_mode.to_string(),
+                result.batch_rows,
+                result.rows_per_sec(opts.rows).unwrap_or_default(),
+                result.bytes as f64 / elapsed.as_secs_f64().max(1e-6) / (1024.0 * 1024.0)
+            ),
+            Err(e) => println!(
+                "{:<10} {:>10}   failed: {}",
+                result.insert_mode.to_string(),
+                result.batch_rows,
+                e
+            ),
+        }
+    }
+    println!("═══════════════════════════════════════");
+
+    let best = results.iter().max_by(|a, b| {
+        let a = a.rows_per_sec(opts.rows).unwrap_or_default();
+        let b = b.rows_per_sec(opts.rows).unwrap_or_default();
+        a.total_cmp(&b)
+    });
+    match best.filter(|b| b.outcome.is_ok()) {
+        Some(best) => println!(
+            "Fastest: --insert-mode {} --batch-rows {}",
+            best.insert_mode, best.batch_rows
+        ),
+        None => println!("No run completed"),
+    }
+
+    Ok(())
+}
+
+/// Insert the synthetic rows once for every mode and batch size. Each run
+/// gets a fresh session and table, so one failing leaves the rest unaffected.
+pub async fn run_benchmarks(
+    engine: &dyn DbEngine,
+    url: &str,
+    opts: &BenchOptions,
+) -> Result<Vec<BenchResult>> {
+    let columns: Vec<String> = ["id", "name", "amount", "created_at"]
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    let rows: Vec<Vec<SqlValue>> = (0..opts.rows).map(synthetic_row).collect();
+    let bytes = rows.iter().map(|row| row_sql_len(row)).sum();
+
+    let mut results = Vec::new();
+    for &insert_mode in &opts.insert_modes {
+        for &batch_rows in &opts.batch_sizes {
+            print!(
+                "Running {} with {} rows per batch...",
+                insert_mode, batch_rows
+            );
+            let outcome = run_once(engine, url, opts, insert_mode, batch_rows, &columns, &rows)
+                .await
+                .map_err(|e| format!("{:#}", e));
+            match &outcome {
+                Ok(elapsed) => println!(" {:.2}s", elapsed.as_secs_f64()),
+                Err(_) => println!(" failed"),
+            }
+
+            results.push(BenchResult {
+                insert_mode,
+                batch_rows,
+                outcome,
+                bytes,
+            });
+        }
+    }
+
+    Ok(results)
+}
+
+async fn run_once(
+    engine: &dyn DbEngine,
+    url: &str,
+    opts: &BenchOptions,
+    insert_mode: InsertMode,
+    batch_rows: usize,
+    columns: &[String],
+    rows: &[Vec<SqlValue>],
+) -> Result<Duration> {
+    let mut session = engine
+        .connect(url)
+        .await
+        .context("Failed to connect to database")?;
+    session.set_insert_mode(insert_mode)?;
+
+    let drop = session.dialect().drop_table_statement(&opts.table);
+    session.execute(&drop).await?;
+    session
+        .create_table_from_columns(&opts.table, columns, &rows[0])
+        .await
+        .context("Failed to create benchmark table")?;
+    session.commit().await?;
+
+    let limit = BatchLimit::new(batch_rows, None, session.max_statement_bytes());
+    let started = Instant::now();
+    let inserted = insert_rows(&mut *session, &opts.table, columns, rows, limit).await;
+    let elapsed = inserted
+        .and(session.commit().await)
+        .map(|_| started.elapsed());
+
+    // Clean up even after a failed run; that failure is the one to report
+    let dropped = async {
+        let mut session = engine.connect(url).await?;
+        session.execute(&drop).await?;
+        session.commit().await
+    }
+    .await;
+
+    let elapsed = elapsed?;
+    dropped.context("Failed to drop benchmark table")?;
+    Ok(elapsed)
+}
+
+async fn insert_rows(
+    session: &mut dyn DbSession,
+    table: &str,
+    columns: &[String],
+    rows: &[Vec<SqlValue>],
+    limit: BatchLimit,
+) -> Result<()> {
+    let mut start = 0;
+    let mut bytes = 0;
+    for (i, row) in rows.iter().enumerate() {
+        bytes += row_sql_len(row);
+        if limit.is_full(i + 1 - start, bytes) {
+            session
+                .insert_batch(table, columns, &rows[start..=i])
+                .await?;
+            start = i + 1;
+            bytes = 0;
+        }
+    }
+    if start < rows.len() {
+        session.insert_batch(table, columns, &rows[start..]).await?;
+    }
+    Ok(())
+}
+
+/// A row resembling typical application data: key, short text, a float
+/// and a timestamp
+fn synthetic_row(i: usize) -> Vec<SqlValue> {
+    let seconds = (i % 86_400) as u32;
+    vec![
+        SqlValue::Int(i as i64 + 1),
+        SqlValue::String(format!("customer {} / order {}", i % 9973, i)),
+        SqlValue::Float((i % 100_000) as f64 / 100.0),
+        SqlValue::Timestamp {
+            y: 2024,
+            m: (i % 12) as u32 + 1,
+            d: (i % 28) as u32 + 1,
+            hh: seconds / 3600,
+            mm: seconds / 60 % 60,
+            ss: seconds % 60,
+            us: 0,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::sqlite::SqliteEngine;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reports_each_mode_and_batch_size() {
+        let opts = BenchOptions {
+            table: "bench".to_string(),
+            rows: 250,
+            batch_sizes: vec![10, 100],
+            insert_modes: vec![InsertMode::Literal, InsertMode::Copy],
+        };
+
+        let results = run_benchmarks(&SqliteEngine, "sqlite::memory:", &opts)
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 4);
+        assert!(results[..2].iter().all(|r| r.outcome.is_ok()));
+        // SQLite has no COPY; those runs fail without stopping the others
+        assert!(results[2..].iter().all(|r| r.outcome.is_err()));
+        assert!(results[0].rows_per_sec(opts.rows).unwrap() > 0.0);
+    }
+}
@@ -1,4 +1,69 @@
-use clap::{Parser, Subcommand};
+use crate::engine::dialect::OnConflict;
+use crate::engine::{self, InsertMode};
+use crate::migrate::{AutoAlter, IdentifierCase, IfExists, Verify};
+use crate::util::batch::BatchRows;
+use crate::util::charset::Charset;
+use crate::util::compatible::Compatible;
+use crate::util::compress::Codec;
+use crate::util::encrypt::Recipient;
+use crate::util::manifest::DumpFormat;
+use clap::{ArgAction, Parser, Subcommand};
+use std::time::Duration;
+
+/// Accept any provider `engine::create_engine` can resolve, including
+/// engines registered at runtime
+fn parse_provider(name: &str) -> Result<String, String> {
+    engine::create_engine(name)
+        .map(|_| name.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// A duration such as `500ms`, `2s`, `1m` or `1h`; a bare number is seconds
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(at) => value.split_at(at),
+        None => (value, "s"),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", value))?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(format!("invalid duration '{}' (use ms, s, m or h)", value)),
+    };
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+/// A rename such as `prod_db:staging_db`
+fn parse_mapping(value: &str) -> Result<(String, String), String> {
+    match value.split_once(':') {
+        Some((old, new)) if !old.trim().is_empty() && !new.trim().is_empty() => {
+            Ok((old.trim().to_string(), new.trim().to_string()))
+        }
+        _ => Err(format!("invalid mapping '{}' (expected old:new)", value)),
+    }
+}
+
+/// A share of rows such as `5%`, or a fraction such as `0.05`
+fn parse_sample(value: &str) -> Result<f64, String> {
+    let value = value.trim();
+    let fraction = match value.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>().map(|p| p / 100.0),
+        None => value.parse::<f64>(),
+    }
+    .map_err(|_| format!("invalid sample '{}'", value))?;
+    if !(fraction > 0.0 && fraction <= 1.0) {
+        return Err(format!(
+            "invalid sample '{}' (must be above 0% and at most 100%)",
+            value
+        ));
+    }
+    Ok(fraction)
+}
 
 #[derive(Parser, Debug)]
 #[command(name = "migrasquiel")]
@@ -11,11 +76,15 @@ pub struct Cli {
     pub command: Commands,
 }
 
+// Parsed once per run, so the size of the largest command's options is of
+// no concern
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Dump database to SQL file
     Dump {
-        /// Source database URL (mysql://, postgres://, or mssql://)
+        /// Source database URL (mysql://, postgres://, mssql://, oracle://, sqlite:// or duckdb://;
+        /// CockroachDB and Redshift use postgres://)
         #[arg(short, long)]
         source: Option<String>,
 
@@ -23,22 +92,60 @@ pub enum Commands {
         #[arg(long)]
         source_env: Option<String>,
 
-        /// Output file path (.sql or .sql.gz)
+        /// Output file path (.sql, or compressed as .sql.gz, .sql.zst, .sql.xz or .sql.lz4), -
+        /// for stdout, or an s3://, gs:// or az:// URL to upload to
         #[arg(short, long)]
         output: String,
 
-        /// Database provider (mysql|postgres|sqlserver)
-        #[arg(long, default_value = "mysql", value_parser = ["mysql", "postgres", "sqlserver"])]
+        /// Database provider (mysql|mariadb|postgres|sqlserver|sqlite|cockroachdb|clickhouse|duckdb|oracle|redshift|snowflake)
+        #[arg(long, default_value = "mysql", value_parser = parse_provider)]
         provider: String,
 
-        /// Tables to include (comma-separated)
+        /// Authenticate with a short-lived RDS IAM token instead of the URL password (MySQL/PostgreSQL)
+        #[arg(long)]
+        aws_iam_auth: bool,
+
+        /// Connect through the Cloud SQL connector to this instance (project:region:instance)
+        #[arg(long)]
+        cloudsql_instance: Option<String>,
+
+        /// Tables to include (comma-separated; * and ? wildcards, e.g. 'audit_*')
         #[arg(long, value_delimiter = ',')]
         tables: Vec<String>,
 
-        /// Tables to exclude (comma-separated)
+        /// Tables to exclude (comma-separated; * and ? wildcards, e.g. '*_tmp')
         #[arg(long, value_delimiter = ',')]
         exclude: Vec<String>,
 
+        /// Also include tables whose whole name matches this regular expression;
+        /// may be repeated
+        #[arg(long, value_name = "REGEX")]
+        tables_regex: Vec<String>,
+
+        /// Also exclude tables whose whole name matches this regular expression;
+        /// may be repeated
+        #[arg(long, value_name = "REGEX")]
+        exclude_regex: Vec<String>,
+
+        /// Schemas to include (comma-separated; PostgreSQL), all but the system
+        /// schemas by default. --tables also takes schema.table and * wildcards
+        #[arg(long, value_delimiter = ',')]
+        schemas: Vec<String>,
+
+        /// Dump these databases of the server rather than the one in the URL
+        /// (comma-separated; MySQL), each created and selected in the output
+        #[arg(long, value_delimiter = ',', conflicts_with = "all_databases")]
+        databases: Vec<String>,
+
+        /// Dump every database on the server but the system ones (MySQL)
+        #[arg(long)]
+        all_databases: bool,
+
+        /// Open the dump with CREATE DATABASE IF NOT EXISTS and USE for the source
+        /// database (MySQL), so it restores into an empty server
+        #[arg(long)]
+        create_database: bool,
+
         /// Dump schema only (no data)
         #[arg(long)]
         schema_only: bool,
@@ -47,22 +154,208 @@ pub enum Commands {
         #[arg(long)]
         data_only: bool,
 
+        /// Tables to dump without their rows, schema only (comma-separated; * and ?
+        /// wildcards)
+        #[arg(long, value_delimiter = ',', value_name = "TABLES")]
+        exclude_data: Vec<String>,
+
+        /// Write INSERT IGNORE (MySQL), INSERT OR IGNORE (SQLite) or ON CONFLICT DO
+        /// NOTHING (PostgreSQL), so rows already on the server are kept
+        #[arg(long, conflicts_with = "schema_only")]
+        insert_ignore: bool,
+
+        /// Write REPLACE INTO (MySQL), INSERT OR REPLACE (SQLite) or ON CONFLICT DO
+        /// UPDATE (PostgreSQL), so rows already on the server are overwritten
+        #[arg(long, conflicts_with_all = ["schema_only", "insert_ignore"])]
+        replace: bool,
+
+        /// Also dump comments on tables and columns (PostgreSQL; MySQL keeps them in
+        /// the table definition)
+        #[arg(long)]
+        comments: bool,
+
+        /// Also dump each table's owner and the privileges granted on it (PostgreSQL);
+        /// the roles must exist where the dump is restored
+        #[arg(long)]
+        privileges: bool,
+
+        /// Create every table bare first, then load all data, then add indexes and
+        /// foreign keys in a final post-data section (MySQL and PostgreSQL)
+        #[arg(long, conflicts_with = "data_only")]
+        post_data: bool,
+
+        /// Leave views out of the dump (they are dumped after all tables otherwise)
+        #[arg(long)]
+        skip_views: bool,
+
+        /// Leave triggers out of the dump (they follow each table's data otherwise)
+        #[arg(long)]
+        skip_triggers: bool,
+
+        /// Also dump stored procedures and functions (MySQL and PostgreSQL), ahead of
+        /// the tables
+        #[arg(long)]
+        routines: bool,
+
+        /// Leave the DEFINER out of views, triggers, routines and events (MySQL), so
+        /// they belong to whoever restores them
+        #[arg(long)]
+        skip_definer: bool,
+
+        /// Also dump scheduled events (MySQL), after everything else
+        #[arg(long)]
+        events: bool,
+
+        /// Also dump the server's user accounts (MySQL) or roles (PostgreSQL) and
+        /// their grants; the dump then holds their password hashes
+        #[arg(long)]
+        users: bool,
+
         /// Rows per INSERT batch
         #[arg(long, default_value = "1000")]
         batch_rows: usize,
 
+        /// Write many rows per INSERT; false writes one row per statement, for tools
+        /// that cannot read multi-row INSERTs
+        #[arg(long, default_value_t = true, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
+        extended_insert: bool,
+
+        /// Name the columns in every INSERT; false leaves the list out, so the values
+        /// must follow the destination table's column order
+        #[arg(long, default_value_t = true, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
+        complete_insert: bool,
+
+        /// Also end a batch once its SQL reaches about this many bytes; MySQL servers
+        /// additionally cap it below max_allowed_packet
+        #[arg(long, value_name = "BYTES")]
+        batch_bytes: Option<usize>,
+
+        /// Read each table in primary key order, N rows per query (keyset pagination)
+        #[arg(long, value_name = "N")]
+        chunk_rows: Option<usize>,
+
+        /// Dump at most N rows of each table; with --chunk-rows, the first N in primary
+        /// key order
+        #[arg(long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<u64>::new().range(1..))]
+        limit_rows: Option<u64>,
+
+        /// Dump a sample of each table's rows, e.g. 5% or 0.05 (every Nth row, so the
+        /// same rows each run)
+        #[arg(long, value_name = "PERCENT", value_parser = parse_sample)]
+        sample: Option<f64>,
+
         /// Use consistent snapshot (REPEATABLE READ transaction)
         #[arg(long)]
         consistent_snapshot: bool,
 
-        /// Compress output with gzip
-        #[arg(long)]
+        /// Record the binary log file, position and GTID set (MySQL) or WAL LSN
+        /// (PostgreSQL) the snapshot matches in the dump header and manifest; MySQL
+        /// briefly takes a global read lock and needs the RELOAD privilege
+        #[arg(long, visible_alias = "source-data", requires = "consistent_snapshot")]
+        master_data: bool,
+
+        /// Compress output with none, gzip, zstd, xz or lz4 (default: from the output
+        /// file extension, e.g. .sql.zst)
+        #[arg(long, value_name = "CODEC")]
+        compress: Option<Codec>,
+
+        /// Compress output with gzip (same as --compress gzip)
+        #[arg(long, conflicts_with = "compress")]
         gzip: bool,
+
+        /// Compression level for the selected codec (gzip 1-9, zstd 1-22, xz 0-9)
+        #[arg(long, value_name = "N", conflicts_with_all = ["fast", "best"])]
+        compress_level: Option<u32>,
+
+        /// Use the codec's fastest compression level
+        #[arg(long, conflicts_with = "best")]
+        fast: bool,
+
+        /// Use the codec's highest compression level (smallest output)
+        #[arg(long)]
+        best: bool,
+
+        /// Encrypt the output to an age recipient as it is written, e.g.
+        /// age:age1...; repeat to let any of several keys decrypt it
+        #[arg(long, value_name = "RECIPIENT")]
+        encrypt: Vec<Recipient>,
+
+        /// Write a single SQL file, a directory (dir) with schema/<table>.sql,
+        /// data/<table>.sql[.gz] and manifest.json, or a single file with the rows
+        /// in binary frames (binary) that restore loads without parsing SQL
+        #[arg(long, default_value = "file")]
+        format: DumpFormat,
+
+        /// Continue a --format dir dump that was interrupted, skipping the tables it
+        /// finished and picking up --chunk-rows reads after the last key it recorded
+        #[arg(long)]
+        resume: bool,
+
+        /// Leave the generation date out of the dump and its manifest, so unchanged data
+        /// dumps to identical files
+        #[arg(long, visible_alias = "no-metadata")]
+        reproducible: bool,
+
+        /// Write the dump for another database (ansi|postgres|mysql): table and view
+        /// definitions are rewritten with its quoting and types, without engine-specific
+        /// clauses, and statements in the source's own SQL are left out
+        #[arg(long)]
+        compatible: Option<Compatible>,
+
+        /// Output that may queue for the writer thread before the dump waits, in MB
+        /// (per file with --jobs)
+        #[arg(long, value_name = "MB", default_value = "8", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        write_buffer_mb: usize,
+
+        /// Number of tables to dump concurrently; with --consistent-snapshot all jobs
+        /// share one snapshot (PostgreSQL only)
+        #[arg(long, default_value = "1")]
+        jobs: usize,
+
+        /// Most connections open at once to the database (jobs are reduced to fit)
+        #[arg(long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        max_connections: Option<usize>,
+
+        /// Read at most this many rows per second from the source, across all jobs
+        #[arg(long, value_name = "N")]
+        max_rows_per_sec: Option<f64>,
+
+        /// Read at most this many megabytes (of row data, as SQL) per second from the source
+        #[arg(long, value_name = "MB")]
+        max_mb_per_sec: Option<f64>,
+
+        /// Ping the session holding a consistent snapshot this often while it is idle,
+        /// and have the server keep the connection alive (e.g. 60s)
+        #[arg(long, value_parser = parse_duration)]
+        keepalive: Option<Duration>,
+
+        /// Warn when a consistent snapshot has been open this long, and again each
+        /// time as much more passes (0 to never warn)
+        #[arg(long, default_value = "1h", value_parser = parse_duration)]
+        snapshot_warn_age: Duration,
+
+        /// Retry a call that failed on a transient error (dropped connection, timeout,
+        /// deadlock) this many times, on a new connection
+        #[arg(long, default_value = "0")]
+        retries: u32,
+
+        /// Wait before the first retry (e.g. 500ms, 2s, 1m); doubles with each attempt
+        #[arg(long, default_value = "1s", value_parser = parse_duration)]
+        retry_backoff: Duration,
+
+        /// Give up on a connection attempt after this long (e.g. 10s)
+        #[arg(long, value_parser = parse_duration)]
+        connect_timeout: Option<Duration>,
+
+        /// Have the server abort any statement running longer than this (e.g. 5m)
+        #[arg(long, value_parser = parse_duration)]
+        statement_timeout: Option<Duration>,
     },
 
     /// Restore database from SQL file
     Restore {
-        /// Destination database URL (mysql://, postgres://, or mssql://)
+        /// Destination database URL (mysql://, postgres://, mssql://, clickhouse://, snowflake://,
+        /// sqlite:// or duckdb://; CockroachDB and Redshift use postgres://)
         #[arg(short, long)]
         destination: Option<String>,
 
@@ -70,22 +363,158 @@ pub enum Commands {
         #[arg(long)]
         destination_env: Option<String>,
 
-        /// Input file path (.sql or .sql.gz)
-        #[arg(short, long)]
-        input: String,
+        /// Input file path (.sql, or compressed as .sql.gz, .sql.zst, .sql.xz or .sql.lz4), -
+        /// for stdin, an s3://, gs://, az:// or http(s):// URL to stream from, or a directory
+        /// written by dump --format dir. Repeat it, or match file names with * and ?
+        /// (quoted), to restore several files in order; a pattern's files go in lexical
+        /// order
+        #[arg(short, long, required = true)]
+        input: Vec<String>,
 
-        /// Database provider (mysql|postgres|sqlserver)
-        #[arg(long, default_value = "mysql", value_parser = ["mysql", "postgres", "sqlserver"])]
+        /// Database provider (mysql|mariadb|postgres|sqlserver|sqlite|cockroachdb|clickhouse|duckdb|oracle|redshift|snowflake)
+        #[arg(long, default_value = "mysql", value_parser = parse_provider)]
         provider: String,
 
+        /// Authenticate with a short-lived RDS IAM token instead of the URL password (MySQL/PostgreSQL)
+        #[arg(long)]
+        aws_iam_auth: bool,
+
+        /// Connect through the Cloud SQL connector to this instance (project:region:instance)
+        #[arg(long)]
+        cloudsql_instance: Option<String>,
+
         /// Disable foreign key checks during restore
         #[arg(long, default_value = "true")]
         disable_fk_checks: bool,
+
+        /// Tables to restore from the dump (comma-separated; * and ? wildcards;
+        /// default: all). A single file runs only the statements on them
+        #[arg(long, value_delimiter = ',')]
+        tables: Vec<String>,
+
+        /// Restore the schema only, skipping rows and sequence values
+        #[arg(long)]
+        schema_only: bool,
+
+        /// Restore the data only (rows and sequence values), into tables that exist
+        #[arg(long, conflicts_with = "schema_only")]
+        data_only: bool,
+
+        /// Speed up loading into PostgreSQL: skip triggers and foreign key checks
+        /// (session_replication_role = replica) and turn off synchronous_commit
+        #[arg(long)]
+        fast_load: bool,
+
+        /// Strip the DEFINER from views, triggers, routines and events as they are
+        /// restored (MySQL)
+        #[arg(long)]
+        skip_definer: bool,
+
+        /// How the rows of a binary dump are inserted: literal (multi-row VALUES),
+        /// prepared (bind parameters, MySQL/PostgreSQL) or copy (COPY FROM STDIN,
+        /// PostgreSQL)
+        #[arg(long, default_value = "literal")]
+        insert_mode: InsertMode,
+
+        /// age identity file (as written by age-keygen) to decrypt an encrypted dump;
+        /// may be repeated
+        #[arg(long, value_name = "FILE")]
+        identity: Vec<String>,
+
+        /// Number of connections restoring tables concurrently, for multi-core servers;
+        /// a table's schema runs before its rows, statements for one table stay in
+        /// order and tables wait for the tables they reference
+        #[arg(long, default_value = "1")]
+        jobs: usize,
+
+        /// Commit after every N statements, rather than after each (with --jobs, each
+        /// connection also commits as it finishes a table)
+        #[arg(long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<u64>::new().range(1..))]
+        commit_every: Option<u64>,
+
+        /// Run the whole restore in one transaction, rolled back if any statement
+        /// fails. MySQL commits on each CREATE, DROP or ALTER regardless
+        #[arg(long, conflicts_with = "commit_every")]
+        single_transaction: bool,
+
+        /// Carry on past statements that fail, writing each to --errors-file with its
+        /// line and error, and count the failures by table at the end
+        #[arg(long, conflicts_with_all = ["commit_every", "single_transaction"])]
+        skip_errors: bool,
+
+        /// Where --skip-errors writes the failed statements
+        #[arg(long, default_value = "restore-errors.sql", requires = "skip_errors")]
+        errors_file: String,
+
+        /// Rewrite INSERT statements so rows already on the server are kept (ignore),
+        /// overwritten (replace: REPLACE INTO in MySQL and SQLite) or updated in place
+        /// (update: ON DUPLICATE KEY / ON CONFLICT DO UPDATE), making a partly applied
+        /// restore safe to run again
+        #[arg(long, value_name = "ignore|replace|update")]
+        on_conflict: Option<OnConflict>,
+
+        /// Restore a schema (a database in MySQL) under another name, rewriting it in
+        /// DDL and INSERT statements; comma-separated or repeated
+        #[arg(long, value_name = "OLD:NEW", value_delimiter = ',', value_parser = parse_mapping)]
+        remap_schema: Vec<(String, String)>,
+
+        /// Restore a table under another name; OLD may be schema-qualified to rename
+        /// it in that schema only. Comma-separated or repeated
+        #[arg(long, value_name = "OLD:NEW", value_delimiter = ',', value_parser = parse_mapping)]
+        remap_table: Vec<(String, String)>,
+
+        /// Never drop a table: leave out DROP TABLE statements and turn CREATE TABLE
+        /// into CREATE TABLE IF NOT EXISTS, so tables already there are kept
+        #[arg(long)]
+        no_drop: bool,
+
+        /// Where a sequential restore notes how far it has got, after each commit;
+        /// removed once the restore completes
+        #[arg(long, default_value = "restore-state.json")]
+        state_file: String,
+
+        /// Continue a sequential restore that stopped part way, skipping what
+        /// --state-file notes as committed. Give the same input and options
+        #[arg(long, conflicts_with = "single_transaction")]
+        resume: bool,
+
+        /// Read the whole input without connecting: count the statements on each table
+        /// and flag syntax the provider does not take, failing if any is found. No
+        /// destination is needed
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Retry a call that failed on a transient error (dropped connection, timeout,
+        /// deadlock) this many times, on a new connection
+        #[arg(long, default_value = "0")]
+        retries: u32,
+
+        /// Wait before the first retry (e.g. 500ms, 2s, 1m); doubles with each attempt
+        #[arg(long, default_value = "1s", value_parser = parse_duration)]
+        retry_backoff: Duration,
+
+        /// Give up on a connection attempt after this long (e.g. 10s)
+        #[arg(long, value_parser = parse_duration)]
+        connect_timeout: Option<Duration>,
+
+        /// Have the server abort any statement running longer than this (e.g. 5m)
+        #[arg(long, value_parser = parse_duration)]
+        statement_timeout: Option<Duration>,
+
+        /// SQL to run on every new destination session, such as
+        /// "SET sql_mode=''; SET time_zone='+00:00'"; may be repeated
+        #[arg(long, value_name = "SQL")]
+        init_sql: Vec<String>,
+
+        /// File of SQL to run on every new destination session, ahead of --init-sql
+        #[arg(long, value_name = "FILE")]
+        init_sql_file: Option<String>,
     },
 
     /// Migrate database directly from source to destination
     Migrate {
-        /// Source database URL (mysql://, postgres://, or mssql://)
+        /// Source database URL (mysql://, postgres://, mssql://, oracle://, sqlite:// or duckdb://;
+        /// CockroachDB and Redshift use postgres://)
         #[arg(short, long)]
         source: Option<String>,
 
@@ -93,7 +522,8 @@ pub enum Commands {
         #[arg(long)]
         source_env: Option<String>,
 
-        /// Destination database URL (mysql://, postgres://, or mssql://)
+        /// Destination database URL (mysql://, postgres://, mssql://, clickhouse://, snowflake://,
+        /// sqlite:// or duckdb://; CockroachDB and Redshift use postgres://)
         #[arg(short, long)]
         destination: Option<String>,
 
@@ -101,18 +531,74 @@ pub enum Commands {
         #[arg(long)]
         destination_env: Option<String>,
 
-        /// Database provider (mysql|postgres|sqlserver)
-        #[arg(long, default_value = "mysql", value_parser = ["mysql", "postgres", "sqlserver"])]
+        /// Database provider for both sides (mysql|mariadb|postgres|sqlserver|sqlite|cockroachdb|clickhouse|duckdb|oracle|redshift|snowflake)
+        #[arg(long, default_value = "mysql", value_parser = parse_provider)]
         provider: String,
 
-        /// Tables to include (comma-separated)
+        /// Source database provider (defaults to --provider)
+        #[arg(long, value_parser = parse_provider)]
+        source_provider: Option<String>,
+
+        /// Destination database provider (defaults to --provider)
+        #[arg(long, value_parser = parse_provider)]
+        dest_provider: Option<String>,
+
+        /// Authenticate both sides with short-lived RDS IAM tokens instead of URL passwords (MySQL/PostgreSQL)
+        #[arg(long)]
+        aws_iam_auth: bool,
+        /// Connect both sides through the Cloud SQL connector to this instance (project:region:instance)
+        /// Connect through the Cloud SQL connector to this instance (project:region:instance)
+        #[arg(long)]
+        cloudsql_instance: Option<String>,
+
+        /// Authenticate the source with an RDS IAM token
+        #[arg(long)]
+        source_aws_iam_auth: bool,
+
+        /// Authenticate the destination with an RDS IAM token
+        #[arg(long)]
+        dest_aws_iam_auth: bool,
+
+        /// Cloud SQL instance for the source (defaults to --cloudsql-instance)
+        #[arg(long)]
+        source_cloudsql_instance: Option<String>,
+
+        /// Cloud SQL instance for the destination (defaults to --cloudsql-instance)
+        #[arg(long)]
+        dest_cloudsql_instance: Option<String>,
+
+        /// Tables to include (comma-separated; * and ? wildcards, e.g. 'audit_*')
         #[arg(long, value_delimiter = ',')]
         tables: Vec<String>,
 
-        /// Tables to exclude (comma-separated)
+        /// Tables to exclude (comma-separated; * and ? wildcards, e.g. '*_tmp')
         #[arg(long, value_delimiter = ',')]
         exclude: Vec<String>,
 
+        /// Also include tables whose whole name matches this regular expression;
+        /// may be repeated
+        #[arg(long, value_name = "REGEX")]
+        tables_regex: Vec<String>,
+
+        /// Also exclude tables whose whole name matches this regular expression;
+        /// may be repeated
+        #[arg(long, value_name = "REGEX")]
+        exclude_regex: Vec<String>,
+
+        /// Schemas to include (comma-separated; PostgreSQL), all but the system
+        /// schemas by default. --tables also takes schema.table and * wildcards
+        #[arg(long, value_delimiter = ',')]
+        schemas: Vec<String>,
+
+        /// Migrate these databases of the server rather than the one in the URL
+        /// (comma-separated; MySQL), each to a database of the same name
+        #[arg(long, value_delimiter = ',', conflicts_with = "all_databases")]
+        databases: Vec<String>,
+
+        /// Migrate every database on the server but the system ones (MySQL)
+        #[arg(long)]
+        all_databases: bool,
+
         /// Migrate schema only (no data)
         #[arg(long)]
         schema_only: bool,
@@ -121,9 +607,33 @@ pub enum Commands {
         #[arg(long)]
         data_only: bool,
 
-        /// Rows per INSERT batch
+        /// Tables to migrate without their rows, schema only (comma-separated; * and ?
+        /// wildcards)
+        #[arg(long, value_delimiter = ',', value_name = "TABLES")]
+        exclude_data: Vec<String>,
+
+        /// Rows per INSERT batch, or auto to tune it from insert throughput
         #[arg(long, default_value = "1000")]
-        batch_rows: usize,
+        batch_rows: BatchRows,
+
+        /// Also end a batch once its SQL reaches about this many bytes; MySQL servers
+        /// additionally cap it below max_allowed_packet
+        #[arg(long, value_name = "BYTES")]
+        batch_bytes: Option<usize>,
+
+        /// Read each table in primary key order, N rows per query (keyset pagination)
+        #[arg(long, value_name = "N")]
+        chunk_rows: Option<usize>,
+
+        /// Copy at most N rows of each table; with --chunk-rows, the first N in primary
+        /// key order
+        #[arg(long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<u64>::new().range(1..))]
+        limit_rows: Option<u64>,
+
+        /// Copy a sample of each table's rows, e.g. 5% or 0.05 (every Nth row, so the
+        /// same rows each run)
+        #[arg(long, value_name = "PERCENT", value_parser = parse_sample)]
+        sample: Option<f64>,
 
         /// Use consistent snapshot (REPEATABLE READ transaction)
         #[arg(long)]
@@ -136,11 +646,183 @@ pub enum Commands {
         /// Skip rows with errors instead of failing
         #[arg(long, default_value = "false")]
         skip_errors: bool,
+
+        /// Carry on with the other tables when one fails, noting it in --failed-tables;
+        /// the run still exits with an error, listing the tables that failed
+        #[arg(long, conflicts_with = "position_file")]
+        continue_on_error: bool,
+
+        /// Migrate only the tables an earlier --continue-on-error run noted as failed,
+        /// carrying on past failures again
+        #[arg(long, conflicts_with_all = ["tables", "tables_regex", "position_file"])]
+        retry_failed: bool,
+
+        /// Where --continue-on-error notes the tables that failed, and --retry-failed
+        /// reads them; removed once a run has no failures
+        #[arg(long, value_name = "FILE", default_value = "migrate-failed.json")]
+        failed_tables: String,
+
+        /// Once the tables are copied, compare them with the source: counts (exact row
+        /// counts) or checksums (rows matched by primary key, in digested key ranges);
+        /// the run fails with a report of the tables that differ
+        #[arg(long, value_name = "counts|checksums", conflicts_with_all = ["schema_only", "limit_rows", "sample"])]
+        verify: Option<Verify>,
+
+        /// What to do when --data-only or --no-drop finds rows in a destination table:
+        /// fail, skip the table, truncate it first, or append to what is there
+        #[arg(long, value_name = "fail|skip|truncate|append", default_value = "fail")]
+        if_exists: IfExists,
+
+        /// Never drop a destination table: tables already there keep their schema and
+        /// only have rows copied into them (see --if-exists); missing ones are created
+        #[arg(long, conflicts_with = "data_only")]
+        no_drop: bool,
+
+        /// Create the destination database named in the URL when it is missing,
+        /// connecting to the server without it (MySQL, PostgreSQL)
+        #[arg(long, conflicts_with_all = ["databases", "all_databases"])]
+        create_destination: bool,
+
+        /// With --data-only, only warn when destination columns are missing, typed
+        /// differently or NOT NULL where the source's are not, instead of failing
+        /// before any rows are copied
+        #[arg(long, requires = "data_only")]
+        allow_drift: bool,
+
+        /// With --data-only, change destination tables to fit the source before
+        /// copying: add-columns adds the columns only the source has
+        #[arg(long, value_name = "add-columns", requires = "data_only")]
+        auto_alter: Option<AutoAlter>,
+
+        /// Move text to this character set: rewrite the CHARSET and COLLATE clauses of
+        /// MySQL DDL, and repair UTF-8 text stored in columns of other character sets
+        /// (MySQL sources)
+        #[arg(long, value_name = "utf8mb4")]
+        convert_charset: Option<Charset>,
+
+        /// Case of table and column names created and written on another engine:
+        /// preserve the source's spelling, or fold them to lower or upper case
+        #[arg(long, value_name = "preserve|lower|upper", default_value = "preserve")]
+        identifier_case: IdentifierCase,
+
+        /// Number of tables to migrate concurrently, each job with its own connections
+        #[arg(long, default_value = "1")]
+        jobs: usize,
+
+        /// Split tables with an integer primary key into N key ranges copied concurrently
+        #[arg(long, value_name = "N", default_value = "1")]
+        split_ranges: usize,
+
+        /// Most connections open at once to each of the source and destination (jobs
+        /// and ranges are reduced to fit)
+        #[arg(long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        max_connections: Option<usize>,
+
+        /// Batches read ahead while the destination inserts; the reader waits once this
+        /// many are queued
+        #[arg(long, value_name = "N", default_value = "4")]
+        max_inflight_batches: usize,
+
+        /// Read at most this many rows per second from the source, across all jobs
+        #[arg(long, value_name = "N")]
+        max_rows_per_sec: Option<f64>,
+
+        /// Read at most this many megabytes (of row data, as SQL) per second from the source
+        #[arg(long, value_name = "MB")]
+        max_mb_per_sec: Option<f64>,
+
+        /// Speed up loading into PostgreSQL: skip triggers and foreign key checks
+        /// (session_replication_role = replica) and turn off synchronous_commit
+        #[arg(long)]
+        fast_load: bool,
+
+        /// With --fast-load, drop each table's secondary indexes before copying its
+        /// rows and rebuild them afterwards
+        #[arg(long, requires = "fast_load")]
+        drop_indexes: bool,
+
+        /// Create each table without its secondary indexes and add them after its rows
+        /// are copied (MySQL, PostgreSQL and SQLite DDL)
+        #[arg(long)]
+        defer_indexes: bool,
+
+        /// How rows are inserted: literal (multi-row VALUES), prepared (bind parameters,
+        /// MySQL/PostgreSQL) or copy (COPY FROM STDIN, PostgreSQL)
+        #[arg(long, default_value = "literal")]
+        insert_mode: InsertMode,
+
+        /// Ping the session holding a consistent snapshot this often while it is idle,
+        /// and have the server keep the connection alive (e.g. 60s)
+        #[arg(long, value_parser = parse_duration)]
+        keepalive: Option<Duration>,
+
+        /// Warn when a consistent snapshot has been open this long, and again each
+        /// time as much more passes (0 to never warn)
+        #[arg(long, default_value = "1h", value_parser = parse_duration)]
+        snapshot_warn_age: Duration,
+
+        /// Retry a call that failed on a transient error (dropped connection, timeout,
+        /// deadlock) this many times, on a new connection
+        #[arg(long, default_value = "0")]
+        retries: u32,
+
+        /// Wait before the first retry (e.g. 500ms, 2s, 1m); doubles with each attempt
+        #[arg(long, default_value = "1s", value_parser = parse_duration)]
+        retry_backoff: Duration,
+
+        /// Give up on a connection attempt after this long (e.g. 10s)
+        #[arg(long, value_parser = parse_duration)]
+        connect_timeout: Option<Duration>,
+
+        /// Have the server abort any statement running longer than this (e.g. 5m)
+        #[arg(long, value_parser = parse_duration)]
+        statement_timeout: Option<Duration>,
+
+        /// SQL to run on every new destination session, such as
+        /// "SET sql_mode=''; SET time_zone='+00:00'"; may be repeated
+        #[arg(long, value_name = "SQL")]
+        init_sql: Vec<String>,
+
+        /// File of SQL to run on every new destination session, ahead of --init-sql
+        #[arg(long, value_name = "FILE")]
+        init_sql_file: Option<String>,
+
+        /// TOML file of destination types to use instead of the translated
+        /// ones, by source type ([types]) or by table.column ([columns])
+        #[arg(long, value_name = "FILE")]
+        type_map: Option<String>,
+
+        /// Write a column to the destination under another name, as TABLE.COLUMN:NEW;
+        /// TABLE may be schema-qualified. Comma-separated or repeated
+        #[arg(long, value_name = "TABLE.COLUMN:NEW", value_delimiter = ',', value_parser = parse_mapping)]
+        rename_column: Vec<(String, String)>,
+
+        /// Copy only rows whose value of this column (e.g. updated_at) is past the
+        /// largest one an earlier run copied, updating rows already there by
+        /// primary key; tables not copied before are migrated in full
+        #[arg(long, value_name = "COLUMN", conflicts_with = "schema_only")]
+        incremental: Option<String>,
+
+        /// Where --incremental keeps each table's largest copied value
+        #[arg(long, default_value = "migrate-watermarks.json")]
+        incremental_state: String,
+
+        /// Take the snapshot at the source's replication position and write that
+        /// position to this file once done, for `replicate` to carry on from;
+        /// MySQL briefly takes a global read lock and needs the RELOAD privilege,
+        /// PostgreSQL gets a logical replication slot first
+        #[arg(long, value_name = "FILE", requires = "consistent_snapshot")]
+        position_file: Option<String>,
+
+        /// Name of the logical replication slot and publication --position-file creates (PostgreSQL)
+        #[arg(long, default_value = "migrasquiel")]
+        slot: String,
     },
 
     /// Import CSV file to database table
     Import {
-        /// Destination database URL (mysql://, postgres://)
+        /// Destination database URL (mysql://, postgres://, mssql://, clickhouse://, snowflake://,
+        /// sqlite:// or duckdb://; CockroachDB and Redshift use postgres://)
         #[arg(short, long)]
         destination: Option<String>,
 
@@ -156,25 +838,271 @@ pub enum Commands {
         #[arg(short, long)]
         table: String,
 
-        /// Database provider (mysql|postgres)
-        #[arg(long, default_value = "mysql", value_parser = ["mysql", "postgres"])]
+        /// Database provider (mysql|mariadb|postgres|sqlserver|sqlite|cockroachdb|clickhouse|duckdb|oracle|redshift|snowflake)
+        #[arg(long, default_value = "mysql", value_parser = parse_provider)]
         provider: String,
 
-        /// Rows per INSERT batch
+        /// Authenticate with a short-lived RDS IAM token instead of the URL password (MySQL/PostgreSQL)
+        #[arg(long)]
+        aws_iam_auth: bool,
+
+        /// Connect through the Cloud SQL connector to this instance (project:region:instance)
+        #[arg(long)]
+        cloudsql_instance: Option<String>,
+
+        /// Rows per INSERT batch, or auto to tune it from insert throughput
         #[arg(long, default_value = "1000")]
-        batch_rows: usize,
+        batch_rows: BatchRows,
 
-        /// Disable foreign key checks during import
-        #[arg(long, default_value = "true")]
+        /// Also end a batch once its SQL reaches about this many bytes; MySQL servers
+        /// additionally cap it below max_allowed_packet
+        #[arg(long, value_name = "BYTES")]
+        batch_bytes: Option<usize>,
+
+        /// Disable foreign key checks during import; false keeps them on
+        #[arg(long, default_value_t = true, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
         disable_fk_checks: bool,
 
         /// Column mapping: csv_col1:db_col1,csv_col2:db_col2
-        #[arg(long)]
+        #[arg(long, visible_alias = "map")]
         columns: Option<String>,
 
-        /// Skip rows with errors instead of failing
-        #[arg(long, default_value = "true")]
+        /// Skip rows with errors instead of failing; false stops at the first bad row
+        #[arg(long, default_value_t = true, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
         skip_errors: bool,
+
+        /// How rows are inserted: literal (multi-row VALUES), prepared (bind parameters,
+        /// MySQL/PostgreSQL) or copy (COPY FROM STDIN, PostgreSQL)
+        #[arg(long, default_value = "literal")]
+        insert_mode: InsertMode,
+
+        /// Retry a call that failed on a transient error (dropped connection, timeout,
+        /// deadlock) this many times, on a new connection
+        #[arg(long, default_value = "0")]
+        retries: u32,
+
+        /// Wait before the first retry (e.g. 500ms, 2s, 1m); doubles with each attempt
+        #[arg(long, default_value = "1s", value_parser = parse_duration)]
+        retry_backoff: Duration,
+
+        /// Give up on a connection attempt after this long (e.g. 10s)
+        #[arg(long, value_parser = parse_duration)]
+        connect_timeout: Option<Duration>,
+
+        /// Have the server abort any statement running longer than this (e.g. 5m)
+        #[arg(long, value_parser = parse_duration)]
+        statement_timeout: Option<Duration>,
+    },
+
+    /// Export tables to CSV files, with a header row
+    Export {
+        /// Source database URL (mysql://, postgres://, or sqlite://)
+        #[arg(short, long)]
+        source: Option<String>,
+
+        /// Environment variable containing source URL
+        #[arg(long)]
+        source_env: Option<String>,
+
+        /// Output CSV file (compressed as .csv.gz, .csv.zst, .csv.xz or .csv.lz4), - for
+        /// stdout, or an s3://, gs:// or az:// URL; a directory when several tables match
+        #[arg(short, long)]
+        output: String,
+
+        /// Database provider (mysql|mariadb|postgres|sqlserver|sqlite|cockroachdb|clickhouse|duckdb|oracle|redshift|snowflake)
+        #[arg(long, default_value = "mysql", value_parser = parse_provider)]
+        provider: String,
+
+        /// Authenticate with a short-lived RDS IAM token instead of the URL password (MySQL/PostgreSQL)
+        #[arg(long)]
+        aws_iam_auth: bool,
+
+        /// Connect through the Cloud SQL connector to this instance (project:region:instance)
+        #[arg(long)]
+        cloudsql_instance: Option<String>,
+
+        /// Tables to export (comma-separated; * and ? wildcards, e.g. 'audit_*')
+        #[arg(long, value_delimiter = ',')]
+        tables: Vec<String>,
+
+        /// Tables to leave out (comma-separated; * and ? wildcards, e.g. '*_tmp')
+        #[arg(long, value_delimiter = ',')]
+        exclude: Vec<String>,
+
+        /// Only export rows satisfying this SQL condition (MySQL, PostgreSQL, SQLite)
+        #[arg(long = "where", value_name = "CONDITION")]
+        where_clause: Option<String>,
+
+        /// Compress output with none, gzip, zstd, xz or lz4 (default: from the output
+        /// file extension, e.g. .csv.gz)
+        #[arg(long, value_name = "CODEC")]
+        compress: Option<Codec>,
+
+        /// Compress output with gzip (same as --compress gzip)
+        #[arg(long, conflicts_with = "compress")]
+        gzip: bool,
+
+        /// Retry a call that failed on a transient error (dropped connection, timeout,
+        /// deadlock) this many times, on a new connection
+        #[arg(long, default_value = "0")]
+        retries: u32,
+
+        /// Wait before the first retry (e.g. 500ms, 2s, 1m); doubles with each attempt
+        #[arg(long, default_value = "1s", value_parser = parse_duration)]
+        retry_backoff: Duration,
+
+        /// Give up on a connection attempt after this long (e.g. 10s)
+        #[arg(long, value_parser = parse_duration)]
+        connect_timeout: Option<Duration>,
+
+        /// Have the server abort any statement running longer than this (e.g. 5m)
+        #[arg(long, value_parser = parse_duration)]
+        statement_timeout: Option<Duration>,
+    },
+
+    /// Measure insert throughput on a database for several batch sizes and insert modes
+    Bench {
+        /// Database URL to benchmark (mysql://, postgres://, mssql://, clickhouse://, snowflake://,
+        /// sqlite:// or duckdb://; CockroachDB and Redshift use postgres://)
+        #[arg(short, long)]
+        destination: Option<String>,
+
+        /// Environment variable containing destination URL
+        #[arg(long)]
+        destination_env: Option<String>,
+
+        /// Database provider (mysql|mariadb|postgres|sqlserver|sqlite|cockroachdb|clickhouse|duckdb|oracle|redshift|snowflake)
+        #[arg(long, default_value = "mysql", value_parser = parse_provider)]
+        provider: String,
+
+        /// Authenticate with a short-lived RDS IAM token instead of the URL password (MySQL/PostgreSQL)
+        #[arg(long)]
+        aws_iam_auth: bool,
+
+        /// Connect through the Cloud SQL connector to this instance (project:region:instance)
+        #[arg(long)]
+        cloudsql_instance: Option<String>,
+
+        /// Scratch table created and dropped for every run
+        #[arg(long, default_value = "migrasquiel_bench")]
+        table: String,
+
+        /// Synthetic rows inserted per run
+        #[arg(long, default_value = "100000", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        rows: usize,
+
+        /// Batch sizes to try (comma-separated)
+        #[arg(long, value_delimiter = ',', default_value = "100,1000,5000")]
+        batch_sizes: Vec<usize>,
+
+        /// Insert modes to try (comma-separated); modes the provider lacks are reported as failed
+        #[arg(long, value_delimiter = ',', default_value = "literal,prepared,copy")]
+        insert_modes: Vec<InsertMode>,
+    },
+
+    /// Apply the changes committed on a MySQL or PostgreSQL source since `migrate --position-file` to the destination, until stopped with Ctrl-C
+    Replicate {
+        /// Source database URL (mysql:// or postgres://)
+        #[arg(short, long)]
+        source: Option<String>,
+
+        /// Environment variable containing source URL
+        #[arg(long)]
+        source_env: Option<String>,
+
+        /// Destination database URL (mysql://, postgres://, mssql://, clickhouse://, snowflake://,
+        /// sqlite:// or duckdb://; CockroachDB and Redshift use postgres://)
+        #[arg(short, long)]
+        destination: Option<String>,
+
+        /// Environment variable containing destination URL
+        #[arg(long)]
+        destination_env: Option<String>,
+
+        /// Database provider for both sides (mysql|mariadb|postgres|sqlserver|sqlite|cockroachdb|clickhouse|duckdb|oracle|redshift|snowflake)
+        #[arg(long, default_value = "mysql", value_parser = parse_provider)]
+        provider: String,
+
+        /// Source database provider (defaults to --provider)
+        #[arg(long, value_parser = parse_provider)]
+        source_provider: Option<String>,
+
+        /// Destination database provider (defaults to --provider)
+        #[arg(long, value_parser = parse_provider)]
+        dest_provider: Option<String>,
+
+        /// Tables to include (comma-separated; * and ? wildcards, e.g. 'audit_*')
+        #[arg(long, value_delimiter = ',')]
+        tables: Vec<String>,
+
+        /// Tables to exclude (comma-separated; * and ? wildcards, e.g. '*_tmp')
+        #[arg(long, value_delimiter = ',')]
+        exclude: Vec<String>,
+
+        /// Position written by `migrate --position-file`; moved on as each
+        /// transaction is applied, so a later run carries on where this one stopped
+        #[arg(long, value_name = "FILE")]
+        position_file: String,
+
+        /// Server id to read the binary log as; must differ from the source's
+        /// and from every other replica's (MySQL)
+        #[arg(long, default_value = "424242")]
+        server_id: u32,
+
+        /// Logical replication slot `migrate --position-file` created (PostgreSQL)
+        #[arg(long, default_value = "migrasquiel")]
+        slot: String,
+    },
+
+    /// Compare tables on both sides by primary key and write only the rows that differ to the destination
+    Sync {
+        /// Source database URL (mysql://, postgres://, or sqlite://)
+        #[arg(short, long)]
+        source: Option<String>,
+
+        /// Environment variable containing source URL
+        #[arg(long)]
+        source_env: Option<String>,
+
+        /// Destination database URL (mysql://, postgres://, or sqlite://)
+        #[arg(short, long)]
+        destination: Option<String>,
+
+        /// Environment variable containing destination URL
+        #[arg(long)]
+        destination_env: Option<String>,
+
+        /// Database provider for both sides (mysql|mariadb|postgres|sqlserver|sqlite|cockroachdb|clickhouse|duckdb|oracle|redshift|snowflake)
+        #[arg(long, default_value = "mysql", value_parser = parse_provider)]
+        provider: String,
+
+        /// Source database provider (defaults to --provider)
+        #[arg(long, value_parser = parse_provider)]
+        source_provider: Option<String>,
+
+        /// Destination database provider (defaults to --provider)
+        #[arg(long, value_parser = parse_provider)]
+        dest_provider: Option<String>,
+
+        /// Tables to include (comma-separated; * and ? wildcards, e.g. 'audit_*')
+        #[arg(long, value_delimiter = ',')]
+        tables: Vec<String>,
+
+        /// Tables to exclude (comma-separated; * and ? wildcards, e.g. '*_tmp')
+        #[arg(long, value_delimiter = ',')]
+        exclude: Vec<String>,
+
+        /// Rows per key range compared by checksum; only ranges that differ are read
+        #[arg(long, value_name = "N", default_value = "1000", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        chunk_rows: usize,
+
+        /// Also delete destination rows whose key the source does not have
+        #[arg(long)]
+        delete: bool,
+
+        /// Report the differences without changing the destination
+        #[arg(long)]
+        dry_run: bool,
     },
 }
 
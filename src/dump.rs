@@ -1,121 +1,1508 @@
-use crate::engine::dialect::SqlDialect;
+use crate::engine::dialect::{
+    conflict_clauses_for, create_schema_statements, in_schemas, split_table_name, OnConflict,
+    SqlDialect,
+};
+use crate::engine::filter::{table_matches, TableFilter};
+use crate::engine::keyset::{stream_rows_after, KeysetRows};
+use crate::engine::mssql::identity_insert_statement;
+use crate::engine::pool::SessionPool;
 use crate::engine::value::SqlValue;
-use crate::engine::{DbEngine, DbSession};
-use anyhow::{Context, Result};
-use flate2::write::GzEncoder;
-use flate2::Compression;
+use crate::engine::{select_databases, DbEngine, DbSession, ReplicationPosition, Routine};
+use crate::util::batch::{row_sql_len, BatchLimit};
+use crate::util::binary;
+use crate::util::compatible::Compatible;
+use crate::util::compress::{BackgroundEncoder, Codec, Level, Syncer, STDIO};
+use crate::util::encrypt::Encryption;
+use crate::util::keepalive::SnapshotWatch;
+use crate::util::manifest::{
+    DataSection, DumpFormat, Manifest, SectionWriter, TableEntry, DROP_VIEWS_FILE, EVENTS_FILE,
+    EXTENSIONS_FILE, GRANTS_FILE, MANIFEST_FILE, ROUTINES_FILE, SCHEMAS_FILE, SIDECAR_SUFFIX,
+    USERS_FILE, VIEWS_FILE,
+};
+use crate::util::state::{Checkpoint, StateFile, STATE_FILE};
+use crate::util::storage;
+use crate::util::subset::RowSubset;
+use crate::util::throttle::Throttle;
+use crate::util::url::{database_of, with_database};
+use anyhow::{bail, Context, Result};
 use futures::StreamExt;
-use indicatif::{ProgressBar, ProgressStyle};
-use std::fs::File;
-use std::io::{BufWriter, Write};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often a chunked read of a directory dump's table notes how far it
+/// has got, for `--resume`
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
 
 pub struct DumpOptions {
-    pub tables: Vec<String>,
-    pub exclude: Vec<String>,
+    /// Which tables and views to dump
+    pub filter: TableFilter,
+    /// Dump these databases of the server rather than the one in the URL,
+    /// or all of them when empty (MySQL)
+    pub databases: Option<Vec<String>>,
+    /// Open the dump by creating the database and switching to it
+    pub create_database: bool,
+    /// Dump only tables, views and routines in these schemas; all of them
+    /// when empty
+    pub schemas: Vec<String>,
     pub schema_only: bool,
     pub data_only: bool,
+    /// Dump only the schema of tables matching these patterns
+    pub exclude_data: Vec<String>,
     pub batch_rows: usize,
+    /// Also end an INSERT once its SQL reaches about this many bytes
+    pub batch_bytes: Option<usize>,
+    /// Write many rows per INSERT rather than one
+    pub extended_insert: bool,
+    /// Name the columns in every INSERT
+    pub complete_insert: bool,
     pub consistent_snapshot: bool,
-    pub gzip: bool,
+    /// Record where the source's binary log or WAL stood at the snapshot
+    pub master_data: bool,
+    pub compress: Codec,
+    /// Encrypt the dump's files to these age recipients
+    pub encrypt: Option<Encryption>,
+    /// Compression level, or the codec's default
+    pub compress_level: Option<Level>,
+    /// Read tables in primary key order, this many rows per query
+    pub chunk_rows: Option<usize>,
+    /// Number of tables dumped concurrently, each over its own connection
+    pub jobs: usize,
+    /// Most sessions open on the source at once, the coordinating one included
+    pub max_connections: Option<usize>,
+    /// Caps the rate rows are read from the source, across all jobs
+    pub throttle: Throttle,
+    /// Keeps the snapshot's session alive and warns as it grows old
+    pub snapshot_watch: SnapshotWatch,
+    /// Bytes of output that may queue for the writer thread, per file
+    pub write_buffer: usize,
+    /// A single SQL file, a directory with files per table, or a single
+    /// file with rows in binary frames
+    pub format: DumpFormat,
+    /// Continue the directory dump an earlier run left unfinished
+    pub resume: bool,
+    /// Dump only a limited or sampled part of each table's rows
+    pub subset: RowSubset,
+    /// Leave out the timestamps, so the same data dumps to the same bytes
+    pub reproducible: bool,
+    /// Write the dump in this target's SQL rather than the source's
+    pub compatible: Option<Compatible>,
+    /// What the dump's INSERTs do with rows already on the server
+    pub on_conflict: OnConflict,
+    /// Leave views out of the dump
+    pub skip_views: bool,
+    /// Leave triggers out of the dump
+    pub skip_triggers: bool,
+    /// Also dump stored procedures and functions
+    pub routines: bool,
+    /// Leave the account out of views, triggers, routines and events, so
+    /// they belong to whoever restores them
+    pub skip_definer: bool,
+    /// Also dump scheduled events
+    pub events: bool,
+    /// Also dump the server's user accounts and their grants
+    pub users: bool,
+    /// Also dump the comments on tables and columns
+    pub comments: bool,
+    /// Also dump each table's owner and the privileges granted on it
+    pub privileges: bool,
+    /// Create every table bare first and add its indexes and foreign keys
+    /// after all the data
+    pub post_data: bool,
 }
 
 pub async fn dump(
     engine: &dyn DbEngine,
     source_url: &str,
     output_path: &str,
-    opts: DumpOptions,
+    mut opts: DumpOptions,
+) -> Result<()> {
+    eprintln!("Starting database dump...");
+
+    // Binary rows are only read back by restore, and always with plain inserts
+    if opts.format == DumpFormat::Binary {
+        if opts.compatible.is_some() {
+            bail!("--compatible writes SQL for other tools, which cannot read --format binary");
+        }
+        if opts.on_conflict != OnConflict::Error {
+            bail!("--insert-ignore and --replace do not apply to --format binary");
+        }
+    }
+
+    if storage::is_http(output_path) {
+        bail!("Dumps cannot be uploaded over HTTP; write to an s3://, gs:// or az:// URL instead");
+    }
+    if opts.format == DumpFormat::Dir && storage::is_remote(output_path) {
+        bail!("--format dir writes a local directory; dump a single file to object storage");
+    }
+    if opts.resume && opts.format != DumpFormat::Dir {
+        bail!("--resume continues a --format dir dump; a single file cannot be picked up part way");
+    }
+
+    // Reject a bad compression level before doing any work
+    let level = opts
+        .compress_level
+        .map(|level| opts.compress.level(level))
+        .transpose()
+        .map_err(anyhow::Error::msg)?;
+
+    match opts.databases.take() {
+        Some(names) => {
+            dump_databases(engine, source_url, output_path, level, &names, &mut opts).await?
+        }
+        None => dump_database(engine, source_url, output_path, level, None, &opts).await?,
+    }
+
+    eprintln!("\nDump completed successfully!");
+    if output_path != STDIO {
+        eprintln!("Output: {}", output_path);
+    }
+
+    Ok(())
+}
+
+/// Dump each database `names` selects on the source server (all of them
+/// when it names none): into one file, each after lines creating and
+/// switching to it, or into a subdirectory per database. Users belong to
+/// the server rather than a database, so they come with the first one.
+async fn dump_databases(
+    engine: &dyn DbEngine,
+    source_url: &str,
+    output_path: &str,
+    level: Option<u32>,
+    names: &[String],
+    opts: &mut DumpOptions,
 ) -> Result<()> {
-    println!("Starting database dump...");
+    let databases = {
+        let mut session = engine
+            .connect(source_url)
+            .await
+            .context("Failed to connect to source database")?;
+        select_databases(&mut *session, names).await?
+    };
+    eprintln!("Found {} database(s) to dump", databases.len());
+
+    let mut shared = SharedFile::default();
+    for (idx, database) in databases.iter().enumerate() {
+        eprintln!(
+            "\n[{}/{}] Dumping database '{}'...",
+            idx + 1,
+            databases.len(),
+            database
+        );
+        let url = with_database(source_url, database)?;
+        let dumped = match opts.format {
+            DumpFormat::File | DumpFormat::Binary => {
+                let shared = Some((database.as_str(), &mut shared));
+                dump_database(engine, &url, output_path, level, shared, opts).await
+            }
+            DumpFormat::Dir => {
+                let path = Path::new(output_path).join(database);
+                let path = path.to_string_lossy();
+                dump_database(engine, &url, &path, level, None, opts).await
+            }
+        };
+        dumped.with_context(|| format!("Failed to dump database '{}'", database))?;
+        opts.users = false;
+    }
+
+    if let Some(mut writer) = shared.writer.take() {
+        // Grants may name a table in any of the databases
+        if !shared.grants.is_empty() {
+            write_grants(&mut writer, &shared.grants)?;
+        }
+        writer.finish()?;
 
+        if output_path != STDIO {
+            let mut manifest = Manifest::new(
+                opts.format,
+                shared.dialect,
+                opts.compress,
+                &shared.tables,
+                false,
+                false,
+            );
+            complete_manifest(&mut manifest, shared.sections, opts);
+            manifest.write(Path::new(&format!("{}{}", output_path, SIDECAR_SUFFIX)))?;
+        }
+    }
+    Ok(())
+}
+
+/// Dump the database at `source_url` to `output_path`. Given `shared`, the
+/// database is one of several in the same file: its dump follows lines
+/// creating and switching to it, and goes into the stream the databases
+/// before it were written to, left open for the next one.
+async fn dump_database(
+    engine: &dyn DbEngine,
+    source_url: &str,
+    output_path: &str,
+    level: Option<u32>,
+    mut shared: Option<(&str, &mut SharedFile)>,
+    opts: &DumpOptions,
+) -> Result<()> {
     // Connect to source
-    let mut session = engine
-        .connect(source_url)
+    let pool = engine.connect_pool(source_url, opts.max_connections.unwrap_or(usize::MAX));
+    let mut session = pool
+        .get()
         .await
         .context("Failed to connect to source database")?;
 
-    let dialect = session.dialect();
+    // The dump is in the source's SQL, or with --compatible in the target's
+    let source = session.dialect();
+    let dialect = opts.compatible.map_or(source, Compatible::dialect);
+    let native = keeps_native_sql(source, opts);
+    if opts.on_conflict != OnConflict::Error
+        && dialect
+            .conflict_clauses(opts.on_conflict, &[], &[])
+            .is_none()
+    {
+        bail!(
+            "--insert-ignore and --replace are not supported for {} dumps",
+            dialect.name()
+        );
+    }
+    if let (Some(compatible), false, false) = (opts.compatible, native, opts.data_only) {
+        eprintln!(
+            "Note: --compatible {} leaves out triggers, routines, events, users, extensions, comments and privileges, which are in {}'s own SQL",
+            compatible,
+            source.name()
+        );
+    }
+
+    // Statements creating the database and switching to it, ahead of
+    // everything else in a file dump
+    let database = match &shared {
+        Some((database, _)) => Some(database.to_string()),
+        None if opts.create_database => match database_of(source_url)? {
+            Some(database) => Some(database),
+            None => bail!("--create-database needs the source URL to name a database"),
+        },
+        None => None,
+    };
+    let database_statements = database
+        .as_deref()
+        .map(|database| dialect.database_statements(database))
+        .unwrap_or_default();
+    if opts.create_database && shared.is_none() {
+        if database_statements.is_empty() {
+            eprintln!(
+                "Note: --create-database has no effect on {}; restore into an existing database",
+                dialect.name()
+            );
+        } else if opts.format == DumpFormat::Dir {
+            eprintln!("Note: --create-database has no effect on directory dumps, restored into the --destination database");
+        }
+    }
 
     // Start consistent snapshot if requested
+    let snapshot_started = tokio::time::Instant::now();
+    let mut position = None;
     if opts.consistent_snapshot {
-        println!("Starting consistent snapshot...");
-        session.start_consistent_snapshot().await?;
+        eprintln!("Starting consistent snapshot...");
+        if opts.master_data {
+            position = session.start_snapshot_at_position().await?;
+            match &position {
+                Some(position) => eprintln!("Replication position: {}", position),
+                None => eprintln!(
+                    "Note: {} reports no replication position (is binary logging on?); --master-data has no effect",
+                    source.name()
+                ),
+            }
+        } else {
+            session.start_consistent_snapshot().await?;
+        }
     }
 
-    // Create output writer
-    let mut writer: Box<dyn Write> = if opts.gzip {
-        println!("Output will be gzip compressed");
-        Box::new(GzEncoder::new(
-            BufWriter::new(File::create(output_path)?),
-            Compression::default(),
-        ))
+    // Get list of tables
+    let mut tables = session.list_tables(&opts.filter).await?;
+    tables.retain(|table| in_schemas(table, &opts.schemas));
+    eprintln!("Found {} table(s) to dump", tables.len());
+    let mut views = Vec::new();
+    if !opts.skip_views && !opts.data_only {
+        for view in session.list_views(&opts.filter).await? {
+            if !in_schemas(&view, &opts.schemas) {
+                continue;
+            }
+            let create = session
+                .show_create_view(&view)
+                .await
+                .with_context(|| format!("Failed to dump view '{}'", view))?;
+            let create = definition(&create, source, opts);
+            let create = match opts.compatible {
+                Some(compatible) => compatible.translate(&view, &create),
+                None => create,
+            };
+            views.push((view, create));
+        }
+        views = order_views(views);
+    }
+    if !views.is_empty() {
+        eprintln!("Found {} view(s) to dump", views.len());
+    }
+    let extensions = if opts.data_only || !native {
+        Vec::new()
+    } else {
+        session.extension_statements().await?
+    };
+    if !extensions.is_empty() {
+        eprintln!("Found {} extension(s) to create", extensions.len());
+    }
+    let mut routines = Vec::new();
+    if opts.routines && !opts.data_only && native {
+        for routine in session.list_routines().await? {
+            if !in_schemas(&routine.name, &opts.schemas) {
+                continue;
+            }
+            let create = session
+                .show_create_routine(&routine)
+                .await
+                .with_context(|| format!("Failed to dump routine '{}'", routine))?;
+            routines.push((routine, definition(&create, source, opts)));
+        }
+        eprintln!("Found {} routine(s) to dump", routines.len());
+    }
+    let schemas = if opts.data_only {
+        Vec::new()
     } else {
-        Box::new(BufWriter::new(File::create(output_path)?))
+        create_schema_statements(
+            dialect,
+            tables
+                .iter()
+                .chain(views.iter().map(|(view, _)| view))
+                .chain(routines.iter().map(|(routine, _)| &routine.name))
+                .map(String::as_str),
+        )
+    };
+    let mut events = Vec::new();
+    if opts.events && !opts.data_only && native {
+        for event in session.list_events().await? {
+            let create = session
+                .show_create_event(&event)
+                .await
+                .with_context(|| format!("Failed to dump event '{}'", event))?;
+            events.push((event, definition(&create, source, opts)));
+        }
+        eprintln!("Found {} event(s) to dump", events.len());
+    }
+    let (users, grants) = if opts.users && native {
+        session
+            .user_statements()
+            .await
+            .context("Failed to dump users")?
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    if opts.users && native {
+        eprintln!("Found {} user statement(s) to dump", users.len());
+    }
+    if opts.throttle.is_limited() {
+        eprintln!("Throttling source reads to {}", opts.throttle);
+    }
+    if opts.subset.is_set() && !opts.schema_only {
+        eprintln!("Dumping {}", opts.subset);
+    }
+
+    // Create output
+    if opts.compress != Codec::None {
+        eprintln!("Output will be {} compressed", opts.compress);
+    }
+    if opts.encrypt.is_some() {
+        eprintln!("Output will be age encrypted");
+    }
+    let mut output = match opts.format {
+        DumpFormat::File | DumpFormat::Binary => {
+            let mut writer = match shared.as_mut().and_then(|(_, file)| file.writer.take()) {
+                Some(writer) => writer,
+                None => {
+                    let encoder = opts
+                        .compress
+                        .create(output_path, level, opts.encrypt.as_ref())
+                        .with_context(|| {
+                            format!("Failed to create output file '{}'", output_path)
+                        })?;
+                    let mut writer = BackgroundEncoder::spawn(encoder, opts.write_buffer)?;
+                    if opts.format == DumpFormat::Binary {
+                        writer.write_all(binary::MAGIC)?;
+                    }
+                    writer
+                }
+            };
+            write_dump_header(&mut writer, dialect, opts)?;
+            if let Some(position) = &position {
+                write_replication_position(&mut writer, position)?;
+            }
+            if let Some(database) = &database {
+                write_database(&mut writer, database, &database_statements)?;
+            }
+            // Owners and grants anywhere in the dump need the users first
+            write_users(&mut writer, &users)?;
+            write_schemas(&mut writer, &schemas)?;
+            write_extensions(&mut writer, &extensions)?;
+            // Tables cannot be dropped while views depend on them
+            write_view_drops(&mut writer, &views, dialect)?;
+            // Routines come before the tables, whose triggers and defaults
+            // may call them
+            write_routines(&mut writer, &routines, dialect)?;
+            Output::File(writer)
+        }
+        DumpFormat::Dir => {
+            let mut manifest = Manifest::new(
+                DumpFormat::Dir,
+                dialect.name(),
+                opts.compress,
+                &tables,
+                !opts.data_only,
+                !opts.schema_only,
+            );
+            for entry in &mut manifest.tables {
+                if !dumps_data(&entry.name, opts) {
+                    entry.data = None;
+                }
+            }
+            if !views.is_empty() {
+                manifest.drop_views = Some(DROP_VIEWS_FILE.to_string());
+                manifest.views = Some(VIEWS_FILE.to_string());
+            }
+            if !routines.is_empty() {
+                manifest.routines = Some(ROUTINES_FILE.to_string());
+            }
+            if !events.is_empty() {
+                manifest.events = Some(EVENTS_FILE.to_string());
+            }
+            if opts.post_data {
+                manifest.add_post_data();
+            }
+            if !extensions.is_empty() {
+                manifest.extensions = Some(EXTENSIONS_FILE.to_string());
+            }
+            if !users.is_empty() {
+                manifest.users = Some(USERS_FILE.to_string());
+            }
+            if !grants.is_empty() {
+                manifest.grants = Some(GRANTS_FILE.to_string());
+            }
+            if !schemas.is_empty() {
+                manifest.schemas = Some(SCHEMAS_FILE.to_string());
+            }
+            manifest.replication = position.clone();
+            let dir = DirOutput::create(output_path, level, manifest, opts.resume)?;
+            if !users.is_empty() {
+                dir.write_file(USERS_FILE, dialect, opts, |w| write_users(w, &users))?;
+            }
+            if !schemas.is_empty() {
+                dir.write_file(SCHEMAS_FILE, dialect, opts, |w| write_schemas(w, &schemas))?;
+            }
+            if !extensions.is_empty() {
+                dir.write_file(EXTENSIONS_FILE, dialect, opts, |w| {
+                    write_extensions(w, &extensions)
+                })?;
+            }
+            if !routines.is_empty() {
+                dir.write_file(ROUTINES_FILE, dialect, opts, |w| {
+                    write_routines(w, &routines, dialect)
+                })?;
+            }
+            Output::Dir(Box::new(dir))
+        }
     };
 
-    // Write header
-    write_dump_header(&mut writer, dialect)?;
+    let mut jobs = opts.jobs;
+    if let Some(max) = opts.max_connections {
+        // This session stays open throughout, so workers share what is left
+        if jobs > 1 && jobs >= max {
+            jobs = (max - 1).max(1);
+            eprintln!(
+                "Limiting to {} job(s) to stay within --max-connections {}",
+                jobs, max
+            );
+        }
+    }
 
-    // Get list of tables
-    let tables = session.list_tables(&opts.tables, &opts.exclude).await?;
-    println!("Found {} table(s) to dump", tables.len());
+    let mut snapshot = None;
+    if jobs > 1 && opts.consistent_snapshot {
+        // Workers join the coordinator's snapshot so the dump stays consistent
+        snapshot = session.export_snapshot().await?;
+        if snapshot.is_none() {
+            eprintln!(
+                "{} cannot share snapshots between connections; dumping sequentially",
+                dialect.name()
+            );
+            jobs = 1;
+        }
+    }
 
-    // Dump each table
-    for (idx, table) in tables.iter().enumerate() {
-        println!(
-            "\n[{}/{}] Dumping table '{}'...",
-            idx + 1,
-            tables.len(),
-            table
+    // With --post-data every table is created bare up front, and its indexes
+    // and foreign keys wait until all the data is in
+    let mut post_data = Vec::new();
+    if opts.post_data && !opts.data_only {
+        eprintln!("\nDumping the structure of {} table(s)...", tables.len());
+        for (idx, table) in tables.iter().enumerate() {
+            let mut post = Vec::new();
+            match &mut output {
+                Output::File(writer) => {
+                    dump_schema(&mut *session, writer, table, dialect, opts, Some(&mut post))
+                        .await?
+                }
+                Output::Dir(dir) => {
+                    let entry = &dir.manifest.tables[idx];
+                    dump_schema_file(
+                        &mut *session,
+                        dir,
+                        entry,
+                        table,
+                        dialect,
+                        opts,
+                        Some(&mut post),
+                    )
+                    .await?
+                }
+            }
+            post_data.push(post);
+        }
+    }
+
+    let sections = if jobs > 1 {
+        eprintln!("Dumping with {} parallel jobs", jobs);
+        let source = Source {
+            pool: &pool,
+            snapshot: snapshot.as_deref(),
+        };
+        let work = dump_parallel(
+            source,
+            &mut output,
+            output_path,
+            &tables,
+            dialect,
+            jobs,
+            opts,
         );
+        if opts.consistent_snapshot {
+            // This session holds the snapshot the workers share, and sits
+            // idle until they finish
+            opts.snapshot_watch
+                .watch(snapshot_started, Some(&mut *session), work)
+                .await?
+        } else {
+            work.await?
+        }
+    } else {
+        // Dump each table
+        let work = async {
+            let mut sections = Vec::with_capacity(tables.len());
+            for (idx, table) in tables.iter().enumerate() {
+                eprintln!(
+                    "\n[{}/{}] Dumping table '{}'...",
+                    idx + 1,
+                    tables.len(),
+                    table
+                );
 
-        dump_table(&mut *session, &mut writer, table, dialect, &opts)
+                let dumped = match &mut output {
+                    Output::File(writer) => {
+                        dump_table(&mut *session, writer, table, dialect, opts, None).await
+                    }
+                    Output::Dir(dir) => {
+                        let files = TableFiles::Dir(dir, &dir.manifest.tables[idx]);
+                        dump_table_files(&mut *session, table, files, dialect, opts, None).await
+                    }
+                };
+                sections.push(dumped.with_context(|| format!("Failed to dump table '{}'", table))?);
+            }
+            Ok(sections)
+        };
+        if opts.consistent_snapshot {
+            opts.snapshot_watch
+                .watch(snapshot_started, None, work)
+                .await?
+        } else {
+            work.await?
+        }
+    };
+
+    if post_data.iter().any(|post| !post.is_empty()) {
+        eprintln!("\nDumping indexes and foreign keys...");
+    }
+    match &mut output {
+        Output::File(writer) => {
+            for (table, post) in tables.iter().zip(&post_data) {
+                write_post_data(writer, table, post)?;
+            }
+        }
+        Output::Dir(dir) => {
+            for (idx, post) in post_data.iter().enumerate() {
+                if post.is_empty() {
+                    // Nothing was held back, so the table needs no file
+                    dir.manifest.tables[idx].post_data = None;
+                    continue;
+                }
+                let entry = &dir.manifest.tables[idx];
+                if let Some(file) = &entry.post_data {
+                    dir.write_file(file, dialect, opts, |w| {
+                        write_post_data(w, &entry.name, post)
+                    })?;
+                }
+            }
+        }
+    }
+
+    // Views are created after every table, as they may select from any of them
+    if !views.is_empty() {
+        eprintln!("\nDumping {} view(s)...", views.len());
+        match &mut output {
+            Output::File(writer) => write_views(writer, &views)?,
+            Output::Dir(dir) => {
+                dir.write_file(DROP_VIEWS_FILE, dialect, opts, |w| {
+                    write_view_drops(w, &views, dialect)
+                })?;
+                dir.write_file(VIEWS_FILE, dialect, opts, |w| write_views(w, &views))?;
+            }
+        }
+    }
+
+    // Events may run as soon as they exist, so they wait for everything else
+    if !events.is_empty() {
+        eprintln!("\nDumping {} event(s)...", events.len());
+        match &mut output {
+            Output::File(writer) => write_events(writer, &events, dialect)?,
+            Output::Dir(dir) => dir.write_file(EVENTS_FILE, dialect, opts, |w| {
+                write_events(w, &events, dialect)
+            })?,
+        }
+    }
+
+    // Grants may name any table or routine, so they follow everything else
+    if !grants.is_empty() {
+        match &mut output {
+            Output::File(writer) => match &mut shared {
+                // Written once every database is in
+                Some((_, file)) => file.grants.extend_from_slice(&grants),
+                None => write_grants(writer, &grants)?,
+            },
+            Output::Dir(dir) => {
+                dir.write_file(GRANTS_FILE, dialect, opts, |w| write_grants(w, &grants))?
+            }
+        }
+    }
+
+    match output {
+        Output::File(mut writer) => {
+            // Write footer
+            write_dump_footer(&mut writer, dialect, opts)?;
+
+            // Commit transaction if opened
+            session.commit().await?;
+
+            if let Some((database, file)) = shared {
+                // The next database carries on in the same stream
+                file.writer = Some(writer);
+                file.dialect = dialect.name();
+                file.tables
+                    .extend(tables.iter().map(|table| format!("{}.{}", database, table)));
+                file.sections.extend(sections);
+                return Ok(());
+            }
+
+            // Flush and close
+            writer.finish()?;
+
+            // Row counts and checksums go beside the file, which has no
+            // room for them
+            if output_path != STDIO {
+                let mut manifest = Manifest::new(
+                    opts.format,
+                    dialect.name(),
+                    opts.compress,
+                    &tables,
+                    false,
+                    false,
+                );
+                complete_manifest(&mut manifest, sections, opts);
+                manifest.replication = position;
+                manifest.write(Path::new(&format!("{}{}", output_path, SIDECAR_SUFFIX)))?;
+            }
+        }
+        Output::Dir(mut dir) => {
+            session.commit().await?;
+
+            // Written last, so only a complete directory has one
+            complete_manifest(&mut dir.manifest, sections, opts);
+            dir.manifest.write(&dir.path.join(MANIFEST_FILE))?;
+            dir.state.remove()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Record what was dumped of each table, in table order
+fn complete_manifest(
+    manifest: &mut Manifest,
+    sections: Vec<Option<DataSection>>,
+    opts: &DumpOptions,
+) {
+    for (entry, section) in manifest.tables.iter_mut().zip(sections) {
+        entry.section = section;
+    }
+    if opts.reproducible {
+        manifest.created = None;
+    }
+}
+
+/// A file several databases are dumped into, one after the other
+#[derive(Default)]
+struct SharedFile {
+    /// The open stream, between two databases
+    writer: Option<BackgroundEncoder>,
+    dialect: &'static str,
+    /// Tables dumped so far, as `database.table`, and their data sections
+    tables: Vec<String>,
+    sections: Vec<Option<DataSection>>,
+    /// Grants of the server's users, written after every database
+    grants: Vec<String>,
+}
+
+/// Where the dump is written
+enum Output {
+    /// A single SQL stream, to a file or stdout
+    File(BackgroundEncoder),
+    /// A directory with schema and data files per table
+    Dir(Box<DirOutput>),
+}
+
+/// A directory dump being written
+struct DirOutput {
+    path: PathBuf,
+    /// Compression level of the data files
+    level: Option<u32>,
+    manifest: Manifest,
+    /// Which tables are done, for `--resume`
+    state: StateFile,
+}
+
+impl DirOutput {
+    /// Create the directory and its `schema` and `data` subdirectories. An
+    /// existing directory must be empty, so no stale files are mixed in,
+    /// unless `resume` continues the dump an interrupted run left there.
+    fn create(path: &str, level: Option<u32>, manifest: Manifest, resume: bool) -> Result<Self> {
+        if path == STDIO {
+            bail!("A directory dump cannot be written to stdout");
+        }
+        let path = PathBuf::from(path);
+        let resumed = if resume {
+            StateFile::open(&path)?
+        } else {
+            None
+        };
+        match &resumed {
+            Some(state) => {
+                if state.compress() != manifest.compress {
+                    bail!(
+                        "'{}' was dumped with --compress {}; resume it with the same",
+                        path.display(),
+                        state.compress()
+                    );
+                }
+                eprintln!("Resuming the interrupted dump in '{}'", path.display());
+            }
+            None => {
+                if let Ok(mut entries) = std::fs::read_dir(&path) {
+                    if entries.next().is_some() {
+                        if resume && path.join(MANIFEST_FILE).exists() {
+                            bail!(
+                                "'{}' holds a complete dump; there is nothing to resume",
+                                path.display()
+                            );
+                        }
+                        if path.join(STATE_FILE).exists() {
+                            bail!(
+                                "Output directory '{}' holds an interrupted dump; pass --resume to continue it",
+                                path.display()
+                            );
+                        }
+                        bail!("Output directory '{}' is not empty", path.display());
+                    }
+                }
+            }
+        }
+        let post_data = manifest.tables.iter().any(|t| t.post_data.is_some());
+        let subdirs = ["schema", "data", "post-data"];
+        for sub in &subdirs[..if post_data { 3 } else { 2 }] {
+            std::fs::create_dir_all(path.join(sub)).with_context(|| {
+                format!("Failed to create output directory '{}'", path.display())
+            })?;
+        }
+
+        let state = match resumed {
+            Some(state) => state,
+            None => StateFile::create(&path, manifest.compress)?,
+        };
+        Ok(DirOutput {
+            path,
+            level,
+            manifest,
+            state,
+        })
+    }
+
+    /// Start writing `file`, relative to the directory
+    fn create_file(
+        &self,
+        file: &str,
+        codec: Codec,
+        level: Option<u32>,
+        opts: &DumpOptions,
+    ) -> Result<BackgroundEncoder> {
+        let path = self.path.join(file);
+        let path = path.to_string_lossy();
+        let encoder = codec
+            .create(&path, level, opts.encrypt.as_ref())
+            .with_context(|| format!("Failed to create '{}'", path))?;
+        Ok(BackgroundEncoder::spawn(encoder, opts.write_buffer)?)
+    }
+
+    /// Write a plain SQL file with the dump's header and footer around `body`
+    fn write_file(
+        &self,
+        file: &str,
+        dialect: &dyn SqlDialect,
+        opts: &DumpOptions,
+        body: impl FnOnce(&mut dyn Write) -> Result<()>,
+    ) -> Result<()> {
+        let mut writer = self.create_file(file, Codec::None, None, opts)?;
+        write_dump_header(&mut writer, dialect, opts)?;
+        body(&mut writer)?;
+        write_dump_footer(&mut writer, dialect, opts)?;
+        writer
+            .finish()
+            .with_context(|| format!("Failed to write '{}'", file))
+    }
+}
+
+/// Files one table is dumped into by itself
+#[derive(Clone, Copy)]
+enum TableFiles<'a> {
+    /// Part of the output file, appended to it once every job is done;
+    /// sealed with a throwaway key when the dump is encrypted
+    Part(&'a str, Option<&'a Encryption>),
+    /// The table's schema and data files in a directory dump
+    Dir(&'a DirOutput, &'a TableEntry),
+}
+
+/// Where dump jobs get their sessions, and the snapshot they share if any
+#[derive(Clone, Copy)]
+struct Source<'a, 'e> {
+    pool: &'a SessionPool<'e>,
+    snapshot: Option<&'a str>,
+}
+
+/// Dump tables concurrently. Into a single output, each table goes to a part
+/// file and the parts are appended in table order, so the output matches a
+/// sequential dump; a directory dump has files per table already. Returns
+/// what was dumped of each table's data, in table order.
+async fn dump_parallel(
+    source: Source<'_, '_>,
+    output: &mut Output,
+    output_path: &str,
+    tables: &[String],
+    dialect: &dyn SqlDialect,
+    jobs: usize,
+    opts: &DumpOptions,
+) -> Result<Vec<Option<DataSection>>> {
+    let writer = match output {
+        Output::File(writer) => writer,
+        Output::Dir(dir) => {
+            let queue = tables
+                .iter()
+                .zip(&dir.manifest.tables)
+                .map(|(table, entry)| (table, TableFiles::Dir(dir, entry)))
+                .collect();
+            return run_workers(source, queue, dialect, jobs, opts).await;
+        }
+    };
+
+    // Parts of a dump to stdout or object storage go to the temp directory
+    // instead
+    let base = match output_path {
+        path if path != STDIO && !storage::is_remote(path) => path.to_string(),
+        _ => std::env::temp_dir()
+            .join(format!("migrasquiel-dump-{}", std::process::id()))
+            .to_string_lossy()
+            .into_owned(),
+    };
+    let parts: Vec<String> = (0..tables.len())
+        .map(|idx| format!("{}.part{}", base, idx))
+        .collect();
+    let key = opts.encrypt.as_ref().map(|_| Encryption::ephemeral());
+    let queue = tables
+        .iter()
+        .zip(&parts)
+        .map(|(table, part)| (table, TableFiles::Part(part, key.as_ref().map(|(e, _)| e))))
+        .collect();
+    let result = run_workers(source, queue, dialect, jobs, opts).await;
+
+    if result.is_ok() {
+        for part in &parts {
+            let mut reader = Codec::None.open(part, key.as_ref().map(|(_, d)| d))?;
+            std::io::copy(&mut reader, writer)?;
+        }
+        writer.flush()?;
+    }
+    for part in &parts {
+        let _ = std::fs::remove_file(part);
+    }
+
+    result
+}
+
+/// Dump the queued tables with `jobs` workers, returning what was dumped of
+/// each in queue order
+async fn run_workers(
+    source: Source<'_, '_>,
+    queue: Vec<(&String, TableFiles<'_>)>,
+    dialect: &dyn SqlDialect,
+    jobs: usize,
+    opts: &DumpOptions,
+) -> Result<Vec<Option<DataSection>>> {
+    let mut sections = vec![None; queue.len()];
+    let queue = Mutex::new(queue.into_iter().enumerate().collect());
+    let progress = MultiProgress::new();
+    let workers = (0..jobs).map(|_| dump_worker(source, &queue, dialect, opts, &progress));
+    for (idx, section) in futures::future::try_join_all(workers)
+        .await?
+        .into_iter()
+        .flatten()
+    {
+        sections[idx] = section;
+    }
+    Ok(sections)
+}
+
+/// A table waiting for a worker, by its position in the dump
+type Job<'a> = (usize, (&'a String, TableFiles<'a>));
+
+async fn dump_worker(
+    source: Source<'_, '_>,
+    queue: &Mutex<VecDeque<Job<'_>>>,
+    dialect: &dyn SqlDialect,
+    opts: &DumpOptions,
+    progress: &MultiProgress,
+) -> Result<Vec<(usize, Option<DataSection>)>> {
+    let mut session = source
+        .pool
+        .get()
+        .await
+        .context("Failed to connect to source database")?;
+
+    if let Some(snapshot) = source.snapshot {
+        session.import_snapshot(snapshot).await?;
+    }
+
+    let mut sections = Vec::new();
+    loop {
+        let next = queue.lock().unwrap_or_else(|e| e.into_inner()).pop_front();
+        let Some((idx, (table, files))) = next else {
+            break;
+        };
+
+        let section = dump_table_files(&mut *session, table, files, dialect, opts, Some(progress))
             .await
             .with_context(|| format!("Failed to dump table '{}'", table))?;
+        sections.push((idx, section));
     }
 
-    // Write footer
-    write_dump_footer(&mut writer, dialect)?;
-
-    // Commit transaction if opened
     session.commit().await?;
+    Ok(sections)
+}
 
-    // Flush and close
-    writer.flush()?;
+/// Dump one table into files of its own
+async fn dump_table_files(
+    session: &mut dyn DbSession,
+    table: &str,
+    files: TableFiles<'_>,
+    dialect: &dyn SqlDialect,
+    opts: &DumpOptions,
+    progress: Option<&MultiProgress>,
+) -> Result<Option<DataSection>> {
+    let mut section = None;
+    match files {
+        TableFiles::Part(part, encryption) => {
+            let encoder = Codec::None.create(part, None, encryption)?;
+            let mut writer = BackgroundEncoder::spawn(encoder, opts.write_buffer)?;
+            section = dump_table(session, &mut writer, table, dialect, opts, progress).await?;
+            writer
+                .finish()
+                .with_context(|| format!("Failed to write '{}'", part))?;
+        }
+        TableFiles::Dir(dir, entry) => {
+            if let Some(section) = dir.state.done(table) {
+                if progress.is_none() {
+                    eprintln!("  Dumped before the interruption; skipping");
+                }
+                return Ok(section);
+            }
+            // With --post-data the schema files are already written
+            if !opts.post_data {
+                dump_schema_file(session, dir, entry, table, dialect, opts, None).await?;
+            }
+            if let Some(file) = &entry.data {
+                section =
+                    Some(dump_data_file(session, dir, file, table, dialect, opts, progress).await?);
+            }
+            dir.state.record_done(table, section.clone())?;
+        }
+    }
+    Ok(section)
+}
+
+/// Dump a table's data, sequences and triggers into `file` of a directory
+/// dump. A chunked read notes in the state file how far it has got; when an
+/// interrupted dump left such a checkpoint, the rows before it are kept and
+/// reading carries on after its key.
+async fn dump_data_file(
+    session: &mut dyn DbSession,
+    dir: &DirOutput,
+    file: &str,
+    table: &str,
+    dialect: &dyn SqlDialect,
+    opts: &DumpOptions,
+    progress: Option<&MultiProgress>,
+) -> Result<DataSection> {
+    let path = dir.path.join(file).to_string_lossy().into_owned();
+    let interrupted = format!("{}.interrupted", path);
 
-    println!("\nDump completed successfully!");
-    println!("Output: {}", output_path);
+    // An encrypted file cannot be read back without the recipients' keys,
+    // and a subset's limits count from the first row, so those tables start
+    // over
+    let checkpoints = opts.encrypt.is_none() && !opts.subset.is_set();
+    let mut from = None;
+    if let Some(checkpoint) = dir.state.checkpoint(table).filter(|_| checkpoints) {
+        // The end of the file may be cut short, but everything up to the
+        // checkpoint was flushed to disk before it was recorded
+        let kept = std::fs::rename(&path, &interrupted).is_ok()
+            && opts
+                .compress
+                .open(&interrupted, None)
+                .and_then(|old| {
+                    std::io::copy(&mut old.take(checkpoint.bytes), &mut std::io::sink())
+                })
+                .is_ok_and(|bytes| bytes == checkpoint.bytes);
+        if kept {
+            from = Some(checkpoint);
+        } else {
+            eprintln!("  The interrupted data file is incomplete; dumping the table again");
+        }
+    }
 
-    Ok(())
+    // The checksum covers the whole file, so a restore can check it before
+    // running any of it
+    let writer = dir.create_file(file, opts.compress, dir.level, opts)?;
+    let syncer = writer.syncer();
+    let mut writer = SectionWriter::new(writer);
+    match &from {
+        Some(checkpoint) => {
+            let old = opts.compress.open(&interrupted, None)?;
+            std::io::copy(&mut old.take(checkpoint.bytes), &mut writer)?;
+            eprintln!("  Continuing after row {}", checkpoint.rows);
+        }
+        None => write_dump_header(&mut writer, dialect, opts)?,
+    }
+    let resume = syncer.filter(|_| checkpoints).map(|syncer| DataResume {
+        state: &dir.state,
+        syncer,
+        from,
+    });
+    let rows = dump_data(session, &mut writer, table, dialect, opts, progress, resume).await?;
+    dump_sequences(session, &mut writer, table, opts).await?;
+    dump_triggers(session, &mut writer, table, dialect, opts).await?;
+    write_dump_footer(&mut writer, dialect, opts)?;
+    let (writer, section) = writer.finish(rows);
+    writer
+        .finish()
+        .with_context(|| format!("Failed to write '{}'", file))?;
+    let _ = std::fs::remove_file(&interrupted);
+    Ok(section)
 }
 
+/// Dump one table, returning what was dumped of its data. With `progress`,
+/// the table runs alongside others and its bar joins the shared display.
 async fn dump_table(
     session: &mut dyn DbSession,
-    writer: &mut Box<dyn Write>,
+    writer: &mut dyn Write,
+    table: &str,
+    dialect: &dyn SqlDialect,
+    opts: &DumpOptions,
+    progress: Option<&MultiProgress>,
+) -> Result<Option<DataSection>> {
+    if !opts.data_only && !opts.post_data {
+        dump_schema(session, writer, table, dialect, opts, None).await?;
+    }
+    if !dumps_data(table, opts) {
+        dump_sequences(session, writer, table, opts).await?;
+        dump_triggers(session, writer, table, dialect, opts).await?;
+        return Ok(None);
+    }
+    let mut writer = SectionWriter::new(writer);
+    let rows = dump_data(session, &mut writer, table, dialect, opts, progress, None).await?;
+    let (writer, section) = writer.finish(rows);
+    dump_sequences(session, writer, table, opts).await?;
+    dump_triggers(session, writer, table, dialect, opts).await?;
+    Ok(Some(section))
+}
+
+/// Write what sets the sequences behind `table`'s columns, after its rows.
+/// A schema-only dump leaves the sequences at their start.
+async fn dump_sequences(
+    session: &mut dyn DbSession,
+    writer: &mut dyn Write,
+    table: &str,
+    opts: &DumpOptions,
+) -> Result<()> {
+    if !keeps_native_sql(session.dialect(), opts) {
+        return Ok(());
+    }
+    let statements = session
+        .sequence_statements(table, dumps_data(table, opts))
+        .await?;
+    if statements.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(writer)?;
+    writeln!(writer, "-- Sequences for {}", table)?;
+    for statement in &statements {
+        writeln!(writer, "{}", statement)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Whether the rows of `table` go in the dump: not under `--schema-only`,
+/// nor when `--exclude-data` names it
+fn dumps_data(table: &str, opts: &DumpOptions) -> bool {
+    !opts.schema_only && !opts.exclude_data.iter().any(|p| table_matches(p, table))
+}
+
+/// Whether statements only the source's own SQL can express, such as
+/// triggers and routines, go in the dump: always, unless `--compatible`
+/// names another database
+fn keeps_native_sql(source: &dyn SqlDialect, opts: &DumpOptions) -> bool {
+    opts.compatible
+        .is_none_or(|compatible| compatible.is_native(source))
+}
+
+/// `create` as it goes in the dump, without its definer under
+/// `--skip-definer`
+fn definition(create: &str, dialect: &dyn SqlDialect, opts: &DumpOptions) -> String {
+    if opts.skip_definer {
+        dialect.strip_definer(create)
+    } else {
+        create.to_string()
+    }
+}
+
+/// Write the triggers on `table`. They follow its rows, so loading the rows
+/// does not fire them.
+async fn dump_triggers(
+    session: &mut dyn DbSession,
+    writer: &mut dyn Write,
     table: &str,
     dialect: &dyn SqlDialect,
     opts: &DumpOptions,
 ) -> Result<()> {
-    // Dump schema
-    if !opts.data_only {
-        let create_stmt = session.show_create_table(table).await?;
+    if opts.skip_triggers || opts.data_only || !keeps_native_sql(session.dialect(), opts) {
+        return Ok(());
+    }
+    let mut triggers = Vec::new();
+    for trigger in session.list_triggers(table).await? {
+        let create = session.show_create_trigger(table, &trigger).await?;
+        triggers.push(definition(&create, dialect, opts));
+    }
+    if triggers.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(writer)?;
+    writeln!(writer, "-- Triggers for {}", table)?;
+    write_compound_statements(writer, &triggers, dialect)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Create `database` and switch to it, in a file holding several or with
+/// `--create-database`
+fn write_database(writer: &mut dyn Write, database: &str, statements: &[String]) -> Result<()> {
+    if statements.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer)?;
+    writeln!(writer, "-- Database {}", database)?;
+    for statement in statements {
+        writeln!(writer, "{};", statement)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Create the server's user accounts, ahead of everything else
+fn write_users(writer: &mut dyn Write, users: &[String]) -> Result<()> {
+    if users.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer)?;
+    writeln!(writer, "-- Users")?;
+    for statement in users {
+        writeln!(writer, "{};", statement.trim_end_matches(';'))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Create the schemas the tables, views and routines live in
+fn write_schemas(writer: &mut dyn Write, schemas: &[String]) -> Result<()> {
+    if schemas.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer)?;
+    writeln!(writer, "-- Schemas")?;
+    for statement in schemas {
+        writeln!(writer, "{};", statement)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Grant the users their privileges, after everything they may name exists
+fn write_grants(writer: &mut dyn Write, grants: &[String]) -> Result<()> {
+    writeln!(writer)?;
+    writeln!(writer, "-- Grants")?;
+    for statement in grants {
+        writeln!(writer, "{};", statement.trim_end_matches(';'))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Create the extensions the tables may need, ahead of everything else
+fn write_extensions(writer: &mut dyn Write, extensions: &[String]) -> Result<()> {
+    if extensions.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer)?;
+    writeln!(writer, "-- Extensions")?;
+    for statement in extensions {
+        writeln!(writer, "{}", statement)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Create the stored procedures and functions of `routines`, each dropped
+/// first where its definition does not replace it
+fn write_routines(
+    writer: &mut dyn Write,
+    routines: &[(Routine, String)],
+    dialect: &dyn SqlDialect,
+) -> Result<()> {
+    for (routine, create) in routines {
         writeln!(writer)?;
-        writeln!(writer, "-- Table structure for {}", table)?;
-        writeln!(writer, "DROP TABLE IF EXISTS `{}`;", table)?;
-        let normalized_create = create_stmt.trim_end_matches(';');
-        writeln!(writer, "{};", normalized_create)?;
-        writer.flush()?;
+        writeln!(
+            writer,
+            "-- {} structure for {}",
+            routine.kind.to_lowercase(),
+            routine
+        )?;
+        if let Some(drop) = dialect.drop_routine_statement(routine) {
+            writeln!(writer, "{};", drop)?;
+        }
+        write_compound_statements(writer, std::slice::from_ref(create), dialect)?;
     }
+    writer.flush()?;
+    Ok(())
+}
 
-    // Dump data
-    if !opts.schema_only {
+/// Create the scheduled events of `events`, each dropped first
+fn write_events(
+    writer: &mut dyn Write,
+    events: &[(String, String)],
+    dialect: &dyn SqlDialect,
+) -> Result<()> {
+    for (event, create) in events {
+        writeln!(writer)?;
+        writeln!(writer, "-- Event structure for {}", event)?;
+        writeln!(
+            writer,
+            "DROP EVENT IF EXISTS {};",
+            dialect.quote_identifier(event)
+        )?;
+        write_compound_statements(writer, std::slice::from_ref(create), dialect)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write statements that may hold `;` in their body, between `DELIMITER`
+/// lines where the dialect's client needs them
+fn write_compound_statements(
+    writer: &mut dyn Write,
+    statements: &[String],
+    dialect: &dyn SqlDialect,
+) -> Result<()> {
+    let delimiter = dialect.compound_delimiter();
+    if let Some(delimiter) = delimiter {
+        writeln!(writer, "DELIMITER {}", delimiter)?;
+    }
+    for statement in statements {
+        writeln!(
+            writer,
+            "{}{}",
+            statement.trim_end().trim_end_matches(';'),
+            delimiter.unwrap_or(";")
+        )?;
+    }
+    if delimiter.is_some() {
+        writeln!(writer, "DELIMITER ;")?;
+    }
+    Ok(())
+}
+
+/// Write the structure of `table`. With `post`, its indexes and foreign keys
+/// are held back there instead, where the dialect can split them off.
+async fn dump_schema(
+    session: &mut dyn DbSession,
+    writer: &mut dyn Write,
+    table: &str,
+    dialect: &dyn SqlDialect,
+    opts: &DumpOptions,
+    post: Option<&mut Vec<String>>,
+) -> Result<()> {
+    let mut create_stmt = session.show_create_table(table).await?;
+    if let Some(compatible) = opts.compatible {
+        create_stmt = compatible.translate(table, &create_stmt);
+    }
+    let native = keeps_native_sql(session.dialect(), opts);
+    let mut normalized_create = create_stmt.trim_end_matches(';').to_string();
+    if let Some(post) = post {
+        // Indexes split first, so MySQL still sees the keys foreign keys use
+        let (create, indexes) = dialect.split_secondary_indexes(table, &normalized_create);
+        let (create, foreign_keys) = dialect.split_foreign_keys(table, &create);
+        post.extend(indexes);
+        post.extend(foreign_keys);
+        normalized_create = create.trim_end_matches(';').to_string();
+    }
+    writeln!(writer)?;
+    writeln!(writer, "-- Table structure for {}", table)?;
+    writeln!(writer, "{};", dialect.drop_table_statement(table))?;
+    writeln!(writer, "{};", normalized_create)?;
+    if opts.comments && native {
+        for statement in session.comment_statements(table).await? {
+            writeln!(writer, "{}", statement)?;
+        }
+    }
+    if opts.privileges && native {
+        for statement in session.privilege_statements(table).await? {
+            writeln!(writer, "{}", statement)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write the schema file of `entry` in a directory dump. Each file carries
+/// the session settings, so it restores alone.
+async fn dump_schema_file(
+    session: &mut dyn DbSession,
+    dir: &DirOutput,
+    entry: &TableEntry,
+    table: &str,
+    dialect: &dyn SqlDialect,
+    opts: &DumpOptions,
+    post: Option<&mut Vec<String>>,
+) -> Result<()> {
+    let Some(file) = &entry.schema else {
+        return Ok(());
+    };
+    let mut writer = dir.create_file(file, Codec::None, None, opts)?;
+    write_dump_header(&mut writer, dialect, opts)?;
+    dump_schema(session, &mut writer, table, dialect, opts, post).await?;
+    if entry.data.is_none() {
+        dump_sequences(session, &mut writer, table, opts).await?;
+        dump_triggers(session, &mut writer, table, dialect, opts).await?;
+    }
+    write_dump_footer(&mut writer, dialect, opts)?;
+    writer
+        .finish()
+        .with_context(|| format!("Failed to write '{}'", file))
+}
+
+/// Add the indexes and foreign keys held back from `table`'s structure
+fn write_post_data(writer: &mut dyn Write, table: &str, statements: &[String]) -> Result<()> {
+    if statements.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer)?;
+    writeln!(writer, "-- Indexes and foreign keys for {}", table)?;
+    for statement in statements {
+        writeln!(writer, "{};", statement.trim_end_matches(';'))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// How a chunked read of a table's data keeps the state file of a directory
+/// dump up to date, and where an interrupted one left off
+struct DataResume<'a> {
+    state: &'a StateFile,
+    /// Flushes the data file before each checkpoint
+    syncer: Syncer,
+    /// The checkpoint to carry on from; the file already holds everything
+    /// before it
+    from: Option<Checkpoint>,
+}
+
+async fn dump_data(
+    session: &mut dyn DbSession,
+    writer: &mut dyn Write,
+    table: &str,
+    dialect: &dyn SqlDialect,
+    opts: &DumpOptions,
+    progress: Option<&MultiProgress>,
+    mut resume: Option<DataResume<'_>>,
+) -> Result<u64> {
+    let from = resume.as_mut().and_then(|resume| resume.from.take());
+
+    // Explicit identity values are rejected unless IDENTITY_INSERT is on
+    let identity_insert = dialect.name() == "SQL Server";
+    if from.is_none() {
         writeln!(writer)?;
         writeln!(writer, "-- Data for table `{}`", table)?;
+        if identity_insert {
+            writeln!(writer, "{}", identity_insert_statement(table, true))?;
+        }
+    }
 
-        // Get approximate row count for progress
-        let approx_count = session.approximate_row_count(table).await?;
+    // Get approximate row count for progress
+    let approx_count = opts
+        .subset
+        .expected_rows(session.approximate_row_count(table).await?);
 
-        // Create progress bar
-        let pb = if approx_count > 0 {
+    // Create progress bar
+    let pb = match progress {
+        Some(progress) => {
+            let pb = progress.add(ProgressBar::new(approx_count));
+            pb.set_style(
+                ProgressStyle::default_bar()
+                    .template("{spinner:.green} {prefix:20!} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} rows ({per_sec}) {msg}")
+                    .unwrap()
+                    .progress_chars("#>-"),
+            );
+            pb.set_prefix(table.to_string());
+            Some(pb)
+        }
+        None if approx_count > 0 => {
             let pb = ProgressBar::new(approx_count);
             pb.set_style(
                 ProgressStyle::default_bar()
@@ -124,75 +1511,243 @@ async fn dump_table(
                     .progress_chars("#>-"),
             );
             Some(pb)
+        }
+        None => None,
+    };
+
+    // --replace names the primary key where the dialect updates rows by it
+    let key = if opts.on_conflict == OnConflict::Replace {
+        session.primary_key(table).await?
+    } else {
+        Vec::new()
+    };
+
+    // The dump is most likely restored into the same kind of server, so
+    // its packet limit bounds the statements written
+    let limit = BatchLimit::new(
+        if opts.extended_insert {
+            opts.batch_rows
         } else {
-            None
-        };
+            1
+        },
+        opts.batch_bytes,
+        session.max_statement_bytes(),
+    );
 
-        // Stream rows
-        let (columns, mut row_stream) = session.stream_rows(table).await?;
+    // Stream rows
+    let KeysetRows {
+        columns,
+        key_positions,
+        rows: row_stream,
+    } = stream_rows_after(
+        &mut *session,
+        table,
+        opts.subset.chunk_rows(opts.chunk_rows),
+        from.as_ref().map(|from| from.after.clone()),
+    )
+    .await?;
+    let mut row_stream = opts.subset.apply(row_stream);
+    let conflict = match opts.on_conflict {
+        OnConflict::Error => None,
+        on_conflict => Some(conflict_clauses_for(
+            dialect,
+            table,
+            on_conflict,
+            &columns,
+            &key,
+        )?),
+    };
+    // An empty column list leaves it out of the INSERTs
+    let listed: &[String] = if opts.complete_insert { &columns } else { &[] };
+    let write_batch = |writer: &mut dyn Write, batch: &[Vec<SqlValue>]| match opts.format {
+        DumpFormat::Binary => Ok(binary::write_rows(writer, table, &columns, batch)?),
+        _ => write_insert_batch(writer, table, dialect, listed, batch, conflict.as_ref()),
+    };
 
-        let mut batch: Vec<Vec<SqlValue>> = Vec::with_capacity(opts.batch_rows);
-        let mut total_rows = 0u64;
+    let mut batch: Vec<Vec<SqlValue>> = Vec::with_capacity(limit.rows);
+    let mut batch_bytes = 0usize;
+    let mut total_rows = from.map_or(0, |from| from.rows);
+    let mut checkpointed = Instant::now();
 
-        while let Some(row_result) = row_stream.next().await {
-            let row = row_result?;
-            batch.push(row);
+    while let Some(row_result) = row_stream.next().await {
+        let row = row_result?;
+        let row_bytes = row_sql_len(&row);
+        opts.throttle.consume(1, row_bytes).await;
+        batch_bytes += row_bytes;
+        batch.push(row);
 
-            // Write batch when full
-            if batch.len() >= opts.batch_rows {
-                write_insert_batch(writer, table, dialect, &columns, &batch)?;
-                total_rows += batch.len() as u64;
+        // Write batch when full
+        if limit.is_full(batch.len(), batch_bytes) {
+            write_batch(writer, &batch)?;
+            total_rows += batch.len() as u64;
 
-                if let Some(pb) = &pb {
-                    pb.set_position(total_rows);
-                }
+            if let Some(pb) = &pb {
+                pb.set_position(total_rows);
+            }
 
-                batch.clear();
+            // Rows come in key order, so the last one written marks how far
+            // the read has got
+            if let (Some(resume), Some(positions)) = (&resume, &key_positions) {
+                if checkpointed.elapsed() >= CHECKPOINT_INTERVAL {
+                    let last = &batch[batch.len() - 1];
+                    writer.flush()?;
+                    let checkpoint = Checkpoint {
+                        rows: total_rows,
+                        bytes: resume.syncer.sync()?,
+                        after: positions.iter().map(|&i| last[i].clone()).collect(),
+                    };
+                    resume.state.record_checkpoint(table, checkpoint)?;
+                    checkpointed = Instant::now();
+                }
             }
-        }
 
-        // Write remaining rows
-        if !batch.is_empty() {
-            write_insert_batch(writer, table, dialect, &columns, &batch)?;
-            total_rows += batch.len() as u64;
+            batch.clear();
+            batch_bytes = 0;
         }
+    }
 
-        if let Some(pb) = &pb {
-            pb.finish_with_message(format!("Dumped {} rows", total_rows));
-        } else {
-            println!("  Dumped {} rows", total_rows);
+    // Write remaining rows
+    if !batch.is_empty() {
+        write_batch(writer, &batch)?;
+        total_rows += batch.len() as u64;
+    }
+
+    if identity_insert {
+        writeln!(writer, "{}", identity_insert_statement(table, false))?;
+    }
+
+    if let Some(pb) = &pb {
+        pb.finish_with_message(format!("Dumped {} rows", total_rows));
+    } else {
+        eprintln!("  Dumped {} rows", total_rows);
+    }
+
+    writer.flush()?;
+
+    Ok(total_rows)
+}
+
+/// Order views so that each follows the views its definition mentions.
+/// Mentions are found by name, so a false match only costs ordering.
+fn order_views(views: Vec<(String, String)>) -> Vec<(String, String)> {
+    let names: Vec<String> = views
+        .iter()
+        .map(|(view, _)| split_table_name(view).1.to_lowercase())
+        .collect();
+    let definitions: Vec<String> = views.iter().map(|(_, sql)| sql.to_lowercase()).collect();
+    let depends = |i: usize, j: usize| i != j && mentions(&definitions[i], &names[j]);
+
+    // Depth-first, placing a view after everything it depends on; a cycle
+    // (only possible through false matches) is cut where it closes
+    fn visit(
+        i: usize,
+        count: usize,
+        depends: &dyn Fn(usize, usize) -> bool,
+        state: &mut [u8],
+        order: &mut Vec<usize>,
+    ) {
+        if state[i] != 0 {
+            return;
         }
+        state[i] = 1;
+        for j in 0..count {
+            if depends(i, j) {
+                visit(j, count, depends, state, order);
+            }
+        }
+        state[i] = 2;
+        order.push(i);
+    }
 
-        writer.flush()?;
+    let mut state = vec![0u8; views.len()];
+    let mut order = Vec::with_capacity(views.len());
+    for i in 0..views.len() {
+        visit(i, views.len(), &depends, &mut state, &mut order);
     }
 
+    let mut views: Vec<Option<(String, String)>> = views.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|i| views[i].take()).collect()
+}
+
+/// Whether `sql` contains `name` as a whole identifier
+fn mentions(sql: &str, name: &str) -> bool {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+    sql.match_indices(name).any(|(at, _)| {
+        let before = sql[..at].chars().next_back();
+        let after = sql[at + name.len()..].chars().next();
+        !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+    })
+}
+
+/// Drop the views of `views`, dependents first
+fn write_view_drops(
+    writer: &mut dyn Write,
+    views: &[(String, String)],
+    dialect: &dyn SqlDialect,
+) -> Result<()> {
+    if views.is_empty() {
+        return Ok(());
+    }
+    writeln!(writer, "-- Views, recreated after the tables")?;
+    for (view, _) in views.iter().rev() {
+        writeln!(writer, "{};", dialect.drop_view_statement(view))?;
+    }
+    Ok(())
+}
+
+/// Create the views of `views`, in order
+fn write_views(writer: &mut dyn Write, views: &[(String, String)]) -> Result<()> {
+    for (view, create) in views {
+        writeln!(writer)?;
+        writeln!(writer, "-- View structure for {}", view)?;
+        writeln!(writer, "{};", create.trim_end_matches(';'))?;
+    }
+    writer.flush()?;
     Ok(())
 }
 
 fn write_insert_batch(
-    writer: &mut Box<dyn Write>,
+    writer: &mut dyn Write,
     table: &str,
     dialect: &dyn SqlDialect,
     columns: &[String],
     rows: &[Vec<SqlValue>],
+    conflict: Option<&(&str, String)>,
 ) -> Result<()> {
     if rows.is_empty() {
         return Ok(());
     }
 
-    let sql = dialect.insert_values_sql(table, columns, rows);
+    let mut sql = dialect.insert_values_sql(table, columns, rows);
+    if let Some((opening, clause)) = conflict {
+        if let Some(rest) = sql.strip_prefix("INSERT INTO") {
+            sql = format!("{}{}{};", opening, rest.trim_end_matches(';'), clause);
+        }
+    }
     writeln!(writer, "{}", sql)?;
 
     Ok(())
 }
 
-fn write_dump_header(writer: &mut Box<dyn Write>, dialect: &dyn SqlDialect) -> Result<()> {
+fn write_dump_header(
+    writer: &mut dyn Write,
+    dialect: &dyn SqlDialect,
+    opts: &DumpOptions,
+) -> Result<()> {
     writeln!(writer, "-- {} Database Dump", dialect.name())?;
     writeln!(writer, "-- Generated by migrasquiel")?;
-    writeln!(writer, "-- Date: {}", chrono::Utc::now().to_rfc3339())?;
+    if !opts.reproducible {
+        writeln!(writer, "-- Date: {}", chrono::Utc::now().to_rfc3339())?;
+    }
     writeln!(writer)?;
 
     match dialect.name() {
+        // Without the versioned comments other MySQL-like servers may skip
+        "MySQL" if opts.compatible.is_some() => {
+            writeln!(writer, "SET NAMES utf8mb4;")?;
+            writeln!(writer, "SET FOREIGN_KEY_CHECKS = 0;")?;
+            writeln!(writer, "SET UNIQUE_CHECKS = 0;")?;
+        }
         "MySQL" => {
             writeln!(
                 writer,
@@ -220,6 +1775,15 @@ fn write_dump_header(writer: &mut Box<dyn Write>, dialect: &dyn SqlDialect) -> R
         "PostgreSQL" => {
             writeln!(writer, "SET client_encoding = 'UTF8';")?;
             writeln!(writer, "SET standard_conforming_strings = on;")?;
+            // Function bodies may use tables created further on
+            writeln!(writer, "SET check_function_bodies = false;")?;
+        }
+        "SQL Server" => {
+            writeln!(writer, "SET NOCOUNT ON;")?;
+            writeln!(writer, "SET XACT_ABORT ON;")?;
+        }
+        "SQLite" => {
+            writeln!(writer, "PRAGMA foreign_keys=OFF;")?;
         }
         _ => {}
     }
@@ -229,9 +1793,41 @@ fn write_dump_header(writer: &mut Box<dyn Write>, dialect: &dyn SqlDialect) -> R
     Ok(())
 }
 
-fn write_dump_footer(writer: &mut Box<dyn Write>, dialect: &dyn SqlDialect) -> Result<()> {
+/// Comment lines recording where a replica loaded from the dump carries on
+fn write_replication_position(
+    writer: &mut dyn Write,
+    position: &ReplicationPosition,
+) -> Result<()> {
+    writeln!(writer, "-- Replication position at the snapshot")?;
+    match position {
+        ReplicationPosition::Binlog {
+            file,
+            position,
+            gtid_set,
+        } => {
+            writeln!(writer, "-- Binlog file: {}", file)?;
+            writeln!(writer, "-- Binlog position: {}", position)?;
+            if let Some(gtid_set) = gtid_set {
+                writeln!(writer, "-- GTID set: {}", gtid_set)?;
+            }
+        }
+        ReplicationPosition::Wal { lsn } => writeln!(writer, "-- WAL LSN: {}", lsn)?,
+    }
+    writeln!(writer)?;
+    Ok(())
+}
+
+fn write_dump_footer(
+    writer: &mut dyn Write,
+    dialect: &dyn SqlDialect,
+    opts: &DumpOptions,
+) -> Result<()> {
     writeln!(writer)?;
     match dialect.name() {
+        "MySQL" if opts.compatible.is_some() => {
+            writeln!(writer, "SET UNIQUE_CHECKS = 1;")?;
+            writeln!(writer, "SET FOREIGN_KEY_CHECKS = 1;")?;
+        }
         "MySQL" => {
             writeln!(writer, "/*!40101 SET SQL_MODE=@OLD_SQL_MODE */;")?;
             writeln!(
@@ -255,8 +1851,48 @@ fn write_dump_footer(writer: &mut Box<dyn Write>, dialect: &dyn SqlDialect) -> R
         "PostgreSQL" => {
             writeln!(writer, "RESET ALL;")?;
         }
+        "SQL Server" => {
+            writeln!(writer, "SET NOCOUNT OFF;")?;
+        }
+        "SQLite" => {
+            writeln!(writer, "PRAGMA foreign_keys=ON;")?;
+        }
         _ => {}
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_views_after_the_views_they_select_from() {
+        let views = vec![
+            ("public.totals", "SELECT sum(n) FROM recent_orders"),
+            ("public.recent_orders", "SELECT * FROM orders_2024"),
+            ("public.orders_2024", "SELECT * FROM orders"),
+            ("public.orders", "SELECT * FROM raw_orders"),
+        ];
+        let views = views
+            .into_iter()
+            .map(|(view, select)| {
+                (
+                    view.to_string(),
+                    format!("CREATE VIEW {} AS {}", view, select),
+                )
+            })
+            .collect();
+        let ordered: Vec<String> = order_views(views).into_iter().map(|(v, _)| v).collect();
+        assert_eq!(
+            ordered,
+            [
+                "public.orders",
+                "public.orders_2024",
+                "public.recent_orders",
+                "public.totals"
+            ]
+        );
+    }
+}
@@ -0,0 +1,428 @@
+use super::cdc::{Change, ChangeStream, RowImage};
+use super::ddl::{enum_labels, Column, ColumnType};
+use super::filter::TableFilter;
+use super::value::SqlValue;
+use super::{DbSession, ReplicationPosition};
+use crate::util::url::{database_of, decode_component};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use futures::StreamExt;
+use mysql_async::binlog::events::{Event, EventData};
+use mysql_async::binlog::row::BinlogRow;
+use mysql_async::binlog::value::BinlogValue;
+use mysql_async::{BinlogStream, BinlogStreamRequest, OptsBuilder, SslOpts, Value};
+use std::collections::{HashMap, VecDeque};
+
+/// Row changes read from a MySQL server's binary log, as a replica reads
+/// them. Needs `binlog_format = ROW` and the REPLICATION SLAVE and
+/// REPLICATION CLIENT privileges.
+pub struct BinlogChanges {
+    stream: BinlogStream,
+    /// Session on the source the names and types of columns come from,
+    /// which the log leaves out
+    session: Box<dyn DbSession>,
+    database: String,
+    filter: TableFilter,
+    /// The log being read, and where the event after the last one ends
+    file: String,
+    position: u64,
+    columns: HashMap<String, Vec<Column>>,
+    pending: VecDeque<Change>,
+}
+
+impl BinlogChanges {
+    /// Read the log of the server `url` points at from `from`, keeping the
+    /// changes to tables of its database that `filter` matches
+    pub async fn open(
+        url: &str,
+        session: Box<dyn DbSession>,
+        from: &ReplicationPosition,
+        server_id: u32,
+        filter: TableFilter,
+    ) -> Result<Self> {
+        let ReplicationPosition::Binlog { file, position, .. } = from else {
+            bail!("'{}' is not a binary log position", from);
+        };
+        let database = database_of(url)?
+            .ok_or_else(|| anyhow!("The source URL names no database to replicate"))?;
+        let conn = mysql_async::Conn::new(binlog_opts(url)?)
+            .await
+            .context("Failed to connect to MySQL to read the binary log")?;
+        let request = BinlogStreamRequest::new(server_id)
+            .with_filename(file.as_bytes())
+            .with_pos(*position);
+        let stream = conn
+            .get_binlog_stream(request)
+            .await
+            .context("Failed to read the binary log (needs the REPLICATION SLAVE privilege)")?;
+
+        Ok(Self {
+            stream,
+            session,
+            database,
+            filter,
+            file: file.clone(),
+            position: *position,
+            columns: HashMap::new(),
+            pending: VecDeque::new(),
+        })
+    }
+
+    fn commit(&mut self) {
+        self.pending
+            .push_back(Change::Commit(ReplicationPosition::Binlog {
+                file: self.file.clone(),
+                position: self.position,
+                gtid_set: None,
+            }));
+    }
+
+    async fn read_event(&mut self, event: Event) -> Result<()> {
+        let Some(data) = event
+            .read_data()
+            .context("Failed to decode a binary log event")?
+        else {
+            return Ok(());
+        };
+        if let EventData::RotateEvent(rotate) = &data {
+            self.file = rotate.name().into_owned();
+            self.position = rotate.position();
+            return Ok(());
+        }
+        // Events the server makes up, such as heartbeats, end nowhere
+        let end = u64::from(event.header().log_pos());
+        if end > 0 {
+            self.position = end;
+        }
+
+        match data {
+            EventData::XidEvent(_) => self.commit(),
+            EventData::QueryEvent(query) => match query.query().trim() {
+                "BEGIN" => {}
+                // Tables without transactions, such as MyISAM ones
+                "COMMIT" => self.commit(),
+                sql => {
+                    if query.schema() == self.database {
+                        // Columns are looked up again after the change
+                        self.columns.clear();
+                        self.pending.push_back(Change::Schema(sql.to_string()));
+                    }
+                    self.commit();
+                }
+            },
+            EventData::RowsEvent(rows) => {
+                let tme = self
+                    .stream
+                    .get_tme(rows.table_id())
+                    .ok_or_else(|| anyhow!("Rows event for a table the log never mapped"))?
+                    .clone();
+                let table = tme.table_name().into_owned();
+                if tme.database_name() != self.database || !self.filter.matches(&table) {
+                    return Ok(());
+                }
+                let mut columns = self.columns_of(&table).await?;
+                for row in rows.rows(&tme) {
+                    let (before, after) = row.context("Failed to decode a binary log row")?;
+                    let width = before.as_ref().or(after.as_ref()).map_or(0, |r| r.len());
+                    if width != columns.len() {
+                        self.columns.remove(&table);
+                        columns = self.columns_of(&table).await?;
+                        if width != columns.len() {
+                            bail!(
+                                "'{}' has {} columns in the binary log but {} on the server; \
+                                 was it altered since?",
+                                table,
+                                width,
+                                columns.len()
+                            );
+                        }
+                    }
+                    let before = before.map(|r| row_image(r, &columns)).transpose()?;
+                    let after = after.map(|r| row_image(r, &columns)).transpose()?;
+                    let table = table.clone();
+                    self.pending.push_back(match (before, after) {
+                        (None, Some(row)) => Change::Insert { table, row },
+                        (Some(before), Some(after)) => Change::Update {
+                            table,
+                            before,
+                            after,
+                        },
+                        (Some(row), None) => Change::Delete { table, row },
+                        (None, None) => continue,
+                    });
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn columns_of(&mut self, table: &str) -> Result<Vec<Column>> {
+        if let Some(columns) = self.columns.get(table) {
+            return Ok(columns.clone());
+        }
+        let columns = self.session.describe_columns(table).await?;
+        self.columns.insert(table.to_string(), columns.clone());
+        Ok(columns)
+    }
+}
+
+#[async_trait]
+impl ChangeStream for BinlogChanges {
+    async fn next_change(&mut self) -> Result<Change> {
+        loop {
+            if let Some(change) = self.pending.pop_front() {
+                return Ok(change);
+            }
+            let event = self
+                .stream
+                .next()
+                .await
+                .ok_or_else(|| anyhow!("The server closed the binary log stream"))?
+                .context("Failed to read the binary log")?;
+            self.read_event(event).await?;
+        }
+    }
+}
+
+/// Connection options for the log reader, which speaks the replication
+/// protocol sqlx does not
+fn binlog_opts(url: &str) -> Result<mysql_async::Opts> {
+    let parsed = url::Url::parse(url).context("Invalid connection URL")?;
+    let mut builder = OptsBuilder::default()
+        .ip_or_hostname(parsed.host_str().unwrap_or("localhost"))
+        .tcp_port(parsed.port().unwrap_or(3306))
+        .user(Some(decode_component(parsed.username())?))
+        .pass(parsed.password().map(decode_component).transpose()?);
+    let ssl_mode = parsed
+        .query_pairs()
+        .find(|(key, _)| key == "ssl-mode" || key == "sslmode")
+        .map(|(_, mode)| mode.to_ascii_lowercase());
+    match ssl_mode.as_deref() {
+        Some("required") => {
+            builder = builder.ssl_opts(SslOpts::default().with_danger_accept_invalid_certs(true))
+        }
+        Some("verify_ca" | "verify_identity") => builder = builder.ssl_opts(SslOpts::default()),
+        _ => {}
+    }
+    Ok(builder.into())
+}
+
+/// The columns `row` holds; an image may leave some out
+fn row_image(mut row: BinlogRow, columns: &[Column]) -> Result<RowImage> {
+    let mut image = RowImage {
+        columns: Vec::new(),
+        values: Vec::new(),
+    };
+    for (index, column) in columns.iter().enumerate() {
+        let Some(value) = row.take(index) else {
+            continue;
+        };
+        let value = match value {
+            BinlogValue::Value(value) => convert_value(value, column),
+            BinlogValue::Jsonb(json) => {
+                let json: serde_json::Value = json
+                    .parse()
+                    .with_context(|| format!("Invalid JSON in column '{}'", column.name))?
+                    .into();
+                SqlValue::String(json.to_string())
+            }
+            BinlogValue::JsonDiff(_) => bail!(
+                "Partial JSON updates in the binary log are not supported; \
+                 set binlog_row_value_options to ''"
+            ),
+        };
+        image.columns.push(column.name.clone());
+        image.values.push(value);
+    }
+    Ok(image)
+}
+
+/// A value as the binary log encodes it, read by the column's type
+fn convert_value(value: Value, column: &Column) -> SqlValue {
+    let declared = column.declared.to_ascii_lowercase();
+    match (value, &column.column_type) {
+        (Value::NULL, _) => SqlValue::Null,
+        (Value::Int(index), ColumnType::Enum(labels)) => {
+            // Index 0 is the '' stored for an invalid value
+            let label = usize::try_from(index - 1).ok().and_then(|i| labels.get(i));
+            SqlValue::String(label.cloned().unwrap_or_default())
+        }
+        // Without the full row metadata the log does not say a column is
+        // unsigned, so large values come back negative
+        (Value::Int(n), _) if n < 0 && declared.contains("unsigned") => {
+            let bits = match declared.split(['(', ' ']).next() {
+                Some("tinyint") => 8,
+                Some("smallint") => 16,
+                Some("mediumint") => 24,
+                Some("int" | "integer") => 32,
+                _ => 64,
+            };
+            let n = (n as u64) & (u64::MAX >> (64 - bits));
+            i64::try_from(n).map_or_else(|_| SqlValue::Decimal(n.to_string()), SqlValue::Int)
+        }
+        (Value::Int(n), _) => SqlValue::Int(n),
+        (Value::UInt(n), _) => {
+            i64::try_from(n).map_or_else(|_| SqlValue::Decimal(n.to_string()), SqlValue::Int)
+        }
+        (Value::Float(n), _) => SqlValue::Float(n.into()),
+        (Value::Double(n), _) => SqlValue::Float(n),
+        (Value::Date(y, m, d, ..), ColumnType::Date) => SqlValue::Date {
+            y: y.into(),
+            m: m.into(),
+            d: d.into(),
+        },
+        (Value::Date(y, m, d, hh, mm, ss, us), _) => SqlValue::Timestamp {
+            y: y.into(),
+            m: m.into(),
+            d: d.into(),
+            hh: hh.into(),
+            mm: mm.into(),
+            ss: ss.into(),
+            us,
+        },
+        (Value::Time(neg, days, h, m, s, us), _) => SqlValue::Time {
+            neg,
+            h: days * 24 + u32::from(h),
+            m: m.into(),
+            s: s.into(),
+            us,
+        },
+        (Value::Bytes(bytes), column_type) => match column_type {
+            ColumnType::Decimal(_) => SqlValue::Decimal(String::from_utf8_lossy(&bytes).into()),
+            ColumnType::Bool => SqlValue::Bool(bytes.iter().any(|b| *b != 0)),
+            ColumnType::Bytes => SqlValue::Bytes(bytes),
+            // TIMESTAMP is logged as seconds since the epoch, in UTC
+            _ if declared.starts_with("timestamp") => {
+                epoch_timestamp(&String::from_utf8_lossy(&bytes)).unwrap_or(SqlValue::Bytes(bytes))
+            }
+            _ if declared.starts_with("set(") => {
+                let args = &column.declared[4..column.declared.rfind(')').unwrap_or(4).max(4)];
+                let mut mask = bytes
+                    .iter()
+                    .rev()
+                    .fold(0u64, |mask, byte| mask << 8 | u64::from(*byte));
+                let mut chosen = Vec::new();
+                for label in enum_labels(args) {
+                    if mask & 1 == 1 {
+                        chosen.push(label);
+                    }
+                    mask >>= 1;
+                }
+                SqlValue::String(chosen.join(","))
+            }
+            _ => String::from_utf8(bytes)
+                .map_or_else(|e| SqlValue::Bytes(e.into_bytes()), SqlValue::String),
+        },
+    }
+}
+
+/// `seconds[.fraction]` since the epoch as a UTC timestamp; 0 is the zero
+/// date
+fn epoch_timestamp(text: &str) -> Option<SqlValue> {
+    let (seconds, fraction) = text.split_once('.').unwrap_or((text, ""));
+    let seconds: i64 = seconds.parse().ok()?;
+    let us: u32 = format!("{:0<6}", fraction).get(..6)?.parse().ok()?;
+    if seconds == 0 && us == 0 {
+        return Some(SqlValue::Timestamp {
+            y: 0,
+            m: 0,
+            d: 0,
+            hh: 0,
+            mm: 0,
+            ss: 0,
+            us: 0,
+        });
+    }
+    let at = chrono::DateTime::from_timestamp(seconds, us * 1000)?;
+    Some(SqlValue::from_datetime(at.naive_utc()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(declared: &str) -> Column {
+        Column {
+            name: "c".into(),
+            column_type: ColumnType::parse(declared),
+            declared: declared.into(),
+            nullable: true,
+            auto_increment: false,
+        }
+    }
+
+    #[test]
+    fn converts_logged_values_by_column_type() {
+        let cases = [
+            (
+                Value::Int(2),
+                "enum('new','done')",
+                SqlValue::String("done".into()),
+            ),
+            (Value::Int(-1), "int unsigned", SqlValue::Int(4_294_967_295)),
+            (
+                Value::Int(-1),
+                "bigint unsigned",
+                SqlValue::Decimal("18446744073709551615".into()),
+            ),
+            (
+                Value::Bytes(b"12.50".to_vec()),
+                "decimal(10,2)",
+                SqlValue::Decimal("12.50".into()),
+            ),
+            (Value::Bytes(vec![1]), "bit(1)", SqlValue::Bool(true)),
+            (
+                Value::Bytes(vec![0b101]),
+                "set('a','b','c')",
+                SqlValue::String("a,c".into()),
+            ),
+            (
+                Value::Bytes(b"86400.5".to_vec()),
+                "timestamp(3)",
+                SqlValue::Timestamp {
+                    y: 1970,
+                    m: 1,
+                    d: 2,
+                    hh: 0,
+                    mm: 0,
+                    ss: 0,
+                    us: 500_000,
+                },
+            ),
+            (
+                Value::Date(2024, 2, 29, 0, 0, 0, 0),
+                "date",
+                SqlValue::Date {
+                    y: 2024,
+                    m: 2,
+                    d: 29,
+                },
+            ),
+            (
+                Value::Time(true, 1, 2, 3, 4, 0),
+                "time",
+                SqlValue::Time {
+                    neg: true,
+                    h: 26,
+                    m: 3,
+                    s: 4,
+                    us: 0,
+                },
+            ),
+            (
+                Value::Bytes(b"hi".to_vec()),
+                "varchar(10)",
+                SqlValue::String("hi".into()),
+            ),
+        ];
+        for (value, declared, expected) in cases {
+            assert_eq!(
+                convert_value(value, &column(declared)),
+                expected,
+                "{}",
+                declared
+            );
+        }
+    }
+}
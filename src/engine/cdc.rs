@@ -0,0 +1,54 @@
+use super::value::SqlValue;
+use super::ReplicationPosition;
+use anyhow::Result;
+use async_trait::async_trait;
+
+/// Some or all of a row's columns, as a change log records them
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowImage {
+    pub columns: Vec<String>,
+    pub values: Vec<SqlValue>,
+}
+
+impl RowImage {
+    /// The values of `key`, or `None` when the image leaves one out
+    pub fn values_of(&self, key: &[String]) -> Option<Vec<SqlValue>> {
+        key.iter()
+            .map(|k| {
+                let index = self.columns.iter().position(|c| c == k)?;
+                Some(self.values[index].clone())
+            })
+            .collect()
+    }
+}
+
+/// One change read from a source's log, in the order it was committed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Insert {
+        table: String,
+        row: RowImage,
+    },
+    /// `before` holds at least the primary key; `after` the columns set
+    Update {
+        table: String,
+        before: RowImage,
+        after: RowImage,
+    },
+    Delete {
+        table: String,
+        row: RowImage,
+    },
+    /// A statement changing the schema, or a TRUNCATE, which is not applied
+    Schema(String),
+    /// The end of a transaction; what came before it is durable at `position`
+    Commit(ReplicationPosition),
+}
+
+/// A source's stream of committed changes, read from where a snapshot was
+/// taken onwards
+#[async_trait]
+pub trait ChangeStream: Send {
+    /// The next change, waiting until one is committed
+    async fn next_change(&mut self) -> Result<Change>;
+}
@@ -0,0 +1,379 @@
+use super::dialect::SqlDialect;
+use super::{DbEngine, DbSession};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use aws_lc_rs::encoding::{AsDer, Pkcs8V1Der, PublicKeyX509Der};
+use aws_lc_rs::rsa::{KeyPair, KeySize};
+use aws_lc_rs::signature::KeyPair as _;
+use base64::Engine as _;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use serde_json::{json, Value};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsConnector;
+
+const ADMIN_API: &str = "https://sqladmin.googleapis.com/sql/v1beta4";
+const ADMIN_SCOPE: &str = "https://www.googleapis.com/auth/sqlservice.admin";
+
+/// Cloud SQL instances accept connector traffic on this port only
+const SERVER_PORT: u16 = 3307;
+
+/// Wraps a MySQL or PostgreSQL engine and reaches a Cloud SQL instance through
+/// an in-process TLS tunnel, the same way the Cloud SQL Auth Proxy does.
+pub struct CloudSqlEngine {
+    inner: Box<dyn DbEngine>,
+    instance: InstanceName,
+}
+
+impl CloudSqlEngine {
+    pub fn new(inner: Box<dyn DbEngine>, instance: &str) -> Result<Self> {
+        Ok(Self {
+            inner,
+            instance: InstanceName::parse(instance)?,
+        })
+    }
+}
+
+#[async_trait]
+impl DbEngine for CloudSqlEngine {
+    async fn connect(&self, url: &str) -> Result<Box<dyn DbSession>> {
+        let mut parsed = url::Url::parse(url).context("Invalid connection URL")?;
+        let (tls_param, tls_value) = match parsed.scheme() {
+            "mysql" | "mariadb" => ("ssl-mode", "DISABLED"),
+            "postgres" | "postgresql" => ("sslmode", "disable"),
+            other => bail!(
+                "Cloud SQL connections are not supported for {}:// URLs",
+                other
+            ),
+        };
+
+        // Ephemeral certificates only last an hour, so each connection gets a
+        // fresh one along with its own tunnel
+        let port = open_tunnel(&self.instance)
+            .await
+            .with_context(|| format!("Failed to open a tunnel to {}", self.instance))?;
+
+        parsed
+            .set_host(Some("127.0.0.1"))
+            .map_err(|e| anyhow!("Cannot rewrite connection URL host: {}", e))?;
+        parsed
+            .set_port(Some(port))
+            .map_err(|_| anyhow!("Cannot rewrite connection URL port"))?;
+
+        // The tunnel is already encrypted end to end
+        let pairs: Vec<(String, String)> = parsed
+            .query_pairs()
+            .filter(|(k, _)| k != tls_param && k != "host")
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+        parsed
+            .query_pairs_mut()
+            .clear()
+            .extend_pairs(pairs)
+            .append_pair(tls_param, tls_value);
+
+        self.inner.connect(parsed.as_str()).await
+    }
+
+    fn dialect(&self) -> Option<&'static dyn SqlDialect> {
+        self.inner.dialect()
+    }
+}
+
+/// `project:region:instance`; legacy domain-scoped projects contain a colon too
+#[derive(Debug, Clone, PartialEq)]
+struct InstanceName {
+    project: String,
+    region: String,
+    name: String,
+}
+
+impl InstanceName {
+    fn parse(value: &str) -> Result<Self> {
+        let mut parts = value.rsplitn(3, ':');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(name), Some(region), Some(project))
+                if !name.is_empty() && !region.is_empty() && !project.is_empty() =>
+            {
+                Ok(Self {
+                    project: project.to_string(),
+                    region: region.to_string(),
+                    name: name.to_string(),
+                })
+            }
+            _ => bail!(
+                "Invalid Cloud SQL instance '{}' (expected project:region:instance)",
+                value
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for InstanceName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.project, self.region, self.name)
+    }
+}
+
+/// Listen on a loopback port and forward the first connection to the instance
+async fn open_tunnel(instance: &InstanceName) -> Result<u16> {
+    let token = gcp_auth::provider()
+        .await
+        .context("No Google Cloud credentials found")?
+        .token(&[ADMIN_SCOPE])
+        .await
+        .context("Failed to obtain a Google Cloud access token")?;
+    let client = reqwest::Client::new();
+    let base = format!(
+        "{}/projects/{}/instances/{}",
+        ADMIN_API, instance.project, instance.name
+    );
+
+    let settings = admin_request(
+        client
+            .get(format!("{}/connectSettings", base))
+            .bearer_auth(token.as_str()),
+    )
+    .await?;
+    if let Some(region) = settings.get("region").and_then(Value::as_str) {
+        if region != instance.region {
+            bail!(
+                "Instance {} is in region {}, not {}",
+                instance.name,
+                region,
+                instance.region
+            );
+        }
+    }
+
+    let address = instance_address(&settings)?;
+    let server_ca = settings
+        .pointer("/serverCaCert/cert")
+        .and_then(Value::as_str)
+        .context("Cloud SQL did not return a server CA certificate")?;
+
+    let key = KeyPair::generate(KeySize::Rsa2048)
+        .map_err(|_| anyhow!("Failed to generate an RSA key pair"))?;
+    let public_key = key
+        .public_key()
+        .as_der()
+        .map_err(|_| anyhow!("Failed to encode the RSA public key"))?;
+    let private_key: Pkcs8V1Der = key
+        .as_der()
+        .map_err(|_| anyhow!("Failed to encode the RSA private key"))?;
+
+    let ephemeral = admin_request(
+        client
+            .post(format!("{}:generateEphemeralCert", base))
+            .bearer_auth(token.as_str())
+            .header("Content-Type", "application/json")
+            .body(json!({ "public_key": public_key_pem(&public_key) }).to_string()),
+    )
+    .await?;
+    let client_cert = ephemeral
+        .pointer("/ephemeralCert/cert")
+        .and_then(Value::as_str)
+        .context("Cloud SQL did not return an ephemeral certificate")?;
+
+    let connector = tls_connector(
+        server_ca,
+        client_cert,
+        PrivateKeyDer::Pkcs8(private_key.as_ref().to_vec().into()),
+    )?;
+    let server_name = match settings.get("dnsName").and_then(Value::as_str) {
+        Some(dns) if !dns.is_empty() => ServerName::try_from(dns.trim_end_matches('.'))?.to_owned(),
+        _ => ServerName::IpAddress(address.into()),
+    };
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+    let port = listener.local_addr()?.port();
+
+    tokio::spawn(async move {
+        let result: Result<()> = async {
+            let (mut local, _) = listener.accept().await?;
+            let upstream = TcpStream::connect((address, SERVER_PORT)).await?;
+            let mut upstream = connector.connect(server_name, upstream).await?;
+            tokio::io::copy_bidirectional(&mut local, &mut upstream).await?;
+            Ok(())
+        }
+        .await;
+        if let Err(e) = result {
+            eprintln!("Cloud SQL tunnel closed: {}", e);
+        }
+    });
+
+    Ok(port)
+}
+
+async fn admin_request(request: reqwest::RequestBuilder) -> Result<Value> {
+    let response = request.header("User-Agent", "migrasquiel").send().await?;
+    let status = response.status();
+    let text = response.text().await?;
+    let value: Value = serde_json::from_str(&text).with_context(|| {
+        format!(
+            "Unexpected Cloud SQL Admin API response ({}): {}",
+            status, text
+        )
+    })?;
+
+    if !status.is_success() {
+        let message = value
+            .pointer("/error/message")
+            .and_then(Value::as_str)
+            .unwrap_or(text.as_str());
+        bail!("Cloud SQL Admin API returned {}: {}", status, message);
+    }
+
+    Ok(value)
+}
+
+/// Prefer the public address and fall back to the private one
+fn instance_address(settings: &Value) -> Result<IpAddr> {
+    let addresses = settings
+        .get("ipAddresses")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    ["PRIMARY", "PRIVATE"]
+        .iter()
+        .find_map(|kind| {
+            addresses
+                .iter()
+                .find(|a| a.get("type").and_then(Value::as_str) == Some(kind))
+                .and_then(|a| a.get("ipAddress").and_then(Value::as_str))
+        })
+        .context("Instance has no public or private IP address")?
+        .parse()
+        .context("Cloud SQL returned an invalid IP address")
+}
+
+fn public_key_pem(der: &PublicKeyX509Der) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(der.as_ref());
+    let mut pem = String::from("-----BEGIN PUBLIC KEY-----\n");
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).unwrap_or_default());
+        pem.push('\n');
+    }
+    pem.push_str("-----END PUBLIC KEY-----\n");
+    pem
+}
+
+fn tls_connector(
+    server_ca: &str,
+    client_cert: &str,
+    private_key: PrivateKeyDer<'static>,
+) -> Result<TlsConnector> {
+    let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+
+    let mut roots = RootCertStore::empty();
+    for cert in CertificateDer::pem_slice_iter(server_ca.as_bytes()) {
+        roots.add(cert?)?;
+    }
+    let verifier = InstanceVerifier(
+        WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone()).build()?,
+    );
+
+    let client_chain =
+        CertificateDer::pem_slice_iter(client_cert.as_bytes()).collect::<Result<Vec<_>, _>>()?;
+
+    let config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_client_auth_cert(client_chain, private_key)?;
+
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+/// Older instances present certificates named `project:instance` rather than a
+/// DNS name. The CA is unique to the instance, so a chain that validates
+/// against it is accepted even when the name does not match.
+#[derive(Debug)]
+struct InstanceVerifier(Arc<WebPkiServerVerifier>);
+
+impl ServerCertVerifier for InstanceVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        match self
+            .0
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+        {
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::NotValidForName | CertificateError::NotValidForNameContext { .. },
+            )) => Ok(ServerCertVerified::assertion()),
+            other => other,
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.0.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.0.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_instance_names() {
+        assert_eq!(
+            InstanceName::parse("my-project:europe-west1:db").unwrap(),
+            InstanceName {
+                project: "my-project".into(),
+                region: "europe-west1".into(),
+                name: "db".into(),
+            }
+        );
+        assert_eq!(
+            InstanceName::parse("example.com:my-project:us-central1:db")
+                .unwrap()
+                .project,
+            "example.com:my-project"
+        );
+        assert!(InstanceName::parse("my-project:db").is_err());
+    }
+
+    #[test]
+    fn picks_public_address_first() {
+        let settings = json!({
+            "ipAddresses": [
+                { "type": "PRIVATE", "ipAddress": "10.0.0.5" },
+                { "type": "PRIMARY", "ipAddress": "203.0.113.7" }
+            ]
+        });
+        assert_eq!(
+            instance_address(&settings).unwrap(),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+    }
+}
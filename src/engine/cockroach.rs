@@ -0,0 +1,402 @@
+use super::postgres::{parse_table_name, PostgresSession};
+use super::{DbEngine, DbSession, InsertMode, ReplicationPosition, Routine, RowStream};
+use crate::engine::ddl::Column;
+use crate::engine::dialect::{format_qualified_table, split_table_name, SqlDialect};
+use crate::engine::filter::TableFilter;
+use crate::engine::value::SqlValue;
+use crate::util::dialects::cockroach::COCKROACH_DIALECT;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use sqlx::postgres::PgConnection;
+use sqlx::{Connection, Row};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+/// Schemas CockroachDB exposes through pg_tables that hold no user data.
+const SYSTEM_SCHEMAS: &[&str] = &["crdb_internal", "pg_extension"];
+
+pub struct CockroachEngine;
+
+#[async_trait]
+impl DbEngine for CockroachEngine {
+    async fn connect(&self, url: &str) -> Result<Box<dyn DbSession>> {
+        let conn = PgConnection::connect(url)
+            .await
+            .context("Failed to connect to CockroachDB database")?;
+
+        Ok(Box::new(CockroachSession {
+            inner: PostgresSession::skipping_hidden(conn),
+        }))
+    }
+
+    fn dialect(&self) -> Option<&'static dyn SqlDialect> {
+        Some(&COCKROACH_DIALECT)
+    }
+}
+
+/// CockroachDB session reusing the Postgres wire layer, overriding the catalog
+/// queries whose behavior differs (DDL, hidden columns, statistics, constraints).
+/// Comments come with `SHOW CREATE TABLE`. Snapshots cannot be shared between
+/// connections, and there are no settings for fast loads or keepalives;
+/// dumping privileges, dropping indexes for a load and logical replication
+/// fail with an error.
+pub struct CockroachSession {
+    inner: PostgresSession,
+}
+
+#[async_trait]
+impl DbSession for CockroachSession {
+    fn dialect(&self) -> &'static dyn SqlDialect {
+        &COCKROACH_DIALECT
+    }
+
+    fn in_transaction(&self) -> bool {
+        self.inner.in_transaction
+    }
+
+    async fn start_consistent_snapshot(&mut self) -> Result<()> {
+        // Historical reads never conflict with concurrent writers, so long dumps are not
+        // aborted by transaction retries
+        sqlx::query("BEGIN AS OF SYSTEM TIME '-1s'")
+            .execute(&mut self.inner.conn)
+            .await?;
+        self.inner.in_transaction = true;
+        Ok(())
+    }
+
+    async fn list_tables(&mut self, filter: &TableFilter) -> Result<Vec<String>> {
+        let mut tables = self.inner.list_tables(filter).await?;
+        tables.retain(|t| {
+            let (schema, _) = split_table_name(t);
+            !schema.is_some_and(|s| SYSTEM_SCHEMAS.contains(&s))
+        });
+        Ok(tables)
+    }
+
+    async fn list_views(&mut self, filter: &TableFilter) -> Result<Vec<String>> {
+        let mut views = self.inner.list_views(filter).await?;
+        views.retain(|v| {
+            let (schema, _) = split_table_name(v);
+            !schema.is_some_and(|s| SYSTEM_SCHEMAS.contains(&s))
+        });
+        Ok(views)
+    }
+
+    async fn show_create_view(&mut self, view: &str) -> Result<String> {
+        self.inner.show_create_view(view).await
+    }
+
+    async fn list_routines(&mut self) -> Result<Vec<Routine>> {
+        self.inner.list_routines().await
+    }
+
+    async fn show_create_routine(&mut self, routine: &Routine) -> Result<String> {
+        self.inner.show_create_routine(routine).await
+    }
+
+    async fn create_database(&mut self, name: &str) -> Result<bool> {
+        self.inner.create_database(name).await
+    }
+
+    async fn list_triggers(&mut self, table: &str) -> Result<Vec<String>> {
+        self.inner.list_triggers(table).await
+    }
+
+    async fn show_create_trigger(&mut self, table: &str, trigger: &str) -> Result<String> {
+        self.inner.show_create_trigger(table, trigger).await
+    }
+
+    async fn extension_statements(&mut self) -> Result<Vec<String>> {
+        self.inner.extension_statements().await
+    }
+
+    async fn privilege_statements(&mut self, _table: &str) -> Result<Vec<String>> {
+        // Grants live in SHOW GRANTS, not in pg_class.relacl
+        bail!("Dumping privileges is not supported for CockroachDB")
+    }
+
+    async fn sequence_statements(&mut self, table: &str, values: bool) -> Result<Vec<String>> {
+        self.inner.sequence_statements(table, values).await
+    }
+
+    async fn show_create_table(&mut self, table: &str) -> Result<String> {
+        let query = format!(
+            "SHOW CREATE TABLE {}",
+            format_qualified_table(&COCKROACH_DIALECT, table)
+        );
+        let row = sqlx::query(&query)
+            .fetch_optional(&mut self.inner.conn)
+            .await?
+            .with_context(|| format!("Table {} not found", table))?;
+
+        let create_stmt: String = row.try_get("create_statement")?;
+        Ok(normalize_show_create(&create_stmt))
+    }
+
+    async fn stream_rows(&mut self, table: &str) -> Result<(Vec<String>, RowStream)> {
+        // Hidden columns (the implicit rowid key) are listed in information_schema
+        // but never returned by SELECT *; the inner session leaves them out
+        self.inner.stream_rows(table).await
+    }
+
+    async fn approximate_row_count(&mut self, table: &str) -> Result<u64> {
+        // pg_class.reltuples is not maintained; use the optimizer's table statistics
+        let (schema, name) = parse_table_name(table)?;
+        let estimate: Option<i64> = sqlx::query_scalar(
+            "SELECT estimated_row_count
+             FROM crdb_internal.table_row_statistics s
+             INNER JOIN crdb_internal.tables t ON t.table_id = s.table_id
+             WHERE t.database_name = current_database()
+               AND t.schema_name = $1 AND t.name = $2",
+        )
+        .bind(&schema)
+        .bind(&name)
+        .fetch_optional(&mut self.inner.conn)
+        .await?;
+
+        Ok(estimate.unwrap_or(0).max(0) as u64)
+    }
+
+    async fn has_rows(&mut self, table: &str) -> Result<bool> {
+        self.inner.has_rows(table).await
+    }
+
+    async fn count_rows(&mut self, table: &str) -> Result<u64> {
+        self.inner.count_rows(table).await
+    }
+
+    fn set_insert_mode(&mut self, mode: InsertMode) -> Result<()> {
+        self.inner.set_insert_mode(mode)
+    }
+
+    async fn copy_in(&mut self, statement: &str, data: &[u8]) -> Result<()> {
+        self.inner.copy_in(statement, data).await
+    }
+
+    async fn insert_batch(
+        &mut self,
+        table: &str,
+        column_names: &[String],
+        rows: &[Vec<SqlValue>],
+    ) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        if self.inner.insert_mode != InsertMode::Literal {
+            return self.inner.insert_batch(table, column_names, rows).await;
+        }
+
+        let sql = COCKROACH_DIALECT.insert_values_sql(table, column_names, rows);
+        sqlx::query(&sql)
+            .execute(&mut self.inner.conn)
+            .await
+            .with_context(|| format!("Failed to insert batch into table '{}'", table))?;
+        Ok(())
+    }
+
+    async fn disable_constraints(&mut self) -> Result<()> {
+        // session_replication_role is not supported; foreign keys stay enforced
+        eprintln!("Note: CockroachDB cannot disable foreign key checks; load order matters");
+        Ok(())
+    }
+
+    async fn enable_constraints(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn set_statement_timeout(&mut self, timeout: Duration) -> Result<bool> {
+        self.inner.set_statement_timeout(timeout).await
+    }
+
+    async fn drop_indexes(&mut self, _table: &str) -> Result<Vec<String>> {
+        bail!("Dropping indexes for the load is not supported for CockroachDB")
+    }
+
+    async fn execute(&mut self, sql: &str) -> Result<()> {
+        self.inner.execute(sql).await
+    }
+
+    async fn commit(&mut self) -> Result<()> {
+        self.inner.commit().await
+    }
+
+    async fn begin(&mut self) -> Result<bool> {
+        self.inner.begin().await
+    }
+
+    async fn rollback(&mut self) -> Result<()> {
+        self.inner.rollback().await
+    }
+
+    async fn create_table_from_columns(
+        &mut self,
+        table: &str,
+        column_names: &[String],
+        column_types: &[SqlValue],
+    ) -> Result<()> {
+        self.inner
+            .create_table_from_columns(table, column_names, column_types)
+            .await
+    }
+
+    async fn primary_key(&mut self, table: &str) -> Result<Vec<String>> {
+        // A table declared without one is keyed on its hidden rowid
+        let key = self.inner.primary_key(table).await?;
+        let columns = self.inner.column_names(table).await?;
+        if key.iter().any(|column| !columns.contains(column)) {
+            return Ok(Vec::new());
+        }
+        Ok(key)
+    }
+
+    async fn referenced_tables(&mut self, table: &str) -> Result<Vec<String>> {
+        self.inner.referenced_tables(table).await
+    }
+
+    async fn describe_columns(&mut self, table: &str) -> Result<Vec<Column>> {
+        let mut described = self.inner.describe_columns(table).await?;
+        let columns = self.inner.column_names(table).await?;
+        described.retain(|column| columns.contains(&column.name));
+        Ok(described)
+    }
+
+    async fn read_chunk(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        limit: usize,
+    ) -> Result<(Vec<String>, Vec<Vec<SqlValue>>)> {
+        self.inner.read_chunk(table, key, after, limit).await
+    }
+
+    async fn key_after(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        offset: usize,
+    ) -> Result<Option<Vec<SqlValue>>> {
+        self.inner.key_after(table, key, after, offset).await
+    }
+
+    async fn read_key_window(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        upto: Option<&[SqlValue]>,
+    ) -> Result<(Vec<String>, Vec<Vec<SqlValue>>)> {
+        self.inner.read_key_window(table, key, after, upto).await
+    }
+
+    async fn checksum_key_window(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        upto: Option<&[SqlValue]>,
+    ) -> Result<Option<String>> {
+        self.inner
+            .checksum_key_window(table, key, after, upto)
+            .await
+    }
+
+    async fn key_bounds(&mut self, table: &str, key: &str) -> Result<Option<(i64, i64)>> {
+        self.inner.key_bounds(table, key).await
+    }
+
+    async fn stream_key_range(
+        &mut self,
+        table: &str,
+        key: &str,
+        range: RangeInclusive<i64>,
+    ) -> Result<(Vec<String>, RowStream)> {
+        self.inner.stream_key_range(table, key, range).await
+    }
+
+    async fn column_max(&mut self, table: &str, column: &str) -> Result<SqlValue> {
+        self.inner.column_max(table, column).await
+    }
+
+    async fn stream_rows_since(
+        &mut self,
+        table: &str,
+        column: &str,
+        after: &SqlValue,
+        upto: &SqlValue,
+    ) -> Result<(Vec<String>, RowStream)> {
+        self.inner
+            .stream_rows_since(table, column, after, upto)
+            .await
+    }
+
+    async fn stream_rows_where(
+        &mut self,
+        table: &str,
+        condition: &str,
+    ) -> Result<(Vec<String>, RowStream)> {
+        self.inner.stream_rows_where(table, condition).await
+    }
+
+    async fn create_replication_slot(
+        &mut self,
+        _slot: &str,
+        _tables: &[String],
+    ) -> Result<Option<ReplicationPosition>> {
+        bail!("CockroachDB has no logical replication slots; follow changes with a changefeed")
+    }
+}
+
+/// Turn `SHOW CREATE TABLE` output into one statement per line, making the
+/// CREATE TABLE idempotent. Trailing statements (COMMENT ON, ALTER TABLE ...
+/// VALIDATE CONSTRAINT) are kept as separate lines.
+fn normalize_show_create(create_stmt: &str) -> String {
+    let mut statements = Vec::new();
+    let mut current = Vec::new();
+
+    for line in create_stmt.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        current.push(trimmed);
+        if trimmed.ends_with(';') {
+            statements.push(current.join(" "));
+            current.clear();
+        }
+    }
+    if !current.is_empty() {
+        statements.push(current.join(" "));
+    }
+
+    statements
+        .into_iter()
+        .map(|stmt| {
+            let stmt = stmt.trim_end_matches(';');
+            match stmt.strip_prefix("CREATE TABLE ") {
+                Some(rest) if !rest.starts_with("IF NOT EXISTS") => {
+                    format!("CREATE TABLE IF NOT EXISTS {};", rest)
+                }
+                _ => format!("{};", stmt),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_show_create_output() {
+        let input = "CREATE TABLE public.users (\n\tid INT8 NOT NULL DEFAULT unique_rowid(),\n\tname STRING NULL,\n\tCONSTRAINT users_pkey PRIMARY KEY (id ASC)\n);\nCOMMENT ON TABLE public.users IS 'people';";
+        let output = normalize_show_create(input);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("CREATE TABLE IF NOT EXISTS public.users ("));
+        assert!(lines[0].ends_with(");"));
+        assert_eq!(lines[1], "COMMENT ON TABLE public.users IS 'people';");
+    }
+}
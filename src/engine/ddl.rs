@@ -0,0 +1,442 @@
+use super::dialect::{format_qualified_table, SqlDialect};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+
+/// A column's type, read from the source's catalog and reduced to what
+/// every dialect can write its own way
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColumnType {
+    Bool,
+    SmallInt,
+    Int,
+    BigInt,
+    /// Precision and scale, if the source fixed them
+    Decimal(Option<(u32, u32)>),
+    Real,
+    Double,
+    Char(u32),
+    Varchar(Option<u32>),
+    Text,
+    Bytes,
+    Date,
+    Time,
+    Timestamp,
+    TimestampTz,
+    Json,
+    Uuid,
+    Enum(Vec<String>),
+    /// Anything else, by the source's name for it; written as text
+    Other(String),
+}
+
+impl ColumnType {
+    /// Read a type as MySQL's `COLUMN_TYPE` or PostgreSQL's `format_type`
+    /// gives it, such as `int(10) unsigned`, `enum('a','b')` or `timestamp(3)
+    /// with time zone`. `tinyint(1)` stays an integer, as MySQL's booleans
+    /// hold any small number.
+    pub fn parse(declared: &str) -> ColumnType {
+        let lower = declared.trim().to_ascii_lowercase();
+        let (base, args) = match (lower.find('('), lower.rfind(')')) {
+            (Some(open), Some(close)) if open < close => (
+                format!("{} {}", &lower[..open], &lower[close + 1..]),
+                &declared.trim()[open + 1..close],
+            ),
+            _ => (lower.clone(), ""),
+        };
+        let words: Vec<&str> = base
+            .split_whitespace()
+            .filter(|w| !matches!(*w, "zerofill" | "signed"))
+            .collect();
+        let unsigned = words.contains(&"unsigned");
+        let base = words
+            .iter()
+            .filter(|w| **w != "unsigned")
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let numbers: Vec<u32> = args
+            .split(',')
+            .filter_map(|n| n.trim().parse().ok())
+            .collect();
+
+        match base.as_str() {
+            "bool" | "boolean" => ColumnType::Bool,
+            "bit" if matches!(numbers[..], [] | [1]) => ColumnType::Bool,
+            "tinyint" | "smallint" | "int2" | "year" if !unsigned || base == "tinyint" => {
+                ColumnType::SmallInt
+            }
+            "smallint" | "int2" | "year" | "mediumint" => ColumnType::Int,
+            "int" | "integer" | "int4" if !unsigned => ColumnType::Int,
+            "int" | "integer" | "int4" => ColumnType::BigInt,
+            "bigint" | "int8" if !unsigned => ColumnType::BigInt,
+            "bigint" | "int8" => ColumnType::Decimal(Some((20, 0))),
+            "decimal" | "numeric" | "dec" | "fixed" => match numbers[..] {
+                [precision] => ColumnType::Decimal(Some((precision, 0))),
+                [precision, scale] => ColumnType::Decimal(Some((precision, scale))),
+                _ => ColumnType::Decimal(None),
+            },
+            "float" | "real" | "float4" => ColumnType::Real,
+            "double" | "double precision" | "float8" => ColumnType::Double,
+            "char" | "character" | "bpchar" | "nchar" => {
+                ColumnType::Char(numbers.first().copied().unwrap_or(1))
+            }
+            "varchar" | "character varying" | "nvarchar" => {
+                ColumnType::Varchar(numbers.first().copied())
+            }
+            "tinytext" | "text" | "mediumtext" | "longtext" | "citext" => ColumnType::Text,
+            "binary" | "varbinary" | "tinyblob" | "blob" | "mediumblob" | "longblob" | "bytea" => {
+                ColumnType::Bytes
+            }
+            "date" => ColumnType::Date,
+            "time" | "time without time zone" | "time with time zone" | "timetz" => {
+                ColumnType::Time
+            }
+            "datetime" | "timestamp" | "timestamp without time zone" => ColumnType::Timestamp,
+            "timestamp with time zone" | "timestamptz" => ColumnType::TimestampTz,
+            "json" | "jsonb" => ColumnType::Json,
+            "uuid" => ColumnType::Uuid,
+            "enum" => ColumnType::Enum(enum_labels(args)),
+            _ => ColumnType::Other(declared.trim().to_string()),
+        }
+    }
+}
+
+/// The quoted labels of `enum('a','it''s')`
+pub(crate) fn enum_labels(args: &str) -> Vec<String> {
+    let mut labels = Vec::new();
+    let mut chars = args.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\'' {
+            continue;
+        }
+        let mut label = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' if chars.peek() == Some(&'\'') => {
+                    chars.next();
+                    label.push('\'');
+                }
+                '\'' => break,
+                '\\' => label.extend(chars.next()),
+                c => label.push(c),
+            }
+        }
+        labels.push(label);
+    }
+    labels
+}
+
+/// A source column, as `describe_columns` reports it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    pub column_type: ColumnType,
+    /// The type as the source's catalog spells it, which `--type-map` matches
+    pub declared: String,
+    pub nullable: bool,
+    /// Filled in by the server: `AUTO_INCREMENT`, `serial` or an identity
+    pub auto_increment: bool,
+}
+
+/// Types chosen over the translated ones, read from a `--type-map` file:
+///
+/// ```toml
+/// # By source type, as declared or without its arguments
+/// [types]
+/// "tinyint(1)" = "BOOLEAN"
+/// json = "JSON"
+///
+/// # By destination table and column, ahead of [types]
+/// [columns]
+/// "orders.total" = "NUMERIC(12,2)"
+/// ```
+///
+/// Types are written as given, so an overridden counter column loses its
+/// `AUTO_INCREMENT` or `SERIAL`.
+#[derive(Debug, Clone, Default)]
+pub struct TypeMap {
+    types: HashMap<String, String>,
+    columns: HashMap<String, String>,
+}
+
+impl TypeMap {
+    pub fn load(path: &str) -> Result<TypeMap> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read --type-map '{}'", path))?;
+        TypeMap::parse(&text).with_context(|| format!("Invalid --type-map '{}'", path))
+    }
+
+    pub fn parse(text: &str) -> Result<TypeMap> {
+        let value: toml::Value = text.parse()?;
+        let mut map = TypeMap::default();
+        let Some(sections) = value.as_table() else {
+            bail!("expected [types] and [columns] tables");
+        };
+        for (section, entries) in sections {
+            let target = match section.as_str() {
+                "types" => &mut map.types,
+                "columns" => &mut map.columns,
+                _ => bail!(
+                    "unknown section [{}]; expected [types] or [columns]",
+                    section
+                ),
+            };
+            let Some(entries) = entries.as_table() else {
+                bail!("[{}] must be a table", section);
+            };
+            for (key, sql) in entries {
+                let Some(sql) = sql.as_str() else {
+                    bail!("{}.\"{}\" must be a type name in quotes", section, key);
+                };
+                let key = match section.as_str() {
+                    "types" => normalize_type(key),
+                    _ => key.clone(),
+                };
+                target.insert(key, sql.to_string());
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty() && self.columns.is_empty()
+    }
+
+    /// The type to write for `column` of `table` instead of the translated one
+    pub fn type_for(&self, table: &str, column: &Column) -> Option<&str> {
+        if let Some(sql) = self.columns.get(&format!("{}.{}", table, column.name)) {
+            return Some(sql);
+        }
+        let declared = normalize_type(&column.declared);
+        let base = match (declared.find('('), declared.rfind(')')) {
+            (Some(open), Some(close)) if open < close => {
+                normalize_type(&format!("{} {}", &declared[..open], &declared[close + 1..]))
+            }
+            _ => declared.clone(),
+        };
+        self.types
+            .get(&declared)
+            .or_else(|| self.types.get(&base))
+            .map(String::as_str)
+    }
+}
+
+/// Lowercase, with single spaces between words and none inside parentheses
+fn normalize_type(declared: &str) -> String {
+    declared
+        .to_ascii_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("( ", "(")
+        .replace(" (", "(")
+        .replace(" )", ")")
+        .replace(", ", ",")
+}
+
+/// How the `dest` columns of a table differ from the `source` columns rows
+/// are copied from, in ways that make inserting them fail: a column the
+/// destination lacks, or that takes no NULL there when the source allows
+/// it. With `types`, which only compare within one engine, declared types
+/// must match as well. Columns only the destination has are left to it.
+pub fn column_drift(source: &[Column], dest: &[Column], types: bool) -> Vec<String> {
+    let mut drift = Vec::new();
+    for column in source {
+        let Some(dest_column) = dest.iter().find(|c| c.name == column.name) else {
+            drift.push(format!(
+                "column '{}' is missing on the destination",
+                column.name
+            ));
+            continue;
+        };
+        if types && normalize_type(&column.declared) != normalize_type(&dest_column.declared) {
+            drift.push(format!(
+                "column '{}' is {} on the source but {} on the destination",
+                column.name, column.declared, dest_column.declared
+            ));
+        }
+        if column.nullable && !dest_column.nullable {
+            drift.push(format!(
+                "column '{}' allows NULL on the source but not on the destination",
+                column.name
+            ));
+        }
+    }
+    drift
+}
+
+/// `CREATE TABLE` for `columns` and primary `key` in `dialect`, or `None`
+/// when there are no columns or the dialect cannot write one of their
+/// types. Defaults, secondary indexes and foreign keys are not carried over.
+pub fn create_table_sql(
+    dialect: &dyn SqlDialect,
+    table: &str,
+    columns: &[Column],
+    key: &[String],
+    type_map: &TypeMap,
+) -> Option<String> {
+    if columns.is_empty() {
+        return None;
+    }
+    let mut lines = Vec::with_capacity(columns.len() + 1);
+    for column in columns {
+        let column_type = match type_map.type_for(table, column) {
+            Some(sql) => sql.to_string(),
+            None => dialect.column_type_sql(column)?,
+        };
+        let mut line = format!("{} {}", dialect.quote_identifier(&column.name), column_type);
+        if !column.nullable {
+            line.push_str(" NOT NULL");
+        }
+        lines.push(line);
+    }
+    if !key.is_empty() {
+        let key: Vec<String> = key.iter().map(|k| dialect.quote_identifier(k)).collect();
+        lines.push(format!("PRIMARY KEY ({})", key.join(", ")));
+    }
+    Some(format!(
+        "CREATE TABLE {} (\n    {}\n)",
+        format_qualified_table(dialect, table),
+        lines.join(",\n    ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::dialects::mysql::MYSQL_DIALECT;
+    use crate::util::dialects::postgres::POSTGRES_DIALECT;
+
+    #[test]
+    fn parses_mysql_and_postgres_types() {
+        let cases = [
+            ("int(11)", ColumnType::Int),
+            ("int(10) unsigned", ColumnType::BigInt),
+            ("bigint unsigned", ColumnType::Decimal(Some((20, 0)))),
+            ("tinyint(1)", ColumnType::SmallInt),
+            ("decimal(10,2)", ColumnType::Decimal(Some((10, 2)))),
+            ("numeric", ColumnType::Decimal(None)),
+            ("character varying(100)", ColumnType::Varchar(Some(100))),
+            ("longtext", ColumnType::Text),
+            ("mediumblob", ColumnType::Bytes),
+            ("datetime(6)", ColumnType::Timestamp),
+            ("timestamp(3) with time zone", ColumnType::TimestampTz),
+            ("double precision", ColumnType::Double),
+            ("jsonb", ColumnType::Json),
+            (
+                "enum('new','it''s','a,b')",
+                ColumnType::Enum(vec!["new".into(), "it's".into(), "a,b".into()]),
+            ),
+            ("point", ColumnType::Other("point".into())),
+        ];
+        for (declared, expected) in cases {
+            assert_eq!(ColumnType::parse(declared), expected, "{}", declared);
+        }
+    }
+
+    #[test]
+    fn writes_create_table_for_each_dialect() {
+        let columns = vec![
+            Column {
+                name: "id".into(),
+                declared: "int(11)".into(),
+                column_type: ColumnType::Int,
+                nullable: false,
+                auto_increment: true,
+            },
+            Column {
+                name: "status".into(),
+                declared: "enum('new','done')".into(),
+                column_type: ColumnType::Enum(vec!["new".into(), "done".into()]),
+                nullable: true,
+                auto_increment: false,
+            },
+            Column {
+                name: "created".into(),
+                declared: "datetime".into(),
+                column_type: ColumnType::Timestamp,
+                nullable: false,
+                auto_increment: false,
+            },
+        ];
+        let key = ["id".to_string()];
+
+        assert_eq!(
+            create_table_sql(&POSTGRES_DIALECT, "orders", &columns, &key, &TypeMap::default()).unwrap(),
+            "CREATE TABLE \"orders\" (\n    \"id\" SERIAL NOT NULL,\n    \"status\" TEXT CHECK (\"status\" IN ('new', 'done')),\n    \"created\" TIMESTAMP NOT NULL,\n    PRIMARY KEY (\"id\")\n)"
+        );
+        assert_eq!(
+            create_table_sql(&MYSQL_DIALECT, "orders", &columns, &key, &TypeMap::default()).unwrap(),
+            "CREATE TABLE `orders` (\n    `id` INT AUTO_INCREMENT NOT NULL,\n    `status` ENUM('new', 'done'),\n    `created` DATETIME(6) NOT NULL,\n    PRIMARY KEY (`id`)\n)"
+        );
+        assert!(
+            create_table_sql(&POSTGRES_DIALECT, "orders", &[], &key, &TypeMap::default()).is_none()
+        );
+    }
+
+    #[test]
+    fn type_map_overrides_by_column_then_by_type() {
+        let map = TypeMap::parse(
+            "[types]\n\"TINYINT( 1 )\" = \"BOOLEAN\"\ndatetime = \"TIMESTAMPTZ\"\n\n[columns]\n\"orders.created\" = \"DATE\"\n",
+        )
+        .unwrap();
+        let column = |name: &str, declared: &str| Column {
+            name: name.into(),
+            column_type: ColumnType::parse(declared),
+            declared: declared.into(),
+            nullable: true,
+            auto_increment: false,
+        };
+        let columns = vec![
+            column("paid", "tinyint(1)"),
+            column("count", "tinyint(4)"),
+            column("updated", "datetime(6)"),
+            column("created", "datetime"),
+        ];
+
+        assert_eq!(
+            create_table_sql(&POSTGRES_DIALECT, "orders", &columns, &[], &map).unwrap(),
+            "CREATE TABLE \"orders\" (\n    \"paid\" BOOLEAN,\n    \"count\" SMALLINT,\n    \"updated\" TIMESTAMPTZ,\n    \"created\" DATE\n)"
+        );
+        assert!(TypeMap::parse("[tables]\na = \"b\"").is_err());
+        assert!(TypeMap::parse("[types]\nint = 4").is_err());
+    }
+
+    #[test]
+    fn reports_columns_that_drifted() {
+        let column = |name: &str, declared: &str, nullable: bool| Column {
+            name: name.into(),
+            column_type: ColumnType::parse(declared),
+            declared: declared.into(),
+            nullable,
+            auto_increment: false,
+        };
+        let source = vec![
+            column("id", "int(11)", false),
+            column("total", "decimal(10,2)", true),
+            column("note", "text", true),
+        ];
+        let dest = vec![
+            column("id", "INT( 11 )", false),
+            column("total", "decimal(8,2)", false),
+            column("added", "text", false),
+        ];
+
+        assert_eq!(
+            column_drift(&source, &dest, true),
+            vec![
+                "column 'total' is decimal(10,2) on the source but decimal(8,2) on the destination",
+                "column 'total' allows NULL on the source but not on the destination",
+                "column 'note' is missing on the destination",
+            ]
+        );
+        assert_eq!(
+            column_drift(&source, &dest, false),
+            vec![
+                "column 'total' allows NULL on the source but not on the destination",
+                "column 'note' is missing on the destination",
+            ]
+        );
+        assert!(column_drift(&source, &source, true).is_empty());
+    }
+}
@@ -1,4 +1,42 @@
+use crate::engine::ddl::Column;
 use crate::engine::value::SqlValue;
+use crate::engine::Routine;
+use crate::util::splitter::Syntax;
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// `--insert-ignore` / `--replace`, or `restore --on-conflict`: what INSERTs
+/// do with rows whose key already exists on the server they are loaded into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Fail, as a plain INSERT does
+    #[default]
+    Error,
+    /// Keep the existing row
+    Ignore,
+    /// Overwrite the existing row
+    Replace,
+    /// Set the existing row's other columns, keeping the row itself
+    Update,
+}
+
+impl FromStr for OnConflict {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ignore" => Ok(OnConflict::Ignore),
+            "replace" => Ok(OnConflict::Replace),
+            "update" => Ok(OnConflict::Update),
+            other => Err(format!(
+                "Unknown conflict handling '{}' (expected ignore, replace or update)",
+                other
+            )),
+        }
+    }
+}
 
 /// SQL dialect abstraction for identifier and literal formatting.
 pub trait SqlDialect: Send + Sync {
@@ -15,15 +53,208 @@ pub trait SqlDialect: Send + Sync {
     fn insert_values_sql(&self, table: &str, columns: &[String], rows: &[Vec<SqlValue>]) -> String;
 
     /// Format a drop table statement using the dialect's identifier rules.
-    fn drop_table_statement(&self, table: &str) -> String
-    where
-        Self: Sized,
-    {
+    fn drop_table_statement(&self, table: &str) -> String {
+        let (schema, name) = split_table_name(table);
+        match schema {
+            Some(schema_name) => format!(
+                "DROP TABLE IF EXISTS {}.{}",
+                self.quote_identifier(schema_name),
+                self.quote_identifier(name)
+            ),
+            None => format!("DROP TABLE IF EXISTS {}", self.quote_identifier(name)),
+        }
+    }
+
+    fn drop_view_statement(&self, view: &str) -> String {
+        let (schema, name) = split_table_name(view);
+        match schema {
+            Some(schema_name) => format!(
+                "DROP VIEW IF EXISTS {}.{}",
+                self.quote_identifier(schema_name),
+                self.quote_identifier(name)
+            ),
+            None => format!("DROP VIEW IF EXISTS {}", self.quote_identifier(name)),
+        }
+    }
+
+    /// Statement dropping a routine before the dump creates it again, or
+    /// `None` where its definition replaces it in place
+    fn drop_routine_statement(&self, routine: &Routine) -> Option<String> {
+        let name = match split_table_name(&routine.name) {
+            (Some(schema), name) => format!(
+                "{}.{}",
+                self.quote_identifier(schema),
+                self.quote_identifier(name)
+            ),
+            (None, name) => self.quote_identifier(name),
+        };
+        Some(format!("DROP {} IF EXISTS {}", routine.kind, name))
+    }
+
+    /// Statement creating `schema` ahead of the tables in it, or `None` for
+    /// dialects without schemas and schemas every database has
+    fn create_schema_statement(&self, _schema: &str) -> Option<String> {
+        None
+    }
+
+    /// Database a session connects to for creating another, empty when the
+    /// server takes connections without one; `None` for dialects whose
+    /// databases are not created over a connection
+    fn server_database(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Statements at the top of a dump creating `database` and switching to
+    /// it, so a restore can start from an empty server. Dialects whose
+    /// scripts cannot switch databases return none.
+    fn database_statements(&self, _database: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// `sql` without the clause naming the account a view, trigger, routine
+    /// or event runs as, so it can be created on a server without that
+    /// account and belongs to whoever creates it. Statements that define no
+    /// such object come back unchanged.
+    fn strip_definer(&self, sql: &str) -> String {
+        sql.to_string()
+    }
+
+    /// How an INSERT from `insert_values_sql` changes so that rows colliding
+    /// with existing ones are handled as `on_conflict` says: the keywords it
+    /// then opens with in place of `INSERT INTO`, and a clause to put after
+    /// its values. `key` is the table's primary key, for dialects that name
+    /// the conflict. `None` where the dialect has no such statement.
+    fn conflict_clauses(
+        &self,
+        _on_conflict: OnConflict,
+        _columns: &[String],
+        _key: &[String],
+    ) -> Option<(&'static str, String)> {
+        None
+    }
+
+    /// Whether a backslash escapes the next character in string literals,
+    /// so a restore knows where they end
+    fn backslash_escapes(&self) -> bool {
+        false
+    }
+
+    /// The quoting and comment rules a restore splits statements by
+    fn syntax(&self) -> Syntax {
+        Syntax {
+            backslash_escapes: self.backslash_escapes(),
+            ..Syntax::STANDARD
+        }
+    }
+
+    /// Delimiter that ends statements with `;` inside their body, such as
+    /// triggers, written between `DELIMITER` lines. Dialects whose clients
+    /// take such statements as they are return `None`.
+    fn compound_delimiter(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Split `create` (DDL from `show_create_table`) into the statement that
+    /// creates the table and the statements that add its secondary indexes,
+    /// so the indexes can be built after the data is loaded. Dialects that
+    /// cannot separate them return `create` whole.
+    fn split_secondary_indexes(&self, _table: &str, create: &str) -> (String, Vec<String>) {
+        (create.to_string(), Vec::new())
+    }
+
+    /// Split `create` into the statement that creates the table and the
+    /// statements that add its foreign keys, for dumps that load every table
+    /// before any key is checked. Dialects that cannot separate them return
+    /// `create` whole.
+    fn split_foreign_keys(&self, _table: &str, create: &str) -> (String, Vec<String>) {
+        (create.to_string(), Vec::new())
+    }
+
+    /// `create` with the character sets of the table and its columns changed
+    /// to `charset`, for text converted on the way. Dialects without
+    /// character sets of their own return it unchanged.
+    fn with_charset(&self, create: &str, _charset: &str) -> String {
+        create.to_string()
+    }
+
+    /// Type for `column` in a `CREATE TABLE` translated from another engine,
+    /// with what makes the server fill it in when it is `auto_increment`.
+    /// Dialects that cannot translate DDL return `None`, and tables are then
+    /// built from the values in their rows.
+    fn column_type_sql(&self, _column: &Column) -> Option<String> {
+        None
+    }
+
+    /// Statement moving the counter behind `table`'s `auto_increment` column
+    /// past the rows copied in with their own values, where the server does
+    /// not do so itself
+    fn reset_auto_increment_sql(&self, _table: &str, _column: &str) -> Option<String> {
+        None
+    }
+
+    /// Statement removing every row of `table`, keeping the table
+    fn truncate_table_statement(&self, table: &str) -> String {
+        let (schema, name) = split_table_name(table);
+        match schema {
+            Some(schema_name) => format!(
+                "TRUNCATE TABLE {}.{}",
+                self.quote_identifier(schema_name),
+                self.quote_identifier(name)
+            ),
+            None => format!("TRUNCATE TABLE {}", self.quote_identifier(name)),
+        }
+    }
+
+    /// Statement renaming `column` of `table` to `new_name`, keeping the
+    /// indexes and constraints on it
+    fn rename_column_statement(&self, table: &str, column: &str, new_name: &str) -> String {
+        let table = match split_table_name(table) {
+            (Some(schema), name) => format!(
+                "{}.{}",
+                self.quote_identifier(schema),
+                self.quote_identifier(name)
+            ),
+            (None, name) => self.quote_identifier(name),
+        };
+        format!(
+            "ALTER TABLE {} RENAME COLUMN {} TO {}",
+            table,
+            self.quote_identifier(column),
+            self.quote_identifier(new_name)
+        )
+    }
+
+    /// Statement adding `column`, of the SQL type `column_type`, to `table`
+    fn add_column_statement(&self, table: &str, column: &str, column_type: &str) -> String {
+        let table = match split_table_name(table) {
+            (Some(schema), name) => format!(
+                "{}.{}",
+                self.quote_identifier(schema),
+                self.quote_identifier(name)
+            ),
+            (None, name) => self.quote_identifier(name),
+        };
         format!(
-            "DROP TABLE IF EXISTS {}",
-            format_qualified_table(self, table)
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table,
+            self.quote_identifier(column),
+            column_type
         )
     }
+
+    /// SELECT returning one text digest of the rows of `table` that
+    /// `condition` (a `WHERE` clause, or empty) keeps, over `columns` in
+    /// `key` order. Two servers of the same dialect return the same digest
+    /// for the same rows. `None` where the dialect has no way to digest them.
+    fn checksum_sql(
+        &self,
+        _table: &str,
+        _columns: &[String],
+        _key: &[String],
+        _condition: &str,
+    ) -> Option<String> {
+        None
+    }
 }
 
 /// Split a qualified table name into (schema, table) components.
@@ -34,6 +265,29 @@ pub fn split_table_name(name: &str) -> (Option<&str>, &str) {
     }
 }
 
+/// Whether the qualified `name` lies in one of `schemas`; every name does
+/// when no schemas are given.
+pub fn in_schemas(name: &str, schemas: &[String]) -> bool {
+    schemas.is_empty()
+        || split_table_name(name)
+            .0
+            .is_some_and(|schema| schemas.iter().any(|s| s.eq_ignore_ascii_case(schema)))
+}
+
+/// Statements creating the schemas the qualified `names` lie in, each once.
+pub fn create_schema_statements<'a>(
+    dialect: &dyn SqlDialect,
+    names: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+    let mut seen = HashSet::new();
+    names
+        .into_iter()
+        .filter_map(|name| split_table_name(name).0)
+        .filter(|schema| seen.insert(*schema))
+        .filter_map(|schema| dialect.create_schema_statement(schema))
+        .collect()
+}
+
 /// Format a potentially schema-qualified table name using the dialect.
 pub fn format_qualified_table(dialect: &dyn SqlDialect, name: &str) -> String {
     let (schema, table) = split_table_name(name);
@@ -46,3 +300,365 @@ pub fn format_qualified_table(dialect: &dyn SqlDialect, name: &str) -> String {
         None => dialect.quote_identifier(table),
     }
 }
+
+/// The table an INSERT writes to, followed by its column list unless
+/// `columns` is empty
+pub fn insert_target(dialect: &dyn SqlDialect, table: &str, columns: &[String]) -> String {
+    let mut target = format_qualified_table(dialect, table);
+    if !columns.is_empty() {
+        let columns: Vec<String> = columns
+            .iter()
+            .map(|col| dialect.quote_identifier(col))
+            .collect();
+        target.push_str(&format!(" ({})", columns.join(", ")));
+    }
+    target
+}
+
+/// `dialect`'s conflict clauses for INSERTs into `table`. Replacing or
+/// updating fails where the dialect would quietly skip conflicting rows
+/// instead, for want of the primary key or of columns outside it, unless
+/// every column is in the key and there is nothing to update.
+pub fn conflict_clauses_for(
+    dialect: &dyn SqlDialect,
+    table: &str,
+    on_conflict: OnConflict,
+    columns: &[String],
+    key: &[String],
+) -> Result<(&'static str, String)> {
+    let clauses = dialect
+        .conflict_clauses(on_conflict, columns, key)
+        .ok_or_else(|| anyhow!("--on-conflict is not supported for {}", dialect.name()))?;
+    let skips = on_conflict != OnConflict::Ignore
+        && dialect.conflict_clauses(OnConflict::Ignore, columns, key) == Some(clauses.clone());
+    let all_key = !key.is_empty() && !columns.is_empty() && columns.iter().all(|c| key.contains(c));
+    if skips && !all_key {
+        bail!(
+            "Conflicting rows in '{}' can only be skipped in {}: replacing or updating them needs its primary key and the columns outside it",
+            table,
+            dialect.name()
+        );
+    }
+    Ok(clauses)
+}
+
+/// Split DDL where every index is its own `CREATE [UNIQUE] INDEX` line after
+/// the `CREATE TABLE` (PostgreSQL and SQLite `show_create_table` output)
+pub fn split_index_statements(create: &str) -> (String, Vec<String>) {
+    let mut table = Vec::new();
+    let mut indexes = Vec::new();
+
+    for line in create.lines() {
+        if line.starts_with("CREATE INDEX ") || line.starts_with("CREATE UNIQUE INDEX ") {
+            indexes.push(line.trim_end_matches(';').to_string());
+        } else {
+            table.push(line);
+        }
+    }
+
+    (table.join("\n"), indexes)
+}
+
+/// Most bind parameters MySQL and PostgreSQL accept in one statement
+pub const MAX_BIND_PARAMS: usize = 65_535;
+
+/// Build a multi-row INSERT with bind parameters for `rows` rows. `placeholder`
+/// renders the parameter for the given zero-based position in the statement.
+pub fn insert_params_sql(
+    dialect: &dyn SqlDialect,
+    table: &str,
+    columns: &[String],
+    rows: usize,
+    placeholder: impl Fn(usize) -> String,
+) -> String {
+    let column_list: Vec<String> = columns
+        .iter()
+        .map(|c| dialect.quote_identifier(c))
+        .collect();
+    let tuples: Vec<String> = (0..rows)
+        .map(|row| {
+            let params: Vec<String> = (0..columns.len())
+                .map(|col| placeholder(row * columns.len() + col))
+                .collect();
+            format!("({})", params.join(", "))
+        })
+        .collect();
+
+    format!(
+        "INSERT INTO {} ({}) VALUES {}",
+        format_qualified_table(dialect, table),
+        column_list.join(", "),
+        tuples.join(", ")
+    )
+}
+
+/// `columns` quoted for a SELECT list. Rows are read column by column
+/// rather than with `SELECT *`, which would also return generated columns.
+fn select_list(dialect: &dyn SqlDialect, columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|c| dialect.quote_identifier(c))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Build a SELECT returning `columns` of every row of `table`.
+pub fn select_rows_sql(dialect: &dyn SqlDialect, table: &str, columns: &[String]) -> String {
+    format!(
+        "SELECT {} FROM {}",
+        select_list(dialect, columns),
+        format_qualified_table(dialect, table)
+    )
+}
+
+/// Build a SELECT returning `columns` of the rows of `table` that satisfy
+/// `condition`, an SQL expression given by the user.
+pub fn filtered_select_sql(
+    dialect: &dyn SqlDialect,
+    table: &str,
+    columns: &[String],
+    condition: &str,
+) -> String {
+    format!(
+        "{} WHERE ({})",
+        select_rows_sql(dialect, table, columns),
+        condition
+    )
+}
+
+/// Build a keyset-paginated SELECT returning `columns` of the rows whose `key`
+/// sorts after `after`, ordered by key and capped at `limit`.
+pub fn keyset_select_sql(
+    dialect: &dyn SqlDialect,
+    table: &str,
+    columns: &[String],
+    key: &[String],
+    after: Option<&[SqlValue]>,
+    limit: usize,
+) -> String {
+    let key_columns: Vec<String> = key.iter().map(|c| dialect.quote_identifier(c)).collect();
+    format!(
+        "SELECT {} FROM {}{} ORDER BY {} LIMIT {}",
+        select_list(dialect, columns),
+        format_qualified_table(dialect, table),
+        key_window_condition(dialect, key, after, None),
+        key_columns.join(", "),
+        limit
+    )
+}
+
+/// Build a SELECT returning `columns` of the rows whose primary key sorts
+/// after `after` and no later than `upto`, in key order; a missing bound
+/// leaves that end of the table open.
+pub fn key_window_select_sql(
+    dialect: &dyn SqlDialect,
+    table: &str,
+    columns: &[String],
+    key: &[String],
+    after: Option<&[SqlValue]>,
+    upto: Option<&[SqlValue]>,
+) -> String {
+    let key_columns: Vec<String> = key.iter().map(|c| dialect.quote_identifier(c)).collect();
+    format!(
+        "SELECT {} FROM {}{} ORDER BY {}",
+        select_list(dialect, columns),
+        format_qualified_table(dialect, table),
+        key_window_condition(dialect, key, after, upto),
+        key_columns.join(", ")
+    )
+}
+
+/// Build a SELECT returning the primary key `offset` rows past `after`.
+pub fn key_offset_sql(
+    dialect: &dyn SqlDialect,
+    table: &str,
+    key: &[String],
+    after: Option<&[SqlValue]>,
+    offset: usize,
+) -> String {
+    let key_columns: Vec<String> = key.iter().map(|c| dialect.quote_identifier(c)).collect();
+    format!(
+        "SELECT {} FROM {}{} ORDER BY {} LIMIT 1 OFFSET {}",
+        key_columns.join(", "),
+        format_qualified_table(dialect, table),
+        key_window_condition(dialect, key, after, None),
+        key_columns.join(", "),
+        offset
+    )
+}
+
+/// The dialect's [`SqlDialect::checksum_sql`] over the rows of a key window,
+/// as [`key_window_select_sql`] bounds it
+pub fn key_window_checksum_sql(
+    dialect: &dyn SqlDialect,
+    table: &str,
+    columns: &[String],
+    key: &[String],
+    after: Option<&[SqlValue]>,
+    upto: Option<&[SqlValue]>,
+) -> Option<String> {
+    let condition = key_window_condition(dialect, key, after, upto);
+    dialect.checksum_sql(table, columns, key, &condition)
+}
+
+/// ` WHERE` clause keeping the keys after `after` and up to `upto`, or
+/// nothing when neither is given
+fn key_window_condition(
+    dialect: &dyn SqlDialect,
+    key: &[String],
+    after: Option<&[SqlValue]>,
+    upto: Option<&[SqlValue]>,
+) -> String {
+    let key_columns: Vec<String> = key.iter().map(|c| dialect.quote_identifier(c)).collect();
+    // Row-value comparison keeps composite keys in lexicographic order
+    let compare = |op: &str, bound: &[SqlValue]| {
+        let values: Vec<String> = bound.iter().map(|v| dialect.to_literal(v)).collect();
+        if key_columns.len() == 1 {
+            format!("{} {} {}", key_columns[0], op, values[0])
+        } else {
+            format!(
+                "({}) {} ({})",
+                key_columns.join(", "),
+                op,
+                values.join(", ")
+            )
+        }
+    };
+    let conditions: Vec<String> = [(">", after), ("<=", upto)]
+        .into_iter()
+        .filter_map(|(op, bound)| Some(compare(op, bound?)))
+        .collect();
+    if conditions.is_empty() {
+        return String::new();
+    }
+    format!(" WHERE {}", conditions.join(" AND "))
+}
+
+/// Build a SELECT returning the smallest and largest value of `key`.
+pub fn key_bounds_sql(dialect: &dyn SqlDialect, table: &str, key: &str) -> String {
+    let key = dialect.quote_identifier(key);
+    format!(
+        "SELECT MIN({}), MAX({}) FROM {}",
+        key,
+        key,
+        format_qualified_table(dialect, table)
+    )
+}
+
+/// Build a SELECT returning one row when `table` has any.
+pub fn any_row_sql(dialect: &dyn SqlDialect, table: &str) -> String {
+    format!(
+        "SELECT 1 FROM {} LIMIT 1",
+        format_qualified_table(dialect, table)
+    )
+}
+
+/// Build a SELECT returning the exact number of rows in `table`.
+pub fn count_rows_sql(dialect: &dyn SqlDialect, table: &str) -> String {
+    format!(
+        "SELECT COUNT(*) FROM {}",
+        format_qualified_table(dialect, table)
+    )
+}
+
+/// Build a SELECT returning the largest value of `column`.
+pub fn column_max_sql(dialect: &dyn SqlDialect, table: &str, column: &str) -> String {
+    format!(
+        "SELECT MAX({}) FROM {}",
+        dialect.quote_identifier(column),
+        format_qualified_table(dialect, table)
+    )
+}
+
+/// Build a SELECT returning `columns` of the rows whose `column` is past
+/// `after` and no further than `upto`.
+pub fn watermark_select_sql(
+    dialect: &dyn SqlDialect,
+    table: &str,
+    columns: &[String],
+    column: &str,
+    after: &SqlValue,
+    upto: &SqlValue,
+) -> String {
+    let column = dialect.quote_identifier(column);
+    format!(
+        "SELECT {} FROM {} WHERE {} > {} AND {} <= {}",
+        select_list(dialect, columns),
+        format_qualified_table(dialect, table),
+        column,
+        dialect.to_literal(after),
+        column,
+        dialect.to_literal(upto)
+    )
+}
+
+/// Build a SELECT returning `columns` of the rows whose integer `key` lies
+/// within `range`.
+pub fn key_range_select_sql(
+    dialect: &dyn SqlDialect,
+    table: &str,
+    columns: &[String],
+    key: &str,
+    range: &RangeInclusive<i64>,
+) -> String {
+    format!(
+        "SELECT {} FROM {} WHERE {} BETWEEN {} AND {}",
+        select_list(dialect, columns),
+        format_qualified_table(dialect, table),
+        dialect.quote_identifier(key),
+        range.start(),
+        range.end()
+    )
+}
+
+/// Build an UPDATE setting `columns` to `values` on the row whose `key`
+/// columns hold `key_values`.
+pub fn update_row_sql(
+    dialect: &dyn SqlDialect,
+    table: &str,
+    columns: &[String],
+    values: &[SqlValue],
+    key: &[String],
+    key_values: &[SqlValue],
+) -> String {
+    format!(
+        "UPDATE {} SET {} WHERE {}",
+        format_qualified_table(dialect, table),
+        assignments(dialect, columns, values, ", "),
+        assignments(dialect, key, key_values, " AND ")
+    )
+}
+
+/// Build a DELETE of the row whose `key` columns hold `key_values`.
+pub fn delete_row_sql(
+    dialect: &dyn SqlDialect,
+    table: &str,
+    key: &[String],
+    key_values: &[SqlValue],
+) -> String {
+    format!(
+        "DELETE FROM {} WHERE {}",
+        format_qualified_table(dialect, table),
+        assignments(dialect, key, key_values, " AND ")
+    )
+}
+
+fn assignments(
+    dialect: &dyn SqlDialect,
+    columns: &[String],
+    values: &[SqlValue],
+    separator: &str,
+) -> String {
+    columns
+        .iter()
+        .zip(values)
+        .map(|(column, value)| {
+            format!(
+                "{} = {}",
+                dialect.quote_identifier(column),
+                dialect.to_literal(value)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(separator)
+}
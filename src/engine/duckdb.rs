@@ -0,0 +1,441 @@
+use super::{DbEngine, DbSession, RowStream};
+use crate::engine::dialect::{
+    any_row_sql, count_rows_sql, format_qualified_table, split_table_name, SqlDialect,
+};
+use crate::engine::filter::TableFilter;
+use crate::engine::value::SqlValue;
+use crate::util::dialects::duckdb::DUCKDB_DIALECT;
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use duckdb::types::{TimeUnit, Value};
+use duckdb::Connection;
+use futures::stream;
+
+pub struct DuckDbEngine;
+
+#[async_trait]
+impl DbEngine for DuckDbEngine {
+    async fn connect(&self, url: &str) -> Result<Box<dyn DbSession>> {
+        let path = parse_database_path(url)?;
+        let conn = if path == ":memory:" {
+            Connection::open_in_memory()
+        } else {
+            Connection::open(path)
+        }
+        .with_context(|| format!("Failed to open DuckDB database at {}", path))?;
+
+        Ok(Box::new(DuckDbSession {
+            conn,
+            in_transaction: false,
+        }))
+    }
+
+    fn dialect(&self) -> Option<&'static dyn SqlDialect> {
+        Some(&DUCKDB_DIALECT)
+    }
+}
+
+/// DuckDB session. The driver is synchronous and runs in-process, so calls are
+/// made through `block_in_place` to keep the async runtime responsive.
+pub struct DuckDbSession {
+    conn: Connection,
+    in_transaction: bool,
+}
+
+impl DuckDbSession {
+    fn query_values(&self, sql: &str) -> Result<(Vec<String>, Vec<Vec<Value>>)> {
+        tokio::task::block_in_place(|| {
+            let mut stmt = self.conn.prepare(sql)?;
+            let mut rows = stmt.query([])?;
+            let mut values = Vec::new();
+            while let Some(row) = rows.next()? {
+                let width = row.as_ref().column_count();
+                let mut record = Vec::with_capacity(width);
+                for idx in 0..width {
+                    record.push(row.get::<_, Value>(idx)?);
+                }
+                values.push(record);
+            }
+            let names = rows.as_ref().map(|s| s.column_names()).unwrap_or_default();
+            Ok((names, values))
+        })
+    }
+
+    fn run_batch(&self, sql: &str) -> Result<()> {
+        tokio::task::block_in_place(|| self.conn.execute_batch(sql))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DbSession for DuckDbSession {
+    fn dialect(&self) -> &'static dyn SqlDialect {
+        &DUCKDB_DIALECT
+    }
+
+    async fn start_consistent_snapshot(&mut self) -> Result<()> {
+        self.run_batch("BEGIN TRANSACTION")?;
+        self.in_transaction = true;
+        Ok(())
+    }
+
+    async fn list_tables(&mut self, filter: &TableFilter) -> Result<Vec<String>> {
+        let (_, rows) = self
+            .query_values(
+                "SELECT schema_name, table_name
+                 FROM duckdb_tables()
+                 WHERE database_name = current_database() AND NOT internal
+                 ORDER BY schema_name, table_name",
+            )
+            .context("Failed to list tables")?;
+
+        let mut tables: Vec<String> = rows
+            .into_iter()
+            .map(|row| {
+                let schema = value_to_string(&row[0]);
+                let table = value_to_string(&row[1]);
+                // Tables in the default schema keep their bare name
+                if schema == "main" {
+                    table
+                } else {
+                    format!("{}.{}", schema, table)
+                }
+            })
+            .collect();
+
+        filter.apply(&mut tables);
+
+        Ok(tables)
+    }
+
+    async fn show_create_table(&mut self, table: &str) -> Result<String> {
+        let (schema, name) = parse_table_name(table);
+        let (_, rows) = self.query_values(&format!(
+            "SELECT sql FROM duckdb_tables()
+             WHERE database_name = current_database()
+               AND schema_name = '{}' AND table_name = '{}'",
+            escape_literal(&schema),
+            escape_literal(&name)
+        ))?;
+
+        let create_stmt = rows
+            .first()
+            .map(|row| value_to_string(&row[0]))
+            .ok_or_else(|| anyhow!("Table {} not found", table))?;
+
+        let mut full_ddl = minify_create_table(&create_stmt);
+
+        let (_, index_rows) = self.query_values(&format!(
+            "SELECT sql FROM duckdb_indexes()
+             WHERE database_name = current_database()
+               AND schema_name = '{}' AND table_name = '{}' AND sql IS NOT NULL",
+            escape_literal(&schema),
+            escape_literal(&name)
+        ))?;
+
+        for row in index_rows {
+            full_ddl.push('\n');
+            full_ddl.push_str(&minify_create_table(&value_to_string(&row[0])));
+        }
+
+        Ok(full_ddl)
+    }
+
+    async fn stream_rows(&mut self, table: &str) -> Result<(Vec<String>, RowStream)> {
+        let select_sql = format!(
+            "SELECT * FROM {}",
+            format_qualified_table(&DUCKDB_DIALECT, table)
+        );
+        let (columns, rows) = self.query_values(&select_sql)?;
+
+        let value_rows: Vec<Result<Vec<SqlValue>>> = rows
+            .into_iter()
+            .map(|row| Ok(row.into_iter().map(convert_value).collect()))
+            .collect();
+
+        let row_stream = stream::iter(value_rows);
+        Ok((columns, Box::pin(row_stream)))
+    }
+
+    async fn approximate_row_count(&mut self, table: &str) -> Result<u64> {
+        let (schema, name) = parse_table_name(table);
+        let (_, rows) = self.query_values(&format!(
+            "SELECT estimated_size FROM duckdb_tables()
+             WHERE database_name = current_database()
+               AND schema_name = '{}' AND table_name = '{}'",
+            escape_literal(&schema),
+            escape_literal(&name)
+        ))?;
+
+        let count = match rows.first().map(|row| &row[0]) {
+            Some(Value::BigInt(v)) => (*v).max(0) as u64,
+            Some(Value::UBigInt(v)) => *v,
+            _ => 0,
+        };
+        Ok(count)
+    }
+
+    async fn has_rows(&mut self, table: &str) -> Result<bool> {
+        let (_, rows) = self.query_values(&any_row_sql(&DUCKDB_DIALECT, table))?;
+        Ok(!rows.is_empty())
+    }
+
+    async fn count_rows(&mut self, table: &str) -> Result<u64> {
+        let (_, rows) = self.query_values(&count_rows_sql(&DUCKDB_DIALECT, table))?;
+        match rows.first().map(|row| &row[0]) {
+            Some(Value::BigInt(v)) => Ok((*v).max(0) as u64),
+            Some(Value::UBigInt(v)) => Ok(*v),
+            other => bail!("Unexpected row count from DuckDB: {:?}", other),
+        }
+    }
+
+    async fn insert_batch(
+        &mut self,
+        table: &str,
+        column_names: &[String],
+        rows: &[Vec<SqlValue>],
+    ) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let sql = DUCKDB_DIALECT.insert_values_sql(table, column_names, rows);
+        self.run_batch(&sql)
+            .with_context(|| format!("Failed to insert batch into table '{}'", table))?;
+
+        Ok(())
+    }
+
+    async fn disable_constraints(&mut self) -> Result<()> {
+        // DuckDB cannot switch off foreign key enforcement
+        Ok(())
+    }
+
+    async fn enable_constraints(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn execute(&mut self, sql: &str) -> Result<()> {
+        self.run_batch(sql)
+            .context("Failed to execute SQL statement")?;
+        Ok(())
+    }
+
+    async fn commit(&mut self) -> Result<()> {
+        if self.in_transaction {
+            self.run_batch("COMMIT")?;
+            self.in_transaction = false;
+        }
+        Ok(())
+    }
+
+    async fn begin(&mut self) -> Result<bool> {
+        self.run_batch("BEGIN TRANSACTION")?;
+        self.in_transaction = true;
+        Ok(true)
+    }
+
+    async fn rollback(&mut self) -> Result<()> {
+        if self.in_transaction {
+            self.run_batch("ROLLBACK")?;
+            self.in_transaction = false;
+        }
+        Ok(())
+    }
+
+    async fn create_table_from_columns(
+        &mut self,
+        table: &str,
+        column_names: &[String],
+        column_types: &[SqlValue],
+    ) -> Result<()> {
+        let mut sql = String::new();
+
+        if let (Some(schema), _) = split_table_name(table) {
+            sql.push_str(&format!(
+                "CREATE SCHEMA IF NOT EXISTS {};\n",
+                DUCKDB_DIALECT.quote_identifier(schema)
+            ));
+        }
+
+        let qualified = format_qualified_table(&DUCKDB_DIALECT, table);
+        let mut column_defs = Vec::new();
+
+        for (col_name, col_type) in column_names.iter().zip(column_types.iter()) {
+            let col_quoted = DUCKDB_DIALECT.quote_identifier(col_name);
+            let type_str = duckdb_column_type(col_type);
+
+            let pk = if col_name == "id" && matches!(col_type, SqlValue::Int(_)) {
+                " PRIMARY KEY"
+            } else {
+                ""
+            };
+
+            column_defs.push(format!("{} {}{}", col_quoted, type_str, pk));
+        }
+
+        sql.push_str(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (\n    {}\n);",
+            qualified,
+            column_defs.join(",\n    ")
+        ));
+
+        self.run_batch(&sql).context("Failed to create table")?;
+
+        Ok(())
+    }
+}
+
+/// DuckDB column type used to store a value of the given kind
+fn duckdb_column_type(value: &SqlValue) -> &'static str {
+    match value {
+        SqlValue::Int(_) => "BIGINT",
+        SqlValue::Float(_) => "DOUBLE",
+        SqlValue::Decimal(_) => "DECIMAL(18,4)",
+        SqlValue::Bool(_) => "BOOLEAN",
+        SqlValue::String(_) => "VARCHAR",
+        SqlValue::Date { .. } => "DATE",
+        SqlValue::Time { .. } => "TIME",
+        SqlValue::Timestamp { .. } => "TIMESTAMP",
+        SqlValue::Bytes(_) => "BLOB",
+        SqlValue::Null => "VARCHAR",
+    }
+}
+
+fn convert_value(value: Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Boolean(v) => SqlValue::Bool(v),
+        Value::TinyInt(v) => SqlValue::Int(v as i64),
+        Value::SmallInt(v) => SqlValue::Int(v as i64),
+        Value::Int(v) => SqlValue::Int(v as i64),
+        Value::BigInt(v) => SqlValue::Int(v),
+        Value::UTinyInt(v) => SqlValue::Int(v as i64),
+        Value::USmallInt(v) => SqlValue::Int(v as i64),
+        Value::UInt(v) => SqlValue::Int(v as i64),
+        Value::UBigInt(v) => match i64::try_from(v) {
+            Ok(v) => SqlValue::Int(v),
+            Err(_) => SqlValue::Decimal(v.to_string()),
+        },
+        Value::HugeInt(v) => match i64::try_from(v) {
+            Ok(v) => SqlValue::Int(v),
+            Err(_) => SqlValue::Decimal(v.to_string()),
+        },
+        Value::UHugeInt(v) => SqlValue::Decimal(v.to_string()),
+        Value::Float(v) => SqlValue::Float(v as f64),
+        Value::Double(v) => SqlValue::Float(v),
+        Value::Decimal(v) => SqlValue::Decimal(v.to_string()),
+        Value::Text(v) | Value::Enum(v) => SqlValue::String(v),
+        Value::Blob(v) | Value::Geometry(v) => SqlValue::Bytes(v),
+        Value::Date32(days) => chrono::DateTime::from_timestamp(days as i64 * 86_400, 0)
+            .map(|dt| SqlValue::from_date(dt.date_naive()))
+            .unwrap_or(SqlValue::Null),
+        Value::Time64(unit, v) => {
+            let micros = unit.to_micros(v);
+            chrono::NaiveTime::from_num_seconds_from_midnight_opt(
+                (micros / 1_000_000) as u32,
+                ((micros % 1_000_000) * 1_000) as u32,
+            )
+            .map(SqlValue::from_time)
+            .unwrap_or(SqlValue::Null)
+        }
+        Value::Timestamp(unit, v) => {
+            chrono::DateTime::from_timestamp_micros(TimeUnit::to_micros(&unit, v))
+                .map(|dt| SqlValue::from_datetime(dt.naive_utc()))
+                .unwrap_or(SqlValue::Null)
+        }
+        // Intervals and nested types have no neutral representation; keep their text form
+        other => SqlValue::String(format!("{:?}", other)),
+    }
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::Text(s) => s.clone(),
+        Value::Null => String::new(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Accept `duckdb://path/to/file.duckdb`, `duckdb:///abs/path` or `duckdb://:memory:`
+fn parse_database_path(url: &str) -> Result<&str> {
+    match url.strip_prefix("duckdb://") {
+        Some(path) if !path.is_empty() => Ok(path),
+        Some(_) => bail!("DuckDB URL is missing a database path"),
+        None => bail!("DuckDB URLs must start with duckdb://"),
+    }
+}
+
+/// Minify CREATE statement to single line and add IF NOT EXISTS
+fn minify_create_table(create_stmt: &str) -> String {
+    let single_line = create_stmt
+        .lines()
+        .map(|l| l.trim())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let single_line = single_line.trim_end_matches(';');
+
+    for prefix in ["CREATE TABLE ", "CREATE INDEX ", "CREATE UNIQUE INDEX "] {
+        if let Some(rest) = single_line.strip_prefix(prefix) {
+            if !rest.starts_with("IF NOT EXISTS") {
+                return format!("{}IF NOT EXISTS {};", prefix, rest);
+            }
+        }
+    }
+
+    format!("{};", single_line)
+}
+
+fn escape_literal(value: &str) -> String {
+    value.replace('\'', "''")
+}
+
+fn parse_table_name(table: &str) -> (String, String) {
+    let (schema, name) = split_table_name(table);
+    (schema.unwrap_or("main").to_string(), name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_database_paths() {
+        assert_eq!(
+            parse_database_path("duckdb://snap.duckdb").unwrap(),
+            "snap.duckdb"
+        );
+        assert_eq!(
+            parse_database_path("duckdb:///tmp/a.db").unwrap(),
+            "/tmp/a.db"
+        );
+        assert!(parse_database_path("sqlite://a.db").is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn creates_and_reads_back_rows() {
+        let mut session = DuckDbEngine.connect("duckdb://:memory:").await.unwrap();
+        let columns = vec!["id".to_string(), "name".to_string()];
+        session
+            .create_table_from_columns(
+                "analytics.items",
+                &columns,
+                &[SqlValue::Int(0), SqlValue::String(String::new())],
+            )
+            .await
+            .unwrap();
+        session
+            .insert_batch(
+                "analytics.items",
+                &columns,
+                &[vec![SqlValue::Int(1), SqlValue::String("a'b".into())]],
+            )
+            .await
+            .unwrap();
+
+        let (names, mut rows) = session.stream_rows("analytics.items").await.unwrap();
+        assert_eq!(names, columns);
+        let row = futures::StreamExt::next(&mut rows).await.unwrap().unwrap();
+        assert_eq!(row, vec![SqlValue::Int(1), SqlValue::String("a'b".into())]);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::engine::dialect::split_table_name;
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+
+/// One `--tables` or `--exclude` entry
+#[derive(Debug, Clone)]
+pub enum TablePattern {
+    /// A name with `*` and `?` wildcards, see `table_matches`
+    Glob(String),
+    /// From `--tables-regex`/`--exclude-regex`: must match the whole name,
+    /// qualified or bare, ignoring case
+    Regex(Regex),
+}
+
+impl TablePattern {
+    pub fn regex(pattern: &str) -> Result<Self> {
+        RegexBuilder::new(&format!("^(?:{})$", pattern))
+            .case_insensitive(true)
+            .build()
+            .map(TablePattern::Regex)
+            .with_context(|| format!("Invalid table regex '{}'", pattern))
+    }
+
+    /// Whether the table (or view) `name`, as the provider lists it, matches
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            TablePattern::Glob(pattern) => table_matches(pattern, name),
+            TablePattern::Regex(regex) => {
+                regex.is_match(name) || regex.is_match(split_table_name(name).1)
+            }
+        }
+    }
+}
+
+/// Which tables and views `list_tables` and `list_views` return: those
+/// matching an include pattern, or all of them when there are none, less
+/// those matching an exclude pattern
+#[derive(Debug, Clone, Default)]
+pub struct TableFilter {
+    pub include: Vec<TablePattern>,
+    pub exclude: Vec<TablePattern>,
+}
+
+impl TableFilter {
+    /// `--tables` and `--exclude` patterns
+    pub fn new(tables: &[String], exclude: &[String]) -> Self {
+        let globs = |names: &[String]| names.iter().cloned().map(TablePattern::Glob).collect();
+        TableFilter {
+            include: globs(tables),
+            exclude: globs(exclude),
+        }
+    }
+
+    /// Add `--tables-regex` and `--exclude-regex` patterns
+    pub fn with_regexes(mut self, tables: &[String], exclude: &[String]) -> Result<Self> {
+        for pattern in tables {
+            self.include.push(TablePattern::regex(pattern)?);
+        }
+        for pattern in exclude {
+            self.exclude.push(TablePattern::regex(pattern)?);
+        }
+        Ok(self)
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.matches(name)))
+            && !self.exclude.iter().any(|p| p.matches(name))
+    }
+
+    /// Keep only the names that match
+    pub fn apply(&self, names: &mut Vec<String>) {
+        names.retain(|name| self.matches(name));
+    }
+}
+
+/// Whether the table `name` matches a `--tables` pattern, ignoring ASCII
+/// case: `orders` in any schema, `billing.orders`, or with `*` and `?`
+/// wildcards such as `billing.*` or `audit_*`. The schema in a pattern is
+/// ignored for providers that list bare names.
+pub fn table_matches(pattern: &str, name: &str) -> bool {
+    let (pattern_schema, pattern_table) = split_table_name(pattern);
+    let (schema, table) = split_table_name(name);
+    let schema_matches = match (pattern_schema, schema) {
+        (Some(p), Some(s)) => wildcard_matches(p, s),
+        _ => true,
+    };
+    schema_matches && wildcard_matches(pattern_table, table)
+}
+
+/// Whether `text` matches `pattern`, ignoring ASCII case, where `*` stands
+/// for any run of characters and `?` for any one
+pub fn wildcard_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_ascii_lowercase().chars().collect();
+    let text: Vec<char> = text.to_ascii_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was, and how much of the text it has taken
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // Let the `*` take one more character and try again
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_table_patterns() {
+        assert!(table_matches("orders", "billing.orders"));
+        assert!(table_matches("Billing.Orders", "billing.orders"));
+        assert!(!table_matches("public.orders", "billing.orders"));
+        assert!(table_matches("billing.*", "billing.orders"));
+        assert!(!table_matches("billing.*", "public.orders"));
+        assert!(table_matches("*.order*", "billing.orders"));
+        assert!(table_matches("log_*_2024", "public.log_api_2024"));
+        assert!(!table_matches("log_*_2024", "public.log_api_2024_old"));
+        assert!(!table_matches("order", "public.orders"));
+        assert!(table_matches("audit_*", "audit_login"));
+        assert!(table_matches("*_tmp", "import_tmp"));
+        assert!(table_matches("t?", "t1"));
+        assert!(!table_matches("t?", "t12"));
+        assert!(table_matches("hr.emp", "EMP"));
+    }
+
+    #[test]
+    fn filters_with_globs_and_regexes() {
+        let tables: Vec<String> = ["audit_login", "audit_tmp", "orders", "users"]
+            .map(String::from)
+            .to_vec();
+        let filter = TableFilter::new(&["audit_*".into()], &["*_tmp".into()])
+            .with_regexes(&["user(s)?".into()], &[])
+            .unwrap();
+        let mut kept = tables;
+        filter.apply(&mut kept);
+        assert_eq!(kept, ["audit_login", "users"]);
+
+        let filter = TableFilter::default()
+            .with_regexes(&[], &["public\\.(orders|users)".into()])
+            .unwrap();
+        assert!(!filter.matches("public.orders"));
+        assert!(filter.matches("billing.orders"));
+        assert!(TableFilter::default()
+            .with_regexes(&["(".into()], &[])
+            .is_err());
+    }
+}
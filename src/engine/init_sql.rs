@@ -0,0 +1,71 @@
+use super::dialect::SqlDialect;
+use super::{DbEngine, DbSession};
+use crate::util::splitter::{StatementSplitter, Syntax};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+
+/// Wrapper running `--init-sql` on every session it opens, such as
+/// `SET sql_mode = ''` to load a dump the server's strict mode refuses,
+/// before the session is used.
+pub struct InitSqlEngine {
+    inner: Box<dyn DbEngine>,
+    statements: Vec<String>,
+}
+
+impl InitSqlEngine {
+    /// Split `sql` into statements by the quoting rules of `inner`'s dialect
+    pub fn new(inner: Box<dyn DbEngine>, sql: &str) -> Self {
+        let syntax = inner.dialect().map_or(Syntax::STANDARD, |d| d.syntax());
+        let mut splitter = StatementSplitter::new(syntax);
+        let mut statements = Vec::new();
+        for line in sql.split_inclusive('\n') {
+            statements.extend(splitter.push_line(line));
+        }
+        statements.extend(splitter.finish());
+        statements.retain(|s| !s.trim().trim_end_matches(';').trim().is_empty());
+        Self { inner, statements }
+    }
+}
+
+#[async_trait]
+impl DbEngine for InitSqlEngine {
+    async fn connect(&self, url: &str) -> Result<Box<dyn DbSession>> {
+        let mut session = self.inner.connect(url).await?;
+        for sql in &self.statements {
+            session
+                .execute(sql)
+                .await
+                .with_context(|| format!("Failed to run --init-sql statement: {}", sql))?;
+        }
+        session.commit().await?;
+        Ok(session)
+    }
+
+    fn dialect(&self) -> Option<&'static dyn SqlDialect> {
+        self.inner.dialect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::sqlite::SqliteEngine;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn runs_init_sql_on_each_session() {
+        let engine = InitSqlEngine::new(
+            Box::new(SqliteEngine),
+            "PRAGMA foreign_keys = ON;\n-- comment\nCREATE TEMP TABLE t (note text DEFAULT 'a;b');",
+        );
+        assert_eq!(engine.statements.len(), 2);
+        let mut session = engine.connect("sqlite::memory:").await.unwrap();
+        session
+            .execute("INSERT INTO t DEFAULT VALUES")
+            .await
+            .unwrap();
+
+        let engine = InitSqlEngine::new(Box::new(SqliteEngine), "SELECT * FROM missing");
+        let error = engine.connect("sqlite::memory:").await.err().unwrap();
+        assert!(error.to_string().contains("--init-sql"));
+    }
+}
@@ -0,0 +1,202 @@
+use super::DbSession;
+use crate::engine::value::SqlValue;
+use anyhow::{bail, Result};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use std::ops::RangeInclusive;
+
+/// Position of a keyset scan through one table
+struct KeysetCursor<'a> {
+    session: &'a mut dyn DbSession,
+    table: String,
+    key: Vec<String>,
+    key_positions: Vec<usize>,
+    limit: usize,
+    after: Option<Vec<SqlValue>>,
+    first: Option<Vec<Vec<SqlValue>>>,
+    finished: bool,
+}
+
+/// Stream a table in primary key order, reading `chunk_rows` rows per query
+/// (`WHERE pk > last ORDER BY pk LIMIT n`). Every query is short-lived, so no
+/// single statement holds the table for the whole read. Without `chunk_rows`,
+/// or for tables without a usable primary key, this is plain `stream_rows`.
+pub async fn stream_rows_chunked<'a>(
+    session: &'a mut dyn DbSession,
+    table: &str,
+    chunk_rows: Option<usize>,
+) -> Result<(Vec<String>, BoxStream<'a, Result<Vec<SqlValue>>>)> {
+    let rows = stream_rows_after(session, table, chunk_rows, None).await?;
+    Ok((rows.columns, rows.rows))
+}
+
+/// A table's rows, as streamed by [`stream_rows_after`]
+pub struct KeysetRows<'a> {
+    pub columns: Vec<String>,
+    /// Where the key columns are in each row, when the rows come in key
+    /// order; a reader can note the last key it saw and pick up after it
+    pub key_positions: Option<Vec<usize>>,
+    pub rows: BoxStream<'a, Result<Vec<SqlValue>>>,
+}
+
+/// [`stream_rows_chunked`], starting after the row whose primary key is
+/// `after`
+pub async fn stream_rows_after<'a>(
+    session: &'a mut dyn DbSession,
+    table: &str,
+    chunk_rows: Option<usize>,
+    after: Option<Vec<SqlValue>>,
+) -> Result<KeysetRows<'a>> {
+    let resuming = after.is_some();
+    let unordered = |session: &'a mut dyn DbSession| async move {
+        if resuming {
+            bail!("'{}' can no longer be read in primary key order", table);
+        }
+        let (columns, rows) = session.stream_rows(table).await?;
+        Ok(KeysetRows {
+            columns,
+            key_positions: None,
+            rows,
+        })
+    };
+    let Some(chunk_rows) = chunk_rows else {
+        return unordered(session).await;
+    };
+
+    let key = session.primary_key(table).await?;
+    if key.is_empty() {
+        return unordered(session).await;
+    }
+
+    let limit = chunk_rows.max(1);
+    let (columns, first) = session
+        .read_chunk(table, &key, after.as_deref(), limit)
+        .await?;
+
+    // Key columns hidden from SELECT * cannot be paged on
+    let key_positions: Option<Vec<usize>> = key
+        .iter()
+        .map(|k| columns.iter().position(|c| c == k))
+        .collect();
+    let Some(key_positions) = key_positions else {
+        return unordered(session).await;
+    };
+
+    let cursor = KeysetCursor {
+        session,
+        table: table.to_string(),
+        key,
+        key_positions: key_positions.clone(),
+        limit,
+        after: None,
+        first: Some(first),
+        finished: false,
+    };
+
+    let chunks = stream::try_unfold(cursor, |mut cursor| async move {
+        let rows = match cursor.first.take() {
+            Some(rows) => rows,
+            None if cursor.finished => return Ok(None),
+            None => {
+                let (_, rows) = cursor
+                    .session
+                    .read_chunk(
+                        &cursor.table,
+                        &cursor.key,
+                        cursor.after.as_deref(),
+                        cursor.limit,
+                    )
+                    .await?;
+                rows
+            }
+        };
+
+        // A short chunk means the end of the table was reached
+        cursor.finished = rows.len() < cursor.limit;
+        let Some(last) = rows.last() else {
+            return Ok(None);
+        };
+        cursor.after = Some(
+            cursor
+                .key_positions
+                .iter()
+                .map(|&i| last[i].clone())
+                .collect(),
+        );
+
+        anyhow::Ok(Some((rows, cursor)))
+    });
+
+    let rows = chunks
+        .map_ok(|rows| stream::iter(rows.into_iter().map(Ok)))
+        .try_flatten();
+    Ok(KeysetRows {
+        columns,
+        key_positions: Some(key_positions),
+        rows: rows.boxed(),
+    })
+}
+
+/// Split the integer key space `min..=max` into at most `parts` contiguous,
+/// non-overlapping ranges of near equal width
+pub fn split_key_range(min: i64, max: i64, parts: usize) -> Vec<RangeInclusive<i64>> {
+    if min > max {
+        return Vec::new();
+    }
+
+    // Widen so spans near the i64 limits cannot overflow
+    let span = max as i128 - min as i128 + 1;
+    let parts = (parts.max(1) as i128).min(span);
+
+    (0..parts)
+        .map(|i| {
+            let start = min as i128 + span * i / parts;
+            let end = min as i128 + span * (i + 1) / parts - 1;
+            start as i64..=end as i64
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::sqlite::SqliteEngine;
+    use crate::engine::DbEngine;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pages_through_composite_keys() {
+        let mut session = SqliteEngine.connect("sqlite::memory:").await.unwrap();
+        session
+            .execute("CREATE TABLE t (a INTEGER, b TEXT, v TEXT, PRIMARY KEY (a, b))")
+            .await
+            .unwrap();
+        session
+            .execute("INSERT INTO t VALUES (2, 'x', 'e'), (1, 'b', 'b'), (1, 'a', 'a'), (1, 'c', 'c'), (2, 'a', 'd')")
+            .await
+            .unwrap();
+
+        let (columns, rows) = stream_rows_chunked(&mut *session, "t", Some(2))
+            .await
+            .unwrap();
+        let rows: Vec<Vec<SqlValue>> = rows.try_collect().await.unwrap();
+
+        assert_eq!(columns, vec!["a", "b", "v"]);
+        let values: Vec<SqlValue> = rows.into_iter().map(|r| r[2].clone()).collect();
+        assert_eq!(
+            values,
+            ["a", "b", "c", "d", "e"]
+                .iter()
+                .map(|v| SqlValue::String(v.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn splits_key_ranges_evenly() {
+        assert_eq!(split_key_range(1, 10, 3), vec![1..=3, 4..=6, 7..=10]);
+        assert_eq!(split_key_range(5, 6, 4), vec![5..=5, 6..=6]);
+        assert_eq!(split_key_range(7, 7, 1), vec![7..=7]);
+
+        let full = split_key_range(i64::MIN, i64::MAX, 2);
+        assert_eq!(full, vec![i64::MIN..=-1, 0..=i64::MAX]);
+    }
+}
@@ -1,23 +1,178 @@
+pub mod binlog;
+pub mod cdc;
+pub mod clickhouse;
+pub mod cloudsql;
+pub mod cockroach;
+pub mod ddl;
 pub mod dialect;
+#[cfg(feature = "duckdb")]
+pub mod duckdb;
+pub mod filter;
+pub mod init_sql;
+pub mod keyset;
+pub mod mssql;
 pub mod mysql;
+pub mod oracle;
+pub mod pgoutput;
+pub mod pool;
 pub mod postgres;
+pub mod rds_iam;
+pub mod redshift;
+pub mod retry;
+pub mod snowflake;
+pub mod sqlite;
+pub mod timeout;
 pub mod value;
 
+use crate::engine::ddl::Column;
 use crate::engine::dialect::SqlDialect;
+use crate::engine::filter::TableFilter;
+use crate::engine::pool::SessionPool;
 use crate::engine::value::SqlValue;
 use anyhow::Result;
 use async_trait::async_trait;
-use futures::Stream;
+use futures::{FutureExt, Stream, StreamExt};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::RangeInclusive;
 use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::Duration;
 
 /// Stream of rows from a database query
 pub type RowStream = Pin<Box<dyn Stream<Item = Result<Vec<SqlValue>>> + Send>>;
 
+/// The databases `--databases` names, checked against those on the server,
+/// or all of them when it names none (`--all-databases`)
+pub async fn select_databases(
+    session: &mut dyn DbSession,
+    names: &[String],
+) -> Result<Vec<String>> {
+    let available = session.list_databases().await?;
+    if names.is_empty() {
+        return Ok(available);
+    }
+    if let Some(missing) = names.iter().find(|name| !available.contains(name)) {
+        anyhow::bail!("Database '{}' does not exist on the source server", missing);
+    }
+    Ok(names.to_vec())
+}
+
+/// A stored procedure or function found by `list_routines`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Routine {
+    /// `PROCEDURE` or `FUNCTION`
+    pub kind: &'static str,
+    /// Name, qualified by schema where the provider has them
+    pub name: String,
+    /// Argument types that tell overloads apart, where the provider allows
+    /// them (PostgreSQL)
+    pub arguments: Option<String>,
+}
+
+impl fmt::Display for Routine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.arguments {
+            Some(arguments) => write!(f, "{}({})", self.name, arguments),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+/// Where the source's change stream stood when a consistent snapshot began,
+/// so a replica loaded from the dump can carry on from there
+/// (`--master-data`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplicationPosition {
+    /// MySQL binary log coordinates, with the executed GTID set when GTIDs
+    /// are in use
+    Binlog {
+        file: String,
+        position: u64,
+        gtid_set: Option<String>,
+    },
+    /// PostgreSQL write-ahead log location
+    Wal { lsn: String },
+}
+
+impl fmt::Display for ReplicationPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplicationPosition::Binlog {
+                file,
+                position,
+                gtid_set,
+            } => {
+                write!(f, "binlog {} position {}", file, position)?;
+                match gtid_set {
+                    Some(gtid_set) => write!(f, ", GTID set {}", gtid_set),
+                    None => Ok(()),
+                }
+            }
+            ReplicationPosition::Wal { lsn } => write!(f, "WAL LSN {}", lsn),
+        }
+    }
+}
+
+/// How `insert_batch` sends rows to the database
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InsertMode {
+    /// Multi-row INSERT with values rendered as SQL literals
+    #[default]
+    Literal,
+    /// INSERT with bind parameters, prepared once per statement shape
+    Prepared,
+    /// Bulk load through `COPY ... FROM STDIN`
+    Copy,
+}
+
+impl fmt::Display for InsertMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            InsertMode::Literal => "literal",
+            InsertMode::Prepared => "prepared",
+            InsertMode::Copy => "copy",
+        })
+    }
+}
+
+impl FromStr for InsertMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "literal" => Ok(InsertMode::Literal),
+            "prepared" => Ok(InsertMode::Prepared),
+            "copy" => Ok(InsertMode::Copy),
+            other => Err(format!(
+                "Unknown insert mode '{}' (expected literal, prepared or copy)",
+                other
+            )),
+        }
+    }
+}
+
 /// Database engine trait for provider abstraction
 #[async_trait]
 pub trait DbEngine: Send + Sync {
     /// Connect to a database using the provider's URL format
     async fn connect(&self, url: &str) -> Result<Box<dyn DbSession>>;
+
+    /// Pool of at most `size` sessions on `url`, connected as they are needed
+    fn connect_pool<'e>(&'e self, url: &str, size: usize) -> SessionPool<'e> {
+        let url = url.to_string();
+        SessionPool::new(size, move || {
+            let url = url.clone();
+            async move { self.connect(&url).await }.boxed()
+        })
+    }
+
+    /// Dialect of the provider's sessions, known without connecting; `None`
+    /// when it can only be told from a session
+    fn dialect(&self) -> Option<&'static dyn SqlDialect> {
+        None
+    }
 }
 
 /// Active database session for executing queries
@@ -26,16 +181,138 @@ pub trait DbSession: Send {
     /// Dialect helper for identifier/literal formatting
     fn dialect(&self) -> &'static dyn SqlDialect;
 
+    /// Whether a transaction is open, so that work since the last commit
+    /// would be lost with the connection. Providers that cannot tell say so.
+    fn in_transaction(&self) -> bool {
+        true
+    }
+
     /// Start a consistent snapshot transaction (REPEATABLE READ)
     async fn start_consistent_snapshot(&mut self) -> Result<()>;
 
-    /// List all tables matching include/exclude filters
-    /// Empty include = all tables; exclude list is applied after
-    async fn list_tables(&mut self, include: &[String], exclude: &[String]) -> Result<Vec<String>>;
+    /// Start a consistent snapshot as [`DbSession::start_consistent_snapshot`]
+    /// does, and return the replication position it matches; `None` when the
+    /// provider or server has none to report
+    async fn start_snapshot_at_position(&mut self) -> Result<Option<ReplicationPosition>> {
+        self.start_consistent_snapshot().await?;
+        Ok(None)
+    }
+
+    /// Create the logical replication slot `slot`, and a publication of the
+    /// same name over `tables`, before a snapshot is taken; returns where the
+    /// slot's changes start, or `None` when the provider needs no slot to
+    /// follow changes from a snapshot
+    async fn create_replication_slot(
+        &mut self,
+        _slot: &str,
+        _tables: &[String],
+    ) -> Result<Option<ReplicationPosition>> {
+        Ok(None)
+    }
+
+    /// List all tables matching `filter`
+    async fn list_tables(&mut self, filter: &TableFilter) -> Result<Vec<String>>;
 
     /// Get CREATE TABLE statement for a table (minified to single line)
     async fn show_create_table(&mut self, table: &str) -> Result<String>;
 
+    /// List views matching `filter`, like `list_tables`.
+    /// Providers that cannot read view definitions list none.
+    async fn list_views(&mut self, _filter: &TableFilter) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Get the CREATE VIEW statement for a view from `list_views`
+    async fn show_create_view(&mut self, view: &str) -> Result<String> {
+        anyhow::bail!("Cannot read the definition of view '{}'", view)
+    }
+
+    /// List the stored procedures and functions of the database. Providers
+    /// that cannot read routine definitions list none.
+    async fn list_routines(&mut self) -> Result<Vec<Routine>> {
+        Ok(Vec::new())
+    }
+
+    /// Get the statement creating a routine from `list_routines`
+    async fn show_create_routine(&mut self, routine: &Routine) -> Result<String> {
+        anyhow::bail!(
+            "Cannot read the definition of {} '{}'",
+            routine.kind.to_lowercase(),
+            routine
+        )
+    }
+
+    /// List the databases on the server other than the system ones, for
+    /// dumping or migrating several at once (MySQL)
+    async fn list_databases(&mut self) -> Result<Vec<String>> {
+        anyhow::bail!("Dumping several databases is not supported for this provider")
+    }
+
+    /// Create the database `name` on the server unless it exists; returns
+    /// whether it was created
+    async fn create_database(&mut self, _name: &str) -> Result<bool> {
+        anyhow::bail!("Creating databases is not supported by this provider")
+    }
+
+    /// List the scheduled events of the database (MySQL). Other providers
+    /// list none.
+    async fn list_events(&mut self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Get the CREATE EVENT statement for an event from `list_events`
+    async fn show_create_event(&mut self, event: &str) -> Result<String> {
+        anyhow::bail!("Cannot read the definition of event '{}'", event)
+    }
+
+    /// List the triggers on a table, in the order they fire. Providers that
+    /// cannot read trigger definitions list none.
+    async fn list_triggers(&mut self, _table: &str) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Get the CREATE TRIGGER statement for a trigger from `list_triggers`
+    async fn show_create_trigger(&mut self, table: &str, trigger: &str) -> Result<String> {
+        anyhow::bail!(
+            "Cannot read the definition of trigger '{}' on '{}'",
+            trigger,
+            table
+        )
+    }
+
+    /// Statements recreating the server's user accounts: those creating them,
+    /// run ahead of everything else, and the grants, run after everything
+    /// they may name exists
+    async fn user_statements(&mut self) -> Result<(Vec<String>, Vec<String>)> {
+        anyhow::bail!("Dumping users is not supported for this provider")
+    }
+
+    /// Statements creating the extensions the database relies on, run ahead
+    /// of any schema. Providers without extensions have none.
+    async fn extension_statements(&mut self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// COMMENT statements for a table and its columns, where the provider
+    /// keeps comments outside the table's DDL
+    async fn comment_statements(&mut self, _table: &str) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Statements giving a table its owner and the privileges granted on it,
+    /// where the provider keeps them per table
+    async fn privilege_statements(&mut self, _table: &str) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Statements that bring the sequences behind a table's columns where
+    /// they stand on the source, to run once its rows are loaded. With
+    /// `values`, each sequence is set to its current value; without, only
+    /// settings that would have blocked the load are restored.
+    async fn sequence_statements(&mut self, _table: &str, _values: bool) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
     /// Stream all rows from a table
     /// Returns rows as Vec<SqlValue> in column order
     async fn stream_rows(&mut self, table: &str) -> Result<(Vec<String>, RowStream)>;
@@ -43,6 +320,46 @@ pub trait DbSession: Send {
     /// Get approximate row count for a table (for progress indication)
     async fn approximate_row_count(&mut self, table: &str) -> Result<u64>;
 
+    /// Whether a table has any rows. Providers without a cheaper way read
+    /// up to the first one.
+    async fn has_rows(&mut self, table: &str) -> Result<bool> {
+        let (_, mut rows) = self.stream_rows(table).await?;
+        Ok(rows.next().await.transpose()?.is_some())
+    }
+
+    /// Exact number of rows in a table. Providers without a cheaper way
+    /// read them all.
+    async fn count_rows(&mut self, table: &str) -> Result<u64> {
+        let (_, mut rows) = self.stream_rows(table).await?;
+        let mut count = 0;
+        while let Some(row) = rows.next().await {
+            row?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Choose how later `insert_batch` calls send rows. Providers without
+    /// other modes only accept [`InsertMode::Literal`].
+    fn set_insert_mode(&mut self, mode: InsertMode) -> Result<()> {
+        if mode != InsertMode::Literal {
+            anyhow::bail!("Insert mode '{}' is not supported by this provider", mode);
+        }
+        Ok(())
+    }
+
+    /// Largest statement the server accepts in bytes (MySQL's
+    /// `max_allowed_packet`); `None` when there is no practical limit
+    fn max_statement_bytes(&self) -> Option<usize> {
+        None
+    }
+
+    /// Run a `COPY ... FROM STDIN` statement, sending `data` (rows in the
+    /// format the statement names) as its input
+    async fn copy_in(&mut self, _statement: &str, _data: &[u8]) -> Result<()> {
+        anyhow::bail!("COPY FROM STDIN is not supported by this provider")
+    }
+
     /// Insert a batch of rows into a table
     async fn insert_batch(
         &mut self,
@@ -57,12 +374,50 @@ pub trait DbSession: Send {
     /// Enable foreign key checks
     async fn enable_constraints(&mut self) -> Result<()>;
 
+    /// Switch the session to settings that speed up bulk loading at the cost
+    /// of durability and of trigger and constraint enforcement. Returns
+    /// `false` when the provider has no such settings.
+    async fn enable_fast_load(&mut self) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Abort any later statement that runs longer than `timeout`. Returns
+    /// `false` when the provider has no such setting.
+    async fn set_statement_timeout(&mut self, _timeout: Duration) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Have the server probe the connection with TCP keepalives every
+    /// `interval` and not end the session for sitting idle in a transaction.
+    /// Returns `false` when the provider has no such settings.
+    async fn set_keepalive(&mut self, _interval: Duration) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Drop the table's secondary indexes ahead of a bulk load and return the
+    /// statements that recreate them. Indexes backing constraints are kept.
+    async fn drop_indexes(&mut self, _table: &str) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
     /// Execute a raw SQL statement
     async fn execute(&mut self, sql: &str) -> Result<()>;
 
     /// Commit current transaction
     async fn commit(&mut self) -> Result<()>;
 
+    /// Open a transaction for the statements that follow, ended by `commit`
+    /// or `rollback`. Returns false for providers that apply each statement
+    /// on its own.
+    async fn begin(&mut self) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Undo the current transaction
+    async fn rollback(&mut self) -> Result<()> {
+        Ok(())
+    }
+
     /// Create a table from column definitions
     async fn create_table_from_columns(
         &mut self,
@@ -70,16 +425,254 @@ pub trait DbSession: Send {
         column_names: &[String],
         column_types: &[SqlValue],
     ) -> Result<()>;
+
+    /// Primary key columns in key order; empty when the table has none or the
+    /// provider cannot report them
+    async fn primary_key(&mut self, _table: &str) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Tables the table's foreign keys reference, named as `list_tables`
+    /// names them; empty when it has none or the provider cannot report them
+    async fn referenced_tables(&mut self, _table: &str) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Columns with their types, nullability and counters, as the catalog
+    /// describes them, for building the table on another engine; empty when
+    /// the provider cannot report them
+    async fn describe_columns(&mut self, _table: &str) -> Result<Vec<Column>> {
+        Ok(Vec::new())
+    }
+
+    /// The character set of each text column, where the provider keeps one
+    /// per column (MySQL); empty elsewhere
+    async fn column_charsets(&mut self, _table: &str) -> Result<Vec<(String, String)>> {
+        Ok(Vec::new())
+    }
+
+    /// Read at most `limit` rows whose primary key sorts after `after`, in key
+    /// order. Returns the column names alongside the rows.
+    async fn read_chunk(
+        &mut self,
+        _table: &str,
+        _key: &[String],
+        _after: Option<&[SqlValue]>,
+        _limit: usize,
+    ) -> Result<(Vec<String>, Vec<Vec<SqlValue>>)> {
+        anyhow::bail!("Chunked reads are not supported by this provider")
+    }
+
+    /// The primary key `offset` rows past `after` in key order; `None` when
+    /// the table ends first
+    async fn key_after(
+        &mut self,
+        _table: &str,
+        _key: &[String],
+        _after: Option<&[SqlValue]>,
+        _offset: usize,
+    ) -> Result<Option<Vec<SqlValue>>> {
+        anyhow::bail!("Keyed comparisons are not supported by this provider")
+    }
+
+    /// Read the rows whose primary key sorts after `after` and no later than
+    /// `upto`, in key order; a missing bound leaves that end open
+    async fn read_key_window(
+        &mut self,
+        _table: &str,
+        _key: &[String],
+        _after: Option<&[SqlValue]>,
+        _upto: Option<&[SqlValue]>,
+    ) -> Result<(Vec<String>, Vec<Vec<SqlValue>>)> {
+        anyhow::bail!("Keyed comparisons are not supported by this provider")
+    }
+
+    /// A digest of the rows [`DbSession::read_key_window`] would read,
+    /// comparable with one from another server of the same dialect; `None`
+    /// when the provider cannot compute one
+    async fn checksum_key_window(
+        &mut self,
+        _table: &str,
+        _key: &[String],
+        _after: Option<&[SqlValue]>,
+        _upto: Option<&[SqlValue]>,
+    ) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Export the open snapshot so other sessions can read the same data;
+    /// `None` when the provider cannot share snapshots between connections
+    async fn export_snapshot(&mut self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Start a snapshot transaction on an id from [`DbSession::export_snapshot`]
+    async fn import_snapshot(&mut self, _snapshot: &str) -> Result<()> {
+        anyhow::bail!("Shared snapshots are not supported by this provider")
+    }
+
+    /// Smallest and largest value of an integer key column; `None` when the
+    /// table is empty, the column is not integral or the provider cannot tell
+    async fn key_bounds(&mut self, _table: &str, _key: &str) -> Result<Option<(i64, i64)>> {
+        Ok(None)
+    }
+
+    /// Stream the rows whose integer `key` lies within `range`
+    async fn stream_key_range(
+        &mut self,
+        _table: &str,
+        _key: &str,
+        _range: RangeInclusive<i64>,
+    ) -> Result<(Vec<String>, RowStream)> {
+        anyhow::bail!("Key range reads are not supported by this provider")
+    }
+
+    /// Largest value of `column`, or `SqlValue::Null` when the table is empty
+    async fn column_max(&mut self, _table: &str, _column: &str) -> Result<SqlValue> {
+        anyhow::bail!("Incremental reads are not supported by this provider")
+    }
+
+    /// Stream the rows whose `column` is past `after` and no further than
+    /// `upto`, the rows a `--incremental` run has not copied yet
+    async fn stream_rows_since(
+        &mut self,
+        _table: &str,
+        _column: &str,
+        _after: &SqlValue,
+        _upto: &SqlValue,
+    ) -> Result<(Vec<String>, RowStream)> {
+        anyhow::bail!("Incremental reads are not supported by this provider")
+    }
+
+    /// Stream the rows of `table` that satisfy `condition`, an SQL expression
+    /// in the provider's own syntax
+    async fn stream_rows_where(
+        &mut self,
+        _table: &str,
+        _condition: &str,
+    ) -> Result<(Vec<String>, RowStream)> {
+        anyhow::bail!("Filtered reads are not supported by this provider")
+    }
+
+    /// `pgoutput` messages for the transactions committed after the last
+    /// position `slot` was advanced to, stopping after about `limit` of them;
+    /// the slot is left where it is
+    async fn peek_slot_changes(&mut self, _slot: &str, _limit: usize) -> Result<Vec<Vec<u8>>> {
+        anyhow::bail!("Logical replication is not supported by this provider")
+    }
+
+    /// Move `slot` past `lsn`, once the changes up to it are applied
+    async fn advance_slot(&mut self, _slot: &str, _lsn: &str) -> Result<()> {
+        anyhow::bail!("Logical replication is not supported by this provider")
+    }
+}
+
+/// Constructor for a registered engine
+pub type EngineFactory = dyn Fn() -> Box<dyn DbEngine> + Send + Sync;
+
+/// Engines known by provider name. Seeded with the built-in providers;
+/// downstream crates add their own through [`register_engine`].
+static REGISTRY: LazyLock<RwLock<HashMap<String, Arc<EngineFactory>>>> = LazyLock::new(|| {
+    let mut engines: HashMap<String, Arc<EngineFactory>> = HashMap::new();
+    let mut add = |names: &[&str], factory: Arc<EngineFactory>| {
+        for name in names {
+            engines.insert(name.to_string(), factory.clone());
+        }
+    };
+
+    add(
+        &["mysql", "mariadb"],
+        Arc::new(|| Box::new(mysql::MysqlEngine)),
+    );
+    add(
+        &["postgres"],
+        Arc::new(|| Box::new(postgres::PostgresEngine)),
+    );
+    add(
+        &["sqlserver", "mssql"],
+        Arc::new(|| Box::new(mssql::SqlServerEngine)),
+    );
+    add(&["sqlite"], Arc::new(|| Box::new(sqlite::SqliteEngine)));
+    add(
+        &["cockroachdb", "cockroach"],
+        Arc::new(|| Box::new(cockroach::CockroachEngine)),
+    );
+    add(
+        &["clickhouse"],
+        Arc::new(|| Box::new(clickhouse::ClickHouseEngine)),
+    );
+    add(&["oracle"], Arc::new(|| Box::new(oracle::OracleEngine)));
+    add(
+        &["redshift"],
+        Arc::new(|| Box::new(redshift::RedshiftEngine)),
+    );
+    add(
+        &["snowflake"],
+        Arc::new(|| Box::new(snowflake::SnowflakeEngine)),
+    );
+    #[cfg(feature = "duckdb")]
+    add(&["duckdb"], Arc::new(|| Box::new(duckdb::DuckDbEngine)));
+
+    RwLock::new(engines)
+});
+
+/// Register an engine under a provider name so `create_engine` (and the
+/// `--provider` flags) can resolve it. Names are case-insensitive; registering
+/// an existing name replaces the previous engine.
+pub fn register_engine<F>(name: &str, factory: F)
+where
+    F: Fn() -> Box<dyn DbEngine> + Send + Sync + 'static,
+{
+    REGISTRY
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name.to_lowercase(), Arc::new(factory));
 }
 
 /// Factory for creating database engines
 pub fn create_engine(provider: &str) -> Result<Box<dyn DbEngine>> {
-    match provider.to_lowercase().as_str() {
-        "mysql" => Ok(Box::new(mysql::MysqlEngine)),
-        "postgres" => Ok(Box::new(postgres::PostgresEngine)),
-        _ => Err(anyhow::anyhow!(
+    let name = provider.to_lowercase();
+    let factory = REGISTRY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&name)
+        .cloned();
+
+    match factory {
+        Some(factory) => Ok(factory()),
+        #[cfg(not(feature = "duckdb"))]
+        None if name == "duckdb" => Err(anyhow::anyhow!(
+            "DuckDB support is not compiled in; rebuild with `--features duckdb`"
+        )),
+        None => Err(anyhow::anyhow!(
             "Unsupported database provider: {}",
             provider
         )),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CustomEngine;
+
+    #[async_trait]
+    impl DbEngine for CustomEngine {
+        async fn connect(&self, _url: &str) -> Result<Box<dyn DbSession>> {
+            anyhow::bail!("custom engine reached")
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn resolves_registered_engines() {
+        assert!(create_engine("ACME").is_err());
+
+        register_engine("Acme", || Box::new(CustomEngine));
+        let engine = create_engine("acme").unwrap();
+        let err = engine.connect("acme://localhost").await.err().unwrap();
+        assert_eq!(err.to_string(), "custom engine reached");
+
+        assert!(create_engine("Postgres").is_ok());
+    }
+}
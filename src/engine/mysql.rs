@@ -1,12 +1,27 @@
-use super::{DbEngine, DbSession, RowStream};
-use crate::engine::dialect::SqlDialect;
+use super::{DbEngine, DbSession, InsertMode, ReplicationPosition, Routine, RowStream};
+use crate::engine::ddl::{Column, ColumnType};
+use crate::engine::dialect::{
+    any_row_sql, column_max_sql, count_rows_sql, filtered_select_sql, insert_params_sql,
+    key_bounds_sql, key_offset_sql, key_range_select_sql, key_window_checksum_sql,
+    key_window_select_sql, keyset_select_sql, select_rows_sql, watermark_select_sql, SqlDialect,
+    MAX_BIND_PARAMS,
+};
+use crate::engine::filter::TableFilter;
 use crate::engine::value::SqlValue;
 use crate::util::dialects::mysql::MYSQL_DIALECT;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
-use futures::stream;
-use sqlx::mysql::MySqlConnection;
+use futures::{stream, StreamExt};
+use sqlx::mysql::{MySql, MySqlArguments, MySqlConnection};
+use sqlx::query::Query;
 use sqlx::{Connection, Row};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+
+/// Rows buffered between the cursor task and the consumer of `stream_rows`
+const STREAM_BUFFER_ROWS: usize = 1024;
 
 pub struct MysqlEngine;
 
@@ -17,16 +32,197 @@ impl DbEngine for MysqlEngine {
             .await
             .context("Failed to connect to MySQL database")?;
 
-        Ok(Box::new(MysqlSession {
-            conn,
+        let mut session = MysqlSession {
+            conn: Arc::new(Mutex::new(conn)),
             in_transaction: false,
-        }))
+            flavor: ServerFlavor::Mysql,
+            insert_mode: InsertMode::Literal,
+            max_packet: None,
+        };
+
+        let version: String = sqlx::query_scalar("SELECT VERSION()")
+            .fetch_one(&mut *session.conn.lock().await)
+            .await
+            .context("Failed to read server version")?;
+        session.flavor = ServerFlavor::from_version(&version);
+
+        // Some proxies hide server variables; batches then go by row count only
+        let max_packet: Option<u64> =
+            sqlx::query_scalar("SELECT CAST(@@max_allowed_packet AS UNSIGNED)")
+                .fetch_one(&mut *session.conn.lock().await)
+                .await
+                .ok();
+        session.max_packet = max_packet.and_then(|bytes| usize::try_from(bytes).ok());
+
+        Ok(Box::new(session))
+    }
+
+    fn dialect(&self) -> Option<&'static dyn SqlDialect> {
+        Some(&MYSQL_DIALECT)
+    }
+}
+
+/// Server implementation behind the MySQL protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerFlavor {
+    Mysql,
+    MariaDb,
+}
+
+impl ServerFlavor {
+    /// MariaDB reports versions such as `10.11.6-MariaDB-log`
+    fn from_version(version: &str) -> Self {
+        if version.to_ascii_lowercase().contains("mariadb") {
+            ServerFlavor::MariaDb
+        } else {
+            ServerFlavor::Mysql
+        }
     }
 }
 
 pub struct MysqlSession {
-    conn: MySqlConnection,
+    /// Shared with the task feeding an open row stream; other calls wait for it
+    conn: Arc<Mutex<MySqlConnection>>,
     in_transaction: bool,
+    flavor: ServerFlavor,
+    insert_mode: InsertMode,
+    /// Server `max_allowed_packet`, read at connect time
+    max_packet: Option<usize>,
+}
+
+impl MysqlSession {
+    /// Columns whose values are read and written. Invisible columns
+    /// (including MariaDB system-versioning periods) are left out, and so
+    /// are generated ones, which the server computes.
+    async fn column_names(&mut self, table: &str) -> Result<Vec<String>> {
+        let query = format!(
+            "SELECT COLUMN_NAME FROM information_schema.COLUMNS \
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = '{}' \
+             AND EXTRA NOT LIKE '%INVISIBLE%' \
+             AND EXTRA NOT LIKE '%VIRTUAL GENERATED%' \
+             AND EXTRA NOT LIKE '%STORED GENERATED%' \
+             ORDER BY ORDINAL_POSITION",
+            table.replace('\'', "''")
+        );
+        let col_rows = sqlx::query(&query)
+            .fetch_all(&mut *self.conn.lock().await)
+            .await?;
+
+        Ok(col_rows.iter().map(|row| row.get::<String, _>(0)).collect())
+    }
+
+    /// Run `sql` and stream its rows. Rows are read through a server-side
+    /// cursor on a background task; the channel bounds how far it runs ahead
+    /// of the consumer.
+    async fn stream_query(&mut self, sql: String, column_count: usize) -> RowStream {
+        let mut conn = self.conn.clone().lock_owned().await;
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER_ROWS);
+        tokio::spawn(async move {
+            let mut rows = sqlx::query(&sql).fetch(&mut *conn);
+            while let Some(row) = rows.next().await {
+                let values = row
+                    .map(|row| {
+                        (0..column_count)
+                            .map(|i| convert_sqlx_value(&row, i))
+                            .collect()
+                    })
+                    .map_err(anyhow::Error::from);
+                let failed = values.is_err();
+                if tx.send(values).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        Box::pin(stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|row| (row, rx))
+        }))
+    }
+
+    /// Insert rows through bound parameters, as many rows per statement as
+    /// the placeholder limit allows
+    async fn insert_prepared(
+        &mut self,
+        table: &str,
+        column_names: &[String],
+        rows: &[Vec<SqlValue>],
+    ) -> Result<()> {
+        let rows_per_statement = (MAX_BIND_PARAMS / column_names.len().max(1)).max(1);
+        let mut conn = self.conn.lock().await;
+
+        for chunk in rows.chunks(rows_per_statement) {
+            let sql = insert_params_sql(&MYSQL_DIALECT, table, column_names, chunk.len(), |_| {
+                "?".to_string()
+            });
+            let query = chunk
+                .iter()
+                .flatten()
+                .fold(sqlx::query(&sql), |query, value| bind_value(query, value));
+            query
+                .execute(&mut *conn)
+                .await
+                .with_context(|| format!("Failed to insert batch into table '{}'", table))?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether `table` is a MariaDB SEQUENCE object rather than a base table
+    async fn is_sequence(&mut self, table: &str) -> Result<bool> {
+        if self.flavor != ServerFlavor::MariaDb {
+            return Ok(false);
+        }
+
+        let table_type: Option<String> = sqlx::query_scalar(
+            "SELECT TABLE_TYPE FROM information_schema.TABLES \
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ?",
+        )
+        .bind(table)
+        .fetch_optional(&mut *self.conn.lock().await)
+        .await?;
+
+        Ok(table_type.as_deref() == Some("SEQUENCE"))
+    }
+
+    /// DDL for a sequence, followed by a SETVAL restoring its current position
+    async fn show_create_sequence(&mut self, table: &str) -> Result<String> {
+        let quoted = MYSQL_DIALECT.quote_identifier(table);
+        let row = sqlx::query(&format!("SHOW CREATE SEQUENCE {}", quoted))
+            .fetch_optional(&mut *self.conn.lock().await)
+            .await?
+            .context("No CREATE SEQUENCE result")?;
+        let create_stmt: String = row.get(1);
+
+        let next_value: i64 =
+            sqlx::query_scalar(&format!("SELECT next_not_cached_value FROM {}", quoted))
+                .fetch_one(&mut *self.conn.lock().await)
+                .await?;
+
+        Ok(format!(
+            "{};\nSELECT SETVAL({}, {}, 0);",
+            minify_create_table(&create_stmt),
+            quoted,
+            next_value
+        ))
+    }
+
+    /// Tables of the current database whose `SHOW FULL TABLES` type passes
+    /// `type_filter`, narrowed by `filter`
+    async fn list_objects(
+        &mut self,
+        type_filter: &str,
+        filter: &TableFilter,
+    ) -> Result<Vec<String>> {
+        let rows = sqlx::query(&format!("SHOW FULL TABLES WHERE {}", type_filter))
+            .fetch_all(&mut *self.conn.lock().await)
+            .await?;
+
+        let mut names: Vec<String> = rows.iter().map(|row| row.get::<String, _>(0)).collect();
+
+        filter.apply(&mut names);
+
+        Ok(names)
+    }
 }
 
 #[async_trait]
@@ -35,40 +231,282 @@ impl DbSession for MysqlSession {
         &MYSQL_DIALECT
     }
 
+    fn in_transaction(&self) -> bool {
+        self.in_transaction
+    }
+
     async fn start_consistent_snapshot(&mut self) -> Result<()> {
         sqlx::query("SET SESSION TRANSACTION ISOLATION LEVEL REPEATABLE READ")
-            .execute(&mut self.conn)
+            .execute(&mut *self.conn.lock().await)
             .await?;
         sqlx::query("START TRANSACTION WITH CONSISTENT SNAPSHOT")
-            .execute(&mut self.conn)
+            .execute(&mut *self.conn.lock().await)
             .await?;
         self.in_transaction = true;
         Ok(())
     }
 
-    async fn list_tables(&mut self, include: &[String], exclude: &[String]) -> Result<Vec<String>> {
-        let rows = sqlx::query("SHOW TABLES")
-            .fetch_all(&mut self.conn)
+    /// Writes are held off with a global read lock while the snapshot starts
+    /// and the binary log position is read, as mysqldump `--source-data` does
+    async fn start_snapshot_at_position(&mut self) -> Result<Option<ReplicationPosition>> {
+        let mut conn = self.conn.lock().await;
+        sqlx::query("FLUSH TABLES WITH READ LOCK")
+            .execute(&mut *conn)
+            .await
+            .context("Failed to lock tables for --master-data (needs the RELOAD privilege)")?;
+
+        let position = async {
+            sqlx::query("SET SESSION TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+                .execute(&mut *conn)
+                .await?;
+            sqlx::query("START TRANSACTION WITH CONSISTENT SNAPSHOT")
+                .execute(&mut *conn)
+                .await?;
+
+            // MySQL 8.2 renamed SHOW MASTER STATUS, and 8.4 dropped the old name
+            let status = match sqlx::query("SHOW BINARY LOG STATUS")
+                .fetch_optional(&mut *conn)
+                .await
+            {
+                Ok(status) => status,
+                Err(_) => sqlx::query("SHOW MASTER STATUS")
+                    .fetch_optional(&mut *conn)
+                    .await
+                    .context("Failed to read the binary log position")?,
+            };
+            // No row when binary logging is off
+            let Some(status) = status else {
+                return Ok(None);
+            };
+
+            let gtid_set: Option<String> = match self.flavor {
+                ServerFlavor::Mysql => status.try_get("Executed_Gtid_Set").ok(),
+                ServerFlavor::MariaDb => sqlx::query_scalar("SELECT @@GLOBAL.gtid_binlog_pos")
+                    .fetch_one(&mut *conn)
+                    .await
+                    .ok(),
+            };
+            Ok::<_, anyhow::Error>(Some(ReplicationPosition::Binlog {
+                file: status.try_get("File")?,
+                position: status.try_get("Position")?,
+                gtid_set: gtid_set
+                    .map(|set| set.replace('\n', ""))
+                    .filter(|set| !set.is_empty()),
+            }))
+        }
+        .await;
+
+        sqlx::query("UNLOCK TABLES").execute(&mut *conn).await?;
+        self.in_transaction = position.is_ok();
+        position
+    }
+
+    async fn list_tables(&mut self, filter: &TableFilter) -> Result<Vec<String>> {
+        // Views are listed by `list_views`; sequences stay, as they dump like tables
+        self.list_objects("Table_type <> 'VIEW'", filter)
             .await
-            .context("Failed to list tables")?;
+            .context("Failed to list tables")
+    }
+
+    async fn list_views(&mut self, filter: &TableFilter) -> Result<Vec<String>> {
+        self.list_objects("Table_type = 'VIEW'", filter)
+            .await
+            .context("Failed to list views")
+    }
+
+    async fn show_create_view(&mut self, view: &str) -> Result<String> {
+        let query = format!("SHOW CREATE VIEW `{}`", view.replace('`', "``"));
+        let row = sqlx::query(&query)
+            .fetch_optional(&mut *self.conn.lock().await)
+            .await?
+            .with_context(|| format!("View {} not found", view))?;
+
+        let create_stmt: String = row.get(1);
+        Ok(create_stmt)
+    }
+
+    async fn list_routines(&mut self) -> Result<Vec<Routine>> {
+        let rows = sqlx::query(
+            "SELECT ROUTINE_TYPE, ROUTINE_NAME FROM information_schema.ROUTINES \
+             WHERE ROUTINE_SCHEMA = DATABASE() ORDER BY ROUTINE_TYPE, ROUTINE_NAME",
+        )
+        .fetch_all(&mut *self.conn.lock().await)
+        .await
+        .context("Failed to list routines")?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let kind: String = row.get(0);
+                Routine {
+                    kind: if kind == "PROCEDURE" {
+                        "PROCEDURE"
+                    } else {
+                        "FUNCTION"
+                    },
+                    name: row.get(1),
+                    arguments: None,
+                }
+            })
+            .collect())
+    }
+
+    async fn show_create_routine(&mut self, routine: &Routine) -> Result<String> {
+        let query = format!(
+            "SHOW CREATE {} `{}`",
+            routine.kind,
+            routine.name.replace('`', "``")
+        );
+        let row = sqlx::query(&query)
+            .fetch_optional(&mut *self.conn.lock().await)
+            .await?
+            .with_context(|| format!("Routine {} not found", routine))?;
+
+        // The definition reads as NULL without the privileges to see it
+        let create_stmt: Option<String> = row.get(2);
+        let create_stmt = create_stmt.with_context(|| {
+            format!(
+                "Not allowed to read the definition of {}; it needs SHOW_ROUTINE or ownership",
+                routine
+            )
+        })?;
+        Ok(create_stmt)
+    }
 
-        let mut tables: Vec<String> = rows.iter().map(|row| row.get::<String, _>(0)).collect();
+    async fn user_statements(&mut self) -> Result<(Vec<String>, Vec<String>)> {
+        // Hashes of caching_sha2_password hold raw bytes; MySQL 8.0.17+ can
+        // print them as hex instead
+        let _ = sqlx::query("SET SESSION print_identified_with_as_hex = ON")
+            .execute(&mut *self.conn.lock().await)
+            .await;
 
-        if !include.is_empty() {
-            tables.retain(|t| include.contains(t));
+        let accounts: Vec<(String, String)> = sqlx::query_as(
+            "SELECT CAST(User AS CHAR), CAST(Host AS CHAR) FROM mysql.user \
+             WHERE User NOT IN ('', 'mysql.sys', 'mysql.session', 'mysql.infoschema', 'mariadb.sys') \
+             ORDER BY User, Host",
+        )
+        .fetch_all(&mut *self.conn.lock().await)
+        .await
+        .context("Failed to list users; reading mysql.user needs SELECT on it")?;
+
+        let mut creates = Vec::with_capacity(accounts.len());
+        let mut grants = Vec::new();
+        for (user, host) in accounts {
+            let account = format!(
+                "{}@{}",
+                MYSQL_DIALECT.to_literal(&SqlValue::String(user)),
+                MYSQL_DIALECT.to_literal(&SqlValue::String(host))
+            );
+            let create: String = sqlx::query_scalar(&format!("SHOW CREATE USER {}", account))
+                .fetch_one(&mut *self.conn.lock().await)
+                .await
+                .with_context(|| format!("Failed to read user {}", account))?;
+            creates.push(match create.strip_prefix("CREATE USER ") {
+                Some(rest) => format!("CREATE USER IF NOT EXISTS {}", rest),
+                None => create,
+            });
+
+            let rows: Vec<String> = sqlx::query_scalar(&format!("SHOW GRANTS FOR {}", account))
+                .fetch_all(&mut *self.conn.lock().await)
+                .await
+                .with_context(|| format!("Failed to read the grants of {}", account))?;
+            grants.extend(rows);
         }
+        Ok((creates, grants))
+    }
+
+    async fn list_databases(&mut self) -> Result<Vec<String>> {
+        let databases = sqlx::query_scalar(
+            "SELECT CAST(SCHEMA_NAME AS CHAR) FROM information_schema.SCHEMATA \
+             WHERE SCHEMA_NAME NOT IN ('mysql', 'information_schema', 'performance_schema', 'sys') \
+             ORDER BY SCHEMA_NAME",
+        )
+        .fetch_all(&mut *self.conn.lock().await)
+        .await
+        .context("Failed to list databases")?;
 
-        if !exclude.is_empty() {
-            tables.retain(|t| !exclude.contains(t));
+        Ok(databases)
+    }
+
+    async fn create_database(&mut self, name: &str) -> Result<bool> {
+        let exists: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM information_schema.SCHEMATA WHERE SCHEMA_NAME = ?",
+        )
+        .bind(name)
+        .fetch_one(&mut *self.conn.lock().await)
+        .await?;
+        if exists > 0 {
+            return Ok(false);
         }
+        let create = format!(
+            "CREATE DATABASE IF NOT EXISTS {}",
+            MYSQL_DIALECT.quote_identifier(name)
+        );
+        sqlx::query(&create)
+            .execute(&mut *self.conn.lock().await)
+            .await
+            .with_context(|| format!("Failed to create database '{}'", name))?;
+        Ok(true)
+    }
 
-        Ok(tables)
+    async fn list_events(&mut self) -> Result<Vec<String>> {
+        let events = sqlx::query_scalar(
+            "SELECT EVENT_NAME FROM information_schema.EVENTS \
+             WHERE EVENT_SCHEMA = DATABASE() ORDER BY EVENT_NAME",
+        )
+        .fetch_all(&mut *self.conn.lock().await)
+        .await
+        .context("Failed to list events")?;
+
+        Ok(events)
+    }
+
+    async fn show_create_event(&mut self, event: &str) -> Result<String> {
+        let query = format!("SHOW CREATE EVENT `{}`", event.replace('`', "``"));
+        let row = sqlx::query(&query)
+            .fetch_optional(&mut *self.conn.lock().await)
+            .await?
+            .with_context(|| format!("Event {} not found", event))?;
+
+        // Columns: Event, sql_mode, time_zone, Create Event, ...
+        let create_stmt: String = row.get(3);
+        Ok(create_stmt)
+    }
+
+    async fn list_triggers(&mut self, table: &str) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            "SELECT TRIGGER_NAME FROM information_schema.TRIGGERS \
+             WHERE EVENT_OBJECT_SCHEMA = DATABASE() AND EVENT_OBJECT_TABLE = ? \
+             ORDER BY ACTION_TIMING, EVENT_MANIPULATION, ACTION_ORDER",
+        )
+        .bind(table)
+        .fetch_all(&mut *self.conn.lock().await)
+        .await
+        .with_context(|| format!("Failed to list triggers on {}", table))?;
+
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    async fn show_create_trigger(&mut self, _table: &str, trigger: &str) -> Result<String> {
+        let query = format!("SHOW CREATE TRIGGER `{}`", trigger.replace('`', "``"));
+        let row = sqlx::query(&query)
+            .fetch_optional(&mut *self.conn.lock().await)
+            .await?
+            .with_context(|| format!("Trigger {} not found", trigger))?;
+
+        // Columns: Trigger, sql_mode, SQL Original Statement, ...
+        let create_stmt: String = row.get(2);
+        Ok(create_stmt)
     }
 
     async fn show_create_table(&mut self, table: &str) -> Result<String> {
+        if self.is_sequence(table).await? {
+            return self.show_create_sequence(table).await;
+        }
+
         let query = format!("SHOW CREATE TABLE `{}`", table.replace('`', "``"));
         let row = sqlx::query(&query)
-            .fetch_optional(&mut self.conn)
+            .fetch_optional(&mut *self.conn.lock().await)
             .await?
             .context("No CREATE TABLE result")?;
 
@@ -79,32 +517,16 @@ impl DbSession for MysqlSession {
     }
 
     async fn stream_rows(&mut self, table: &str) -> Result<(Vec<String>, RowStream)> {
-        let query = format!(
-            "SELECT COLUMN_NAME FROM information_schema.COLUMNS \
-             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = '{}' \
-             ORDER BY ORDINAL_POSITION",
-            table.replace('\'', "''")
-        );
-        let col_rows = sqlx::query(&query).fetch_all(&mut self.conn).await?;
+        let columns = self.column_names(table).await?;
 
-        let columns: Vec<String> = col_rows.iter().map(|row| row.get::<String, _>(0)).collect();
-
-        let data_query = format!("SELECT * FROM `{}`", table.replace('`', "``"));
-        let rows = sqlx::query(&data_query).fetch_all(&mut self.conn).await?;
-
-        let value_rows: Vec<Result<Vec<SqlValue>>> = rows
-            .iter()
-            .map(|row| {
-                let mut values = Vec::with_capacity(columns.len());
-                for i in 0..columns.len() {
-                    values.push(convert_sqlx_value(row, i));
-                }
-                Ok(values)
-            })
-            .collect();
+        // A sequence's state is carried by the SETVAL in its DDL
+        if self.is_sequence(table).await? {
+            return Ok((columns, Box::pin(stream::empty())));
+        }
 
-        let row_stream = stream::iter(value_rows);
-        Ok((columns, Box::pin(row_stream)))
+        let data_query = select_rows_sql(&MYSQL_DIALECT, table, &columns);
+        let row_stream = self.stream_query(data_query, columns.len()).await;
+        Ok((columns, row_stream))
     }
 
     async fn approximate_row_count(&mut self, table: &str) -> Result<u64> {
@@ -115,12 +537,26 @@ impl DbSession for MysqlSession {
         );
 
         let count: Option<u64> = sqlx::query_scalar(&query)
-            .fetch_optional(&mut self.conn)
+            .fetch_optional(&mut *self.conn.lock().await)
             .await?
             .flatten();
         Ok(count.unwrap_or(0))
     }
 
+    async fn has_rows(&mut self, table: &str) -> Result<bool> {
+        let row: Option<i64> = sqlx::query_scalar(&any_row_sql(&MYSQL_DIALECT, table))
+            .fetch_optional(&mut *self.conn.lock().await)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    async fn count_rows(&mut self, table: &str) -> Result<u64> {
+        let count: i64 = sqlx::query_scalar(&count_rows_sql(&MYSQL_DIALECT, table))
+            .fetch_one(&mut *self.conn.lock().await)
+            .await?;
+        Ok(count.max(0) as u64)
+    }
+
     async fn insert_batch(
         &mut self,
         table: &str,
@@ -131,38 +567,83 @@ impl DbSession for MysqlSession {
             return Ok(());
         }
 
+        if self.insert_mode == InsertMode::Prepared {
+            return self.insert_prepared(table, column_names, rows).await;
+        }
+
         let sql = MYSQL_DIALECT.insert_values_sql(table, column_names, rows);
         sqlx::query(&sql)
-            .execute(&mut self.conn)
+            .execute(&mut *self.conn.lock().await)
             .await
             .with_context(|| format!("Failed to insert batch into table '{}'", table))?;
 
         Ok(())
     }
 
+    fn max_statement_bytes(&self) -> Option<usize> {
+        self.max_packet
+    }
+
+    fn set_insert_mode(&mut self, mode: InsertMode) -> Result<()> {
+        if mode == InsertMode::Copy {
+            anyhow::bail!("Insert mode 'copy' is only supported by PostgreSQL");
+        }
+        self.insert_mode = mode;
+        Ok(())
+    }
+
     async fn disable_constraints(&mut self) -> Result<()> {
         sqlx::query("SET FOREIGN_KEY_CHECKS=0")
-            .execute(&mut self.conn)
+            .execute(&mut *self.conn.lock().await)
             .await?;
         sqlx::query("SET UNIQUE_CHECKS=0")
-            .execute(&mut self.conn)
+            .execute(&mut *self.conn.lock().await)
             .await?;
         Ok(())
     }
 
     async fn enable_constraints(&mut self) -> Result<()> {
         sqlx::query("SET FOREIGN_KEY_CHECKS=1")
-            .execute(&mut self.conn)
+            .execute(&mut *self.conn.lock().await)
             .await?;
         sqlx::query("SET UNIQUE_CHECKS=1")
-            .execute(&mut self.conn)
+            .execute(&mut *self.conn.lock().await)
             .await?;
         Ok(())
     }
 
+    async fn set_keepalive(&mut self, interval: Duration) -> Result<bool> {
+        // The driver exposes no TCP keepalive; keep the server's idle timeout
+        // well above the ping interval instead
+        let seconds = interval.as_secs().max(1) * 2;
+        sqlx::query(&format!(
+            "SET SESSION wait_timeout = GREATEST(@@session.wait_timeout, {})",
+            seconds
+        ))
+        .execute(&mut *self.conn.lock().await)
+        .await?;
+        Ok(true)
+    }
+
+    async fn set_statement_timeout(&mut self, timeout: Duration) -> Result<bool> {
+        // MySQL's limit covers SELECT statements only; MariaDB's covers all
+        let sql = match self.flavor {
+            ServerFlavor::Mysql => {
+                format!("SET SESSION max_execution_time = {}", timeout.as_millis())
+            }
+            ServerFlavor::MariaDb => {
+                format!("SET SESSION max_statement_time = {}", timeout.as_secs_f64())
+            }
+        };
+        sqlx::query(&sql)
+            .execute(&mut *self.conn.lock().await)
+            .await?;
+        Ok(true)
+    }
+
     async fn execute(&mut self, sql: &str) -> Result<()> {
         sqlx::query(sql)
-            .execute(&mut self.conn)
+            .execute(&mut *self.conn.lock().await)
             .await
             .context("Failed to execute SQL statement")?;
         Ok(())
@@ -170,7 +651,27 @@ impl DbSession for MysqlSession {
 
     async fn commit(&mut self) -> Result<()> {
         if self.in_transaction {
-            sqlx::query("COMMIT").execute(&mut self.conn).await?;
+            sqlx::query("COMMIT")
+                .execute(&mut *self.conn.lock().await)
+                .await?;
+            self.in_transaction = false;
+        }
+        Ok(())
+    }
+
+    async fn begin(&mut self) -> Result<bool> {
+        sqlx::query("START TRANSACTION")
+            .execute(&mut *self.conn.lock().await)
+            .await?;
+        self.in_transaction = true;
+        Ok(true)
+    }
+
+    async fn rollback(&mut self) -> Result<()> {
+        if self.in_transaction {
+            sqlx::query("ROLLBACK")
+                .execute(&mut *self.conn.lock().await)
+                .await?;
             self.in_transaction = false;
         }
         Ok(())
@@ -215,12 +716,247 @@ impl DbSession for MysqlSession {
         sql.push_str(") ENGINE=InnoDB DEFAULT CHARSET=utf8mb4");
 
         sqlx::query(&sql)
-            .execute(&mut self.conn)
+            .execute(&mut *self.conn.lock().await)
             .await
             .context("Failed to create table")?;
 
         Ok(())
     }
+
+    async fn primary_key(&mut self, table: &str) -> Result<Vec<String>> {
+        let key: Vec<String> = sqlx::query_scalar(
+            "SELECT COLUMN_NAME FROM information_schema.KEY_COLUMN_USAGE \
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? \
+             AND CONSTRAINT_NAME = 'PRIMARY' \
+             ORDER BY ORDINAL_POSITION",
+        )
+        .bind(table)
+        .fetch_all(&mut *self.conn.lock().await)
+        .await?;
+        Ok(key)
+    }
+
+    async fn referenced_tables(&mut self, table: &str) -> Result<Vec<String>> {
+        // Tables of other databases are not among those `list_tables` names
+        let tables: Vec<String> = sqlx::query_scalar(
+            "SELECT DISTINCT REFERENCED_TABLE_NAME \
+             FROM information_schema.REFERENTIAL_CONSTRAINTS \
+             WHERE CONSTRAINT_SCHEMA = DATABASE() AND TABLE_NAME = ? \
+             AND UNIQUE_CONSTRAINT_SCHEMA = DATABASE() \
+             ORDER BY REFERENCED_TABLE_NAME",
+        )
+        .bind(table)
+        .fetch_all(&mut *self.conn.lock().await)
+        .await?;
+        Ok(tables)
+    }
+
+    async fn describe_columns(&mut self, table: &str) -> Result<Vec<Column>> {
+        let rows: Vec<(String, String, String, String)> = sqlx::query_as(
+            "SELECT COLUMN_NAME, CAST(COLUMN_TYPE AS CHAR), IS_NULLABLE, EXTRA \
+             FROM information_schema.COLUMNS \
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? \
+             AND EXTRA NOT LIKE '%INVISIBLE%' \
+             AND EXTRA NOT LIKE '%VIRTUAL GENERATED%' \
+             AND EXTRA NOT LIKE '%STORED GENERATED%' \
+             ORDER BY ORDINAL_POSITION",
+        )
+        .bind(table)
+        .fetch_all(&mut *self.conn.lock().await)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(name, declared, nullable, extra)| Column {
+                name,
+                column_type: ColumnType::parse(&declared),
+                declared,
+                nullable: nullable == "YES",
+                auto_increment: extra.to_ascii_lowercase().contains("auto_increment"),
+            })
+            .collect())
+    }
+
+    async fn column_charsets(&mut self, table: &str) -> Result<Vec<(String, String)>> {
+        let charsets = sqlx::query_as(
+            "SELECT COLUMN_NAME, CAST(CHARACTER_SET_NAME AS CHAR) \
+             FROM information_schema.COLUMNS \
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? \
+             AND CHARACTER_SET_NAME IS NOT NULL \
+             ORDER BY ORDINAL_POSITION",
+        )
+        .bind(table)
+        .fetch_all(&mut *self.conn.lock().await)
+        .await?;
+        Ok(charsets)
+    }
+
+    async fn read_chunk(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        limit: usize,
+    ) -> Result<(Vec<String>, Vec<Vec<SqlValue>>)> {
+        let columns = self.column_names(table).await?;
+
+        let select_sql = keyset_select_sql(&MYSQL_DIALECT, table, &columns, key, after, limit);
+        let rows = sqlx::query(&select_sql)
+            .fetch_all(&mut *self.conn.lock().await)
+            .await?
+            .iter()
+            .map(|row| {
+                (0..columns.len())
+                    .map(|i| convert_sqlx_value(row, i))
+                    .collect()
+            })
+            .collect();
+
+        Ok((columns, rows))
+    }
+
+    async fn key_after(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        offset: usize,
+    ) -> Result<Option<Vec<SqlValue>>> {
+        let offset_sql = key_offset_sql(&MYSQL_DIALECT, table, key, after, offset);
+        let row = sqlx::query(&offset_sql)
+            .fetch_optional(&mut *self.conn.lock().await)
+            .await?;
+        Ok(row.map(|row| {
+            (0..key.len())
+                .map(|i| convert_sqlx_value(&row, i))
+                .collect()
+        }))
+    }
+
+    async fn read_key_window(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        upto: Option<&[SqlValue]>,
+    ) -> Result<(Vec<String>, Vec<Vec<SqlValue>>)> {
+        let columns = self.column_names(table).await?;
+
+        let select_sql = key_window_select_sql(&MYSQL_DIALECT, table, &columns, key, after, upto);
+        let rows = sqlx::query(&select_sql)
+            .fetch_all(&mut *self.conn.lock().await)
+            .await?
+            .iter()
+            .map(|row| {
+                (0..columns.len())
+                    .map(|i| convert_sqlx_value(row, i))
+                    .collect()
+            })
+            .collect();
+
+        Ok((columns, rows))
+    }
+
+    async fn checksum_key_window(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        upto: Option<&[SqlValue]>,
+    ) -> Result<Option<String>> {
+        let columns = self.column_names(table).await?;
+        let Some(checksum_sql) =
+            key_window_checksum_sql(&MYSQL_DIALECT, table, &columns, key, after, upto)
+        else {
+            return Ok(None);
+        };
+        let row = sqlx::query(&checksum_sql)
+            .fetch_one(&mut *self.conn.lock().await)
+            .await?;
+        Ok(Some(match convert_sqlx_value(&row, 0) {
+            SqlValue::String(checksum) => checksum,
+            SqlValue::Bytes(checksum) => String::from_utf8_lossy(&checksum).into_owned(),
+            other => format!("{:?}", other),
+        }))
+    }
+
+    async fn key_bounds(&mut self, table: &str, key: &str) -> Result<Option<(i64, i64)>> {
+        let bounds_sql = key_bounds_sql(&MYSQL_DIALECT, table, key);
+        let row = sqlx::query(&bounds_sql)
+            .fetch_one(&mut *self.conn.lock().await)
+            .await?;
+
+        match (convert_sqlx_value(&row, 0), convert_sqlx_value(&row, 1)) {
+            (SqlValue::Int(min), SqlValue::Int(max)) => Ok(Some((min, max))),
+            _ => Ok(None),
+        }
+    }
+
+    async fn stream_key_range(
+        &mut self,
+        table: &str,
+        key: &str,
+        range: RangeInclusive<i64>,
+    ) -> Result<(Vec<String>, RowStream)> {
+        let columns = self.column_names(table).await?;
+
+        let select_sql = key_range_select_sql(&MYSQL_DIALECT, table, &columns, key, &range);
+        let row_stream = self.stream_query(select_sql, columns.len()).await;
+        Ok((columns, row_stream))
+    }
+
+    async fn column_max(&mut self, table: &str, column: &str) -> Result<SqlValue> {
+        let max_sql = column_max_sql(&MYSQL_DIALECT, table, column);
+        let row = sqlx::query(&max_sql)
+            .fetch_one(&mut *self.conn.lock().await)
+            .await?;
+        Ok(convert_sqlx_value(&row, 0))
+    }
+
+    async fn stream_rows_since(
+        &mut self,
+        table: &str,
+        column: &str,
+        after: &SqlValue,
+        upto: &SqlValue,
+    ) -> Result<(Vec<String>, RowStream)> {
+        let columns = self.column_names(table).await?;
+
+        let select_sql = watermark_select_sql(&MYSQL_DIALECT, table, &columns, column, after, upto);
+        let row_stream = self.stream_query(select_sql, columns.len()).await;
+        Ok((columns, row_stream))
+    }
+
+    async fn stream_rows_where(
+        &mut self,
+        table: &str,
+        condition: &str,
+    ) -> Result<(Vec<String>, RowStream)> {
+        let columns = self.column_names(table).await?;
+
+        let select_sql = filtered_select_sql(&MYSQL_DIALECT, table, &columns, condition);
+        let row_stream = self.stream_query(select_sql, columns.len()).await;
+        Ok((columns, row_stream))
+    }
+}
+
+/// Bind a value to the next placeholder. MySQL converts strings on insert, so
+/// decimals and temporal values travel as text and keep their exact form.
+fn bind_value<'q>(
+    query: Query<'q, MySql, MySqlArguments>,
+    value: &SqlValue,
+) -> Query<'q, MySql, MySqlArguments> {
+    match value {
+        SqlValue::Null => query.bind(None::<String>),
+        SqlValue::Bool(v) => query.bind(*v),
+        SqlValue::Int(v) => query.bind(*v),
+        SqlValue::Float(v) => query.bind(*v),
+        SqlValue::Decimal(v) | SqlValue::String(v) => query.bind(v.clone()),
+        SqlValue::Bytes(v) => query.bind(v.clone()),
+        SqlValue::Date { .. } | SqlValue::Time { .. } | SqlValue::Timestamp { .. } => {
+            query.bind(value.temporal_text())
+        }
+    }
 }
 
 /// Convert a SQLx MySQL row value to SqlValue
@@ -290,7 +1026,8 @@ fn convert_sqlx_value(row: &sqlx::mysql::MySqlRow, index: usize) -> SqlValue {
     SqlValue::Null
 }
 
-/// Minify CREATE TABLE statement to single line and add IF NOT EXISTS
+/// Minify CREATE TABLE (or MariaDB CREATE SEQUENCE) statement to single line
+/// and add IF NOT EXISTS
 fn minify_create_table(create_stmt: &str) -> String {
     let single_line = create_stmt
         .lines()
@@ -300,6 +1037,8 @@ fn minify_create_table(create_stmt: &str) -> String {
 
     if single_line.starts_with("CREATE TABLE") {
         single_line.replacen("CREATE TABLE", "CREATE TABLE IF NOT EXISTS", 1)
+    } else if single_line.starts_with("CREATE SEQUENCE") {
+        single_line.replacen("CREATE SEQUENCE", "CREATE SEQUENCE IF NOT EXISTS", 1)
     } else {
         single_line
     }
@@ -321,4 +1060,30 @@ mod tests {
         assert!(output.contains("CREATE TABLE IF NOT EXISTS"));
         assert!(!output.contains('\n'));
     }
+
+    #[test]
+    fn test_minify_create_sequence() {
+        let input = "CREATE SEQUENCE `order_seq` start with 1 minvalue 1 maxvalue 9223372036854775806 increment by 1 cache 1000 nocycle ENGINE=InnoDB";
+        let output = minify_create_table(input);
+        assert!(output.starts_with("CREATE SEQUENCE IF NOT EXISTS `order_seq`"));
+    }
+
+    #[test]
+    fn builds_parameterized_inserts() {
+        let columns = vec!["id".to_string(), "name".to_string()];
+        let sql = insert_params_sql(&MYSQL_DIALECT, "users", &columns, 2, |_| "?".to_string());
+        assert_eq!(
+            sql,
+            "INSERT INTO `users` (`id`, `name`) VALUES (?, ?), (?, ?)"
+        );
+    }
+
+    #[test]
+    fn detects_server_flavor() {
+        assert_eq!(
+            ServerFlavor::from_version("10.11.6-MariaDB-0+deb12u1-log"),
+            ServerFlavor::MariaDb
+        );
+        assert_eq!(ServerFlavor::from_version("8.0.36"), ServerFlavor::Mysql);
+    }
 }
@@ -0,0 +1,429 @@
+use super::cdc::{Change, ChangeStream, RowImage};
+use super::filter::TableFilter;
+use super::value::SqlValue;
+use super::{DbSession, ReplicationPosition};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+/// Changes read from the slot per poll; whole transactions are returned, so
+/// a poll can go past this
+const PEEK_LIMIT: usize = 1000;
+
+/// Wait between polls of a slot with nothing new
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Row changes decoded by `pgoutput` from a PostgreSQL logical replication
+/// slot, which is polled over an ordinary session. The slot and the
+/// publication it decodes, both named `slot`, are made by
+/// `migrate --position-file`.
+pub struct PgoutputChanges {
+    session: Box<dyn DbSession>,
+    slot: String,
+    filter: TableFilter,
+    relations: HashMap<u32, Relation>,
+    pending: VecDeque<Change>,
+    /// End of the last transaction read, which the slot is moved past once
+    /// everything read has been handed out and applied
+    read_upto: Option<String>,
+}
+
+/// A table as the `Relation` message describes it, ahead of its first change
+/// in each session
+struct Relation {
+    table: String,
+    columns: Vec<String>,
+    type_oids: Vec<u32>,
+    /// Whether each column is part of the replica identity
+    key: Vec<bool>,
+}
+
+impl PgoutputChanges {
+    /// Read `slot` from `from`, the position a previous run got to, keeping
+    /// the changes to tables `filter` matches
+    pub async fn open(
+        mut session: Box<dyn DbSession>,
+        slot: &str,
+        from: &ReplicationPosition,
+        filter: TableFilter,
+    ) -> Result<Self> {
+        let ReplicationPosition::Wal { lsn } = from else {
+            bail!("'{}' is not a WAL position", from);
+        };
+        // A run stopped between applying a transaction and moving the slot
+        // would otherwise read it again
+        session.advance_slot(slot, lsn).await?;
+
+        Ok(Self {
+            session,
+            slot: slot.to_string(),
+            filter,
+            relations: HashMap::new(),
+            pending: VecDeque::new(),
+            read_upto: None,
+        })
+    }
+
+    fn read_message(&mut self, message: &[u8]) -> Result<()> {
+        let mut reader = Reader(message);
+        match reader.u8()? {
+            b'R' => {
+                let id = reader.u32()?;
+                let namespace = reader.string()?;
+                let name = reader.string()?;
+                reader.u8()?; // replica identity setting
+                let count = reader.u16()?;
+                let mut relation = Relation {
+                    table: format!("{}.{}", namespace, name),
+                    columns: Vec::new(),
+                    type_oids: Vec::new(),
+                    key: Vec::new(),
+                };
+                for _ in 0..count {
+                    relation.key.push(reader.u8()? & 1 == 1);
+                    relation.columns.push(reader.string()?);
+                    relation.type_oids.push(reader.u32()?);
+                    reader.u32()?; // type modifier
+                }
+                self.relations.insert(id, relation);
+            }
+            b'I' => {
+                let relation = self.relation(reader.u32()?)?;
+                reader.expect(b'N')?;
+                let row = reader.tuple(relation, false)?;
+                let table = relation.table.clone();
+                self.push_row(Change::Insert { table, row });
+            }
+            b'U' => {
+                let relation = self.relation(reader.u32()?)?;
+                // The old row comes first when the key changed, or always
+                // with REPLICA IDENTITY FULL
+                let mut tag = reader.u8()?;
+                let mut before = None;
+                if tag == b'K' || tag == b'O' {
+                    before = Some(reader.tuple(relation, tag == b'K')?);
+                    tag = reader.u8()?;
+                }
+                if tag != b'N' {
+                    bail!("Malformed pgoutput update message");
+                }
+                let after = reader.tuple(relation, false)?;
+                let before = match before {
+                    Some(before) => before,
+                    None => key_image(relation, &after),
+                };
+                let table = relation.table.clone();
+                self.push_row(Change::Update {
+                    table,
+                    before,
+                    after,
+                });
+            }
+            b'D' => {
+                let relation = self.relation(reader.u32()?)?;
+                let tag = reader.u8()?;
+                let row = reader.tuple(relation, tag == b'K')?;
+                let table = relation.table.clone();
+                self.push_row(Change::Delete { table, row });
+            }
+            b'T' => {
+                let count = reader.u32()?;
+                reader.u8()?; // CASCADE / RESTART IDENTITY
+                let mut tables = Vec::new();
+                for _ in 0..count {
+                    let table = &self.relation(reader.u32()?)?.table;
+                    if self.filter.matches(table) {
+                        tables.push(table.clone());
+                    }
+                }
+                if !tables.is_empty() {
+                    self.pending
+                        .push_back(Change::Schema(format!("TRUNCATE {}", tables.join(", "))));
+                }
+            }
+            b'C' => {
+                reader.u8()?; // flags
+                reader.u64()?; // start of the commit record
+                let end = format_lsn(reader.u64()?);
+                self.read_upto = Some(end.clone());
+                self.pending
+                    .push_back(Change::Commit(ReplicationPosition::Wal { lsn: end }));
+            }
+            // Begin, Origin, Type and logical decoding messages
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn relation(&self, id: u32) -> Result<&Relation> {
+        self.relations
+            .get(&id)
+            .ok_or_else(|| anyhow!("Change to relation {} before its description", id))
+    }
+
+    fn push_row(&mut self, change: Change) {
+        let (Change::Insert { table, .. }
+        | Change::Update { table, .. }
+        | Change::Delete { table, .. }) = &change
+        else {
+            return;
+        };
+        if self.filter.matches(table) {
+            self.pending.push_back(change);
+        }
+    }
+}
+
+#[async_trait]
+impl ChangeStream for PgoutputChanges {
+    async fn next_change(&mut self) -> Result<Change> {
+        loop {
+            if let Some(change) = self.pending.pop_front() {
+                return Ok(change);
+            }
+            if let Some(lsn) = self.read_upto.take() {
+                self.session.advance_slot(&self.slot, &lsn).await?;
+            }
+            let messages = self
+                .session
+                .peek_slot_changes(&self.slot, PEEK_LIMIT)
+                .await?;
+            if messages.is_empty() {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            for message in messages {
+                self.read_message(&message)?;
+            }
+        }
+    }
+}
+
+/// The replica identity columns of `row`, for an update that left the key
+/// alone and so sent no old row
+fn key_image(relation: &Relation, row: &RowImage) -> RowImage {
+    let mut image = RowImage {
+        columns: Vec::new(),
+        values: Vec::new(),
+    };
+    for (column, value) in row.columns.iter().zip(&row.values) {
+        let is_key = relation
+            .columns
+            .iter()
+            .position(|c| c == column)
+            .is_some_and(|index| relation.key[index]);
+        if is_key {
+            image.columns.push(column.clone());
+            image.values.push(value.clone());
+        }
+    }
+    image
+}
+
+/// `X/Y` as PostgreSQL writes an LSN
+fn format_lsn(lsn: u64) -> String {
+    format!("{:X}/{:X}", lsn >> 32, lsn & 0xFFFF_FFFF)
+}
+
+/// A value in PostgreSQL's text output form, read by its type's OID as
+/// `convert_pg_value` reads a query result
+fn text_value(type_oid: u32, text: &str) -> SqlValue {
+    let parsed = match type_oid {
+        16 => Some(SqlValue::Bool(text == "t")),
+        20 | 21 | 23 | 26 => text.parse().ok().map(SqlValue::Int),
+        700 | 701 => text.parse().ok().map(SqlValue::Float),
+        1700 => Some(SqlValue::Decimal(text.to_string())),
+        17 => text
+            .strip_prefix("\\x")
+            .and_then(|digits| hex::decode(digits).ok())
+            .map(SqlValue::Bytes),
+        1082 => chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d")
+            .ok()
+            .map(SqlValue::from_date),
+        1083 => chrono::NaiveTime::parse_from_str(text, "%H:%M:%S%.f")
+            .ok()
+            .map(SqlValue::from_time),
+        1114 => chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")
+            .ok()
+            .map(SqlValue::from_datetime),
+        1184 => chrono::DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z")
+            .ok()
+            .map(|at| SqlValue::from_datetime(at.naive_utc())),
+        _ => None,
+    };
+    // Text for the rest, and for values such as 'infinity' that have no
+    // other form
+    parsed.unwrap_or_else(|| SqlValue::String(text.to_string()))
+}
+
+/// Cursor over one `pgoutput` message
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8]> {
+        if self.0.len() < len {
+            bail!("Truncated pgoutput message");
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn expect(&mut self, tag: u8) -> Result<()> {
+        if self.u8()? != tag {
+            bail!("Malformed pgoutput message");
+        }
+        Ok(())
+    }
+
+    /// A NUL-terminated string
+    fn string(&mut self) -> Result<String> {
+        let len = self
+            .0
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| anyhow!("Truncated pgoutput message"))?;
+        let text = String::from_utf8_lossy(self.take(len)?).into_owned();
+        self.take(1)?;
+        Ok(text)
+    }
+
+    /// A row; unchanged TOASTed values are left out, as are the columns
+    /// outside the replica identity when `key_only`
+    fn tuple(&mut self, relation: &Relation, key_only: bool) -> Result<RowImage> {
+        let count = usize::from(self.u16()?);
+        if count != relation.columns.len() {
+            bail!(
+                "'{}' has {} columns in the change but {} in its description",
+                relation.table,
+                count,
+                relation.columns.len()
+            );
+        }
+        let mut image = RowImage {
+            columns: Vec::new(),
+            values: Vec::new(),
+        };
+        for index in 0..count {
+            let value = match self.u8()? {
+                b'n' => SqlValue::Null,
+                b'u' => continue,
+                b't' => {
+                    let len = self.u32()? as usize;
+                    let text = String::from_utf8_lossy(self.take(len)?).into_owned();
+                    text_value(relation.type_oids[index], &text)
+                }
+                kind => bail!("Unknown pgoutput column kind '{}'", kind as char),
+            };
+            if key_only && !relation.key[index] {
+                continue;
+            }
+            image.columns.push(relation.columns[index].clone());
+            image.values.push(value);
+        }
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::sqlite::SqliteEngine;
+    use crate::engine::DbEngine;
+
+    fn text(value: &str) -> Vec<u8> {
+        let mut out = vec![b't'];
+        out.extend((value.len() as u32).to_be_bytes());
+        out.extend(value.as_bytes());
+        out
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn decodes_pgoutput_messages() {
+        let mut changes = PgoutputChanges {
+            session: SqliteEngine.connect("sqlite::memory:").await.unwrap(),
+            slot: "migrasquiel".into(),
+            filter: TableFilter::default(),
+            relations: HashMap::new(),
+            pending: VecDeque::new(),
+            read_upto: None,
+        };
+
+        let mut relation = b"R".to_vec();
+        relation.extend(16384u32.to_be_bytes());
+        relation.extend(b"public\0users\0d");
+        relation.extend(3u16.to_be_bytes());
+        for (key, name, oid) in [(1u8, "id", 23u32), (0, "email", 25), (0, "seen", 1184)] {
+            relation.push(key);
+            relation.extend(name.as_bytes());
+            relation.push(0);
+            relation.extend(oid.to_be_bytes());
+            relation.extend((-1i32).to_be_bytes());
+        }
+        changes.read_message(&relation).unwrap();
+
+        let mut update = b"U".to_vec();
+        update.extend(16384u32.to_be_bytes());
+        update.push(b'N');
+        update.extend(3u16.to_be_bytes());
+        update.extend(text("7"));
+        update.push(b'u');
+        update.extend(text("2024-05-01 12:00:00.25+02"));
+        changes.read_message(&update).unwrap();
+
+        let mut commit = b"C\0".to_vec();
+        commit.extend(0x1_0000_0010u64.to_be_bytes());
+        commit.extend(0x1_0000_0040u64.to_be_bytes());
+        commit.extend(0u64.to_be_bytes());
+        changes.read_message(&commit).unwrap();
+
+        assert_eq!(
+            changes.pending.pop_front(),
+            Some(Change::Update {
+                table: "public.users".into(),
+                before: RowImage {
+                    columns: vec!["id".into()],
+                    values: vec![SqlValue::Int(7)],
+                },
+                after: RowImage {
+                    columns: vec!["id".into(), "seen".into()],
+                    values: vec![
+                        SqlValue::Int(7),
+                        SqlValue::Timestamp {
+                            y: 2024,
+                            m: 5,
+                            d: 1,
+                            hh: 10,
+                            mm: 0,
+                            ss: 0,
+                            us: 250_000,
+                        },
+                    ],
+                },
+            })
+        );
+        assert_eq!(
+            changes.pending.pop_front(),
+            Some(Change::Commit(ReplicationPosition::Wal {
+                lsn: "1/40".into()
+            }))
+        );
+        assert_eq!(changes.read_upto.as_deref(), Some("1/40"));
+    }
+}
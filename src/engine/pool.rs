@@ -0,0 +1,107 @@
+use super::DbSession;
+use anyhow::Result;
+use futures::future::BoxFuture;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+type Connect<'e> = Box<dyn Fn() -> BoxFuture<'e, Result<Box<dyn DbSession>>> + Send + Sync + 'e>;
+
+/// Sessions on one database, at most `size` of them checked out at a time.
+/// Sessions are opened when first needed and reused once released, so
+/// concurrent jobs never hold more connections than the pool allows.
+pub struct SessionPool<'e> {
+    connect: Connect<'e>,
+    idle: Mutex<Vec<Box<dyn DbSession>>>,
+    permits: Semaphore,
+}
+
+impl<'e> SessionPool<'e> {
+    pub fn new(
+        size: usize,
+        connect: impl Fn() -> BoxFuture<'e, Result<Box<dyn DbSession>>> + Send + Sync + 'e,
+    ) -> Self {
+        SessionPool {
+            connect: Box::new(connect),
+            idle: Mutex::new(Vec::new()),
+            permits: Semaphore::new(size.clamp(1, Semaphore::MAX_PERMITS)),
+        }
+    }
+
+    /// Check out a session, waiting while the pool is exhausted
+    pub async fn get(&self) -> Result<PooledSession<'_>> {
+        let permit = self.permits.acquire().await?;
+        let idle = self.idle.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        let session = match idle {
+            Some(session) => session,
+            None => (self.connect)().await?,
+        };
+
+        Ok(PooledSession {
+            session: Some(session),
+            idle: &self.idle,
+            _permit: permit,
+        })
+    }
+}
+
+/// A session checked out of a [`SessionPool`]. Dropping it closes the
+/// session; [`PooledSession::release`] hands it back for reuse instead.
+pub struct PooledSession<'p> {
+    session: Option<Box<dyn DbSession>>,
+    idle: &'p Mutex<Vec<Box<dyn DbSession>>>,
+    _permit: SemaphorePermit<'p>,
+}
+
+impl PooledSession<'_> {
+    /// Return the session to the pool. Only release sessions left clean:
+    /// transaction committed and constraints restored.
+    pub fn release(mut self) {
+        if let Some(session) = self.session.take() {
+            self.idle
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(session);
+        }
+    }
+}
+
+impl Deref for PooledSession<'_> {
+    type Target = dyn DbSession;
+
+    fn deref(&self) -> &Self::Target {
+        self.session.as_deref().expect("session already released")
+    }
+}
+
+impl DerefMut for PooledSession<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.session
+            .as_deref_mut()
+            .expect("session already released")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::sqlite::SqliteEngine;
+    use crate::engine::DbEngine;
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn caps_and_reuses_sessions() {
+        let pool = SqliteEngine.connect_pool("sqlite::memory:", 1);
+
+        let mut first = pool.get().await.unwrap();
+        first.execute("CREATE TABLE t (id INTEGER)").await.unwrap();
+
+        // The only session is checked out, so a second caller has to wait
+        let waiting = tokio::time::timeout(Duration::from_millis(50), pool.get()).await;
+        assert!(waiting.is_err());
+
+        // A released session comes back with its state: the same in-memory db
+        first.release();
+        let mut second = pool.get().await.unwrap();
+        assert_eq!(second.approximate_row_count("t").await.unwrap(), 0);
+    }
+}
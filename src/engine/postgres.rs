@@ -1,12 +1,23 @@
-use super::{DbEngine, DbSession, RowStream};
-use crate::engine::dialect::{format_qualified_table, split_table_name, SqlDialect};
+use super::{DbEngine, DbSession, InsertMode, ReplicationPosition, Routine, RowStream};
+use crate::engine::ddl::{Column, ColumnType};
+use crate::engine::dialect::{
+    any_row_sql, column_max_sql, count_rows_sql, filtered_select_sql, format_qualified_table,
+    insert_params_sql, key_bounds_sql, key_offset_sql, key_range_select_sql,
+    key_window_checksum_sql, key_window_select_sql, keyset_select_sql, select_rows_sql,
+    split_table_name, watermark_select_sql, SqlDialect, MAX_BIND_PARAMS,
+};
+use crate::engine::filter::TableFilter;
 use crate::engine::value::SqlValue;
 use crate::util::dialects::postgres::POSTGRES_DIALECT;
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
 use futures::stream;
-use sqlx::postgres::{PgConnection, PgRow, PgValueRef};
+use sqlx::postgres::{PgArguments, PgConnection, PgRow, PgValueRef, Postgres};
+use sqlx::query::Query;
 use sqlx::{Connection, Row, TypeInfo, ValueRef};
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::time::Duration;
 
 pub struct PostgresEngine;
 
@@ -17,18 +28,205 @@ impl DbEngine for PostgresEngine {
             .await
             .with_context(|| format!("Failed to connect to PostgreSQL database at {}", url))?;
 
-        Ok(Box::new(PostgresSession {
-            conn,
-            in_transaction: false,
-            constraints_disabled: false,
-        }))
+        Ok(Box::new(PostgresSession::new(conn)))
+    }
+
+    fn dialect(&self) -> Option<&'static dyn SqlDialect> {
+        Some(&POSTGRES_DIALECT)
     }
 }
 
 pub struct PostgresSession {
-    conn: PgConnection,
-    in_transaction: bool,
+    pub(super) conn: PgConnection,
+    pub(super) in_transaction: bool,
     constraints_disabled: bool,
+    pub(super) insert_mode: InsertMode,
+    /// Leave out the columns the catalog marks hidden, like the `rowid`
+    /// key CockroachDB adds to tables without a primary key
+    skip_hidden: bool,
+    /// Column types by table for prepared inserts; cleared whenever DDL may run
+    column_types: HashMap<String, HashMap<String, String>>,
+}
+
+/// A sequence behind a column, from `serial` or an identity
+struct ColumnSequence {
+    /// Schema-qualified sequence name
+    sequence: String,
+    column: String,
+    identity: bool,
+    /// An identity that is GENERATED ALWAYS
+    always: bool,
+}
+
+impl PostgresSession {
+    pub(super) fn new(conn: PgConnection) -> Self {
+        PostgresSession {
+            conn,
+            in_transaction: false,
+            constraints_disabled: false,
+            insert_mode: InsertMode::Literal,
+            column_types: HashMap::new(),
+            skip_hidden: false,
+        }
+    }
+
+    /// Session whose column lists leave out hidden columns
+    pub(super) fn skipping_hidden(conn: PgConnection) -> Self {
+        PostgresSession {
+            skip_hidden: true,
+            ..PostgresSession::new(conn)
+        }
+    }
+
+    /// Columns whose values are read and written; generated columns are
+    /// computed by the server and left out
+    pub(super) async fn column_names(&mut self, table: &str) -> Result<Vec<String>> {
+        let (schema, name) = parse_table_name(table)?;
+
+        let columns: Vec<String> = sqlx::query_scalar(&format!(
+            "SELECT column_name
+             FROM information_schema.columns
+             WHERE table_schema = $1 AND table_name = $2 AND is_generated = 'NEVER'{}
+             ORDER BY ordinal_position",
+            if self.skip_hidden {
+                " AND is_hidden = 'NO'"
+            } else {
+                ""
+            }
+        ))
+        .bind(&schema)
+        .bind(&name)
+        .fetch_all(&mut self.conn)
+        .await?;
+
+        Ok(columns)
+    }
+
+    async fn table_oid(&mut self, table: &str) -> Result<i32> {
+        let (schema, name) = parse_table_name(table)?;
+        sqlx::query_scalar(
+            "SELECT c.oid::int
+             FROM pg_class c
+             INNER JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE n.nspname = $1 AND c.relname = $2",
+        )
+        .bind(&schema)
+        .bind(&name)
+        .fetch_optional(&mut self.conn)
+        .await?
+        .ok_or_else(|| anyhow!("Table {} not found", table))
+    }
+
+    /// Sequences tied to the columns of table `oid`, in column order
+    async fn column_sequences(&mut self, oid: i32) -> Result<Vec<ColumnSequence>> {
+        let rows = sqlx::query(
+            "SELECT sn.nspname, s.relname, a.attname, d.deptype = 'i', a.attidentity = 'a'
+             FROM pg_catalog.pg_depend d
+             JOIN pg_catalog.pg_class s ON s.oid = d.objid AND s.relkind = 'S'
+             JOIN pg_catalog.pg_namespace sn ON sn.oid = s.relnamespace
+             JOIN pg_catalog.pg_attribute a
+               ON a.attrelid = d.refobjid AND a.attnum = d.refobjsubid
+             WHERE d.classid = 'pg_catalog.pg_class'::regclass
+               AND d.refclassid = 'pg_catalog.pg_class'::regclass
+               AND d.refobjid = $1
+               AND d.deptype IN ('a', 'i')
+             ORDER BY a.attnum",
+        )
+        .bind(oid)
+        .fetch_all(&mut self.conn)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let schema: String = row.get(0);
+                let name: String = row.get(1);
+                ColumnSequence {
+                    sequence: format!("{}.{}", schema, name),
+                    column: row.get(2),
+                    identity: row.get(3),
+                    always: row.get(4),
+                }
+            })
+            .collect())
+    }
+
+    async fn create_sequence_sql(&mut self, sequence: &str) -> Result<String> {
+        let (schema, name) = parse_table_name(sequence)?;
+        let row = sqlx::query(
+            "SELECT data_type::text, start_value, min_value, max_value, increment_by,
+                    cycle, cache_size
+             FROM pg_catalog.pg_sequences
+             WHERE schemaname = $1 AND sequencename = $2",
+        )
+        .bind(&schema)
+        .bind(&name)
+        .fetch_optional(&mut self.conn)
+        .await?
+        .ok_or_else(|| anyhow!("Sequence {} not found", sequence))?;
+
+        let data_type: String = row.get(0);
+        let start: i64 = row.get(1);
+        let min: i64 = row.get(2);
+        let max: i64 = row.get(3);
+        let increment: i64 = row.get(4);
+        let cycle: bool = row.get(5);
+        let cache: i64 = row.get(6);
+        Ok(format!(
+            "CREATE SEQUENCE IF NOT EXISTS {} AS {} START WITH {} INCREMENT BY {} MINVALUE {} MAXVALUE {} CACHE {}{};",
+            format_qualified_table(&POSTGRES_DIALECT, sequence),
+            data_type,
+            start,
+            increment,
+            min,
+            max,
+            cache,
+            if cycle { " CYCLE" } else { "" }
+        ))
+    }
+
+    /// Insert rows through bound parameters, as many rows per statement as
+    /// the placeholder limit allows. Parameters are cast to the column types
+    /// because Postgres will not coerce a typed parameter on its own.
+    async fn insert_prepared(
+        &mut self,
+        table: &str,
+        column_names: &[String],
+        rows: &[Vec<SqlValue>],
+    ) -> Result<()> {
+        if !self.column_types.contains_key(table) {
+            let types: Vec<(String, String)> = sqlx::query_as(
+                "SELECT attname::text, format_type(atttypid, NULL)
+                 FROM pg_catalog.pg_attribute
+                 WHERE attrelid = $1::regclass AND attnum > 0 AND NOT attisdropped",
+            )
+            .bind(format_qualified_table(&POSTGRES_DIALECT, table))
+            .fetch_all(&mut self.conn)
+            .await
+            .with_context(|| format!("Failed to read column types of table '{}'", table))?;
+            self.column_types
+                .insert(table.to_string(), types.into_iter().collect());
+        }
+        let types = &self.column_types[table];
+        let casts: Vec<Option<&String>> = column_names.iter().map(|c| types.get(c)).collect();
+
+        let rows_per_statement = (MAX_BIND_PARAMS / column_names.len().max(1)).max(1);
+        for chunk in rows.chunks(rows_per_statement) {
+            let sql = insert_params_sql(&POSTGRES_DIALECT, table, column_names, chunk.len(), |i| {
+                match casts[i % casts.len()] {
+                    Some(column_type) => format!("CAST(${} AS {})", i + 1, column_type),
+                    None => format!("${}", i + 1),
+                }
+            });
+            let query = chunk
+                .iter()
+                .flatten()
+                .fold(sqlx::query(&sql), |query, value| bind_value(query, value));
+            query.execute(&mut self.conn).await?;
+        }
+
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -37,6 +235,10 @@ impl DbSession for PostgresSession {
         &POSTGRES_DIALECT
     }
 
+    fn in_transaction(&self) -> bool {
+        self.in_transaction
+    }
+
     async fn start_consistent_snapshot(&mut self) -> Result<()> {
         sqlx::query("BEGIN ISOLATION LEVEL REPEATABLE READ")
             .execute(&mut self.conn)
@@ -45,7 +247,43 @@ impl DbSession for PostgresSession {
         Ok(())
     }
 
-    async fn list_tables(&mut self, include: &[String], exclude: &[String]) -> Result<Vec<String>> {
+    /// The snapshot is taken by the first query of the transaction, so the
+    /// WAL location read by that query is where it stands; a standby reports
+    /// how far it has replayed
+    async fn start_snapshot_at_position(&mut self) -> Result<Option<ReplicationPosition>> {
+        self.start_consistent_snapshot().await?;
+        let lsn: String = sqlx::query_scalar(
+            "SELECT (CASE WHEN pg_is_in_recovery() THEN pg_last_wal_replay_lsn() \
+             ELSE pg_current_wal_lsn() END)::text",
+        )
+        .fetch_one(&mut self.conn)
+        .await
+        .context("Failed to read the WAL position")?;
+        Ok(Some(ReplicationPosition::Wal { lsn }))
+    }
+
+    async fn export_snapshot(&mut self) -> Result<Option<String>> {
+        if !self.in_transaction {
+            return Ok(None);
+        }
+        let snapshot: String = sqlx::query_scalar("SELECT pg_export_snapshot()")
+            .fetch_one(&mut self.conn)
+            .await?;
+        Ok(Some(snapshot))
+    }
+
+    async fn import_snapshot(&mut self, snapshot: &str) -> Result<()> {
+        self.start_consistent_snapshot().await?;
+        sqlx::query(&format!(
+            "SET TRANSACTION SNAPSHOT {}",
+            POSTGRES_DIALECT.to_literal(&SqlValue::String(snapshot.to_string()))
+        ))
+        .execute(&mut self.conn)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_tables(&mut self, filter: &TableFilter) -> Result<Vec<String>> {
         let rows = sqlx::query(
             "SELECT schemaname, tablename
              FROM pg_catalog.pg_tables
@@ -64,30 +302,150 @@ impl DbSession for PostgresSession {
             })
             .collect();
 
-        if !include.is_empty() {
-            tables.retain(|t| include.iter().any(|inc| table_matches(inc, t)));
-        }
-
-        if !exclude.is_empty() {
-            tables.retain(|t| !exclude.iter().any(|exc| table_matches(exc, t)));
-        }
+        filter.apply(&mut tables);
 
         Ok(tables)
     }
 
-    async fn show_create_table(&mut self, table: &str) -> Result<String> {
+    async fn list_views(&mut self, filter: &TableFilter) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            "SELECT schemaname, viewname
+             FROM pg_catalog.pg_views
+             WHERE schemaname NOT IN ('pg_catalog', 'information_schema')
+             ORDER BY schemaname, viewname",
+        )
+        .fetch_all(&mut self.conn)
+        .await?;
+
+        let mut views: Vec<String> = rows
+            .into_iter()
+            .map(|row| {
+                let schema: String = row.get(0);
+                let view: String = row.get(1);
+                format!("{}.{}", schema, view)
+            })
+            .collect();
+
+        filter.apply(&mut views);
+
+        Ok(views)
+    }
+
+    async fn show_create_view(&mut self, view: &str) -> Result<String> {
+        let (schema, name) = parse_table_name(view)?;
+        let definition: String = sqlx::query_scalar(
+            "SELECT definition FROM pg_catalog.pg_views WHERE schemaname = $1 AND viewname = $2",
+        )
+        .bind(&schema)
+        .bind(&name)
+        .fetch_optional(&mut self.conn)
+        .await?
+        .ok_or_else(|| anyhow!("View {} not found", view))?;
+
+        Ok(format!(
+            "CREATE OR REPLACE VIEW {} AS\n{}",
+            format_qualified_table(&POSTGRES_DIALECT, view),
+            definition.trim().trim_end_matches(';')
+        ))
+    }
+
+    async fn list_routines(&mut self) -> Result<Vec<Routine>> {
+        // Aggregates and window functions have no CREATE FUNCTION, and
+        // functions of extensions come with the extension
+        let rows = sqlx::query(
+            "SELECT n.nspname, p.proname, pg_catalog.pg_get_function_identity_arguments(p.oid),
+                    p.prokind = 'p'
+             FROM pg_catalog.pg_proc p
+             JOIN pg_catalog.pg_namespace n ON n.oid = p.pronamespace
+             WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
+               AND n.nspname NOT LIKE 'pg\\_%'
+               AND p.prokind IN ('f', 'p')
+               AND NOT EXISTS (
+                   SELECT 1 FROM pg_catalog.pg_depend d
+                   WHERE d.classid = 'pg_catalog.pg_proc'::regclass
+                     AND d.objid = p.oid AND d.deptype = 'e')
+             ORDER BY 1, 2, 3",
+        )
+        .fetch_all(&mut self.conn)
+        .await
+        .context("Failed to list routines")?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let schema: String = row.get(0);
+                let name: String = row.get(1);
+                let procedure: bool = row.get(3);
+                Routine {
+                    kind: if procedure { "PROCEDURE" } else { "FUNCTION" },
+                    name: format!("{}.{}", schema, name),
+                    arguments: Some(row.get(2)),
+                }
+            })
+            .collect())
+    }
+
+    async fn show_create_routine(&mut self, routine: &Routine) -> Result<String> {
+        let (schema, name) = parse_table_name(&routine.name)?;
+        let definition: String = sqlx::query_scalar(
+            "SELECT pg_catalog.pg_get_functiondef(p.oid)
+             FROM pg_catalog.pg_proc p
+             JOIN pg_catalog.pg_namespace n ON n.oid = p.pronamespace
+             WHERE n.nspname = $1 AND p.proname = $2
+               AND pg_catalog.pg_get_function_identity_arguments(p.oid) = $3",
+        )
+        .bind(&schema)
+        .bind(&name)
+        .bind(routine.arguments.as_deref().unwrap_or_default())
+        .fetch_optional(&mut self.conn)
+        .await?
+        .ok_or_else(|| anyhow!("Routine {} not found", routine))?;
+
+        Ok(definition.trim_end().to_string())
+    }
+
+    async fn list_triggers(&mut self, table: &str) -> Result<Vec<String>> {
         let (schema, name) = parse_table_name(table)?;
-        let oid: i32 = sqlx::query_scalar(
-            "SELECT c.oid::int
-             FROM pg_class c
-             INNER JOIN pg_namespace n ON n.oid = c.relnamespace
-             WHERE n.nspname = $1 AND c.relname = $2",
+        // Triggers of the same timing and event fire in name order
+        let triggers: Vec<String> = sqlx::query_scalar(
+            "SELECT t.tgname
+             FROM pg_catalog.pg_trigger t
+             JOIN pg_catalog.pg_class c ON c.oid = t.tgrelid
+             JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+             WHERE n.nspname = $1 AND c.relname = $2 AND NOT t.tgisinternal
+             ORDER BY t.tgname",
+        )
+        .bind(&schema)
+        .bind(&name)
+        .fetch_all(&mut self.conn)
+        .await
+        .with_context(|| format!("Failed to list triggers on {}", table))?;
+
+        Ok(triggers)
+    }
+
+    async fn show_create_trigger(&mut self, table: &str, trigger: &str) -> Result<String> {
+        let (schema, name) = parse_table_name(table)?;
+        let definition: String = sqlx::query_scalar(
+            "SELECT pg_catalog.pg_get_triggerdef(t.oid)
+             FROM pg_catalog.pg_trigger t
+             JOIN pg_catalog.pg_class c ON c.oid = t.tgrelid
+             JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+             WHERE n.nspname = $1 AND c.relname = $2 AND t.tgname = $3",
         )
         .bind(&schema)
         .bind(&name)
+        .bind(trigger)
         .fetch_optional(&mut self.conn)
         .await?
-        .ok_or_else(|| anyhow!("Table {} not found", table))?;
+        .ok_or_else(|| anyhow!("Trigger {} on {} not found", trigger, table))?;
+
+        Ok(definition)
+    }
+
+    async fn show_create_table(&mut self, table: &str) -> Result<String> {
+        let (schema, name) = parse_table_name(table)?;
+        let oid = self.table_oid(table).await?;
 
         let columns = sqlx::query(
             "SELECT
@@ -95,7 +453,8 @@ impl DbSession for PostgresSession {
                  pg_catalog.format_type(a.atttypid, a.atttypmod) AS data_type,
                  a.attnotnull,
                  pg_get_expr(ad.adbin, ad.adrelid) AS column_default,
-                 a.attidentity
+                 a.attidentity::text AS attidentity,
+                 a.attgenerated::text AS attgenerated
              FROM pg_attribute a
              LEFT JOIN pg_attrdef ad
                ON a.attrelid = ad.adrelid AND a.attnum = ad.adnum
@@ -118,21 +477,25 @@ impl DbSession for PostgresSession {
                 .try_get::<Option<String>, _>("attidentity")
                 .ok()
                 .flatten();
+            let generated: String = col.get("attgenerated");
 
             let mut parts = Vec::new();
             parts.push(POSTGRES_DIALECT.quote_identifier(&name));
             parts.push(data_type);
 
-            if let Some(id) = identity {
-                if id == "a" {
-                    parts.push("GENERATED ALWAYS AS IDENTITY".to_string());
-                } else if id == "d" {
-                    parts.push("GENERATED BY DEFAULT AS IDENTITY".to_string());
-                }
+            // GENERATED ALWAYS would refuse the dumped ids; `sequence_statements`
+            // restores it after the load
+            if matches!(identity.as_deref(), Some("a" | "d")) {
+                parts.push("GENERATED BY DEFAULT AS IDENTITY".to_string());
             }
 
-            if let Some(default) = default_expr {
-                parts.push(format!("DEFAULT {}", default));
+            // A generated column's expression is kept where a default would be
+            match default_expr {
+                Some(expr) if generated == "s" => {
+                    parts.push(format!("GENERATED ALWAYS AS ({}) STORED", expr))
+                }
+                Some(default) => parts.push(format!("DEFAULT {}", default)),
+                None => {}
             }
 
             if not_null {
@@ -237,7 +600,21 @@ impl DbSession for PostgresSession {
         .fetch_all(&mut self.conn)
         .await?;
 
-        let mut full_ddl = create_stmt;
+        // A serial column's sequence belongs to the table and is dropped
+        // with it, so it is created again just ahead of the table
+        let owned: Vec<ColumnSequence> = self
+            .column_sequences(oid)
+            .await?
+            .into_iter()
+            .filter(|s| !s.identity)
+            .collect();
+        let mut full_ddl = String::new();
+        for sequence in &owned {
+            full_ddl.push_str(&self.create_sequence_sql(&sequence.sequence).await?);
+            full_ddl.push('\n');
+        }
+
+        full_ddl.push_str(&create_stmt);
         for row in index_rows {
             let _index_name: String = row.get(0);
             let indexdef: String = row.get(1);
@@ -246,34 +623,284 @@ impl DbSession for PostgresSession {
             full_ddl.push(';');
         }
 
+        for sequence in &owned {
+            full_ddl.push_str(&format!(
+                "\nALTER SEQUENCE {} OWNED BY {}.{};",
+                format_qualified_table(&POSTGRES_DIALECT, &sequence.sequence),
+                qualified,
+                POSTGRES_DIALECT.quote_identifier(&sequence.column)
+            ));
+        }
+
         Ok(full_ddl)
     }
 
-    async fn stream_rows(&mut self, table: &str) -> Result<(Vec<String>, RowStream)> {
-        let (schema, name) = parse_table_name(table)?;
+    async fn create_database(&mut self, name: &str) -> Result<bool> {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM pg_catalog.pg_database WHERE datname = $1)",
+        )
+        .bind(name)
+        .fetch_one(&mut self.conn)
+        .await?;
+        if exists {
+            return Ok(false);
+        }
+        // CREATE DATABASE has no IF NOT EXISTS, and cannot run in a transaction
+        let create = format!(
+            "CREATE DATABASE {}",
+            POSTGRES_DIALECT.quote_identifier(name)
+        );
+        sqlx::query(&create)
+            .execute(&mut self.conn)
+            .await
+            .with_context(|| format!("Failed to create database '{}'", name))?;
+        Ok(true)
+    }
 
-        let column_rows = sqlx::query(
-            "SELECT column_name
-             FROM information_schema.columns
-             WHERE table_schema = $1 AND table_name = $2
-             ORDER BY ordinal_position",
+    async fn user_statements(&mut self) -> Result<(Vec<String>, Vec<String>)> {
+        // Password hashes are only in pg_authid, which needs a superuser
+        let roles = "SELECT rolname, rolsuper, rolinherit, rolcreaterole, rolcreatedb,
+                            rolcanlogin, rolreplication, rolbypassrls, rolconnlimit,
+                            {password}, rolvaliduntil::text
+                     FROM pg_catalog.{table}
+                     WHERE rolname !~ '^pg_'
+                     ORDER BY rolname";
+        let rows = match sqlx::query(
+            &roles
+                .replace("{password}", "rolpassword")
+                .replace("{table}", "pg_authid"),
         )
-        .bind(&schema)
-        .bind(&name)
+        .fetch_all(&mut self.conn)
+        .await
+        {
+            Ok(rows) => rows,
+            Err(_) => {
+                eprintln!("Warning: pg_authid is not readable; roles are dumped without passwords");
+                sqlx::query(
+                    &roles
+                        .replace("{password}", "NULL::text")
+                        .replace("{table}", "pg_roles"),
+                )
+                .fetch_all(&mut self.conn)
+                .await
+                .context("Failed to list roles")?
+            }
+        };
+
+        let mut creates = Vec::with_capacity(rows.len() * 2);
+        for row in rows {
+            let name: String = row.get(0);
+            let role = POSTGRES_DIALECT.quote_identifier(&name);
+            let flag = |index: usize, on: &str, off: &str| -> String {
+                if row.get::<bool, _>(index) { on } else { off }.to_string()
+            };
+            let mut options = vec![
+                flag(1, "SUPERUSER", "NOSUPERUSER"),
+                flag(2, "INHERIT", "NOINHERIT"),
+                flag(3, "CREATEROLE", "NOCREATEROLE"),
+                flag(4, "CREATEDB", "NOCREATEDB"),
+                flag(5, "LOGIN", "NOLOGIN"),
+                flag(6, "REPLICATION", "NOREPLICATION"),
+                flag(7, "BYPASSRLS", "NOBYPASSRLS"),
+            ];
+            let limit: i32 = row.get(8);
+            if limit >= 0 {
+                options.push(format!("CONNECTION LIMIT {}", limit));
+            }
+            if let Some(password) = row.get::<Option<String>, _>(9) {
+                options.push(format!(
+                    "PASSWORD {}",
+                    POSTGRES_DIALECT.to_literal(&SqlValue::String(password))
+                ));
+            }
+            if let Some(until) = row.get::<Option<String>, _>(10) {
+                options.push(format!(
+                    "VALID UNTIL {}",
+                    POSTGRES_DIALECT.to_literal(&SqlValue::String(until))
+                ));
+            }
+
+            // CREATE ROLE has no IF NOT EXISTS; an existing role is altered
+            creates.push(format!(
+                "DO $$ BEGIN CREATE ROLE {}; EXCEPTION WHEN duplicate_object THEN NULL; END $$;",
+                role
+            ));
+            creates.push(format!("ALTER ROLE {} WITH {};", role, options.join(" ")));
+        }
+
+        let memberships: Vec<(String, String, bool)> = sqlx::query_as(
+            "SELECT r.rolname, m.rolname, am.admin_option
+             FROM pg_catalog.pg_auth_members am
+             JOIN pg_catalog.pg_roles r ON r.oid = am.roleid
+             JOIN pg_catalog.pg_roles m ON m.oid = am.member
+             WHERE r.rolname !~ '^pg_' AND m.rolname !~ '^pg_'
+             ORDER BY 1, 2",
+        )
+        .fetch_all(&mut self.conn)
+        .await
+        .context("Failed to list role memberships")?;
+        let grants = memberships
+            .into_iter()
+            .map(|(role, member, admin)| {
+                format!(
+                    "GRANT {} TO {}{};",
+                    POSTGRES_DIALECT.quote_identifier(&role),
+                    POSTGRES_DIALECT.quote_identifier(&member),
+                    if admin { " WITH ADMIN OPTION" } else { "" }
+                )
+            })
+            .collect();
+        Ok((creates, grants))
+    }
+
+    async fn extension_statements(&mut self) -> Result<Vec<String>> {
+        // plpgsql comes with every database
+        let rows = sqlx::query(
+            "SELECT e.extname, n.nspname
+             FROM pg_catalog.pg_extension e
+             JOIN pg_catalog.pg_namespace n ON n.oid = e.extnamespace
+             WHERE e.extname <> 'plpgsql'
+             ORDER BY e.extname",
+        )
+        .fetch_all(&mut self.conn)
+        .await
+        .context("Failed to list extensions")?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let name: String = row.get(0);
+                let schema: String = row.get(1);
+                format!(
+                    "CREATE EXTENSION IF NOT EXISTS {} WITH SCHEMA {};",
+                    POSTGRES_DIALECT.quote_identifier(&name),
+                    POSTGRES_DIALECT.quote_identifier(&schema)
+                )
+            })
+            .collect())
+    }
+
+    async fn comment_statements(&mut self, table: &str) -> Result<Vec<String>> {
+        let oid = self.table_oid(table).await?;
+        let rows = sqlx::query(
+            "SELECT a.attname, d.description
+             FROM pg_catalog.pg_description d
+             LEFT JOIN pg_catalog.pg_attribute a
+               ON a.attrelid = d.objoid AND a.attnum = d.objsubid
+             WHERE d.classoid = 'pg_catalog.pg_class'::regclass AND d.objoid = $1
+             ORDER BY d.objsubid",
+        )
+        .bind(oid)
+        .fetch_all(&mut self.conn)
+        .await?;
+
+        let qualified = format_qualified_table(&POSTGRES_DIALECT, table);
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let column: Option<String> = row.get(0);
+                let text = POSTGRES_DIALECT.to_literal(&SqlValue::String(row.get(1)));
+                match column {
+                    Some(column) => format!(
+                        "COMMENT ON COLUMN {}.{} IS {};",
+                        qualified,
+                        POSTGRES_DIALECT.quote_identifier(&column),
+                        text
+                    ),
+                    None => format!("COMMENT ON TABLE {} IS {};", qualified, text),
+                }
+            })
+            .collect())
+    }
+
+    async fn privilege_statements(&mut self, table: &str) -> Result<Vec<String>> {
+        let oid = self.table_oid(table).await?;
+        let owner: String = sqlx::query_scalar(
+            "SELECT pg_catalog.pg_get_userbyid(relowner) FROM pg_catalog.pg_class WHERE oid = $1",
+        )
+        .bind(oid)
+        .fetch_one(&mut self.conn)
+        .await?;
+
+        // The owner's own privileges come with ownership; grantee 0 is PUBLIC
+        let grants = sqlx::query(
+            "SELECT CASE WHEN acl.grantee = 0 THEN NULL
+                         ELSE pg_catalog.pg_get_userbyid(acl.grantee) END,
+                    acl.is_grantable,
+                    string_agg(acl.privilege_type, ', ' ORDER BY acl.privilege_type)
+             FROM pg_catalog.pg_class c, aclexplode(c.relacl) acl
+             WHERE c.oid = $1 AND acl.grantee <> c.relowner
+             GROUP BY acl.grantee, acl.is_grantable
+             ORDER BY 1 NULLS FIRST, 2",
+        )
+        .bind(oid)
         .fetch_all(&mut self.conn)
         .await?;
 
-        let columns: Vec<String> = column_rows.into_iter().map(|row| row.get(0)).collect();
+        let qualified = format_qualified_table(&POSTGRES_DIALECT, table);
+        let mut statements = vec![format!(
+            "ALTER TABLE {} OWNER TO {};",
+            qualified,
+            POSTGRES_DIALECT.quote_identifier(&owner)
+        )];
+        for row in grants {
+            let grantee: Option<String> = row.get(0);
+            let grantable: bool = row.get(1);
+            let privileges: String = row.get(2);
+            statements.push(format!(
+                "GRANT {} ON TABLE {} TO {}{};",
+                privileges,
+                qualified,
+                grantee.map_or("PUBLIC".to_string(), |role| POSTGRES_DIALECT
+                    .quote_identifier(&role)),
+                if grantable { " WITH GRANT OPTION" } else { "" }
+            ));
+        }
+        Ok(statements)
+    }
 
-        let select_sql = format!(
-            "SELECT * FROM {}",
-            format_qualified_table(&POSTGRES_DIALECT, table)
-        );
+    async fn sequence_statements(&mut self, table: &str, values: bool) -> Result<Vec<String>> {
+        let oid = self.table_oid(table).await?;
+        let qualified = format_qualified_table(&POSTGRES_DIALECT, table);
+        let mut statements = Vec::new();
+        for sequence in self.column_sequences(oid).await? {
+            let column = POSTGRES_DIALECT.quote_identifier(&sequence.column);
+            if values {
+                let query = format!(
+                    "SELECT last_value, is_called FROM {}",
+                    format_qualified_table(&POSTGRES_DIALECT, &sequence.sequence)
+                );
+                let row = sqlx::query(&query).fetch_one(&mut self.conn).await?;
+                let last_value: i64 = row.get(0);
+                let is_called: bool = row.get(1);
+                // Looked up by column, as an identity's sequence is named by
+                // the server that creates it
+                statements.push(format!(
+                    "SELECT pg_catalog.setval(pg_catalog.pg_get_serial_sequence('{}', '{}'), {}, {});",
+                    qualified.replace('\'', "''"),
+                    sequence.column.replace('\'', "''"),
+                    last_value,
+                    is_called
+                ));
+            }
+            if sequence.always {
+                statements.push(format!(
+                    "ALTER TABLE {} ALTER COLUMN {} SET GENERATED ALWAYS;",
+                    qualified, column
+                ));
+            }
+        }
+        Ok(statements)
+    }
+
+    async fn stream_rows(&mut self, table: &str) -> Result<(Vec<String>, RowStream)> {
+        let columns = self.column_names(table).await?;
+
+        let select_sql = select_rows_sql(&POSTGRES_DIALECT, table, &columns);
 
         let rows = sqlx::query(&select_sql).fetch_all(&mut self.conn).await?;
 
-        let value_rows: Vec<Result<Vec<SqlValue>>> =
-            rows.into_iter().map(|row| convert_pg_row(row)).collect();
+        let value_rows: Vec<Result<Vec<SqlValue>>> = rows.into_iter().map(convert_pg_row).collect();
 
         let row_stream = stream::iter(value_rows);
         Ok((columns, Box::pin(row_stream)))
@@ -295,6 +922,20 @@ impl DbSession for PostgresSession {
         Ok(estimate.unwrap_or(0.0).max(0.0) as u64)
     }
 
+    async fn has_rows(&mut self, table: &str) -> Result<bool> {
+        let row: Option<i32> = sqlx::query_scalar(&any_row_sql(&POSTGRES_DIALECT, table))
+            .fetch_optional(&mut self.conn)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    async fn count_rows(&mut self, table: &str) -> Result<u64> {
+        let count: i64 = sqlx::query_scalar(&count_rows_sql(&POSTGRES_DIALECT, table))
+            .fetch_one(&mut self.conn)
+            .await?;
+        Ok(count.max(0) as u64)
+    }
+
     async fn insert_batch(
         &mut self,
         table: &str,
@@ -305,11 +946,49 @@ impl DbSession for PostgresSession {
             return Ok(());
         }
 
+        match self.insert_mode {
+            InsertMode::Literal => {}
+            InsertMode::Prepared => return self.insert_prepared(table, column_names, rows).await,
+            InsertMode::Copy => {
+                let columns: Vec<String> = column_names
+                    .iter()
+                    .map(|c| POSTGRES_DIALECT.quote_identifier(c))
+                    .collect();
+                let statement = format!(
+                    "COPY {} ({}) FROM STDIN",
+                    format_qualified_table(&POSTGRES_DIALECT, table),
+                    columns.join(", ")
+                );
+
+                let mut data = String::new();
+                for row in rows {
+                    encode_copy_row(row, &mut data);
+                }
+                return self.copy_in(&statement, data.as_bytes()).await;
+            }
+        }
+
         let sql = POSTGRES_DIALECT.insert_values_sql(table, column_names, rows);
         sqlx::query(&sql).execute(&mut self.conn).await?;
         Ok(())
     }
 
+    async fn copy_in(&mut self, statement: &str, data: &[u8]) -> Result<()> {
+        let mut copy = self.conn.copy_in_raw(statement).await?;
+        if let Err(e) = copy.send(data).await {
+            // Abort so the connection leaves COPY mode before reporting
+            let _ = copy.abort(e.to_string()).await;
+            return Err(e.into());
+        }
+        copy.finish().await?;
+        Ok(())
+    }
+
+    fn set_insert_mode(&mut self, mode: InsertMode) -> Result<()> {
+        self.insert_mode = mode;
+        Ok(())
+    }
+
     async fn disable_constraints(&mut self) -> Result<()> {
         sqlx::query("SET session_replication_role = 'replica'")
             .execute(&mut self.conn)
@@ -328,8 +1007,72 @@ impl DbSession for PostgresSession {
         Ok(())
     }
 
+    async fn enable_fast_load(&mut self) -> Result<bool> {
+        // Skips triggers and foreign key checks, like `disable_constraints`
+        sqlx::query("SET session_replication_role = 'replica'")
+            .execute(&mut self.conn)
+            .await?;
+        self.constraints_disabled = true;
+        // Commits return before their WAL is flushed to disk
+        sqlx::query("SET synchronous_commit = off")
+            .execute(&mut self.conn)
+            .await?;
+        Ok(true)
+    }
+
+    async fn set_statement_timeout(&mut self, timeout: Duration) -> Result<bool> {
+        sqlx::query(&format!("SET statement_timeout = {}", timeout.as_millis()))
+            .execute(&mut self.conn)
+            .await?;
+        Ok(true)
+    }
+
+    async fn set_keepalive(&mut self, interval: Duration) -> Result<bool> {
+        let seconds = interval.as_secs().max(1);
+        for sql in [
+            format!("SET tcp_keepalives_idle = {}", seconds),
+            format!("SET tcp_keepalives_interval = {}", seconds),
+            "SET idle_in_transaction_session_timeout = 0".to_string(),
+        ] {
+            sqlx::query(&sql).execute(&mut self.conn).await?;
+        }
+        Ok(true)
+    }
+
+    async fn drop_indexes(&mut self, table: &str) -> Result<Vec<String>> {
+        let qualified = format_qualified_table(&POSTGRES_DIALECT, table);
+        let indexes = sqlx::query(
+            "SELECT n.nspname, c.relname, pg_get_indexdef(i.indexrelid) AS indexdef
+             FROM pg_index i
+             INNER JOIN pg_class c ON c.oid = i.indexrelid
+             INNER JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE i.indrelid = $1::regclass
+               AND NOT EXISTS (SELECT 1 FROM pg_constraint con WHERE con.conindid = i.indexrelid)",
+        )
+        .bind(&qualified)
+        .fetch_all(&mut self.conn)
+        .await?;
+
+        let mut recreate = Vec::with_capacity(indexes.len());
+        for index in indexes {
+            let schema: String = index.get("nspname");
+            let name: String = index.get("relname");
+            sqlx::query(&format!(
+                "DROP INDEX {}.{}",
+                POSTGRES_DIALECT.quote_identifier(&schema),
+                POSTGRES_DIALECT.quote_identifier(&name)
+            ))
+            .execute(&mut self.conn)
+            .await?;
+            recreate.push(index.get("indexdef"));
+        }
+        Ok(recreate)
+    }
+
     async fn execute(&mut self, sql: &str) -> Result<()> {
-        sqlx::query(sql).execute(&mut self.conn).await?;
+        self.column_types.clear();
+        // Simple protocol, as DDL from `show_create_table` holds several statements
+        sqlx::Executor::execute(&mut self.conn, sql).await?;
         Ok(())
     }
 
@@ -341,6 +1084,20 @@ impl DbSession for PostgresSession {
         Ok(())
     }
 
+    async fn begin(&mut self) -> Result<bool> {
+        sqlx::query("BEGIN").execute(&mut self.conn).await?;
+        self.in_transaction = true;
+        Ok(true)
+    }
+
+    async fn rollback(&mut self) -> Result<()> {
+        if self.in_transaction {
+            sqlx::query("ROLLBACK").execute(&mut self.conn).await?;
+            self.in_transaction = false;
+        }
+        Ok(())
+    }
+
     async fn create_table_from_columns(
         &mut self,
         table: &str,
@@ -380,6 +1137,7 @@ impl DbSession for PostgresSession {
             column_defs.join(",\n    ")
         );
 
+        self.column_types.remove(table);
         sqlx::query(&sql)
             .execute(&mut self.conn)
             .await
@@ -387,9 +1145,365 @@ impl DbSession for PostgresSession {
 
         Ok(())
     }
+
+    async fn primary_key(&mut self, table: &str) -> Result<Vec<String>> {
+        let (schema, name) = parse_table_name(table)?;
+
+        let key: Vec<String> = sqlx::query_scalar(
+            "SELECT kcu.column_name
+             FROM information_schema.table_constraints tc
+             INNER JOIN information_schema.key_column_usage kcu
+               ON kcu.constraint_schema = tc.constraint_schema
+              AND kcu.constraint_name = tc.constraint_name
+              AND kcu.table_name = tc.table_name
+             WHERE tc.constraint_type = 'PRIMARY KEY'
+               AND tc.table_schema = $1 AND tc.table_name = $2
+             ORDER BY kcu.ordinal_position",
+        )
+        .bind(&schema)
+        .bind(&name)
+        .fetch_all(&mut self.conn)
+        .await?;
+
+        Ok(key)
+    }
+
+    async fn referenced_tables(&mut self, table: &str) -> Result<Vec<String>> {
+        let oid = self.table_oid(table).await?;
+        let tables: Vec<String> = sqlx::query_scalar(
+            "SELECT DISTINCT n.nspname || '.' || c.relname
+             FROM pg_catalog.pg_constraint con
+             JOIN pg_catalog.pg_class c ON c.oid = con.confrelid
+             JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+             WHERE con.conrelid = $1 AND con.contype = 'f'
+             ORDER BY 1",
+        )
+        .bind(oid)
+        .fetch_all(&mut self.conn)
+        .await?;
+
+        Ok(tables)
+    }
+
+    /// Enum labels come from `pg_enum`; `serial` columns and identities are
+    /// counters
+    async fn describe_columns(&mut self, table: &str) -> Result<Vec<Column>> {
+        let (schema, name) = parse_table_name(table)?;
+
+        let rows: Vec<(String, String, bool, bool, Option<Vec<String>>)> = sqlx::query_as(
+            "SELECT a.attname::text, format_type(a.atttypid, a.atttypmod), NOT a.attnotnull,
+                    a.attidentity <> ''
+                      OR pg_get_serial_sequence(format('%I.%I', n.nspname, c.relname), a.attname) IS NOT NULL,
+                    CASE WHEN t.typtype = 'e' THEN ARRAY(
+                      SELECT e.enumlabel::text FROM pg_enum e
+                      WHERE e.enumtypid = t.oid ORDER BY e.enumsortorder
+                    ) END
+             FROM pg_attribute a
+             JOIN pg_class c ON c.oid = a.attrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             JOIN pg_type t ON t.oid = a.atttypid
+             WHERE n.nspname = $1 AND c.relname = $2 AND a.attnum > 0 AND NOT a.attisdropped
+               AND a.attgenerated = ''
+             ORDER BY a.attnum",
+        )
+        .bind(&schema)
+        .bind(&name)
+        .fetch_all(&mut self.conn)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(name, declared, nullable, auto_increment, labels)| Column {
+                    name,
+                    column_type: match labels {
+                        Some(labels) => ColumnType::Enum(labels),
+                        None => ColumnType::parse(&declared),
+                    },
+                    declared,
+                    nullable,
+                    auto_increment,
+                },
+            )
+            .collect())
+    }
+
+    async fn read_chunk(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        limit: usize,
+    ) -> Result<(Vec<String>, Vec<Vec<SqlValue>>)> {
+        let columns = self.column_names(table).await?;
+
+        let select_sql = keyset_select_sql(&POSTGRES_DIALECT, table, &columns, key, after, limit);
+        let rows = sqlx::query(&select_sql)
+            .fetch_all(&mut self.conn)
+            .await?
+            .into_iter()
+            .map(convert_pg_row)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((columns, rows))
+    }
+
+    async fn key_after(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        offset: usize,
+    ) -> Result<Option<Vec<SqlValue>>> {
+        let offset_sql = key_offset_sql(&POSTGRES_DIALECT, table, key, after, offset);
+        let row = sqlx::query(&offset_sql)
+            .fetch_optional(&mut self.conn)
+            .await?;
+        row.map(convert_pg_row).transpose()
+    }
+
+    async fn read_key_window(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        upto: Option<&[SqlValue]>,
+    ) -> Result<(Vec<String>, Vec<Vec<SqlValue>>)> {
+        let columns = self.column_names(table).await?;
+
+        let select_sql =
+            key_window_select_sql(&POSTGRES_DIALECT, table, &columns, key, after, upto);
+        let rows = sqlx::query(&select_sql)
+            .fetch_all(&mut self.conn)
+            .await?
+            .into_iter()
+            .map(convert_pg_row)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((columns, rows))
+    }
+
+    async fn checksum_key_window(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        upto: Option<&[SqlValue]>,
+    ) -> Result<Option<String>> {
+        let columns = self.column_names(table).await?;
+        let Some(checksum_sql) =
+            key_window_checksum_sql(&POSTGRES_DIALECT, table, &columns, key, after, upto)
+        else {
+            return Ok(None);
+        };
+        let checksum: String = sqlx::query_scalar(&checksum_sql)
+            .fetch_one(&mut self.conn)
+            .await?;
+        Ok(Some(checksum))
+    }
+
+    async fn key_bounds(&mut self, table: &str, key: &str) -> Result<Option<(i64, i64)>> {
+        let bounds_sql = key_bounds_sql(&POSTGRES_DIALECT, table, key);
+        let row = sqlx::query(&bounds_sql).fetch_one(&mut self.conn).await?;
+
+        match convert_pg_row(row)?.as_slice() {
+            [SqlValue::Int(min), SqlValue::Int(max)] => Ok(Some((*min, *max))),
+            _ => Ok(None),
+        }
+    }
+
+    async fn stream_key_range(
+        &mut self,
+        table: &str,
+        key: &str,
+        range: RangeInclusive<i64>,
+    ) -> Result<(Vec<String>, RowStream)> {
+        let columns = self.column_names(table).await?;
+
+        let select_sql = key_range_select_sql(&POSTGRES_DIALECT, table, &columns, key, &range);
+        let rows = sqlx::query(&select_sql).fetch_all(&mut self.conn).await?;
+
+        let value_rows: Vec<Result<Vec<SqlValue>>> = rows.into_iter().map(convert_pg_row).collect();
+
+        let row_stream = stream::iter(value_rows);
+        Ok((columns, Box::pin(row_stream)))
+    }
+
+    async fn column_max(&mut self, table: &str, column: &str) -> Result<SqlValue> {
+        let max_sql = column_max_sql(&POSTGRES_DIALECT, table, column);
+        let row = sqlx::query(&max_sql).fetch_one(&mut self.conn).await?;
+        Ok(convert_pg_row(row)?.pop().unwrap_or(SqlValue::Null))
+    }
+
+    async fn stream_rows_since(
+        &mut self,
+        table: &str,
+        column: &str,
+        after: &SqlValue,
+        upto: &SqlValue,
+    ) -> Result<(Vec<String>, RowStream)> {
+        let columns = self.column_names(table).await?;
+
+        let select_sql =
+            watermark_select_sql(&POSTGRES_DIALECT, table, &columns, column, after, upto);
+        let rows = sqlx::query(&select_sql).fetch_all(&mut self.conn).await?;
+
+        let value_rows: Vec<Result<Vec<SqlValue>>> = rows.into_iter().map(convert_pg_row).collect();
+
+        let row_stream = stream::iter(value_rows);
+        Ok((columns, Box::pin(row_stream)))
+    }
+
+    async fn stream_rows_where(
+        &mut self,
+        table: &str,
+        condition: &str,
+    ) -> Result<(Vec<String>, RowStream)> {
+        let columns = self.column_names(table).await?;
+
+        let select_sql = filtered_select_sql(&POSTGRES_DIALECT, table, &columns, condition);
+        let rows = sqlx::query(&select_sql).fetch_all(&mut self.conn).await?;
+
+        let value_rows: Vec<Result<Vec<SqlValue>>> = rows.into_iter().map(convert_pg_row).collect();
+
+        let row_stream = stream::iter(value_rows);
+        Ok((columns, Box::pin(row_stream)))
+    }
+
+    /// `pgoutput` looks the publication up as it decodes, so it is committed
+    /// before the slot is created
+    async fn create_replication_slot(
+        &mut self,
+        slot: &str,
+        tables: &[String],
+    ) -> Result<Option<ReplicationPosition>> {
+        if tables.is_empty() {
+            return Err(anyhow!("No tables to publish for replication"));
+        }
+        let names: Vec<String> = tables
+            .iter()
+            .map(|table| format_qualified_table(&POSTGRES_DIALECT, table))
+            .collect();
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM pg_catalog.pg_publication WHERE pubname = $1)",
+        )
+        .bind(slot)
+        .fetch_one(&mut self.conn)
+        .await?;
+        let publish_sql = format!(
+            "{} PUBLICATION {} {} TABLE {}",
+            if exists { "ALTER" } else { "CREATE" },
+            POSTGRES_DIALECT.quote_identifier(slot),
+            if exists { "SET" } else { "FOR" },
+            names.join(", ")
+        );
+        sqlx::query(&publish_sql)
+            .execute(&mut self.conn)
+            .await
+            .with_context(|| format!("Failed to publish the tables as '{}'", slot))?;
+
+        let lsn: String = sqlx::query_scalar(
+            "SELECT lsn::text FROM pg_catalog.pg_create_logical_replication_slot($1, 'pgoutput')",
+        )
+        .bind(slot)
+        .fetch_one(&mut self.conn)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to create replication slot '{}' (needs wal_level = logical; \
+                 drop a slot left from an earlier run with SELECT pg_drop_replication_slot('{}'))",
+                slot, slot
+            )
+        })?;
+        Ok(Some(ReplicationPosition::Wal { lsn }))
+    }
+
+    async fn peek_slot_changes(&mut self, slot: &str, limit: usize) -> Result<Vec<Vec<u8>>> {
+        let messages = sqlx::query_scalar(
+            "SELECT data FROM pg_catalog.pg_logical_slot_peek_binary_changes($1, NULL, $2, \
+             'proto_version', '1', 'publication_names', $3)",
+        )
+        .bind(slot)
+        .bind(i32::try_from(limit).unwrap_or(i32::MAX))
+        .bind(POSTGRES_DIALECT.quote_identifier(slot))
+        .fetch_all(&mut self.conn)
+        .await
+        .with_context(|| format!("Failed to read replication slot '{}'", slot))?;
+        Ok(messages)
+    }
+
+    async fn advance_slot(&mut self, slot: &str, lsn: &str) -> Result<()> {
+        sqlx::query("SELECT pg_catalog.pg_replication_slot_advance($1, $2::pg_lsn)")
+            .bind(slot)
+            .bind(lsn)
+            .execute(&mut self.conn)
+            .await
+            .with_context(|| format!("Failed to advance replication slot '{}'", slot))?;
+        Ok(())
+    }
+}
+
+/// Bind a value to the next placeholder; the statement casts it to the
+/// column type. Decimals and temporal values travel as text so their input
+/// functions see the same form a literal would have.
+fn bind_value<'q>(
+    query: Query<'q, Postgres, PgArguments>,
+    value: &SqlValue,
+) -> Query<'q, Postgres, PgArguments> {
+    match value {
+        SqlValue::Null => query.bind(None::<String>),
+        SqlValue::Bool(v) => query.bind(*v),
+        SqlValue::Int(v) => query.bind(*v),
+        SqlValue::Float(v) => query.bind(*v),
+        SqlValue::Decimal(v) | SqlValue::String(v) => query.bind(v.clone()),
+        SqlValue::Bytes(v) => query.bind(v.clone()),
+        SqlValue::Date { .. } | SqlValue::Time { .. } | SqlValue::Timestamp { .. } => {
+            query.bind(value.temporal_text())
+        }
+    }
+}
+
+/// Append `row` to `out` as one line of COPY text format: tab separated,
+/// `\N` for NULL, backslash escapes for control characters
+fn encode_copy_row(row: &[SqlValue], out: &mut String) {
+    for (idx, value) in row.iter().enumerate() {
+        if idx > 0 {
+            out.push('\t');
+        }
+        match value {
+            SqlValue::Null => out.push_str("\\N"),
+            SqlValue::Bool(v) => out.push(if *v { 't' } else { 'f' }),
+            SqlValue::Int(v) => out.push_str(&v.to_string()),
+            SqlValue::Float(v) if v.is_nan() => out.push_str("NaN"),
+            SqlValue::Float(v) if v.is_infinite() => {
+                out.push_str(if *v > 0.0 { "Infinity" } else { "-Infinity" })
+            }
+            SqlValue::Float(v) => out.push_str(&v.to_string()),
+            SqlValue::Decimal(v) | SqlValue::String(v) => {
+                for ch in v.chars() {
+                    match ch {
+                        '\\' => out.push_str("\\\\"),
+                        '\t' => out.push_str("\\t"),
+                        '\n' => out.push_str("\\n"),
+                        '\r' => out.push_str("\\r"),
+                        _ => out.push(ch),
+                    }
+                }
+            }
+            // bytea hex input, with its backslash escaped for COPY
+            SqlValue::Bytes(v) => {
+                out.push_str("\\\\x");
+                out.push_str(&hex::encode(v));
+            }
+            SqlValue::Date { .. } | SqlValue::Time { .. } | SqlValue::Timestamp { .. } => {
+                out.push_str(&value.temporal_text().unwrap_or_default())
+            }
+        }
+    }
+    out.push('\n');
 }
 
-fn convert_pg_row(row: PgRow) -> Result<Vec<SqlValue>> {
+pub(super) fn convert_pg_row(row: PgRow) -> Result<Vec<SqlValue>> {
     let mut values = Vec::with_capacity(row.len());
     for idx in 0..row.len() {
         values.push(convert_pg_value(&row, idx)?);
@@ -407,17 +1521,15 @@ fn convert_pg_value(row: &PgRow, index: usize) -> Result<SqlValue> {
     let type_name = ti.name();
     let value = match type_name {
         "BOOL" => SqlValue::Bool(row.try_get(index)?),
-        "INT2" | "INT4" | "INT8" => {
-            let v: i64 = row.try_get(index)?;
-            SqlValue::Int(v)
-        }
-        "FLOAT4" | "FLOAT8" => {
-            let v: f64 = row.try_get(index)?;
-            SqlValue::Float(v)
-        }
+        // sqlx only decodes integers into a Rust type of the same width
+        "INT2" => SqlValue::Int(row.try_get::<i16, _>(index)? as i64),
+        "INT4" => SqlValue::Int(row.try_get::<i32, _>(index)? as i64),
+        "INT8" => SqlValue::Int(row.try_get(index)?),
+        "FLOAT4" => SqlValue::Float(row.try_get::<f32, _>(index)? as f64),
+        "FLOAT8" => SqlValue::Float(row.try_get(index)?),
         "NUMERIC" | "DECIMAL" => {
-            let v: String = row.try_get(index)?;
-            SqlValue::Decimal(v)
+            let v: sqlx::types::BigDecimal = row.try_get(index)?;
+            SqlValue::Decimal(v.to_string())
         }
         "TEXT" | "VARCHAR" | "BPCHAR" | "NAME" | "JSON" | "JSONB" => {
             let v: String = row.try_get(index)?;
@@ -453,7 +1565,7 @@ fn convert_pg_value(row: &PgRow, index: usize) -> Result<SqlValue> {
     Ok(value)
 }
 
-fn parse_table_name(table: &str) -> Result<(String, String)> {
+pub(super) fn parse_table_name(table: &str) -> Result<(String, String)> {
     let (schema, name) = split_table_name(table);
     let schema = schema
         .map(|s| s.to_string())
@@ -461,11 +1573,24 @@ fn parse_table_name(table: &str) -> Result<(String, String)> {
     Ok((schema, name.to_string()))
 }
 
-fn table_matches(pattern: &str, qualified: &str) -> bool {
-    if pattern.contains('.') {
-        pattern.eq_ignore_ascii_case(qualified)
-    } else {
-        let (_, table_name) = split_table_name(qualified);
-        pattern.eq_ignore_ascii_case(table_name)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_copy_text_rows() {
+        let mut out = String::new();
+        encode_copy_row(
+            &[
+                SqlValue::Int(1),
+                SqlValue::Null,
+                SqlValue::String("a\tb\\c\nd".to_string()),
+                SqlValue::Bytes(vec![0xde, 0xad]),
+                SqlValue::Bool(false),
+                SqlValue::Float(f64::NEG_INFINITY),
+            ],
+            &mut out,
+        );
+        assert_eq!(out, "1\t\\N\ta\\tb\\\\c\\nd\t\\\\xdead\tf\t-Infinity\n");
     }
 }
@@ -0,0 +1,684 @@
+use super::ddl::Column;
+use super::dialect::SqlDialect;
+use super::filter::TableFilter;
+use super::value::SqlValue;
+use super::{DbEngine, DbSession, InsertMode, ReplicationPosition, Routine, RowStream};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Longest wait between two attempts, however many have failed
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// `--retries` and `--retry-backoff`: how often a transient failure is
+/// retried and how long to wait before the first retry. The wait doubles
+/// with every further attempt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `attempt` (counting from zero)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_BACKOFF)
+    }
+}
+
+/// Whether `error` is worth retrying on a fresh connection: the connection
+/// broke or timed out, the server is shutting down or out of connections, or
+/// the statement lost a deadlock or serialization conflict
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        if let Some(error) = cause.downcast_ref::<sqlx::Error>() {
+            return match error {
+                sqlx::Error::Io(_)
+                | sqlx::Error::PoolTimedOut
+                | sqlx::Error::PoolClosed
+                | sqlx::Error::WorkerCrashed => true,
+                sqlx::Error::Database(error) => {
+                    let mysql_number = error
+                        .try_downcast_ref::<sqlx::mysql::MySqlDatabaseError>()
+                        .map(|e| e.number());
+                    matches!(mysql_number, Some(1040 | 1053 | 1205 | 1213))
+                        || error
+                            .code()
+                            .is_some_and(|code| is_transient_sqlstate(&code))
+                }
+                _ => false,
+            };
+        }
+        if let Some(error) = cause.downcast_ref::<tiberius::error::Error>() {
+            return matches!(error, tiberius::error::Error::Io { .. });
+        }
+        if let Some(error) = cause.downcast_ref::<std::io::Error>() {
+            return is_transient_io(error.kind());
+        }
+        false
+    })
+}
+
+/// Connection exceptions (class 08), serialization failures and deadlocks,
+/// too many connections, and server shutdown
+fn is_transient_sqlstate(code: &str) -> bool {
+    code.starts_with("08")
+        || matches!(
+            code,
+            "40001" | "40P01" | "53300" | "57P01" | "57P02" | "57P03"
+        )
+}
+
+fn is_transient_io(kind: std::io::ErrorKind) -> bool {
+    use std::io::ErrorKind::*;
+    matches!(
+        kind,
+        ConnectionReset
+            | ConnectionAborted
+            | ConnectionRefused
+            | NotConnected
+            | BrokenPipe
+            | TimedOut
+            | UnexpectedEof
+    )
+}
+
+/// Wraps an engine so its sessions survive transient failures: the failed
+/// call is retried with exponential backoff on a new connection, after the
+/// session settings made so far (insert mode, constraint checks, fast load)
+/// are applied to it again. Sessions inside a transaction are never retried,
+/// since the transaction's work went with the old connection.
+pub struct RetryEngine {
+    inner: Arc<dyn DbEngine>,
+    policy: RetryPolicy,
+}
+
+impl RetryEngine {
+    pub fn new(inner: Box<dyn DbEngine>, policy: RetryPolicy) -> Self {
+        Self {
+            inner: inner.into(),
+            policy,
+        }
+    }
+}
+
+#[async_trait]
+impl DbEngine for RetryEngine {
+    async fn connect(&self, url: &str) -> Result<Box<dyn DbSession>> {
+        let mut attempt = 0;
+        let session = loop {
+            match self.inner.connect(url).await {
+                Ok(session) => break session,
+                Err(error) => wait_to_retry(&self.policy, error, &mut attempt).await?,
+            }
+        };
+
+        Ok(Box::new(RetrySession {
+            engine: self.inner.clone(),
+            url: url.to_string(),
+            policy: self.policy,
+            session,
+            insert_mode: None,
+            constraints_disabled: false,
+            fast_load: false,
+        }))
+    }
+
+    fn dialect(&self) -> Option<&'static dyn SqlDialect> {
+        self.inner.dialect()
+    }
+}
+
+/// Return `error` when it is not transient or the retries are used up;
+/// otherwise report it and sleep until the next attempt
+async fn wait_to_retry(
+    policy: &RetryPolicy,
+    error: anyhow::Error,
+    attempt: &mut u32,
+) -> Result<()> {
+    if *attempt >= policy.retries || !is_transient(&error) {
+        return Err(error);
+    }
+
+    let delay = policy.delay(*attempt);
+    *attempt += 1;
+    eprintln!(
+        "Transient error ({:#}); retrying in {:.1}s (attempt {}/{})",
+        error,
+        delay.as_secs_f64(),
+        attempt,
+        policy.retries
+    );
+    tokio::time::sleep(delay).await;
+    Ok(())
+}
+
+struct RetrySession {
+    engine: Arc<dyn DbEngine>,
+    url: String,
+    policy: RetryPolicy,
+    session: Box<dyn DbSession>,
+    /// Settings replayed on a replacement connection
+    insert_mode: Option<InsertMode>,
+    constraints_disabled: bool,
+    fast_load: bool,
+}
+
+/// Run `$call` on the current session (bound to `$session`), retrying on a
+/// new connection while its error is transient and attempts remain
+macro_rules! with_retry {
+    ($self:ident, |$session:ident| $call:expr) => {{
+        let mut attempt = 0;
+        loop {
+            let $session = &mut *$self.session;
+            match $call.await {
+                Ok(value) => break Ok(value),
+                Err(error) if $self.session.in_transaction() => break Err(error),
+                Err(error) => $self.recover(error, &mut attempt).await?,
+            }
+        }
+    }};
+}
+
+impl RetrySession {
+    /// Wait out a transient `error` and replace the session with a new
+    /// connection, retrying the connect itself while attempts remain
+    async fn recover(&mut self, error: anyhow::Error, attempt: &mut u32) -> Result<()> {
+        let mut error = error;
+        loop {
+            wait_to_retry(&self.policy, error, attempt).await?;
+            match self.reconnect().await {
+                Ok(()) => return Ok(()),
+                Err(e) => error = e,
+            }
+        }
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        let mut session = self.engine.connect(&self.url).await?;
+        if let Some(mode) = self.insert_mode {
+            session.set_insert_mode(mode)?;
+        }
+        if self.constraints_disabled {
+            session.disable_constraints().await?;
+        }
+        if self.fast_load {
+            session.enable_fast_load().await?;
+        }
+        self.session = session;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DbSession for RetrySession {
+    fn dialect(&self) -> &'static dyn SqlDialect {
+        self.session.dialect()
+    }
+
+    fn in_transaction(&self) -> bool {
+        self.session.in_transaction()
+    }
+
+    async fn start_consistent_snapshot(&mut self) -> Result<()> {
+        self.session.start_consistent_snapshot().await
+    }
+
+    async fn start_snapshot_at_position(&mut self) -> Result<Option<ReplicationPosition>> {
+        self.session.start_snapshot_at_position().await
+    }
+
+    async fn create_replication_slot(
+        &mut self,
+        slot: &str,
+        tables: &[String],
+    ) -> Result<Option<ReplicationPosition>> {
+        self.session.create_replication_slot(slot, tables).await
+    }
+
+    async fn list_tables(&mut self, filter: &TableFilter) -> Result<Vec<String>> {
+        with_retry!(self, |s| s.list_tables(filter))
+    }
+
+    async fn show_create_table(&mut self, table: &str) -> Result<String> {
+        with_retry!(self, |s| s.show_create_table(table))
+    }
+
+    async fn list_views(&mut self, filter: &TableFilter) -> Result<Vec<String>> {
+        with_retry!(self, |s| s.list_views(filter))
+    }
+
+    async fn show_create_view(&mut self, view: &str) -> Result<String> {
+        with_retry!(self, |s| s.show_create_view(view))
+    }
+
+    async fn list_routines(&mut self) -> Result<Vec<Routine>> {
+        with_retry!(self, |s| s.list_routines())
+    }
+
+    async fn show_create_routine(&mut self, routine: &Routine) -> Result<String> {
+        with_retry!(self, |s| s.show_create_routine(routine))
+    }
+
+    async fn list_events(&mut self) -> Result<Vec<String>> {
+        with_retry!(self, |s| s.list_events())
+    }
+
+    async fn show_create_event(&mut self, event: &str) -> Result<String> {
+        with_retry!(self, |s| s.show_create_event(event))
+    }
+
+    async fn list_databases(&mut self) -> Result<Vec<String>> {
+        with_retry!(self, |s| s.list_databases())
+    }
+
+    async fn create_database(&mut self, name: &str) -> Result<bool> {
+        with_retry!(self, |s| s.create_database(name))
+    }
+
+    async fn user_statements(&mut self) -> Result<(Vec<String>, Vec<String>)> {
+        with_retry!(self, |s| s.user_statements())
+    }
+
+    async fn extension_statements(&mut self) -> Result<Vec<String>> {
+        with_retry!(self, |s| s.extension_statements())
+    }
+
+    async fn comment_statements(&mut self, table: &str) -> Result<Vec<String>> {
+        with_retry!(self, |s| s.comment_statements(table))
+    }
+
+    async fn privilege_statements(&mut self, table: &str) -> Result<Vec<String>> {
+        with_retry!(self, |s| s.privilege_statements(table))
+    }
+
+    async fn sequence_statements(&mut self, table: &str, values: bool) -> Result<Vec<String>> {
+        with_retry!(self, |s| s.sequence_statements(table, values))
+    }
+
+    async fn list_triggers(&mut self, table: &str) -> Result<Vec<String>> {
+        with_retry!(self, |s| s.list_triggers(table))
+    }
+
+    async fn show_create_trigger(&mut self, table: &str, trigger: &str) -> Result<String> {
+        with_retry!(self, |s| s.show_create_trigger(table, trigger))
+    }
+
+    /// Only opening the stream is retried; a scan that breaks part-way
+    /// cannot be resumed (chunked reads through `read_chunk` can)
+    async fn stream_rows(&mut self, table: &str) -> Result<(Vec<String>, RowStream)> {
+        with_retry!(self, |s| s.stream_rows(table))
+    }
+
+    async fn approximate_row_count(&mut self, table: &str) -> Result<u64> {
+        with_retry!(self, |s| s.approximate_row_count(table))
+    }
+
+    async fn has_rows(&mut self, table: &str) -> Result<bool> {
+        with_retry!(self, |s| s.has_rows(table))
+    }
+
+    async fn count_rows(&mut self, table: &str) -> Result<u64> {
+        with_retry!(self, |s| s.count_rows(table))
+    }
+
+    fn set_insert_mode(&mut self, mode: InsertMode) -> Result<()> {
+        self.session.set_insert_mode(mode)?;
+        self.insert_mode = Some(mode);
+        Ok(())
+    }
+
+    fn max_statement_bytes(&self) -> Option<usize> {
+        self.session.max_statement_bytes()
+    }
+
+    async fn copy_in(&mut self, statement: &str, data: &[u8]) -> Result<()> {
+        with_retry!(self, |s| s.copy_in(statement, data))
+    }
+
+    async fn insert_batch(
+        &mut self,
+        table: &str,
+        column_names: &[String],
+        rows: &[Vec<SqlValue>],
+    ) -> Result<()> {
+        with_retry!(self, |s| s.insert_batch(table, column_names, rows))
+    }
+
+    async fn disable_constraints(&mut self) -> Result<()> {
+        self.session.disable_constraints().await?;
+        self.constraints_disabled = true;
+        Ok(())
+    }
+
+    async fn enable_constraints(&mut self) -> Result<()> {
+        self.session.enable_constraints().await?;
+        self.constraints_disabled = false;
+        Ok(())
+    }
+
+    async fn enable_fast_load(&mut self) -> Result<bool> {
+        self.fast_load = self.session.enable_fast_load().await?;
+        Ok(self.fast_load)
+    }
+
+    // Engines below this one apply the timeouts to each new connection
+    async fn set_statement_timeout(&mut self, timeout: Duration) -> Result<bool> {
+        self.session.set_statement_timeout(timeout).await
+    }
+
+    async fn set_keepalive(&mut self, interval: Duration) -> Result<bool> {
+        self.session.set_keepalive(interval).await
+    }
+
+    /// Not retried: a drop that went through before the connection broke
+    /// would come back without the statements to rebuild its indexes
+    async fn drop_indexes(&mut self, table: &str) -> Result<Vec<String>> {
+        self.session.drop_indexes(table).await
+    }
+
+    async fn execute(&mut self, sql: &str) -> Result<()> {
+        with_retry!(self, |s| s.execute(sql))
+    }
+
+    async fn commit(&mut self) -> Result<()> {
+        self.session.commit().await
+    }
+
+    async fn begin(&mut self) -> Result<bool> {
+        self.session.begin().await
+    }
+
+    async fn rollback(&mut self) -> Result<()> {
+        self.session.rollback().await
+    }
+
+    async fn create_table_from_columns(
+        &mut self,
+        table: &str,
+        column_names: &[String],
+        column_types: &[SqlValue],
+    ) -> Result<()> {
+        with_retry!(self, |s| s.create_table_from_columns(
+            table,
+            column_names,
+            column_types
+        ))
+    }
+
+    async fn primary_key(&mut self, table: &str) -> Result<Vec<String>> {
+        with_retry!(self, |s| s.primary_key(table))
+    }
+
+    async fn referenced_tables(&mut self, table: &str) -> Result<Vec<String>> {
+        with_retry!(self, |s| s.referenced_tables(table))
+    }
+
+    async fn describe_columns(&mut self, table: &str) -> Result<Vec<Column>> {
+        with_retry!(self, |s| s.describe_columns(table))
+    }
+
+    async fn column_charsets(&mut self, table: &str) -> Result<Vec<(String, String)>> {
+        with_retry!(self, |s| s.column_charsets(table))
+    }
+
+    async fn read_chunk(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        limit: usize,
+    ) -> Result<(Vec<String>, Vec<Vec<SqlValue>>)> {
+        with_retry!(self, |s| s.read_chunk(table, key, after, limit))
+    }
+
+    async fn key_after(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        offset: usize,
+    ) -> Result<Option<Vec<SqlValue>>> {
+        with_retry!(self, |s| s.key_after(table, key, after, offset))
+    }
+
+    async fn read_key_window(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        upto: Option<&[SqlValue]>,
+    ) -> Result<(Vec<String>, Vec<Vec<SqlValue>>)> {
+        with_retry!(self, |s| s.read_key_window(table, key, after, upto))
+    }
+
+    async fn checksum_key_window(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        upto: Option<&[SqlValue]>,
+    ) -> Result<Option<String>> {
+        with_retry!(self, |s| s.checksum_key_window(table, key, after, upto))
+    }
+
+    async fn export_snapshot(&mut self) -> Result<Option<String>> {
+        self.session.export_snapshot().await
+    }
+
+    async fn import_snapshot(&mut self, snapshot: &str) -> Result<()> {
+        self.session.import_snapshot(snapshot).await
+    }
+
+    async fn key_bounds(&mut self, table: &str, key: &str) -> Result<Option<(i64, i64)>> {
+        with_retry!(self, |s| s.key_bounds(table, key))
+    }
+
+    async fn stream_key_range(
+        &mut self,
+        table: &str,
+        key: &str,
+        range: RangeInclusive<i64>,
+    ) -> Result<(Vec<String>, RowStream)> {
+        with_retry!(self, |s| s.stream_key_range(table, key, range.clone()))
+    }
+
+    async fn column_max(&mut self, table: &str, column: &str) -> Result<SqlValue> {
+        with_retry!(self, |s| s.column_max(table, column))
+    }
+
+    async fn stream_rows_since(
+        &mut self,
+        table: &str,
+        column: &str,
+        after: &SqlValue,
+        upto: &SqlValue,
+    ) -> Result<(Vec<String>, RowStream)> {
+        with_retry!(self, |s| s.stream_rows_since(table, column, after, upto))
+    }
+
+    async fn stream_rows_where(
+        &mut self,
+        table: &str,
+        condition: &str,
+    ) -> Result<(Vec<String>, RowStream)> {
+        with_retry!(self, |s| s.stream_rows_where(table, condition))
+    }
+
+    async fn peek_slot_changes(&mut self, slot: &str, limit: usize) -> Result<Vec<Vec<u8>>> {
+        with_retry!(self, |s| s.peek_slot_changes(slot, limit))
+    }
+
+    async fn advance_slot(&mut self, slot: &str, lsn: &str) -> Result<()> {
+        with_retry!(self, |s| s.advance_slot(slot, lsn))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::dialects::sqlite::SQLITE_DIALECT;
+    use std::io::{Error, ErrorKind};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Sessions whose first `failures` inserts (counted across connections)
+    /// fail as if the connection dropped
+    struct FlakyEngine {
+        connects: AtomicUsize,
+        failures: AtomicUsize,
+        inserted: Arc<AtomicUsize>,
+    }
+
+    struct FlakySession {
+        engine: &'static FlakyEngine,
+        constraints_disabled: bool,
+    }
+
+    #[async_trait]
+    impl DbEngine for &'static FlakyEngine {
+        async fn connect(&self, _url: &str) -> Result<Box<dyn DbSession>> {
+            self.connects.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(FlakySession {
+                engine: self,
+                constraints_disabled: false,
+            }))
+        }
+    }
+
+    #[async_trait]
+    impl DbSession for FlakySession {
+        fn dialect(&self) -> &'static dyn SqlDialect {
+            &SQLITE_DIALECT
+        }
+
+        fn in_transaction(&self) -> bool {
+            false
+        }
+
+        async fn start_consistent_snapshot(&mut self) -> Result<()> {
+            unimplemented!()
+        }
+
+        async fn list_tables(&mut self, _: &TableFilter) -> Result<Vec<String>> {
+            unimplemented!()
+        }
+
+        async fn show_create_table(&mut self, _: &str) -> Result<String> {
+            unimplemented!()
+        }
+
+        async fn stream_rows(&mut self, _: &str) -> Result<(Vec<String>, RowStream)> {
+            unimplemented!()
+        }
+
+        async fn approximate_row_count(&mut self, _: &str) -> Result<u64> {
+            unimplemented!()
+        }
+
+        async fn insert_batch(
+            &mut self,
+            _: &str,
+            _: &[String],
+            rows: &[Vec<SqlValue>],
+        ) -> Result<()> {
+            assert!(self.constraints_disabled, "settings not replayed");
+            let failures = &self.engine.failures;
+            if failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok()
+            {
+                return Err(Error::from(ErrorKind::ConnectionReset).into());
+            }
+            self.engine.inserted.fetch_add(rows.len(), Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn disable_constraints(&mut self) -> Result<()> {
+            self.constraints_disabled = true;
+            Ok(())
+        }
+
+        async fn enable_constraints(&mut self) -> Result<()> {
+            self.constraints_disabled = false;
+            Ok(())
+        }
+
+        async fn execute(&mut self, _: &str) -> Result<()> {
+            anyhow::bail!("syntax error")
+        }
+
+        async fn commit(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn create_table_from_columns(
+            &mut self,
+            _: &str,
+            _: &[String],
+            _: &[SqlValue],
+        ) -> Result<()> {
+            unimplemented!()
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn retries_transient_failures_on_a_new_connection() {
+        let flaky: &'static FlakyEngine = Box::leak(Box::new(FlakyEngine {
+            connects: AtomicUsize::new(0),
+            failures: AtomicUsize::new(2),
+            inserted: Arc::new(AtomicUsize::new(0)),
+        }));
+        let policy = RetryPolicy {
+            retries: 2,
+            backoff: Duration::from_millis(1),
+        };
+        let engine = RetryEngine::new(Box::new(flaky), policy);
+
+        let mut session = engine.connect("flaky://").await.unwrap();
+        session.disable_constraints().await.unwrap();
+        let rows = vec![vec![SqlValue::Int(1)]; 3];
+        session
+            .insert_batch("t", &["id".into()], &rows)
+            .await
+            .unwrap();
+        assert_eq!(flaky.inserted.load(Ordering::SeqCst), 3);
+        assert_eq!(flaky.connects.load(Ordering::SeqCst), 3);
+
+        // Errors that are not transient come straight back
+        assert!(session.execute("SELEC 1").await.is_err());
+        assert_eq!(flaky.connects.load(Ordering::SeqCst), 3);
+
+        // Once the retries are used up, the error surfaces
+        flaky.failures.store(3, Ordering::SeqCst);
+        assert!(session
+            .insert_batch("t", &["id".into()], &rows)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn classifies_errors_and_backs_off() {
+        assert!(is_transient(&Error::from(ErrorKind::BrokenPipe).into()));
+        assert!(is_transient(
+            &anyhow::Error::new(sqlx::Error::PoolTimedOut).context("Failed to connect")
+        ));
+        assert!(!is_transient(
+            &Error::from(ErrorKind::PermissionDenied).into()
+        ));
+        assert!(!is_transient(&anyhow::anyhow!("syntax error")));
+        assert!(is_transient_sqlstate("08006"));
+        assert!(!is_transient_sqlstate("23505"));
+
+        let policy = RetryPolicy {
+            retries: 10,
+            backoff: Duration::from_millis(500),
+        };
+        assert_eq!(policy.delay(0), Duration::from_millis(500));
+        assert_eq!(policy.delay(3), Duration::from_secs(4));
+        assert_eq!(policy.delay(20), MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,650 @@
+use super::{DbEngine, DbSession, RowStream};
+use crate::engine::ddl::{self, ColumnType};
+use crate::engine::dialect::{
+    any_row_sql, column_max_sql, count_rows_sql, filtered_select_sql, format_qualified_table,
+    key_bounds_sql, key_offset_sql, key_range_select_sql, key_window_select_sql, keyset_select_sql,
+    select_rows_sql, watermark_select_sql, SqlDialect,
+};
+use crate::engine::filter::TableFilter;
+use crate::engine::value::SqlValue;
+use crate::util::dialects::sqlite::SQLITE_DIALECT;
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use futures::stream;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection, SqliteRow, SqliteValueRef};
+use sqlx::{Column, ConnectOptions, Row, TypeInfo, ValueRef};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+pub struct SqliteEngine;
+
+#[async_trait]
+impl DbEngine for SqliteEngine {
+    async fn connect(&self, url: &str) -> Result<Box<dyn DbSession>> {
+        let conn = SqliteConnectOptions::from_str(url)
+            .context("Invalid SQLite URL")?
+            .create_if_missing(true)
+            .connect()
+            .await
+            .with_context(|| format!("Failed to open SQLite database at {}", url))?;
+
+        Ok(Box::new(SqliteSession {
+            conn,
+            in_transaction: false,
+            constraints_disabled: false,
+        }))
+    }
+
+    fn dialect(&self) -> Option<&'static dyn SqlDialect> {
+        Some(&SQLITE_DIALECT)
+    }
+}
+
+pub struct SqliteSession {
+    conn: SqliteConnection,
+    in_transaction: bool,
+    constraints_disabled: bool,
+}
+
+impl SqliteSession {
+    async fn table_info(&mut self, table: &str) -> Result<Vec<SqliteRow>> {
+        let pragma = format!(
+            "PRAGMA table_info({})",
+            SQLITE_DIALECT.quote_identifier(table)
+        );
+        Ok(sqlx::query(&pragma).fetch_all(&mut self.conn).await?)
+    }
+}
+
+#[async_trait]
+impl DbSession for SqliteSession {
+    fn dialect(&self) -> &'static dyn SqlDialect {
+        &SQLITE_DIALECT
+    }
+
+    async fn start_consistent_snapshot(&mut self) -> Result<()> {
+        // A read transaction in SQLite already sees a stable snapshot of the file
+        sqlx::query("BEGIN").execute(&mut self.conn).await?;
+        self.in_transaction = true;
+        Ok(())
+    }
+
+    async fn list_tables(&mut self, filter: &TableFilter) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            "SELECT name FROM sqlite_master
+             WHERE type = 'table' AND name NOT LIKE 'sqlite_%'
+             ORDER BY name",
+        )
+        .fetch_all(&mut self.conn)
+        .await
+        .context("Failed to list tables")?;
+
+        let mut tables: Vec<String> = rows.iter().map(|row| row.get::<String, _>(0)).collect();
+
+        filter.apply(&mut tables);
+
+        Ok(tables)
+    }
+
+    async fn list_views(&mut self, filter: &TableFilter) -> Result<Vec<String>> {
+        let rows = sqlx::query("SELECT name FROM sqlite_master WHERE type = 'view' ORDER BY name")
+            .fetch_all(&mut self.conn)
+            .await
+            .context("Failed to list views")?;
+
+        let mut views: Vec<String> = rows.iter().map(|row| row.get::<String, _>(0)).collect();
+
+        filter.apply(&mut views);
+
+        Ok(views)
+    }
+
+    async fn show_create_view(&mut self, view: &str) -> Result<String> {
+        sqlx::query_scalar("SELECT sql FROM sqlite_master WHERE type = 'view' AND name = ?")
+            .bind(view)
+            .fetch_optional(&mut self.conn)
+            .await?
+            .ok_or_else(|| anyhow!("View {} not found", view))
+    }
+
+    async fn list_triggers(&mut self, table: &str) -> Result<Vec<String>> {
+        sqlx::query_scalar(
+            "SELECT name FROM sqlite_master WHERE type = 'trigger' AND tbl_name = ? ORDER BY name",
+        )
+        .bind(table)
+        .fetch_all(&mut self.conn)
+        .await
+        .with_context(|| format!("Failed to list triggers on {}", table))
+    }
+
+    async fn show_create_trigger(&mut self, _table: &str, trigger: &str) -> Result<String> {
+        sqlx::query_scalar("SELECT sql FROM sqlite_master WHERE type = 'trigger' AND name = ?")
+            .bind(trigger)
+            .fetch_optional(&mut self.conn)
+            .await?
+            .ok_or_else(|| anyhow!("Trigger {} not found", trigger))
+    }
+
+    async fn show_create_table(&mut self, table: &str) -> Result<String> {
+        let create_stmt: String =
+            sqlx::query_scalar("SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?")
+                .bind(table)
+                .fetch_optional(&mut self.conn)
+                .await?
+                .ok_or_else(|| anyhow!("Table {} not found", table))?;
+
+        let mut full_ddl = minify_create_table(&create_stmt);
+        full_ddl.push(';');
+
+        // Automatic indexes (from UNIQUE/PRIMARY KEY) have no SQL and are recreated by the table
+        let index_rows: Vec<String> = sqlx::query_scalar(
+            "SELECT sql FROM sqlite_master
+             WHERE type = 'index' AND tbl_name = ? AND sql IS NOT NULL
+             ORDER BY name",
+        )
+        .bind(table)
+        .fetch_all(&mut self.conn)
+        .await?;
+
+        for index_sql in index_rows {
+            full_ddl.push('\n');
+            full_ddl.push_str(&minify_create_table(&index_sql));
+            full_ddl.push(';');
+        }
+
+        Ok(full_ddl)
+    }
+
+    async fn stream_rows(&mut self, table: &str) -> Result<(Vec<String>, RowStream)> {
+        let columns: Vec<String> = self
+            .table_info(table)
+            .await?
+            .iter()
+            .map(|row| row.get::<String, _>("name"))
+            .collect();
+
+        let select_sql = select_rows_sql(&SQLITE_DIALECT, table, &columns);
+        let rows = sqlx::query(&select_sql).fetch_all(&mut self.conn).await?;
+
+        let value_rows: Vec<Result<Vec<SqlValue>>> =
+            rows.into_iter().map(convert_sqlite_row).collect();
+
+        let row_stream = stream::iter(value_rows);
+        Ok((columns, Box::pin(row_stream)))
+    }
+
+    async fn approximate_row_count(&mut self, table: &str) -> Result<u64> {
+        // SQLite keeps no row estimates; an exact count is cheap for the files it targets
+        self.count_rows(table).await
+    }
+
+    async fn has_rows(&mut self, table: &str) -> Result<bool> {
+        let row: Option<i64> = sqlx::query_scalar(&any_row_sql(&SQLITE_DIALECT, table))
+            .fetch_optional(&mut self.conn)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    async fn count_rows(&mut self, table: &str) -> Result<u64> {
+        let count: i64 = sqlx::query_scalar(&count_rows_sql(&SQLITE_DIALECT, table))
+            .fetch_one(&mut self.conn)
+            .await?;
+        Ok(count.max(0) as u64)
+    }
+
+    async fn insert_batch(
+        &mut self,
+        table: &str,
+        column_names: &[String],
+        rows: &[Vec<SqlValue>],
+    ) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let sql = SQLITE_DIALECT.insert_values_sql(table, column_names, rows);
+        sqlx::query(&sql)
+            .execute(&mut self.conn)
+            .await
+            .with_context(|| format!("Failed to insert batch into table '{}'", table))?;
+
+        Ok(())
+    }
+
+    async fn disable_constraints(&mut self) -> Result<()> {
+        sqlx::query("PRAGMA foreign_keys = OFF")
+            .execute(&mut self.conn)
+            .await?;
+        self.constraints_disabled = true;
+        Ok(())
+    }
+
+    async fn enable_constraints(&mut self) -> Result<()> {
+        if self.constraints_disabled {
+            sqlx::query("PRAGMA foreign_keys = ON")
+                .execute(&mut self.conn)
+                .await?;
+            self.constraints_disabled = false;
+        }
+        Ok(())
+    }
+
+    async fn execute(&mut self, sql: &str) -> Result<()> {
+        sqlx::query(sql)
+            .execute(&mut self.conn)
+            .await
+            .context("Failed to execute SQL statement")?;
+        Ok(())
+    }
+
+    async fn commit(&mut self) -> Result<()> {
+        if self.in_transaction {
+            sqlx::query("COMMIT").execute(&mut self.conn).await?;
+            self.in_transaction = false;
+        }
+        Ok(())
+    }
+
+    async fn begin(&mut self) -> Result<bool> {
+        sqlx::query("BEGIN").execute(&mut self.conn).await?;
+        self.in_transaction = true;
+        Ok(true)
+    }
+
+    async fn rollback(&mut self) -> Result<()> {
+        if self.in_transaction {
+            sqlx::query("ROLLBACK").execute(&mut self.conn).await?;
+            self.in_transaction = false;
+        }
+        Ok(())
+    }
+
+    async fn create_table_from_columns(
+        &mut self,
+        table: &str,
+        column_names: &[String],
+        column_types: &[SqlValue],
+    ) -> Result<()> {
+        let qualified = format_qualified_table(&SQLITE_DIALECT, table);
+        let mut column_defs = Vec::new();
+
+        for (col_name, col_type) in column_names.iter().zip(column_types.iter()) {
+            let col_quoted = SQLITE_DIALECT.quote_identifier(col_name);
+            let type_str = match col_type {
+                SqlValue::Int(_) => "INTEGER",
+                SqlValue::Float(_) => "REAL",
+                SqlValue::Decimal(_) => "NUMERIC",
+                SqlValue::Bool(_) => "BOOLEAN",
+                SqlValue::String(_) => "TEXT",
+                SqlValue::Date { .. } => "DATE",
+                SqlValue::Time { .. } => "TIME",
+                SqlValue::Timestamp { .. } => "DATETIME",
+                SqlValue::Bytes(_) => "BLOB",
+                SqlValue::Null => "TEXT",
+            };
+
+            let pk = if col_name == "id" && matches!(col_type, SqlValue::Int(_)) {
+                " PRIMARY KEY"
+            } else {
+                ""
+            };
+
+            column_defs.push(format!("{} {}{}", col_quoted, type_str, pk));
+        }
+
+        let sql = format!(
+            "CREATE TABLE IF NOT EXISTS {} (\n    {}\n);",
+            qualified,
+            column_defs.join(",\n    ")
+        );
+
+        sqlx::query(&sql)
+            .execute(&mut self.conn)
+            .await
+            .context("Failed to create table")?;
+
+        Ok(())
+    }
+
+    async fn primary_key(&mut self, table: &str) -> Result<Vec<String>> {
+        // The pk column holds the 1-based position within the key, 0 otherwise
+        let mut key: Vec<(i64, String)> = self
+            .table_info(table)
+            .await?
+            .iter()
+            .map(|row| (row.get::<i64, _>("pk"), row.get::<String, _>("name")))
+            .filter(|(position, _)| *position > 0)
+            .collect();
+        key.sort();
+        Ok(key.into_iter().map(|(_, name)| name).collect())
+    }
+
+    /// A sole `INTEGER PRIMARY KEY` aliases the rowid, which SQLite fills
+    /// in itself
+    async fn describe_columns(&mut self, table: &str) -> Result<Vec<ddl::Column>> {
+        let rows = self.table_info(table).await?;
+        let keyed = rows
+            .iter()
+            .filter(|row| row.get::<i64, _>("pk") > 0)
+            .count();
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let declared: String = row.get("type");
+                let rowid = keyed == 1
+                    && row.get::<i64, _>("pk") == 1
+                    && declared.eq_ignore_ascii_case("INTEGER");
+                ddl::Column {
+                    name: row.get("name"),
+                    column_type: ColumnType::parse(&declared),
+                    nullable: row.get::<i64, _>("notnull") == 0 && !rowid,
+                    auto_increment: rowid,
+                    declared,
+                }
+            })
+            .collect())
+    }
+
+    async fn read_chunk(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        limit: usize,
+    ) -> Result<(Vec<String>, Vec<Vec<SqlValue>>)> {
+        let columns: Vec<String> = self
+            .table_info(table)
+            .await?
+            .iter()
+            .map(|row| row.get::<String, _>("name"))
+            .collect();
+
+        let select_sql = keyset_select_sql(&SQLITE_DIALECT, table, &columns, key, after, limit);
+        let rows = sqlx::query(&select_sql)
+            .fetch_all(&mut self.conn)
+            .await?
+            .into_iter()
+            .map(convert_sqlite_row)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((columns, rows))
+    }
+
+    async fn key_after(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        offset: usize,
+    ) -> Result<Option<Vec<SqlValue>>> {
+        let offset_sql = key_offset_sql(&SQLITE_DIALECT, table, key, after, offset);
+        let row = sqlx::query(&offset_sql)
+            .fetch_optional(&mut self.conn)
+            .await?;
+        row.map(convert_sqlite_row).transpose()
+    }
+
+    async fn read_key_window(
+        &mut self,
+        table: &str,
+        key: &[String],
+        after: Option<&[SqlValue]>,
+        upto: Option<&[SqlValue]>,
+    ) -> Result<(Vec<String>, Vec<Vec<SqlValue>>)> {
+        let columns: Vec<String> = self
+            .table_info(table)
+            .await?
+            .iter()
+            .map(|row| row.get::<String, _>("name"))
+            .collect();
+
+        let select_sql = key_window_select_sql(&SQLITE_DIALECT, table, &columns, key, after, upto);
+        let rows = sqlx::query(&select_sql)
+            .fetch_all(&mut self.conn)
+            .await?
+            .into_iter()
+            .map(convert_sqlite_row)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((columns, rows))
+    }
+
+    async fn key_bounds(&mut self, table: &str, key: &str) -> Result<Option<(i64, i64)>> {
+        let bounds_sql = key_bounds_sql(&SQLITE_DIALECT, table, key);
+        let row = sqlx::query(&bounds_sql).fetch_one(&mut self.conn).await?;
+
+        match convert_sqlite_row(row)?.as_slice() {
+            [SqlValue::Int(min), SqlValue::Int(max)] => Ok(Some((*min, *max))),
+            _ => Ok(None),
+        }
+    }
+
+    async fn stream_key_range(
+        &mut self,
+        table: &str,
+        key: &str,
+        range: RangeInclusive<i64>,
+    ) -> Result<(Vec<String>, RowStream)> {
+        let columns: Vec<String> = self
+            .table_info(table)
+            .await?
+            .iter()
+            .map(|row| row.get::<String, _>("name"))
+            .collect();
+
+        let select_sql = key_range_select_sql(&SQLITE_DIALECT, table, &columns, key, &range);
+        let rows = sqlx::query(&select_sql).fetch_all(&mut self.conn).await?;
+
+        let value_rows: Vec<Result<Vec<SqlValue>>> =
+            rows.into_iter().map(convert_sqlite_row).collect();
+
+        let row_stream = stream::iter(value_rows);
+        Ok((columns, Box::pin(row_stream)))
+    }
+
+    async fn column_max(&mut self, table: &str, column: &str) -> Result<SqlValue> {
+        let max_sql = column_max_sql(&SQLITE_DIALECT, table, column);
+        let row = sqlx::query(&max_sql).fetch_one(&mut self.conn).await?;
+        Ok(convert_sqlite_row(row)?.pop().unwrap_or(SqlValue::Null))
+    }
+
+    async fn stream_rows_since(
+        &mut self,
+        table: &str,
+        column: &str,
+        after: &SqlValue,
+        upto: &SqlValue,
+    ) -> Result<(Vec<String>, RowStream)> {
+        let columns: Vec<String> = self
+            .table_info(table)
+            .await?
+            .iter()
+            .map(|row| row.get::<String, _>("name"))
+            .collect();
+
+        let select_sql =
+            watermark_select_sql(&SQLITE_DIALECT, table, &columns, column, after, upto);
+        let rows = sqlx::query(&select_sql).fetch_all(&mut self.conn).await?;
+
+        let value_rows: Vec<Result<Vec<SqlValue>>> =
+            rows.into_iter().map(convert_sqlite_row).collect();
+
+        let row_stream = stream::iter(value_rows);
+        Ok((columns, Box::pin(row_stream)))
+    }
+
+    async fn stream_rows_where(
+        &mut self,
+        table: &str,
+        condition: &str,
+    ) -> Result<(Vec<String>, RowStream)> {
+        let columns: Vec<String> = self
+            .table_info(table)
+            .await?
+            .iter()
+            .map(|row| row.get::<String, _>("name"))
+            .collect();
+
+        let select_sql = filtered_select_sql(&SQLITE_DIALECT, table, &columns, condition);
+        let rows = sqlx::query(&select_sql).fetch_all(&mut self.conn).await?;
+
+        let value_rows: Vec<Result<Vec<SqlValue>>> =
+            rows.into_iter().map(convert_sqlite_row).collect();
+
+        let row_stream = stream::iter(value_rows);
+        Ok((columns, Box::pin(row_stream)))
+    }
+}
+
+fn convert_sqlite_row(row: SqliteRow) -> Result<Vec<SqlValue>> {
+    let mut values = Vec::with_capacity(row.len());
+    for idx in 0..row.len() {
+        values.push(convert_sqlite_value(&row, idx)?);
+    }
+    Ok(values)
+}
+
+fn convert_sqlite_value(row: &SqliteRow, index: usize) -> Result<SqlValue> {
+    let raw: SqliteValueRef<'_> = row.try_get_raw(index)?;
+    if raw.is_null() {
+        return Ok(SqlValue::Null);
+    }
+
+    // Storage class of this value, refined by the column's declared affinity
+    let storage = raw.type_info().name().to_string();
+    let declared = row.column(index).type_info().name().to_ascii_uppercase();
+
+    let value = match storage.as_str() {
+        "INTEGER" | "BOOLEAN" => {
+            let v: i64 = row.try_get_unchecked(index)?;
+            if declared.contains("BOOL") {
+                SqlValue::Bool(v != 0)
+            } else {
+                SqlValue::Int(v)
+            }
+        }
+        "REAL" => {
+            let v: f64 = row.try_get_unchecked(index)?;
+            SqlValue::Float(v)
+        }
+        "BLOB" => {
+            let v: Vec<u8> = row.try_get_unchecked(index)?;
+            SqlValue::Bytes(v)
+        }
+        _ => {
+            let v: String = row.try_get_unchecked(index)?;
+            parse_declared_text(&declared, v)
+        }
+    };
+
+    Ok(value)
+}
+
+/// Interpret a TEXT value according to the declared column type, falling back to a string
+fn parse_declared_text(declared: &str, text: String) -> SqlValue {
+    use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+    if declared.contains("DATETIME") || declared.contains("TIMESTAMP") {
+        for fmt in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+            if let Ok(dt) = NaiveDateTime::parse_from_str(&text, fmt) {
+                return SqlValue::from_datetime(dt);
+            }
+        }
+    } else if declared.contains("DATE") {
+        if let Ok(date) = NaiveDate::parse_from_str(&text, "%Y-%m-%d") {
+            return SqlValue::from_date(date);
+        }
+    } else if declared.contains("TIME") {
+        if let Ok(time) = NaiveTime::parse_from_str(&text, "%H:%M:%S%.f") {
+            return SqlValue::from_time(time);
+        }
+    } else if declared.contains("NUMERIC") || declared.contains("DECIMAL") {
+        return SqlValue::Decimal(text);
+    }
+
+    SqlValue::String(text)
+}
+
+/// Minify CREATE TABLE statement to single line and add IF NOT EXISTS
+fn minify_create_table(create_stmt: &str) -> String {
+    let single_line = create_stmt
+        .lines()
+        .map(|l| l.trim())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    for prefix in ["CREATE TABLE ", "CREATE INDEX ", "CREATE UNIQUE INDEX "] {
+        if let Some(rest) = single_line.strip_prefix(prefix) {
+            if rest.starts_with("IF NOT EXISTS") {
+                break;
+            }
+            return format!("{}IF NOT EXISTS {}", prefix, rest);
+        }
+    }
+
+    single_line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+
+    #[test]
+    fn test_minify_create_table() {
+        let input = "CREATE TABLE \"users\" (\n  id INTEGER PRIMARY KEY,\n  name TEXT\n)";
+        let output = minify_create_table(input);
+        assert_eq!(
+            output,
+            "CREATE TABLE IF NOT EXISTS \"users\" ( id INTEGER PRIMARY KEY, name TEXT )"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn leaves_generated_columns_out_of_rows() {
+        let mut session = SqliteEngine.connect("sqlite::memory:").await.unwrap();
+        session
+            .execute("CREATE TABLE t (id INTEGER PRIMARY KEY, doubled INTEGER GENERATED ALWAYS AS (n * 2), n INTEGER)")
+            .await
+            .unwrap();
+        session
+            .execute("INSERT INTO t (id, n) VALUES (1, 10), (2, 20)")
+            .await
+            .unwrap();
+
+        let (columns, rows) = session.stream_rows("t").await.unwrap();
+        let rows: Vec<Vec<SqlValue>> = rows.try_collect().await.unwrap();
+        assert_eq!(columns, vec!["id", "n"]);
+        assert_eq!(rows[1], vec![SqlValue::Int(2), SqlValue::Int(20)]);
+
+        let (columns, rows) = session
+            .read_chunk("t", &["id".to_string()], None, 1)
+            .await
+            .unwrap();
+        assert_eq!(columns, vec!["id", "n"]);
+        assert_eq!(rows, vec![vec![SqlValue::Int(1), SqlValue::Int(10)]]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reads_triggers_from_sqlite_master() {
+        let mut session = SqliteEngine.connect("sqlite::memory:").await.unwrap();
+        session
+            .execute("CREATE TABLE t (id INTEGER PRIMARY KEY, n INTEGER)")
+            .await
+            .unwrap();
+        session
+            .execute("CREATE TABLE u (id INTEGER PRIMARY KEY)")
+            .await
+            .unwrap();
+        let create = "CREATE TRIGGER t_bump AFTER INSERT ON t BEGIN UPDATE t SET n = n + 1 WHERE id = NEW.id; END";
+        session.execute(create).await.unwrap();
+
+        assert_eq!(session.list_triggers("t").await.unwrap(), vec!["t_bump"]);
+        assert!(session.list_triggers("u").await.unwrap().is_empty());
+        assert_eq!(
+            session.show_create_trigger("t", "t_bump").await.unwrap(),
+            create
+        );
+        assert!(session.show_create_trigger("t", "missing").await.is_err());
+    }
+}
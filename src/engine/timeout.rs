@@ -0,0 +1,133 @@
+use super::dialect::SqlDialect;
+use super::{DbEngine, DbSession};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// `--connect-timeout`, `--statement-timeout` and `--keepalive`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Give up on a connection attempt after this long
+    pub connect: Option<Duration>,
+    /// Have the server abort any statement running longer than this
+    pub statement: Option<Duration>,
+    /// Have the server probe the connection this often and let it idle in a
+    /// transaction
+    pub keepalive: Option<Duration>,
+}
+
+impl Timeouts {
+    pub fn is_set(&self) -> bool {
+        self.connect.is_some() || self.statement.is_some() || self.keepalive.is_some()
+    }
+}
+
+/// Wrapper bounding how long connecting may take and applying the statement
+/// timeout and keepalive settings to every session it opens. A connect timeout surfaces as an I/O
+/// `TimedOut` error, so `--retries` treats it as transient.
+pub struct TimeoutEngine {
+    inner: Box<dyn DbEngine>,
+    timeouts: Timeouts,
+    /// Whether the provider was already found to lack a statement timeout
+    warned: AtomicBool,
+    /// Whether the provider was already found to lack keepalive settings
+    warned_keepalive: AtomicBool,
+}
+
+impl TimeoutEngine {
+    pub fn new(inner: Box<dyn DbEngine>, timeouts: Timeouts) -> Self {
+        Self {
+            inner,
+            timeouts,
+            warned: AtomicBool::new(false),
+            warned_keepalive: AtomicBool::new(false),
+        }
+    }
+}
+
+#[async_trait]
+impl DbEngine for TimeoutEngine {
+    async fn connect(&self, url: &str) -> Result<Box<dyn DbSession>> {
+        let mut session = match self.timeouts.connect {
+            Some(limit) => tokio::time::timeout(limit, self.inner.connect(url))
+                .await
+                .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))
+                .with_context(|| format!("Connecting took longer than {:?}", limit))??,
+            None => self.inner.connect(url).await?,
+        };
+
+        if let Some(limit) = self.timeouts.statement {
+            let applied = session
+                .set_statement_timeout(limit)
+                .await
+                .context("Failed to set statement timeout")?;
+            if !applied && !self.warned.swap(true, Ordering::Relaxed) {
+                eprintln!(
+                    "Note: this provider has no statement timeout; --statement-timeout is ignored"
+                );
+            }
+        }
+
+        if let Some(interval) = self.timeouts.keepalive {
+            let applied = session
+                .set_keepalive(interval)
+                .await
+                .context("Failed to set keepalive")?;
+            if !applied && !self.warned_keepalive.swap(true, Ordering::Relaxed) {
+                eprintln!("Note: this provider has no keepalive settings; idle snapshot sessions are still pinged");
+            }
+        }
+
+        Ok(session)
+    }
+
+    fn dialect(&self) -> Option<&'static dyn SqlDialect> {
+        self.inner.dialect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::retry::is_transient;
+    use crate::engine::sqlite::SqliteEngine;
+
+    /// Never finishes connecting
+    struct HangingEngine;
+
+    #[async_trait]
+    impl DbEngine for HangingEngine {
+        async fn connect(&self, _url: &str) -> Result<Box<dyn DbSession>> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn gives_up_on_a_hung_connect() {
+        let engine = TimeoutEngine::new(
+            Box::new(HangingEngine),
+            Timeouts {
+                connect: Some(Duration::from_millis(20)),
+                ..Timeouts::default()
+            },
+        );
+        let error = engine.connect("hang://").await.err().unwrap();
+        assert!(error.to_string().contains("longer than 20ms"));
+        assert!(is_transient(&error));
+
+        // A provider without the setting still connects
+        let engine = TimeoutEngine::new(
+            Box::new(SqliteEngine),
+            Timeouts {
+                connect: Some(Duration::from_secs(5)),
+                statement: Some(Duration::from_secs(1)),
+                keepalive: Some(Duration::from_secs(60)),
+            },
+        );
+        assert!(engine.connect("sqlite::memory:").await.is_ok());
+        assert!(engine.warned.load(Ordering::Relaxed));
+        assert!(engine.warned_keepalive.load(Ordering::Relaxed));
+    }
+}
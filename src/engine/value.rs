@@ -66,6 +66,49 @@ impl SqlValue {
             us: time.nanosecond() / 1_000,
         }
     }
+
+    /// Helper to render date and time values as ISO 8601 text, the form
+    /// database input functions accept. Other values return `None`.
+    pub fn temporal_text(&self) -> Option<String> {
+        let fraction = |us: u32| {
+            if us == 0 {
+                String::new()
+            } else {
+                format!(".{:06}", us)
+            }
+        };
+
+        match self {
+            SqlValue::Date { y, m, d } => Some(format!("{:04}-{:02}-{:02}", y, m, d)),
+            SqlValue::Time { neg, h, m, s, us } => Some(format!(
+                "{}{:02}:{:02}:{:02}{}",
+                if *neg { "-" } else { "" },
+                h,
+                m,
+                s,
+                fraction(*us)
+            )),
+            SqlValue::Timestamp {
+                y,
+                m,
+                d,
+                hh,
+                mm,
+                ss,
+                us,
+            } => Some(format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}:{:02}{}",
+                y,
+                m,
+                d,
+                hh,
+                mm,
+                ss,
+                fraction(*us)
+            )),
+            _ => None,
+        }
+    }
 }
 
 impl From<Option<bool>> for SqlValue {
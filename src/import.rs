@@ -1,5 +1,7 @@
+use crate::engine::filter::TableFilter;
 use crate::engine::value::SqlValue;
-use crate::engine::{DbEngine, DbSession};
+use crate::engine::{DbEngine, DbSession, InsertMode};
+use crate::util::batch::{row_sql_len, BatchLimit, BatchRows};
 use anyhow::{bail, Context, Result};
 use chrono::Datelike;
 use csv::ReaderBuilder;
@@ -7,14 +9,19 @@ use indicatif::{ProgressBar, ProgressStyle};
 use std::collections::HashMap;
 use std::fs::File;
 use std::path::Path;
+use std::time::Instant;
 
 pub struct ImportOptions {
     pub input: String,
     pub table: String,
-    pub batch_rows: usize,
+    pub batch_rows: BatchRows,
+    /// Also flush a batch once its SQL reaches about this many bytes
+    pub batch_bytes: Option<usize>,
     pub disable_fk_checks: bool,
     pub skip_errors: bool,
     pub column_mapping: Option<HashMap<String, String>>,
+    /// How rows are sent to the database
+    pub insert_mode: InsertMode,
 }
 
 pub async fn import(engine: &dyn DbEngine, url: &str, options: ImportOptions) -> Result<()> {
@@ -26,6 +33,7 @@ pub async fn import(engine: &dyn DbEngine, url: &str, options: ImportOptions) ->
         .connect(url)
         .await
         .context("Failed to connect to database")?;
+    session.set_insert_mode(options.insert_mode)?;
 
     // Check if input file exists
     if !Path::new(&options.input).exists() {
@@ -69,7 +77,7 @@ pub async fn import(engine: &dyn DbEngine, url: &str, options: ImportOptions) ->
 
     // Check if table exists
     let tables = session
-        .list_tables(&[options.table.clone()], &[])
+        .list_tables(&TableFilter::new(std::slice::from_ref(&options.table), &[]))
         .await
         .context("Failed to list tables")?;
 
@@ -106,7 +114,14 @@ pub async fn import(engine: &dyn DbEngine, url: &str, options: ImportOptions) ->
     // Skip header
     let _headers = csv_reader.headers().context("Failed to read CSV headers")?;
 
+    let mut tuner = options.batch_rows.tuner();
+    let mut limit = BatchLimit::new(
+        tuner.rows(),
+        options.batch_bytes,
+        session.max_statement_bytes(),
+    );
     let mut batch: Vec<(usize, Vec<SqlValue>)> = Vec::new();
+    let mut batch_bytes = 0usize;
     let mut error_rows: Vec<(usize, String)> = Vec::new();
     let mut row_number = 1; // Header is row 1
     let mut total_inserted = 0u64;
@@ -125,9 +140,11 @@ pub async fn import(engine: &dyn DbEngine, url: &str, options: ImportOptions) ->
         match result {
             Ok(row) => match parse_row(&row, &csv_columns, &db_columns, &inferred_types) {
                 Ok(values) => {
+                    batch_bytes += row_sql_len(&values);
                     batch.push((row_number, values));
 
-                    if batch.len() >= options.batch_rows {
+                    if limit.is_full(batch.len(), batch_bytes) {
+                        let started = Instant::now();
                         total_inserted += insert_batch_with_row_tracking(
                             &mut *session,
                             &options.table,
@@ -138,8 +155,11 @@ pub async fn import(engine: &dyn DbEngine, url: &str, options: ImportOptions) ->
                         )
                         .await
                         .context("Failed to insert batch")?;
+                        tuner.record(batch.len(), started.elapsed());
+                        limit.rows = tuner.rows();
                         progress.set_message(format!("Inserted {} rows...", total_inserted));
                         batch.clear();
+                        batch_bytes = 0;
                     }
                 }
                 Err(e) => {
@@ -199,6 +219,9 @@ pub async fn import(engine: &dyn DbEngine, url: &str, options: ImportOptions) ->
     println!("Total rows:    {} (including header)", row_number);
     println!("Inserted:      {} rows ✓", total_inserted);
     println!("Failed:        {} rows ✗", error_rows.len());
+    if tuner.is_adaptive() {
+        println!("Batch size:    {} rows (auto)", tuner.rows());
+    }
     println!("═══════════════════════════════════════");
 
     // Show failed rows
@@ -367,19 +390,17 @@ fn detect_value_type(value: &str) -> String {
     }
 
     // Check for date (without time)
-    if let Ok(_) = NaiveDate::parse_from_str(trimmed, "%Y-%m-%d") {
+    if NaiveDate::parse_from_str(trimmed, "%Y-%m-%d").is_ok() {
         return "date".to_string();
     }
 
     // Check for float
-    if let Ok(_) = trimmed.parse::<f64>() {
-        if trimmed.contains('.') {
-            return "float".to_string();
-        }
+    if trimmed.parse::<f64>().is_ok() && trimmed.contains('.') {
+        return "float".to_string();
     }
 
     // Check for integer
-    if let Ok(_) = trimmed.parse::<i64>() {
+    if trimmed.parse::<i64>().is_ok() {
         return "int".to_string();
     }
 
@@ -0,0 +1,750 @@
+//! Library side of the `migrasquiel` binary.
+//!
+//! Downstream crates can plug in their own providers by implementing
+//! [`engine::DbEngine`] and calling [`engine::register_engine`] before
+//! parsing the command line:
+//!
+//! ```no_run
+//! # use migrasquiel::engine::{register_engine, DbEngine};
+//! # fn acme_engine() -> Box<dyn DbEngine> { unimplemented!() }
+//! register_engine("acme", acme_engine);
+//! ```
+//!
+//! After registration `--provider acme` resolves like a built-in provider, and
+//! [`run`] drives the same commands as the `migrasquiel` binary.
+
+pub mod bench;
+pub mod cli;
+pub mod dump;
+pub mod engine;
+pub mod export;
+pub mod import;
+pub mod migrate;
+pub mod replicate;
+pub mod restore;
+pub mod sync;
+pub mod util;
+
+use anyhow::{bail, Context, Result};
+use cli::{Cli, Commands};
+use engine::cloudsql::CloudSqlEngine;
+use engine::ddl::TypeMap;
+use engine::dialect::OnConflict;
+use engine::filter::TableFilter;
+use engine::init_sql::InitSqlEngine;
+use engine::rds_iam::RdsIamEngine;
+use engine::retry::{RetryEngine, RetryPolicy};
+use engine::timeout::{TimeoutEngine, Timeouts};
+use engine::DbEngine;
+use util::compress::{Codec, Level};
+use util::encrypt::{Decryption, Encryption};
+use util::keepalive::SnapshotWatch;
+use util::remap::{ColumnRenames, Remap};
+use util::state::{FailedTables, FailedTablesFile, WatermarkFile};
+use util::subset::RowSubset;
+use util::throttle::Throttle;
+
+/// Execute a parsed command line
+pub async fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Commands::Dump {
+            source,
+            source_env,
+            output,
+            provider,
+            aws_iam_auth,
+            cloudsql_instance,
+            tables,
+            exclude,
+            tables_regex,
+            exclude_regex,
+            schemas,
+            databases,
+            all_databases,
+            create_database,
+            schema_only,
+            data_only,
+            exclude_data,
+            insert_ignore,
+            replace,
+            batch_rows,
+            extended_insert,
+            complete_insert,
+            batch_bytes,
+            chunk_rows,
+            limit_rows,
+            sample,
+            consistent_snapshot,
+            master_data,
+            compress,
+            gzip,
+            compress_level,
+            fast,
+            best,
+            encrypt,
+            write_buffer_mb,
+            format,
+            resume,
+            reproducible,
+            compatible,
+            comments,
+            privileges,
+            post_data,
+            skip_views,
+            skip_triggers,
+            routines,
+            skip_definer,
+            events,
+            users,
+            jobs,
+            max_connections,
+            max_rows_per_sec,
+            max_mb_per_sec,
+            keepalive,
+            snapshot_warn_age,
+            retries,
+            retry_backoff,
+            connect_timeout,
+            statement_timeout,
+        } => {
+            let source_url = Commands::get_url(&source, &source_env, "source")?;
+
+            eprintln!("Connecting to: {}", Commands::redact_url(&source_url));
+
+            let engine = create_engine(
+                &provider,
+                aws_iam_auth,
+                cloudsql_instance.as_deref(),
+                RetryPolicy {
+                    retries,
+                    backoff: retry_backoff,
+                },
+                Timeouts {
+                    connect: connect_timeout,
+                    statement: statement_timeout,
+                    keepalive,
+                },
+            )?;
+
+            let opts = dump::DumpOptions {
+                filter: TableFilter::new(&tables, &exclude)
+                    .with_regexes(&tables_regex, &exclude_regex)?,
+                schemas,
+                databases: (all_databases || !databases.is_empty()).then_some(databases),
+                create_database,
+                schema_only,
+                data_only,
+                exclude_data,
+                on_conflict: match (insert_ignore, replace) {
+                    (_, true) => OnConflict::Replace,
+                    (true, false) => OnConflict::Ignore,
+                    (false, false) => OnConflict::Error,
+                },
+                batch_rows,
+                batch_bytes,
+                extended_insert,
+                complete_insert,
+                chunk_rows,
+                consistent_snapshot,
+                master_data,
+                compress: match (compress, gzip) {
+                    (Some(codec), _) => codec,
+                    (None, true) => Codec::Gzip,
+                    (None, false) => Codec::from_path(&output),
+                },
+                compress_level: match (compress_level, fast, best) {
+                    (Some(level), _, _) => Some(Level::Exact(level)),
+                    (None, true, _) => Some(Level::Fast),
+                    (None, _, true) => Some(Level::Best),
+                    (None, false, false) => None,
+                },
+                encrypt: Encryption::new(encrypt),
+                jobs,
+                max_connections,
+                throttle: Throttle::new(max_rows_per_sec, max_mb_per_sec),
+                snapshot_watch: SnapshotWatch {
+                    keepalive,
+                    warn_age: Some(snapshot_warn_age).filter(|age| !age.is_zero()),
+                },
+                subset: RowSubset {
+                    limit: limit_rows,
+                    sample,
+                },
+                write_buffer: write_buffer_mb * 1024 * 1024,
+                format,
+                resume,
+                reproducible,
+                compatible,
+                skip_views,
+                skip_triggers,
+                routines,
+                skip_definer,
+                events,
+                users,
+                comments,
+                privileges,
+                post_data,
+            };
+
+            dump::dump(&*engine, &source_url, &output, opts).await?;
+        }
+
+        Commands::Restore {
+            destination,
+            destination_env,
+            input,
+            provider,
+            aws_iam_auth,
+            cloudsql_instance,
+            disable_fk_checks,
+            tables,
+            schema_only,
+            data_only,
+            fast_load,
+            skip_definer,
+            insert_mode,
+            identity,
+            jobs,
+            commit_every,
+            single_transaction,
+            skip_errors,
+            errors_file,
+            state_file,
+            resume,
+            on_conflict,
+            remap_schema,
+            remap_table,
+            no_drop,
+            dry_run,
+            retries,
+            retry_backoff,
+            connect_timeout,
+            statement_timeout,
+            init_sql,
+            init_sql_file,
+        } => {
+            // A dry run never connects, so needs no destination
+            let dest_url = match dry_run {
+                true => None,
+                false => Some(Commands::get_url(
+                    &destination,
+                    &destination_env,
+                    "destination",
+                )?),
+            };
+            if let Some(url) = &dest_url {
+                println!("Connecting to: {}", Commands::redact_url(url));
+            }
+
+            let engine = create_engine(
+                &provider,
+                aws_iam_auth,
+                cloudsql_instance.as_deref(),
+                RetryPolicy {
+                    retries,
+                    backoff: retry_backoff,
+                },
+                Timeouts {
+                    connect: connect_timeout,
+                    statement: statement_timeout,
+                    keepalive: None,
+                },
+            )?;
+            let engine = with_init_sql(engine, &init_sql, init_sql_file.as_deref())?;
+
+            let opts = restore::RestoreOptions {
+                disable_fk_checks,
+                fast_load,
+                jobs,
+                tables,
+                schema_only,
+                data_only,
+                skip_definer,
+                insert_mode,
+                decryption: Decryption::from_files(&identity)?,
+                skip_errors: skip_errors.then(|| errors_file.into()),
+                commit_every,
+                single_transaction,
+                state_file: state_file.into(),
+                resume,
+                on_conflict: on_conflict.unwrap_or_default(),
+                remap: Remap::new(&remap_schema, &remap_table)?,
+                no_drop,
+            };
+            if no_drop {
+                println!("Leaving out DROP TABLE; existing tables are kept");
+            }
+            if !opts.remap.is_empty() {
+                println!("Remapping schemas and tables as statements are restored");
+            }
+
+            let inputs = restore::expand_inputs(&input)?;
+            match dest_url {
+                Some(url) => restore::restore(&*engine, &url, &inputs, opts).await?,
+                None => restore::dry_run(&*engine, &inputs, &opts)?,
+            }
+        }
+
+        Commands::Migrate {
+            source,
+            source_env,
+            destination,
+            destination_env,
+            provider,
+            aws_iam_auth,
+            cloudsql_instance,
+            source_provider,
+            dest_provider,
+            source_aws_iam_auth,
+            dest_aws_iam_auth,
+            source_cloudsql_instance,
+            dest_cloudsql_instance,
+            tables,
+            exclude,
+            tables_regex,
+            exclude_regex,
+            schemas,
+            databases,
+            all_databases,
+            schema_only,
+            data_only,
+            exclude_data,
+            batch_rows,
+            batch_bytes,
+            chunk_rows,
+            limit_rows,
+            sample,
+            consistent_snapshot,
+            disable_fk_checks,
+            skip_errors,
+            continue_on_error,
+            retry_failed,
+            failed_tables,
+            verify,
+            if_exists,
+            no_drop,
+            create_destination,
+            allow_drift,
+            auto_alter,
+            convert_charset,
+            identifier_case,
+            jobs,
+            split_ranges,
+            max_connections,
+            max_inflight_batches,
+            max_rows_per_sec,
+            max_mb_per_sec,
+            fast_load,
+            drop_indexes,
+            defer_indexes,
+            insert_mode,
+            keepalive,
+            snapshot_warn_age,
+            retries,
+            retry_backoff,
+            connect_timeout,
+            statement_timeout,
+            init_sql,
+            init_sql_file,
+            type_map,
+            rename_column,
+            incremental,
+            incremental_state,
+            position_file,
+            slot,
+        } => {
+            let source_url = Commands::get_url(&source, &source_env, "source")?;
+            let dest_url = Commands::get_url(&destination, &destination_env, "destination")?;
+
+            println!("Source: {}", Commands::redact_url(&source_url));
+            println!("Destination: {}", Commands::redact_url(&dest_url));
+
+            let retry = RetryPolicy {
+                retries,
+                backoff: retry_backoff,
+            };
+            let timeouts = Timeouts {
+                connect: connect_timeout,
+                statement: statement_timeout,
+                keepalive,
+            };
+            let source_engine = create_engine(
+                source_provider.as_deref().unwrap_or(&provider),
+                aws_iam_auth || source_aws_iam_auth,
+                source_cloudsql_instance
+                    .as_deref()
+                    .or(cloudsql_instance.as_deref()),
+                retry,
+                timeouts,
+            )?;
+            let dest_engine = create_engine(
+                dest_provider.as_deref().unwrap_or(&provider),
+                aws_iam_auth || dest_aws_iam_auth,
+                dest_cloudsql_instance
+                    .as_deref()
+                    .or(cloudsql_instance.as_deref()),
+                retry,
+                timeouts,
+            )?;
+            let dest_engine = with_init_sql(dest_engine, &init_sql, init_sql_file.as_deref())?;
+
+            let tables = if retry_failed {
+                let failed = FailedTables::load(failed_tables.as_ref())?;
+                if failed.tables.is_empty() {
+                    bail!("'{}' notes no failed tables", failed_tables);
+                }
+                println!(
+                    "Retrying {} table(s) that failed before",
+                    failed.tables.len()
+                );
+                failed.tables.into_keys().collect()
+            } else {
+                tables
+            };
+
+            let opts = migrate::MigrateOptions {
+                filter: TableFilter::new(&tables, &exclude)
+                    .with_regexes(&tables_regex, &exclude_regex)?,
+                schemas,
+                databases: (all_databases || !databases.is_empty()).then_some(databases),
+                schema_only,
+                data_only,
+                exclude_data,
+                batch_rows,
+                batch_bytes,
+                chunk_rows,
+                consistent_snapshot,
+                disable_fk_checks,
+                skip_errors,
+                jobs,
+                split_ranges,
+                insert_mode,
+                max_connections,
+                max_inflight_batches,
+                fast_load,
+                drop_indexes,
+                defer_indexes,
+                throttle: Throttle::new(max_rows_per_sec, max_mb_per_sec),
+                snapshot_watch: SnapshotWatch {
+                    keepalive,
+                    warn_age: Some(snapshot_warn_age).filter(|age| !age.is_zero()),
+                },
+                subset: RowSubset {
+                    limit: limit_rows,
+                    sample,
+                },
+                type_map: match type_map {
+                    Some(path) => TypeMap::load(&path)?,
+                    None => TypeMap::default(),
+                },
+                rename_columns: ColumnRenames::new(&rename_column)?,
+                incremental: incremental
+                    .map(|column| WatermarkFile::open(incremental_state.as_ref(), &column))
+                    .transpose()?,
+                position_file: position_file.map(Into::into),
+                slot,
+                failed_tables: (continue_on_error || retry_failed)
+                    .then(|| FailedTablesFile::new(failed_tables.as_ref())),
+                verify,
+                if_exists,
+                no_drop,
+                create_destination,
+                allow_drift,
+                auto_alter,
+                convert_charset,
+                identifier_case,
+            };
+
+            migrate::migrate(&*source_engine, &*dest_engine, &source_url, &dest_url, opts).await?;
+        }
+
+        Commands::Import {
+            destination,
+            destination_env,
+            input,
+            table,
+            provider,
+            aws_iam_auth,
+            cloudsql_instance,
+            batch_rows,
+            batch_bytes,
+            disable_fk_checks,
+            columns,
+            skip_errors,
+            insert_mode,
+            retries,
+            retry_backoff,
+            connect_timeout,
+            statement_timeout,
+        } => {
+            let dest_url = Commands::get_url(&destination, &destination_env, "destination")?;
+
+            println!("Destination: {}", Commands::redact_url(&dest_url));
+
+            let engine = create_engine(
+                &provider,
+                aws_iam_auth,
+                cloudsql_instance.as_deref(),
+                RetryPolicy {
+                    retries,
+                    backoff: retry_backoff,
+                },
+                Timeouts {
+                    connect: connect_timeout,
+                    statement: statement_timeout,
+                    keepalive: None,
+                },
+            )?;
+
+            let column_mapping = columns
+                .as_ref()
+                .map(|c| import::parse_column_mapping(c))
+                .transpose()?;
+
+            let opts = import::ImportOptions {
+                input,
+                table,
+                batch_rows,
+                batch_bytes,
+                disable_fk_checks,
+                skip_errors,
+                column_mapping,
+                insert_mode,
+            };
+
+            import::import(&*engine, &dest_url, opts).await?;
+        }
+
+        Commands::Export {
+            source,
+            source_env,
+            output,
+            provider,
+            aws_iam_auth,
+            cloudsql_instance,
+            tables,
+            exclude,
+            where_clause,
+            compress,
+            gzip,
+            retries,
+            retry_backoff,
+            connect_timeout,
+            statement_timeout,
+        } => {
+            let source_url = Commands::get_url(&source, &source_env, "source")?;
+
+            eprintln!("Source: {}", Commands::redact_url(&source_url));
+
+            let engine = create_engine(
+                &provider,
+                aws_iam_auth,
+                cloudsql_instance.as_deref(),
+                RetryPolicy {
+                    retries,
+                    backoff: retry_backoff,
+                },
+                Timeouts {
+                    connect: connect_timeout,
+                    statement: statement_timeout,
+                    keepalive: None,
+                },
+            )?;
+
+            let opts = export::ExportOptions {
+                filter: TableFilter::new(&tables, &exclude),
+                where_clause,
+                compress: match (compress, gzip) {
+                    (Some(codec), _) => codec,
+                    (None, true) => Codec::Gzip,
+                    (None, false) => Codec::from_path(&output),
+                },
+            };
+
+            export::export(&*engine, &source_url, &output, opts).await?;
+        }
+
+        Commands::Bench {
+            destination,
+            destination_env,
+            provider,
+            aws_iam_auth,
+            cloudsql_instance,
+            table,
+            rows,
+            batch_sizes,
+            insert_modes,
+        } => {
+            let dest_url = Commands::get_url(&destination, &destination_env, "destination")?;
+
+            println!("Destination: {}", Commands::redact_url(&dest_url));
+
+            let engine = create_engine(
+                &provider,
+                aws_iam_auth,
+                cloudsql_instance.as_deref(),
+                RetryPolicy::default(),
+                Timeouts::default(),
+            )?;
+
+            let opts = bench::BenchOptions {
+                table,
+                rows,
+                batch_sizes,
+                insert_modes,
+            };
+
+            bench::bench(&*engine, &dest_url, opts).await?;
+        }
+
+        Commands::Replicate {
+            source,
+            source_env,
+            destination,
+            destination_env,
+            provider,
+            source_provider,
+            dest_provider,
+            tables,
+            exclude,
+            position_file,
+            server_id,
+            slot,
+        } => {
+            let source_url = Commands::get_url(&source, &source_env, "source")?;
+            let dest_url = Commands::get_url(&destination, &destination_env, "destination")?;
+
+            println!("Source: {}", Commands::redact_url(&source_url));
+            println!("Destination: {}", Commands::redact_url(&dest_url));
+
+            let source_provider = source_provider.unwrap_or_else(|| provider.clone());
+            let source_engine = create_engine(
+                &source_provider,
+                false,
+                None,
+                RetryPolicy::default(),
+                Timeouts::default(),
+            )?;
+            let dest_engine = create_engine(
+                dest_provider.as_deref().unwrap_or(&provider),
+                false,
+                None,
+                RetryPolicy::default(),
+                Timeouts::default(),
+            )?;
+
+            let opts = replicate::ReplicateOptions {
+                filter: TableFilter::new(&tables, &exclude),
+                position_file: position_file.into(),
+                server_id,
+                slot,
+            };
+
+            replicate::replicate(
+                &*source_engine,
+                &*dest_engine,
+                &source_provider,
+                &source_url,
+                &dest_url,
+                opts,
+            )
+            .await?;
+        }
+
+        Commands::Sync {
+            source,
+            source_env,
+            destination,
+            destination_env,
+            provider,
+            source_provider,
+            dest_provider,
+            tables,
+            exclude,
+            chunk_rows,
+            delete,
+            dry_run,
+        } => {
+            let source_url = Commands::get_url(&source, &source_env, "source")?;
+            let dest_url = Commands::get_url(&destination, &destination_env, "destination")?;
+
+            println!("Source: {}", Commands::redact_url(&source_url));
+            println!("Destination: {}", Commands::redact_url(&dest_url));
+
+            let source_engine = create_engine(
+                source_provider.as_deref().unwrap_or(&provider),
+                false,
+                None,
+                RetryPolicy::default(),
+                Timeouts::default(),
+            )?;
+            let dest_engine = create_engine(
+                dest_provider.as_deref().unwrap_or(&provider),
+                false,
+                None,
+                RetryPolicy::default(),
+                Timeouts::default(),
+            )?;
+
+            let opts = sync::SyncOptions {
+                filter: TableFilter::new(&tables, &exclude),
+                chunk_rows,
+                delete,
+                dry_run,
+            };
+
+            sync::sync(&*source_engine, &*dest_engine, &source_url, &dest_url, opts).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Run `--init-sql-file` and then `--init-sql` on each session `engine` opens
+fn with_init_sql(
+    engine: Box<dyn DbEngine>,
+    init_sql: &[String],
+    init_sql_file: Option<&str>,
+) -> Result<Box<dyn DbEngine>> {
+    let mut sql = match init_sql_file {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read --init-sql-file '{}'", path))?,
+        None => String::new(),
+    };
+    for statement in init_sql {
+        sql.push('\n');
+        sql.push_str(statement);
+        // Each --init-sql ends a statement, with or without its `;`
+        sql.push_str("\n;");
+    }
+    if sql.trim().is_empty() {
+        return Ok(engine);
+    }
+    Ok(Box::new(InitSqlEngine::new(engine, &sql)))
+}
+
+/// Resolve a provider, optionally signing in with RDS IAM tokens,
+/// tunnelling through the Cloud SQL connector, bounding connect and
+/// statement time and retrying transient failures
+fn create_engine(
+    provider: &str,
+    aws_iam_auth: bool,
+    cloudsql_instance: Option<&str>,
+    retry: RetryPolicy,
+    timeouts: Timeouts,
+) -> Result<Box<dyn DbEngine>> {
+    let mut engine = engine::create_engine(provider)?;
+    if aws_iam_auth {
+        engine = Box::new(RdsIamEngine::new(engine));
+    }
+    if let Some(instance) = cloudsql_instance {
+        engine = Box::new(CloudSqlEngine::new(engine, instance)?);
+    }
+    if timeouts.is_set() {
+        engine = Box::new(TimeoutEngine::new(engine, timeouts));
+    }
+    if retry.retries > 0 {
+        engine = Box::new(RetryEngine::new(engine, retry));
+    }
+    Ok(engine)
+}
@@ -1,53 +1,571 @@
-use crate::engine::dialect::SqlDialect;
+use crate::engine::ddl::{column_drift, create_table_sql, Column, TypeMap};
+use crate::engine::dialect::{
+    create_schema_statements, in_schemas, split_table_name, OnConflict, SqlDialect,
+};
+use crate::engine::filter::{table_matches, TableFilter};
+use crate::engine::keyset::{split_key_range, stream_rows_chunked};
+use crate::engine::pool::{PooledSession, SessionPool};
 use crate::engine::value::SqlValue;
-use crate::engine::{DbEngine, DbSession};
-use anyhow::{bail, Context, Result};
-use futures::StreamExt;
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::engine::{select_databases, DbEngine, DbSession, InsertMode};
+use crate::sync::{sync_table, SyncOptions};
+use crate::util::batch::{row_sql_len, BatchLimit, BatchRows};
+use crate::util::charset::{Charset, TextRepair};
+use crate::util::keepalive::SnapshotWatch;
+use crate::util::remap::ColumnRenames;
+use crate::util::state::{FailedTablesFile, PositionFile, WatermarkFile};
+use crate::util::subset::RowSubset;
+use crate::util::throttle::Throttle;
+use crate::util::url::{database_of, with_database};
+use anyhow::{anyhow, bail, Context, Result};
+use futures::{Stream, StreamExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use tokio::sync::mpsc;
 
 pub struct MigrateOptions {
-    pub tables: Vec<String>,
-    pub exclude: Vec<String>,
+    /// Which tables to migrate
+    pub filter: TableFilter,
+    /// Migrate these databases of the server rather than the one in the
+    /// URL, or all of them when empty (MySQL)
+    pub databases: Option<Vec<String>>,
+    /// Migrate only tables in these schemas; all of them when empty
+    pub schemas: Vec<String>,
     pub schema_only: bool,
     pub data_only: bool,
-    pub batch_rows: usize,
+    /// Migrate only the schema of tables matching these patterns
+    pub exclude_data: Vec<String>,
+    pub batch_rows: BatchRows,
+    /// Also flush a batch once its SQL reaches about this many bytes
+    pub batch_bytes: Option<usize>,
     pub consistent_snapshot: bool,
     pub disable_fk_checks: bool,
     pub skip_errors: bool,
+    /// Read tables in primary key order, this many rows per query
+    pub chunk_rows: Option<usize>,
+    /// Number of tables migrated concurrently, each over its own connections
+    pub jobs: usize,
+    /// Split tables with an integer primary key into this many key ranges,
+    /// copied concurrently over their own connections
+    pub split_ranges: usize,
+    /// How rows are sent to the destination
+    pub insert_mode: InsertMode,
+    /// Most sessions open at once on each of the source and destination
+    pub max_connections: Option<usize>,
+    /// Batches read ahead of the destination while it inserts; bounds memory
+    /// when the source is faster
+    pub max_inflight_batches: usize,
+    /// Relax durability and constraint enforcement on destination sessions
+    pub fast_load: bool,
+    /// Drop secondary indexes before loading a table and rebuild them after
+    pub drop_indexes: bool,
+    /// Create tables without their secondary indexes and add those once the
+    /// rows are loaded
+    pub defer_indexes: bool,
+    /// Caps the rate rows are read from the source, across all jobs and ranges
+    pub throttle: Throttle,
+    /// Warns as source snapshots grow old
+    pub snapshot_watch: SnapshotWatch,
+    /// Copy only a limited or sampled part of each table's rows
+    pub subset: RowSubset,
+    /// Types to write instead of the translated ones when the engines differ
+    pub type_map: TypeMap,
+    /// Columns written to the destination under new names
+    pub rename_columns: ColumnRenames,
+    /// `--incremental`: copy only rows whose watermark column has grown
+    /// since the last run, noted in this file
+    pub incremental: Option<WatermarkFile>,
+    /// Take the source snapshot at a replication position and write it
+    /// here once the migration is done, for `replicate` to carry on from
+    pub position_file: Option<PathBuf>,
+    /// Logical replication slot, and publication, created ahead of the
+    /// snapshot for `--position-file` on PostgreSQL
+    pub slot: String,
+    /// `--continue-on-error`: a table that fails is noted here and the run
+    /// goes on with the next one, failing once all are done
+    pub failed_tables: Option<FailedTablesFile>,
+    /// Compare the copied tables with the source once the migration is done
+    pub verify: Option<Verify>,
+    /// What to do with a destination table that already has rows, when it
+    /// is not created afresh
+    pub if_exists: IfExists,
+    /// Keep tables already on the destination, schema and all, instead of
+    /// dropping and creating them again
+    pub no_drop: bool,
+    /// Create the database the destination URL names when it is missing
+    pub create_destination: bool,
+    /// Only warn when a `--data-only` copy finds the destination's columns
+    /// differ from the source's
+    pub allow_drift: bool,
+    /// Change the destination's tables to fit the source's before a
+    /// `--data-only` copy
+    pub auto_alter: Option<AutoAlter>,
+    /// Move text kept in other character sets to this one, in the DDL and
+    /// in the rows
+    pub convert_charset: Option<Charset>,
+    /// Case of the table and column names written across engines
+    pub identifier_case: IdentifierCase,
 }
 
+/// `--if-exists`: what becomes of the rows already in a destination table
+/// that `--data-only` or `--no-drop` copies into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IfExists {
+    /// Stop with an error
+    #[default]
+    Fail,
+    /// Leave the table as it is and copy nothing into it
+    Skip,
+    /// Empty the table first
+    Truncate,
+    /// Insert the source rows alongside them
+    Append,
+}
+
+impl fmt::Display for IfExists {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IfExists::Fail => "fail",
+            IfExists::Skip => "skip",
+            IfExists::Truncate => "truncate",
+            IfExists::Append => "append",
+        })
+    }
+}
+
+impl FromStr for IfExists {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fail" => Ok(IfExists::Fail),
+            "skip" => Ok(IfExists::Skip),
+            "truncate" => Ok(IfExists::Truncate),
+            "append" => Ok(IfExists::Append),
+            other => Err(format!(
+                "Unknown --if-exists policy '{}' (expected fail, skip, truncate or append)",
+                other
+            )),
+        }
+    }
+}
+
+/// `--auto-alter`: what a `--data-only` copy changes in destination tables
+/// that drifted from the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoAlter {
+    /// Add the columns only the source has
+    AddColumns,
+}
+
+impl fmt::Display for AutoAlter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AutoAlter::AddColumns => "add-columns",
+        })
+    }
+}
+
+impl FromStr for AutoAlter {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "add-columns" => Ok(AutoAlter::AddColumns),
+            other => Err(format!(
+                "Unknown --auto-alter change '{}' (expected add-columns)",
+                other
+            )),
+        }
+    }
+}
+
+/// `--identifier-case`: how table and column names are written when
+/// migrating to another engine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdentifierCase {
+    /// Keep each name as the source spells it
+    #[default]
+    Preserve,
+    Lower,
+    Upper,
+}
+
+impl IdentifierCase {
+    /// `name` in this case
+    pub fn apply(&self, name: &str) -> String {
+        match self {
+            IdentifierCase::Preserve => name.to_string(),
+            IdentifierCase::Lower => name.to_lowercase(),
+            IdentifierCase::Upper => name.to_uppercase(),
+        }
+    }
+}
+
+impl fmt::Display for IdentifierCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IdentifierCase::Preserve => "preserve",
+            IdentifierCase::Lower => "lower",
+            IdentifierCase::Upper => "upper",
+        })
+    }
+}
+
+impl FromStr for IdentifierCase {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "preserve" => Ok(IdentifierCase::Preserve),
+            "lower" => Ok(IdentifierCase::Lower),
+            "upper" => Ok(IdentifierCase::Upper),
+            other => Err(format!(
+                "Unknown --identifier-case '{}' (expected preserve, lower or upper)",
+                other
+            )),
+        }
+    }
+}
+
+/// `--verify`: how the copied tables are checked against the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verify {
+    /// Exact row counts on both sides
+    Counts,
+    /// Rows matched by primary key, compared window by window; windows whose
+    /// digests agree between servers of the same engine are not read
+    Checksums,
+}
+
+impl fmt::Display for Verify {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Verify::Counts => "counts",
+            Verify::Checksums => "checksums",
+        })
+    }
+}
+
+impl FromStr for Verify {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "counts" => Ok(Verify::Counts),
+            "checksums" => Ok(Verify::Checksums),
+            other => Err(format!(
+                "Unknown verification '{}' (expected counts or checksums)",
+                other
+            )),
+        }
+    }
+}
+
+/// Source rows per key window `--verify checksums` compares, unless
+/// `--chunk-rows` says otherwise
+const VERIFY_WINDOW_ROWS: usize = 1000;
+
 pub async fn migrate(
-    engine: &dyn DbEngine,
+    source_engine: &dyn DbEngine,
+    dest_engine: &dyn DbEngine,
     source_url: &str,
     destination_url: &str,
-    opts: MigrateOptions,
+    mut opts: MigrateOptions,
 ) -> Result<()> {
     println!("Starting database migration...");
 
+    if let Some(max) = opts.max_connections {
+        limit_concurrency(&mut opts, max);
+    }
+
+    if opts.incremental.is_some() && opts.databases.is_some() {
+        bail!("--incremental migrates one database at a time");
+    }
+    if opts.failed_tables.is_some() && opts.databases.is_some() {
+        bail!("--continue-on-error migrates one database at a time");
+    }
+    if opts.verify == Some(Verify::Checksums) && !opts.rename_columns.is_empty() {
+        bail!(
+            "--verify checksums matches columns by name; use --verify counts with --rename-column"
+        );
+    }
+    if opts.verify == Some(Verify::Checksums) && opts.identifier_case != IdentifierCase::Preserve {
+        bail!(
+            "--verify checksums matches columns by name; use --verify counts with --identifier-case"
+        );
+    }
+    if opts.position_file.is_some()
+        && (opts.databases.is_some() || opts.jobs > 1 || opts.split_ranges > 1)
+    {
+        bail!("--position-file needs every table read in one snapshot; leave out --jobs, --split-ranges and --databases");
+    }
+
+    if opts.create_destination {
+        create_destination(dest_engine, destination_url).await?;
+    }
+
+    match opts.databases.take() {
+        Some(names) => {
+            migrate_databases(
+                source_engine,
+                dest_engine,
+                source_url,
+                destination_url,
+                &names,
+                &opts,
+            )
+            .await?
+        }
+        None => {
+            migrate_database(
+                source_engine,
+                dest_engine,
+                source_url,
+                destination_url,
+                &opts,
+            )
+            .await?
+        }
+    }
+
+    if let Some(failed) = &opts.failed_tables {
+        let failures = failed.failures();
+        if !failures.is_empty() {
+            println!("\nFailed to migrate {} table(s):", failures.len());
+            for (table, error) in &failures {
+                println!("  {}: {}", table, error);
+            }
+            bail!(
+                "{} table(s) failed to migrate; they are noted in '{}', so run again with --retry-failed to migrate just those",
+                failures.len(),
+                failed.path().display()
+            );
+        }
+        failed.remove_if_clean()?;
+    }
+
+    println!("\nMigration completed successfully!");
+    Ok(())
+}
+
+/// `--create-destination`: create the database `url` names over a connection
+/// to the server without it
+async fn create_destination(dest_engine: &dyn DbEngine, url: &str) -> Result<()> {
+    let database = database_of(url)?.ok_or_else(|| {
+        anyhow!("--create-destination needs a database named in the destination URL")
+    })?;
+    let server_database = dest_engine
+        .dialect()
+        .and_then(|dialect| dialect.server_database())
+        .ok_or_else(|| anyhow!("--create-destination is not supported for this destination"))?;
+
+    println!("Connecting to destination server...");
+    let mut server = dest_engine
+        .connect(&with_database(url, server_database)?)
+        .await
+        .context("Failed to connect to destination server")?;
+    if server.create_database(&database).await? {
+        println!("Created destination database '{}'", database);
+    } else {
+        println!("Destination database '{}' already exists", database);
+    }
+    Ok(())
+}
+
+/// Migrate each database `names` selects on the source server (all of them
+/// when it names none) to a database of the same name on the destination
+/// server, created when missing
+async fn migrate_databases(
+    source_engine: &dyn DbEngine,
+    dest_engine: &dyn DbEngine,
+    source_url: &str,
+    destination_url: &str,
+    names: &[String],
+    opts: &MigrateOptions,
+) -> Result<()> {
+    let databases = {
+        let mut source = source_engine
+            .connect(source_url)
+            .await
+            .context("Failed to connect to source database")?;
+        select_databases(&mut *source, names).await?
+    };
+    println!("Found {} database(s) to migrate", databases.len());
+
+    let mut dest = dest_engine
+        .connect(destination_url)
+        .await
+        .context("Failed to connect to destination database")?;
+    if dest.dialect().name() != "MySQL" {
+        bail!("Migrating several databases needs a MySQL destination");
+    }
+
+    for (idx, database) in databases.iter().enumerate() {
+        println!(
+            "\n[{}/{}] Migrating database '{}'...",
+            idx + 1,
+            databases.len(),
+            database
+        );
+        if let Some(create) = dest.dialect().create_schema_statement(database) {
+            dest.execute(&create)
+                .await
+                .with_context(|| format!("Failed to create database '{}'", database))?;
+        }
+        migrate_database(
+            source_engine,
+            dest_engine,
+            &with_database(source_url, database)?,
+            &with_database(destination_url, database)?,
+            opts,
+        )
+        .await
+        .with_context(|| format!("Failed to migrate database '{}'", database))?;
+    }
+    Ok(())
+}
+
+/// Migrate the database at `source_url` to the one at `destination_url`
+async fn migrate_database(
+    source_engine: &dyn DbEngine,
+    dest_engine: &dyn DbEngine,
+    source_url: &str,
+    destination_url: &str,
+    opts: &MigrateOptions,
+) -> Result<()> {
+    let pool_size = opts.max_connections.unwrap_or(usize::MAX);
+    let pools = Pools {
+        source: &source_engine.connect_pool(source_url, pool_size),
+        dest: &dest_engine.connect_pool(destination_url, pool_size),
+    };
+
     // Connect to source and destination
     println!("Connecting to source database...");
-    let mut source = engine
-        .connect(source_url)
+    let mut source = pools
+        .source
+        .get()
         .await
         .context("Failed to connect to source database")?;
 
     println!("Connecting to destination database...");
-    let mut dest = engine
-        .connect(destination_url)
+    let mut dest = pools
+        .dest
+        .get()
         .await
         .context("Failed to connect to destination database")?;
+    dest.set_insert_mode(opts.insert_mode)?;
+    if opts.fast_load {
+        if dest.enable_fast_load().await? {
+            println!("Fast load: replication role replica, synchronous_commit off on destination");
+        } else {
+            println!(
+                "Note: --fast-load has no effect on {}",
+                dest.dialect().name()
+            );
+        }
+    }
 
     let src_dialect = source.dialect();
     let dest_dialect = dest.dialect();
 
+    if opts.throttle.is_limited() {
+        println!("Throttling source reads to {}", opts.throttle);
+    }
+    if opts.subset.is_set() && !opts.schema_only {
+        println!("Copying {}", opts.subset);
+    }
+    if let Some(charset) = opts.convert_charset {
+        println!("Converting text to {}", charset);
+    }
+
     if src_dialect.name() != dest_dialect.name() {
-        bail!("Cross-engine migrations are not supported in this release");
+        println!(
+            "Migrating across engines ({} -> {}); destination tables are created from sampled column types",
+            src_dialect.name(),
+            dest_dialect.name()
+        );
+    }
+
+    if opts.jobs > 1 {
+        let tables = list_tables(&mut *source, &mut *dest, opts).await?;
+        println!(
+            "Found {} table(s) to migrate with {} parallel jobs",
+            tables.len(),
+            opts.jobs
+        );
+        let cycles = ForeignKeyCycles::find(&mut *source, &tables, dest_dialect, opts).await?;
+        if opts.data_only {
+            check_drift(&mut *source, &mut *dest, &tables, opts).await?;
+        }
+        if opts.consistent_snapshot {
+            println!(
+                "Note: each job takes its own snapshot; tables may not be mutually consistent"
+            );
+        }
+        // Workers pick these sessions back up from the pools
+        source.release();
+        dest.release();
+
+        let verified = opts.verify.map(|_| tables.clone());
+        let work = migrate_parallel(pools, tables, &cycles, opts);
+        if opts.consistent_snapshot {
+            // Jobs take their snapshots as they start, so this is about their age
+            opts.snapshot_watch
+                .watch(tokio::time::Instant::now(), None, work)
+                .await?;
+        } else {
+            work.await?;
+        }
+        if cycles.has_held() {
+            let mut dest = pools
+                .dest
+                .get()
+                .await
+                .context("Failed to connect to destination database")?;
+            cycles.add_held(&mut *dest).await?;
+            dest.commit().await?;
+        }
+
+        if let (Some(verify), Some(tables)) = (opts.verify, verified) {
+            verify_tables(pools, &tables, verify, opts).await?;
+        }
+        return Ok(());
     }
 
     // Start consistent snapshot on source if requested
+    let snapshot_started = tokio::time::Instant::now();
+    let mut position = None;
     if opts.consistent_snapshot {
         println!("Starting consistent snapshot on source...");
-        source.start_consistent_snapshot().await?;
+        if opts.position_file.is_some() {
+            // The slot goes in first so no later change is missed; the
+            // changes between it and the snapshot are applied again, to the
+            // same effect
+            let mut tables = source.list_tables(&opts.filter).await?;
+            tables.retain(|table| in_schemas(table, &opts.schemas));
+            let slot_at = source.create_replication_slot(&opts.slot, &tables).await?;
+            if slot_at.is_some() {
+                println!("Created replication slot '{}'", opts.slot);
+            }
+            let snapshot_at = source.start_snapshot_at_position().await?;
+            let at = slot_at.or(snapshot_at).ok_or_else(|| {
+                anyhow!(
+                    "{} reports no replication position (is binary logging on?)",
+                    src_dialect.name()
+                )
+            })?;
+            println!("Replication position: {}", at);
+            position = Some(at);
+        } else {
+            source.start_consistent_snapshot().await?;
+        }
     }
 
     // Disable constraints on destination if requested
@@ -57,21 +575,35 @@ pub async fn migrate(
     }
 
     // Get list of tables from source
-    let tables = source.list_tables(&opts.tables, &opts.exclude).await?;
+    let tables = list_tables(&mut *source, &mut *dest, opts).await?;
     println!("Found {} table(s) to migrate", tables.len());
+    let cycles = ForeignKeyCycles::find(&mut *source, &tables, dest_dialect, opts).await?;
+    if opts.data_only {
+        check_drift(&mut *source, &mut *dest, &tables, opts).await?;
+    }
 
     // Migrate each table
-    for (idx, table) in tables.iter().enumerate() {
-        println!(
-            "\n[{}/{}] Migrating table '{}'...",
-            idx + 1,
-            tables.len(),
-            table
-        );
+    let work = async {
+        for (idx, table) in tables.iter().enumerate() {
+            println!(
+                "\n[{}/{}] Migrating table '{}'...",
+                idx + 1,
+                tables.len(),
+                table
+            );
 
-        migrate_table(&mut *source, &mut *dest, table, dest_dialect, &opts)
-            .await
-            .with_context(|| format!("Failed to migrate table '{}'", table))?;
+            let migrated =
+                migrate_table(&mut *source, &mut *dest, pools, table, &cycles, opts, None).await;
+            note_failure(opts, table, migrated, None)?;
+        }
+        Ok(())
+    };
+    if opts.consistent_snapshot {
+        opts.snapshot_watch
+            .watch(snapshot_started, None, work)
+            .await?;
+    } else {
+        work.await?;
     }
 
     // Re-enable constraints on destination
@@ -79,139 +611,1221 @@ pub async fn migrate(
         println!("\nRe-enabling foreign key checks on destination...");
         dest.enable_constraints().await?;
     }
+    cycles.add_held(&mut *dest).await?;
 
     // Commit both sessions
     println!("Committing transactions...");
     source.commit().await?;
     dest.commit().await?;
 
-    println!("\nMigration completed successfully!");
+    if let (Some(path), Some(position)) = (&opts.position_file, position) {
+        PositionFile::create(path, position)?;
+        println!("Wrote replication position to '{}'", path.display());
+    }
+
+    if let Some(verify) = opts.verify {
+        source.release();
+        dest.release();
+        verify_tables(pools, &tables, verify, opts).await?;
+    }
 
     Ok(())
 }
 
+/// `--verify`: compare each copied table with the source, failing with a
+/// report of those that differ. Tables left out of the copy, or that failed
+/// under `--continue-on-error`, are passed over.
+async fn verify_tables(
+    pools: Pools<'_, '_>,
+    tables: &[String],
+    verify: Verify,
+    opts: &MigrateOptions,
+) -> Result<()> {
+    let (mut source, mut dest) = pools.get().await?;
+    let cross_engine = source.dialect().name() != dest.dialect().name();
+    let failed: Vec<String> = opts
+        .failed_tables
+        .iter()
+        .flat_map(|failed| failed.failures())
+        .map(|(table, _)| table)
+        .collect();
+    let compare = SyncOptions {
+        filter: TableFilter::default(),
+        chunk_rows: opts.chunk_rows.unwrap_or(VERIFY_WINDOW_ROWS),
+        delete: false,
+        dry_run: true,
+    };
+
+    println!("\nVerifying tables against the source ({})...", verify);
+    let mut mismatches = Vec::new();
+    for table in tables {
+        if opts.exclude_data.iter().any(|p| table_matches(p, table)) || failed.contains(table) {
+            continue;
+        }
+        let dest_table = &dest_table_name(table, cross_engine, opts.identifier_case);
+
+        let checked = async {
+            let key = match verify {
+                Verify::Checksums => source.primary_key(table).await?,
+                Verify::Counts => Vec::new(),
+            };
+            if key.is_empty() {
+                let source_rows = source.count_rows(table).await?;
+                let dest_rows = dest.count_rows(dest_table).await?;
+                let counted = match verify {
+                    Verify::Checksums => "no primary key, so only counted",
+                    Verify::Counts => "counted",
+                };
+                return anyhow::Ok(if source_rows == dest_rows {
+                    Ok(format!("{} row(s), {}", source_rows, counted))
+                } else {
+                    Err(format!(
+                        "{} row(s) on the source, {} on the destination",
+                        source_rows, dest_rows
+                    ))
+                });
+            }
+            let counts = sync_table(
+                &mut *source,
+                &mut *dest,
+                table,
+                dest_table,
+                &key,
+                !cross_engine,
+                &compare,
+            )
+            .await?;
+            Ok(if counts.in_sync() {
+                Ok(format!("{} window(s) compared", counts.windows))
+            } else {
+                Err(format!(
+                    "{} row(s) missing, {} different and {} extra on the destination",
+                    counts.inserted, counts.updated, counts.extra
+                ))
+            })
+        }
+        .await
+        .with_context(|| format!("Failed to verify table '{}'", table))?;
+
+        match checked {
+            Ok(summary) => println!("  {}: matches ({})", table, summary),
+            Err(mismatch) => {
+                println!("  {}: DIFFERS: {}", table, mismatch);
+                mismatches.push((table, mismatch));
+            }
+        }
+    }
+    source.release();
+    dest.release();
+
+    if !mismatches.is_empty() {
+        println!("\n{} table(s) differ from the source:", mismatches.len());
+        for (table, mismatch) in &mismatches {
+            println!("  {}: {}", table, mismatch);
+        }
+        bail!("Verification failed for {} table(s)", mismatches.len());
+    }
+    Ok(())
+}
+
+/// The source tables to migrate, within `--schemas`. Their schemas are
+/// created on the destination when it takes the source's DDL as it is.
+async fn list_tables(
+    source: &mut dyn DbSession,
+    dest: &mut dyn DbSession,
+    opts: &MigrateOptions,
+) -> Result<Vec<String>> {
+    let mut tables = source.list_tables(&opts.filter).await?;
+    tables.retain(|table| in_schemas(table, &opts.schemas));
+
+    let dest_dialect = dest.dialect();
+    if !opts.data_only && source.dialect().name() == dest_dialect.name() {
+        let schemas = create_schema_statements(dest_dialect, tables.iter().map(String::as_str));
+        for statement in schemas {
+            dest.execute(&statement)
+                .await
+                .with_context(|| format!("Failed to run '{}' on destination", statement))?;
+        }
+    }
+    Ok(tables)
+}
+
+/// Lower `--jobs` and `--split-ranges` until they fit in `max` sessions per
+/// side. Every job holds a session on each side for the whole run, and range
+/// copies need at least one more to make progress.
+fn limit_concurrency(opts: &mut MigrateOptions, max: usize) {
+    if opts.split_ranges > 1 && max < 2 {
+        println!("Not splitting tables into ranges: --max-connections 1 leaves no spare session");
+        opts.split_ranges = 1;
+    }
+
+    let spare = usize::from(opts.split_ranges > 1);
+    if opts.jobs + spare > max {
+        opts.jobs = max - spare;
+        println!(
+            "Limiting to {} job(s) to stay within --max-connections {}",
+            opts.jobs, max
+        );
+    }
+}
+
+/// Source and destination pools jobs check their sessions out of
+#[derive(Clone, Copy)]
+struct Pools<'a, 'e> {
+    source: &'a SessionPool<'e>,
+    dest: &'a SessionPool<'e>,
+}
+
+impl<'a> Pools<'a, '_> {
+    async fn get(&self) -> Result<(PooledSession<'a>, PooledSession<'a>)> {
+        let source = self
+            .source
+            .get()
+            .await
+            .context("Failed to connect to source database")?;
+        let dest = self
+            .dest
+            .get()
+            .await
+            .context("Failed to connect to destination database")?;
+        Ok((source, dest))
+    }
+}
+
+/// Run `opts.jobs` workers that take tables from a shared queue
+async fn migrate_parallel(
+    pools: Pools<'_, '_>,
+    tables: Vec<String>,
+    cycles: &ForeignKeyCycles,
+    opts: &MigrateOptions,
+) -> Result<()> {
+    let queue = Mutex::new(tables.into_iter().collect::<VecDeque<_>>());
+    let progress = MultiProgress::new();
+
+    let workers = (0..opts.jobs).map(|_| migrate_worker(pools, &queue, cycles, opts, &progress));
+    futures::future::try_join_all(workers).await?;
+
+    Ok(())
+}
+
+async fn migrate_worker(
+    pools: Pools<'_, '_>,
+    queue: &Mutex<VecDeque<String>>,
+    cycles: &ForeignKeyCycles,
+    opts: &MigrateOptions,
+    progress: &MultiProgress,
+) -> Result<()> {
+    let (mut source, mut dest) = pools.get().await?;
+    dest.set_insert_mode(opts.insert_mode)?;
+    if opts.fast_load {
+        dest.enable_fast_load().await?;
+    }
+
+    if opts.consistent_snapshot {
+        source.start_consistent_snapshot().await?;
+    }
+    // Foreign key checks are per session, so every job turns them off
+    if opts.disable_fk_checks {
+        dest.disable_constraints().await?;
+    }
+
+    loop {
+        let next = queue.lock().unwrap_or_else(|e| e.into_inner()).pop_front();
+        let Some(table) = next else {
+            break;
+        };
+
+        let migrated = migrate_table(
+            &mut *source,
+            &mut *dest,
+            pools,
+            &table,
+            cycles,
+            opts,
+            Some(progress),
+        )
+        .await;
+        note_failure(opts, &table, migrated, Some(progress))?;
+    }
+
+    if opts.disable_fk_checks {
+        dest.enable_constraints().await?;
+    }
+    source.commit().await?;
+    dest.commit().await?;
+    source.release();
+    dest.release();
+
+    Ok(())
+}
+
+/// Pass on the outcome of migrating `table`, unless it failed under
+/// `--continue-on-error`: then the failure is noted and the run goes on
+fn note_failure(
+    opts: &MigrateOptions,
+    table: &str,
+    migrated: Result<()>,
+    progress: Option<&MultiProgress>,
+) -> Result<()> {
+    let Some(failed) = &opts.failed_tables else {
+        return migrated.with_context(|| format!("Failed to migrate table '{}'", table));
+    };
+    let Err(e) = migrated else {
+        return Ok(());
+    };
+    let error = format!("{:#}", e);
+    let report = format!("  Failed, moving on: {}", error);
+    match progress {
+        Some(progress) => progress.println(report)?,
+        None => println!("{}", report),
+    }
+    failed.record(table, &error)
+}
+
+/// Migrate one table. With `progress`, the table runs alongside others: step
+/// messages are suppressed and its bar joins the shared display.
 async fn migrate_table(
     source: &mut dyn DbSession,
     dest: &mut dyn DbSession,
+    pools: Pools<'_, '_>,
     table: &str,
-    _dest_dialect: &dyn SqlDialect,
+    cycles: &ForeignKeyCycles,
     opts: &MigrateOptions,
+    progress: Option<&MultiProgress>,
 ) -> Result<()> {
-    // Migrate schema
-    if !opts.data_only {
-        println!("  Creating table schema...");
-        let create_stmt = source.show_create_table(table).await?;
+    let dest_dialect = dest.dialect();
+    let cross_engine = source.dialect().name() != dest_dialect.name();
+    let mut copy_data =
+        !opts.schema_only && !opts.exclude_data.iter().any(|p| table_matches(p, table));
+
+    let dest_table = &dest_table_name(table, cross_engine, opts.identifier_case);
+
+    // An --incremental run tops up the tables it copied before
+    let catching_up = opts
+        .incremental
+        .as_ref()
+        .is_some_and(|state| state.watermark(table).is_some());
+    // With --no-drop, a table already on the destination keeps its schema
+    let kept = opts.no_drop
+        && !opts.data_only
+        && !catching_up
+        && dest_table_exists(dest, dest_table).await?;
+    if kept && progress.is_none() {
+        println!("  Keeping the existing destination table");
+    }
+
+    // Rows already in a table this run does not create
+    if copy_data && (opts.data_only || kept) && !catching_up && dest.has_rows(dest_table).await? {
+        match opts.if_exists {
+            IfExists::Fail => bail!(
+                "Destination table '{}' already has rows; choose what to do with them with --if-exists skip|truncate|append",
+                dest_table
+            ),
+            IfExists::Skip => {
+                if progress.is_none() {
+                    println!("  Skipping data: the destination table already has rows");
+                }
+                copy_data = false;
+            }
+            IfExists::Truncate => {
+                if progress.is_none() {
+                    println!("  Emptying the destination table...");
+                }
+                let truncate = dest_dialect.truncate_table_statement(dest_table);
+                dest.execute(&truncate)
+                    .await
+                    .with_context(|| format!("Failed to empty table: {}", truncate))?;
+            }
+            IfExists::Append => {}
+        }
+    }
+
+    // With --incremental, how far earlier runs copied the table and how far
+    // this one goes; a table copied before only takes its newer rows
+    let watermark = match &opts.incremental {
+        Some(state) if copy_data => {
+            let column = state.column();
+            let upto = source.column_max(table, &column).await.with_context(|| {
+                format!(
+                    "Failed to read --incremental column '{}' of '{}'",
+                    column, table
+                )
+            })?;
+            Some((column, state.watermark(table), upto))
+        }
+        _ => None,
+    };
+    let since = match &watermark {
+        Some((column, Some(after), upto)) => Some((column.as_str(), after, upto)),
+        _ => None,
+    };
+
+    let renames = opts.rename_columns.for_table(table);
+
+    // Index statements held back until the table's rows are loaded
+    let mut deferred_indexes = Vec::new();
+    // Counter columns of a table translated from another engine
+    let mut counters = Vec::new();
+
+    // Migrate schema, unless the table is kept or only catching up
+    let creates = !opts.data_only && !kept && since.is_none();
+    if creates {
+        if progress.is_none() {
+            println!("  Creating table schema...");
+        }
 
         // Drop table first if it exists
-        let drop_stmt = format!("DROP TABLE IF EXISTS `{}`;", table);
-        dest.execute(&drop_stmt).await?;
+        let drop_stmt = format!("{};", dest_dialect.drop_table_statement(dest_table));
 
-        // Create table
-        let normalized_create = create_stmt.trim_end_matches(';');
-        dest.execute(normalized_create).await?;
+        if !cross_engine {
+            let create_stmt = source.show_create_table(table).await?;
+            dest.execute(&drop_stmt).await?;
+
+            // Create table
+            let mut normalized_create = create_stmt.trim_end_matches(';').to_string();
+            if let Some(charset) = opts.convert_charset {
+                normalized_create =
+                    dest_dialect.with_charset(&normalized_create, &charset.to_string());
+            }
+            // Index statements split off the source DDL name the columns as
+            // they were, so a table with renamed columns keeps its indexes
+            let mut create_table = normalized_create;
+            if opts.defer_indexes && copy_data && renames.is_empty() {
+                let (create, indexes) =
+                    dest_dialect.split_secondary_indexes(dest_table, &create_table);
+                create_table = create;
+                deferred_indexes = indexes;
+            }
+            // Indexes split first, so MySQL still sees the keys foreign keys use
+            if cycles.contains(table) {
+                let (create, foreign_keys) =
+                    dest_dialect.split_foreign_keys(dest_table, &create_table);
+                create_table = create;
+                cycles.hold(foreign_keys);
+            }
+            dest.execute(create_table.trim_end_matches(';')).await?;
+            for (old, new) in &renames {
+                let rename = dest_dialect.rename_column_statement(dest_table, old, new);
+                dest.execute(&rename)
+                    .await
+                    .with_context(|| format!("Failed to rename column '{}' to '{}'", old, new))?;
+            }
+        } else {
+            // Source DDL cannot be replayed on another engine: translate the
+            // columns the catalog describes, or failing that let the
+            // destination build the table from the types seen in the data
+            let mut columns = source.describe_columns(table).await?;
+            let names: Vec<String> = columns.iter().map(|c| c.name.clone()).collect();
+            for (column, name) in columns
+                .iter_mut()
+                .zip(dest_column_names(table, &names, true, opts))
+            {
+                column.name = name;
+            }
+            let key = source.primary_key(table).await?;
+            let key = dest_column_names(table, &key, true, opts);
+            match create_table_sql(dest_dialect, dest_table, &columns, &key, &opts.type_map) {
+                Some(create) => {
+                    dest.execute(&drop_stmt).await?;
+                    dest.execute(&create)
+                        .await
+                        .with_context(|| format!("Failed to create table: {}", create))?;
+                    counters = columns
+                        .into_iter()
+                        .filter(|c| c.auto_increment)
+                        .map(|c| c.name)
+                        .collect();
+                }
+                None => {
+                    let (columns, column_types) =
+                        sample_column_types(source, table, opts.batch_rows.hint()).await?;
+                    let columns = dest_column_names(table, &columns, true, opts);
+                    dest.execute(&drop_stmt).await?;
+                    dest.create_table_from_columns(dest_table, &columns, &column_types)
+                        .await?;
+                }
+            }
+        }
+    }
+
+    // A table this run does not create may hold counters still at their
+    // start, as after a --data-only run across engines
+    if cross_engine && copy_data && !creates {
+        let columns = dest.describe_columns(dest_table).await?;
+        if columns.is_empty() {
+            let note = format!(
+                "  Note: {} cannot describe the columns of '{}'; identity or sequence counters there are not moved past the copied rows",
+                dest_dialect.name(),
+                dest_table
+            );
+            match progress {
+                Some(progress) => progress.println(note)?,
+                None => println!("{}", note),
+            }
+        }
+        counters = columns
+            .into_iter()
+            .filter(|c| c.auto_increment)
+            .map(|c| c.name)
+            .collect();
     }
 
     // Migrate data
-    if !opts.schema_only {
-        println!("  Migrating data...");
+    if copy_data {
+        if progress.is_none() {
+            println!("  Migrating data...");
+        }
 
         // Get approximate row count for progress
-        let approx_count = source.approximate_row_count(table).await?;
+        let approx_count = opts
+            .subset
+            .expected_rows(source.approximate_row_count(table).await?);
 
         // Create progress bar
-        let pb = if approx_count > 0 {
-            let pb = ProgressBar::new(approx_count);
-            pb.set_style(
-                ProgressStyle::default_bar()
-                    .template("  {spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} rows ({per_sec})")
-                    .unwrap()
-                    .progress_chars("#>-"),
-            );
-            Some(pb)
-        } else {
-            None
+        let pb = match progress {
+            Some(progress) => {
+                let pb = progress.add(ProgressBar::new(approx_count));
+                pb.set_style(
+                    ProgressStyle::default_bar()
+                        .template("  {spinner:.green} {prefix:20!} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} rows ({per_sec}) {msg}")
+                        .unwrap()
+                        .progress_chars("#>-"),
+                );
+                pb.set_prefix(table.to_string());
+                Some(pb)
+            }
+            None if approx_count > 0 => {
+                let pb = ProgressBar::new(approx_count);
+                pb.set_style(
+                    ProgressStyle::default_bar()
+                        .template("  {spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} rows ({per_sec})")
+                        .unwrap()
+                        .progress_chars("#>-"),
+                );
+                Some(pb)
+            }
+            None => None,
         };
 
-        // Stream rows from source
-        let (columns, mut row_stream) = source.stream_rows(table).await?;
-
-        let mut batch: Vec<(u64, Vec<SqlValue>)> = Vec::with_capacity(opts.batch_rows);
-        let mut total_rows = 0u64;
-        let mut failed_rows: Vec<(u64, String)> = Vec::new();
-        let mut source_row_number = 0u64;
-
-        while let Some(row_result) = row_stream.next().await {
-            let row = row_result?;
-            source_row_number += 1;
-            batch.push((source_row_number, row));
+        let mut indexes = deferred_indexes;
+        if opts.drop_indexes {
+            let dropped = dest.drop_indexes(dest_table).await?;
+            if !dropped.is_empty() && progress.is_none() {
+                println!("  Dropped {} index(es) for the load", dropped.len());
+            }
+            indexes.extend(dropped);
+        }
 
-            // Insert batch when full
-            if batch.len() >= opts.batch_rows {
-                let inserted = insert_batch_with_fallback(
+        let copied = async {
+            if let Some((column, after, upto)) = since {
+                if progress.is_none() {
+                    println!(
+                        "  Copying rows with '{}' after {}",
+                        column,
+                        source.dialect().to_literal(after)
+                    );
+                }
+                let charsets = text_charsets(source, table, opts).await?;
+                let (columns, row_stream) =
+                    source.stream_rows_since(table, column, after, upto).await?;
+                let row_stream = TextRepair::new(&columns, &charsets).apply(row_stream);
+                let columns = dest_column_names(table, &columns, cross_engine, opts);
+                let upsert = upsert_clauses(dest, dest_table, &columns, dest_dialect).await?;
+                return copy_rows(
                     dest,
-                    table,
+                    dest_table,
                     &columns,
-                    &batch,
+                    row_stream,
+                    Some(&upsert),
                     opts,
-                    &mut failed_rows,
+                    pb.as_ref(),
                 )
-                .await?;
-                total_rows += inserted;
+                .await;
+            }
 
-                if let Some(pb) = &pb {
-                    pb.set_position(total_rows);
-                }
+            // A subset is taken from the table as a whole, not per range
+            let split = if opts.split_ranges > 1 && !opts.subset.is_set() {
+                split_by_key(source, table, opts.split_ranges).await?
+            } else {
+                None
+            };
 
-                batch.clear();
-            }
+            let copied = match split {
+                Some(split) => {
+                    // Range copies join this session's snapshot where the
+                    // provider can share it
+                    let snapshot = if opts.consistent_snapshot {
+                        source.export_snapshot().await?
+                    } else {
+                        None
+                    };
+                    if progress.is_none() {
+                        println!(
+                            "  Copying {} ranges of '{}' concurrently",
+                            split.ranges.len(),
+                            split.key
+                        );
+                        if opts.consistent_snapshot && snapshot.is_none() {
+                            println!("  Note: each range takes its own snapshot");
+                        }
+                    }
+                    migrate_ranges(
+                        pools,
+                        snapshot.as_deref(),
+                        table,
+                        dest_table,
+                        split,
+                        opts,
+                        pb.as_ref(),
+                    )
+                    .await?
+                }
+                None => {
+                    let chunk_rows = opts.subset.chunk_rows(opts.chunk_rows);
+                    let charsets = text_charsets(source, table, opts).await?;
+                    let (columns, row_stream) =
+                        stream_rows_chunked(&mut *source, table, chunk_rows).await?;
+                    let row_stream = TextRepair::new(&columns, &charsets).apply(row_stream);
+                    let columns = dest_column_names(table, &columns, cross_engine, opts);
+                    let row_stream = opts.subset.apply(row_stream);
+                    copy_rows(
+                        dest,
+                        dest_table,
+                        &columns,
+                        row_stream,
+                        None,
+                        opts,
+                        pb.as_ref(),
+                    )
+                    .await?
+                }
+            };
+            anyhow::Ok(copied)
         }
+        .await;
 
-        // Insert remaining rows
-        if !batch.is_empty() {
-            let inserted =
-                insert_batch_with_fallback(dest, table, &columns, &batch, opts, &mut failed_rows)
-                    .await?;
-            total_rows += inserted;
+        // Build indexes even when the copy failed, so the table keeps them
+        if !indexes.is_empty() && progress.is_none() {
+            println!("  Building {} index(es)...", indexes.len());
         }
+        for index in &indexes {
+            dest.execute(index)
+                .await
+                .with_context(|| format!("Failed to create index: {}", index))?;
+        }
+        let copied = copied?;
 
+        let mut summary = format!("Migrated {} rows", copied.rows);
+        if let Some(batch_rows) = copied.tuned_batch_rows {
+            summary.push_str(&format!(" (batch size settled at {} rows)", batch_rows));
+        }
         if let Some(pb) = &pb {
-            pb.finish_with_message(format!("Migrated {} rows", total_rows));
+            pb.finish_with_message(summary);
         } else {
-            println!("  Migrated {} rows", total_rows);
+            println!("  {}", summary);
         }
 
+        let failed_rows = copied.failed_rows;
         if !failed_rows.is_empty() {
-            println!("  Failed to insert {} row(s)", failed_rows.len());
+            let mut report = format!(
+                "  Failed to insert {} row(s) into '{}'",
+                failed_rows.len(),
+                table
+            );
             for (row_number, err) in failed_rows.iter().take(10) {
-                println!("    Source row {}: {}", row_number, err);
+                report.push_str(&format!("\n    Source row {}: {}", row_number, err));
             }
             if failed_rows.len() > 10 {
-                println!("    ... and {} more errors", failed_rows.len() - 10);
+                report.push_str(&format!(
+                    "\n    ... and {} more errors",
+                    failed_rows.len() - 10
+                ));
+            }
+            match progress {
+                Some(progress) => progress.println(report)?,
+                None => println!("{}", report),
             }
         }
     }
 
+    // Sequences carry over only where the source DDL was replayed; a
+    // translated table's counters start after the rows copied in
+    if !cross_engine {
+        for mut statement in source.sequence_statements(table, copy_data).await? {
+            // PostgreSQL's statements name the column after the table, in
+            // `pg_get_serial_sequence('table', 'column')` or `ALTER COLUMN`
+            for (old, new) in &renames {
+                statement = statement
+                    .replace(
+                        &format!("COLUMN {}", dest_dialect.quote_identifier(old)),
+                        &format!("COLUMN {}", dest_dialect.quote_identifier(new)),
+                    )
+                    .replace(
+                        &format!(", '{}')", old.replace('\'', "''")),
+                        &format!(", '{}')", new.replace('\'', "''")),
+                    );
+            }
+            dest.execute(statement.trim_end_matches(';'))
+                .await
+                .with_context(|| format!("Failed to set sequence: {}", statement))?;
+        }
+    }
+    for column in &counters {
+        if let Some(statement) = dest_dialect.reset_auto_increment_sql(dest_table, column) {
+            dest.execute(&statement)
+                .await
+                .with_context(|| format!("Failed to set sequence: {}", statement))?;
+        }
+    }
+
+    if let (Some(state), Some((_, _, upto))) = (&opts.incremental, watermark) {
+        // An empty table has no watermark yet and is copied whole next time
+        if upto != SqlValue::Null {
+            state.record(table, upto)?;
+        }
+    }
+
     Ok(())
 }
 
+/// Before a `--data-only` copy, compare the columns of each table on both
+/// sides, failing with every mismatch unless `--allow-drift` only warns.
+/// `--auto-alter add-columns` first adds the columns the destination lacks.
+async fn check_drift(
+    source: &mut dyn DbSession,
+    dest: &mut dyn DbSession,
+    tables: &[String],
+    opts: &MigrateOptions,
+) -> Result<()> {
+    let same_engine = source.dialect().name() == dest.dialect().name();
+    let mut drift = Vec::new();
+    for table in tables {
+        if opts.exclude_data.iter().any(|p| table_matches(p, table)) {
+            continue;
+        }
+        let dest_table = &dest_table_name(table, !same_engine, opts.identifier_case);
+        let mut columns = source.describe_columns(table).await?;
+        let names: Vec<String> = columns.iter().map(|c| c.name.clone()).collect();
+        for (column, name) in
+            columns
+                .iter_mut()
+                .zip(dest_column_names(table, &names, !same_engine, opts))
+        {
+            column.name = name;
+        }
+        if !dest_table_exists(dest, dest_table).await? {
+            drift.extend(table_drift(table, &columns, None, same_engine));
+            continue;
+        }
+        let mut dest_columns = dest.describe_columns(dest_table).await?;
+
+        let described = !columns.is_empty() && !dest_columns.is_empty();
+        if described && opts.auto_alter == Some(AutoAlter::AddColumns) {
+            for column in &columns {
+                if dest_columns.iter().any(|c| c.name == column.name) {
+                    continue;
+                }
+                let added = add_column(dest, dest_table, column, same_engine, opts).await?;
+                println!("  {}: added column '{}'", table, column.name);
+                dest_columns.push(added);
+            }
+        }
+        drift.extend(table_drift(
+            table,
+            &columns,
+            Some(&dest_columns),
+            same_engine,
+        ));
+    }
+    if drift.is_empty() {
+        return Ok(());
+    }
+
+    println!("The destination schema differs from the source:");
+    for mismatch in &drift {
+        println!("  {}", mismatch);
+    }
+    if !opts.allow_drift {
+        bail!(
+            "{} mismatch(es) between source and destination; align the destination schema, or copy anyway with --allow-drift",
+            drift.len()
+        );
+    }
+    println!("Copying anyway (--allow-drift)");
+    Ok(())
+}
+
+/// How `table` on the destination, with `dest` columns or missing when
+/// `None`, differs from the source's `columns`, each mismatch named after
+/// the table. Columns are only compared where both providers can describe
+/// them.
+fn table_drift(
+    table: &str,
+    columns: &[Column],
+    dest: Option<&[Column]>,
+    same_engine: bool,
+) -> Vec<String> {
+    let Some(dest) = dest else {
+        return vec![format!("{}: table is missing on the destination", table)];
+    };
+    if columns.is_empty() || dest.is_empty() {
+        return Vec::new();
+    }
+    column_drift(columns, dest, same_engine)
+        .into_iter()
+        .map(|mismatch| format!("{}: {}", table, mismatch))
+        .collect()
+}
+
+/// Add the source's `column` to `table` on the destination, typed as on the
+/// source within one engine and translated as for a created table across
+/// engines. It takes NULL, as the rows already there have no value for it.
+async fn add_column(
+    dest: &mut dyn DbSession,
+    table: &str,
+    column: &Column,
+    same_engine: bool,
+    opts: &MigrateOptions,
+) -> Result<Column> {
+    let dialect = dest.dialect();
+    let column_type = if same_engine {
+        column.declared.clone()
+    } else {
+        match opts.type_map.type_for(table, column) {
+            Some(sql) => sql.to_string(),
+            None => dialect.column_type_sql(column).ok_or_else(|| {
+                anyhow!(
+                    "{} has no type for column '{}' ({}); give it one with --type-map",
+                    dialect.name(),
+                    column.name,
+                    column.declared
+                )
+            })?,
+        }
+    };
+    let alter = dialect.add_column_statement(table, &column.name, &column_type);
+    dest.execute(&alter)
+        .await
+        .with_context(|| format!("Failed to add column: {}", alter))?;
+    Ok(Column {
+        declared: column_type,
+        nullable: true,
+        ..column.clone()
+    })
+}
+
+/// The name `table` is written under on the destination. Schemas rarely
+/// line up across engines, so there tables land in the destination's
+/// default schema, named in `case` (`--identifier-case`).
+fn dest_table_name(table: &str, cross_engine: bool, case: IdentifierCase) -> String {
+    if cross_engine {
+        case.apply(split_table_name(table).1)
+    } else {
+        table.to_string()
+    }
+}
+
+/// `columns` of `table` as the destination names them: renamed by
+/// `--rename-column` and, across engines, in `--identifier-case`
+fn dest_column_names(
+    table: &str,
+    columns: &[String],
+    cross_engine: bool,
+    opts: &MigrateOptions,
+) -> Vec<String> {
+    let columns = opts.rename_columns.apply(table, columns);
+    if cross_engine {
+        columns
+            .iter()
+            .map(|c| opts.identifier_case.apply(c))
+            .collect()
+    } else {
+        columns
+    }
+}
+
+/// With `--convert-charset`, the character set of each text column of
+/// `table` on the source, so text kept in another one can be repaired as
+/// its rows stream by
+async fn text_charsets(
+    source: &mut dyn DbSession,
+    table: &str,
+    opts: &MigrateOptions,
+) -> Result<Vec<(String, String)>> {
+    match opts.convert_charset {
+        Some(_) => source.column_charsets(table).await,
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Whether `table` is on the destination already
+async fn dest_table_exists(dest: &mut dyn DbSession, table: &str) -> Result<bool> {
+    let filter = TableFilter::new(&[table.to_string()], &[]);
+    Ok(!dest.list_tables(&filter).await?.is_empty())
+}
+
+/// Tables whose foreign keys reference each other in a cycle, directly or
+/// through other tables. No order of creating and loading them satisfies
+/// every key, so each is created without its foreign keys, held here and
+/// added once all the tables are loaded.
+#[derive(Default)]
+struct ForeignKeyCycles {
+    tables: HashSet<String>,
+    held: Mutex<Vec<String>>,
+}
+
+impl ForeignKeyCycles {
+    /// Find the cycles among `tables`. Only tables whose source DDL is
+    /// replayed carry foreign keys over.
+    async fn find(
+        source: &mut dyn DbSession,
+        tables: &[String],
+        dest_dialect: &dyn SqlDialect,
+        opts: &MigrateOptions,
+    ) -> Result<Self> {
+        if opts.data_only || source.dialect().name() != dest_dialect.name() {
+            return Ok(Self::default());
+        }
+        let listed: HashSet<&str> = tables.iter().map(String::as_str).collect();
+        let mut references = HashMap::new();
+        for table in tables {
+            let mut referenced = source.referenced_tables(table).await?;
+            referenced.retain(|t| listed.contains(t.as_str()));
+            references.insert(table.as_str(), referenced);
+        }
+        let cycles = Self {
+            tables: tables_in_cycles(&references),
+            ..Self::default()
+        };
+        if !cycles.tables.is_empty() {
+            let mut names: Vec<&str> = cycles.tables.iter().map(String::as_str).collect();
+            names.sort_unstable();
+            println!(
+                "Foreign keys reference each other in a cycle among {}; they are added once every table is loaded",
+                names.join(", ")
+            );
+        }
+        Ok(cycles)
+    }
+
+    fn contains(&self, table: &str) -> bool {
+        self.tables.contains(table)
+    }
+
+    fn hold(&self, foreign_keys: Vec<String>) {
+        self.held
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(foreign_keys);
+    }
+
+    fn has_held(&self) -> bool {
+        !self
+            .held
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_empty()
+    }
+
+    /// Add the foreign keys held back, which checks the loaded rows against
+    /// them where the destination validates new constraints
+    async fn add_held(&self, dest: &mut dyn DbSession) -> Result<()> {
+        let held = std::mem::take(&mut *self.held.lock().unwrap_or_else(|e| e.into_inner()));
+        if held.is_empty() {
+            return Ok(());
+        }
+        println!("\nAdding {} foreign key(s) held back...", held.len());
+        for statement in &held {
+            dest.execute(statement)
+                .await
+                .with_context(|| format!("Failed to add foreign key: {}", statement))?;
+        }
+        Ok(())
+    }
+}
+
+/// Tables of `references` that reach themselves through the tables they
+/// reference, a table referencing itself included
+fn tables_in_cycles(references: &HashMap<&str, Vec<String>>) -> HashSet<String> {
+    references
+        .keys()
+        .filter(|&&table| {
+            let mut seen = HashSet::new();
+            let mut pending: Vec<&str> = references[table].iter().map(String::as_str).collect();
+            while let Some(next) = pending.pop() {
+                if next == table {
+                    return true;
+                }
+                if seen.insert(next) {
+                    if let Some(referenced) = references.get(next) {
+                        pending.extend(referenced.iter().map(String::as_str));
+                    }
+                }
+            }
+            false
+        })
+        .map(|table| table.to_string())
+        .collect()
+}
+
+/// How INSERTs into `table` change so that rows copied before are updated
+/// in place, for an `--incremental` run
+async fn upsert_clauses(
+    dest: &mut dyn DbSession,
+    table: &str,
+    columns: &[String],
+    dialect: &dyn SqlDialect,
+) -> Result<(&'static str, String)> {
+    let key = dest.primary_key(table).await?;
+    if key.is_empty() {
+        bail!(
+            "--incremental needs a primary key on '{}' to update rows copied before",
+            table
+        );
+    }
+    dialect
+        .conflict_clauses(OnConflict::Update, columns, &key)
+        .ok_or_else(|| anyhow::anyhow!("--incremental is not supported for {}", dialect.name()))
+}
+
+/// Primary key ranges a table is copied in
+struct KeySplit {
+    key: String,
+    ranges: Vec<RangeInclusive<i64>>,
+}
+
+/// Split `table` on its primary key, or `None` when the key is not a single
+/// integer column or the table is too small to split
+async fn split_by_key(
+    source: &mut dyn DbSession,
+    table: &str,
+    parts: usize,
+) -> Result<Option<KeySplit>> {
+    let key = source.primary_key(table).await?;
+    let [key] = key.as_slice() else {
+        return Ok(None);
+    };
+    let Some((min, max)) = source.key_bounds(table, key).await? else {
+        return Ok(None);
+    };
+
+    let ranges = split_key_range(min, max, parts);
+    if ranges.len() < 2 {
+        return Ok(None);
+    }
+    Ok(Some(KeySplit {
+        key: key.clone(),
+        ranges,
+    }))
+}
+
+/// Copy every range of `split` concurrently, each over its own source and
+/// destination sessions from the pools. Progress from all ranges lands on the
+/// one `pb`.
+async fn migrate_ranges(
+    pools: Pools<'_, '_>,
+    snapshot: Option<&str>,
+    table: &str,
+    dest_table: &str,
+    split: KeySplit,
+    opts: &MigrateOptions,
+    pb: Option<&ProgressBar>,
+) -> Result<Copied> {
+    let key = split.key.as_str();
+
+    let copies = split.ranges.into_iter().map(|range| async move {
+        let (mut source, mut dest) = pools.get().await?;
+        dest.set_insert_mode(opts.insert_mode)?;
+        if opts.fast_load {
+            dest.enable_fast_load().await?;
+        }
+
+        match snapshot {
+            Some(snapshot) => source.import_snapshot(snapshot).await?,
+            None if opts.consistent_snapshot => source.start_consistent_snapshot().await?,
+            None => {}
+        }
+        if opts.disable_fk_checks {
+            dest.disable_constraints().await?;
+        }
+
+        let charsets = text_charsets(&mut *source, table, opts).await?;
+        let (columns, row_stream) = source.stream_key_range(table, key, range).await?;
+        let row_stream = TextRepair::new(&columns, &charsets).apply(row_stream);
+        let cross_engine = source.dialect().name() != dest.dialect().name();
+        let columns = dest_column_names(table, &columns, cross_engine, opts);
+        let copied =
+            copy_rows(&mut *dest, dest_table, &columns, row_stream, None, opts, pb).await?;
+
+        if opts.disable_fk_checks {
+            dest.enable_constraints().await?;
+        }
+        source.commit().await?;
+        dest.commit().await?;
+        source.release();
+        dest.release();
+
+        anyhow::Ok(copied)
+    });
+
+    let mut total = Copied::default();
+    for copied in futures::future::try_join_all(copies).await? {
+        total.rows += copied.rows;
+        total.failed_rows.extend(copied.failed_rows);
+        total.tuned_batch_rows = total.tuned_batch_rows.max(copied.tuned_batch_rows);
+    }
+    Ok(total)
+}
+
+/// Outcome of copying a table's rows
+#[derive(Default)]
+struct Copied {
+    rows: u64,
+    /// Rows skipped under `skip_errors`, by source row number
+    failed_rows: Vec<(u64, String)>,
+    /// Batch size `--batch-rows auto` ended on
+    tuned_batch_rows: Option<usize>,
+}
+
+/// Insert every row of `row_stream` into `dest_table` in batches
+/// Rows of one batch, each with its source row number
+type Batch = Vec<(u64, Vec<SqlValue>)>;
+
+/// Copy `row_stream` into `dest_table`. Reading and inserting run side by
+/// side: batches pass from the reader to the inserter over a channel holding
+/// at most `max_inflight_batches`, so the reader waits for a slow destination
+/// instead of buffering the table in memory.
+async fn copy_rows(
+    dest: &mut dyn DbSession,
+    dest_table: &str,
+    columns: &[String],
+    mut row_stream: impl Stream<Item = Result<Vec<SqlValue>>> + Unpin,
+    upsert: Option<&(&'static str, String)>,
+    opts: &MigrateOptions,
+    pb: Option<&ProgressBar>,
+) -> Result<Copied> {
+    let mut tuner = opts.batch_rows.tuner();
+    let mut limit = BatchLimit::new(tuner.rows(), opts.batch_bytes, dest.max_statement_bytes());
+    // The inserter tunes the batch size; the reader picks it up from here
+    let batch_rows = &AtomicUsize::new(limit.rows);
+    let (sender, mut receiver) = mpsc::channel::<Batch>(opts.max_inflight_batches.max(1));
+
+    let read = async move {
+        let mut batch: Batch = Vec::with_capacity(limit.rows);
+        let mut batch_bytes = 0usize;
+        let mut source_row_number = 0u64;
+
+        while let Some(row_result) = row_stream.next().await {
+            let row = row_result?;
+            source_row_number += 1;
+            let row_bytes = row_sql_len(&row);
+            opts.throttle.consume(1, row_bytes).await;
+            batch_bytes += row_bytes;
+            batch.push((source_row_number, row));
+
+            limit.rows = batch_rows.load(Ordering::Relaxed);
+            if limit.is_full(batch.len(), batch_bytes) {
+                let full = std::mem::replace(&mut batch, Vec::with_capacity(limit.rows));
+                batch_bytes = 0;
+                if sender.send(full).await.is_err() {
+                    // The inserter only hangs up when it failed; its error is returned
+                    return Ok(());
+                }
+            }
+        }
+
+        // Remaining rows
+        if !batch.is_empty() {
+            let _ = sender.send(batch).await;
+        }
+        anyhow::Ok(())
+    };
+
+    let insert = async {
+        let mut total_rows = 0u64;
+        let mut failed_rows: Vec<(u64, String)> = Vec::new();
+
+        while let Some(batch) = receiver.recv().await {
+            let started = Instant::now();
+            let inserted = insert_batch_with_fallback(
+                dest,
+                dest_table,
+                columns,
+                &batch,
+                upsert,
+                opts,
+                &mut failed_rows,
+            )
+            .await?;
+            total_rows += inserted;
+
+            tuner.record(batch.len(), started.elapsed());
+            batch_rows.store(tuner.rows(), Ordering::Relaxed);
+
+            if let Some(pb) = pb {
+                pb.inc(inserted);
+            }
+        }
+
+        anyhow::Ok(Copied {
+            rows: total_rows,
+            failed_rows,
+            tuned_batch_rows: tuner.is_adaptive().then(|| tuner.rows()),
+        })
+    };
+
+    let ((), copied) = futures::try_join!(read, insert)?;
+    Ok(copied)
+}
+
+/// Read up to `sample_rows` rows and take each column's first non-NULL value
+/// as its type. Columns with only NULLs in the sample stay `SqlValue::Null`.
+async fn sample_column_types(
+    source: &mut dyn DbSession,
+    table: &str,
+    sample_rows: usize,
+) -> Result<(Vec<String>, Vec<SqlValue>)> {
+    let (columns, mut row_stream) = source.stream_rows(table).await?;
+    let mut column_types = vec![SqlValue::Null; columns.len()];
+
+    let mut seen = 0;
+    while seen < sample_rows.max(1) {
+        let Some(row) = row_stream.next().await else {
+            break;
+        };
+        for (slot, value) in column_types.iter_mut().zip(row?) {
+            if matches!(slot, SqlValue::Null) {
+                *slot = value;
+            }
+        }
+        if column_types.iter().all(|t| !matches!(t, SqlValue::Null)) {
+            break;
+        }
+        seen += 1;
+    }
+
+    Ok((columns, column_types))
+}
+
 async fn insert_batch_with_fallback(
     dest: &mut dyn DbSession,
     table: &str,
     columns: &[String],
     batch: &[(u64, Vec<SqlValue>)],
+    upsert: Option<&(&'static str, String)>,
     opts: &MigrateOptions,
     failed_rows: &mut Vec<(u64, String)>,
 ) -> Result<u64> {
     let rows: Vec<Vec<SqlValue>> = batch.iter().map(|(_, row)| row.clone()).collect();
 
-    match dest.insert_batch(table, columns, &rows).await {
+    match insert_rows(dest, table, columns, &rows, upsert).await {
         Ok(()) => Ok(batch.len() as u64),
         Err(_batch_error) => {
             let mut inserted = 0u64;
 
             for (row_number, row) in batch {
                 let single_row = vec![row.clone()];
-                match dest.insert_batch(table, columns, &single_row).await {
+                match insert_rows(dest, table, columns, &single_row, upsert).await {
                     Ok(()) => inserted += 1,
                     Err(row_error) => {
                         let record = summarize_record(row);
@@ -238,6 +1852,24 @@ async fn insert_batch_with_fallback(
     }
 }
 
+/// Insert `rows`, or with `upsert` write them over the rows they collide with
+pub(crate) async fn insert_rows(
+    dest: &mut dyn DbSession,
+    table: &str,
+    columns: &[String],
+    rows: &[Vec<SqlValue>],
+    upsert: Option<&(&'static str, String)>,
+) -> Result<()> {
+    let Some((keywords, clause)) = upsert else {
+        return dest.insert_batch(table, columns, rows).await;
+    };
+    let sql = dest.dialect().insert_values_sql(table, columns, rows);
+    let values = sql.strip_prefix("INSERT INTO").unwrap_or(&sql);
+    let values = values.trim_end().trim_end_matches(';');
+    dest.execute(&format!("{}{}{}", keywords, values, clause))
+        .await
+}
+
 fn summarize_record(row: &[SqlValue]) -> String {
     const MAX_LEN: usize = 200;
     let full = format!("{:?}", row);
@@ -248,3 +1880,110 @@ fn summarize_record(row: &[SqlValue]) -> String {
         format!("{}...", &full[..MAX_LEN])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::ddl::ColumnType;
+
+    fn column(name: &str, declared: &str) -> Column {
+        Column {
+            name: name.into(),
+            column_type: ColumnType::parse(declared),
+            declared: declared.into(),
+            nullable: true,
+            auto_increment: false,
+        }
+    }
+
+    #[test]
+    fn reports_drift_by_table() {
+        let source = vec![column("id", "int"), column("total", "decimal(10,2)")];
+
+        assert_eq!(
+            table_drift("orders", &source, None, true),
+            ["orders: table is missing on the destination"]
+        );
+        // Columns only the destination has are left to it
+        let dest = vec![
+            column("id", "int"),
+            column("total", "decimal(10,2)"),
+            column("added", "text"),
+        ];
+        assert!(table_drift("orders", &source, Some(&dest), true).is_empty());
+        // A column only the source has is not
+        assert_eq!(
+            table_drift("orders", &dest, Some(&source), true),
+            ["orders: column 'added' is missing on the destination"]
+        );
+        // Types only compare within one engine
+        let dest = vec![column("id", "bigint"), column("total", "decimal(10,2)")];
+        assert_eq!(
+            table_drift("orders", &source, Some(&dest), true),
+            ["orders: column 'id' is int on the source but bigint on the destination"]
+        );
+        assert!(table_drift("orders", &source, Some(&dest), false).is_empty());
+        // Nor are the columns of a provider that cannot describe them
+        assert!(table_drift("orders", &source, Some(&[]), true).is_empty());
+        assert!(table_drift("orders", &[], Some(&dest), true).is_empty());
+    }
+
+    #[test]
+    fn finds_tables_in_foreign_key_cycles() {
+        let cycles = |references: &[(&str, &[&str])]| {
+            let references: HashMap<&str, Vec<String>> = references
+                .iter()
+                .map(|(table, referenced)| {
+                    (*table, referenced.iter().map(|t| t.to_string()).collect())
+                })
+                .collect();
+            let mut tables: Vec<String> = tables_in_cycles(&references).into_iter().collect();
+            tables.sort();
+            tables
+        };
+
+        // A table referencing itself
+        assert_eq!(
+            cycles(&[("employees", &["employees"]), ("teams", &[])]),
+            ["employees"]
+        );
+        // Two tables referencing each other, and one referencing them
+        assert_eq!(
+            cycles(&[
+                ("authors", &["books"]),
+                ("books", &["authors"]),
+                ("reviews", &["books"]),
+            ]),
+            ["authors", "books"]
+        );
+        // A chain comes back to no table
+        assert!(cycles(&[
+            ("order_items", &["orders", "products"]),
+            ("orders", &["customers"]),
+            ("products", &[]),
+            ("customers", &[]),
+        ])
+        .is_empty());
+    }
+
+    #[test]
+    fn names_destination_tables() {
+        assert_eq!(
+            dest_table_name("sales.Orders", false, IdentifierCase::Lower),
+            "sales.Orders"
+        );
+        // Across engines the schema goes, and the case follows the option
+        assert_eq!(
+            dest_table_name("sales.Orders", true, IdentifierCase::Preserve),
+            "Orders"
+        );
+        assert_eq!(
+            dest_table_name("sales.Orders", true, IdentifierCase::Lower),
+            "orders"
+        );
+        assert_eq!(
+            dest_table_name("Orders", true, IdentifierCase::Upper),
+            "ORDERS"
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use crate::engine::binlog::BinlogChanges;
+use crate::engine::cdc::{Change, ChangeStream};
+use crate::engine::dialect::{delete_row_sql, update_row_sql, OnConflict};
+use crate::engine::filter::TableFilter;
+use crate::engine::pgoutput::PgoutputChanges;
+use crate::engine::{DbEngine, DbSession};
+use crate::migrate::insert_rows;
+use crate::util::state::PositionFile;
+use anyhow::{anyhow, bail, Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+pub struct ReplicateOptions {
+    pub filter: TableFilter,
+    /// Where to carry on from, written by `migrate --position-file` and
+    /// moved on as transactions are applied
+    pub position_file: PathBuf,
+    /// Id the log reader registers with, unique among the source's replicas
+    /// (MySQL)
+    pub server_id: u32,
+    /// Logical replication slot the changes are read from (PostgreSQL)
+    pub slot: String,
+}
+
+/// Apply the changes committed on the source since the position in
+/// `opts.position_file` to the destination, one source transaction per
+/// destination transaction, until stopped with Ctrl-C
+pub async fn replicate(
+    source_engine: &dyn DbEngine,
+    dest_engine: &dyn DbEngine,
+    source_provider: &str,
+    source_url: &str,
+    dest_url: &str,
+    opts: ReplicateOptions,
+) -> Result<()> {
+    let mut position = PositionFile::open(&opts.position_file)?;
+    println!("Replicating from {}", position.position());
+
+    println!("Connecting to source database...");
+    let source = source_engine
+        .connect(source_url)
+        .await
+        .context("Failed to connect to source database")?;
+    println!("Connecting to destination database...");
+    let mut dest = dest_engine
+        .connect(dest_url)
+        .await
+        .context("Failed to connect to destination database")?;
+
+    let mut changes: Box<dyn ChangeStream> = match source_provider.to_lowercase().as_str() {
+        "mysql" | "mariadb" => Box::new(
+            BinlogChanges::open(
+                source_url,
+                source,
+                position.position(),
+                opts.server_id,
+                opts.filter,
+            )
+            .await?,
+        ),
+        "postgres" => Box::new(
+            PgoutputChanges::open(source, &opts.slot, position.position(), opts.filter).await?,
+        ),
+        _ => bail!("replicate is not supported for {} sources", source_provider),
+    };
+
+    let progress = ProgressBar::new_spinner();
+    progress.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.green} {msg}")
+            .unwrap(),
+    );
+    progress.set_message("Waiting for changes (Ctrl-C to stop)");
+
+    let mut keys = HashMap::new();
+    let mut in_transaction = false;
+    let mut applied = 0u64;
+    let mut pending = 0u64;
+    let stop = tokio::signal::ctrl_c();
+    tokio::pin!(stop);
+    loop {
+        let change = tokio::select! {
+            change = changes.next_change() => change?,
+            _ = &mut stop => break,
+        };
+        match change {
+            Change::Commit(at) => {
+                if in_transaction {
+                    dest.commit().await?;
+                    in_transaction = false;
+                }
+                applied += pending;
+                pending = 0;
+                position.record(at)?;
+                progress.set_message(format!(
+                    "{} change(s) applied, at {}",
+                    applied,
+                    position.position()
+                ));
+            }
+            Change::Schema(sql) => {
+                progress.suspend(|| {
+                    println!(
+                        "Note: not applying '{}'; apply it to the destination by hand",
+                        sql
+                    )
+                });
+            }
+            change => {
+                if !in_transaction {
+                    dest.begin().await?;
+                    in_transaction = true;
+                }
+                apply_change(&mut *dest, &mut keys, change).await?;
+                pending += 1;
+            }
+        }
+    }
+    if in_transaction {
+        dest.rollback().await?;
+    }
+    progress.finish_and_clear();
+
+    println!(
+        "Stopped after applying {} change(s); the next run carries on from {}",
+        applied,
+        position.position()
+    );
+    Ok(())
+}
+
+async fn apply_change(
+    dest: &mut dyn DbSession,
+    keys: &mut HashMap<String, Vec<String>>,
+    change: Change,
+) -> Result<()> {
+    let dialect = dest.dialect();
+    match change {
+        Change::Insert { table, row } => {
+            let key = primary_key(dest, keys, &table).await?;
+            // The row may be there already when the snapshot saw it
+            let upsert = dialect
+                .conflict_clauses(OnConflict::Update, &row.columns, &key)
+                .ok_or_else(|| anyhow!("replicate is not supported for {}", dialect.name()))?;
+            insert_rows(dest, &table, &row.columns, &[row.values], Some(&upsert))
+                .await
+                .with_context(|| format!("Failed to apply an insert to '{}'", table))
+        }
+        Change::Update {
+            table,
+            before,
+            after,
+        } => {
+            let key = primary_key(dest, keys, &table).await?;
+            let key_values = before.values_of(&key).ok_or_else(|| {
+                anyhow!("The log leaves out the key of a row updated in '{}'", table)
+            })?;
+            if after.columns.is_empty() {
+                return Ok(());
+            }
+            let sql = update_row_sql(
+                dialect,
+                &table,
+                &after.columns,
+                &after.values,
+                &key,
+                &key_values,
+            );
+            dest.execute(&sql)
+                .await
+                .with_context(|| format!("Failed to apply an update to '{}'", table))
+        }
+        Change::Delete { table, row } => {
+            let key = primary_key(dest, keys, &table).await?;
+            let key_values = row.values_of(&key).ok_or_else(|| {
+                anyhow!(
+                    "The log leaves out the key of a row deleted from '{}'",
+                    table
+                )
+            })?;
+            dest.execute(&delete_row_sql(dialect, &table, &key, &key_values))
+                .await
+                .with_context(|| format!("Failed to apply a delete to '{}'", table))
+        }
+        Change::Schema(_) | Change::Commit(_) => Ok(()),
+    }
+}
+
+/// The destination's primary key of `table`, which rows are matched on
+async fn primary_key(
+    dest: &mut dyn DbSession,
+    keys: &mut HashMap<String, Vec<String>>,
+    table: &str,
+) -> Result<Vec<String>> {
+    if let Some(key) = keys.get(table) {
+        return Ok(key.clone());
+    }
+    let key = dest.primary_key(table).await?;
+    if key.is_empty() {
+        bail!(
+            "replicate needs a primary key on '{}' to match changed rows",
+            table
+        );
+    }
+    keys.insert(table.to_string(), key.clone());
+    Ok(key)
+}
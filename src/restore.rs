@@ -1,106 +1,240 @@
-use crate::engine::DbEngine;
-use anyhow::{Context, Result};
-use flate2::read::GzDecoder;
+use crate::engine::dialect::{conflict_clauses_for, OnConflict, SqlDialect};
+use crate::engine::filter::{table_matches, wildcard_matches};
+use crate::engine::pool::{PooledSession, SessionPool};
+use crate::engine::{DbEngine, DbSession, InsertMode};
+use crate::util::binary::{self, Segment};
+use crate::util::compress::Codec;
+use crate::util::dialects::ansi::ANSI_DIALECT;
+use crate::util::encrypt::Decryption;
+use crate::util::manifest::{Manifest, SectionWriter, TableEntry, MANIFEST_FILE};
+use crate::util::remap::Remap;
+use crate::util::splitter::{tokenize, StatementSplitter, Syntax, TokenKind};
+use crate::util::state::RestoreStateFile;
+use crate::util::storage;
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fs::File;
-use std::io::{BufRead, BufReader, Read};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, OnceLock};
+use tokio::sync::{mpsc, watch, Mutex};
+
+/// COPY data is sent to the server in pieces of about this size
+const COPY_CHUNK_BYTES: usize = 8 * 1024 * 1024;
+
+/// Statements queued for a table job before the reader waits for its worker
+const JOB_QUEUE_ITEMS: usize = 64;
 
 pub struct RestoreOptions {
     pub disable_fk_checks: bool,
+    /// Relax durability and constraint enforcement while loading
+    pub fast_load: bool,
+    /// Number of worker connections restoring tables concurrently
+    pub jobs: usize,
+    /// Tables to restore: the files of a directory dump, or the statements
+    /// on them in a single file; empty restores all
+    pub tables: Vec<String>,
+    /// Run only the statements that define things, or only those that load
+    /// rows
+    pub schema_only: bool,
+    pub data_only: bool,
+    /// Drop the account from views, triggers, routines and events, so they
+    /// belong to the restoring user
+    pub skip_definer: bool,
+    /// How the rows of a binary dump are inserted
+    pub insert_mode: InsertMode,
+    /// Keys for reading an encrypted dump
+    pub decryption: Option<Decryption>,
+    /// Log statements that fail to this file and carry on, rather than stop
+    /// at the first
+    pub skip_errors: Option<PathBuf>,
+    /// Commit after every this many statements, rather than after each
+    pub commit_every: Option<u64>,
+    /// Run the whole restore in one transaction, rolled back if it fails
+    pub single_transaction: bool,
+    /// Where a sequential restore notes how far it has got
+    pub state_file: PathBuf,
+    /// Skip what the state file notes as restored, rather than start over
+    pub resume: bool,
+    /// What INSERT statements do with rows already on the server
+    pub on_conflict: OnConflict,
+    /// Schemas and tables restored under other names
+    pub remap: Remap,
+    /// Leave out `DROP TABLE`, and create tables only where they are missing
+    pub no_drop: bool,
 }
 
 pub async fn restore(
     engine: &dyn DbEngine,
     destination_url: &str,
-    input_path: &str,
+    inputs: &[String],
     opts: RestoreOptions,
 ) -> Result<()> {
     println!("Starting database restore...");
+    if opts.single_transaction && opts.jobs > 1 {
+        bail!("--single-transaction restores over one connection; leave out --jobs");
+    }
+    if opts.resume && opts.jobs > 1 {
+        bail!("--resume continues a sequential restore; leave out --jobs");
+    }
+    // A single transaction leaves nothing behind to resume, and parallel
+    // jobs commit out of order
+    let mut checkpoints = match opts.single_transaction || opts.jobs > 1 {
+        true => None,
+        false => Some(Checkpoints::start(&inputs.join(", "), &opts)?),
+    };
 
-    // Connect to destination
-    let mut session = engine
-        .connect(destination_url)
+    // Connect to destination; parallel workers draw from the same pool
+    let pool = engine.connect_pool(destination_url, opts.jobs.max(1) + 1);
+    let mut session = pool
+        .get()
         .await
         .context("Failed to connect to destination database")?;
 
-    // Disable constraints if requested
-    if opts.disable_fk_checks {
-        println!("Disabling foreign key checks...");
-        session.disable_constraints().await?;
+    prepare_session(&mut *session, &opts, true).await?;
+    if opts.on_conflict != OnConflict::Error {
+        let dialect = session.dialect();
+        if dialect
+            .conflict_clauses(opts.on_conflict, &[], &[])
+            .is_none()
+        {
+            bail!("--on-conflict is not supported for {}", dialect.name());
+        }
+        println!("Rewriting INSERT statements to handle rows already there");
     }
 
-    // Open input file
-    let reader: Box<dyn Read> = if input_path.ends_with(".gz") {
-        println!("Decompressing gzip input...");
-        Box::new(GzDecoder::new(File::open(input_path)?))
-    } else {
-        Box::new(File::open(input_path)?)
-    };
-
-    let buf_reader = BufReader::new(reader);
+    let mut filter = statement_filter(inputs, &opts);
 
-    // Execute SQL statements line by line
-    let mut statement_count = 0u64;
-    let mut current_statement = String::new();
-    let mut line_count = 0u64;
+    let errors = opts
+        .skip_errors
+        .as_deref()
+        .map(ErrorLog::create)
+        .transpose()?;
+    let errors = errors.as_ref();
 
-    println!("Executing SQL statements...");
-
-    for line_result in buf_reader.lines() {
-        let line = line_result?;
-        line_count += 1;
-
-        // Skip empty lines and comments (except special MySQL comments)
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            continue;
+    if opts.single_transaction {
+        if session.begin().await? {
+            println!("Restoring in a single transaction");
+        } else {
+            println!(
+                "Note: {} has no transactions; --single-transaction has no effect",
+                session.dialect().name()
+            );
         }
+    } else if let Some(every) = opts.commit_every {
+        println!("Committing every {} statements", every);
+    }
 
-        if trimmed.starts_with("--") && !trimmed.starts_with("-- ") {
-            // Keep special comments like --
-            continue;
+    let restored = async {
+        // Open input file
+        let mut input = BufReader::new(open_input(inputs, &opts.tables, opts.decryption.as_ref())?);
+        if binary::is_binary(input.fill_buf()?) {
+            if inputs.len() > 1 {
+                bail!("Binary dumps are restored one at a time; give one --input");
+            }
+            let statement_count = restore_binary(
+                &mut *session,
+                input,
+                &opts,
+                &mut filter,
+                errors,
+                checkpoints.as_mut(),
+            )
+            .await?;
+            return Ok((statement_count, filter));
         }
-
-        // Skip pure comment lines (starting with --)
-        if trimmed.starts_with("-- ") {
-            continue;
+        if opts.insert_mode != InsertMode::Literal {
+            println!("Note: --insert-mode only applies to binary dumps");
         }
 
-        // Add line to current statement
-        current_statement.push_str(&line);
-        current_statement.push(' ');
+        let mut statements = StatementReader::new(input, session.dialect());
+        if opts.skip_definer {
+            statements.strip_definer = Some(session.dialect());
+        }
+        statements.filter = filter;
+        statements.remap = remapping(&opts, session.dialect());
+        statements.no_drop = opts.no_drop;
 
-        // Check if statement is complete (ends with ;)
-        if trimmed.ends_with(';') {
-            // Execute the statement
-            let stmt = current_statement.trim();
-            if !stmt.is_empty() {
-                session.execute(stmt).await.with_context(|| {
-                    format!(
-                        "Failed to execute statement at line {}: {}",
-                        line_count, stmt
-                    )
-                })?;
+        let mut jobs = opts.jobs;
+        if jobs > 1 && matches!(session.dialect().name(), "SQLite" | "DuckDB") {
+            println!(
+                "{} allows a single writer; restoring sequentially",
+                session.dialect().name()
+            );
+            jobs = 1;
+        }
 
-                statement_count += 1;
+        println!("Executing SQL statements...");
 
-                if statement_count % 100 == 0 {
-                    print!("\rExecuted {} statements...", statement_count);
-                    use std::io::Write;
-                    std::io::stdout().flush()?;
+        let statement_count = if jobs > 1 {
+            println!("Restoring tables with {} parallel jobs", jobs);
+            restore_parallel(&pool, &mut session, &mut statements, jobs, &opts, errors).await?
+        } else {
+            let mut batches = Batches::new(opts.commit_every);
+            let mut inserts = InsertRewriter::new(opts.on_conflict);
+            let mut count = 0u64;
+            while let Some(mut item) = statements.next_item()? {
+                if let Some(checkpoints) = &mut checkpoints {
+                    if checkpoints.skip(&mut *session, &item).await? {
+                        continue;
+                    }
+                }
+                inserts.apply(&mut *session, &mut item).await?;
+                batches.begin(&mut *session).await?;
+                execute_item(&mut *session, &item, errors).await?;
+                if item.is_complete() {
+                    batches.finished(&mut *session).await?;
+                    count += 1;
+                    report_progress(count)?;
+                }
+                if let Some(checkpoints) = &mut checkpoints {
+                    checkpoints.committed(&batches)?;
                 }
             }
+            count
+        };
 
-            current_statement.clear();
-        }
+        anyhow::Ok((statement_count, statements.filter.take()))
     }
+    .await;
 
-    // Execute any remaining statement
-    if !current_statement.trim().is_empty() {
-        session.execute(current_statement.trim()).await?;
-        statement_count += 1;
+    match restored {
+        Ok((statement_count, filter)) => {
+            finish(session, opts, statement_count, filter, errors).await?;
+            match checkpoints {
+                Some(checkpoints) => checkpoints.file.remove(),
+                None => Ok(()),
+            }
+        }
+        Err(e) if opts.single_transaction => {
+            match session.rollback().await {
+                Ok(()) => println!("\nRolled back the restore"),
+                Err(rollback) => println!("\nRollback failed as well: {:#}", rollback),
+            }
+            Err(e)
+        }
+        Err(e) => Err(e),
     }
+}
 
+/// Wrap up a restore that ran `statement_count` statements, and report what
+/// `filter` left out and what failed
+async fn finish(
+    mut session: PooledSession<'_>,
+    opts: RestoreOptions,
+    statement_count: u64,
+    filter: Option<StatementFilter>,
+    errors: Option<&ErrorLog>,
+) -> Result<()> {
     println!("\rExecuted {} statements total", statement_count);
+    if let Some(mut filter) = filter {
+        filter.finish();
+        filter.report();
+    }
+    if let Some(errors) = errors {
+        errors.report();
+    }
 
     // Re-enable constraints
     if opts.disable_fk_checks {
@@ -115,3 +249,2476 @@ pub async fn restore(
 
     Ok(())
 }
+
+/// Read the input as a restore would, without connecting: count the
+/// statements on each table and flag syntax the provider does not take.
+/// Anything flagged fails the run, so it can gate a CI pipeline.
+pub fn dry_run(engine: &dyn DbEngine, inputs: &[String], opts: &RestoreOptions) -> Result<()> {
+    println!("Dry run: reading the input without connecting");
+    let dialect = engine.dialect();
+    if dialect.is_none() {
+        println!(
+            "Note: this provider's dialect is only known once connected; syntax is not checked"
+        );
+    }
+    let mut filter = statement_filter(inputs, opts);
+    let splitting = dialect.unwrap_or(&ANSI_DIALECT);
+    let mut check = DryRun::new(dialect);
+
+    let mut input = BufReader::new(open_input(inputs, &opts.tables, opts.decryption.as_ref())?);
+    if binary::is_binary(input.fill_buf()?) {
+        if inputs.len() > 1 {
+            bail!("Binary dumps are restored one at a time; give one --input");
+        }
+        let mut reader = binary::Reader::new(input)?;
+        while let Some(segment) = reader.next_segment()? {
+            match segment {
+                Segment::Sql(sql) => {
+                    // Lines count from the start of each SQL section, so none are given
+                    let mut statements = StatementReader::new(sql.as_slice(), splitting);
+                    statements.filter = filter.take();
+                    statements.remap = remapping(opts, splitting);
+                    statements.no_drop = opts.no_drop;
+                    while let Some(item) = statements.next_item()? {
+                        check.statement(&item, None);
+                    }
+                    filter = statements.filter.take();
+                }
+                Segment::Rows { table, rows, .. } => {
+                    if filter.as_mut().is_none_or(|f| f.keeps_rows(&table)) {
+                        check.rows(opts.remap.apply_to_table(&table), rows.len());
+                    }
+                }
+            }
+        }
+    } else {
+        let mut statements = StatementReader::new(input, splitting);
+        statements.filter = filter;
+        statements.remap = remapping(opts, splitting);
+        statements.no_drop = opts.no_drop;
+        while let Some(item) = statements.next_item()? {
+            check.statement(&item, Some(item.line()));
+        }
+        filter = statements.filter.take();
+    }
+
+    if let Some(mut filter) = filter {
+        filter.finish();
+        filter.report();
+    }
+    check.report()
+}
+
+/// What `--tables`, `--schema-only` and `--data-only` keep of the input.
+/// A directory dump picks tables by file; a single file by statement.
+fn statement_filter(inputs: &[String], opts: &RestoreOptions) -> Option<StatementFilter> {
+    let patterns: &[String] = match inputs {
+        [dir] if Path::new(dir).is_dir() => &[],
+        _ => &opts.tables,
+    };
+    if !patterns.is_empty() {
+        println!("Restoring only statements on the listed tables; views, routines and anything else are skipped");
+    }
+    if opts.schema_only {
+        println!("Restoring the schema only; rows are skipped");
+    } else if opts.data_only {
+        println!("Restoring the data only; the schema must already be in place");
+    }
+    (!patterns.is_empty() || opts.schema_only || opts.data_only)
+        .then(|| StatementFilter::new(patterns, !opts.data_only, !opts.schema_only))
+}
+
+/// The `--input` files to restore, in order: each pattern as given, or the
+/// local files its `*` and `?` wildcards match, in lexical order
+pub fn expand_inputs(patterns: &[String]) -> Result<Vec<String>> {
+    let mut inputs = Vec::new();
+    for pattern in patterns {
+        if storage::is_remote(pattern) || !pattern.contains(['*', '?']) {
+            inputs.push(pattern.clone());
+            continue;
+        }
+        let path = Path::new(pattern);
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if dir.to_string_lossy().contains(['*', '?']) {
+            bail!(
+                "'{}' has wildcards in its directory; only file names are matched",
+                pattern
+            );
+        }
+        let mut matched: Vec<String> = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to list '{}'", dir.display()))?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|t| !t.is_dir()))
+            .filter(|entry| wildcard_matches(&name, &entry.file_name().to_string_lossy()))
+            .map(|entry| match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent
+                    .join(entry.file_name())
+                    .to_string_lossy()
+                    .into_owned(),
+                _ => entry.file_name().to_string_lossy().into_owned(),
+            })
+            .collect();
+        if matched.is_empty() {
+            bail!("No file matches '{}'", pattern);
+        }
+        matched.sort();
+        inputs.extend(matched);
+    }
+    Ok(inputs)
+}
+
+/// The SQL to run: a dump file or stdin, several files one after another,
+/// or the files of a directory dump with the users, schemas, extensions,
+/// routines and every table's schema ahead of the data, any post-data
+/// after it, and the views, events and grants last. Encrypted files are
+/// read with `decryption`.
+fn open_input(
+    inputs: &[String],
+    tables: &[String],
+    decryption: Option<&Decryption>,
+) -> Result<Box<dyn Read>> {
+    let input_path = match inputs {
+        [input_path] => input_path.as_str(),
+        _ => {
+            if let Some(dir) = inputs.iter().find(|i| Path::new(i).is_dir()) {
+                bail!(
+                    "'{}' is a directory dump, which is restored on its own",
+                    dir
+                );
+            }
+            println!("Restoring {} files in order", inputs.len());
+            return Ok(Box::new(FilesReader {
+                files: inputs.iter().map(PathBuf::from).collect(),
+                decryption: decryption.cloned(),
+                current: None,
+            }));
+        }
+    };
+    let dir = Path::new(input_path);
+    if !dir.is_dir() {
+        let codec = Codec::from_path(input_path);
+        if codec != Codec::None {
+            println!("Decompressing {} input...", codec);
+        }
+        return codec.open(input_path, decryption).with_context(|| {
+            format!(
+                "Failed to open input file '{}'",
+                storage::display_location(input_path)
+            )
+        });
+    }
+
+    let manifest =
+        Manifest::read(&dir.join(MANIFEST_FILE)).context("Is this a complete directory dump?")?;
+    if let Some(missing) = tables
+        .iter()
+        .find(|t| !manifest.tables.iter().any(|e| e.matches(t)))
+    {
+        bail!("Table '{}' is not in the directory dump", missing);
+    }
+    let entries: Vec<&TableEntry> = manifest
+        .tables
+        .iter()
+        .filter(|e| tables.is_empty() || tables.iter().any(|t| e.matches(t)))
+        .collect();
+    println!(
+        "Restoring {} of {} table(s) from a {} directory dump",
+        entries.len(),
+        manifest.tables.len(),
+        manifest.dialect
+    );
+    verify_data_files(dir, &entries, decryption)?;
+    let entries = order_by_references(dir, entries, decryption)?;
+
+    // Views, events and grants may name any table, so they only come with
+    // all of them
+    let whole = tables.is_empty();
+    if (manifest.views.is_some() || manifest.events.is_some() || manifest.grants.is_some())
+        && !whole
+    {
+        println!("Skipping views, events and grants, as --tables restores only part of the dump");
+    }
+    let drop_views = manifest.drop_views.as_ref().filter(|_| whole);
+    let create_views = manifest.views.as_ref().filter(|_| whole);
+    let events = manifest.events.as_ref().filter(|_| whole);
+    let grants = manifest.grants.as_ref().filter(|_| whole);
+
+    let schema = entries.iter().filter_map(|e| e.schema.as_ref());
+    let data = entries.iter().filter_map(|e| e.data.as_ref());
+    let post_data = entries.iter().filter_map(|e| e.post_data.as_ref());
+    Ok(Box::new(FilesReader {
+        files: manifest
+            .users
+            .iter()
+            .chain(&manifest.schemas)
+            .chain(&manifest.extensions)
+            .chain(drop_views)
+            .chain(&manifest.routines)
+            .chain(schema)
+            .chain(data)
+            .chain(post_data)
+            .chain(create_views)
+            .chain(events)
+            .chain(grants)
+            .map(|file| dir.join(file))
+            .collect(),
+        decryption: decryption.cloned(),
+        current: None,
+    }))
+}
+
+/// Order tables so that each is created and loaded after the tables its
+/// foreign keys reference, as found in its schema file. References to
+/// tables outside the dump are ignored, and a cycle is cut where it closes.
+fn order_by_references<'a>(
+    dir: &Path,
+    entries: Vec<&'a TableEntry>,
+    decryption: Option<&Decryption>,
+) -> Result<Vec<&'a TableEntry>> {
+    let keys: Vec<Option<String>> = entries.iter().map(|e| table_key(&e.name)).collect();
+    let mut references = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        let mut sql = String::new();
+        if let Some(file) = &entry.schema {
+            let path = dir.join(file);
+            let path = path.to_string_lossy();
+            let mut reader = Codec::from_path(&path)
+                .open(&path, decryption)
+                .with_context(|| format!("Failed to open '{}'", path))?;
+            reader
+                .read_to_string(&mut sql)
+                .with_context(|| format!("Failed to read '{}'", path))?;
+        }
+        references.push(referenced_tables(&sql));
+    }
+    let depends = |i: usize, j: usize| {
+        i != j
+            && keys[j]
+                .as_ref()
+                .is_some_and(|key| references[i].contains(key))
+    };
+
+    // Depth-first, placing a table after everything it references
+    fn visit(
+        i: usize,
+        count: usize,
+        depends: &dyn Fn(usize, usize) -> bool,
+        state: &mut [u8],
+        order: &mut Vec<usize>,
+    ) {
+        if state[i] != 0 {
+            return;
+        }
+        state[i] = 1;
+        for j in 0..count {
+            if depends(i, j) {
+                visit(j, count, depends, state, order);
+            }
+        }
+        state[i] = 2;
+        order.push(i);
+    }
+
+    let mut state = vec![0u8; entries.len()];
+    let mut order = Vec::with_capacity(entries.len());
+    for i in 0..entries.len() {
+        visit(i, entries.len(), &depends, &mut state, &mut order);
+    }
+    if order.iter().enumerate().any(|(at, i)| at != *i) {
+        println!("Ordering tables after the tables their foreign keys reference");
+    }
+    Ok(order.into_iter().map(|i| entries[i]).collect())
+}
+
+/// Check each data file against the size and checksum in the manifest, so a
+/// damaged dump fails before any of it is run
+fn verify_data_files(
+    dir: &Path,
+    entries: &[&TableEntry],
+    decryption: Option<&Decryption>,
+) -> Result<()> {
+    let files: Vec<_> = entries
+        .iter()
+        .filter_map(|e| Some((e.data.as_ref()?, e.section.as_ref()?)))
+        .collect();
+    if files.is_empty() {
+        return Ok(());
+    }
+    println!("Verifying {} data file(s)...", files.len());
+
+    let mut rows = 0u64;
+    for (file, expected) in files {
+        let path = dir.join(file);
+        let path = path.to_string_lossy();
+        let mut reader = Codec::from_path(&path)
+            .open(&path, decryption)
+            .with_context(|| format!("Failed to open '{}'", path))?;
+        let mut writer = SectionWriter::new(io::sink());
+        io::copy(&mut reader, &mut writer).with_context(|| format!("Failed to read '{}'", path))?;
+        let (_, found) = writer.finish(expected.rows);
+        if found != *expected {
+            bail!(
+                "'{}' does not match the manifest ({} bytes, sha256 {}; expected {} bytes, sha256 {}); the dump is damaged",
+                path,
+                found.bytes,
+                found.sha256,
+                expected.bytes,
+                expected.sha256
+            );
+        }
+        rows += expected.rows;
+    }
+    println!("Data files match the manifest ({} rows)", rows);
+    Ok(())
+}
+
+/// Reads a list of dump files one after another, decompressing each by its
+/// extension. Files are opened only when reached.
+struct FilesReader {
+    files: VecDeque<PathBuf>,
+    decryption: Option<Decryption>,
+    current: Option<Box<dyn Read>>,
+}
+
+impl Read for FilesReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(reader) = &mut self.current {
+                match reader.read(buf)? {
+                    0 => self.current = None,
+                    n => return Ok(n),
+                }
+            }
+
+            let Some(path) = self.files.pop_front() else {
+                return Ok(0);
+            };
+            let path = path.to_string_lossy();
+            let codec = Codec::from_path(&path);
+            let reader = codec.open(&path, self.decryption.as_ref()).map_err(|e| {
+                io::Error::new(e.kind(), format!("Failed to open '{}': {}", path, e))
+            })?;
+            self.current = Some(reader);
+        }
+    }
+}
+
+/// Run a binary dump: its SQL as in a file dump, and each frame of rows
+/// straight through `insert_batch`, with no SQL to build or parse. Returns
+/// the number of statements run.
+async fn restore_binary<R: BufRead>(
+    session: &mut dyn DbSession,
+    input: R,
+    opts: &RestoreOptions,
+    filter: &mut Option<StatementFilter>,
+    errors: Option<&ErrorLog>,
+    mut checkpoints: Option<&mut Checkpoints>,
+) -> Result<u64> {
+    if opts.jobs > 1 {
+        println!("Note: binary dumps are restored sequentially");
+    }
+    session.set_insert_mode(opts.insert_mode)?;
+    let strip_definer = opts.skip_definer.then(|| session.dialect());
+    let remap = remapping(opts, session.dialect());
+
+    if opts.on_conflict != OnConflict::Error {
+        println!("Note: --on-conflict applies to INSERT statements, not the rows of binary dumps");
+    }
+
+    println!("Executing SQL statements and loading rows...");
+    let mut reader = binary::Reader::new(input)?;
+    let mut batches = Batches::new(opts.commit_every);
+    let mut inserts = InsertRewriter::new(opts.on_conflict);
+    let mut count = 0u64;
+    let mut rows = 0u64;
+    while let Some(segment) = reader.next_segment()? {
+        match segment {
+            Segment::Sql(sql) => {
+                let mut statements = StatementReader::new(sql.as_slice(), session.dialect());
+                statements.strip_definer = strip_definer;
+                statements.filter = filter.take();
+                statements.remap = remap.clone();
+                statements.no_drop = opts.no_drop;
+                while let Some(mut item) = statements.next_item()? {
+                    if let Some(checkpoints) = checkpoints.as_deref_mut() {
+                        if checkpoints.skip(session, &item).await? {
+                            continue;
+                        }
+                    }
+                    inserts.apply(session, &mut item).await?;
+                    batches.begin(session).await?;
+                    execute_item(session, &item, errors).await?;
+                    if item.is_complete() {
+                        batches.finished(session).await?;
+                        count += 1;
+                        report_progress(count)?;
+                    }
+                    if let Some(checkpoints) = checkpoints.as_deref_mut() {
+                        checkpoints.committed(&batches)?;
+                    }
+                }
+                *filter = statements.filter.take();
+            }
+            Segment::Rows {
+                table,
+                columns,
+                rows: batch,
+            } => {
+                if filter.as_mut().is_some_and(|f| !f.keeps_rows(&table)) {
+                    continue;
+                }
+                let table = match &remap {
+                    Some((remap, _)) => remap.apply_to_table(&table),
+                    None => table,
+                };
+                if checkpoints
+                    .as_deref_mut()
+                    .is_some_and(|c| c.restored_before())
+                {
+                    continue;
+                }
+                batches.begin(session).await?;
+                let loaded = session.insert_batch(&table, &columns, &batch).await;
+                match (loaded, errors) {
+                    (Err(e), Some(errors)) => errors.record_rows(&table, batch.len(), &e)?,
+                    (loaded, _) => {
+                        loaded.with_context(|| {
+                            format!("Failed to load rows into table '{}'", table)
+                        })?;
+                        rows += batch.len() as u64;
+                    }
+                }
+                batches.finished(session).await?;
+                if let Some(checkpoints) = checkpoints.as_deref_mut() {
+                    checkpoints.committed(&batches)?;
+                }
+            }
+        }
+    }
+    println!("\rLoaded {} rows", rows);
+    Ok(count)
+}
+
+/// Apply the restore's session settings. Only the first session reports them.
+async fn prepare_session(
+    session: &mut dyn DbSession,
+    opts: &RestoreOptions,
+    report: bool,
+) -> Result<()> {
+    // Disable constraints if requested
+    if opts.disable_fk_checks {
+        if report {
+            println!("Disabling foreign key checks...");
+        }
+        session.disable_constraints().await?;
+    }
+
+    // Names the dump leaves unqualified, such as the sequences in column
+    // defaults, were in `public`
+    let dialect = session.dialect();
+    if let Some(schema) = opts.remap.schema("public") {
+        if dialect.name() == "PostgreSQL" {
+            let path = format!("SET search_path TO {}", dialect.quote_identifier(schema));
+            session.execute(&path).await?;
+        }
+    }
+
+    if opts.fast_load {
+        let enabled = session.enable_fast_load().await?;
+        if report && enabled {
+            println!("Fast load: replication role replica, synchronous_commit off");
+        } else if report {
+            println!(
+                "Note: --fast-load has no effect on {}",
+                session.dialect().name()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// `--remap-schema` and `--remap-table`, with the dialect that quotes the
+/// new names, when any were given
+fn remapping(
+    opts: &RestoreOptions,
+    dialect: &'static dyn SqlDialect,
+) -> Option<(Remap, &'static dyn SqlDialect)> {
+    (!opts.remap.is_empty()).then(|| (opts.remap.clone(), dialect))
+}
+
+fn report_progress(count: u64) -> Result<()> {
+    if count.is_multiple_of(100) {
+        print!("\rExecuted {} statements...", count);
+        std::io::stdout().flush()?;
+    }
+    Ok(())
+}
+
+/// One unit of work read from the input
+#[derive(Clone)]
+enum Item {
+    /// A complete SQL statement and the line it ends on
+    Statement { sql: String, line: usize },
+    /// A piece of the rows of a `COPY ... FROM stdin`; `end` marks the last
+    CopyData {
+        statement: String,
+        data: Vec<u8>,
+        line: usize,
+        end: bool,
+    },
+}
+
+impl Item {
+    /// Whether this finishes a statement, for counting
+    fn is_complete(&self) -> bool {
+        match self {
+            Item::Statement { .. } => true,
+            Item::CopyData { end, .. } => *end,
+        }
+    }
+
+    fn sql(&self) -> &str {
+        match self {
+            Item::Statement { sql, .. } => sql,
+            Item::CopyData { statement, .. } => statement,
+        }
+    }
+
+    /// Line of the input this ends on
+    fn line(&self) -> usize {
+        match self {
+            Item::Statement { line, .. } | Item::CopyData { line, .. } => *line,
+        }
+    }
+}
+
+/// Run `item`; under `--skip-errors`, a failure is logged to `errors`
+/// rather than returned
+async fn execute_item(
+    session: &mut dyn DbSession,
+    item: &Item,
+    errors: Option<&ErrorLog>,
+) -> Result<()> {
+    let result = match item {
+        Item::Statement { sql, .. } => session.execute(sql).await,
+        Item::CopyData {
+            statement, data, ..
+        } => session.copy_in(statement, data).await,
+    };
+    match (result, errors) {
+        (Err(e), Some(errors)) => errors.record(item, &e),
+        (result, _) => result.with_context(|| match item {
+            Item::Statement { sql, line } => {
+                format!("Failed to execute statement at line {}: {}", line, sql)
+            }
+            Item::CopyData {
+                statement, line, ..
+            } => format!("Failed to copy data ending at line {}: {}", line, statement),
+        }),
+    }
+}
+
+/// Statements that failed under `--skip-errors`. Each is written to a file
+/// as it fails, after a comment giving its line and the error, so the file
+/// can be fixed up and run again; the failures are counted by table.
+struct ErrorLog {
+    path: PathBuf,
+    state: std::sync::Mutex<ErrorLogState>,
+}
+
+struct ErrorLogState {
+    file: BufWriter<File>,
+    /// Failed statements by table, or under "(no table)"
+    failed: BTreeMap<String, u64>,
+}
+
+impl ErrorLog {
+    fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create errors file '{}'", path.display()))?;
+        Ok(ErrorLog {
+            path: path.to_path_buf(),
+            state: std::sync::Mutex::new(ErrorLogState {
+                file: BufWriter::new(file),
+                failed: BTreeMap::new(),
+            }),
+        })
+    }
+
+    fn record(&self, item: &Item, error: &anyhow::Error) -> Result<()> {
+        let (line, text) = match item {
+            Item::Statement { sql, line } => (line, format!("{}\n", sql)),
+            Item::CopyData {
+                statement,
+                data,
+                line,
+                ..
+            } => {
+                let data = String::from_utf8_lossy(data);
+                (line, format!("{};\n{}\\.\n", statement, data))
+            }
+        };
+        let entry = format!("-- Line {}: {}\n{}", line, one_line(error), text);
+        self.write(statement_table_name(item.sql()), &entry)
+    }
+
+    fn record_rows(&self, table: &str, rows: usize, error: &anyhow::Error) -> Result<()> {
+        let entry = format!("-- {} rows for {}: {}\n", rows, table, one_line(error));
+        self.write(Some(table.to_string()), &entry)
+    }
+
+    fn write(&self, table: Option<String>, entry: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let table = table.unwrap_or_else(|| "(no table)".to_string());
+        *state.failed.entry(table).or_default() += 1;
+        state
+            .file
+            .write_all(entry.as_bytes())
+            .and_then(|()| state.file.flush())
+            .with_context(|| format!("Failed to write '{}'", self.path.display()))
+    }
+
+    /// Print the failures by table
+    fn report(&self) {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let total: u64 = state.failed.values().sum();
+        if total == 0 {
+            println!("No statements failed");
+            return;
+        }
+        println!(
+            "{} statements failed and were skipped; they are in '{}'",
+            total,
+            self.path.display()
+        );
+        for (table, count) in &state.failed {
+            println!("  {}: {}", table, count);
+        }
+    }
+}
+
+/// Groups the statements run over one session into transactions of
+/// `--commit-every` statements
+struct Batches {
+    /// Statements per transaction; `None` leaves each to commit on its own
+    every: Option<u64>,
+    /// Whether a transaction is open, and how many statements it has run
+    open: bool,
+    statements: u64,
+}
+
+impl Batches {
+    fn new(every: Option<u64>) -> Self {
+        Batches {
+            every,
+            open: false,
+            statements: 0,
+        }
+    }
+
+    /// Open a transaction for the next statement, unless one is open
+    async fn begin(&mut self, session: &mut dyn DbSession) -> Result<()> {
+        if self.every.is_some() && !self.open {
+            self.open = session.begin().await?;
+        }
+        Ok(())
+    }
+
+    /// Count a finished statement, committing once the transaction is full
+    async fn finished(&mut self, session: &mut dyn DbSession) -> Result<()> {
+        self.statements += 1;
+        if self.every.is_some_and(|every| self.statements >= every) {
+            self.commit(session).await?;
+        }
+        Ok(())
+    }
+
+    /// Commit the open transaction, if any
+    async fn commit(&mut self, session: &mut dyn DbSession) -> Result<()> {
+        if self.open {
+            session.commit().await?;
+            self.open = false;
+        }
+        self.statements = 0;
+        Ok(())
+    }
+}
+
+/// Rewrites `INSERT INTO` statements for `--on-conflict`, in the form the
+/// destination's dialect handles rows already there with
+struct InsertRewriter {
+    on_conflict: OnConflict,
+    /// Keywords and clause for each table and column list, as written
+    clauses: HashMap<String, (&'static str, String)>,
+}
+
+impl InsertRewriter {
+    fn new(on_conflict: OnConflict) -> Self {
+        InsertRewriter {
+            on_conflict,
+            clauses: HashMap::new(),
+        }
+    }
+
+    /// Rewrite `item` if it is a plain INSERT. Dialects that name the
+    /// conflict or the columns to update look up the table's primary key,
+    /// and its columns when the INSERT does not list them.
+    async fn apply(&mut self, session: &mut dyn DbSession, item: &mut Item) -> Result<()> {
+        if self.on_conflict == OnConflict::Error {
+            return Ok(());
+        }
+        let Item::Statement { sql, .. } = item else {
+            return Ok(());
+        };
+        let Some((target, rest)) = split_insert(sql, session.dialect().syntax()) else {
+            return Ok(());
+        };
+
+        if !self.clauses.contains_key(target) {
+            let parts = name_parts(target);
+            let table = parts.join(".");
+            let key = session.primary_key(&table).await?;
+            let listed = target[target.find('(').unwrap_or(target.len())..]
+                .trim_start_matches('(')
+                .trim_end_matches(')');
+            let mut columns: Vec<String> = listed
+                .split(',')
+                .filter_map(|column| name_parts(column).pop())
+                .filter(|column| !column.is_empty())
+                .collect();
+            if columns.is_empty() {
+                columns = session
+                    .describe_columns(&table)
+                    .await?
+                    .into_iter()
+                    .map(|c| c.name)
+                    .collect();
+            }
+            let clauses =
+                conflict_clauses_for(session.dialect(), &table, self.on_conflict, &columns, &key)?;
+            self.clauses.insert(target.to_string(), clauses);
+        }
+
+        let (keywords, clause) = &self.clauses[target];
+        let rest = rest.trim_end().trim_end_matches(';').trim_end();
+        *sql = format!("{} {} {}{};", keywords, target, rest, clause);
+        Ok(())
+    }
+}
+
+/// A plain `INSERT INTO` split into the table with any column list, and
+/// the rest from `VALUES` or `SELECT`, read by the rules of `syntax`.
+/// INSERTs that already handle conflicts, with `ON CONFLICT` or
+/// `ON DUPLICATE KEY` outside any parentheses, are left alone.
+fn split_insert(sql: &str, syntax: Syntax) -> Option<(&str, &str)> {
+    let sql = sql.trim_start();
+    let body = sql
+        .get(..12)
+        .filter(|head| head.eq_ignore_ascii_case("INSERT INTO "))
+        .map(|_| &sql[12..])?;
+
+    let mut split = None;
+    let mut depth = 0usize;
+    let mut bracket = false;
+    // Words outside parentheses after the split, upper-cased
+    let mut words: Vec<String> = Vec::new();
+    let mut at = 0;
+    for token in tokenize(body, syntax) {
+        let start = at;
+        at += token.text.len();
+        match (token.kind, token.text) {
+            (TokenKind::Space | TokenKind::LineComment | TokenKind::BlockComment, _) => continue,
+            // SQL Server's `[name]`
+            (TokenKind::Punct, "[") if split.is_none() => bracket = true,
+            (TokenKind::Punct, "]") if bracket => bracket = false,
+            _ if bracket => {}
+            (TokenKind::Punct, "(") => {
+                if split.is_some() && depth == 0 {
+                    words.push("(".to_string());
+                }
+                depth += 1;
+            }
+            (TokenKind::Punct, ")") => depth = depth.saturating_sub(1),
+            (TokenKind::Str, _) if split.is_none() => return None,
+            (TokenKind::Word, word)
+                if split.is_none()
+                    && depth == 0
+                    && (word.eq_ignore_ascii_case("VALUES")
+                        || word.eq_ignore_ascii_case("SELECT")) =>
+            {
+                split = Some(start);
+            }
+            _ if split.is_none() || depth > 0 => {}
+            (_, text) => words.push(text.to_ascii_uppercase()),
+        }
+    }
+
+    let handled = words.windows(3).any(|w| match w {
+        [on, conflict, next] if on == "ON" && conflict == "CONFLICT" => {
+            matches!(next.as_str(), "(" | "DO" | "ON")
+        }
+        [on, duplicate, key] => on == "ON" && duplicate == "DUPLICATE" && key == "KEY",
+        _ => false,
+    });
+    let split = split?;
+    (!handled).then(|| (body[..split].trim_end(), &body[split..]))
+}
+
+/// How far a sequential restore has got, noted in `--state-file` whenever
+/// what has been read is all committed, so `--resume` can skip it
+struct Checkpoints {
+    file: RestoreStateFile,
+    /// Statements, pieces of COPY data and row batches read so far
+    items: u64,
+    /// How many of them a resumed restore had already committed
+    resume_after: u64,
+}
+
+impl Checkpoints {
+    fn start(input_path: &str, opts: &RestoreOptions) -> Result<Self> {
+        let previous = RestoreStateFile::open(&opts.state_file)?;
+        let resume_after = match (previous, opts.resume) {
+            (Some(file), true) => {
+                if file.state().input != input_path {
+                    bail!(
+                        "'{}' notes a restore of '{}', not this input",
+                        file.path().display(),
+                        file.state().input
+                    );
+                }
+                println!(
+                    "Resuming after the first {} statements and row batches, restored before",
+                    file.state().items
+                );
+                file.state().items
+            }
+            (None, true) => bail!(
+                "No restore to resume: '{}' does not exist",
+                opts.state_file.display()
+            ),
+            (Some(file), false) => {
+                println!(
+                    "Note: starting over; --resume would continue the restore noted in '{}'",
+                    file.path().display()
+                );
+                0
+            }
+            (None, false) => 0,
+        };
+
+        let file = RestoreStateFile::create(&opts.state_file, input_path, resume_after)?;
+        Ok(Checkpoints {
+            file,
+            items: 0,
+            resume_after,
+        })
+    }
+
+    /// Count `item`, and whether it was restored before the restore was
+    /// resumed. Skipped statements that set up the session still run.
+    async fn skip(&mut self, session: &mut dyn DbSession, item: &Item) -> Result<bool> {
+        if !self.restored_before() {
+            return Ok(false);
+        }
+        if let Item::Statement { sql, .. } = item {
+            if is_session_statement(sql) || is_use_statement(sql) {
+                session.execute(sql).await?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Count an item read from the input, and whether it was restored
+    /// before the restore was resumed
+    fn restored_before(&mut self) -> bool {
+        self.items += 1;
+        self.items <= self.resume_after
+    }
+
+    /// Note how far the restore has got, unless `batches` has a transaction
+    /// open
+    fn committed(&mut self, batches: &Batches) -> Result<()> {
+        match batches.open {
+            true => Ok(()),
+            false => self.file.record(self.items),
+        }
+    }
+}
+
+/// An error and its causes on one line, for a SQL comment. A cause that
+/// only repeats the message before it is left out.
+fn one_line(error: &anyhow::Error) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    for cause in error.chain() {
+        let text = cause.to_string();
+        if !parts.last().is_some_and(|last| last.contains(&text)) {
+            parts.push(text);
+        }
+    }
+    parts.join(": ").replace(['\r', '\n'], " ")
+}
+
+/// Splits the input into statements with a [`StatementSplitter`], which
+/// follows the quoting rules of the dialect restored into. psql
+/// meta-commands are skipped, `DELIMITER` lines change what ends a
+/// statement, and the rows after a `COPY ... FROM stdin` are passed on in
+/// chunks until `\.`.
+struct StatementReader<R> {
+    reader: R,
+    line: String,
+    line_count: usize,
+    splitter: StatementSplitter,
+    /// Statements completed by the last line read, when it held several
+    pending: VecDeque<Item>,
+    /// Open `COPY ... FROM stdin` statement and the data lines read for it
+    copy_statement: Option<String>,
+    copy_data: Vec<u8>,
+    /// Dialect that takes the definer out of each statement, with
+    /// `--skip-definer`
+    strip_definer: Option<&'static dyn SqlDialect>,
+    /// Statements passed on by `filter`, when an item gave several
+    filtered: VecDeque<Item>,
+    /// What `restore --tables` keeps of a single-file dump
+    filter: Option<StatementFilter>,
+    /// Names to rewrite in what `filter` keeps, and the dialect to quote
+    /// them for
+    remap: Option<(Remap, &'static dyn SqlDialect)>,
+    /// Leave out `DROP TABLE` and keep existing tables, with `--no-drop`
+    no_drop: bool,
+}
+
+impl<R: BufRead> StatementReader<R> {
+    fn new(reader: R, dialect: &dyn SqlDialect) -> Self {
+        StatementReader {
+            reader,
+            line: String::new(),
+            line_count: 0,
+            splitter: StatementSplitter::new(dialect.syntax()),
+            pending: VecDeque::new(),
+            copy_statement: None,
+            copy_data: Vec::new(),
+            strip_definer: None,
+            filtered: VecDeque::new(),
+            filter: None,
+            remap: None,
+            no_drop: false,
+        }
+    }
+
+    fn next_item(&mut self) -> Result<Option<Item>> {
+        let item = self.filtered_item()?;
+        let Some((remap, dialect)) = &self.remap else {
+            return Ok(item);
+        };
+        Ok(item.map(|item| match item {
+            Item::Statement { sql, line } => Item::Statement {
+                sql: remap.apply(&sql, *dialect),
+                line,
+            },
+            Item::CopyData {
+                statement,
+                data,
+                line,
+                end,
+            } => Item::CopyData {
+                statement: remap.apply(&statement, *dialect),
+                data,
+                line,
+                end,
+            },
+        }))
+    }
+
+    fn filtered_item(&mut self) -> Result<Option<Item>> {
+        loop {
+            if let Some(item) = self.filtered.pop_front() {
+                return Ok(Some(item));
+            }
+            let item = match (self.definer_stripped_item()?, self.no_drop) {
+                (Some(Item::Statement { sql, line }), true) => match without_drop(&sql) {
+                    Some(sql) => Some(Item::Statement { sql, line }),
+                    None => continue,
+                },
+                (item, _) => item,
+            };
+            match (item, &mut self.filter) {
+                (Some(item), Some(filter)) => self.filtered.extend(filter.apply(item)),
+                (item, _) => return Ok(item),
+            }
+        }
+    }
+
+    fn definer_stripped_item(&mut self) -> Result<Option<Item>> {
+        let item = self.read_item()?;
+        match (item, self.strip_definer) {
+            (Some(Item::Statement { sql, line }), Some(dialect)) => Ok(Some(Item::Statement {
+                sql: dialect.strip_definer(&sql),
+                line,
+            })),
+            (item, _) => Ok(item),
+        }
+    }
+
+    fn read_item(&mut self) -> Result<Option<Item>> {
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Ok(Some(item));
+            }
+
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                break;
+            }
+            self.line_count += 1;
+            let line = self.line.trim_end_matches(['\n', '\r']);
+
+            // Data lines of a COPY block go to the server verbatim until `\.`
+            if let Some(statement) = &self.copy_statement {
+                if line == "\\." {
+                    let item = Item::CopyData {
+                        statement: statement.clone(),
+                        data: std::mem::take(&mut self.copy_data),
+                        line: self.line_count,
+                        end: true,
+                    };
+                    self.copy_statement = None;
+                    return Ok(Some(item));
+                }
+
+                self.copy_data.extend_from_slice(line.as_bytes());
+                self.copy_data.push(b'\n');
+                if self.copy_data.len() >= COPY_CHUNK_BYTES {
+                    // Each chunk re-runs the same COPY, which appends to the table
+                    return Ok(Some(Item::CopyData {
+                        statement: statement.clone(),
+                        data: std::mem::take(&mut self.copy_data),
+                        line: self.line_count,
+                        end: false,
+                    }));
+                }
+                continue;
+            }
+
+            if self.splitter.is_idle() {
+                let trimmed = line.trim();
+                // Skip psql meta-commands (`\connect`, `\restrict`, ...) from pg_dump output
+                if trimmed.starts_with('\\') {
+                    continue;
+                }
+                // `DELIMITER ;;` (a mysql client command) lets statements such as
+                // triggers carry `;` in their body
+                if let Some(delimiter) = delimiter_command(trimmed) {
+                    self.splitter.set_delimiter(delimiter);
+                    continue;
+                }
+            }
+
+            for sql in self.splitter.push_line(&self.line) {
+                if is_copy_from_stdin(&sql) {
+                    // Rows follow on the next lines
+                    self.copy_statement = Some(sql.trim_end_matches(';').to_string());
+                    break;
+                }
+                self.pending.push_back(Item::Statement {
+                    sql,
+                    line: self.line_count,
+                });
+            }
+        }
+
+        if let Some(statement) = &self.copy_statement {
+            bail!("Input ended inside the data of: {}", statement);
+        }
+
+        // Any remaining statement
+        Ok(self.splitter.finish().map(|sql| Item::Statement {
+            sql,
+            line: self.line_count,
+        }))
+    }
+}
+
+/// The new delimiter of a `DELIMITER x` line. As in the mysql client, it is
+/// the first word after the command, or a quoted string; the rest of the
+/// line is ignored.
+fn delimiter_command(line: &str) -> Option<&str> {
+    let (command, rest) = line.split_once(char::is_whitespace)?;
+    if !command.eq_ignore_ascii_case("DELIMITER") {
+        return None;
+    }
+    let rest = rest.trim_start();
+    let delimiter = match rest.chars().next()? {
+        quote @ ('\'' | '"' | '`') => rest[1..].split(quote).next()?,
+        _ => rest.split(char::is_whitespace).next()?,
+    };
+    (!delimiter.is_empty()).then_some(delimiter)
+}
+
+/// Picks the statements a restore runs. `--schema-only` and `--data-only`
+/// keep the statements that define things or those that load rows; session
+/// settings, `USE` and transaction control go with either.
+///
+/// `--tables` on a single-file dump keeps the statements on the listed
+/// tables. As from a directory dump, the schemas, extensions, types and
+/// routines they may need are kept. Sequence statements wait for the next
+/// statement on a table and share its fate, as a dump creates a table's
+/// sequences just before or after it. Anything else, such as views and
+/// other tables, is skipped.
+struct StatementFilter {
+    /// `--tables` patterns; empty keeps every table
+    patterns: Vec<String>,
+    /// Which patterns have named a table in the dump
+    matched: Vec<bool>,
+    /// Whether to run the schema, and the data
+    schema: bool,
+    data: bool,
+    /// Sequence statements waiting for the table they belong to
+    held: Vec<Item>,
+    /// Statements left out
+    skipped: u64,
+}
+
+impl StatementFilter {
+    fn new(patterns: &[String], schema: bool, data: bool) -> Self {
+        StatementFilter {
+            patterns: patterns.to_vec(),
+            matched: vec![false; patterns.len()],
+            schema,
+            data,
+            held: Vec::new(),
+            skipped: 0,
+        }
+    }
+
+    /// Whether `--tables` names `table`
+    fn matches(&mut self, table: &str) -> bool {
+        let mut any = false;
+        for (pattern, matched) in self.patterns.iter().zip(&mut self.matched) {
+            if table_matches(pattern, table) {
+                *matched = true;
+                any = true;
+            }
+        }
+        any
+    }
+
+    /// Whether to load a binary dump's rows for `table`
+    fn keeps_rows(&mut self, table: &str) -> bool {
+        self.data && (self.patterns.is_empty() || self.matches(table))
+    }
+
+    /// The items to run for `item`: none, or it after any held ones
+    fn apply(&mut self, item: Item) -> Vec<Item> {
+        let sql = item.sql();
+        let data = is_data_statement(sql);
+        if !data
+            && (is_session_statement(sql) || is_use_statement(sql) || is_transaction_control(sql))
+        {
+            return vec![item];
+        }
+        let wanted = if data { self.data } else { self.schema };
+        if !wanted {
+            self.skip(&item);
+            return Vec::new();
+        }
+        if self.patterns.is_empty() || is_table_support(sql) {
+            return vec![item];
+        }
+        match statement_table_name(sql) {
+            Some(table) if self.matches(&table) => {
+                let mut items = std::mem::take(&mut self.held);
+                items.push(item);
+                items
+            }
+            Some(_) => {
+                self.finish();
+                self.skip(&item);
+                Vec::new()
+            }
+            None if is_sequence_statement(sql) => {
+                self.held.push(item);
+                Vec::new()
+            }
+            None => {
+                self.skip(&item);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Drop the held statements, which no table claimed
+    fn finish(&mut self) {
+        self.skipped += self.held.len() as u64;
+        self.held.clear();
+    }
+
+    fn skip(&mut self, item: &Item) {
+        if item.is_complete() {
+            self.skipped += 1;
+        }
+    }
+
+    fn report(&self) {
+        let mut options = Vec::new();
+        if !self.patterns.is_empty() {
+            options.push("--tables");
+        }
+        if !self.data {
+            options.push("--schema-only");
+        }
+        if !self.schema {
+            options.push("--data-only");
+        }
+        println!(
+            "Skipped {} statements left out by {}",
+            self.skipped,
+            options.join(" and ")
+        );
+        for (pattern, _) in self
+            .patterns
+            .iter()
+            .zip(&self.matched)
+            .filter(|(_, m)| !**m)
+        {
+            println!("Note: no table in the dump matches '{}'", pattern);
+        }
+    }
+}
+
+/// What `restore --dry-run` found in the input: statements and rows by
+/// table, and the statements using syntax the provider does not take
+struct DryRun {
+    /// Provider's dialect, when it is known without connecting
+    dialect: Option<&'static dyn SqlDialect>,
+    /// Statements and rows by table, with `(no table)` for the rest
+    tables: BTreeMap<String, (u64, u64)>,
+    statements: u64,
+    /// Statements matching each rule, and the line of the first
+    flagged: BTreeMap<&'static str, (u64, Option<usize>)>,
+    /// Statements matching any rule
+    unsupported: u64,
+}
+
+impl DryRun {
+    fn new(dialect: Option<&'static dyn SqlDialect>) -> Self {
+        DryRun {
+            dialect,
+            tables: BTreeMap::new(),
+            statements: 0,
+            flagged: BTreeMap::new(),
+            unsupported: 0,
+        }
+    }
+
+    /// Count `item` once it is complete, and check its syntax
+    fn statement(&mut self, item: &Item, line: Option<usize>) {
+        if !item.is_complete() {
+            return;
+        }
+        let table = statement_table_name(item.sql()).unwrap_or_else(|| "(no table)".to_string());
+        self.tables.entry(table).or_default().0 += 1;
+        self.statements += 1;
+
+        let Some(dialect) = self.dialect else {
+            return;
+        };
+        let code = statement_code(item.sql(), dialect.syntax()).to_ascii_uppercase();
+        let mut unsupported = false;
+        for rule in SYNTAX_RULES
+            .iter()
+            .filter(|r| !r.dialects.contains(&dialect.name()) && (r.found)(&code))
+        {
+            let (count, first) = self.flagged.entry(rule.name).or_default();
+            if *count == 0 {
+                *first = line;
+            }
+            *count += 1;
+            unsupported = true;
+        }
+        self.unsupported += unsupported as u64;
+    }
+
+    fn rows(&mut self, table: String, rows: usize) {
+        self.tables.entry(table).or_default().1 += rows as u64;
+    }
+
+    /// Print the counts and what was flagged; fails if anything was
+    fn report(&self) -> Result<()> {
+        println!("Read {} statements", self.statements);
+        for (table, (statements, rows)) in &self.tables {
+            match rows {
+                0 => println!("  {}: {}", table, statements),
+                rows => println!("  {}: {} statements, {} rows", table, statements, rows),
+            }
+        }
+
+        let Some(dialect) = self.dialect else {
+            return Ok(());
+        };
+        if self.unsupported == 0 {
+            println!("No syntax found that {} does not take", dialect.name());
+            return Ok(());
+        }
+        println!("Syntax {} does not take:", dialect.name());
+        for (name, (count, first)) in &self.flagged {
+            match first {
+                Some(line) => println!("  {}: {} statements, first at line {}", name, count, line),
+                None => println!("  {}: {} statements", name, count),
+            }
+        }
+        bail!(
+            "{} statements use syntax {} does not take",
+            self.unsupported,
+            dialect.name()
+        )
+    }
+}
+
+/// Syntax only some servers take, flagged by `restore --dry-run` in
+/// statements bound for any other
+struct SyntaxRule {
+    name: &'static str,
+    /// Names of the dialects that take it
+    dialects: &'static [&'static str],
+    /// Whether a statement's code, upper-cased, uses it
+    found: fn(&str) -> bool,
+}
+
+const SYNTAX_RULES: [SyntaxRule; 16] = [
+    SyntaxRule {
+        name: "backtick-quoted identifiers",
+        dialects: &["MySQL", "SQLite", "ClickHouse"],
+        found: |code| code.contains('`'),
+    },
+    SyntaxRule {
+        name: "ENGINE= table option",
+        dialects: &["MySQL", "ClickHouse"],
+        found: |code| code.contains(" ENGINE=") || code.contains(" ENGINE ="),
+    },
+    SyntaxRule {
+        name: "AUTO_INCREMENT",
+        dialects: &["MySQL"],
+        found: |code| code.contains("AUTO_INCREMENT"),
+    },
+    SyntaxRule {
+        name: "UNSIGNED columns",
+        dialects: &["MySQL", "SQLite"],
+        found: |code| code.contains(" UNSIGNED"),
+    },
+    SyntaxRule {
+        name: "LOCK TABLES",
+        dialects: &["MySQL"],
+        found: |code| code.starts_with("LOCK TABLES ") || code.starts_with("UNLOCK TABLES"),
+    },
+    SyntaxRule {
+        name: "INSERT IGNORE",
+        dialects: &["MySQL"],
+        found: |code| code.starts_with("INSERT IGNORE "),
+    },
+    SyntaxRule {
+        name: "REPLACE INTO",
+        dialects: &["MySQL", "SQLite"],
+        found: |code| code.starts_with("REPLACE INTO "),
+    },
+    SyntaxRule {
+        name: "COPY ... FROM stdin",
+        dialects: &["PostgreSQL"],
+        found: is_copy_from_stdin,
+    },
+    SyntaxRule {
+        name: ":: casts",
+        dialects: &[
+            "PostgreSQL",
+            "CockroachDB",
+            "Redshift",
+            "DuckDB",
+            "Snowflake",
+            "ClickHouse",
+        ],
+        found: |code| code.contains("::"),
+    },
+    SyntaxRule {
+        name: "dollar-quoted bodies",
+        dialects: &[
+            "PostgreSQL",
+            "CockroachDB",
+            "Redshift",
+            "DuckDB",
+            "Snowflake",
+        ],
+        found: |code| DOLLAR_QUOTE.is_match(code),
+    },
+    SyntaxRule {
+        name: "CREATE EXTENSION",
+        dialects: &["PostgreSQL", "CockroachDB"],
+        found: |code| code.starts_with("CREATE EXTENSION "),
+    },
+    SyntaxRule {
+        name: "pg_catalog references",
+        dialects: &["PostgreSQL", "CockroachDB", "Redshift", "DuckDB"],
+        found: |code| code.contains("PG_CATALOG."),
+    },
+    SyntaxRule {
+        name: "bracket-quoted identifiers",
+        dialects: &["SQL Server", "SQLite"],
+        found: |code| BRACKET_IDENTIFIER.is_match(code),
+    },
+    SyntaxRule {
+        name: "SET IDENTITY_INSERT",
+        dialects: &["SQL Server"],
+        found: |code| code.starts_with("SET IDENTITY_INSERT "),
+    },
+    SyntaxRule {
+        name: "PRAGMA statements",
+        dialects: &["SQLite", "DuckDB"],
+        found: |code| code.starts_with("PRAGMA "),
+    },
+    SyntaxRule {
+        // MySQL takes them as strings
+        name: "double-quoted identifiers",
+        dialects: &[
+            "PostgreSQL",
+            "CockroachDB",
+            "Redshift",
+            "DuckDB",
+            "Snowflake",
+            "ClickHouse",
+            "SQLite",
+            "SQL Server",
+            "Oracle",
+            "ANSI SQL",
+        ],
+        found: |code| code.contains('"'),
+    },
+];
+
+/// `$$` or `$tag$` opening a dollar-quoted body
+static DOLLAR_QUOTE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|[^A-Z0-9_$])\$[A-Z_]*\$").unwrap());
+
+/// `[name]` outside an expression, where it would index an array
+static BRACKET_IDENTIFIER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|[\s,.(])\[[A-Z_][A-Z0-9_ ]*\]").unwrap());
+
+/// `sql` with the text of string literals and quoted identifiers blanked
+/// out, leaving the quotes (and the tags of dollar-quoted bodies), and
+/// comments removed, so syntax checks only see its code. `syntax` says
+/// where strings and comments end.
+fn statement_code(sql: &str, syntax: Syntax) -> String {
+    let mut code = String::with_capacity(sql.len());
+    for token in tokenize(sql, syntax) {
+        let text = token.text;
+        match token.kind {
+            TokenKind::LineComment | TokenKind::BlockComment => code.push(' '),
+            TokenKind::Str | TokenKind::QuotedName => {
+                let quote = match text.strip_prefix('$') {
+                    Some(rest) => &text[..rest.find('$').map_or(text.len(), |at| at + 2)],
+                    None => &text[..1],
+                };
+                let closed = text.len() >= 2 * quote.len() && text.ends_with(quote);
+                let blank = text.len() - quote.len() * if closed { 2 } else { 1 };
+                code.push_str(quote);
+                code.extend(std::iter::repeat_n(' ', blank));
+                if closed {
+                    code.push_str(quote);
+                }
+            }
+            _ => code.push_str(text),
+        }
+    }
+    code
+}
+
+/// A run of consecutive statements on one table, restored by one worker
+struct TableJob {
+    items: mpsc::Receiver<Item>,
+    /// Jobs that must finish first: earlier work on the same table and on
+    /// the tables it references
+    after: Vec<watch::Receiver<bool>>,
+    done: watch::Sender<bool>,
+}
+
+/// Restore with `jobs` workers. Statements are grouped into runs on the same
+/// table; each run goes to one worker, which keeps its statements in order.
+/// Session settings read before the first table are replayed on every
+/// worker, later ones travel with the next table's run, and anything else
+/// waits for all running tables and executes on `session`. A `USE` in a
+/// dump of several databases starts every later run.
+async fn restore_parallel<R: BufRead>(
+    pool: &SessionPool<'_>,
+    session: &mut PooledSession<'_>,
+    statements: &mut StatementReader<R>,
+    jobs: usize,
+    opts: &RestoreOptions,
+    errors: Option<&ErrorLog>,
+) -> Result<u64> {
+    let (job_sender, job_receiver) = mpsc::channel::<TableJob>(1);
+    let job_receiver = Mutex::new(job_receiver);
+    let preamble = OnceLock::new();
+
+    let read = async {
+        let mut count = 0u64;
+        // Session settings waiting for the next table run
+        let mut pending: Vec<Item> = Vec::new();
+        let mut current: Option<(String, mpsc::Sender<Item>)> = None;
+        // Latest job for each table, and every job since the last barrier
+        let mut last_job: HashMap<String, watch::Receiver<bool>> = HashMap::new();
+        let mut running: Vec<watch::Receiver<bool>> = Vec::new();
+        // Tables each table's foreign keys reference, whose rows must be in
+        // before its own
+        let mut referenced: HashMap<String, Vec<String>> = HashMap::new();
+        // The last `USE`, for runs on workers that have not seen it
+        let mut database: Option<Item> = None;
+
+        while let Some(item) = statements.next_item()? {
+            let sql = item.sql();
+            let complete = item.is_complete();
+
+            if is_table_lock(sql) {
+                // Locks are per session and would block the other workers
+            } else if is_use_statement(sql) {
+                current = None;
+                execute_item(&mut **session, &item, errors).await?;
+                database = Some(item.clone());
+            } else if let Some(table) = statement_table(sql) {
+                let references = referenced_tables(sql);
+                let continues_run = references.is_empty()
+                    && current.as_ref().is_some_and(|(open, _)| *open == table);
+                if !references.is_empty() {
+                    referenced
+                        .entry(table.clone())
+                        .or_default()
+                        .extend(references.iter().cloned());
+                }
+
+                if !continues_run {
+                    if preamble.get().is_none() {
+                        let statements = pending.drain(..).map(|i| i.sql().to_string());
+                        let _ = preamble.set(statements.collect::<Vec<_>>());
+                    }
+
+                    let (items, receiver) = mpsc::channel(JOB_QUEUE_ITEMS);
+                    let (done, done_receiver) = watch::channel(false);
+                    let after = std::iter::once(&table)
+                        .chain(referenced.get(&table).into_iter().flatten())
+                        .filter_map(|t| last_job.get(t).cloned())
+                        .collect();
+
+                    last_job.insert(table.clone(), done_receiver.clone());
+                    running.push(done_receiver);
+                    job_sender
+                        .send(TableJob {
+                            items: receiver,
+                            after,
+                            done,
+                        })
+                        .await
+                        .map_err(|_| anyhow!("Restore workers stopped"))?;
+
+                    if let Some(database) = &database {
+                        items.send(database.clone()).await?;
+                    }
+                    for item in pending.drain(..) {
+                        items.send(item).await?;
+                    }
+                    current = Some((table, items));
+                }
+
+                if let Some((_, items)) = &current {
+                    items
+                        .send(item)
+                        .await
+                        .map_err(|_| anyhow!("Restore worker stopped"))?;
+                }
+            } else if is_session_statement(sql) {
+                if preamble.get().is_none() {
+                    execute_item(&mut **session, &item, errors).await?;
+                }
+                pending.push(item);
+            } else {
+                // Not tied to one table: let every table finish first
+                current = None;
+                for job in running.drain(..) {
+                    wait_for_job(job).await?;
+                }
+                for pending in pending.drain(..) {
+                    execute_item(&mut **session, &pending, errors).await?;
+                }
+                execute_item(&mut **session, &item, errors).await?;
+            }
+
+            if complete {
+                count += 1;
+                report_progress(count)?;
+            }
+        }
+
+        // Let the workers drain their queues and stop
+        drop(current);
+        drop(job_sender);
+        for job in running {
+            wait_for_job(job).await?;
+        }
+        for pending in pending {
+            execute_item(&mut **session, &pending, errors).await?;
+        }
+
+        anyhow::Ok(count)
+    };
+
+    let workers = (0..jobs).map(|_| restore_worker(pool, &job_receiver, &preamble, opts, errors));
+    let (count, _) = futures::try_join!(read, futures::future::try_join_all(workers))?;
+    Ok(count)
+}
+
+async fn wait_for_job(mut done: watch::Receiver<bool>) -> Result<()> {
+    done.wait_for(|done| *done)
+        .await
+        .map(|_| ())
+        .map_err(|_| anyhow!("A restore job failed"))
+}
+
+/// Take table jobs off the queue and run them over one session, opened when
+/// the first job arrives
+async fn restore_worker(
+    pool: &SessionPool<'_>,
+    jobs: &Mutex<mpsc::Receiver<TableJob>>,
+    preamble: &OnceLock<Vec<String>>,
+    opts: &RestoreOptions,
+    errors: Option<&ErrorLog>,
+) -> Result<()> {
+    let mut session: Option<PooledSession<'_>> = None;
+    let mut batches = Batches::new(opts.commit_every);
+    let mut inserts = InsertRewriter::new(opts.on_conflict);
+
+    loop {
+        let next = jobs.lock().await.recv().await;
+        let Some(mut job) = next else {
+            break;
+        };
+
+        for after in job.after.drain(..) {
+            wait_for_job(after).await?;
+        }
+
+        let session = match &mut session {
+            Some(session) => session,
+            None => {
+                let mut new_session = pool
+                    .get()
+                    .await
+                    .context("Failed to connect to destination database")?;
+                prepare_session(&mut *new_session, opts, false).await?;
+                for sql in preamble.get().into_iter().flatten() {
+                    new_session.execute(sql).await?;
+                }
+                session.insert(new_session)
+            }
+        };
+
+        while let Some(mut item) = job.items.recv().await {
+            inserts.apply(&mut **session, &mut item).await?;
+            batches.begin(&mut **session).await?;
+            execute_item(&mut **session, &item, errors).await?;
+            if item.is_complete() {
+                batches.finished(&mut **session).await?;
+            }
+        }
+        // Tables waiting on this one must see its rows
+        batches.commit(&mut **session).await?;
+        let _ = job.done.send(true);
+    }
+
+    if let Some(mut session) = session {
+        if opts.disable_fk_checks {
+            session.enable_constraints().await?;
+        }
+        session.commit().await?;
+    }
+    Ok(())
+}
+
+/// `COPY table (...) FROM stdin;` as written by pg_dump in plain format
+fn is_copy_from_stdin(statement: &str) -> bool {
+    let upper = statement.to_ascii_uppercase();
+    upper.starts_with("COPY ")
+        && upper
+            .trim_end_matches(';')
+            .trim_end()
+            .ends_with("FROM STDIN")
+}
+
+/// Unwrap a MySQL versioned comment (`/*!40000 ALTER TABLE ... */;`)
+fn strip_versioned_comment(sql: &str) -> &str {
+    let sql = sql.trim().trim_end_matches(';').trim_end();
+    match sql
+        .strip_prefix("/*!")
+        .and_then(|rest| rest.strip_suffix("*/"))
+    {
+        Some(inner) => inner
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .trim(),
+        None => sql,
+    }
+}
+
+/// Statement forms whose table follows directly
+const TABLE_PREFIXES: [&str; 19] = [
+    "INSERT INTO ",
+    "INSERT IGNORE INTO ",
+    "INSERT OR IGNORE INTO ",
+    "INSERT OR REPLACE INTO ",
+    "REPLACE INTO ",
+    "COPY ",
+    "CREATE TABLE IF NOT EXISTS ",
+    "CREATE TABLE ",
+    "CREATE UNLOGGED TABLE ",
+    "DROP TABLE IF EXISTS ",
+    "DROP TABLE ",
+    "ALTER TABLE IF EXISTS ONLY ",
+    "ALTER TABLE IF EXISTS ",
+    "ALTER TABLE ONLY ",
+    "ALTER TABLE ",
+    "SET IDENTITY_INSERT ",
+    "TRUNCATE TABLE ",
+    "TRUNCATE ",
+    "COMMENT ON TABLE ",
+];
+
+/// `SELECT setval(...)` on a table's serial sequence, as dumps write it
+const SERIAL_SETVAL: &str = "SELECT PG_CATALOG.SETVAL(PG_CATALOG.PG_GET_SERIAL_SEQUENCE('";
+
+/// The table a statement works on, as a key that matches however the name
+/// is quoted or qualified; `None` for statements not tied to one table
+fn statement_table(sql: &str) -> Option<String> {
+    statement_target(sql)?
+        .pop()
+        .filter(|name| !name.is_empty())
+        .map(|name| name.to_lowercase())
+}
+
+/// The table a statement works on, unquoted, with its schema if it has
+/// one, for matching `--tables`
+fn statement_table_name(sql: &str) -> Option<String> {
+    let parts = statement_target(sql)?;
+    (!parts.last()?.is_empty()).then(|| parts.join("."))
+}
+
+/// The parts of the name of the table a statement works on
+fn statement_target(sql: &str) -> Option<Vec<String>> {
+    let sql = strip_versioned_comment(sql);
+    let upper = sql.to_ascii_uppercase();
+
+    if let Some(prefix) = TABLE_PREFIXES.iter().find(|p| upper.starts_with(*p)) {
+        return Some(name_parts(&sql[prefix.len()..]));
+    }
+    if upper.starts_with(SERIAL_SETVAL) {
+        return Some(name_parts(&sql[SERIAL_SETVAL.len()..]));
+    }
+
+    // CREATE [UNIQUE] INDEX ... ON [ONLY] table
+    if upper.starts_with("CREATE INDEX ") || upper.starts_with("CREATE UNIQUE INDEX ") {
+        let on = upper.find(" ON ")? + " ON ".len();
+        let rest = &sql[on..];
+        let rest = match rest.get(..5) {
+            Some(only) if only.eq_ignore_ascii_case("ONLY ") => &rest[5..],
+            _ => rest,
+        };
+        return Some(name_parts(rest));
+    }
+
+    // CREATE TRIGGER name BEFORE INSERT ON table
+    if is_create_trigger(&upper) {
+        let trigger = upper.find("TRIGGER ")?;
+        let on = trigger + upper[trigger..].find(" ON ")? + " ON ".len();
+        return Some(name_parts(&sql[on..]));
+    }
+
+    // COMMENT ON COLUMN table.column, ALTER SEQUENCE s OWNED BY table.column
+    let column = if upper.starts_with("COMMENT ON COLUMN ") {
+        &sql["COMMENT ON COLUMN ".len()..]
+    } else if upper.starts_with("ALTER SEQUENCE ") {
+        &sql[upper.find(" OWNED BY ")? + " OWNED BY ".len()..]
+    } else {
+        return None;
+    };
+    let mut parts = name_parts(column);
+    parts.pop();
+    (!parts.is_empty()).then_some(parts)
+}
+
+/// `sql` as `--no-drop` runs it: `None` for a `DROP TABLE`, and a `CREATE
+/// TABLE` made to leave an existing table be
+fn without_drop(sql: &str) -> Option<String> {
+    let upper = strip_versioned_comment(sql).to_ascii_uppercase();
+    if object_statement(&upper) == Some(("DROP", "TABLE")) {
+        return None;
+    }
+    let start = sql.len() - sql.trim_start().len();
+    let upper = sql[start..].to_ascii_uppercase();
+    let create = ["CREATE TABLE ", "CREATE UNLOGGED TABLE "]
+        .into_iter()
+        .find(|prefix| upper.starts_with(prefix));
+    match create {
+        Some(prefix) if !upper[prefix.len()..].starts_with("IF NOT EXISTS ") => {
+            let at = start + prefix.len();
+            Some(format!("{}IF NOT EXISTS {}", &sql[..at], &sql[at..]))
+        }
+        _ => Some(sql.to_string()),
+    }
+}
+
+/// `CREATE [OR REPLACE] [DEFINER=...] TRIGGER`
+fn is_create_trigger(upper: &str) -> bool {
+    object_statement(upper) == Some(("CREATE", "TRIGGER"))
+}
+
+/// The verb and kind of object of a `CREATE`, `ALTER` or `DROP` statement
+/// in upper case, such as `("CREATE", "TRIGGER")` for `CREATE OR REPLACE
+/// DEFINER=... TRIGGER`, also as mysqldump spreads it over versioned
+/// comments
+fn object_statement(upper: &str) -> Option<(&str, &str)> {
+    let mut words = upper
+        .split_whitespace()
+        .map(|word| {
+            word.trim_start_matches("/*!")
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .trim_end_matches("*/")
+        })
+        .filter(|word| !word.is_empty());
+    let verb = words
+        .next()
+        .filter(|verb| matches!(*verb, "CREATE" | "ALTER" | "DROP"))?;
+    let kind =
+        words.find(|word| !matches!(*word, "OR" | "REPLACE") && !word.starts_with("DEFINER="))?;
+    Some((verb, kind))
+}
+
+/// Schemas, extensions, types and routines, which tables may need however
+/// few of them are restored
+fn is_table_support(sql: &str) -> bool {
+    let upper = strip_versioned_comment(sql).to_ascii_uppercase();
+    object_statement(&upper).is_some_and(|(_, kind)| {
+        matches!(
+            kind,
+            "SCHEMA" | "EXTENSION" | "TYPE" | "DOMAIN" | "FUNCTION" | "PROCEDURE"
+        )
+    })
+}
+
+/// Statements that load rows or follow them rather than define anything:
+/// inserts and `COPY`, sequence values, and what dumps wrap around rows.
+/// `SET GENERATED ALWAYS` counts too: dumps create identities `BY DEFAULT`
+/// and switch them once the rows, which it would refuse, are in.
+fn is_data_statement(sql: &str) -> bool {
+    let upper = strip_versioned_comment(sql).to_ascii_uppercase();
+    const DATA_PREFIXES: [&str; 11] = [
+        "INSERT ",
+        "REPLACE ",
+        "COPY ",
+        "TRUNCATE ",
+        "SET IDENTITY_INSERT ",
+        "LOCK TABLES ",
+        "UNLOCK TABLES",
+        "SELECT PG_CATALOG.SETVAL(",
+        "SELECT SETVAL(",
+        "DELETE FROM ",
+        "UPDATE ",
+    ];
+    DATA_PREFIXES.iter().any(|prefix| upper.starts_with(prefix))
+        // mysqldump's /*!40000 ALTER TABLE t DISABLE KEYS */ around rows
+        || (upper.starts_with("ALTER TABLE ")
+            && [" DISABLE KEYS", " ENABLE KEYS", " SET GENERATED ALWAYS"]
+                .iter()
+                .any(|suffix| upper.ends_with(suffix)))
+}
+
+/// `BEGIN`, `START TRANSACTION`, `COMMIT` or `ROLLBACK`
+fn is_transaction_control(sql: &str) -> bool {
+    let upper = sql
+        .trim()
+        .trim_end_matches(';')
+        .trim_end()
+        .to_ascii_uppercase();
+    matches!(
+        upper.as_str(),
+        "BEGIN" | "BEGIN TRANSACTION" | "START TRANSACTION" | "COMMIT" | "ROLLBACK"
+    )
+}
+
+/// `CREATE`, `ALTER` or `DROP SEQUENCE`
+fn is_sequence_statement(sql: &str) -> bool {
+    let upper = strip_versioned_comment(sql).to_ascii_uppercase();
+    ["CREATE SEQUENCE ", "ALTER SEQUENCE ", "DROP SEQUENCE "]
+        .iter()
+        .any(|prefix| upper.starts_with(prefix))
+}
+
+/// Tables named after `REFERENCES` (foreign keys) in a statement
+fn referenced_tables(sql: &str) -> Vec<String> {
+    let upper = sql.to_ascii_uppercase();
+    upper
+        .match_indices("REFERENCES ")
+        .filter_map(|(at, keyword)| table_key(&sql[at + keyword.len()..]))
+        .collect()
+}
+
+/// Key for the (possibly quoted, possibly schema-qualified) name at the
+/// start of `sql`: the unquoted, lowercased last part. Tables in different
+/// schemas can share a key, which only makes their jobs wait on each other.
+fn table_key(sql: &str) -> Option<String> {
+    name_parts(sql)
+        .pop()
+        .filter(|name| !name.is_empty())
+        .map(|name| name.to_lowercase())
+}
+
+/// The unquoted parts of the dotted name at the start of `sql`
+fn name_parts(sql: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut chars = sql.trim_start().chars().peekable();
+
+    loop {
+        let mut part = String::new();
+        match chars.peek().copied() {
+            Some(open @ ('`' | '"' | '[')) => {
+                let close = if open == '[' { ']' } else { open };
+                chars.next();
+                while let Some(c) = chars.next() {
+                    if c == close {
+                        // A doubled quote is an escaped one
+                        if close != ']' && chars.peek() == Some(&close) {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    part.push(c);
+                }
+            }
+            _ => {
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ';' | ',' | '.' | '\'') {
+                        break;
+                    }
+                    part.push(c);
+                    chars.next();
+                }
+            }
+        }
+        parts.push(part);
+
+        if chars.peek() == Some(&'.') {
+            chars.next();
+        } else {
+            break;
+        }
+    }
+    parts
+}
+
+/// Per-session settings (`SET ...`, `PRAGMA ...`) that every connection
+/// running later statements needs
+fn is_session_statement(sql: &str) -> bool {
+    let upper = strip_versioned_comment(sql).to_ascii_uppercase();
+    upper.starts_with("SET ")
+        || upper.starts_with("PRAGMA ")
+        || upper.starts_with("RESET ")
+        || upper.starts_with("SELECT PG_CATALOG.SET_CONFIG(")
+}
+
+/// `USE` switching databases in a dump of several
+fn is_use_statement(sql: &str) -> bool {
+    sql.trim_start().to_ascii_uppercase().starts_with("USE ")
+}
+
+/// `LOCK TABLES` / `UNLOCK TABLES` from mysqldump
+fn is_table_lock(sql: &str) -> bool {
+    let upper = sql.trim_start().to_ascii_uppercase();
+    upper.starts_with("LOCK TABLES ") || upper.starts_with("UNLOCK TABLES")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::dialects::mysql::MYSQL_DIALECT;
+    use crate::util::dialects::postgres::POSTGRES_DIALECT;
+    use crate::util::dialects::sqlite::SQLITE_DIALECT;
+
+    #[test]
+    fn finds_the_table_of_a_statement() {
+        let table = |sql| statement_table(sql);
+        assert_eq!(
+            table("INSERT INTO `orders` VALUES (1);"),
+            Some("orders".into())
+        );
+        assert_eq!(
+            table("INSERT IGNORE INTO `orders` VALUES (1);"),
+            Some("orders".into())
+        );
+        assert_eq!(
+            table("COPY public.\"Orders\" (id, note) FROM stdin"),
+            Some("Orders".to_lowercase())
+        );
+        assert_eq!(
+            table("/*!40000 ALTER TABLE `orders` DISABLE KEYS */;"),
+            Some("orders".into())
+        );
+        assert_eq!(
+            table("CREATE INDEX idx ON ONLY public.orders USING btree (id);"),
+            Some("orders".into())
+        );
+        assert_eq!(
+            table("SET IDENTITY_INSERT [dbo].[orders] ON;"),
+            Some("orders".into())
+        );
+        assert_eq!(table("SELECT pg_catalog.setval('s', 1, true);"), None);
+        assert_eq!(
+            table("/*!50003 CREATE*/ /*!50017 DEFINER=`root`@`%`*/ /*!50003 TRIGGER `t` BEFORE INSERT ON `orders` FOR EACH ROW SET NEW.x = 1 */;"),
+            Some("orders".into())
+        );
+        assert_eq!(
+            statement_table_name("ALTER SEQUENCE public.orders_id_seq OWNED BY public.orders.id;"),
+            Some("public.orders".into())
+        );
+        assert_eq!(
+            statement_table_name("SELECT pg_catalog.setval(pg_catalog.pg_get_serial_sequence('\"billing\".\"Orders\"', 'id'), 5, true);"),
+            Some("billing.Orders".into())
+        );
+
+        assert_eq!(
+            referenced_tables("ALTER TABLE ONLY public.a ADD CONSTRAINT fk FOREIGN KEY (b_id) REFERENCES public.b(id);"),
+            vec!["b".to_string()]
+        );
+        assert!(is_session_statement("/*!40101 SET NAMES utf8mb4 */;"));
+        assert!(!is_session_statement("CREATE SEQUENCE s;"));
+    }
+
+    #[test]
+    fn keeps_only_statements_on_the_listed_tables() {
+        let input = "SET client_encoding = 'UTF8';\nCREATE FUNCTION f() RETURNS int AS $$select 1$$ LANGUAGE sql;\nDROP TABLE IF EXISTS \"public\".\"orders\";\nCREATE SEQUENCE IF NOT EXISTS \"public\".\"orders_id_seq\";\nCREATE TABLE \"public\".\"orders\" (id int);\nCREATE SEQUENCE IF NOT EXISTS \"public\".\"users_id_seq\";\nCREATE TABLE \"public\".\"users\" (id int);\nCOPY public.users (id) FROM stdin;\n1\n\\.\nCOPY public.orders (id) FROM stdin;\n2\n\\.\nCREATE VIEW v AS SELECT * FROM orders;";
+        let mut reader = StatementReader::new(input.as_bytes(), &POSTGRES_DIALECT);
+        let patterns = ["Orders".to_string(), "audit".to_string()];
+        reader.filter = Some(StatementFilter::new(&patterns, true, true));
+
+        let mut items = Vec::new();
+        while let Some(item) = reader.next_item().unwrap() {
+            items.push(item.sql().to_string());
+        }
+
+        assert_eq!(
+            items,
+            [
+                "SET client_encoding = 'UTF8';",
+                "CREATE FUNCTION f() RETURNS int AS $$select 1$$ LANGUAGE sql;",
+                "DROP TABLE IF EXISTS \"public\".\"orders\";",
+                "CREATE SEQUENCE IF NOT EXISTS \"public\".\"orders_id_seq\";",
+                "CREATE TABLE \"public\".\"orders\" (id int);",
+                "COPY public.orders (id) FROM stdin",
+            ]
+        );
+        let filter = reader.filter.unwrap();
+        assert_eq!(filter.skipped, 4);
+        assert_eq!(filter.matched, [true, false]);
+    }
+
+    #[test]
+    fn keeps_the_schema_or_the_data() {
+        let input = "/*!40101 SET NAMES utf8mb4 */;\nDROP TABLE IF EXISTS `t`;\nCREATE TABLE `t` (id int);\nLOCK TABLES `t` WRITE;\n/*!40000 ALTER TABLE `t` DISABLE KEYS */;\nINSERT INTO `t` VALUES (1);\n/*!40000 ALTER TABLE `t` ENABLE KEYS */;\nUNLOCK TABLES;\nCREATE INDEX i ON t (id);\nCOMMIT;";
+        let run = |schema, data| {
+            let mut reader = StatementReader::new(input.as_bytes(), &MYSQL_DIALECT);
+            reader.filter = Some(StatementFilter::new(&[], schema, data));
+            let mut items = Vec::new();
+            while let Some(item) = reader.next_item().unwrap() {
+                items.push(item.sql().to_string());
+            }
+            items
+        };
+
+        assert_eq!(
+            run(true, false),
+            [
+                "/*!40101 SET NAMES utf8mb4 */;",
+                "DROP TABLE IF EXISTS `t`;",
+                "CREATE TABLE `t` (id int);",
+                "CREATE INDEX i ON t (id);",
+                "COMMIT;",
+            ]
+        );
+        assert_eq!(
+            run(false, true),
+            [
+                "/*!40101 SET NAMES utf8mb4 */;",
+                "LOCK TABLES `t` WRITE;",
+                "/*!40000 ALTER TABLE `t` DISABLE KEYS */;",
+                "INSERT INTO `t` VALUES (1);",
+                "/*!40000 ALTER TABLE `t` ENABLE KEYS */;",
+                "UNLOCK TABLES;",
+                "COMMIT;",
+            ]
+        );
+    }
+
+    #[test]
+    fn flags_syntax_the_provider_does_not_take() {
+        let input = "/*!40101 SET NAMES utf8mb4 */;\nCREATE TABLE `t` (\n  id int unsigned NOT NULL AUTO_INCREMENT\n) ENGINE=InnoDB;\nINSERT INTO `t` VALUES (1);\nINSERT INTO t VALUES ('a::b', '[x]', 'it''s `q`');\nCREATE VIEW v AS SELECT 1;";
+        let check = |dialect: &'static dyn SqlDialect| {
+            let mut check = DryRun::new(Some(dialect));
+            let mut reader = StatementReader::new(input.as_bytes(), dialect);
+            while let Some(item) = reader.next_item().unwrap() {
+                check.statement(&item, Some(item.line()));
+            }
+            check
+        };
+
+        let mysql = check(&MYSQL_DIALECT);
+        assert_eq!(mysql.statements, 5);
+        assert_eq!(mysql.tables["t"], (3, 0));
+        assert_eq!(mysql.tables["(no table)"], (2, 0));
+        assert!(mysql.flagged.is_empty());
+        assert!(mysql.report().is_ok());
+
+        let postgres = check(&POSTGRES_DIALECT);
+        assert_eq!(postgres.unsupported, 2);
+        assert_eq!(
+            postgres.flagged.keys().copied().collect::<Vec<_>>(),
+            [
+                "AUTO_INCREMENT",
+                "ENGINE= table option",
+                "UNSIGNED columns",
+                "backtick-quoted identifiers"
+            ]
+        );
+        assert_eq!(
+            postgres.flagged["backtick-quoted identifiers"],
+            (2, Some(4))
+        );
+        assert!(postgres.report().is_err());
+
+        // A sqlite3 dump bound for MySQL
+        let input = "PRAGMA foreign_keys=OFF;\nCREATE TABLE \"t\" (id INTEGER);\nINSERT INTO t VALUES('a\"b');";
+        let mut mysql = DryRun::new(Some(&MYSQL_DIALECT));
+        let mut reader = StatementReader::new(input.as_bytes(), &SQLITE_DIALECT);
+        while let Some(item) = reader.next_item().unwrap() {
+            mysql.statement(&item, Some(item.line()));
+        }
+        assert_eq!(
+            mysql.flagged.keys().copied().collect::<Vec<_>>(),
+            ["PRAGMA statements", "double-quoted identifiers"]
+        );
+        assert_eq!(mysql.flagged["double-quoted identifiers"], (1, Some(2)));
+    }
+
+    #[test]
+    fn blanks_strings_and_bodies_out_of_statement_code() {
+        assert_eq!(
+            statement_code(
+                "SELECT E'it\\'s ::', $f$ `x` $f$ /* [y] */ FROM \"t\"",
+                Syntax::POSTGRES
+            ),
+            "SELECT E'        ', $f$     $f$   FROM \" \""
+        );
+        assert_eq!(
+            statement_code("SELECT 'a\\'b', \"c\" # `d`", Syntax::MYSQL),
+            "SELECT '    ', \" \"  "
+        );
+    }
+
+    #[test]
+    fn splits_inserts_to_rewrite() {
+        assert_eq!(
+            split_insert(
+                "INSERT INTO \"public\".\"t\" (\"id\", \"values\") VALUES (1, 'a');",
+                Syntax::POSTGRES
+            ),
+            Some(("\"public\".\"t\" (\"id\", \"values\")", "VALUES (1, 'a');"))
+        );
+        assert_eq!(
+            split_insert("insert into `t` values(1),(2)", Syntax::MYSQL),
+            Some(("`t`", "values(1),(2)"))
+        );
+        assert_eq!(
+            split_insert("INSERT INTO t SELECT * FROM s;", Syntax::STANDARD),
+            Some(("t", "SELECT * FROM s;"))
+        );
+        assert_eq!(
+            split_insert("INSERT IGNORE INTO t VALUES (1);", Syntax::MYSQL),
+            None
+        );
+        assert_eq!(
+            split_insert(
+                "INSERT INTO t VALUES (1) ON CONFLICT DO NOTHING;",
+                Syntax::POSTGRES
+            ),
+            None
+        );
+
+        // Only a clause outside the rows counts, wherever it is
+        assert_eq!(
+            split_insert(
+                "INSERT INTO t VALUES (1, 'a ON CONFLICT') ON CONFLICT (id) DO NOTHING;",
+                Syntax::POSTGRES
+            ),
+            None
+        );
+        assert_eq!(
+            split_insert(
+                "INSERT INTO t VALUES (1, ' ON CONFLICT');",
+                Syntax::POSTGRES
+            ),
+            Some(("t", "VALUES (1, ' ON CONFLICT');"))
+        );
+        let updates: Vec<String> = (0..100)
+            .map(|i| format!("c{0} = VALUES(c{0})", i))
+            .collect();
+        let insert = format!(
+            "INSERT INTO t VALUES (1) ON DUPLICATE KEY UPDATE {};",
+            updates.join(", ")
+        );
+        assert_eq!(split_insert(&insert, Syntax::MYSQL), None);
+        assert_eq!(
+            split_insert("INSERT INTO [dbo].[t] VALUES (1);", Syntax::STANDARD),
+            Some(("[dbo].[t]", "VALUES (1);"))
+        );
+
+        let columns = ["id".to_string(), "v".to_string()];
+        let key = ["id".to_string()];
+        assert_eq!(
+            MYSQL_DIALECT.conflict_clauses(OnConflict::Update, &columns, &key),
+            Some((
+                "INSERT INTO",
+                " ON DUPLICATE KEY UPDATE `v` = VALUES(`v`)".to_string()
+            ))
+        );
+        assert_eq!(
+            MYSQL_DIALECT.conflict_clauses(OnConflict::Update, &key, &key),
+            Some(("INSERT IGNORE INTO", String::new()))
+        );
+        assert_eq!(
+            POSTGRES_DIALECT.conflict_clauses(OnConflict::Update, &columns, &key),
+            Some((
+                "INSERT INTO",
+                " ON CONFLICT (\"id\") DO UPDATE SET \"v\" = EXCLUDED.\"v\"".to_string()
+            ))
+        );
+        // An upsert that could only skip rows fails, unless nothing is
+        // outside the key
+        let upsert = |dialect: &dyn SqlDialect, columns: &[String], key: &[String]| {
+            conflict_clauses_for(dialect, "t", OnConflict::Update, columns, key)
+        };
+        assert!(upsert(&POSTGRES_DIALECT, &columns, &[]).is_err());
+        assert!(upsert(&POSTGRES_DIALECT, &[], &key).is_err());
+        assert!(upsert(&MYSQL_DIALECT, &[], &key).is_err());
+        assert_eq!(
+            upsert(&MYSQL_DIALECT, &key, &key).unwrap(),
+            ("INSERT IGNORE INTO", String::new())
+        );
+        assert!(conflict_clauses_for(&MYSQL_DIALECT, "t", OnConflict::Replace, &[], &[]).is_ok());
+    }
+
+    #[test]
+    fn logs_failed_statements_by_table() {
+        let path =
+            std::env::temp_dir().join(format!("migrasquiel-errors-{}.sql", std::process::id()));
+        let errors = ErrorLog::create(&path).unwrap();
+        let failed = |sql: &str, line| Item::Statement {
+            sql: sql.to_string(),
+            line,
+        };
+        let error = anyhow!("duplicate key").context("error returned from database: duplicate key");
+        errors
+            .record(
+                &failed("INSERT INTO \"public\".\"t\" VALUES (1);", 7),
+                &error,
+            )
+            .unwrap();
+        errors
+            .record(
+                &failed("CREATE VIEW v AS SELECT 1;", 9),
+                &anyhow!("no\nway"),
+            )
+            .unwrap();
+
+        let state = errors.state.lock().unwrap();
+        assert_eq!(state.failed.get("public.t"), Some(&1));
+        assert_eq!(state.failed.get("(no table)"), Some(&1));
+        drop(state);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "-- Line 7: error returned from database: duplicate key\nINSERT INTO \"public\".\"t\" VALUES (1);\n-- Line 9: no way\nCREATE VIEW v AS SELECT 1;\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn expands_input_patterns_in_lexical_order() {
+        let dir = std::env::temp_dir().join(format!("migrasquiel-inputs-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("dump-sub.sql")).unwrap();
+        for file in [
+            "dump-2.sql.gz",
+            "dump-10.sql.gz",
+            "dump-1.sql.gz",
+            "other.sql",
+        ] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        let dir_str = dir.to_string_lossy();
+
+        let inputs =
+            expand_inputs(&["schema.sql".to_string(), format!("{}/dump-*.sql*", dir_str)]).unwrap();
+        let expected = ["dump-1.sql.gz", "dump-10.sql.gz", "dump-2.sql.gz"]
+            .map(|file| format!("{}/{}", dir_str, file));
+        assert_eq!(inputs[0], "schema.sql");
+        assert_eq!(inputs[1..], expected);
+        assert!(expand_inputs(&[format!("{}/none-*.sql", dir_str)]).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn orders_tables_after_those_they_reference() {
+        let dir = std::env::temp_dir().join(format!("migrasquiel-order-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("schema")).unwrap();
+        let tables = [
+            (
+                "public.child",
+                "CREATE TABLE child (parent_id int REFERENCES \"public\".\"parent\"(id));",
+            ),
+            (
+                "public.other",
+                "CREATE TABLE other (id int REFERENCES elsewhere(id));",
+            ),
+            (
+                "public.parent",
+                "CREATE TABLE parent (id int, up int REFERENCES parent(id));",
+            ),
+        ];
+        let entries: Vec<TableEntry> = tables
+            .iter()
+            .map(|(name, sql)| {
+                let file = format!("schema/{}.sql", name);
+                std::fs::write(dir.join(&file), sql).unwrap();
+                TableEntry {
+                    name: name.to_string(),
+                    schema: Some(file),
+                    data: None,
+                    post_data: None,
+                    section: None,
+                }
+            })
+            .collect();
+
+        let ordered = order_by_references(&dir, entries.iter().collect(), None).unwrap();
+        let names: Vec<&str> = ordered.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["public.parent", "public.child", "public.other"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn strips_definers_when_asked() {
+        let input = "CREATE DEFINER=`root`@`%` VIEW `v` AS select 1;\nCOPY t (id) FROM stdin;\nDEFINER=x\n\\.";
+        let mut reader = StatementReader::new(input.as_bytes(), &MYSQL_DIALECT);
+        reader.strip_definer = Some(&MYSQL_DIALECT);
+
+        let view = reader.next_item().unwrap().unwrap();
+        assert_eq!(view.sql(), "CREATE VIEW `v` AS select 1;");
+        match reader.next_item().unwrap().unwrap() {
+            Item::CopyData { data, .. } => assert_eq!(data.as_slice(), b"DEFINER=x\n"),
+            Item::Statement { .. } => panic!("expected COPY data"),
+        }
+    }
+
+    #[test]
+    fn leaves_out_drops_when_asked() {
+        let input = "DROP TABLE IF EXISTS `t`;\nCREATE TABLE `t` (id int);\nCREATE TABLE IF NOT EXISTS u (id int);\nDROP VIEW v;\nINSERT INTO `t` VALUES (1);";
+        let mut reader = StatementReader::new(input.as_bytes(), &MYSQL_DIALECT);
+        reader.no_drop = true;
+
+        let mut statements = Vec::new();
+        while let Some(item) = reader.next_item().unwrap() {
+            statements.push(item.sql().to_string());
+        }
+        assert_eq!(
+            statements,
+            [
+                "CREATE TABLE IF NOT EXISTS `t` (id int);",
+                "CREATE TABLE IF NOT EXISTS u (id int);",
+                "DROP VIEW v;",
+                "INSERT INTO `t` VALUES (1);",
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_statements_and_copy_blocks() {
+        let input = "-- comment\nSET x = 1;\nCREATE TABLE t (\n  id int\n);\nCOPY t (id) FROM stdin;\n1\n2\n\\.\nINSERT INTO t VALUES (3)";
+        let mut reader = StatementReader::new(input.as_bytes(), &POSTGRES_DIALECT);
+
+        let mut items = Vec::new();
+        while let Some(item) = reader.next_item().unwrap() {
+            items.push(item);
+        }
+
+        assert_eq!(items.len(), 4);
+        assert_eq!(items[1].sql(), "CREATE TABLE t (   id int );");
+        match &items[2] {
+            Item::CopyData { data, end, .. } => {
+                assert_eq!(data.as_slice(), b"1\n2\n");
+                assert!(end);
+            }
+            Item::Statement { .. } => panic!("expected COPY data"),
+        }
+        assert_eq!(items[3].sql(), "INSERT INTO t VALUES (3)");
+    }
+
+    #[test]
+    fn reads_statements_between_delimiter_lines() {
+        let input = "DELIMITER ;;\nCREATE TRIGGER t BEFORE INSERT ON a FOR EACH ROW BEGIN\n  SET NEW.x = 1;\nEND ;;\ndelimiter ;\nINSERT INTO a VALUES (1);\nDELIMITER $$ -- routines\nCREATE PROCEDURE p()\nBEGIN\n  SELECT '$$;';\nEND$$\nDELIMITER ;\nCALL p();";
+        let mut reader = StatementReader::new(input.as_bytes(), &MYSQL_DIALECT);
+
+        let mut items = Vec::new();
+        while let Some(item) = reader.next_item().unwrap() {
+            items.push(item);
+        }
+
+        assert_eq!(items.len(), 4);
+        assert_eq!(
+            items[0].sql(),
+            "CREATE TRIGGER t BEFORE INSERT ON a FOR EACH ROW BEGIN\n  SET NEW.x = 1;\nEND"
+        );
+        assert_eq!(items[1].sql(), "INSERT INTO a VALUES (1);");
+        assert_eq!(
+            items[2].sql(),
+            "CREATE PROCEDURE p()\nBEGIN\n  SELECT '$$;';\nEND"
+        );
+        assert_eq!(items[3].sql(), "CALL p();");
+        assert_eq!(delimiter_command("delimiter '//' x"), Some("//"));
+        assert_eq!(delimiter_command("DELIMITERS ;"), None);
+    }
+
+    #[test]
+    fn reads_dollar_quoted_bodies_whole() {
+        let input = "CREATE FUNCTION f(a int) RETURNS int AS $body$\nBEGIN\n  RETURN a + $1; -- add\nEND;\n$body$ LANGUAGE plpgsql;\nSELECT 'x$y$z', $$;$$;\nCREATE FUNCTION g() RETURNS int AS $f$select 1$f$;";
+        let mut reader = StatementReader::new(input.as_bytes(), &POSTGRES_DIALECT);
+
+        let mut items = Vec::new();
+        while let Some(item) = reader.next_item().unwrap() {
+            items.push(item);
+        }
+
+        assert_eq!(items.len(), 3);
+        assert_eq!(
+            items[0].sql(),
+            "CREATE FUNCTION f(a int) RETURNS int AS $body$\nBEGIN\n  RETURN a + $1; -- add\nEND;\n$body$ LANGUAGE plpgsql;"
+        );
+        assert_eq!(items[1].sql(), "SELECT 'x$y$z', $$;$$;");
+        assert_eq!(
+            items[2].sql(),
+            "CREATE FUNCTION g() RETURNS int AS $f$select 1$f$;"
+        );
+    }
+}
@@ -0,0 +1,349 @@
+use crate::engine::dialect::{delete_row_sql, update_row_sql};
+use crate::engine::filter::TableFilter;
+use crate::engine::value::SqlValue;
+use crate::engine::{DbEngine, DbSession};
+use crate::migrate::insert_rows;
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+
+pub struct SyncOptions {
+    pub filter: TableFilter,
+    /// Rows per window compared by digest, and read whole when they differ
+    pub chunk_rows: usize,
+    /// Delete the destination rows the source does not have
+    pub delete: bool,
+    /// Count the differences without changing the destination
+    pub dry_run: bool,
+}
+
+/// What a sync changed, or would change, in one table
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct SyncCounts {
+    pub inserted: u64,
+    pub updated: u64,
+    /// Rows only on the destination; deleted with `--delete`
+    pub extra: u64,
+    pub windows: u64,
+    /// Windows whose digests matched, which were not read
+    pub unchanged_windows: u64,
+}
+
+impl SyncCounts {
+    /// Whether the destination matched the source
+    pub fn in_sync(&self) -> bool {
+        self.inserted == 0 && self.updated == 0 && self.extra == 0
+    }
+}
+
+/// Rows to write so that one key window of the destination matches the source
+#[derive(Debug, Default)]
+struct WindowDiff {
+    insert_columns: Vec<String>,
+    inserts: Vec<Vec<SqlValue>>,
+    /// Changed columns and their source values, with the key of the row
+    updates: Vec<(Vec<String>, Vec<SqlValue>, Vec<SqlValue>)>,
+    /// Keys of the rows only the destination has
+    extras: Vec<Vec<SqlValue>>,
+}
+
+pub async fn sync(
+    source_engine: &dyn DbEngine,
+    dest_engine: &dyn DbEngine,
+    source_url: &str,
+    dest_url: &str,
+    opts: SyncOptions,
+) -> Result<()> {
+    println!("Starting table sync...");
+
+    println!("Connecting to source database...");
+    let mut source = source_engine
+        .connect(source_url)
+        .await
+        .context("Failed to connect to source database")?;
+    println!("Connecting to destination database...");
+    let mut dest = dest_engine
+        .connect(dest_url)
+        .await
+        .context("Failed to connect to destination database")?;
+
+    let tables = source.list_tables(&opts.filter).await?;
+    println!("Found {} table(s) to compare", tables.len());
+    // Digests only agree between servers that render values alike
+    let digests = source.dialect().name() == dest.dialect().name();
+
+    for (idx, table) in tables.iter().enumerate() {
+        println!(
+            "\n[{}/{}] Comparing table '{}'...",
+            idx + 1,
+            tables.len(),
+            table
+        );
+        let key = source.primary_key(table).await?;
+        if key.is_empty() {
+            println!("  Skipped: no primary key to match rows on");
+            continue;
+        }
+
+        let counts = sync_table(&mut *source, &mut *dest, table, table, &key, digests, &opts)
+            .await
+            .with_context(|| format!("Failed to sync table '{}'", table))?;
+        println!(
+            "  {} of {} window(s) unchanged",
+            counts.unchanged_windows, counts.windows
+        );
+        let would = if opts.dry_run { "to be " } else { "" };
+        println!(
+            "  {} row(s) {}inserted, {} {}updated",
+            counts.inserted, would, counts.updated, would
+        );
+        if counts.extra > 0 {
+            if opts.delete {
+                println!("  {} row(s) {}deleted", counts.extra, would);
+            } else {
+                println!(
+                    "  {} row(s) only on the destination; --delete removes them",
+                    counts.extra
+                );
+            }
+        }
+    }
+
+    if opts.dry_run {
+        println!("\nDry run completed; the destination was not changed");
+    } else {
+        println!("\nSync completed successfully!");
+    }
+    Ok(())
+}
+
+/// Walk `table` in windows of `chunk_rows` source rows, bounded by the
+/// source's keys, and bring each window of `dest_table` in line
+pub(crate) async fn sync_table(
+    source: &mut dyn DbSession,
+    dest: &mut dyn DbSession,
+    table: &str,
+    dest_table: &str,
+    key: &[String],
+    digests: bool,
+    opts: &SyncOptions,
+) -> Result<SyncCounts> {
+    let mut counts = SyncCounts::default();
+    let mut after: Option<Vec<SqlValue>> = None;
+    loop {
+        // The last window is open-ended, so it also takes in the
+        // destination's rows past the source's last key
+        let upto = source
+            .key_after(table, key, after.as_deref(), opts.chunk_rows.max(1) - 1)
+            .await?;
+        let (after_key, upto_key) = (after.as_deref(), upto.as_deref());
+        counts.windows += 1;
+
+        let unchanged = digests
+            && match (
+                source
+                    .checksum_key_window(table, key, after_key, upto_key)
+                    .await?,
+                dest.checksum_key_window(dest_table, key, after_key, upto_key)
+                    .await?,
+            ) {
+                (Some(source_digest), Some(dest_digest)) => source_digest == dest_digest,
+                _ => false,
+            };
+        if unchanged {
+            counts.unchanged_windows += 1;
+        } else {
+            let (columns, rows) = source
+                .read_key_window(table, key, after_key, upto_key)
+                .await?;
+            let (dest_columns, dest_rows) = dest
+                .read_key_window(dest_table, key, after_key, upto_key)
+                .await?;
+            let diff = diff_window(key, &columns, rows, &dest_columns, dest_rows)?;
+            counts.inserted += diff.inserts.len() as u64;
+            counts.updated += diff.updates.len() as u64;
+            counts.extra += diff.extras.len() as u64;
+            if !opts.dry_run {
+                apply_diff(dest, dest_table, key, diff, opts.delete).await?;
+            }
+        }
+
+        match upto {
+            Some(upto) => after = Some(upto),
+            None => return Ok(counts),
+        }
+    }
+}
+
+/// Compare the rows of one window, matched on `key`. Columns only one side
+/// has are left out.
+fn diff_window(
+    key: &[String],
+    columns: &[String],
+    rows: Vec<Vec<SqlValue>>,
+    dest_columns: &[String],
+    dest_rows: Vec<Vec<SqlValue>>,
+) -> Result<WindowDiff> {
+    let positions = |columns: &[String]| -> Result<Vec<usize>> {
+        key.iter()
+            .map(|k| {
+                columns
+                    .iter()
+                    .position(|c| c == k)
+                    .ok_or_else(|| anyhow!("Key column '{}' is missing", k))
+            })
+            .collect()
+    };
+    let key_positions = positions(columns)?;
+    let dest_key_positions = positions(dest_columns)?;
+    let key_text = |row: &[SqlValue], positions: &[usize]| -> Vec<Option<String>> {
+        positions.iter().map(|&i| canonical(&row[i])).collect()
+    };
+
+    // Where each source column sits in a destination row
+    let shared: Vec<(usize, usize)> = columns
+        .iter()
+        .enumerate()
+        .filter_map(|(i, c)| Some((i, dest_columns.iter().position(|d| d == c)?)))
+        .collect();
+
+    let mut dest_by_key: HashMap<Vec<Option<String>>, Vec<SqlValue>> = dest_rows
+        .into_iter()
+        .map(|row| (key_text(&row, &dest_key_positions), row))
+        .collect();
+
+    let mut diff = WindowDiff {
+        insert_columns: shared.iter().map(|&(i, _)| columns[i].clone()).collect(),
+        ..WindowDiff::default()
+    };
+    for row in rows {
+        let key_values: Vec<SqlValue> = key_positions.iter().map(|&i| row[i].clone()).collect();
+        match dest_by_key.remove(&key_text(&row, &key_positions)) {
+            None => diff
+                .inserts
+                .push(shared.iter().map(|&(i, _)| row[i].clone()).collect()),
+            Some(dest_row) => {
+                let changed: Vec<usize> = shared
+                    .iter()
+                    .filter(|&&(i, d)| canonical(&row[i]) != canonical(&dest_row[d]))
+                    .map(|&(i, _)| i)
+                    .collect();
+                if !changed.is_empty() {
+                    diff.updates.push((
+                        changed.iter().map(|&i| columns[i].clone()).collect(),
+                        changed.iter().map(|&i| row[i].clone()).collect(),
+                        key_values,
+                    ));
+                }
+            }
+        }
+    }
+    diff.extras = dest_by_key
+        .into_values()
+        .map(|row| dest_key_positions.iter().map(|&i| row[i].clone()).collect())
+        .collect();
+    Ok(diff)
+}
+
+/// Write one window's differences in a transaction of its own; extra rows
+/// go first, so they free any unique values the source rows take
+async fn apply_diff(
+    dest: &mut dyn DbSession,
+    table: &str,
+    key: &[String],
+    diff: WindowDiff,
+    delete: bool,
+) -> Result<()> {
+    let dialect = dest.dialect();
+    dest.begin().await?;
+    if delete {
+        for key_values in &diff.extras {
+            dest.execute(&delete_row_sql(dialect, table, key, key_values))
+                .await?;
+        }
+    }
+    for (columns, values, key_values) in &diff.updates {
+        dest.execute(&update_row_sql(
+            dialect, table, columns, values, key, key_values,
+        ))
+        .await?;
+    }
+    if !diff.inserts.is_empty() {
+        insert_rows(dest, table, &diff.insert_columns, &diff.inserts, None).await?;
+    }
+    dest.commit().await
+}
+
+/// A value's text, alike for the types different engines read the same
+/// value as, such as a boolean and a tinyint
+fn canonical(value: &SqlValue) -> Option<String> {
+    match value {
+        SqlValue::Null => None,
+        SqlValue::Bool(b) => Some(u8::from(*b).to_string()),
+        SqlValue::Int(n) => Some(n.to_string()),
+        SqlValue::Float(n) => Some(n.to_string()),
+        SqlValue::Decimal(text) | SqlValue::String(text) => Some(text.clone()),
+        SqlValue::Bytes(bytes) => Some(hex::encode(bytes)),
+        temporal => temporal.temporal_text(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::sqlite::SqliteEngine;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn brings_destination_in_line_by_key() {
+        let mut source = SqliteEngine.connect("sqlite::memory:").await.unwrap();
+        let mut dest = SqliteEngine.connect("sqlite::memory:").await.unwrap();
+        for (session, rows) in [
+            (
+                &mut source,
+                "(1, 'a'), (2, 'b'), (3, 'c'), (4, 'd'), (6, 'f')",
+            ),
+            (
+                &mut dest,
+                "(0, 'z'), (1, 'a'), (2, 'B'), (4, 'd'), (5, 'e'), (9, 'y')",
+            ),
+        ] {
+            session
+                .execute("CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT)")
+                .await
+                .unwrap();
+            session
+                .execute(&format!("INSERT INTO t VALUES {}", rows))
+                .await
+                .unwrap();
+        }
+
+        let mut opts = SyncOptions {
+            filter: TableFilter::default(),
+            chunk_rows: 2,
+            delete: false,
+            dry_run: true,
+        };
+        let key = vec!["id".to_string()];
+        let counts = sync_table(&mut *source, &mut *dest, "t", "t", &key, false, &opts)
+            .await
+            .unwrap();
+        assert_eq!(
+            counts,
+            SyncCounts {
+                inserted: 2,
+                updated: 1,
+                extra: 3,
+                windows: 3,
+                unchanged_windows: 0,
+            }
+        );
+
+        opts.dry_run = false;
+        opts.delete = true;
+        sync_table(&mut *source, &mut *dest, "t", "t", &key, false, &opts)
+            .await
+            .unwrap();
+        let (_, rows) = dest.read_key_window("t", &key, None, None).await.unwrap();
+        let (_, expected) = source.read_key_window("t", &key, None, None).await.unwrap();
+        assert_eq!(rows, expected);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::engine::value::SqlValue;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Room left under the server's packet limit for the statement text around
+/// the values (INSERT INTO, column list, protocol framing)
+const PACKET_HEADROOM: usize = 64 * 1024;
+
+/// Rows per batch the tuner starts from
+const AUTO_START_ROWS: usize = 100;
+/// Bounds the tuner keeps the batch size within
+const AUTO_MIN_ROWS: usize = 10;
+const AUTO_MAX_ROWS: usize = 100_000;
+/// Row count used where `auto` needs a single number up front
+const DEFAULT_ROWS: usize = 1000;
+
+/// `--batch-rows`: a fixed row count, or `auto` to tune it while inserting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchRows {
+    Fixed(usize),
+    Auto,
+}
+
+impl BatchRows {
+    /// A row count for uses that cannot adapt, such as type sampling
+    pub fn hint(self) -> usize {
+        match self {
+            BatchRows::Fixed(rows) => rows,
+            BatchRows::Auto => DEFAULT_ROWS,
+        }
+    }
+
+    pub fn tuner(self) -> BatchTuner {
+        match self {
+            BatchRows::Fixed(rows) => BatchTuner {
+                rows: rows.max(1),
+                adaptive: false,
+                last_rate: None,
+            },
+            BatchRows::Auto => BatchTuner {
+                rows: AUTO_START_ROWS,
+                adaptive: true,
+                last_rate: None,
+            },
+        }
+    }
+}
+
+impl FromStr for BatchRows {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(BatchRows::Auto);
+        }
+        s.parse()
+            .map(BatchRows::Fixed)
+            .map_err(|_| format!("expected a row count or 'auto', got '{}'", s))
+    }
+}
+
+/// Picks the next batch size from the throughput of the last one. The size
+/// doubles while rows per second keep improving and backs off when they
+/// drop, so it settles near the point where larger statements stop paying
+/// off (or start failing and falling back to row-by-row inserts).
+#[derive(Debug, Clone)]
+pub struct BatchTuner {
+    rows: usize,
+    adaptive: bool,
+    last_rate: Option<f64>,
+}
+
+impl BatchTuner {
+    /// Rows to put in the next batch
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn is_adaptive(&self) -> bool {
+        self.adaptive
+    }
+
+    /// Record that a batch of `rows` rows took `elapsed` to insert
+    pub fn record(&mut self, rows: usize, elapsed: Duration) {
+        if !self.adaptive || rows == 0 {
+            return;
+        }
+
+        let rate = rows as f64 / elapsed.as_secs_f64().max(1e-6);
+        // A batch cut short by the byte limit says nothing about larger ones
+        let full = rows >= self.rows;
+
+        self.rows = match self.last_rate {
+            Some(last) if rate < last * 0.5 => self.rows / 2,
+            Some(last) if rate < last * 0.9 => self.rows * 3 / 4,
+            Some(last) if rate <= last * 1.05 => self.rows,
+            _ if full => self.rows * 2,
+            _ => self.rows,
+        }
+        .clamp(AUTO_MIN_ROWS, AUTO_MAX_ROWS);
+        self.last_rate = Some(rate);
+    }
+}
+
+/// When a batch of rows is flushed: after `rows` rows, or once the rendered
+/// SQL reaches `bytes`, whichever comes first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchLimit {
+    pub rows: usize,
+    pub bytes: Option<usize>,
+}
+
+impl BatchLimit {
+    /// Combine the configured limits with the server's packet size, if it
+    /// has one; the smaller byte limit wins
+    pub fn new(rows: usize, bytes: Option<usize>, server_packet: Option<usize>) -> Self {
+        let packet_bytes = server_packet.map(|packet| {
+            if packet > PACKET_HEADROOM * 2 {
+                packet - PACKET_HEADROOM
+            } else {
+                packet / 2
+            }
+        });
+
+        let bytes = match (bytes, packet_bytes) {
+            (Some(bytes), Some(packet)) => Some(bytes.min(packet)),
+            (bytes, packet) => bytes.or(packet),
+        };
+
+        BatchLimit {
+            rows: rows.max(1),
+            bytes,
+        }
+    }
+
+    /// Whether a batch holding `rows` rows of about `bytes` bytes is full
+    pub fn is_full(&self, rows: usize, bytes: usize) -> bool {
+        rows >= self.rows || self.bytes.is_some_and(|limit| bytes >= limit)
+    }
+}
+
+/// Estimated size of a row once its values are rendered as SQL literals,
+/// including the surrounding parentheses and separators
+pub fn row_sql_len(row: &[SqlValue]) -> usize {
+    let values: usize = row
+        .iter()
+        .map(|value| match value {
+            SqlValue::Null => 4,
+            SqlValue::Bool(_) => 5,
+            SqlValue::Int(_) => 20,
+            SqlValue::Float(_) => 24,
+            SqlValue::Decimal(v) => v.len(),
+            // Quotes, plus one more byte for every character that gets escaped
+            SqlValue::String(v) => {
+                v.len() + 2 + v.bytes().filter(|b| matches!(b, b'\'' | b'\\')).count()
+            }
+            // Hex digits plus the widest wrapper (`'\x...'::bytea`)
+            SqlValue::Bytes(v) => v.len() * 2 + 12,
+            SqlValue::Date { .. } => 12,
+            SqlValue::Time { .. } => 18,
+            SqlValue::Timestamp { .. } => 28,
+        })
+        .sum();
+
+    values + row.len() * 2 + 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caps_bytes_at_the_server_packet() {
+        let limit = BatchLimit::new(1000, Some(64 << 20), Some(4 << 20));
+        assert_eq!(limit.bytes, Some((4 << 20) - PACKET_HEADROOM));
+
+        let limit = BatchLimit::new(1000, None, Some(64 * 1024));
+        assert_eq!(limit.bytes, Some(32 * 1024));
+
+        assert_eq!(BatchLimit::new(0, None, None).rows, 1);
+        assert!(BatchLimit::new(10, Some(100), None).is_full(3, 120));
+        assert!(!BatchLimit::new(10, None, None).is_full(3, usize::MAX));
+    }
+
+    #[test]
+    fn tunes_batch_rows_by_throughput() {
+        assert_eq!("auto".parse::<BatchRows>(), Ok(BatchRows::Auto));
+        assert_eq!("500".parse::<BatchRows>(), Ok(BatchRows::Fixed(500)));
+        assert!("lots".parse::<BatchRows>().is_err());
+
+        let mut fixed = BatchRows::Fixed(500).tuner();
+        fixed.record(500, Duration::from_millis(10));
+        assert_eq!(fixed.rows(), 500);
+
+        let mut tuner = BatchRows::Auto.tuner();
+        tuner.record(100, Duration::from_millis(10));
+        assert_eq!(tuner.rows(), 200);
+        // Twice the rows in the same time: keep growing
+        tuner.record(200, Duration::from_millis(10));
+        assert_eq!(tuner.rows(), 400);
+        // Throughput collapsed: back off hard
+        tuner.record(400, Duration::from_millis(1000));
+        assert_eq!(tuner.rows(), 200);
+    }
+
+    #[test]
+    fn estimates_escaped_strings() {
+        let row = vec![SqlValue::String("it's".to_string()), SqlValue::Null];
+        assert_eq!(row_sql_len(&row), (4 + 2 + 1) + 4 + 2 * 2 + 2);
+    }
+}
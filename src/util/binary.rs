@@ -0,0 +1,438 @@
+use crate::engine::value::SqlValue;
+use anyhow::{anyhow, bail, Context, Result};
+use std::io::{self, BufRead, Write};
+
+/// First bytes of a binary dump (`--format binary`), with its layout
+/// version. Plain SQL text follows as in a file dump, except that each
+/// batch of rows is a frame instead of an INSERT: a zero byte, the payload
+/// length, then the table, its columns and the rows as typed values. SQL
+/// text never holds a zero byte, so the two need no further framing, and
+/// the part files of a parallel dump still append to one another. Numbers
+/// are LEB128 varints, signed ones zigzag encoded; floats are their bits,
+/// little-endian.
+pub const MAGIC: &[u8] = b"\0MIGRASQUIEL-BINARY 1\n";
+
+/// Opens every row frame
+const FRAME: u8 = 0;
+
+/// Largest frame a reader accepts, against a damaged length
+const MAX_FRAME_BYTES: u64 = 1 << 32;
+
+const NULL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const INT: u8 = 3;
+const FLOAT: u8 = 4;
+const DECIMAL: u8 = 5;
+const STRING: u8 = 6;
+const BYTES: u8 = 7;
+const DATE: u8 = 8;
+const TIME: u8 = 9;
+const TIMESTAMP: u8 = 10;
+
+/// Write a frame holding `rows` of `table`
+pub fn write_rows(
+    writer: &mut dyn Write,
+    table: &str,
+    columns: &[String],
+    rows: &[Vec<SqlValue>],
+) -> io::Result<()> {
+    let mut payload = Vec::new();
+    put_bytes(&mut payload, table.as_bytes());
+    put_uint(&mut payload, columns.len() as u64);
+    for column in columns {
+        put_bytes(&mut payload, column.as_bytes());
+    }
+    put_uint(&mut payload, rows.len() as u64);
+    for row in rows {
+        for value in row {
+            put_value(&mut payload, value);
+        }
+    }
+
+    let mut header = vec![FRAME];
+    put_uint(&mut header, payload.len() as u64);
+    writer.write_all(&header)?;
+    writer.write_all(&payload)
+}
+
+/// `values` encoded as in a frame, to keep a row's key outside a dump
+pub fn encode_values(values: &[SqlValue]) -> Vec<u8> {
+    let mut out = Vec::new();
+    put_uint(&mut out, values.len() as u64);
+    for value in values {
+        put_value(&mut out, value);
+    }
+    out
+}
+
+/// Values written by [`encode_values`]
+pub fn decode_values(mut input: &[u8]) -> Result<Vec<SqlValue>> {
+    let input = &mut input;
+    let values = (0..read_uint(input)?)
+        .map(|_| take_value(input))
+        .collect::<Result<Vec<_>>>()?;
+    if !input.is_empty() {
+        bail!("Encoded values have trailing bytes");
+    }
+    Ok(values)
+}
+
+/// What a binary dump holds next
+#[derive(Debug, PartialEq)]
+pub enum Segment {
+    /// SQL text running up to the next frame, made of whole statements
+    Sql(Vec<u8>),
+    /// One frame of rows
+    Rows {
+        table: String,
+        columns: Vec<String>,
+        rows: Vec<Vec<SqlValue>>,
+    },
+}
+
+/// Reads a binary dump back as [`Segment`]s
+pub struct Reader<R> {
+    inner: R,
+}
+
+impl<R: BufRead> Reader<R> {
+    /// Start reading after [`MAGIC`], which must open the input
+    pub fn new(mut inner: R) -> Result<Self> {
+        let mut magic = vec![0u8; MAGIC.len()];
+        inner
+            .read_exact(&mut magic)
+            .context("Failed to read the binary dump header")?;
+        if magic != MAGIC {
+            bail!("Not a binary dump, or one from a newer version");
+        }
+        Ok(Reader { inner })
+    }
+
+    /// The next segment, or `None` at the end of the dump
+    pub fn next_segment(&mut self) -> Result<Option<Segment>> {
+        let buf = self.inner.fill_buf()?;
+        match buf.first() {
+            None => Ok(None),
+            Some(&FRAME) => {
+                self.inner.consume(1);
+                let len = read_uint(&mut self.inner)?;
+                if len > MAX_FRAME_BYTES {
+                    bail!("Row frame of {} bytes; the dump is damaged", len);
+                }
+                let mut payload = vec![0u8; len as usize];
+                self.inner
+                    .read_exact(&mut payload)
+                    .context("Row frame cut short; the dump is damaged")?;
+                decode_rows(&payload).map(Some)
+            }
+            Some(_) => {
+                let mut sql = Vec::new();
+                loop {
+                    let buf = self.inner.fill_buf()?;
+                    if buf.is_empty() {
+                        break;
+                    }
+                    match buf.iter().position(|&b| b == FRAME) {
+                        Some(end) => {
+                            sql.extend_from_slice(&buf[..end]);
+                            self.inner.consume(end);
+                            break;
+                        }
+                        None => {
+                            let len = buf.len();
+                            sql.extend_from_slice(buf);
+                            self.inner.consume(len);
+                        }
+                    }
+                }
+                Ok(Some(Segment::Sql(sql)))
+            }
+        }
+    }
+}
+
+/// Whether an input starting with `start` is a binary dump; SQL text
+/// never opens with a zero byte
+pub fn is_binary(start: &[u8]) -> bool {
+    start.first() == MAGIC.first()
+}
+
+fn decode_rows(mut payload: &[u8]) -> Result<Segment> {
+    let input = &mut payload;
+    let table = take_string(input)?;
+    let columns = (0..read_uint(input)?)
+        .map(|_| take_string(input))
+        .collect::<Result<Vec<_>>>()?;
+    let count = read_uint(input)?;
+    let mut rows = Vec::with_capacity(count.min(1 << 16) as usize);
+    for _ in 0..count {
+        let row = (0..columns.len())
+            .map(|_| take_value(input))
+            .collect::<Result<Vec<_>>>()?;
+        rows.push(row);
+    }
+    if !input.is_empty() {
+        bail!(
+            "Row frame for '{}' has trailing bytes; the dump is damaged",
+            table
+        );
+    }
+    Ok(Segment::Rows {
+        table,
+        columns,
+        rows,
+    })
+}
+
+fn put_uint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn put_int(out: &mut Vec<u8>, v: i64) {
+    put_uint(out, ((v << 1) ^ (v >> 63)) as u64);
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    put_uint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn put_value(out: &mut Vec<u8>, value: &SqlValue) {
+    match value {
+        SqlValue::Null => out.push(NULL),
+        SqlValue::Bool(false) => out.push(FALSE),
+        SqlValue::Bool(true) => out.push(TRUE),
+        SqlValue::Int(v) => {
+            out.push(INT);
+            put_int(out, *v);
+        }
+        SqlValue::Float(v) => {
+            out.push(FLOAT);
+            out.extend_from_slice(&v.to_bits().to_le_bytes());
+        }
+        SqlValue::Decimal(v) => {
+            out.push(DECIMAL);
+            put_bytes(out, v.as_bytes());
+        }
+        SqlValue::String(v) => {
+            out.push(STRING);
+            put_bytes(out, v.as_bytes());
+        }
+        SqlValue::Bytes(v) => {
+            out.push(BYTES);
+            put_bytes(out, v);
+        }
+        SqlValue::Date { y, m, d } => {
+            out.push(DATE);
+            put_int(out, *y as i64);
+            put_uint(out, *m as u64);
+            put_uint(out, *d as u64);
+        }
+        SqlValue::Time { neg, h, m, s, us } => {
+            out.push(TIME);
+            out.push(*neg as u8);
+            for part in [h, m, s, us] {
+                put_uint(out, *part as u64);
+            }
+        }
+        SqlValue::Timestamp {
+            y,
+            m,
+            d,
+            hh,
+            mm,
+            ss,
+            us,
+        } => {
+            out.push(TIMESTAMP);
+            put_int(out, *y as i64);
+            for part in [m, d, hh, mm, ss, us] {
+                put_uint(out, *part as u64);
+            }
+        }
+    }
+}
+
+fn read_uint(input: &mut impl io::Read) -> Result<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8];
+        input
+            .read_exact(&mut byte)
+            .context("Row frame cut short; the dump is damaged")?;
+        v |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    bail!("Overlong number in a row frame; the dump is damaged")
+}
+
+fn take_int(input: &mut &[u8]) -> Result<i64> {
+    let v = read_uint(input)?;
+    Ok((v >> 1) as i64 ^ -((v & 1) as i64))
+}
+
+fn take_u32(input: &mut &[u8]) -> Result<u32> {
+    u32::try_from(read_uint(input)?).map_err(|_| anyhow!("Value out of range in a row frame"))
+}
+
+fn take_i32(input: &mut &[u8]) -> Result<i32> {
+    i32::try_from(take_int(input)?).map_err(|_| anyhow!("Value out of range in a row frame"))
+}
+
+fn take_bytes(input: &mut &[u8]) -> Result<Vec<u8>> {
+    let len = read_uint(input)?;
+    if len > input.len() as u64 {
+        bail!("Row frame cut short; the dump is damaged");
+    }
+    let (bytes, rest) = input.split_at(len as usize);
+    *input = rest;
+    Ok(bytes.to_vec())
+}
+
+fn take_string(input: &mut &[u8]) -> Result<String> {
+    String::from_utf8(take_bytes(input)?)
+        .map_err(|_| anyhow!("Text that is not UTF-8 in a row frame; the dump is damaged"))
+}
+
+fn take_value(input: &mut &[u8]) -> Result<SqlValue> {
+    let Some((&tag, rest)) = input.split_first() else {
+        bail!("Row frame cut short; the dump is damaged");
+    };
+    *input = rest;
+    Ok(match tag {
+        NULL => SqlValue::Null,
+        FALSE => SqlValue::Bool(false),
+        TRUE => SqlValue::Bool(true),
+        INT => SqlValue::Int(take_int(input)?),
+        FLOAT => {
+            let Some((bits, rest)) = input.split_first_chunk::<8>() else {
+                bail!("Row frame cut short; the dump is damaged");
+            };
+            *input = rest;
+            SqlValue::Float(f64::from_bits(u64::from_le_bytes(*bits)))
+        }
+        DECIMAL => SqlValue::Decimal(take_string(input)?),
+        STRING => SqlValue::String(take_string(input)?),
+        BYTES => SqlValue::Bytes(take_bytes(input)?),
+        DATE => SqlValue::Date {
+            y: take_i32(input)?,
+            m: take_u32(input)?,
+            d: take_u32(input)?,
+        },
+        TIME => {
+            let Some((&neg, rest)) = input.split_first() else {
+                bail!("Row frame cut short; the dump is damaged");
+            };
+            *input = rest;
+            SqlValue::Time {
+                neg: neg != 0,
+                h: take_u32(input)?,
+                m: take_u32(input)?,
+                s: take_u32(input)?,
+                us: take_u32(input)?,
+            }
+        }
+        TIMESTAMP => SqlValue::Timestamp {
+            y: take_i32(input)?,
+            m: take_u32(input)?,
+            d: take_u32(input)?,
+            hh: take_u32(input)?,
+            mm: take_u32(input)?,
+            ss: take_u32(input)?,
+            us: take_u32(input)?,
+        },
+        other => bail!(
+            "Unknown value type {} in a row frame; the dump is damaged",
+            other
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_back_sql_and_rows() {
+        let columns = vec!["id".to_string(), "v".to_string()];
+        let rows = vec![
+            vec![SqlValue::Int(-300), SqlValue::String("x\0y".into())],
+            vec![SqlValue::Null, SqlValue::Float(1.5)],
+            vec![SqlValue::Bool(true), SqlValue::Bytes(vec![0, 1, 2])],
+            vec![
+                SqlValue::Decimal("12.50".into()),
+                SqlValue::Timestamp {
+                    y: -44,
+                    m: 3,
+                    d: 15,
+                    hh: 12,
+                    mm: 0,
+                    ss: 1,
+                    us: 999_999,
+                },
+            ],
+            vec![
+                SqlValue::Date {
+                    y: 2024,
+                    m: 2,
+                    d: 29,
+                },
+                SqlValue::Time {
+                    neg: true,
+                    h: 838,
+                    m: 59,
+                    s: 59,
+                    us: 0,
+                },
+            ],
+        ];
+
+        let mut dump = MAGIC.to_vec();
+        dump.extend_from_slice(b"CREATE TABLE t (id int);\n");
+        write_rows(&mut dump, "t", &columns, &rows).unwrap();
+        write_rows(&mut dump, "t", &columns, &rows[..1]).unwrap();
+        dump.extend_from_slice(b"COMMIT;\n");
+
+        let mut reader = Reader::new(dump.as_slice()).unwrap();
+        let mut next = || reader.next_segment().unwrap();
+        assert_eq!(
+            next(),
+            Some(Segment::Sql(b"CREATE TABLE t (id int);\n".to_vec()))
+        );
+        assert_eq!(
+            next(),
+            Some(Segment::Rows {
+                table: "t".into(),
+                columns: columns.clone(),
+                rows: rows.clone(),
+            })
+        );
+        assert!(matches!(next(), Some(Segment::Rows { rows, .. }) if rows.len() == 1));
+        assert_eq!(next(), Some(Segment::Sql(b"COMMIT;\n".to_vec())));
+        assert_eq!(next(), None);
+    }
+
+    #[test]
+    fn rejects_damaged_frames() {
+        let mut dump = MAGIC.to_vec();
+        write_rows(
+            &mut dump,
+            "t",
+            &["id".to_string()],
+            &[vec![SqlValue::Int(1)]],
+        )
+        .unwrap();
+        dump.truncate(dump.len() - 1);
+        let mut reader = Reader::new(dump.as_slice()).unwrap();
+        assert!(reader.next_segment().is_err());
+
+        assert!(Reader::new(&b"-- MySQL dump\n"[..]).is_err());
+    }
+}
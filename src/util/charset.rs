@@ -0,0 +1,167 @@
+use crate::engine::value::SqlValue;
+use anyhow::Result;
+use encoding_rs::Encoding;
+use futures::stream::{BoxStream, StreamExt};
+use std::fmt;
+use std::str::FromStr;
+
+/// `--convert-charset`: the character set migrated text is kept in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    Utf8mb4,
+}
+
+impl fmt::Display for Charset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Charset::Utf8mb4 => "utf8mb4",
+        })
+    }
+}
+
+impl FromStr for Charset {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf8mb4" => Ok(Charset::Utf8mb4),
+            other => Err(format!(
+                "Unknown --convert-charset character set '{}' (expected utf8mb4)",
+                other
+            )),
+        }
+    }
+}
+
+/// The encoding behind a MySQL character set; `None` for the UTF-8 sets,
+/// `binary` and sets without a counterpart
+pub fn mysql_encoding(charset: &str) -> Option<&'static Encoding> {
+    let label = match charset.to_ascii_lowercase().as_str() {
+        // MySQL's latin1 is Windows-1252
+        "latin1" | "ascii" => "windows-1252",
+        "latin2" => "iso-8859-2",
+        "latin5" => "windows-1254",
+        "latin7" => "iso-8859-13",
+        "cp1250" => "windows-1250",
+        "cp1251" => "windows-1251",
+        "cp1256" => "windows-1256",
+        "cp1257" => "windows-1257",
+        "greek" => "iso-8859-7",
+        "hebrew" => "iso-8859-8",
+        "koi8r" => "koi8-r",
+        "koi8u" => "koi8-u",
+        "sjis" | "cp932" => "shift_jis",
+        "ujis" | "eucjpms" => "euc-jp",
+        "euckr" => "euc-kr",
+        "gbk" => "gbk",
+        "gb18030" => "gb18030",
+        "big5" => "big5",
+        _ => return None,
+    };
+    Encoding::for_label(label.as_bytes())
+}
+
+/// `text` as read from a column stored in `encoding`, when the column held
+/// the bytes of UTF-8 text: each of those bytes then reads back as a
+/// character of its own, such as `Ã©` for `é`. The bytes are taken back
+/// and read as UTF-8. `None` when `text` reads as it should already.
+pub fn repair_text(text: &str, encoding: &'static Encoding) -> Option<String> {
+    if text.is_ascii() {
+        return None;
+    }
+    let (bytes, _, unmappable) = encoding.encode(text);
+    if unmappable {
+        return None;
+    }
+    match std::str::from_utf8(&bytes) {
+        Ok(repaired) if repaired != text => Some(repaired.to_string()),
+        _ => None,
+    }
+}
+
+/// The columns of a table's rows whose text is stored in another character
+/// set than UTF-8, repaired as the rows stream by
+#[derive(Debug, Default)]
+pub struct TextRepair {
+    columns: Vec<(usize, &'static Encoding)>,
+}
+
+impl TextRepair {
+    /// Repair for rows of `columns`, given the character set of each text
+    /// column by name
+    pub fn new(columns: &[String], charsets: &[(String, String)]) -> Self {
+        let columns = columns
+            .iter()
+            .enumerate()
+            .filter_map(|(index, name)| {
+                let (_, charset) = charsets.iter().find(|(column, _)| column == name)?;
+                Some((index, mysql_encoding(charset)?))
+            })
+            .collect();
+        Self { columns }
+    }
+
+    pub fn apply<'a>(
+        self,
+        rows: BoxStream<'a, Result<Vec<SqlValue>>>,
+    ) -> BoxStream<'a, Result<Vec<SqlValue>>> {
+        if self.columns.is_empty() {
+            return rows;
+        }
+        rows.map(move |row| {
+            let mut row = row?;
+            for &(index, encoding) in &self.columns {
+                if let Some(SqlValue::String(text)) = row.get_mut(index) {
+                    if let Some(repaired) = repair_text(text, encoding) {
+                        *text = repaired;
+                    }
+                }
+            }
+            Ok(row)
+        })
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn repairs_utf8_read_as_another_charset() {
+        let latin1 = mysql_encoding("latin1").unwrap();
+        assert_eq!(
+            repair_text("CafÃ© crÃ¨me â€“ ok", latin1).unwrap(),
+            "Café crème – ok"
+        );
+        // Text the column held in its own character set is left alone
+        assert_eq!(repair_text("Café", latin1), None);
+        assert_eq!(repair_text("plain", latin1), None);
+        assert_eq!(repair_text("日本", latin1), None);
+        assert!(mysql_encoding("utf8mb4").is_none());
+
+        let columns = vec!["id".to_string(), "name".to_string(), "note".to_string()];
+        let charsets = vec![
+            ("name".to_string(), "latin1".to_string()),
+            ("note".to_string(), "utf8mb4".to_string()),
+        ];
+        let rows = futures::stream::iter(vec![Ok(vec![
+            SqlValue::Int(1),
+            SqlValue::String("JosÃ©".into()),
+            SqlValue::String("JosÃ©".into()),
+        ])])
+        .boxed();
+        let rows: Vec<_> = TextRepair::new(&columns, &charsets)
+            .apply(rows)
+            .collect()
+            .await;
+        assert_eq!(
+            rows[0].as_ref().unwrap(),
+            &vec![
+                SqlValue::Int(1),
+                SqlValue::String("José".into()),
+                SqlValue::String("JosÃ©".into()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,881 @@
+use crate::engine::dialect::{format_qualified_table, split_table_name, OnConflict, SqlDialect};
+use crate::engine::value::SqlValue;
+use crate::engine::Routine;
+use crate::util::dialects::ansi::ANSI_DIALECT;
+use crate::util::dialects::mysql::MYSQL_DIALECT;
+use crate::util::dialects::postgres::POSTGRES_DIALECT;
+use crate::util::splitter::{self, Syntax, TokenKind};
+use std::fmt;
+use std::str::FromStr;
+
+/// `--compatible`: write a dump in another database's SQL, or in standard
+/// SQL, rather than the source's own. Table and view definitions are
+/// rewritten without engine-specific clauses, with the target's quoting and
+/// types, and rows use the target's literals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compatible {
+    Ansi,
+    Postgres,
+    Mysql,
+}
+
+impl fmt::Display for Compatible {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Compatible::Ansi => "ansi",
+            Compatible::Postgres => "postgres",
+            Compatible::Mysql => "mysql",
+        })
+    }
+}
+
+impl FromStr for Compatible {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ansi" => Ok(Compatible::Ansi),
+            "postgres" | "postgresql" => Ok(Compatible::Postgres),
+            "mysql" => Ok(Compatible::Mysql),
+            _ => Err(format!(
+                "unknown compatibility '{}' (expected ansi, postgres or mysql)",
+                s
+            )),
+        }
+    }
+}
+
+impl Compatible {
+    /// Dialect the dump is written in
+    pub fn dialect(self) -> &'static dyn SqlDialect {
+        match self {
+            Compatible::Ansi => &ANSI_TARGET,
+            Compatible::Postgres => &POSTGRES_TARGET,
+            Compatible::Mysql => &MYSQL_TARGET,
+        }
+    }
+
+    /// Whether `source` already speaks the target's SQL, so triggers,
+    /// routines and the other statements that cannot be rewritten may stay
+    pub fn is_native(self, source: &dyn SqlDialect) -> bool {
+        matches!(
+            (self, source.name()),
+            (Compatible::Postgres, "PostgreSQL") | (Compatible::Mysql, "MySQL")
+        )
+    }
+
+    /// Rewrite `create`, the definition of `table` or of a view as the
+    /// source wrote it, for the target. The result holds one statement per
+    /// line group, each ended by `;`, like PostgreSQL's `show_create_table`.
+    pub fn translate(self, table: &str, create: &str) -> String {
+        let mut statements = Vec::new();
+        for tokens in split_statements(tokenize(create)) {
+            let head: Vec<String> = tokens
+                .iter()
+                .filter_map(|token| match token {
+                    Token::Word(word) => Some(word.to_ascii_uppercase()),
+                    _ => None,
+                })
+                .take(4)
+                .collect();
+            let head: Vec<&str> = head.iter().map(String::as_str).collect();
+            match head.as_slice() {
+                ["CREATE", "TABLE", ..] | ["CREATE", "TEMPORARY", "TABLE", ..] => {
+                    statements.extend(self.translate_table(table, tokens))
+                }
+                ["CREATE", "INDEX", ..] | ["CREATE", "UNIQUE", "INDEX", ..] => {
+                    statements.push(render(&self.translate_index(tokens), self))
+                }
+                // Sequences and their values only exist as such in PostgreSQL;
+                // elsewhere the column becomes an identity
+                [_, "SEQUENCE", ..] | ["SELECT", ..] if self != Compatible::Postgres => {}
+                _ => statements.push(render(&self.rewrite(tokens), self)),
+            }
+        }
+        statements
+            .iter()
+            .map(|statement| format!("{};", statement.trim()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// `table` as named in the dump: MySQL has no default schema, so names
+    /// in PostgreSQL's lose it rather than pointing at a `public` database
+    fn local(self, table: &str) -> &str {
+        match split_table_name(table) {
+            (Some("public"), name) if self == Compatible::Mysql => name,
+            _ => table,
+        }
+    }
+
+    /// The `CREATE TABLE` with its body item by item, and the indexes that
+    /// cannot stay inside it
+    fn translate_table(self, table: &str, tokens: Vec<Token<'_>>) -> Vec<String> {
+        let Some(open) = tokens.iter().position(|t| *t == Token::Punct("(")) else {
+            return vec![render(&self.rewrite(tokens), self)];
+        };
+        let close = matching(&tokens, open);
+        let indent = match tokens.get(open + 1) {
+            Some(Token::Space(space)) if space.contains('\n') => *space,
+            _ => "\n  ",
+        };
+
+        let mut items = Vec::new();
+        let mut indexes = Vec::new();
+        for item in split_items(&tokens[open + 1..close]) {
+            let first = match item.first() {
+                Some(Token::Word(word)) => word.to_ascii_uppercase(),
+                _ => String::new(),
+            };
+            let keeps_keys = self == Compatible::Mysql;
+            match first.as_str() {
+                "KEY" | "INDEX" if !keeps_keys => indexes.push(self.index_statement(table, &item)),
+                "UNIQUE" if !keeps_keys => items.push(self.unique_constraint(&item)),
+                // Text search and spatial indexes have no portable form
+                "FULLTEXT" | "SPATIAL" if !keeps_keys => {}
+                "PRIMARY" | "UNIQUE" | "KEY" | "INDEX" | "FULLTEXT" | "SPATIAL" | "CONSTRAINT"
+                | "CHECK" | "FOREIGN" => items.push(render(&self.translate_key(item), self)),
+                _ => items.push(render(&self.translate_column(item), self)),
+            }
+        }
+
+        let head = render(&self.rewrite(tokens[..open].to_vec()), self);
+        let mut create = format!("{} ({}", head.trim_end(), indent);
+        let items: Vec<&str> = items.iter().map(|item| item.trim()).collect();
+        create.push_str(&items.join(&format!(",{}", indent)));
+        create.push_str("\n)");
+
+        let mut statements = vec![create];
+        statements.extend(indexes);
+        statements
+    }
+
+    /// A column: its name, the target's type for it, and its attributes
+    fn translate_column(self, item: Vec<Token<'_>>) -> Vec<Token<'_>> {
+        let start = skip_space(&item, 1);
+        if !matches!(item.get(start), Some(Token::Word(_))) {
+            return self.rewrite(item);
+        }
+        let (column_type, end) = parse_type(&item, start);
+
+        let mut tokens = vec![item[0].clone(), Token::Space(" ")];
+        tokens.push(Token::Text(self.map_type(&column_type)));
+
+        let mut i = end;
+        while i < item.len() {
+            let next = skip_space(&item, i + 1);
+            match &item[i] {
+                Token::Word(word) if word.eq_ignore_ascii_case("AUTO_INCREMENT") => {
+                    if self == Compatible::Mysql {
+                        tokens.push(item[i].clone());
+                    } else {
+                        tokens.push(Token::Text(IDENTITY.to_string()));
+                    }
+                    i += 1;
+                }
+                // GENERATED { ALWAYS | BY DEFAULT } AS IDENTITY [ (options) ]
+                Token::Word(word)
+                    if word.eq_ignore_ascii_case("GENERATED") && self == Compatible::Mysql =>
+                {
+                    let Some(identity) = (i..item.len())
+                        .take_while(|&j| !matches!(item[j], Token::Punct(_)))
+                        .find(|&j| is_word(&item[j], "IDENTITY"))
+                    else {
+                        tokens.push(item[i].clone());
+                        i += 1;
+                        continue;
+                    };
+                    tokens.push(Token::Text("AUTO_INCREMENT".to_string()));
+                    i = identity + 1;
+                    let options = skip_space(&item, i);
+                    if item.get(options) == Some(&Token::Punct("(")) {
+                        i = matching(&item, options) + 1;
+                    }
+                }
+                // A PostgreSQL serial column's default draws from its sequence
+                Token::Word(word)
+                    if word.eq_ignore_ascii_case("DEFAULT")
+                        && item.get(next).is_some_and(|t| is_word(t, "nextval"))
+                        && self != Compatible::Postgres =>
+                {
+                    let open = skip_space(&item, next + 1);
+                    tokens.push(Token::Text(match self {
+                        Compatible::Mysql => "AUTO_INCREMENT".to_string(),
+                        _ => IDENTITY.to_string(),
+                    }));
+                    i = matching(&item, open) + 1;
+                }
+                Token::Word(word)
+                    if word.eq_ignore_ascii_case("COMMENT") && self != Compatible::Mysql =>
+                {
+                    i = next + 1;
+                }
+                // ON UPDATE CURRENT_TIMESTAMP[(fsp)]
+                Token::Word(word)
+                    if word.eq_ignore_ascii_case("ON")
+                        && item.get(next).is_some_and(|t| is_word(t, "UPDATE"))
+                        && self != Compatible::Mysql =>
+                {
+                    i = skip_space(&item, next + 1) + 1;
+                    if item.get(i) == Some(&Token::Punct("(")) {
+                        i = matching(&item, i) + 1;
+                    }
+                }
+                token => {
+                    tokens.push(token.clone());
+                    i += 1;
+                }
+            }
+        }
+        self.rewrite(tokens)
+    }
+
+    /// A key or constraint kept inside the table body
+    fn translate_key(self, item: Vec<Token<'_>>) -> Vec<Token<'_>> {
+        if self == Compatible::Mysql {
+            return self.rewrite(item);
+        }
+        self.rewrite(strip_index_options(item))
+    }
+
+    /// `UNIQUE KEY name (columns)` as a standard constraint
+    fn unique_constraint(self, item: &[Token<'_>]) -> String {
+        let (name, columns) = self.key_parts(item);
+        match name {
+            Some(name) => format!(
+                "CONSTRAINT {} UNIQUE {}",
+                self.dialect().quote_identifier(&name),
+                columns
+            ),
+            None => format!("UNIQUE {}", columns),
+        }
+    }
+
+    /// `KEY name (columns)` from inside a MySQL table body as its own
+    /// `CREATE INDEX`
+    fn index_statement(self, table: &str, item: &[Token<'_>]) -> String {
+        let (name, columns) = self.key_parts(item);
+        format!(
+            "CREATE INDEX {}ON {} {}",
+            name.map(|name| format!("{} ", self.dialect().quote_identifier(&name)))
+                .unwrap_or_default(),
+            format_qualified_table(self.dialect(), self.local(table)),
+            columns
+        )
+    }
+
+    /// The name of a MySQL key item, if it has one, and its column list
+    /// without prefix lengths
+    fn key_parts(self, item: &[Token<'_>]) -> (Option<String>, String) {
+        let mut name = None;
+        let mut i = 0;
+        while i < item.len() {
+            match &item[i] {
+                Token::Punct("(") => break,
+                Token::Ident(ident) => name = Some(ident.clone()),
+                Token::Word(word)
+                    if !["UNIQUE", "KEY", "INDEX"]
+                        .iter()
+                        .any(|w| word.eq_ignore_ascii_case(w)) =>
+                {
+                    name = Some(word.to_string())
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        let close = matching(item, i);
+        let columns = item.get(i..=close).unwrap_or_default().to_vec();
+        (
+            name,
+            render(&self.rewrite(strip_index_options(columns)), self),
+        )
+    }
+
+    /// A `CREATE INDEX` statement, without the access method and `ONLY`
+    /// where they are PostgreSQL's own
+    fn translate_index(self, tokens: Vec<Token<'_>>) -> Vec<Token<'_>> {
+        if self == Compatible::Postgres {
+            return self.rewrite(tokens);
+        }
+        let tokens: Vec<Token> = tokens
+            .into_iter()
+            .filter(|token| !is_word(token, "ONLY"))
+            .collect();
+        self.rewrite(strip_index_options(tokens))
+    }
+
+    /// Rewrites that apply anywhere in a statement: quoting, versioned
+    /// comments, casts, and the clauses naming character sets, collations
+    /// and a view's definer
+    fn rewrite<'a>(self, tokens: Vec<Token<'a>>) -> Vec<Token<'a>> {
+        let mysql = self == Compatible::Mysql;
+        let mut out = Vec::with_capacity(tokens.len());
+        let mut i = 0;
+        while i < tokens.len() {
+            let next = skip_space(&tokens, i + 1);
+            match &tokens[i] {
+                // MySQL runs what is inside; nothing else reads it
+                Token::Versioned(body) => {
+                    if mysql {
+                        out.extend(self.rewrite(tokenize(body)));
+                    }
+                    i += 1;
+                }
+                token
+                    if mysql
+                        && raw(token) == "public"
+                        && tokens.get(i + 1) == Some(&Token::Punct(".")) =>
+                {
+                    i += 2;
+                }
+                Token::Punct("::") if self != Compatible::Postgres => {
+                    i = parse_type(&tokens, skip_space(&tokens, i + 1)).1;
+                }
+                Token::Word(word)
+                    if ["CHARSET", "COLLATE"]
+                        .iter()
+                        .any(|w| word.eq_ignore_ascii_case(w)) =>
+                {
+                    let value = match tokens.get(next) {
+                        Some(Token::Punct("=")) => skip_space(&tokens, next + 1),
+                        _ => next,
+                    };
+                    i = value + 1;
+                }
+                Token::Word(word)
+                    if word.eq_ignore_ascii_case("CHARACTER")
+                        && tokens.get(next).is_some_and(|t| is_word(t, "SET")) =>
+                {
+                    i = skip_space(&tokens, next + 1) + 1;
+                }
+                // A character set introducer, as in _utf8mb4'text'
+                Token::Word(word)
+                    if !mysql
+                        && word.starts_with('_')
+                        && matches!(tokens.get(i + 1), Some(Token::Str(_))) =>
+                {
+                    i += 1;
+                }
+                // ALGORITHM=..., DEFINER=user@host and SQL SECURITY ... of
+                // a MySQL view
+                Token::Word(word)
+                    if !mysql
+                        && ["ALGORITHM", "DEFINER"]
+                            .iter()
+                            .any(|w| word.eq_ignore_ascii_case(w))
+                        && tokens.get(next) == Some(&Token::Punct("=")) =>
+                {
+                    i = skip_space(&tokens, next + 1) + 1;
+                    if tokens.get(i) == Some(&Token::Punct("@")) {
+                        i += 2;
+                    }
+                }
+                Token::Word(word)
+                    if !mysql
+                        && word.eq_ignore_ascii_case("SQL")
+                        && tokens.get(next).is_some_and(|t| is_word(t, "SECURITY")) =>
+                {
+                    i = skip_space(&tokens, next + 1) + 1;
+                }
+                token => {
+                    out.push(token.clone());
+                    i += 1;
+                }
+            }
+        }
+        tidy(out)
+    }
+
+    /// The target's name for a column type, from either MySQL's or
+    /// PostgreSQL's spelling of it
+    fn map_type(self, column_type: &ColumnType) -> String {
+        let base = column_type.words.join(" ");
+        let args = column_type.args.as_deref();
+        let with_args = |name: &str| match args {
+            Some(args) => format!("{}({})", name, args),
+            None => name.to_string(),
+        };
+
+        let mapped = match self {
+            Compatible::Mysql => match base.as_str() {
+                "character varying" | "varchar" if args.is_none() => "longtext".to_string(),
+                "character varying" => with_args("varchar"),
+                "character" | "bpchar" => with_args("char"),
+                "text" if column_type.array => "json".to_string(),
+                "text" => "longtext".to_string(),
+                "timestamp without time zone" | "timestamp with time zone" => with_args("datetime"),
+                "time without time zone" | "time with time zone" => with_args("time"),
+                "double precision" => "double".to_string(),
+                "real" => "float".to_string(),
+                "bytea" => "longblob".to_string(),
+                "jsonb" => "json".to_string(),
+                "uuid" => "char(36)".to_string(),
+                // PostgreSQL's numeric without a precision is unbounded
+                "numeric" | "decimal" if args.is_none() => "decimal(65,30)".to_string(),
+                _ if column_type.array => "json".to_string(),
+                _ => with_args(&base),
+            },
+            Compatible::Postgres | Compatible::Ansi => {
+                let ansi = self == Compatible::Ansi;
+                match base.as_str() {
+                    "tinyint" | "smallint" if column_type.unsigned => "integer".to_string(),
+                    "tinyint" | "smallint" | "year" => "smallint".to_string(),
+                    "mediumint" | "int" | "integer" if column_type.unsigned => "bigint".to_string(),
+                    "mediumint" | "int" | "integer" => "integer".to_string(),
+                    "bigint" if column_type.unsigned => "numeric(20)".to_string(),
+                    "bigint" => "bigint".to_string(),
+                    "float" if args.is_none() => "real".to_string(),
+                    "double" | "double precision" => "double precision".to_string(),
+                    "datetime" | "timestamp without time zone" => with_args("timestamp"),
+                    "time without time zone" => with_args("time"),
+                    "tinytext" | "text" | "mediumtext" | "longtext" => {
+                        if ansi { "clob" } else { "text" }.to_string()
+                    }
+                    "tinyblob" | "blob" | "mediumblob" | "longblob" | "bytea" => {
+                        if ansi { "blob" } else { "bytea" }.to_string()
+                    }
+                    "binary" | "varbinary" if !ansi => "bytea".to_string(),
+                    "enum" | "set" => "varchar(255)".to_string(),
+                    "jsonb" if ansi => "json".to_string(),
+                    "uuid" if ansi => "char(36)".to_string(),
+                    _ => with_args(&base),
+                }
+            }
+        };
+        if self == Compatible::Postgres && column_type.array {
+            format!("{}[]", mapped)
+        } else if self == Compatible::Mysql && column_type.unsigned {
+            format!("{} unsigned", mapped)
+        } else {
+            mapped
+        }
+    }
+}
+
+/// The standard's auto-numbered column, for MySQL's `AUTO_INCREMENT`
+const IDENTITY: &str = "GENERATED BY DEFAULT AS IDENTITY";
+
+/// Words that continue a type name: `double precision`, `character
+/// varying`, `timestamp(3) without time zone`, `int unsigned`
+const TYPE_WORDS: [&str; 9] = [
+    "precision",
+    "varying",
+    "without",
+    "with",
+    "time",
+    "zone",
+    "unsigned",
+    "signed",
+    "zerofill",
+];
+
+/// A column type as written: its words in lower case, the text inside its
+/// parentheses, and whether it is unsigned or an array
+#[derive(Debug, Default, PartialEq)]
+struct ColumnType {
+    words: Vec<String>,
+    args: Option<String>,
+    unsigned: bool,
+    array: bool,
+}
+
+/// The type starting at the word `start`, and the index after it
+fn parse_type(tokens: &[Token<'_>], start: usize) -> (ColumnType, usize) {
+    let mut column_type = ColumnType::default();
+    let Some(Token::Word(first)) = tokens.get(start) else {
+        return (column_type, start);
+    };
+    column_type.words.push(first.to_ascii_lowercase());
+
+    let mut end = start + 1;
+    loop {
+        let next = skip_space(tokens, end);
+        match tokens.get(next) {
+            Some(Token::Punct("(")) if column_type.args.is_none() => {
+                let close = matching(tokens, next);
+                column_type.args = Some(render_raw(&tokens[next + 1..close]));
+                end = close + 1;
+            }
+            Some(Token::Punct("[")) => {
+                column_type.array = true;
+                end = (next..tokens.len())
+                    .find(|&i| tokens[i] == Token::Punct("]"))
+                    .map_or(tokens.len(), |close| close + 1);
+            }
+            Some(Token::Word(word)) if TYPE_WORDS.iter().any(|w| word.eq_ignore_ascii_case(w)) => {
+                match word.to_ascii_lowercase().as_str() {
+                    "unsigned" => column_type.unsigned = true,
+                    "signed" | "zerofill" => {}
+                    word => column_type.words.push(word.to_string()),
+                }
+                end = next + 1;
+            }
+            _ => break,
+        }
+    }
+    (column_type, end)
+}
+
+/// Drop what only MySQL reads in a key: `USING BTREE`, and the prefix
+/// length after a column, as in (`name`(10))
+fn strip_index_options(tokens: Vec<Token<'_>>) -> Vec<Token<'_>> {
+    let mut out: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        let next = skip_space(&tokens, i + 1);
+        match &tokens[i] {
+            Token::Word(word) if word.eq_ignore_ascii_case("USING") => i = next + 1,
+            Token::Punct("(")
+                if matches!(out.last(), Some(Token::Ident(_)))
+                    && matches!(tokens.get(i + 1), Some(Token::Word(w)) if w.bytes().all(|b| b.is_ascii_digit()))
+                    && tokens.get(i + 2) == Some(&Token::Punct(")")) =>
+            {
+                i += 3;
+            }
+            token => {
+                out.push(token.clone());
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token<'a> {
+    /// Whitespace, and comments other than versioned ones
+    Space(&'a str),
+    Word(&'a str),
+    /// Quoted identifier, without its quotes
+    Ident(String),
+    /// String literal with its quotes
+    Str(&'a str),
+    /// Body of a MySQL versioned comment, `/*!50100 ... */`
+    Versioned(&'a str),
+    Punct(&'a str),
+    /// Text written by the rewrite
+    Text(String),
+}
+
+/// How definitions are read: backticks and double quotes both quote
+/// identifiers, as in MySQL's and PostgreSQL's own output
+const SOURCE_SYNTAX: Syntax = Syntax {
+    dollar_quotes: false,
+    ..Syntax::STANDARD
+};
+
+/// Split SQL into tokens
+fn tokenize(sql: &str) -> Vec<Token<'_>> {
+    splitter::tokenize(sql, SOURCE_SYNTAX)
+        .into_iter()
+        .map(|token| match token.kind {
+            TokenKind::Space => Token::Space(token.text),
+            TokenKind::LineComment => Token::Space(" "),
+            TokenKind::BlockComment => match token.text.strip_prefix("/*!") {
+                Some(body) => {
+                    let body = body.strip_suffix("*/").unwrap_or(body);
+                    Token::Versioned(body.trim_start_matches(|c: char| c.is_ascii_digit()))
+                }
+                None => Token::Space(" "),
+            },
+            TokenKind::Word => Token::Word(token.text),
+            TokenKind::Str => Token::Str(token.text),
+            TokenKind::QuotedName => {
+                // A doubled quote stands for one inside
+                let quote = &token.text[..1];
+                let inner = &token.text[1..];
+                let inner = inner.strip_suffix(quote).unwrap_or(inner);
+                Token::Ident(inner.replace(&quote.repeat(2), quote))
+            }
+            TokenKind::Punct => Token::Punct(token.text),
+        })
+        .collect()
+}
+
+fn render(tokens: &[Token<'_>], target: Compatible) -> String {
+    let dialect = target.dialect();
+    let mut sql = String::new();
+    for token in tokens {
+        match token {
+            Token::Ident(name) => sql.push_str(&dialect.quote_identifier(name)),
+            Token::Versioned(body) => {
+                sql.push_str("/*!");
+                sql.push_str(body);
+                sql.push_str("*/");
+            }
+            token => sql.push_str(raw(token)),
+        }
+    }
+    sql
+}
+
+/// Tokens as they were written, for the arguments of a type
+fn render_raw(tokens: &[Token<'_>]) -> String {
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Ident(name) => name.as_str(),
+            token => raw(token),
+        })
+        .collect()
+}
+
+fn raw<'t>(token: &'t Token<'_>) -> &'t str {
+    match token {
+        Token::Space(text)
+        | Token::Word(text)
+        | Token::Str(text)
+        | Token::Versioned(text)
+        | Token::Punct(text) => text,
+        Token::Ident(text) | Token::Text(text) => text,
+    }
+}
+
+/// One space where clauses were taken out, and none left before a comma or
+/// a closing parenthesis on the same line
+fn tidy(tokens: Vec<Token<'_>>) -> Vec<Token<'_>> {
+    let mut out: Vec<Token> = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        match (&token, out.last()) {
+            (Token::Space(space), Some(Token::Space(last))) => {
+                if space.contains('\n') && !last.contains('\n') {
+                    out.pop();
+                    out.push(token);
+                }
+            }
+            (Token::Punct("," | ")"), Some(Token::Space(last))) if !last.contains('\n') => {
+                out.pop();
+                out.push(token);
+            }
+            (Token::Text(_) | Token::Word(_), Some(Token::Text(_) | Token::Word(_))) => {
+                out.push(Token::Space(" "));
+                out.push(token);
+            }
+            _ => out.push(token),
+        }
+    }
+    out
+}
+
+fn is_word(token: &Token<'_>, word: &str) -> bool {
+    matches!(token, Token::Word(w) if w.eq_ignore_ascii_case(word))
+}
+
+/// Index of the first token at or after `i` that is not whitespace
+fn skip_space(tokens: &[Token<'_>], i: usize) -> usize {
+    (i..tokens.len())
+        .find(|&i| !matches!(tokens[i], Token::Space(_)))
+        .unwrap_or(tokens.len())
+}
+
+/// Index of the parenthesis closing the one at `open`, or of the last token
+/// when it is never closed
+fn matching(tokens: &[Token<'_>], open: usize) -> usize {
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::Punct("(") => depth += 1,
+            Token::Punct(")") => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len().saturating_sub(1)
+}
+
+/// The statements of `tokens`, split at semicolons outside parentheses
+fn split_statements(tokens: Vec<Token<'_>>) -> Vec<Vec<Token<'_>>> {
+    split_at_depth(tokens, ";")
+        .into_iter()
+        .filter(|statement| !statement.is_empty())
+        .collect()
+}
+
+/// The comma-separated items of a table body, without surrounding space
+fn split_items<'a>(tokens: &[Token<'a>]) -> Vec<Vec<Token<'a>>> {
+    split_at_depth(tokens.to_vec(), ",")
+}
+
+fn split_at_depth<'a>(tokens: Vec<Token<'a>>, separator: &str) -> Vec<Vec<Token<'a>>> {
+    let mut parts = vec![Vec::new()];
+    let mut depth = 0usize;
+    for token in tokens {
+        match &token {
+            Token::Punct("(") => depth += 1,
+            Token::Punct(")") => depth = depth.saturating_sub(1),
+            Token::Punct(p) if depth == 0 && *p == separator => {
+                parts.push(Vec::new());
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(part) = parts.last_mut() {
+            part.push(token);
+        }
+    }
+    for part in &mut parts {
+        while matches!(part.last(), Some(Token::Space(_))) {
+            part.pop();
+        }
+        let start = skip_space(part, 0);
+        part.drain(..start);
+    }
+    parts
+}
+
+/// The dialect of a `--compatible` dump: the target's own, with names in
+/// PostgreSQL's `public` schema left unqualified where schemas are
+/// databases
+struct Target(Compatible);
+
+static ANSI_TARGET: Target = Target(Compatible::Ansi);
+static POSTGRES_TARGET: Target = Target(Compatible::Postgres);
+static MYSQL_TARGET: Target = Target(Compatible::Mysql);
+
+impl Target {
+    fn base(&self) -> &'static dyn SqlDialect {
+        match self.0 {
+            Compatible::Ansi => &ANSI_DIALECT,
+            Compatible::Postgres => &POSTGRES_DIALECT,
+            Compatible::Mysql => &MYSQL_DIALECT,
+        }
+    }
+}
+
+impl SqlDialect for Target {
+    fn name(&self) -> &'static str {
+        self.base().name()
+    }
+
+    fn quote_identifier(&self, name: &str) -> String {
+        self.base().quote_identifier(name)
+    }
+
+    fn to_literal(&self, value: &SqlValue) -> String {
+        self.base().to_literal(value)
+    }
+
+    fn insert_values_sql(&self, table: &str, columns: &[String], rows: &[Vec<SqlValue>]) -> String {
+        self.base()
+            .insert_values_sql(self.0.local(table), columns, rows)
+    }
+
+    fn drop_table_statement(&self, table: &str) -> String {
+        self.base().drop_table_statement(self.0.local(table))
+    }
+
+    fn drop_view_statement(&self, view: &str) -> String {
+        self.base().drop_view_statement(self.0.local(view))
+    }
+
+    fn drop_routine_statement(&self, routine: &Routine) -> Option<String> {
+        self.base().drop_routine_statement(routine)
+    }
+
+    fn create_schema_statement(&self, schema: &str) -> Option<String> {
+        match self.0 {
+            Compatible::Ansi => None,
+            Compatible::Mysql if schema == "public" => None,
+            _ => self.base().create_schema_statement(schema),
+        }
+    }
+
+    fn database_statements(&self, database: &str) -> Vec<String> {
+        self.base().database_statements(database)
+    }
+
+    fn strip_definer(&self, sql: &str) -> String {
+        self.base().strip_definer(sql)
+    }
+
+    fn conflict_clauses(
+        &self,
+        on_conflict: OnConflict,
+        columns: &[String],
+        key: &[String],
+    ) -> Option<(&'static str, String)> {
+        self.base().conflict_clauses(on_conflict, columns, key)
+    }
+
+    fn compound_delimiter(&self) -> Option<&'static str> {
+        self.base().compound_delimiter()
+    }
+
+    fn split_secondary_indexes(&self, table: &str, create: &str) -> (String, Vec<String>) {
+        self.base()
+            .split_secondary_indexes(self.0.local(table), create)
+    }
+
+    /// Outside MySQL, rewritten tables lay out their foreign keys as
+    /// PostgreSQL does, one `CONSTRAINT` per line
+    fn split_foreign_keys(&self, table: &str, create: &str) -> (String, Vec<String>) {
+        match self.0 {
+            Compatible::Mysql => MYSQL_DIALECT.split_foreign_keys(self.0.local(table), create),
+            _ => POSTGRES_DIALECT.split_foreign_keys(table, create),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MYSQL_TABLE: &str = "CREATE TABLE `orders` (\n  `id` int unsigned NOT NULL AUTO_INCREMENT,\n  `code` varchar(20) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin NOT NULL COMMENT 'customer''s code',\n  `placed` datetime(3) DEFAULT CURRENT_TIMESTAMP(3) ON UPDATE CURRENT_TIMESTAMP(3),\n  `state` enum('new','paid') DEFAULT 'new',\n  `body` longtext,\n  `flag` tinyint(1) NOT NULL DEFAULT '0',\n  PRIMARY KEY (`id`),\n  UNIQUE KEY `uq_code` (`code`),\n  KEY `idx_placed` (`placed`,`code`(10)) USING BTREE,\n  FULLTEXT KEY `ft_body` (`body`),\n  CONSTRAINT `fk_customer` FOREIGN KEY (`id`) REFERENCES `customers` (`id`)\n) ENGINE=InnoDB AUTO_INCREMENT=42 DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_0900_ai_ci /*!50100 PARTITION BY HASH (`id`) */";
+
+    #[test]
+    fn rewrites_mysql_tables_for_postgres() {
+        assert_eq!(
+            Compatible::Postgres.translate("orders", MYSQL_TABLE),
+            "CREATE TABLE \"orders\" (\n  \"id\" bigint NOT NULL GENERATED BY DEFAULT AS IDENTITY,\n  \"code\" varchar(20) NOT NULL,\n  \"placed\" timestamp(3) DEFAULT CURRENT_TIMESTAMP(3),\n  \"state\" varchar(255) DEFAULT 'new',\n  \"body\" text,\n  \"flag\" smallint NOT NULL DEFAULT '0',\n  PRIMARY KEY (\"id\"),\n  CONSTRAINT \"uq_code\" UNIQUE (\"code\"),\n  CONSTRAINT \"fk_customer\" FOREIGN KEY (\"id\") REFERENCES \"customers\" (\"id\")\n);\nCREATE INDEX \"idx_placed\" ON \"orders\" (\"placed\",\"code\");"
+        );
+    }
+
+    #[test]
+    fn keeps_mysql_tables_without_their_options() {
+        assert_eq!(
+            Compatible::Mysql.translate("orders", "CREATE TABLE `t` (\n  `id` int NOT NULL AUTO_INCREMENT,\n  `n` varchar(5) COLLATE utf8mb4_bin DEFAULT NULL,\n  KEY `idx_n` (`n`)\n) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4"),
+            "CREATE TABLE `t` (\n  `id` int NOT NULL AUTO_INCREMENT,\n  `n` varchar(5) DEFAULT NULL,\n  KEY `idx_n` (`n`)\n);"
+        );
+    }
+
+    #[test]
+    fn rewrites_postgres_tables_for_mysql() {
+        let create = "CREATE SEQUENCE IF NOT EXISTS \"public\".\"ser_id_seq\" AS integer START WITH 1;\nCREATE TABLE IF NOT EXISTS \"public\".\"ser\" (\n    \"id\" integer DEFAULT nextval('ser_id_seq'::regclass) NOT NULL,\n    \"v\" character varying(5) DEFAULT 'x'::character varying,\n    \"ts\" timestamp without time zone,\n    \"tags\" text[],\n    \"amount\" numeric,\n    CONSTRAINT \"ser_pkey\" PRIMARY KEY (id)\n);\nALTER SEQUENCE \"public\".\"ser_id_seq\" OWNED BY \"public\".\"ser\".\"id\";\nCREATE INDEX ser_v_idx ON public.ser USING btree (v);";
+        assert_eq!(
+            Compatible::Mysql.translate("public.ser", create),
+            "CREATE TABLE IF NOT EXISTS `ser` (\n    `id` integer AUTO_INCREMENT NOT NULL,\n    `v` varchar(5) DEFAULT 'x',\n    `ts` datetime,\n    `tags` json,\n    `amount` decimal(65,30),\n    CONSTRAINT `ser_pkey` PRIMARY KEY (id)\n);\nCREATE INDEX ser_v_idx ON ser (v);"
+        );
+        assert_eq!(
+            Compatible::Ansi.translate("public.ser", "CREATE TABLE \"public\".\"t\" (\n    \"id\" integer GENERATED BY DEFAULT AS IDENTITY NOT NULL,\n    \"b\" bytea\n);"),
+            "CREATE TABLE \"public\".\"t\" (\n    \"id\" integer GENERATED BY DEFAULT AS IDENTITY NOT NULL,\n    \"b\" blob\n);"
+        );
+    }
+
+    #[test]
+    fn rewrites_views() {
+        let view = "CREATE ALGORITHM=UNDEFINED DEFINER=`root`@`%` SQL SECURITY DEFINER VIEW `v` AS select `t`.`a` AS `a`,_utf8mb4'x' AS `b` from `t`";
+        assert_eq!(
+            Compatible::Ansi.translate("v", view),
+            "CREATE VIEW \"v\" AS select \"t\".\"a\" AS \"a\",'x' AS \"b\" from \"t\";"
+        );
+        assert_eq!(
+            Compatible::Mysql.translate(
+                "public.v",
+                "CREATE OR REPLACE VIEW public.v AS\n SELECT id,\n    (v)::text AS v\n   FROM big"
+            ),
+            "CREATE OR REPLACE VIEW v AS\n SELECT id,\n    (v) AS v\n   FROM big;"
+        );
+    }
+
+    #[test]
+    fn parses_compatibility() {
+        assert_eq!("ANSI".parse::<Compatible>(), Ok(Compatible::Ansi));
+        assert_eq!("postgresql".parse::<Compatible>(), Ok(Compatible::Postgres));
+        assert!("oracle".parse::<Compatible>().is_err());
+    }
+}
@@ -0,0 +1,560 @@
+use crate::util::encrypt::{self, Decryption, Encryption};
+use crate::util::storage::{self, Download, Upload};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::{self, JoinHandle};
+use xz2::read::XzDecoder;
+use xz2::write::XzEncoder;
+
+/// `--output -` / `--input -`: stream through stdout or stdin instead of a file
+pub const STDIO: &str = "-";
+
+/// Most bytes collected before a chunk is handed to the writer thread
+const PIPELINE_CHUNK_BYTES: usize = 1024 * 1024;
+
+/// Compression applied to dump files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+    Lz4,
+}
+
+/// `--compress-level N`, or `--fast`/`--best` for either end of the codec's range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Fast,
+    Best,
+    Exact(u32),
+}
+
+impl Codec {
+    /// Levels the codec accepts, fastest first; `None` when it has no levels
+    pub fn levels(self) -> Option<RangeInclusive<u32>> {
+        match self {
+            Codec::Gzip => Some(1..=9),
+            Codec::Zstd => Some(1..=22),
+            Codec::Xz => Some(0..=9),
+            Codec::None | Codec::Lz4 => None,
+        }
+    }
+
+    /// The numeric level `level` stands for with this codec
+    pub fn level(self, level: Level) -> Result<u32, String> {
+        let Some(range) = self.levels() else {
+            return Err(match self {
+                Codec::None => {
+                    "A compression level needs a compressed output (see --compress)".to_string()
+                }
+                _ => format!("{} compression has no levels", self),
+            });
+        };
+
+        match level {
+            Level::Fast => Ok(*range.start()),
+            Level::Best => Ok(*range.end()),
+            Level::Exact(n) if range.contains(&n) => Ok(n),
+            Level::Exact(n) => Err(format!(
+                "{} compression level must be between {} and {}, got {}",
+                self,
+                range.start(),
+                range.end(),
+                n
+            )),
+        }
+    }
+
+    /// File name extension of this codec's output, e.g. `.gz`
+    pub fn extension(self) -> &'static str {
+        match self {
+            Codec::None => "",
+            Codec::Gzip => ".gz",
+            Codec::Zstd => ".zst",
+            Codec::Xz => ".xz",
+            Codec::Lz4 => ".lz4",
+        }
+    }
+
+    /// Pick the codec from a file name's extension (`.gz`, `.zst`, `.xz`,
+    /// `.lz4`), looking past an `.age` one
+    pub fn from_path(path: &str) -> Self {
+        let lower = storage::display_location(path).to_ascii_lowercase();
+        let path = lower.strip_suffix(".age").unwrap_or(&lower);
+        let extension = path
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("gz") => Codec::Gzip,
+            Some("zst") | Some("zstd") => Codec::Zstd,
+            Some("xz") => Codec::Xz,
+            Some("lz4") => Codec::Lz4,
+            _ => Codec::None,
+        }
+    }
+
+    /// Create `path` (or use stdout for `-`, or upload to an object storage
+    /// URL) and wrap it in this codec's
+    /// encoder, at `level` (as returned by `level`) or the codec's default,
+    /// encrypting the compressed stream when `encryption` is given
+    pub fn create(
+        self,
+        path: &str,
+        level: Option<u32>,
+        encryption: Option<&Encryption>,
+    ) -> io::Result<Encoder> {
+        let file = if path == STDIO {
+            Destination::Local(Box::new(io::stdout()))
+        } else if storage::is_remote(path) {
+            Destination::Remote(Upload::create(path)?)
+        } else {
+            Destination::Local(Box::new(File::create(path)?))
+        };
+        let file = BufWriter::new(file);
+        let file = match encryption {
+            Some(encryption) => Sink::Age(encryption.seal(file)?),
+            None => Sink::Plain(file),
+        };
+        Ok(match self {
+            Codec::None => Encoder::Plain(file),
+            Codec::Gzip => {
+                let level =
+                    level.map_or_else(flate2::Compression::default, flate2::Compression::new);
+                Encoder::Gzip(GzEncoder::new(file, level))
+            }
+            Codec::Zstd => {
+                let level = level.map_or(zstd::DEFAULT_COMPRESSION_LEVEL, |n| n as i32);
+                Encoder::Zstd(zstd::Encoder::new(file, level)?)
+            }
+            Codec::Xz => Encoder::Xz(XzEncoder::new(file, level.unwrap_or(6))),
+            Codec::Lz4 => Encoder::Lz4(lz4_flex::frame::FrameEncoder::new(file)),
+        })
+    }
+
+    /// Open `path` (or stdin for `-`, or download an object storage URL) and
+    /// decompress it with this codec,
+    /// decrypting it first with `decryption` if it is an age file
+    pub fn open(self, path: &str, decryption: Option<&Decryption>) -> io::Result<Box<dyn Read>> {
+        let file: Box<dyn Read> = if path == STDIO {
+            Box::new(io::stdin())
+        } else if storage::is_remote(path) || storage::is_http(path) {
+            Box::new(Download::open(path)?)
+        } else {
+            Box::new(File::open(path)?)
+        };
+        let mut file = BufReader::new(file);
+        let file: Box<dyn Read> = match (encrypt::is_encrypted(file.fill_buf()?), decryption) {
+            (false, _) => Box::new(file),
+            (true, Some(decryption)) => Box::new(decryption.open(file)?),
+            (true, None) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the dump is encrypted; pass --identity with a key it was encrypted to",
+                ))
+            }
+        };
+        Ok(match self {
+            Codec::None => Box::new(file),
+            Codec::Gzip => Box::new(GzDecoder::new(file)),
+            Codec::Zstd => Box::new(zstd::Decoder::new(file)?),
+            Codec::Xz => Box::new(XzDecoder::new(BufReader::new(file))),
+            Codec::Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(file)),
+        })
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Codec::None => "none",
+            Codec::Gzip => "gzip",
+            Codec::Zstd => "zstd",
+            Codec::Xz => "xz",
+            Codec::Lz4 => "lz4",
+        })
+    }
+}
+
+impl FromStr for Codec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Codec::None),
+            "gzip" | "gz" => Ok(Codec::Gzip),
+            "zstd" | "zst" => Ok(Codec::Zstd),
+            "xz" => Ok(Codec::Xz),
+            "lz4" => Ok(Codec::Lz4),
+            _ => Err(format!(
+                "unknown compression '{}' (expected none, gzip, zstd, xz or lz4)",
+                s
+            )),
+        }
+    }
+}
+
+/// A dump file being written through one of the codecs. Call `finish` once
+/// done so the compressed stream is terminated and write errors surface.
+pub enum Encoder {
+    Plain(Sink),
+    Gzip(GzEncoder<Sink>),
+    Zstd(zstd::Encoder<'static, Sink>),
+    Xz(XzEncoder<Sink>),
+    Lz4(lz4_flex::frame::FrameEncoder<Sink>),
+}
+
+/// The file, stdout or upload an `Encoder` writes to, possibly through an
+/// age encryption stream
+pub enum Sink {
+    Plain(BufWriter<Destination>),
+    Age(age::stream::StreamWriter<BufWriter<Destination>>),
+}
+
+impl Sink {
+    /// End the encryption stream, if any, and flush the file
+    fn finish(self) -> io::Result<()> {
+        let file = match self {
+            Sink::Plain(w) => w,
+            Sink::Age(w) => w.finish()?,
+        };
+        file.into_inner().map_err(|e| e.into_error())?.finish()
+    }
+}
+
+/// Where a dump's bytes end up
+pub enum Destination {
+    /// A local file or stdout
+    Local(Box<dyn Write + Send>),
+    /// An object in S3, GCS or Azure
+    Remote(Upload),
+}
+
+impl Destination {
+    /// Flush the file, or complete the upload
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Destination::Local(mut w) => w.flush(),
+            Destination::Remote(upload) => upload.finish(),
+        }
+    }
+}
+
+impl Write for Destination {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Destination::Local(w) => w.write(buf),
+            Destination::Remote(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Destination::Local(w) => w.flush(),
+            Destination::Remote(w) => w.flush(),
+        }
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Plain(w) => w.write(buf),
+            Sink::Age(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Plain(w) => w.flush(),
+            Sink::Age(w) => w.flush(),
+        }
+    }
+}
+
+impl Encoder {
+    fn inner(&mut self) -> &mut dyn Write {
+        match self {
+            Encoder::Plain(w) => w,
+            Encoder::Gzip(w) => w,
+            Encoder::Zstd(w) => w,
+            Encoder::Xz(w) => w,
+            Encoder::Lz4(w) => w,
+        }
+    }
+
+    /// Write the codec's trailer and flush the file
+    pub fn finish(self) -> io::Result<()> {
+        let file = match self {
+            Encoder::Plain(w) => w,
+            Encoder::Gzip(w) => w.finish()?,
+            Encoder::Zstd(w) => w.finish()?,
+            Encoder::Xz(w) => w.finish()?,
+            Encoder::Lz4(w) => w.finish()?,
+        };
+        file.finish()
+    }
+}
+
+impl Write for Encoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner().flush()
+    }
+}
+
+/// Runs an `Encoder` on its own thread. Writes are gathered into chunks and
+/// passed over a bounded channel, so compressing and writing the file overlap
+/// with producing the SQL and a slow disk never blocks the async runtime for
+/// long; when the thread falls behind by more than the buffer, writers block
+/// rather than buffering without limit.
+pub struct BackgroundEncoder {
+    buffer: Vec<u8>,
+    chunk_bytes: usize,
+    sender: Option<SyncSender<Message>>,
+    worker: Option<JoinHandle<io::Result<()>>>,
+}
+
+/// What the writer thread is handed
+enum Message {
+    Chunk(Vec<u8>),
+    /// Flush everything so far into the file, then reply with how many
+    /// bytes went in
+    Sync(SyncSender<io::Result<u64>>),
+}
+
+/// Waits for what was written to a [`BackgroundEncoder`] to reach its file.
+/// Must be dropped before the encoder is finished.
+pub struct Syncer {
+    sender: SyncSender<Message>,
+}
+
+impl Syncer {
+    /// Flush the codec and the file up to what the writer thread has been
+    /// handed, returning the bytes written before compression. The
+    /// encoder's own buffer must be flushed first.
+    pub fn sync(&self) -> io::Result<u64> {
+        let (reply, written) = mpsc::sync_channel(1);
+        self.sender
+            .send(Message::Sync(reply))
+            .map_err(|_| io::Error::other("writer thread stopped early"))?;
+        written
+            .recv()
+            .map_err(|_| io::Error::other("writer thread stopped early"))?
+    }
+}
+
+impl BackgroundEncoder {
+    /// Start the thread, letting about `buffer_bytes` queue up for it
+    pub fn spawn(mut encoder: Encoder, buffer_bytes: usize) -> io::Result<Self> {
+        let chunk_bytes = buffer_bytes.clamp(1, PIPELINE_CHUNK_BYTES);
+        let depth = (buffer_bytes / chunk_bytes).max(1);
+        let (sender, receiver) = mpsc::sync_channel::<Message>(depth);
+        let worker = thread::Builder::new()
+            .name("dump-writer".to_string())
+            .spawn(move || {
+                let mut written = 0u64;
+                for message in receiver {
+                    match message {
+                        Message::Chunk(chunk) => {
+                            encoder.write_all(&chunk)?;
+                            written += chunk.len() as u64;
+                        }
+                        Message::Sync(reply) => {
+                            let _ = reply.send(encoder.flush().map(|()| written));
+                        }
+                    }
+                }
+                encoder.finish()
+            })?;
+
+        Ok(BackgroundEncoder {
+            buffer: Vec::with_capacity(chunk_bytes),
+            chunk_bytes,
+            sender: Some(sender),
+            worker: Some(worker),
+        })
+    }
+
+    /// Hand the buffered bytes to the writer thread
+    fn send(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(self.chunk_bytes));
+        let sent = match &self.sender {
+            Some(sender) => sender.send(Message::Chunk(chunk)).is_ok(),
+            None => false,
+        };
+        if sent {
+            return Ok(());
+        }
+
+        // The thread only hangs up early when writing failed; report why
+        match self.join() {
+            Err(e) => Err(e),
+            Ok(()) => Err(io::Error::other("writer thread stopped early")),
+        }
+    }
+
+    /// A handle for waiting on the file to catch up with the writes
+    pub fn syncer(&self) -> Option<Syncer> {
+        self.sender.clone().map(|sender| Syncer { sender })
+    }
+
+    fn join(&mut self) -> io::Result<()> {
+        self.sender = None;
+        match self.worker.take() {
+            Some(worker) => worker
+                .join()
+                .map_err(|_| io::Error::other("writer thread panicked"))?,
+            None => Err(io::Error::other("writer thread already stopped")),
+        }
+    }
+
+    /// Compress what is left, terminate the stream and wait for the file to
+    /// be written
+    pub fn finish(mut self) -> io::Result<()> {
+        self.send()?;
+        self.join()
+    }
+}
+
+impl Write for BackgroundEncoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= self.chunk_bytes {
+            self.send()?;
+        }
+        Ok(buf.len())
+    }
+
+    /// Passes buffered bytes on to the writer thread; they reach the
+    /// file asynchronously
+    fn flush(&mut self) -> io::Result<()> {
+        self.send()
+    }
+}
+
+impl Drop for BackgroundEncoder {
+    fn drop(&mut self) {
+        // Abandoned without `finish` (e.g. the dump failed): stop the thread
+        // and let it close the file
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_every_codec() {
+        let dir = std::env::temp_dir();
+        let sql = "INSERT INTO t VALUES (1, 'a');\n".repeat(1000);
+
+        for codec in [Codec::None, Codec::Gzip, Codec::Zstd, Codec::Xz, Codec::Lz4] {
+            let path = dir.join(format!("migrasquiel-{}-{}.sql", std::process::id(), codec));
+            let path = path.to_str().unwrap();
+
+            let mut encoder = codec.create(path, None, None).unwrap();
+            encoder.write_all(sql.as_bytes()).unwrap();
+            encoder.finish().unwrap();
+
+            let mut restored = String::new();
+            codec
+                .open(path, None)
+                .unwrap()
+                .read_to_string(&mut restored)
+                .unwrap();
+            std::fs::remove_file(path).unwrap();
+            assert_eq!(restored, sql, "{}", codec);
+        }
+
+        assert_eq!(Codec::from_path("dump.sql.zst"), Codec::Zstd);
+        assert_eq!(Codec::from_path("dump.SQL.GZ"), Codec::Gzip);
+        assert_eq!(Codec::from_path("dump.sql"), Codec::None);
+        assert_eq!(Codec::from_path("dump.sql.xz.age"), Codec::Xz);
+        assert_eq!(
+            Codec::from_path("https://example.com/dump.sql.gz?X-Amz-Expires=60"),
+            Codec::Gzip
+        );
+        assert_eq!("zstd".parse::<Codec>(), Ok(Codec::Zstd));
+    }
+
+    #[test]
+    fn encrypts_after_compressing() {
+        let path =
+            std::env::temp_dir().join(format!("migrasquiel-{}.sql.gz.age", std::process::id()));
+        let path = path.to_str().unwrap();
+        let sql = "INSERT INTO t VALUES (1, 'a');\n".repeat(1000);
+        let (encryption, decryption) = Encryption::ephemeral();
+
+        let codec = Codec::from_path(path);
+        let mut encoder = codec.create(path, None, Some(&encryption)).unwrap();
+        encoder.write_all(sql.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let err = codec.open(path, None).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let mut restored = String::new();
+        codec
+            .open(path, Some(&decryption))
+            .unwrap()
+            .read_to_string(&mut restored)
+            .unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(restored, sql);
+    }
+
+    #[test]
+    fn resolves_compression_levels() {
+        assert_eq!(Codec::Zstd.level(Level::Best), Ok(22));
+        assert_eq!(Codec::Gzip.level(Level::Fast), Ok(1));
+        assert_eq!(Codec::Xz.level(Level::Exact(0)), Ok(0));
+        assert!(Codec::Gzip.level(Level::Exact(12)).is_err());
+        assert!(Codec::Lz4.level(Level::Fast).is_err());
+        assert!(Codec::None.level(Level::Exact(3)).is_err());
+    }
+
+    #[test]
+    fn compresses_on_a_background_thread() {
+        let path =
+            std::env::temp_dir().join(format!("migrasquiel-bg-{}.sql.zst", std::process::id()));
+        let path = path.to_str().unwrap();
+        // Several chunks, with writes straddling chunk boundaries, through a
+        // buffer smaller than one default chunk
+        let line = "INSERT INTO t VALUES (1, 'abcdefghijklmnopqrstuvwxyz');\n";
+        let count = 3 * PIPELINE_CHUNK_BYTES / line.len();
+
+        let encoder = Codec::Zstd.create(path, None, None).unwrap();
+        let mut writer = BackgroundEncoder::spawn(encoder, 64 * 1024).unwrap();
+        for _ in 0..count {
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut restored = String::new();
+        Codec::Zstd
+            .open(path, None)
+            .unwrap()
+            .read_to_string(&mut restored)
+            .unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(restored, line.repeat(count));
+    }
+}
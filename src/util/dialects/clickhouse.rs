@@ -0,0 +1,137 @@
+use crate::engine::dialect::{insert_target, SqlDialect};
+use crate::engine::value::SqlValue;
+
+#[derive(Debug)]
+pub struct ClickHouseDialect;
+
+pub static CLICKHOUSE_DIALECT: ClickHouseDialect = ClickHouseDialect;
+
+impl SqlDialect for ClickHouseDialect {
+    fn name(&self) -> &'static str {
+        "ClickHouse"
+    }
+
+    fn quote_identifier(&self, name: &str) -> String {
+        format!("`{}`", name.replace('\\', "\\\\").replace('`', "\\`"))
+    }
+
+    fn backslash_escapes(&self) -> bool {
+        true
+    }
+
+    fn to_literal(&self, value: &SqlValue) -> String {
+        match value {
+            SqlValue::Null => "NULL".to_string(),
+            SqlValue::Bool(v) => {
+                if *v {
+                    "true".to_string()
+                } else {
+                    "false".to_string()
+                }
+            }
+            SqlValue::Int(v) => v.to_string(),
+            SqlValue::Float(v) => {
+                if v.is_nan() {
+                    "nan".to_string()
+                } else if v.is_infinite() {
+                    if v.is_sign_positive() {
+                        "inf".to_string()
+                    } else {
+                        "-inf".to_string()
+                    }
+                } else {
+                    v.to_string()
+                }
+            }
+            SqlValue::Decimal(v) => v.clone(),
+            SqlValue::String(v) => escape_string(v),
+            SqlValue::Bytes(bytes) => format!("unhex('{}')", hex::encode(bytes)),
+            SqlValue::Date { y, m, d } => format!("'{:04}-{:02}-{:02}'", y, m, d),
+            // No TIME type; times are stored as strings
+            SqlValue::Time { neg, h, m, s, us } => {
+                let sign = if *neg { "-" } else { "" };
+                if *us == 0 {
+                    format!("'{}{:02}:{:02}:{:02}'", sign, h, m, s)
+                } else {
+                    format!("'{}{:02}:{:02}:{:02}.{:06}'", sign, h, m, s, us)
+                }
+            }
+            SqlValue::Timestamp {
+                y,
+                m,
+                d,
+                hh,
+                mm,
+                ss,
+                us,
+            } => format!(
+                "'{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06}'",
+                y, m, d, hh, mm, ss, us
+            ),
+        }
+    }
+
+    fn insert_values_sql(&self, table: &str, columns: &[String], rows: &[Vec<SqlValue>]) -> String {
+        let mut sql = String::new();
+        sql.push_str("INSERT INTO ");
+        sql.push_str(&insert_target(self, table, columns));
+        sql.push_str(" VALUES ");
+
+        for (row_idx, row) in rows.iter().enumerate() {
+            if row_idx > 0 {
+                sql.push_str(", ");
+            }
+            sql.push('(');
+            for (col_idx, value) in row.iter().enumerate() {
+                if col_idx > 0 {
+                    sql.push_str(", ");
+                }
+                sql.push_str(&self.to_literal(value));
+            }
+            sql.push(')');
+        }
+        sql.push(';');
+        sql
+    }
+}
+
+/// ClickHouse string literals use backslash escapes
+fn escape_string(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 8);
+    result.push('\'');
+    for ch in value.chars() {
+        match ch {
+            '\'' => result.push_str("\\'"),
+            '\\' => result.push_str("\\\\"),
+            '\0' => result.push_str("\\0"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            _ => result.push(ch),
+        }
+    }
+    result.push('\'');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_identifiers() {
+        assert_eq!(CLICKHOUSE_DIALECT.quote_identifier("users"), "`users`");
+        assert_eq!(
+            CLICKHOUSE_DIALECT.quote_identifier("user`table"),
+            "`user\\`table`"
+        );
+    }
+
+    #[test]
+    fn escapes_strings() {
+        assert_eq!(
+            CLICKHOUSE_DIALECT.to_literal(&SqlValue::String("O'Re\\illy".into())),
+            "'O\\'Re\\\\illy'"
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use crate::engine::dialect::{insert_target, OnConflict, SqlDialect};
+use crate::engine::value::SqlValue;
+use crate::util::dialects::postgres::POSTGRES_DIALECT;
+use crate::util::splitter::Syntax;
+
+#[derive(Debug)]
+pub struct CockroachDialect;
+
+pub static COCKROACH_DIALECT: CockroachDialect = CockroachDialect;
+
+impl SqlDialect for CockroachDialect {
+    fn name(&self) -> &'static str {
+        "CockroachDB"
+    }
+
+    fn quote_identifier(&self, name: &str) -> String {
+        POSTGRES_DIALECT.quote_identifier(name)
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::POSTGRES
+    }
+
+    fn to_literal(&self, value: &SqlValue) -> String {
+        match value {
+            // BYTES does not accept the escaped '\x..' form under every encoding setting
+            SqlValue::Bytes(bytes) => format!("decode('{}', 'hex')", hex::encode(bytes)),
+            other => POSTGRES_DIALECT.to_literal(other),
+        }
+    }
+
+    fn insert_values_sql(&self, table: &str, columns: &[String], rows: &[Vec<SqlValue>]) -> String {
+        let mut sql = String::new();
+        sql.push_str("INSERT INTO ");
+        sql.push_str(&insert_target(self, table, columns));
+        sql.push_str(" VALUES ");
+
+        for (row_idx, row) in rows.iter().enumerate() {
+            if row_idx > 0 {
+                sql.push_str(", ");
+            }
+            sql.push('(');
+            for (col_idx, value) in row.iter().enumerate() {
+                if col_idx > 0 {
+                    sql.push_str(", ");
+                }
+                sql.push_str(&self.to_literal(value));
+            }
+            sql.push(')');
+        }
+        sql.push(';');
+        sql
+    }
+
+    fn conflict_clauses(
+        &self,
+        on_conflict: OnConflict,
+        columns: &[String],
+        key: &[String],
+    ) -> Option<(&'static str, String)> {
+        POSTGRES_DIALECT.conflict_clauses(on_conflict, columns, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_bytes_with_decode() {
+        assert_eq!(
+            COCKROACH_DIALECT.to_literal(&SqlValue::Bytes(vec![0x01, 0xff])),
+            "decode('01ff', 'hex')"
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use crate::engine::dialect::{format_qualified_table, insert_target, SqlDialect};
+use crate::engine::value::SqlValue;
+use crate::util::dialects::postgres::POSTGRES_DIALECT;
+use crate::util::splitter::Syntax;
+
+#[derive(Debug)]
+pub struct DuckDbDialect;
+
+pub static DUCKDB_DIALECT: DuckDbDialect = DuckDbDialect;
+
+impl SqlDialect for DuckDbDialect {
+    fn name(&self) -> &'static str {
+        "DuckDB"
+    }
+
+    fn quote_identifier(&self, name: &str) -> String {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::POSTGRES
+    }
+
+    fn to_literal(&self, value: &SqlValue) -> String {
+        match value {
+            SqlValue::Float(v) if v.is_nan() => "'NaN'::DOUBLE".to_string(),
+            SqlValue::Float(v) if v.is_infinite() => {
+                if v.is_sign_positive() {
+                    "'Infinity'::DOUBLE".to_string()
+                } else {
+                    "'-Infinity'::DOUBLE".to_string()
+                }
+            }
+            SqlValue::Bytes(bytes) => format!("from_hex('{}')", hex::encode(bytes)),
+            // Remaining literal syntax is shared with PostgreSQL
+            other => POSTGRES_DIALECT.to_literal(other),
+        }
+    }
+
+    fn insert_values_sql(&self, table: &str, columns: &[String], rows: &[Vec<SqlValue>]) -> String {
+        let mut sql = String::new();
+        sql.push_str("INSERT INTO ");
+        sql.push_str(&insert_target(self, table, columns));
+        sql.push_str(" VALUES ");
+
+        for (row_idx, row) in rows.iter().enumerate() {
+            if row_idx > 0 {
+                sql.push_str(", ");
+            }
+            sql.push('(');
+            for (col_idx, value) in row.iter().enumerate() {
+                if col_idx > 0 {
+                    sql.push_str(", ");
+                }
+                sql.push_str(&self.to_literal(value));
+            }
+            sql.push(')');
+        }
+        sql.push(';');
+        sql
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_blobs_with_from_hex() {
+        assert_eq!(
+            DUCKDB_DIALECT.to_literal(&SqlValue::Bytes(vec![0xca, 0xfe])),
+            "from_hex('cafe')"
+        );
+    }
+}
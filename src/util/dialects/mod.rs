@@ -1,2 +1,12 @@
+pub mod ansi;
+pub mod clickhouse;
+pub mod cockroach;
+#[cfg(feature = "duckdb")]
+pub mod duckdb;
+pub mod mssql;
 pub mod mysql;
+pub mod oracle;
 pub mod postgres;
+pub mod redshift;
+pub mod snowflake;
+pub mod sqlite;
@@ -0,0 +1,163 @@
+use crate::engine::dialect::{format_qualified_table, insert_target, SqlDialect};
+use crate::engine::value::SqlValue;
+
+/// SQL Server rejects table value constructors with more than 1000 rows.
+const MAX_ROWS_PER_INSERT: usize = 1000;
+
+#[derive(Debug)]
+pub struct MssqlDialect;
+
+pub static MSSQL_DIALECT: MssqlDialect = MssqlDialect;
+
+impl SqlDialect for MssqlDialect {
+    fn name(&self) -> &'static str {
+        "SQL Server"
+    }
+
+    fn quote_identifier(&self, name: &str) -> String {
+        format!("[{}]", name.replace(']', "]]"))
+    }
+
+    fn to_literal(&self, value: &SqlValue) -> String {
+        match value {
+            SqlValue::Null => "NULL".to_string(),
+            SqlValue::Bool(v) => {
+                if *v {
+                    "1".to_string()
+                } else {
+                    "0".to_string()
+                }
+            }
+            SqlValue::Int(v) => v.to_string(),
+            // FLOAT has no representation for NaN or infinities
+            SqlValue::Float(v) if !v.is_finite() => "NULL".to_string(),
+            SqlValue::Float(v) => v.to_string(),
+            SqlValue::Decimal(v) => v.clone(),
+            SqlValue::String(v) => escape_nstring(v),
+            SqlValue::Bytes(bytes) => {
+                if bytes.is_empty() {
+                    "0x".to_string()
+                } else {
+                    format!("0x{}", hex::encode(bytes))
+                }
+            }
+            SqlValue::Date { y, m, d } => format!("'{:04}-{:02}-{:02}'", y, m, d),
+            SqlValue::Time { h, m, s, us, .. } => {
+                if *us == 0 {
+                    format!("'{:02}:{:02}:{:02}'", h, m, s)
+                } else {
+                    format!("'{:02}:{:02}:{:02}.{:06}'", h, m, s, us)
+                }
+            }
+            SqlValue::Timestamp {
+                y,
+                m,
+                d,
+                hh,
+                mm,
+                ss,
+                us,
+            } => {
+                // ISO 8601 with the 'T' separator is parsed independently of DATEFORMAT
+                if *us == 0 {
+                    format!("'{:04}-{:02}-{:02}T{:02}:{:02}:{:02}'", y, m, d, hh, mm, ss)
+                } else {
+                    format!(
+                        "'{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}'",
+                        y, m, d, hh, mm, ss, us
+                    )
+                }
+            }
+        }
+    }
+
+    fn insert_values_sql(&self, table: &str, columns: &[String], rows: &[Vec<SqlValue>]) -> String {
+        let mut prefix = String::new();
+        prefix.push_str("INSERT INTO ");
+        prefix.push_str(&insert_target(self, table, columns));
+        prefix.push_str(" VALUES ");
+
+        let mut sql = String::new();
+        for (chunk_idx, chunk) in rows.chunks(MAX_ROWS_PER_INSERT).enumerate() {
+            if chunk_idx > 0 {
+                sql.push('\n');
+            }
+            sql.push_str(&prefix);
+            for (row_idx, row) in chunk.iter().enumerate() {
+                if row_idx > 0 {
+                    sql.push_str(", ");
+                }
+                sql.push('(');
+                for (col_idx, value) in row.iter().enumerate() {
+                    if col_idx > 0 {
+                        sql.push_str(", ");
+                    }
+                    sql.push_str(&self.to_literal(value));
+                }
+                sql.push(')');
+            }
+            sql.push(';');
+        }
+        sql
+    }
+
+    /// SQL Server renames through `sp_rename`, which takes the column's
+    /// dotted name as a string
+    fn rename_column_statement(&self, table: &str, column: &str, new_name: &str) -> String {
+        let qualified = format!(
+            "{}.{}",
+            format_qualified_table(self, table),
+            self.quote_identifier(column)
+        );
+        format!(
+            "EXEC sp_rename {}, {}, 'COLUMN'",
+            escape_nstring(&qualified),
+            escape_nstring(new_name)
+        )
+    }
+
+    /// T-SQL's `ADD` takes no `COLUMN` keyword
+    fn add_column_statement(&self, table: &str, column: &str, column_type: &str) -> String {
+        format!(
+            "ALTER TABLE {} ADD {} {}",
+            format_qualified_table(self, table),
+            self.quote_identifier(column),
+            column_type
+        )
+    }
+}
+
+fn escape_nstring(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 8);
+    result.push_str("N'");
+    for ch in value.chars() {
+        if ch == '\'' {
+            result.push('\'');
+        }
+        result.push(ch);
+    }
+    result.push('\'');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_identifiers() {
+        assert_eq!(MSSQL_DIALECT.quote_identifier("users"), "[users]");
+        assert_eq!(
+            MSSQL_DIALECT.quote_identifier("user]table"),
+            "[user]]table]"
+        );
+    }
+
+    #[test]
+    fn splits_inserts_over_row_limit() {
+        let rows = vec![vec![SqlValue::Int(1)]; MAX_ROWS_PER_INSERT + 1];
+        let sql = MSSQL_DIALECT.insert_values_sql("dbo.t", &["id".to_string()], &rows);
+        assert_eq!(sql.matches("INSERT INTO [dbo].[t]").count(), 2);
+        assert!(sql.lines().all(|line| line.ends_with(';')));
+    }
+}
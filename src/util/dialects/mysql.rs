@@ -1,11 +1,21 @@
-use crate::engine::dialect::{format_qualified_table, SqlDialect};
+use crate::engine::ddl::{Column, ColumnType};
+use crate::engine::dialect::{format_qualified_table, insert_target, OnConflict, SqlDialect};
 use crate::engine::value::SqlValue;
+use crate::util::splitter::Syntax;
+use regex::Regex;
+use std::sync::LazyLock;
 
 #[derive(Debug)]
 pub struct MysqlDialect;
 
 pub static MYSQL_DIALECT: MysqlDialect = MysqlDialect;
 
+static CHARSET_CLAUSE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(CHARACTER SET|CHARSET)(\s*=\s*|\s+)([A-Za-z0-9_]+)").unwrap()
+});
+static COLLATE_CLAUSE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\s+COLLATE(\s*=\s*|\s+)([A-Za-z0-9_]+)").unwrap());
+
 impl SqlDialect for MysqlDialect {
     fn name(&self) -> &'static str {
         "MySQL"
@@ -63,15 +73,8 @@ impl SqlDialect for MysqlDialect {
     fn insert_values_sql(&self, table: &str, columns: &[String], rows: &[Vec<SqlValue>]) -> String {
         let mut sql = String::new();
         sql.push_str("INSERT INTO ");
-        sql.push_str(&format_qualified_table(self, table));
-        sql.push_str(" (");
-        for (idx, col) in columns.iter().enumerate() {
-            if idx > 0 {
-                sql.push_str(", ");
-            }
-            sql.push_str(&self.quote_identifier(col));
-        }
-        sql.push_str(") VALUES ");
+        sql.push_str(&insert_target(self, table, columns));
+        sql.push_str(" VALUES ");
 
         for (row_idx, row) in rows.iter().enumerate() {
             if row_idx > 0 {
@@ -89,6 +92,350 @@ impl SqlDialect for MysqlDialect {
         sql.push(';');
         sql
     }
+
+    /// A MySQL schema is a database
+    fn create_schema_statement(&self, schema: &str) -> Option<String> {
+        Some(format!(
+            "CREATE DATABASE IF NOT EXISTS {}",
+            self.quote_identifier(schema)
+        ))
+    }
+
+    fn server_database(&self) -> Option<&'static str> {
+        Some("")
+    }
+
+    fn database_statements(&self, database: &str) -> Vec<String> {
+        vec![
+            format!(
+                "CREATE DATABASE IF NOT EXISTS {}",
+                self.quote_identifier(database)
+            ),
+            format!("USE {}", self.quote_identifier(database)),
+        ]
+    }
+
+    /// Updating sets every column outside the primary key from the new row;
+    /// with no such column, conflicting rows are skipped
+    fn conflict_clauses(
+        &self,
+        on_conflict: OnConflict,
+        columns: &[String],
+        key: &[String],
+    ) -> Option<(&'static str, String)> {
+        let updates: Vec<String> = columns
+            .iter()
+            .filter(|column| !key.contains(column))
+            .map(|column| {
+                let column = self.quote_identifier(column);
+                format!("{} = VALUES({})", column, column)
+            })
+            .collect();
+        match on_conflict {
+            OnConflict::Error => Some(("INSERT INTO", String::new())),
+            OnConflict::Ignore => Some(("INSERT IGNORE INTO", String::new())),
+            OnConflict::Replace => Some(("REPLACE INTO", String::new())),
+            OnConflict::Update if updates.is_empty() => Some(("INSERT IGNORE INTO", String::new())),
+            OnConflict::Update => Some((
+                "INSERT INTO",
+                format!(" ON DUPLICATE KEY UPDATE {}", updates.join(", ")),
+            )),
+        }
+    }
+
+    /// Drops `DEFINER=user@host` from `CREATE` and `ALTER` statements, also
+    /// inside the versioned comments mysqldump writes it in
+    fn strip_definer(&self, sql: &str) -> String {
+        let head = sql.trim_start().to_ascii_uppercase();
+        if !["CREATE", "ALTER", "/*!"]
+            .iter()
+            .any(|p| head.starts_with(p))
+        {
+            return sql.to_string();
+        }
+        let Some(start) = sql.find(" DEFINER=") else {
+            return sql.to_string();
+        };
+        let rest = &sql[start + " DEFINER=".len()..];
+
+        // The account is `user`@`host`, each part bare or quoted with doubled
+        // quotes inside, and ends at a space or the end of a comment
+        let mut quote = None;
+        let mut end = rest.len();
+        let mut chars = rest.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            match (c, quote) {
+                ('`' | '\'' | '"', Some(open)) if c == open => {
+                    if chars.peek().map(|&(_, c)| c) == Some(c) {
+                        chars.next();
+                    } else {
+                        quote = None;
+                    }
+                }
+                ('`' | '\'' | '"', None) => quote = Some(c),
+                (' ', None) => {
+                    end = i;
+                    break;
+                }
+                ('*', None) if rest[i..].starts_with("*/") => {
+                    end = i;
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        format!("{}{}", &sql[..start], &rest[end..])
+    }
+
+    fn compound_delimiter(&self) -> Option<&'static str> {
+        Some(";;")
+    }
+
+    fn backslash_escapes(&self) -> bool {
+        true
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::MYSQL
+    }
+
+    /// Unbounded text and bytes take the `LONG` types, and times keep their
+    /// microseconds. InnoDB moves `AUTO_INCREMENT` past inserted values
+    /// itself.
+    fn column_type_sql(&self, column: &Column) -> Option<String> {
+        let sql = match &column.column_type {
+            ColumnType::Bool => "TINYINT(1)".to_string(),
+            ColumnType::SmallInt => "SMALLINT".to_string(),
+            ColumnType::Int => "INT".to_string(),
+            ColumnType::BigInt => "BIGINT".to_string(),
+            ColumnType::Decimal(Some((precision, scale))) => {
+                format!("DECIMAL({},{})", precision, scale)
+            }
+            ColumnType::Decimal(None) => "DECIMAL(65,30)".to_string(),
+            ColumnType::Real => "FLOAT".to_string(),
+            ColumnType::Double => "DOUBLE".to_string(),
+            ColumnType::Char(length) if *length <= 255 => format!("CHAR({})", length),
+            ColumnType::Varchar(Some(length)) if *length <= 16_383 => {
+                format!("VARCHAR({})", length)
+            }
+            ColumnType::Char(_)
+            | ColumnType::Varchar(_)
+            | ColumnType::Text
+            | ColumnType::Other(_) => "LONGTEXT".to_string(),
+            ColumnType::Bytes => "LONGBLOB".to_string(),
+            ColumnType::Date => "DATE".to_string(),
+            ColumnType::Time => "TIME(6)".to_string(),
+            ColumnType::Timestamp | ColumnType::TimestampTz => "DATETIME(6)".to_string(),
+            ColumnType::Json => "JSON".to_string(),
+            ColumnType::Uuid => "CHAR(36)".to_string(),
+            ColumnType::Enum(labels) => {
+                let labels: Vec<String> = labels.iter().map(|l| escape_string(l)).collect();
+                format!("ENUM({})", labels.join(", "))
+            }
+        };
+        Some(match column.auto_increment {
+            true => format!("{} AUTO_INCREMENT", sql),
+            false => sql,
+        })
+    }
+
+    /// Secondary keys sit inside the `CREATE TABLE` body; they move into one
+    /// `ALTER TABLE ... ADD KEY ..., ADD KEY ...`. Keys that foreign keys rely
+    /// on stay, since MySQL would otherwise create its own index for them.
+    fn split_secondary_indexes(&self, table: &str, create: &str) -> (String, Vec<String>) {
+        let Some(open) = create.find('(') else {
+            return (create.to_string(), Vec::new());
+        };
+        let Some((items, close)) = list_items(create, open) else {
+            return (create.to_string(), Vec::new());
+        };
+
+        let foreign_key_columns: Vec<&str> = items
+            .iter()
+            .filter(|item| item.starts_with("CONSTRAINT "))
+            .filter_map(|item| item.find("FOREIGN KEY (").map(|at| &item[at + 12..]))
+            .filter_map(|rest| list_items(rest, 0).map(|(_, end)| &rest[1..end]))
+            .collect();
+
+        let (keys, kept): (Vec<&str>, Vec<&str>) = items.into_iter().partition(|item| {
+            let secondary = SECONDARY_KEY_PREFIXES.iter().any(|p| item.starts_with(p));
+            let columns = item
+                .find('(')
+                .and_then(|at| list_items(item, at).map(|(_, end)| &item[at + 1..end]));
+            let backs_foreign_key = columns.is_some_and(|columns| {
+                foreign_key_columns.iter().any(|fk| columns.starts_with(fk))
+            });
+            secondary && !backs_foreign_key
+        });
+
+        if keys.is_empty() {
+            return (create.to_string(), Vec::new());
+        }
+
+        let table_ddl = format!(
+            "{} {} {}",
+            &create[..=open],
+            kept.join(", "),
+            &create[close..]
+        );
+        let adds: Vec<String> = keys.iter().map(|key| format!("ADD {}", key)).collect();
+        let alter = format!(
+            "ALTER TABLE {} {}",
+            format_qualified_table(self, table),
+            adds.join(", ")
+        );
+        (table_ddl, vec![alter])
+    }
+
+    /// `CHARSET=` and `CHARACTER SET` clauses outside string literals name
+    /// `charset` instead, but for `binary`. A collation of another set is
+    /// dropped for the default one of `charset`, or its `_bin` one if it was
+    /// binary.
+    fn with_charset(&self, create: &str, charset: &str) -> String {
+        // Splitting on quotes leaves the literals, `''` included, at odd places
+        let parts: Vec<String> = create
+            .split('\'')
+            .enumerate()
+            .map(|(i, part)| {
+                if i % 2 == 1 {
+                    return part.to_string();
+                }
+                let part = CHARSET_CLAUSE.replace_all(part, |caps: &regex::Captures| {
+                    if caps[3].eq_ignore_ascii_case("binary") {
+                        caps[0].to_string()
+                    } else {
+                        format!("{}{}{}", &caps[1], &caps[2], charset)
+                    }
+                });
+                COLLATE_CLAUSE
+                    .replace_all(&part, |caps: &regex::Captures| {
+                        let collation = caps[2].to_ascii_lowercase();
+                        if collation == "binary" || collation.starts_with(&format!("{}_", charset))
+                        {
+                            caps[0].to_string()
+                        } else if collation.ends_with("_bin") {
+                            format!(" COLLATE{}{}_bin", &caps[1], charset)
+                        } else {
+                            String::new()
+                        }
+                    })
+                    .into_owned()
+            })
+            .collect();
+        parts.join("'")
+    }
+
+    /// Foreign keys sit inside the `CREATE TABLE` body as `CONSTRAINT`
+    /// items; each moves into its own `ALTER TABLE ... ADD CONSTRAINT`. The
+    /// keys backing them stay, so MySQL does not add indexes of its own.
+    fn split_foreign_keys(&self, table: &str, create: &str) -> (String, Vec<String>) {
+        let Some(open) = create.find('(') else {
+            return (create.to_string(), Vec::new());
+        };
+        let Some((items, close)) = list_items(create, open) else {
+            return (create.to_string(), Vec::new());
+        };
+
+        let (foreign_keys, kept): (Vec<&str>, Vec<&str>) = items
+            .into_iter()
+            .partition(|item| item.starts_with("CONSTRAINT ") && item.contains(" FOREIGN KEY "));
+        if foreign_keys.is_empty() {
+            return (create.to_string(), Vec::new());
+        }
+
+        let table_ddl = format!(
+            "{} {} {}",
+            &create[..=open],
+            kept.join(", "),
+            &create[close..]
+        );
+        let qualified = format_qualified_table(self, table);
+        let alters = foreign_keys
+            .iter()
+            .map(|fk| format!("ALTER TABLE {} ADD {}", qualified, fk))
+            .collect();
+        (table_ddl, alters)
+    }
+
+    /// Rows are digested one by one and combined with XOR, so the digest
+    /// does not depend on the order rows are read in; NULL and '' differ
+    /// by the flags appended to each row
+    fn checksum_sql(
+        &self,
+        table: &str,
+        columns: &[String],
+        _key: &[String],
+        condition: &str,
+    ) -> Option<String> {
+        let columns: Vec<String> = columns.iter().map(|c| self.quote_identifier(c)).collect();
+        let nulls: Vec<String> = columns.iter().map(|c| format!("ISNULL({})", c)).collect();
+        Some(format!(
+            "SELECT CONCAT(COUNT(*), ':', COALESCE(BIT_XOR(CAST(CONV(LEFT(MD5(CONCAT_WS('|', {}, CONCAT({}))), 16), 16, 10) AS UNSIGNED)), 0)) FROM {}{}",
+            columns.join(", "),
+            nulls.join(", "),
+            format_qualified_table(self, table),
+            condition
+        ))
+    }
+}
+
+/// How `SHOW CREATE TABLE` starts the definition of a non-primary index
+const SECONDARY_KEY_PREFIXES: [&str; 8] = [
+    "KEY ",
+    "INDEX ",
+    "UNIQUE KEY ",
+    "UNIQUE INDEX ",
+    "FULLTEXT KEY ",
+    "FULLTEXT INDEX ",
+    "SPATIAL KEY ",
+    "SPATIAL INDEX ",
+];
+
+/// The trimmed comma-separated items of the parenthesised list opening at
+/// byte `open`, and the position of its closing parenthesis. Nested
+/// parentheses and quoted text are skipped over.
+fn list_items(sql: &str, open: usize) -> Option<(Vec<&str>, usize)> {
+    let bytes = sql.as_bytes();
+    let mut items = Vec::new();
+    let mut depth = 0usize;
+    let mut quote: Option<u8> = None;
+    let mut start = open + 1;
+    let mut i = open + 1;
+
+    while i < bytes.len() {
+        let b = bytes[i];
+        match quote {
+            Some(q) if b == q => {
+                // A doubled quote is an escaped one
+                if bytes.get(i + 1) == Some(&q) {
+                    i += 1;
+                } else {
+                    quote = None;
+                }
+            }
+            Some(q) if b == b'\\' && q != b'`' => i += 1,
+            Some(_) => {}
+            None => match b {
+                b'`' | b'\'' | b'"' => quote = Some(b),
+                b'(' => depth += 1,
+                b')' if depth > 0 => depth -= 1,
+                b')' => {
+                    items.push(sql[start..i].trim());
+                    items.retain(|item| !item.is_empty());
+                    return Some((items, i));
+                }
+                b',' if depth == 0 => {
+                    items.push(sql[start..i].trim());
+                    start = i + 1;
+                }
+                _ => {}
+            },
+        }
+        i += 1;
+    }
+
+    None
 }
 
 fn bytes_literal(bytes: &[u8]) -> String {
@@ -154,6 +501,35 @@ fn format_f64(value: f64) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn strips_definer() {
+        let input = "CREATE ALGORITHM=UNDEFINED DEFINER=`app user`@`%` SQL SECURITY DEFINER VIEW `v` AS select 1 AS `1`";
+        assert_eq!(
+            MYSQL_DIALECT.strip_definer(input),
+            "CREATE ALGORITHM=UNDEFINED SQL SECURITY DEFINER VIEW `v` AS select 1 AS `1`"
+        );
+        assert_eq!(
+            MYSQL_DIALECT.strip_definer("CREATE VIEW `v` AS select 1"),
+            "CREATE VIEW `v` AS select 1"
+        );
+        assert_eq!(
+            MYSQL_DIALECT.strip_definer("CREATE DEFINER=`root`@`localhost` TRIGGER `t` BEFORE INSERT ON `a` FOR EACH ROW SET NEW.x = 1"),
+            "CREATE TRIGGER `t` BEFORE INSERT ON `a` FOR EACH ROW SET NEW.x = 1"
+        );
+        // mysqldump's versioned comments, and unquoted accounts
+        assert_eq!(
+            MYSQL_DIALECT.strip_definer("/*!50003 CREATE*/ /*!50017 DEFINER=`root`@`localhost`*/ /*!50003 TRIGGER `t` BEFORE INSERT ON `a` FOR EACH ROW SET NEW.x = 1 */"),
+            "/*!50003 CREATE*/ /*!50017*/ /*!50003 TRIGGER `t` BEFORE INSERT ON `a` FOR EACH ROW SET NEW.x = 1 */"
+        );
+        assert_eq!(
+            MYSQL_DIALECT.strip_definer("CREATE DEFINER=prod@'10.0.%' PROCEDURE `p`() SELECT 1"),
+            "CREATE PROCEDURE `p`() SELECT 1"
+        );
+        // Rows that merely contain the text stay as they are
+        let insert = "INSERT INTO `notes` VALUES ('CREATE DEFINER=x VIEW')";
+        assert_eq!(MYSQL_DIALECT.strip_definer(insert), insert);
+    }
+
     #[test]
     fn quotes_identifiers() {
         assert_eq!(MYSQL_DIALECT.quote_identifier("users"), "`users`");
@@ -163,6 +539,72 @@ mod tests {
         );
     }
 
+    #[test]
+    fn lists_columns_unless_none_are_given() {
+        let rows = vec![vec![SqlValue::Int(1), SqlValue::String("a".into())]];
+        let columns = ["id".to_string(), "name".to_string()];
+        assert_eq!(
+            MYSQL_DIALECT.insert_values_sql("t", &columns, &rows),
+            "INSERT INTO `t` (`id`, `name`) VALUES (1, 'a');"
+        );
+        assert_eq!(
+            MYSQL_DIALECT.insert_values_sql("t", &[], &rows),
+            "INSERT INTO `t` VALUES (1, 'a');"
+        );
+    }
+
+    #[test]
+    fn splits_secondary_keys_from_create_table() {
+        let create = "CREATE TABLE IF NOT EXISTS `orders` ( `id` int NOT NULL, `customer_id` int, \
+                      `note` varchar(20) DEFAULT 'a, (b)', PRIMARY KEY (`id`), \
+                      KEY `idx_customer` (`customer_id`), UNIQUE KEY `uq_note` (`note`(10)), \
+                      CONSTRAINT `fk_customer` FOREIGN KEY (`customer_id`) REFERENCES `customers` (`id`) \
+                      ) ENGINE=InnoDB COMMENT='x (y)'";
+
+        let (table, indexes) = MYSQL_DIALECT.split_secondary_indexes("orders", create);
+        assert_eq!(
+            table,
+            "CREATE TABLE IF NOT EXISTS `orders` ( `id` int NOT NULL, `customer_id` int, \
+             `note` varchar(20) DEFAULT 'a, (b)', PRIMARY KEY (`id`), \
+             KEY `idx_customer` (`customer_id`), \
+             CONSTRAINT `fk_customer` FOREIGN KEY (`customer_id`) REFERENCES `customers` (`id`) \
+             ) ENGINE=InnoDB COMMENT='x (y)'"
+        );
+        assert_eq!(
+            indexes,
+            vec!["ALTER TABLE `orders` ADD UNIQUE KEY `uq_note` (`note`(10))"]
+        );
+
+        let (table, foreign_keys) = MYSQL_DIALECT.split_foreign_keys("orders", &table);
+        assert_eq!(
+            table,
+            "CREATE TABLE IF NOT EXISTS `orders` ( `id` int NOT NULL, `customer_id` int, \
+             `note` varchar(20) DEFAULT 'a, (b)', PRIMARY KEY (`id`), \
+             KEY `idx_customer` (`customer_id`) ) ENGINE=InnoDB COMMENT='x (y)'"
+        );
+        assert_eq!(
+            foreign_keys,
+            vec!["ALTER TABLE `orders` ADD CONSTRAINT `fk_customer` FOREIGN KEY (`customer_id`) REFERENCES `customers` (`id`)"]
+        );
+    }
+
+    #[test]
+    fn changes_charset_clauses() {
+        let create = "CREATE TABLE IF NOT EXISTS `t` ( `a` varchar(20) CHARACTER SET latin1 COLLATE latin1_bin NOT NULL, \
+                      `b` text CHARACTER SET latin1 COLLATE latin1_swedish_ci DEFAULT 'CHARACTER SET latin1', \
+                      `c` varbinary(10) DEFAULT NULL COMMENT 'it''s COLLATE latin1_bin', \
+                      `d` varchar(5) COLLATE utf8mb4_bin DEFAULT NULL \
+                      ) ENGINE=InnoDB DEFAULT CHARSET=latin1 COLLATE=latin1_swedish_ci";
+        assert_eq!(
+            MYSQL_DIALECT.with_charset(create, "utf8mb4"),
+            "CREATE TABLE IF NOT EXISTS `t` ( `a` varchar(20) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin NOT NULL, \
+             `b` text CHARACTER SET utf8mb4 DEFAULT 'CHARACTER SET latin1', \
+             `c` varbinary(10) DEFAULT NULL COMMENT 'it''s COLLATE latin1_bin', \
+             `d` varchar(5) COLLATE utf8mb4_bin DEFAULT NULL \
+             ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4"
+        );
+    }
+
     #[test]
     fn escapes_strings() {
         assert_eq!(
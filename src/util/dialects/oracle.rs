@@ -0,0 +1,144 @@
+use crate::engine::dialect::{format_qualified_table, insert_target, SqlDialect};
+use crate::engine::value::SqlValue;
+
+#[derive(Debug)]
+pub struct OracleDialect;
+
+pub static ORACLE_DIALECT: OracleDialect = OracleDialect;
+
+impl SqlDialect for OracleDialect {
+    fn name(&self) -> &'static str {
+        "Oracle"
+    }
+
+    fn quote_identifier(&self, name: &str) -> String {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+
+    fn to_literal(&self, value: &SqlValue) -> String {
+        match value {
+            SqlValue::Null => "NULL".to_string(),
+            SqlValue::Bool(v) => {
+                if *v {
+                    "1".to_string()
+                } else {
+                    "0".to_string()
+                }
+            }
+            SqlValue::Int(v) => v.to_string(),
+            SqlValue::Float(v) => {
+                if v.is_nan() {
+                    "BINARY_DOUBLE_NAN".to_string()
+                } else if v.is_infinite() {
+                    if v.is_sign_positive() {
+                        "BINARY_DOUBLE_INFINITY".to_string()
+                    } else {
+                        "-BINARY_DOUBLE_INFINITY".to_string()
+                    }
+                } else {
+                    v.to_string()
+                }
+            }
+            SqlValue::Decimal(v) => v.clone(),
+            SqlValue::String(v) => escape_string(v),
+            SqlValue::Bytes(bytes) => format!("HEXTORAW('{}')", hex::encode_upper(bytes)),
+            SqlValue::Date { y, m, d } => format!("DATE '{:04}-{:02}-{:02}'", y, m, d),
+            // No TIME type; times are stored as strings
+            SqlValue::Time { neg, h, m, s, us } => {
+                let sign = if *neg { "-" } else { "" };
+                if *us == 0 {
+                    format!("'{}{:02}:{:02}:{:02}'", sign, h, m, s)
+                } else {
+                    format!("'{}{:02}:{:02}:{:02}.{:06}'", sign, h, m, s, us)
+                }
+            }
+            SqlValue::Timestamp {
+                y,
+                m,
+                d,
+                hh,
+                mm,
+                ss,
+                us,
+            } => format!(
+                "TIMESTAMP '{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06}'",
+                y, m, d, hh, mm, ss, us
+            ),
+        }
+    }
+
+    /// Oracle has no multi-row VALUES list before 23c, so rows are combined
+    /// with INSERT ALL.
+    fn insert_values_sql(&self, table: &str, columns: &[String], rows: &[Vec<SqlValue>]) -> String {
+        let target = insert_target(self, table, columns);
+
+        let mut sql = String::new();
+        sql.push_str("INSERT ALL");
+        for row in rows {
+            sql.push_str(" INTO ");
+            sql.push_str(&target);
+            sql.push_str(" VALUES (");
+            for (col_idx, value) in row.iter().enumerate() {
+                if col_idx > 0 {
+                    sql.push_str(", ");
+                }
+                sql.push_str(&self.to_literal(value));
+            }
+            sql.push(')');
+        }
+        sql.push_str(" SELECT 1 FROM DUAL;");
+        sql
+    }
+
+    fn add_column_statement(&self, table: &str, column: &str, column_type: &str) -> String {
+        format!(
+            "ALTER TABLE {} ADD ({} {})",
+            format_qualified_table(self, table),
+            self.quote_identifier(column),
+            column_type
+        )
+    }
+
+    /// DROP TABLE IF EXISTS only exists from 23c on; ignore ORA-00942 instead
+    fn drop_table_statement(&self, table: &str) -> String {
+        let drop = format!(
+            "DROP TABLE {} CASCADE CONSTRAINTS PURGE",
+            format_qualified_table(self, table)
+        );
+        format!(
+            "BEGIN EXECUTE IMMEDIATE '{}'; EXCEPTION WHEN OTHERS THEN IF SQLCODE != -942 THEN RAISE; END IF; END",
+            drop.replace('\'', "''")
+        )
+    }
+}
+
+fn escape_string(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 8);
+    result.push('\'');
+    for ch in value.chars() {
+        if ch == '\'' {
+            result.push('\'');
+        }
+        result.push(ch);
+    }
+    result.push('\'');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_insert_all() {
+        let rows = vec![
+            vec![SqlValue::Int(1)],
+            vec![SqlValue::String("it's".into())],
+        ];
+        let sql = ORACLE_DIALECT.insert_values_sql("HR.T", &["ID".to_string()], &rows);
+        assert_eq!(
+            sql,
+            "INSERT ALL INTO \"HR\".\"T\" (\"ID\") VALUES (1) INTO \"HR\".\"T\" (\"ID\") VALUES ('it''s') SELECT 1 FROM DUAL;"
+        );
+    }
+}
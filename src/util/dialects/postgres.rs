@@ -1,5 +1,10 @@
-use crate::engine::dialect::{format_qualified_table, SqlDialect};
+use crate::engine::ddl::{Column, ColumnType};
+use crate::engine::dialect::{
+    format_qualified_table, insert_target, split_index_statements, OnConflict, SqlDialect,
+};
 use crate::engine::value::SqlValue;
+use crate::engine::Routine;
+use crate::util::splitter::Syntax;
 
 #[derive(Debug)]
 pub struct PostgresDialect;
@@ -15,6 +20,10 @@ impl SqlDialect for PostgresDialect {
         format!("\"{}\"", name.replace('"', "\"\""))
     }
 
+    fn syntax(&self) -> Syntax {
+        Syntax::POSTGRES
+    }
+
     fn to_literal(&self, value: &SqlValue) -> String {
         match value {
             SqlValue::Null => "NULL".to_string(),
@@ -41,7 +50,7 @@ impl SqlDialect for PostgresDialect {
             }
             SqlValue::Decimal(v) => v.clone(),
             SqlValue::String(v) => escape_single_quotes(v),
-            SqlValue::Bytes(bytes) => format!("'\\\\x{}'::bytea", hex::encode(bytes)),
+            SqlValue::Bytes(bytes) => format!("'\\x{}'::bytea", hex::encode(bytes)),
             SqlValue::Date { y, m, d } => format!("DATE '{:04}-{:02}-{:02}'", y, m, d),
             SqlValue::Time { neg, h, m, s, us } => {
                 let sign = if *neg { "-" } else { "" };
@@ -78,15 +87,8 @@ impl SqlDialect for PostgresDialect {
     fn insert_values_sql(&self, table: &str, columns: &[String], rows: &[Vec<SqlValue>]) -> String {
         let mut sql = String::new();
         sql.push_str("INSERT INTO ");
-        sql.push_str(&format_qualified_table(self, table));
-        sql.push_str(" (");
-        for (idx, col) in columns.iter().enumerate() {
-            if idx > 0 {
-                sql.push_str(", ");
-            }
-            sql.push_str(&self.quote_identifier(col));
-        }
-        sql.push_str(") VALUES ");
+        sql.push_str(&insert_target(self, table, columns));
+        sql.push_str(" VALUES ");
 
         for (row_idx, row) in rows.iter().enumerate() {
             if row_idx > 0 {
@@ -104,6 +106,169 @@ impl SqlDialect for PostgresDialect {
         sql.push(';');
         sql
     }
+
+    /// Replacing or updating updates the row the primary key finds; without
+    /// a key, or with nothing outside it, conflicting rows are skipped
+    fn conflict_clauses(
+        &self,
+        on_conflict: OnConflict,
+        columns: &[String],
+        key: &[String],
+    ) -> Option<(&'static str, String)> {
+        let updates: Vec<String> = columns
+            .iter()
+            .filter(|column| !key.contains(column))
+            .map(|column| {
+                let column = self.quote_identifier(column);
+                format!("{} = EXCLUDED.{}", column, column)
+            })
+            .collect();
+        let clause = match on_conflict {
+            OnConflict::Error => String::new(),
+            OnConflict::Replace | OnConflict::Update if !key.is_empty() && !updates.is_empty() => {
+                let key: Vec<String> = key.iter().map(|c| self.quote_identifier(c)).collect();
+                format!(
+                    " ON CONFLICT ({}) DO UPDATE SET {}",
+                    key.join(", "),
+                    updates.join(", ")
+                )
+            }
+            OnConflict::Ignore | OnConflict::Replace | OnConflict::Update => {
+                " ON CONFLICT DO NOTHING".to_string()
+            }
+        };
+        Some(("INSERT INTO", clause))
+    }
+
+    fn split_secondary_indexes(&self, _table: &str, create: &str) -> (String, Vec<String>) {
+        split_index_statements(create)
+    }
+
+    /// Foreign keys are `CONSTRAINT` lines of the `CREATE TABLE` body, as
+    /// `show_create_table` writes it; each moves into an `ALTER TABLE`.
+    fn split_foreign_keys(&self, table: &str, create: &str) -> (String, Vec<String>) {
+        let is_foreign_key = |line: &str| {
+            let line = line.trim();
+            line.starts_with("CONSTRAINT ") && line.contains(" FOREIGN KEY ")
+        };
+        let qualified = format_qualified_table(self, table);
+        let foreign_keys: Vec<String> = create
+            .lines()
+            .filter(|line| is_foreign_key(line))
+            .map(|line| {
+                let constraint = line.trim().trim_end_matches(',');
+                format!("ALTER TABLE {} ADD {}", qualified, constraint)
+            })
+            .collect();
+        if foreign_keys.is_empty() {
+            return (create.to_string(), Vec::new());
+        }
+
+        let mut lines: Vec<String> = create
+            .lines()
+            .filter(|line| !is_foreign_key(line))
+            .map(str::to_string)
+            .collect();
+        // The last item of the body must not keep its comma
+        for i in 1..lines.len() {
+            if lines[i].starts_with(')') {
+                let item = lines[i - 1].trim_end_matches(',').to_string();
+                lines[i - 1] = item;
+            }
+        }
+        (lines.join("\n"), foreign_keys)
+    }
+
+    /// `public` is in every database, and creating it may not be allowed
+    fn create_schema_statement(&self, schema: &str) -> Option<String> {
+        (schema != "public").then(|| {
+            format!(
+                "CREATE SCHEMA IF NOT EXISTS {}",
+                self.quote_identifier(schema)
+            )
+        })
+    }
+
+    fn server_database(&self) -> Option<&'static str> {
+        Some("postgres")
+    }
+
+    /// Definitions are `CREATE OR REPLACE`, and dropping a function would
+    /// fail while a trigger still calls it
+    fn drop_routine_statement(&self, _routine: &Routine) -> Option<String> {
+        None
+    }
+
+    /// Counters become `serial` columns; enums, which would need a type of
+    /// their own, become text checked against their labels
+    fn column_type_sql(&self, column: &Column) -> Option<String> {
+        let sql = match &column.column_type {
+            ColumnType::SmallInt if column.auto_increment => "SMALLSERIAL".to_string(),
+            ColumnType::Int if column.auto_increment => "SERIAL".to_string(),
+            ColumnType::BigInt if column.auto_increment => "BIGSERIAL".to_string(),
+            ColumnType::Bool => "BOOLEAN".to_string(),
+            ColumnType::SmallInt => "SMALLINT".to_string(),
+            ColumnType::Int => "INTEGER".to_string(),
+            ColumnType::BigInt => "BIGINT".to_string(),
+            ColumnType::Decimal(Some((precision, scale))) => {
+                format!("NUMERIC({},{})", precision, scale)
+            }
+            ColumnType::Decimal(None) => "NUMERIC".to_string(),
+            ColumnType::Real => "REAL".to_string(),
+            ColumnType::Double => "DOUBLE PRECISION".to_string(),
+            ColumnType::Char(length) => format!("CHAR({})", length),
+            ColumnType::Varchar(Some(length)) => format!("VARCHAR({})", length),
+            ColumnType::Varchar(None) => "VARCHAR".to_string(),
+            ColumnType::Text | ColumnType::Other(_) => "TEXT".to_string(),
+            ColumnType::Bytes => "BYTEA".to_string(),
+            ColumnType::Date => "DATE".to_string(),
+            ColumnType::Time => "TIME".to_string(),
+            ColumnType::Timestamp => "TIMESTAMP".to_string(),
+            ColumnType::TimestampTz => "TIMESTAMPTZ".to_string(),
+            ColumnType::Json => "JSONB".to_string(),
+            ColumnType::Uuid => "UUID".to_string(),
+            ColumnType::Enum(labels) => {
+                let labels: Vec<String> = labels.iter().map(|l| escape_single_quotes(l)).collect();
+                format!(
+                    "TEXT CHECK ({} IN ({}))",
+                    self.quote_identifier(&column.name),
+                    labels.join(", ")
+                )
+            }
+        };
+        Some(sql)
+    }
+
+    fn reset_auto_increment_sql(&self, table: &str, column: &str) -> Option<String> {
+        let qualified = format_qualified_table(self, table);
+        Some(format!(
+            "SELECT setval(pg_get_serial_sequence({}, {}), COALESCE(MAX({}), 0) + 1, false) FROM {}",
+            escape_single_quotes(&qualified),
+            escape_single_quotes(column),
+            self.quote_identifier(column),
+            qualified
+        ))
+    }
+
+    /// Each row's text form is digested, and the digests chained in key
+    /// order
+    fn checksum_sql(
+        &self,
+        table: &str,
+        columns: &[String],
+        key: &[String],
+        condition: &str,
+    ) -> Option<String> {
+        let columns: Vec<String> = columns.iter().map(|c| self.quote_identifier(c)).collect();
+        let key: Vec<String> = key.iter().map(|c| self.quote_identifier(c)).collect();
+        Some(format!(
+            "SELECT COALESCE(md5(string_agg(md5(ROW({})::text), '' ORDER BY {})), '') FROM {}{}",
+            columns.join(", "),
+            key.join(", "),
+            format_qualified_table(self, table),
+            condition
+        ))
+    }
 }
 
 fn escape_single_quotes(value: &str) -> String {
@@ -131,4 +296,36 @@ mod tests {
             "\"user\"\"table\""
         );
     }
+
+    #[test]
+    fn handles_conflicts_on_the_primary_key() {
+        let columns = vec!["id".to_string(), "name".to_string()];
+        let key = vec!["id".to_string()];
+        assert_eq!(
+            POSTGRES_DIALECT.conflict_clauses(OnConflict::Replace, &columns, &key),
+            Some((
+                "INSERT INTO",
+                " ON CONFLICT (\"id\") DO UPDATE SET \"name\" = EXCLUDED.\"name\"".to_string()
+            ))
+        );
+        assert_eq!(
+            POSTGRES_DIALECT.conflict_clauses(OnConflict::Replace, &columns, &[]),
+            Some(("INSERT INTO", " ON CONFLICT DO NOTHING".to_string()))
+        );
+    }
+
+    #[test]
+    fn splits_foreign_keys_from_create_table() {
+        let create = "CREATE TABLE IF NOT EXISTS \"public\".\"orders\" (\n    \"id\" integer NOT NULL,\n    \"customer_id\" integer,\n    CONSTRAINT \"orders_pkey\" PRIMARY KEY (id),\n    CONSTRAINT \"fk_customer\" FOREIGN KEY (customer_id) REFERENCES customers(id)\n);\nCREATE INDEX idx ON public.orders USING btree (customer_id);";
+
+        let (table, foreign_keys) = POSTGRES_DIALECT.split_foreign_keys("public.orders", create);
+        assert_eq!(
+            table,
+            "CREATE TABLE IF NOT EXISTS \"public\".\"orders\" (\n    \"id\" integer NOT NULL,\n    \"customer_id\" integer,\n    CONSTRAINT \"orders_pkey\" PRIMARY KEY (id)\n);\nCREATE INDEX idx ON public.orders USING btree (customer_id);"
+        );
+        assert_eq!(
+            foreign_keys,
+            vec!["ALTER TABLE \"public\".\"orders\" ADD CONSTRAINT \"fk_customer\" FOREIGN KEY (customer_id) REFERENCES customers(id)"]
+        );
+    }
 }
@@ -0,0 +1,445 @@
+use crate::engine::dialect::{insert_target, SqlDialect};
+use crate::engine::value::SqlValue;
+use crate::util::dialects::postgres::POSTGRES_DIALECT;
+use crate::util::splitter::Syntax;
+
+/// Redshift rejects statements larger than 16 MB; stay well below that.
+const MAX_INSERT_BYTES: usize = 8 * 1024 * 1024;
+
+/// Widest VARCHAR Redshift accepts. Lengths are counted in bytes, not characters.
+const MAX_VARCHAR_BYTES: u32 = 65535;
+
+/// Widest VARBYTE Redshift accepts.
+const MAX_VARBYTE_BYTES: u32 = 1_024_000;
+
+#[derive(Debug)]
+pub struct RedshiftDialect;
+
+pub static REDSHIFT_DIALECT: RedshiftDialect = RedshiftDialect;
+
+impl SqlDialect for RedshiftDialect {
+    fn name(&self) -> &'static str {
+        "Redshift"
+    }
+
+    fn quote_identifier(&self, name: &str) -> String {
+        POSTGRES_DIALECT.quote_identifier(name)
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::POSTGRES
+    }
+
+    fn to_literal(&self, value: &SqlValue) -> String {
+        match value {
+            // Binary data is stored as VARBYTE
+            SqlValue::Bytes(bytes) => format!("from_hex('{}')", hex::encode(bytes)),
+            other => POSTGRES_DIALECT.to_literal(other),
+        }
+    }
+
+    fn insert_values_sql(&self, table: &str, columns: &[String], rows: &[Vec<SqlValue>]) -> String {
+        let mut prefix = String::new();
+        prefix.push_str("INSERT INTO ");
+        prefix.push_str(&insert_target(self, table, columns));
+        prefix.push_str(" VALUES ");
+
+        // One statement per line, each kept under the statement size limit
+        let mut sql = String::new();
+        let mut statement_len = 0;
+        for row in rows {
+            let mut tuple = String::from("(");
+            for (col_idx, value) in row.iter().enumerate() {
+                if col_idx > 0 {
+                    tuple.push_str(", ");
+                }
+                tuple.push_str(&self.to_literal(value));
+            }
+            tuple.push(')');
+
+            if statement_len > 0 && statement_len + tuple.len() + 2 > MAX_INSERT_BYTES {
+                sql.push_str(";\n");
+                statement_len = 0;
+            }
+
+            if statement_len == 0 {
+                sql.push_str(&prefix);
+                statement_len = prefix.len();
+            } else {
+                sql.push_str(", ");
+                statement_len += 2;
+            }
+            sql.push_str(&tuple);
+            statement_len += tuple.len();
+        }
+        sql.push(';');
+        sql
+    }
+}
+
+/// Rewrite a statement from a PostgreSQL dump so Redshift accepts it.
+/// Returns `None` for statements Redshift has no equivalent for (indexes,
+/// session settings), which are skipped.
+pub fn translate_postgres_statement(sql: &str) -> Option<String> {
+    let trimmed = sql.trim();
+    let upper = trimmed.to_ascii_uppercase();
+
+    if upper.starts_with("CREATE INDEX")
+        || upper.starts_with("CREATE UNIQUE INDEX")
+        || upper.starts_with("SET CLIENT_ENCODING")
+        || upper.starts_with("SET STANDARD_CONFORMING_STRINGS")
+        || upper.starts_with("SET SESSION_REPLICATION_ROLE")
+    {
+        return None;
+    }
+
+    if upper.starts_with("CREATE TABLE") {
+        return Some(translate_create_table(trimmed));
+    }
+
+    if upper.starts_with("INSERT INTO") {
+        return Some(rewrite_bytea_literals(trimmed));
+    }
+
+    Some(trimmed.to_string())
+}
+
+/// Map column types and drop clauses Redshift does not support
+fn translate_create_table(sql: &str) -> String {
+    let (Some(open), Some(close)) = (sql.find('('), sql.rfind(')')) else {
+        return sql.to_string();
+    };
+
+    let definitions: Vec<String> = split_top_level(&sql[open + 1..close])
+        .into_iter()
+        .filter_map(|item| translate_table_element(item.trim()))
+        .collect();
+
+    format!(
+        "{}({}){}",
+        &sql[..open],
+        definitions.join(", "),
+        &sql[close + 1..]
+    )
+}
+
+fn translate_table_element(item: &str) -> Option<String> {
+    let upper = item.to_ascii_uppercase();
+
+    // CHECK constraints and EXCLUDE are not supported
+    if upper.contains(" CHECK ") || upper.starts_with("CHECK ") || upper.contains(" EXCLUDE ") {
+        return None;
+    }
+
+    if upper.starts_with("CONSTRAINT ")
+        || upper.starts_with("PRIMARY KEY")
+        || upper.starts_with("FOREIGN KEY")
+        || upper.starts_with("UNIQUE")
+    {
+        // Foreign keys are informational only; referential actions are rejected
+        return Some(strip_clauses(
+            item,
+            &[
+                "ON DELETE CASCADE",
+                "ON DELETE SET NULL",
+                "ON DELETE SET DEFAULT",
+                "ON DELETE RESTRICT",
+                "ON DELETE NO ACTION",
+                "ON UPDATE CASCADE",
+                "ON UPDATE SET NULL",
+                "ON UPDATE SET DEFAULT",
+                "ON UPDATE RESTRICT",
+                "ON UPDATE NO ACTION",
+                "NOT DEFERRABLE",
+                "DEFERRABLE",
+                "INITIALLY DEFERRED",
+                "INITIALLY IMMEDIATE",
+                "NOT VALID",
+            ],
+        ));
+    }
+
+    Some(translate_column(item))
+}
+
+/// Translate a single column definition (`"name" type [modifiers]`)
+fn translate_column(item: &str) -> String {
+    let (name, rest) = split_column_name(item);
+    let rest_upper = rest.to_ascii_uppercase();
+
+    // The type runs until the first modifier keyword
+    let type_end = [
+        " GENERATED ",
+        " DEFAULT ",
+        " NOT NULL",
+        " NULL",
+        " COLLATE ",
+        " PRIMARY KEY",
+        " UNIQUE",
+        " REFERENCES ",
+        " ENCODE ",
+    ]
+    .iter()
+    .filter_map(|kw| format!(" {}", rest_upper).find(kw))
+    .min()
+    .unwrap_or(rest.len());
+
+    let pg_type = rest[..type_end].trim();
+    let mut modifiers = rest[type_end..].trim().to_string();
+    let mut redshift_type = map_type(pg_type);
+
+    let modifiers_upper = modifiers.to_ascii_uppercase();
+    let is_serial = pg_type.eq_ignore_ascii_case("serial")
+        || pg_type.eq_ignore_ascii_case("bigserial")
+        || pg_type.eq_ignore_ascii_case("smallserial");
+    let has_sequence_default = modifiers_upper.contains("DEFAULT NEXTVAL(");
+
+    if modifiers_upper.contains("AS IDENTITY") || is_serial || has_sequence_default {
+        modifiers = strip_clauses(
+            &strip_default(&modifiers),
+            &[
+                "GENERATED ALWAYS AS IDENTITY",
+                "GENERATED BY DEFAULT AS IDENTITY",
+            ],
+        );
+        // Explicit ids from the dump must still be insertable
+        redshift_type.push_str(" GENERATED BY DEFAULT AS IDENTITY(1,1)");
+    }
+
+    modifiers = strip_collate(&modifiers);
+
+    let mut column = format!("{} {}", name, redshift_type);
+    if !modifiers.is_empty() {
+        column.push(' ');
+        column.push_str(&modifiers);
+    }
+    column
+}
+
+/// Map a PostgreSQL type name (as printed by `format_type`) to a Redshift type
+fn map_type(pg_type: &str) -> String {
+    let lower = pg_type.trim().to_ascii_lowercase();
+
+    if lower.ends_with("[]") {
+        return "SUPER".to_string();
+    }
+
+    match lower.as_str() {
+        "text" | "character varying" | "varchar" | "citext" | "name" => {
+            return format!("VARCHAR({})", MAX_VARCHAR_BYTES)
+        }
+        "bytea" => return format!("VARBYTE({})", MAX_VARBYTE_BYTES),
+        "json" | "jsonb" => return "SUPER".to_string(),
+        "uuid" => return "CHAR(36)".to_string(),
+        "serial" => return "INTEGER".to_string(),
+        "bigserial" => return "BIGINT".to_string(),
+        "smallserial" => return "SMALLINT".to_string(),
+        "inet" | "cidr" | "macaddr" | "interval" | "xml" | "money" => {
+            return "VARCHAR(256)".to_string()
+        }
+        _ => {}
+    }
+
+    // character varying(n) counts characters; Redshift counts bytes, so leave
+    // room for four-byte UTF-8 sequences
+    for prefix in ["character varying(", "varchar("] {
+        if let Some(len) = lower
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.strip_suffix(')'))
+            .and_then(|n| n.trim().parse::<u32>().ok())
+        {
+            return format!("VARCHAR({})", len.saturating_mul(4).min(MAX_VARCHAR_BYTES));
+        }
+    }
+
+    pg_type.to_string()
+}
+
+fn split_column_name(item: &str) -> (&str, &str) {
+    if item.starts_with('"') {
+        let mut idx = 1;
+        let bytes = item.as_bytes();
+        while idx < bytes.len() {
+            if bytes[idx] == b'"' {
+                if bytes.get(idx + 1) == Some(&b'"') {
+                    idx += 2;
+                    continue;
+                }
+                return (&item[..=idx], item[idx + 1..].trim_start());
+            }
+            idx += 1;
+        }
+        (item, "")
+    } else {
+        match item.split_once(char::is_whitespace) {
+            Some((name, rest)) => (name, rest.trim_start()),
+            None => (item, ""),
+        }
+    }
+}
+
+/// Remove a `DEFAULT <expr>` clause, where the expression ends at the next
+/// top-level space-delimited keyword
+fn strip_default(modifiers: &str) -> String {
+    let upper = modifiers.to_ascii_uppercase();
+    let Some(start) = upper.find("DEFAULT ") else {
+        return modifiers.to_string();
+    };
+
+    let mut depth = 0i32;
+    let mut in_string = false;
+    let expr_start = start + "DEFAULT ".len();
+    let mut end = modifiers.len();
+    for (offset, ch) in modifiers[expr_start..].char_indices() {
+        match ch {
+            '\'' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string => depth -= 1,
+            ' ' if !in_string && depth == 0 => {
+                end = expr_start + offset;
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    format!("{} {}", modifiers[..start].trim(), modifiers[end..].trim())
+        .trim()
+        .to_string()
+}
+
+fn strip_collate(modifiers: &str) -> String {
+    let upper = modifiers.to_ascii_uppercase();
+    let Some(start) = upper.find("COLLATE ") else {
+        return modifiers.to_string();
+    };
+    let rest = &modifiers[start + "COLLATE ".len()..];
+    let name_end = if let Some(quoted) = rest.strip_prefix('"') {
+        quoted.find('"').map(|i| i + 2).unwrap_or(rest.len())
+    } else {
+        rest.find(' ').unwrap_or(rest.len())
+    };
+    format!("{} {}", modifiers[..start].trim(), rest[name_end..].trim())
+        .trim()
+        .to_string()
+}
+
+fn strip_clauses(item: &str, clauses: &[&str]) -> String {
+    let mut result = item.to_string();
+    for clause in clauses {
+        while let Some(pos) = result.to_ascii_uppercase().find(clause) {
+            result.replace_range(pos..pos + clause.len(), "");
+        }
+    }
+    result.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Split on commas that are not nested in parentheses or quotes
+fn split_top_level(body: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut in_single = false;
+    let mut in_double = false;
+    let mut start = 0;
+
+    for (idx, ch) in body.char_indices() {
+        match ch {
+            '\'' if !in_double => in_single = !in_single,
+            '"' if !in_single => in_double = !in_double,
+            '(' if !in_single && !in_double => depth += 1,
+            ')' if !in_single && !in_double => depth -= 1,
+            ',' if depth == 0 && !in_single && !in_double => {
+                parts.push(&body[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&body[start..]);
+    parts
+}
+
+/// Replace `'\\x..'::bytea` literals written by the PostgreSQL dialect with
+/// VARBYTE `from_hex()` calls, leaving other string literals untouched
+fn rewrite_bytea_literals(sql: &str) -> String {
+    let mut result = String::with_capacity(sql.len());
+    let mut rest = sql;
+
+    while let Some(start) = rest.find('\'') {
+        result.push_str(&rest[..start]);
+        let literal_body = &rest[start + 1..];
+
+        // Find the closing quote, skipping doubled quotes
+        let mut end = None;
+        let mut chars = literal_body.char_indices().peekable();
+        while let Some((idx, ch)) = chars.next() {
+            if ch == '\'' {
+                if matches!(chars.peek(), Some((_, '\''))) {
+                    chars.next();
+                    continue;
+                }
+                end = Some(idx);
+                break;
+            }
+        }
+
+        let Some(end) = end else {
+            result.push_str(&rest[start..]);
+            return result;
+        };
+
+        let content = &literal_body[..end];
+        let after = &literal_body[end + 1..];
+        let hex_digits = content.trim_start_matches('\\').strip_prefix('x');
+
+        match hex_digits {
+            Some(hex)
+                if after.starts_with("::bytea") && hex.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                result.push_str(&format!("from_hex('{}')", hex));
+                rest = &after["::bytea".len()..];
+            }
+            _ => {
+                result.push_str(&rest[start..start + end + 2]);
+                rest = after;
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_create_table() {
+        let input = "CREATE TABLE IF NOT EXISTS \"public\".\"users\" (\"id\" integer DEFAULT nextval('users_id_seq'::regclass) NOT NULL, \"name\" character varying(100) COLLATE \"C\", \"bio\" text, \"avatar\" bytea, \"meta\" jsonb, CONSTRAINT \"users_pkey\" PRIMARY KEY (id), CONSTRAINT \"users_org_fkey\" FOREIGN KEY (org_id) REFERENCES orgs(id) ON DELETE CASCADE, CONSTRAINT \"age_check\" CHECK (age > 0));";
+        let output = translate_postgres_statement(input).unwrap();
+        assert_eq!(
+            output,
+            "CREATE TABLE IF NOT EXISTS \"public\".\"users\" (\"id\" integer GENERATED BY DEFAULT AS IDENTITY(1,1) NOT NULL, \"name\" VARCHAR(400), \"bio\" VARCHAR(65535), \"avatar\" VARBYTE(1024000), \"meta\" SUPER, CONSTRAINT \"users_pkey\" PRIMARY KEY (id), CONSTRAINT \"users_org_fkey\" FOREIGN KEY (org_id) REFERENCES orgs(id));"
+        );
+    }
+
+    #[test]
+    fn skips_indexes_and_rewrites_bytea() {
+        assert!(translate_postgres_statement("CREATE INDEX idx ON t (a);").is_none());
+        assert_eq!(
+            translate_postgres_statement("INSERT INTO t VALUES ('\\xcafe'::bytea, 'it''s');")
+                .unwrap(),
+            "INSERT INTO t VALUES (from_hex('cafe'), 'it''s');"
+        );
+    }
+
+    #[test]
+    fn splits_large_inserts() {
+        let big = "x".repeat(MAX_INSERT_BYTES / 2);
+        let rows = vec![vec![SqlValue::String(big)]; 3];
+        let sql = REDSHIFT_DIALECT.insert_values_sql("t", &["v".to_string()], &rows);
+        assert_eq!(sql.lines().count(), 3);
+        assert!(sql.lines().all(|line| line.ends_with(';')));
+    }
+}
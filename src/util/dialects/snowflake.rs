@@ -0,0 +1,152 @@
+use crate::engine::dialect::{insert_target, SqlDialect};
+use crate::engine::value::SqlValue;
+
+/// Snowflake caps a VALUES clause at 16,384 rows.
+const MAX_ROWS_PER_INSERT: usize = 16_384;
+
+#[derive(Debug)]
+pub struct SnowflakeDialect;
+
+pub static SNOWFLAKE_DIALECT: SnowflakeDialect = SnowflakeDialect;
+
+impl SqlDialect for SnowflakeDialect {
+    fn name(&self) -> &'static str {
+        "Snowflake"
+    }
+
+    fn quote_identifier(&self, name: &str) -> String {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+
+    fn backslash_escapes(&self) -> bool {
+        true
+    }
+
+    fn to_literal(&self, value: &SqlValue) -> String {
+        match value {
+            SqlValue::Null => "NULL".to_string(),
+            SqlValue::Bool(v) => {
+                if *v {
+                    "TRUE".to_string()
+                } else {
+                    "FALSE".to_string()
+                }
+            }
+            SqlValue::Int(v) => v.to_string(),
+            SqlValue::Float(v) => {
+                if v.is_nan() {
+                    "'NaN'".to_string()
+                } else if v.is_infinite() {
+                    if v.is_sign_positive() {
+                        "'inf'".to_string()
+                    } else {
+                        "'-inf'".to_string()
+                    }
+                } else {
+                    v.to_string()
+                }
+            }
+            SqlValue::Decimal(v) => v.clone(),
+            SqlValue::String(v) => escape_string(v),
+            SqlValue::Bytes(bytes) => format!("X'{}'", hex::encode_upper(bytes)),
+            SqlValue::Date { y, m, d } => format!("DATE '{:04}-{:02}-{:02}'", y, m, d),
+            SqlValue::Time { h, m, s, us, .. } => {
+                if *us == 0 {
+                    format!("TIME '{:02}:{:02}:{:02}'", h, m, s)
+                } else {
+                    format!("TIME '{:02}:{:02}:{:02}.{:06}'", h, m, s, us)
+                }
+            }
+            SqlValue::Timestamp {
+                y,
+                m,
+                d,
+                hh,
+                mm,
+                ss,
+                us,
+            } => format!(
+                "TIMESTAMP '{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06}'",
+                y, m, d, hh, mm, ss, us
+            ),
+        }
+    }
+
+    fn insert_values_sql(&self, table: &str, columns: &[String], rows: &[Vec<SqlValue>]) -> String {
+        let mut prefix = String::new();
+        prefix.push_str("INSERT INTO ");
+        prefix.push_str(&insert_target(self, table, columns));
+        prefix.push_str(" VALUES ");
+
+        let mut sql = String::new();
+        for (chunk_idx, chunk) in rows.chunks(MAX_ROWS_PER_INSERT).enumerate() {
+            if chunk_idx > 0 {
+                sql.push('\n');
+            }
+            sql.push_str(&prefix);
+            for (row_idx, row) in chunk.iter().enumerate() {
+                if row_idx > 0 {
+                    sql.push_str(", ");
+                }
+                sql.push('(');
+                for (col_idx, value) in row.iter().enumerate() {
+                    if col_idx > 0 {
+                        sql.push_str(", ");
+                    }
+                    sql.push_str(&self.to_literal(value));
+                }
+                sql.push(')');
+            }
+            sql.push(';');
+        }
+        sql
+    }
+}
+
+/// Snowflake column type used to store a value of the given kind
+pub fn snowflake_column_type(value: &SqlValue) -> &'static str {
+    match value {
+        SqlValue::Int(_) => "NUMBER(38,0)",
+        SqlValue::Float(_) => "FLOAT",
+        SqlValue::Decimal(_) => "NUMBER(38,10)",
+        SqlValue::Bool(_) => "BOOLEAN",
+        SqlValue::String(_) => "VARCHAR",
+        SqlValue::Date { .. } => "DATE",
+        SqlValue::Time { .. } => "TIME",
+        SqlValue::Timestamp { .. } => "TIMESTAMP_NTZ",
+        SqlValue::Bytes(_) => "BINARY",
+        SqlValue::Null => "VARCHAR",
+    }
+}
+
+/// Snowflake string literals honour backslash escapes
+fn escape_string(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 8);
+    result.push('\'');
+    for ch in value.chars() {
+        match ch {
+            '\'' => result.push_str("''"),
+            '\\' => result.push_str("\\\\"),
+            _ => result.push(ch),
+        }
+    }
+    result.push('\'');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_backslashes_and_quotes() {
+        assert_eq!(
+            SNOWFLAKE_DIALECT.to_literal(&SqlValue::String("C:\\it's".into())),
+            "'C:\\\\it''s'"
+        );
+        assert_eq!(
+            SNOWFLAKE_DIALECT.to_literal(&SqlValue::Bytes(vec![0xab])),
+            "X'AB'"
+        );
+    }
+}
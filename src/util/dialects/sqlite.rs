@@ -0,0 +1,183 @@
+use crate::engine::dialect::{
+    format_qualified_table, insert_target, split_index_statements, OnConflict, SqlDialect,
+};
+use crate::engine::value::SqlValue;
+use crate::util::splitter::Syntax;
+
+#[derive(Debug)]
+pub struct SqliteDialect;
+
+pub static SQLITE_DIALECT: SqliteDialect = SqliteDialect;
+
+impl SqlDialect for SqliteDialect {
+    fn name(&self) -> &'static str {
+        "SQLite"
+    }
+
+    fn quote_identifier(&self, name: &str) -> String {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::SQLITE
+    }
+
+    fn to_literal(&self, value: &SqlValue) -> String {
+        match value {
+            SqlValue::Null => "NULL".to_string(),
+            SqlValue::Bool(v) => {
+                if *v {
+                    "1".to_string()
+                } else {
+                    "0".to_string()
+                }
+            }
+            SqlValue::Int(v) => v.to_string(),
+            // REAL cannot store NaN; SQLite turns it into NULL anyway
+            SqlValue::Float(v) if v.is_nan() => "NULL".to_string(),
+            SqlValue::Float(v) if v.is_infinite() => {
+                if v.is_sign_positive() {
+                    "9e999".to_string()
+                } else {
+                    "-9e999".to_string()
+                }
+            }
+            SqlValue::Float(v) => v.to_string(),
+            SqlValue::Decimal(v) => v.clone(),
+            SqlValue::String(v) => escape_single_quotes(v),
+            SqlValue::Bytes(bytes) => format!("X'{}'", hex::encode(bytes)),
+            // Date/time values are stored as ISO 8601 text, matching SQLite's date functions
+            SqlValue::Date { y, m, d } => format!("'{:04}-{:02}-{:02}'", y, m, d),
+            SqlValue::Time { neg, h, m, s, us } => {
+                let sign = if *neg { "-" } else { "" };
+                if *us == 0 {
+                    format!("'{}{:02}:{:02}:{:02}'", sign, h, m, s)
+                } else {
+                    format!("'{}{:02}:{:02}:{:02}.{:06}'", sign, h, m, s, us)
+                }
+            }
+            SqlValue::Timestamp {
+                y,
+                m,
+                d,
+                hh,
+                mm,
+                ss,
+                us,
+            } => {
+                if *us == 0 {
+                    format!("'{:04}-{:02}-{:02} {:02}:{:02}:{:02}'", y, m, d, hh, mm, ss)
+                } else {
+                    format!(
+                        "'{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06}'",
+                        y, m, d, hh, mm, ss, us
+                    )
+                }
+            }
+        }
+    }
+
+    fn insert_values_sql(&self, table: &str, columns: &[String], rows: &[Vec<SqlValue>]) -> String {
+        let mut sql = String::new();
+        sql.push_str("INSERT INTO ");
+        sql.push_str(&insert_target(self, table, columns));
+        sql.push_str(" VALUES ");
+
+        for (row_idx, row) in rows.iter().enumerate() {
+            if row_idx > 0 {
+                sql.push_str(", ");
+            }
+            sql.push('(');
+            for (col_idx, value) in row.iter().enumerate() {
+                if col_idx > 0 {
+                    sql.push_str(", ");
+                }
+                sql.push_str(&self.to_literal(value));
+            }
+            sql.push(')');
+        }
+        sql.push(';');
+        sql
+    }
+
+    /// Updating upserts on the primary key; without a key, or with nothing
+    /// outside it, conflicting rows are skipped
+    fn conflict_clauses(
+        &self,
+        on_conflict: OnConflict,
+        columns: &[String],
+        key: &[String],
+    ) -> Option<(&'static str, String)> {
+        let updates: Vec<String> = columns
+            .iter()
+            .filter(|column| !key.contains(column))
+            .map(|column| {
+                let column = self.quote_identifier(column);
+                format!("{} = excluded.{}", column, column)
+            })
+            .collect();
+        match on_conflict {
+            OnConflict::Error => Some(("INSERT INTO", String::new())),
+            OnConflict::Ignore => Some(("INSERT OR IGNORE INTO", String::new())),
+            OnConflict::Replace => Some(("INSERT OR REPLACE INTO", String::new())),
+            OnConflict::Update if key.is_empty() || updates.is_empty() => {
+                Some(("INSERT OR IGNORE INTO", String::new()))
+            }
+            OnConflict::Update => {
+                let key: Vec<String> = key.iter().map(|c| self.quote_identifier(c)).collect();
+                Some((
+                    "INSERT INTO",
+                    format!(
+                        " ON CONFLICT ({}) DO UPDATE SET {}",
+                        key.join(", "),
+                        updates.join(", ")
+                    ),
+                ))
+            }
+        }
+    }
+
+    fn split_secondary_indexes(&self, _table: &str, create: &str) -> (String, Vec<String>) {
+        split_index_statements(create)
+    }
+
+    /// SQLite has no TRUNCATE; an unqualified DELETE empties the table as fast
+    fn truncate_table_statement(&self, table: &str) -> String {
+        format!("DELETE FROM {}", format_qualified_table(self, table))
+    }
+}
+
+fn escape_single_quotes(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 8);
+    result.push('\'');
+    for ch in value.chars() {
+        if ch == '\'' {
+            result.push('\'');
+        }
+        result.push(ch);
+    }
+    result.push('\'');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_identifiers() {
+        assert_eq!(SQLITE_DIALECT.quote_identifier("users"), "\"users\"");
+        assert_eq!(
+            SQLITE_DIALECT.quote_identifier("user\"table"),
+            "\"user\"\"table\""
+        );
+    }
+
+    #[test]
+    fn formats_blobs_as_hex() {
+        assert_eq!(
+            SQLITE_DIALECT.to_literal(&SqlValue::Bytes(vec![0xde, 0xad])),
+            "X'dead'"
+        );
+    }
+}
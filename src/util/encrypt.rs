@@ -0,0 +1,169 @@
+use age::stream::{StreamReader, StreamWriter};
+use age::x25519;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+/// How every age file starts, whatever its recipients
+const AGE_MAGIC: &[u8] = b"age-encryption.org/v1";
+
+/// `--encrypt age:<recipient>`: a public key the dump is encrypted to
+#[derive(Debug, Clone)]
+pub struct Recipient(x25519::Recipient);
+
+impl FromStr for Recipient {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("age", key)) => key
+                .trim()
+                .parse()
+                .map(Recipient)
+                .map_err(|e| format!("invalid age recipient '{}': {}", key, e)),
+            Some(("gpg", _)) => Err(
+                "GPG recipients are not supported; generate a key with age-keygen and use age:<recipient>"
+                    .to_string(),
+            ),
+            _ => Err(format!(
+                "unknown recipient '{}' (expected age:<recipient>)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "age:{}", self.0)
+    }
+}
+
+/// The recipients a dump is encrypted to; any one of their identities
+/// decrypts it
+#[derive(Debug, Clone)]
+pub struct Encryption {
+    recipients: Vec<Recipient>,
+}
+
+impl Encryption {
+    /// `None` when there is nobody to encrypt to
+    pub fn new(recipients: Vec<Recipient>) -> Option<Self> {
+        (!recipients.is_empty()).then_some(Encryption { recipients })
+    }
+
+    /// A key made up for one run, so temporary files are encrypted too and
+    /// no plaintext touches the disk
+    pub fn ephemeral() -> (Self, Decryption) {
+        let identity = x25519::Identity::generate();
+        let encryption = Encryption {
+            recipients: vec![Recipient(identity.to_public())],
+        };
+        (
+            encryption,
+            Decryption {
+                identities: vec![identity],
+            },
+        )
+    }
+
+    /// Wrap `writer` so what is written to it is encrypted. The stream must
+    /// be finished with `StreamWriter::finish`, or it cannot be decrypted.
+    pub fn seal<W: Write>(&self, writer: W) -> io::Result<StreamWriter<W>> {
+        let recipients = self.recipients.iter().map(|r| &r.0 as &dyn age::Recipient);
+        age::Encryptor::with_recipients(recipients)
+            .map_err(io::Error::other)?
+            .wrap_output(writer)
+    }
+}
+
+/// `--identity FILE`: the private keys an encrypted dump is read with
+#[derive(Clone)]
+pub struct Decryption {
+    identities: Vec<x25519::Identity>,
+}
+
+impl Decryption {
+    /// Read the keys in identity files as written by `age-keygen`: one
+    /// `AGE-SECRET-KEY-` line per key, with `#` comments
+    pub fn from_files(paths: &[String]) -> io::Result<Option<Self>> {
+        let mut identities = Vec::new();
+        for path in paths {
+            let before = identities.len();
+            let text = std::fs::read_to_string(path).map_err(|e| {
+                io::Error::new(e.kind(), format!("Failed to read '{}': {}", path, e))
+            })?;
+            for line in text.lines().map(str::trim) {
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let identity = line.parse().map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("'{}' holds an invalid age identity: {}", path, e),
+                    )
+                })?;
+                identities.push(identity);
+            }
+            if identities.len() == before {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("'{}' holds no age identities", path),
+                ));
+            }
+        }
+        Ok((!identities.is_empty()).then_some(Decryption { identities }))
+    }
+
+    /// Wrap `reader`, an age file, so reading it gives the plaintext
+    pub fn open<R: Read>(&self, reader: R) -> io::Result<StreamReader<R>> {
+        let identities = self.identities.iter().map(|i| i as &dyn age::Identity);
+        age::Decryptor::new(reader)
+            .and_then(|decryptor| decryptor.decrypt(identities))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+}
+
+/// Whether input starting with `start` is an age file
+pub fn is_encrypted(start: &[u8]) -> bool {
+    start.starts_with(AGE_MAGIC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_a_recipient() {
+        let (encryption, decryption) = Encryption::ephemeral();
+        let sql = "INSERT INTO t VALUES (1, 'secret');\n".repeat(1000);
+
+        let mut writer = encryption.seal(Vec::new()).unwrap();
+        writer.write_all(sql.as_bytes()).unwrap();
+        let sealed = writer.finish().unwrap();
+        assert!(is_encrypted(&sealed));
+        assert!(!sealed.windows(6).any(|w| w == b"secret"));
+
+        let mut restored = String::new();
+        decryption
+            .open(sealed.as_slice())
+            .unwrap()
+            .read_to_string(&mut restored)
+            .unwrap();
+        assert_eq!(restored, sql);
+
+        // Someone else's key cannot read it
+        let (_, other) = Encryption::ephemeral();
+        assert!(other.open(sealed.as_slice()).is_err());
+    }
+
+    #[test]
+    fn parses_recipients() {
+        let (encryption, _) = Encryption::ephemeral();
+        let key = encryption.recipients[0].to_string();
+        assert!(key.parse::<Recipient>().is_ok());
+        assert!("age:nonsense".parse::<Recipient>().is_err());
+        assert!("gpg:ops@example.com".parse::<Recipient>().is_err());
+        assert!(key.trim_start_matches("age:").parse::<Recipient>().is_err());
+    }
+}
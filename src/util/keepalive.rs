@@ -0,0 +1,102 @@
+use crate::engine::DbSession;
+use anyhow::{Context, Result};
+use indicatif::HumanDuration;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// `--keepalive` and `--snapshot-warn-age`: looks after the transaction
+/// holding a consistent snapshot while a long dump or migration runs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SnapshotWatch {
+    /// Ping the session holding the snapshot this often while it sits idle
+    pub keepalive: Option<Duration>,
+    /// Warn once the snapshot has been open this long, and again each time
+    /// that much more time passes
+    pub warn_age: Option<Duration>,
+}
+
+impl SnapshotWatch {
+    /// Run `work` while watching a snapshot taken at `started`. `idle` is the
+    /// session holding the snapshot when `work` leaves it unused, so that it
+    /// can be pinged; a failed ping fails the run, as the snapshot is gone.
+    pub async fn watch<T>(
+        &self,
+        started: Instant,
+        idle: Option<&mut dyn DbSession>,
+        work: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        tokio::select! {
+            result = work => result,
+            Err(e) = self.tend(started, idle) => Err(e),
+        }
+    }
+
+    /// Ping and warn on schedule; returns only when a ping fails
+    async fn tend(&self, started: Instant, mut idle: Option<&mut dyn DbSession>) -> Result<()> {
+        let interval = self.keepalive.filter(|_| idle.is_some());
+        let mut next_ping = interval.map(|interval| Instant::now() + interval);
+        let mut next_warning = self.warn_age.map(|age| started + age);
+
+        loop {
+            let wake = match (next_ping, next_warning) {
+                (Some(ping), Some(warning)) => ping.min(warning),
+                (Some(at), None) | (None, Some(at)) => at,
+                (None, None) => return std::future::pending().await,
+            };
+            tokio::time::sleep_until(wake).await;
+            let now = Instant::now();
+
+            if let (Some(at), Some(interval), Some(session)) = (next_ping, interval, &mut idle) {
+                if at <= now {
+                    session
+                        .execute("SELECT 1")
+                        .await
+                        .context("Keepalive ping on the snapshot connection failed")?;
+                    next_ping = Some(Instant::now() + interval);
+                }
+            }
+
+            if let (Some(at), Some(age)) = (next_warning, self.warn_age) {
+                if at <= now {
+                    eprintln!(
+                        "Warning: the consistent snapshot has been open for {}; a long-lived snapshot holds back cleanup on the server and may be ended by its timeouts",
+                        HumanDuration(now - started)
+                    );
+                    next_warning = Some(at + age);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::sqlite::SqliteEngine;
+    use crate::engine::DbEngine;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pings_the_idle_session_until_the_work_is_done() {
+        let mut session = SqliteEngine.connect("sqlite::memory:").await.unwrap();
+        let watch = SnapshotWatch {
+            keepalive: Some(Duration::from_millis(5)),
+            warn_age: Some(Duration::from_millis(10)),
+        };
+
+        let started = Instant::now();
+        let work = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(42)
+        };
+        let result = watch.watch(started, Some(&mut *session), work).await;
+        assert_eq!(result.unwrap(), 42);
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        // The work's own failure is passed through
+        let result: Result<()> = watch
+            .watch(started, None, async { anyhow::bail!("copy failed") })
+            .await;
+        assert_eq!(result.unwrap_err().to_string(), "copy failed");
+    }
+}
@@ -0,0 +1,579 @@
+use crate::engine::filter::table_matches;
+use crate::engine::ReplicationPosition;
+use crate::util::compress::Codec;
+use crate::util::storage;
+use anyhow::{anyhow, bail, Context, Result};
+use aws_lc_rs::digest;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// File in a directory dump listing its tables and their files
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// File in a directory dump creating its views, run after every table
+pub const VIEWS_FILE: &str = "views.sql";
+
+/// File in a directory dump dropping its views, run before any table, as
+/// tables cannot be dropped while views depend on them
+pub const DROP_VIEWS_FILE: &str = "drop-views.sql";
+
+/// File in a directory dump creating its stored procedures and functions,
+/// run before any table, as triggers may call them
+pub const ROUTINES_FILE: &str = "routines.sql";
+
+/// File in a directory dump creating the schemas its tables live in, run
+/// ahead of the extensions, which may be installed into them
+pub const SCHEMAS_FILE: &str = "schemas.sql";
+
+/// File in a directory dump creating the extensions its tables rely on, run
+/// first
+pub const EXTENSIONS_FILE: &str = "extensions.sql";
+
+/// File in a directory dump creating its scheduled events, run last
+pub const EVENTS_FILE: &str = "events.sql";
+
+/// File in a directory dump creating the server's user accounts, run first
+pub const USERS_FILE: &str = "users.sql";
+
+/// File in a directory dump granting those accounts their privileges, run last
+pub const GRANTS_FILE: &str = "grants.sql";
+
+/// Suffix of the manifest written beside a single-file dump
+pub const SIDECAR_SUFFIX: &str = ".manifest.json";
+
+/// Layout version written to the manifest; readers reject newer ones
+const MANIFEST_VERSION: u64 = 1;
+
+/// `--format`: a single SQL file, a directory with files per table, or a
+/// single file with the rows in binary frames (see [`crate::util::binary`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DumpFormat {
+    #[default]
+    File,
+    Dir,
+    Binary,
+}
+
+impl fmt::Display for DumpFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DumpFormat::File => "file",
+            DumpFormat::Dir => "dir",
+            DumpFormat::Binary => "binary",
+        })
+    }
+}
+
+impl FromStr for DumpFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "file" => Ok(DumpFormat::File),
+            "dir" | "directory" => Ok(DumpFormat::Dir),
+            "binary" => Ok(DumpFormat::Binary),
+            _ => Err(format!(
+                "unknown dump format '{}' (expected file, dir or binary)",
+                s
+            )),
+        }
+    }
+}
+
+/// What was dumped of a table's data: its row count, and the size and
+/// SHA-256 of the SQL written for it before compression. In a directory dump
+/// that covers the whole data file; in a single file, the table's data
+/// section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataSection {
+    pub rows: u64,
+    pub bytes: u64,
+    /// Lowercase hex
+    pub sha256: String,
+}
+
+/// Passes writes through, counting and hashing them into a [`DataSection`]
+pub struct SectionWriter<W: Write> {
+    inner: W,
+    digest: digest::Context,
+    bytes: u64,
+}
+
+impl<W: Write> SectionWriter<W> {
+    pub fn new(inner: W) -> Self {
+        SectionWriter {
+            inner,
+            digest: digest::Context::new(&digest::SHA256),
+            bytes: 0,
+        }
+    }
+
+    /// The writer back, and what went through it
+    pub fn finish(self, rows: u64) -> (W, DataSection) {
+        let section = DataSection {
+            rows,
+            bytes: self.bytes,
+            sha256: hex::encode(self.digest.finish()),
+        };
+        (self.inner, section)
+    }
+}
+
+impl<W: Write> Write for SectionWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.digest.update(&buf[..n]);
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// One table of a dump, with its files relative to the directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableEntry {
+    pub name: String,
+    /// `schema/<table>.sql`, unless the dump was data only
+    pub schema: Option<String>,
+    /// `data/<table>.sql[.gz]`, unless the dump was schema only
+    pub data: Option<String>,
+    /// `post-data/<table>.sql` with the indexes and foreign keys, run after
+    /// every table's data, when the dump was taken with `--post-data`
+    pub post_data: Option<String>,
+    /// Filled in once the table's data is dumped
+    pub section: Option<DataSection>,
+}
+
+impl TableEntry {
+    /// Whether `--tables` entry `pattern` names this table, as it would
+    /// when dumping (see `table_matches`)
+    pub fn matches(&self, pattern: &str) -> bool {
+        table_matches(pattern, &self.name)
+    }
+}
+
+/// Contents of `manifest.json`: the source dialect and the tables in dump
+/// order, with what was dumped of each. It is written last, so a directory
+/// without one is incomplete. A single-file dump gets one beside it, listing
+/// no files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub format: DumpFormat,
+    pub dialect: String,
+    /// When the dump was taken; left out of reproducible dumps
+    pub created: Option<String>,
+    pub compress: Codec,
+    pub tables: Vec<TableEntry>,
+    /// [`DROP_VIEWS_FILE`] and [`VIEWS_FILE`], when the dump has views
+    pub drop_views: Option<String>,
+    pub views: Option<String>,
+    /// [`ROUTINES_FILE`], when the dump has routines
+    pub routines: Option<String>,
+    /// [`EVENTS_FILE`], when the dump has events
+    pub events: Option<String>,
+    /// [`EXTENSIONS_FILE`], when the source has extensions
+    pub extensions: Option<String>,
+    /// [`SCHEMAS_FILE`], when the tables are in schemas to create
+    pub schemas: Option<String>,
+    /// [`USERS_FILE`] and [`GRANTS_FILE`], when the dump has user accounts
+    pub users: Option<String>,
+    pub grants: Option<String>,
+    /// Where the source's change stream stood at the snapshot, with
+    /// `--master-data`
+    pub replication: Option<ReplicationPosition>,
+}
+
+impl Manifest {
+    /// Name the files of each table in a directory dump. Table names become
+    /// file names with anything unsafe replaced, and a suffix where two would
+    /// collide.
+    pub fn new(
+        format: DumpFormat,
+        dialect: &str,
+        compress: Codec,
+        tables: &[String],
+        schema: bool,
+        data: bool,
+    ) -> Self {
+        let dir = format == DumpFormat::Dir;
+        let mut used = HashSet::new();
+        let tables = tables
+            .iter()
+            .map(|table| {
+                let stem = unique_stem(table, &mut used);
+                TableEntry {
+                    name: table.clone(),
+                    schema: (dir && schema).then(|| format!("schema/{}.sql", stem)),
+                    data: (dir && data)
+                        .then(|| format!("data/{}.sql{}", stem, compress.extension())),
+                    post_data: None,
+                    section: None,
+                }
+            })
+            .collect();
+
+        Manifest {
+            format,
+            dialect: dialect.to_string(),
+            created: Some(chrono::Utc::now().to_rfc3339()),
+            compress,
+            tables,
+            drop_views: None,
+            views: None,
+            routines: None,
+            events: None,
+            extensions: None,
+            schemas: None,
+            users: None,
+            grants: None,
+            replication: None,
+        }
+    }
+
+    /// Give each table with a schema file a post-data file of the same name
+    pub fn add_post_data(&mut self) {
+        for table in &mut self.tables {
+            table.post_data = table
+                .schema
+                .as_ref()
+                .and_then(|file| file.strip_prefix("schema/"))
+                .map(|file| format!("post-data/{}", file));
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let tables: Vec<Value> = self
+            .tables
+            .iter()
+            .map(|t| {
+                let mut table = json!({ "name": t.name, "schema": t.schema, "data": t.data });
+                if let Some(post_data) = &t.post_data {
+                    table["post_data"] = json!(post_data);
+                }
+                if let Some(section) = &t.section {
+                    table["rows"] = json!(section.rows);
+                    table["bytes"] = json!(section.bytes);
+                    table["sha256"] = json!(section.sha256);
+                }
+                table
+            })
+            .collect();
+        let mut manifest = json!({
+            "version": MANIFEST_VERSION,
+            "generator": "migrasquiel",
+            "format": self.format.to_string(),
+            "dialect": self.dialect,
+            "compress": self.compress.to_string(),
+            "tables": tables,
+        });
+        if let Some(created) = &self.created {
+            manifest["created"] = json!(created);
+        }
+        if let (Some(drop_views), Some(views)) = (&self.drop_views, &self.views) {
+            manifest["drop_views"] = json!(drop_views);
+            manifest["views"] = json!(views);
+        }
+        if let Some(routines) = &self.routines {
+            manifest["routines"] = json!(routines);
+        }
+        if let Some(events) = &self.events {
+            manifest["events"] = json!(events);
+        }
+        if let Some(extensions) = &self.extensions {
+            manifest["extensions"] = json!(extensions);
+        }
+        if let Some(schemas) = &self.schemas {
+            manifest["schemas"] = json!(schemas);
+        }
+        if let Some(users) = &self.users {
+            manifest["users"] = json!(users);
+        }
+        if let Some(grants) = &self.grants {
+            manifest["grants"] = json!(grants);
+        }
+        if let Some(replication) = &self.replication {
+            manifest["replication"] = replication_to_json(replication);
+        }
+        manifest
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let version = value["version"].as_u64().unwrap_or(0);
+        if version == 0 || version > MANIFEST_VERSION {
+            bail!("Unsupported manifest version {}", value["version"]);
+        }
+
+        let text = |field: &str| {
+            value[field]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| anyhow!("Manifest is missing '{}'", field))
+        };
+        let file = |table: &Value, field: &str| -> Result<Option<String>> {
+            match &table[field] {
+                Value::Null => Ok(None),
+                Value::String(path) if is_relative(path) => Ok(Some(path.clone())),
+                other => bail!("Invalid {} file {} in manifest", field, other),
+            }
+        };
+        let section = |table: &Value| -> Result<Option<DataSection>> {
+            if table["sha256"].is_null() {
+                return Ok(None);
+            }
+            match (
+                table["rows"].as_u64(),
+                table["bytes"].as_u64(),
+                table["sha256"].as_str(),
+            ) {
+                (Some(rows), Some(bytes), Some(sha256)) => Ok(Some(DataSection {
+                    rows,
+                    bytes,
+                    sha256: sha256.to_ascii_lowercase(),
+                })),
+                _ => bail!("Invalid row count or checksum for table {}", table["name"]),
+            }
+        };
+
+        let tables = value["tables"]
+            .as_array()
+            .ok_or_else(|| anyhow!("Manifest is missing 'tables'"))?
+            .iter()
+            .map(|table| {
+                Ok(TableEntry {
+                    name: table["name"]
+                        .as_str()
+                        .ok_or_else(|| anyhow!("Manifest table without a name"))?
+                        .to_string(),
+                    schema: file(table, "schema")?,
+                    data: file(table, "data")?,
+                    post_data: file(table, "post_data")?,
+                    section: section(table)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let replication = match &value["replication"] {
+            Value::Null => None,
+            replication => Some(replication_from_json(replication)?),
+        };
+
+        // Directory dumps came first and did not record their format
+        let format = match value["format"].as_str() {
+            Some(format) => format.parse().map_err(anyhow::Error::msg)?,
+            None => DumpFormat::Dir,
+        };
+
+        Ok(Manifest {
+            format,
+            dialect: text("dialect")?,
+            created: value["created"].as_str().map(str::to_string),
+            compress: text("compress")?.parse().map_err(anyhow::Error::msg)?,
+            tables,
+            drop_views: file(value, "drop_views")?,
+            views: file(value, "views")?,
+            routines: file(value, "routines")?,
+            events: file(value, "events")?,
+            extensions: file(value, "extensions")?,
+            schemas: file(value, "schemas")?,
+            users: file(value, "users")?,
+            grants: file(value, "grants")?,
+            replication,
+        })
+    }
+
+    /// Write the manifest to `path`, a local file or an object storage URL
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self.to_json())? + "\n";
+        let location = path.to_string_lossy();
+        if storage::is_remote(&location) {
+            storage::put(&location, json.into_bytes())
+        } else {
+            std::fs::write(path, json)
+        }
+        .with_context(|| format!("Failed to write '{}'", path.display()))
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read '{}'", path.display()))?;
+        let value: Value = serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        Self::from_json(&value).with_context(|| format!("Invalid manifest '{}'", path.display()))
+    }
+}
+
+/// A file name for `table` not yet in `used`
+fn unique_stem(table: &str, used: &mut HashSet<String>) -> String {
+    let stem: String = table
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    // A leading dot would hide the file
+    let stem = match stem.strip_prefix('.') {
+        Some(rest) => format!("_{}", rest),
+        None => stem,
+    };
+
+    let mut candidate = stem.clone();
+    let mut n = 1;
+    while !used.insert(candidate.to_ascii_lowercase()) {
+        n += 1;
+        candidate = format!("{}-{}", stem, n);
+    }
+    candidate
+}
+
+/// Manifest paths stay inside the dump directory
+fn is_relative(path: &str) -> bool {
+    let path = Path::new(path);
+    path.is_relative()
+        && path
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)))
+}
+
+/// A replication position as the manifest and `replicate`'s position file
+/// record it
+pub fn replication_to_json(position: &ReplicationPosition) -> Value {
+    match position {
+        ReplicationPosition::Binlog {
+            file,
+            position,
+            gtid_set,
+        } => {
+            let mut replication = json!({ "binlog_file": file, "binlog_position": position });
+            if let Some(gtid_set) = gtid_set {
+                replication["gtid_set"] = json!(gtid_set);
+            }
+            replication
+        }
+        ReplicationPosition::Wal { lsn } => json!({ "wal_lsn": lsn }),
+    }
+}
+
+pub fn replication_from_json(replication: &Value) -> Result<ReplicationPosition> {
+    match (
+        replication["binlog_file"].as_str(),
+        replication["binlog_position"].as_u64(),
+        replication["wal_lsn"].as_str(),
+    ) {
+        (Some(file), Some(position), None) => Ok(ReplicationPosition::Binlog {
+            file: file.to_string(),
+            position,
+            gtid_set: replication["gtid_set"].as_str().map(str::to_string),
+        }),
+        (None, None, Some(lsn)) => Ok(ReplicationPosition::Wal {
+            lsn: lsn.to_string(),
+        }),
+        _ => bail!("Invalid replication position {}", replication),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_table_files_and_round_trips() {
+        let tables = [
+            "public.orders".to_string(),
+            "Orders/2024".to_string(),
+            "orders_2024".to_string(),
+            ".hidden".to_string(),
+        ];
+        let mut manifest = Manifest::new(
+            DumpFormat::Dir,
+            "PostgreSQL",
+            Codec::Gzip,
+            &tables,
+            true,
+            true,
+        );
+
+        let files: Vec<_> = manifest
+            .tables
+            .iter()
+            .map(|t| t.data.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            files,
+            [
+                "data/public.orders.sql.gz",
+                "data/Orders_2024.sql.gz",
+                "data/orders_2024-2.sql.gz",
+                "data/_hidden.sql.gz",
+            ]
+        );
+        assert_eq!(
+            manifest.tables[0].schema.as_deref(),
+            Some("schema/public.orders.sql")
+        );
+
+        assert!(manifest.tables[0].matches("orders"));
+        assert!(manifest.tables[0].matches("PUBLIC.orders"));
+        assert!(!manifest.tables[0].matches("sales.orders"));
+
+        let mut writer = SectionWriter::new(Vec::new());
+        writer.write_all(b"abc").unwrap();
+        let (written, section) = writer.finish(1);
+        assert_eq!(written, b"abc");
+        assert_eq!(
+            section.sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(section.bytes, 3);
+        manifest.tables[1].section = Some(section);
+        manifest.drop_views = Some(DROP_VIEWS_FILE.to_string());
+        manifest.views = Some(VIEWS_FILE.to_string());
+        manifest.routines = Some(ROUTINES_FILE.to_string());
+        manifest.events = Some(EVENTS_FILE.to_string());
+        manifest.extensions = Some(EXTENSIONS_FILE.to_string());
+        manifest.schemas = Some(SCHEMAS_FILE.to_string());
+        manifest.users = Some(USERS_FILE.to_string());
+        manifest.grants = Some(GRANTS_FILE.to_string());
+        manifest.replication = Some(ReplicationPosition::Binlog {
+            file: "binlog.000042".to_string(),
+            position: 157,
+            gtid_set: Some("3e11fa47-71ca-11e1-9e33-c80aa9429562:1-5".to_string()),
+        });
+        manifest.add_post_data();
+        assert_eq!(
+            manifest.tables[1].post_data.as_deref(),
+            Some("post-data/Orders_2024.sql")
+        );
+
+        let read = Manifest::from_json(&manifest.to_json()).unwrap();
+        assert_eq!(read, manifest);
+
+        // A single-file dump's manifest lists no files
+        let mut sidecar =
+            Manifest::new(DumpFormat::File, "MySQL", Codec::None, &tables, true, true);
+        sidecar.created = None;
+        sidecar.replication = Some(ReplicationPosition::Wal {
+            lsn: "0/16B3748".to_string(),
+        });
+        assert!(sidecar.to_json().get("created").is_none());
+        assert!(sidecar
+            .tables
+            .iter()
+            .all(|t| t.schema.is_none() && t.data.is_none()));
+        assert_eq!(Manifest::from_json(&sidecar.to_json()).unwrap(), sidecar);
+
+        let mut escaping = manifest.to_json();
+        escaping["tables"][0]["data"] = json!("../../etc/passwd");
+        assert!(Manifest::from_json(&escaping).is_err());
+    }
+}
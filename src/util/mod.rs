@@ -1 +1,16 @@
+pub mod batch;
+pub mod binary;
+pub mod charset;
+pub mod compatible;
+pub mod compress;
 pub mod dialects;
+pub mod encrypt;
+pub mod keepalive;
+pub mod manifest;
+pub mod remap;
+pub mod splitter;
+pub mod state;
+pub mod storage;
+pub mod subset;
+pub mod throttle;
+pub mod url;
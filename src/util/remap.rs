@@ -0,0 +1,571 @@
+use crate::engine::dialect::{split_table_name, SqlDialect};
+use crate::util::splitter::{is_word, Lexer, Syntax, TokenKind};
+use anyhow::{bail, Result};
+
+/// Keywords after which a name is a table's
+const TABLE_KEYWORDS: [&str; 10] = [
+    "TABLE",
+    "TABLES",
+    "INTO",
+    "FROM",
+    "JOIN",
+    "UPDATE",
+    "REFERENCES",
+    "ON",
+    "TRUNCATE",
+    "COPY",
+];
+
+/// Keywords after which a name is some other object's, in a schema
+const OBJECT_KEYWORDS: [&str; 10] = [
+    "VIEW",
+    "SEQUENCE",
+    "FUNCTION",
+    "PROCEDURE",
+    "TYPE",
+    "DOMAIN",
+    "TRIGGER",
+    "INDEX",
+    "EVENT",
+    "EXTENSION",
+];
+
+/// Functions whose string argument names a sequence or table
+const NAME_FUNCTIONS: [&str; 4] = ["NEXTVAL", "SETVAL", "CURRVAL", "PG_GET_SERIAL_SEQUENCE"];
+
+/// What the name being read stands for, by the keyword before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Context {
+    /// A column, function or anything else; its qualifier may be a table
+    Other,
+    Table,
+    Object,
+    Schema,
+}
+
+/// One part of a dotted name, as written
+#[derive(Debug, Clone)]
+struct Part {
+    name: String,
+    /// Closing quote, if the part was quoted
+    quote: Option<u8>,
+    renamed: bool,
+}
+
+/// `--remap-schema` / `--remap-table`: the names a restore puts in place
+/// of the dump's. Statements are rewritten name by name, so schemas (or
+/// MySQL databases) change wherever they qualify a name or are created,
+/// dropped, used or searched, and tables wherever a statement works on
+/// one or qualifies its columns. Text in strings is left alone, except the
+/// sequence and table names PostgreSQL passes as strings, such as
+/// `nextval('public.users_id_seq'::regclass)`.
+#[derive(Debug, Clone, Default)]
+pub struct Remap {
+    schemas: Vec<(String, String)>,
+    /// Old table, with the schema it must be in if one was given, and its
+    /// new name
+    tables: Vec<(Option<String>, String, String)>,
+}
+
+impl Remap {
+    pub fn new(schemas: &[(String, String)], tables: &[(String, String)]) -> Result<Self> {
+        let mut remap = Remap {
+            schemas: schemas.to_vec(),
+            tables: Vec::new(),
+        };
+        for (old, new) in tables {
+            if new.contains('.') {
+                bail!(
+                    "--remap-table {}:{} gives the new name with a schema; move tables between schemas with --remap-schema",
+                    old,
+                    new
+                );
+            }
+            let (schema, table) = match old.rsplit_once('.') {
+                Some((schema, table)) => (Some(schema.to_string()), table.to_string()),
+                None => (None, old.clone()),
+            };
+            remap.tables.push((schema, table, new.clone()));
+        }
+        Ok(remap)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schemas.is_empty() && self.tables.is_empty()
+    }
+
+    /// The name `schema` is restored under, if it is remapped
+    pub fn schema(&self, schema: &str) -> Option<&str> {
+        self.schemas
+            .iter()
+            .find(|(old, _)| old == schema)
+            .map(|(_, new)| new.as_str())
+    }
+
+    /// `sql` with the names remapped; names that had to be quoted anew are
+    /// quoted for `dialect`
+    pub fn apply(&self, sql: &str, dialect: &dyn SqlDialect) -> String {
+        let bytes = sql.as_bytes();
+        let syntax = dialect.syntax();
+        let mut out = String::with_capacity(sql.len() + 16);
+        let mut context = Context::Other;
+        // In a `search_path` setting, where every name is a schema
+        let mut path = false;
+        // After `nextval(` and the like, where a string is a name
+        let mut name_argument = false;
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let b = bytes[i];
+            // A MySQL `/*!40000 ... */` comment runs as code
+            if sql[i..].starts_with("/*!") {
+                let digits = bytes[i + 3..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_digit())
+                    .count();
+                out.push_str(&sql[i..i + 3 + digits]);
+                i += 3 + digits;
+                continue;
+            }
+
+            let (kind, end) = Lexer::new(syntax).next(sql, i);
+            match kind {
+                TokenKind::Str => {
+                    let text = &sql[i..end];
+                    let regclass = sql
+                        .get(end..end + 10)
+                        .is_some_and(|cast| cast.eq_ignore_ascii_case("::regclass"));
+                    let quoted = text.len() > 1 && text.starts_with('\'') && text.ends_with('\'');
+                    if (name_argument || regclass) && quoted {
+                        out.push('\'');
+                        out.push_str(&self.apply_to_text(&text[1..text.len() - 1], dialect));
+                        out.push('\'');
+                    } else if let Some((tag, body)) = dollar_body(text) {
+                        // A function's body is code, remapped like the rest
+                        out.push_str(tag);
+                        out.push_str(&self.apply(body, dialect));
+                        out.push_str(tag);
+                    } else {
+                        out.push_str(text);
+                    }
+                    name_argument = false;
+                    i = end;
+                    continue;
+                }
+                TokenKind::LineComment | TokenKind::BlockComment => {
+                    out.push_str(&sql[i..end]);
+                    i = end;
+                    continue;
+                }
+                _ => {}
+            }
+
+            if starts_name(b, syntax) {
+                let (mut parts, end) = read_name(sql, i, syntax);
+                i = end;
+                let word = match &parts[..] {
+                    [part] if part.quote.is_none() => Some(part.name.to_ascii_uppercase()),
+                    _ => None,
+                };
+                if let Some(word) = word.as_deref() {
+                    let next = if matches!(word, "IF" | "NOT" | "EXISTS" | "ONLY") {
+                        Some(context)
+                    } else if TABLE_KEYWORDS.contains(&word) {
+                        Some(Context::Table)
+                    } else if OBJECT_KEYWORDS.contains(&word) {
+                        Some(Context::Object)
+                    } else if matches!(word, "SCHEMA" | "DATABASE" | "USE") {
+                        Some(Context::Schema)
+                    } else {
+                        None
+                    };
+                    if word == "SEARCH_PATH" {
+                        path = true;
+                    }
+                    if let Some(next) = next {
+                        out.push_str(&parts[0].name);
+                        context = next;
+                        name_argument = false;
+                        continue;
+                    }
+                }
+
+                let called = bytes[i..]
+                    .iter()
+                    .find(|b| !b.is_ascii_whitespace())
+                    .is_some_and(|&b| b == b'(');
+                let position = match (path, context) {
+                    (true, _) => Context::Schema,
+                    (false, Context::Other) if called && parts.len() > 1 => Context::Object,
+                    (false, context) => context,
+                };
+                self.rename(&mut parts, position);
+                write_name(&mut out, &parts, dialect);
+
+                let last = parts.last().map(|p| p.name.to_ascii_uppercase());
+                name_argument =
+                    called && last.is_some_and(|f| NAME_FUNCTIONS.contains(&f.as_str()));
+                context = Context::Other;
+                continue;
+            }
+
+            if b.is_ascii_digit() {
+                let len = bytes[i..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_alphanumeric() || **b == b'.' || **b == b'_')
+                    .count();
+                out.push_str(&sql[i..i + len]);
+                i += len;
+                context = Context::Other;
+                name_argument = false;
+                continue;
+            }
+
+            // Copy everything else a character at a time
+            let len = sql[i..].chars().next().map_or(1, char::len_utf8);
+            out.push_str(&sql[i..i + len]);
+            i += len;
+            match b {
+                b';' => {
+                    path = false;
+                    context = Context::Other;
+                }
+                b'(' => {}
+                _ if b.is_ascii_whitespace() => {}
+                b',' | b'=' if path => {}
+                _ => {
+                    context = Context::Other;
+                    name_argument = false;
+                }
+            }
+        }
+        out
+    }
+
+    /// A table name as `schema.table` or `table`, remapped
+    pub fn apply_to_table(&self, table: &str) -> String {
+        let mut parts: Vec<Part> = table
+            .split('.')
+            .map(|name| Part {
+                name: name.to_string(),
+                quote: None,
+                renamed: false,
+            })
+            .collect();
+        self.rename(&mut parts, Context::Table);
+        let names: Vec<&str> = parts.iter().map(|p| p.name.as_str()).collect();
+        names.join(".")
+    }
+
+    /// The text of a string naming a table or sequence, remapped
+    fn apply_to_text(&self, text: &str, dialect: &dyn SqlDialect) -> String {
+        if !text
+            .bytes()
+            .next()
+            .is_some_and(|b| starts_name(b, Syntax::STANDARD))
+        {
+            return text.to_string();
+        }
+        let (mut parts, end) = read_name(text, 0, Syntax::STANDARD);
+        if end != text.len() {
+            return text.to_string();
+        }
+        self.rename(&mut parts, Context::Table);
+        let mut out = String::new();
+        write_name(&mut out, &parts, dialect);
+        out
+    }
+
+    /// Remap the parts of a name standing for what `position` says
+    fn rename(&self, parts: &mut [Part], position: Context) {
+        let original: Vec<String> = parts.iter().map(|p| p.name.clone()).collect();
+        let n = parts.len();
+
+        // Which part is a schema, and which a table
+        let (schemas, table) = match position {
+            Context::Schema => (0..n, None),
+            Context::Table => (n.saturating_sub(2)..n.saturating_sub(1), Some(n - 1)),
+            Context::Object => (n.saturating_sub(2)..n.saturating_sub(1), None),
+            Context::Other => (0..n.saturating_sub(1), n.checked_sub(2)),
+        };
+        for i in schemas {
+            if let Some((_, new)) = self
+                .schemas
+                .iter()
+                .find(|(old, _)| old.eq_ignore_ascii_case(&original[i]))
+            {
+                parts[i].name = new.clone();
+                parts[i].renamed = true;
+            }
+        }
+        if let Some(i) = table {
+            let schema = i.checked_sub(1).map(|s| original[s].as_str());
+            let found = self.tables.iter().find(|(old_schema, old, _)| {
+                old.eq_ignore_ascii_case(&original[i])
+                    && match (old_schema, schema) {
+                        (Some(old_schema), Some(schema)) => old_schema.eq_ignore_ascii_case(schema),
+                        _ => true,
+                    }
+            });
+            if let Some((_, _, new)) = found {
+                parts[i].name = new.clone();
+                parts[i].renamed = true;
+            }
+        }
+    }
+}
+
+/// `--rename-column`: columns `migrate` writes under new names. Rows are
+/// still read in the source's column order; only the destination's column
+/// lists and DDL take the new names.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnRenames {
+    /// Table (maybe schema-qualified), old column and new column
+    columns: Vec<(String, String, String)>,
+}
+
+impl ColumnRenames {
+    /// Parse `table.column` / `new` pairs; the table may be schema-qualified
+    pub fn new(mappings: &[(String, String)]) -> Result<Self> {
+        let mut columns = Vec::new();
+        for (old, new) in mappings {
+            let Some((table, column)) = old.rsplit_once('.') else {
+                bail!(
+                    "--rename-column {}:{} needs the column's table, as table.column:new",
+                    old,
+                    new
+                );
+            };
+            if new.contains('.') {
+                bail!(
+                    "--rename-column {}:{} gives the new name with a table; give the column name alone",
+                    old,
+                    new
+                );
+            }
+            columns.push((table.to_string(), column.to_string(), new.clone()));
+        }
+        Ok(ColumnRenames { columns })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// The renames of `table`'s columns, as (old, new) pairs. A rename given
+    /// without a schema applies to the table in any schema.
+    pub fn for_table<'a>(&'a self, table: &str) -> Vec<(&'a str, &'a str)> {
+        let name = split_table_name(table).1;
+        self.columns
+            .iter()
+            .filter(|(t, _, _)| t == table || (!t.contains('.') && t == name))
+            .map(|(_, old, new)| (old.as_str(), new.as_str()))
+            .collect()
+    }
+
+    /// `columns` of `table`, with the renamed ones under their new names
+    pub fn apply(&self, table: &str, columns: &[String]) -> Vec<String> {
+        let renames = self.for_table(table);
+        columns
+            .iter()
+            .map(|column| {
+                renames
+                    .iter()
+                    .find(|(old, _)| old == column)
+                    .map_or_else(|| column.clone(), |(_, new)| new.to_string())
+            })
+            .collect()
+    }
+}
+
+/// Whether a byte starts a name: a letter, `_`, a quote or, outside MySQL,
+/// a bracket
+fn starts_name(b: u8, syntax: Syntax) -> bool {
+    b.is_ascii_alphabetic()
+        || b == b'_'
+        || b >= 0x80
+        || b == b'`'
+        || (!syntax.mysql_quoting && (b == b'"' || b == b'['))
+}
+
+/// The tag and body of a closed `$$` or `$name$` quoted body
+fn dollar_body(text: &str) -> Option<(&str, &str)> {
+    let tag = &text[..text.strip_prefix('$')?.find('$')? + 2];
+    let body = text[tag.len()..].strip_suffix(tag)?;
+    Some((tag, body))
+}
+
+/// The parts of the dotted name starting at `i`, and where it ends
+fn read_name(sql: &str, mut i: usize, syntax: Syntax) -> (Vec<Part>, usize) {
+    let bytes = sql.as_bytes();
+    let mut parts = Vec::new();
+    loop {
+        let part = match bytes[i] {
+            open @ (b'`' | b'"' | b'[') => {
+                let close = if open == b'[' { b']' } else { open };
+                let end = if open == b'[' {
+                    sql[i..].find(']').map_or(sql.len(), |at| i + at + 1)
+                } else {
+                    Lexer::new(syntax).next(sql, i).1
+                };
+                let inner = sql[i + 1..end.max(i + 2) - 1].to_string();
+                let doubled = [close as char; 2].iter().collect::<String>();
+                i = end;
+                Part {
+                    name: inner.replace(&doubled, &(close as char).to_string()),
+                    quote: Some(close),
+                    renamed: false,
+                }
+            }
+            _ => {
+                let len = bytes[i..].iter().take_while(|b| is_word(**b)).count();
+                let name = sql[i..i + len].to_string();
+                i += len;
+                Part {
+                    name,
+                    quote: None,
+                    renamed: false,
+                }
+            }
+        };
+        parts.push(part);
+
+        if bytes.get(i) == Some(&b'.') && bytes.get(i + 1).is_some_and(|&b| starts_name(b, syntax))
+        {
+            i += 1;
+        } else {
+            return (parts, i);
+        }
+    }
+}
+
+/// Write a name back as it was quoted. A bare part that was renamed stays
+/// bare if it can.
+fn write_name(out: &mut String, parts: &[Part], dialect: &dyn SqlDialect) {
+    for (i, part) in parts.iter().enumerate() {
+        if i > 0 {
+            out.push('.');
+        }
+        match part.quote {
+            Some(close) => {
+                let open = if close == b']' { '[' } else { close as char };
+                let close = close as char;
+                out.push(open);
+                out.push_str(&part.name.replace(close, &format!("{}{}", close, close)));
+                out.push(close);
+            }
+            None if part.renamed && !is_plain(&part.name) => {
+                out.push_str(&dialect.quote_identifier(&part.name))
+            }
+            None => out.push_str(&part.name),
+        }
+    }
+}
+
+/// Whether a name can be written unquoted
+fn is_plain(name: &str) -> bool {
+    name.bytes()
+        .next()
+        .is_some_and(|b| b.is_ascii_alphabetic() || b == b'_')
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::dialects::mysql::MYSQL_DIALECT;
+    use crate::util::dialects::postgres::POSTGRES_DIALECT;
+
+    fn remap(schemas: &[(&str, &str)], tables: &[(&str, &str)]) -> Remap {
+        let pairs = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(old, new)| (old.to_string(), new.to_string()))
+                .collect()
+        };
+        Remap::new(&pairs(schemas), &pairs(tables)).unwrap()
+    }
+
+    #[test]
+    fn remaps_schemas_and_tables_in_postgres_statements() {
+        let remap = remap(&[("public", "staging")], &[("public.users", "members")]);
+        let apply = |sql| remap.apply(sql, &POSTGRES_DIALECT);
+
+        assert_eq!(
+            apply("CREATE TABLE IF NOT EXISTS \"public\".\"users\" (\"id\" int DEFAULT nextval('public.users_id_seq'::regclass), users int);"),
+            "CREATE TABLE IF NOT EXISTS \"staging\".\"members\" (\"id\" int DEFAULT nextval('staging.users_id_seq'::regclass), users int);"
+        );
+        assert_eq!(
+            apply("INSERT INTO public.users (id, note) VALUES (1, 'public.users');"),
+            "INSERT INTO staging.members (id, note) VALUES (1, 'public.users');"
+        );
+        assert_eq!(
+            apply("SELECT pg_catalog.setval(pg_catalog.pg_get_serial_sequence('\"public\".\"users\"', 'id'), 3, true);"),
+            "SELECT pg_catalog.setval(pg_catalog.pg_get_serial_sequence('\"staging\".\"members\"', 'id'), 3, true);"
+        );
+        assert_eq!(
+            apply("ALTER SEQUENCE public.users_id_seq OWNED BY public.users.id;"),
+            "ALTER SEQUENCE staging.users_id_seq OWNED BY staging.members.id;"
+        );
+        assert_eq!(
+            apply("CREATE SCHEMA IF NOT EXISTS public; SET search_path = public, pg_catalog;"),
+            "CREATE SCHEMA IF NOT EXISTS staging; SET search_path = staging, pg_catalog;"
+        );
+        assert_eq!(
+            apply("CREATE VIEW public.v AS SELECT users.id FROM ONLY public.users WHERE public.f(users.id);"),
+            "CREATE VIEW staging.v AS SELECT members.id FROM ONLY staging.members WHERE staging.f(members.id);"
+        );
+        assert_eq!(
+            apply("CREATE FUNCTION public.f() RETURNS int AS $$ SELECT count(*) FROM public.users $$ LANGUAGE sql; -- public.users"),
+            "CREATE FUNCTION staging.f() RETURNS int AS $$ SELECT count(*) FROM staging.members $$ LANGUAGE sql; -- public.users"
+        );
+        // Another schema's table of the same name stays
+        assert_eq!(
+            apply("DROP TABLE billing.users;"),
+            "DROP TABLE billing.users;"
+        );
+    }
+
+    #[test]
+    fn remaps_mysql_databases() {
+        let remap = remap(&[("prod_db", "staging db")], &[("orders", "orders_2024")]);
+        let apply = |sql| remap.apply(sql, &MYSQL_DIALECT);
+
+        assert_eq!(
+            apply("CREATE DATABASE IF NOT EXISTS `prod_db`; USE prod_db;"),
+            "CREATE DATABASE IF NOT EXISTS `staging db`; USE `staging db`;"
+        );
+        assert_eq!(
+            apply("/*!40000 ALTER TABLE `orders` DISABLE KEYS */;"),
+            "/*!40000 ALTER TABLE `orders_2024` DISABLE KEYS */;"
+        );
+        assert_eq!(
+            apply("INSERT INTO `orders` VALUES (1,'it\\'s `orders`',\"orders\");"),
+            "INSERT INTO `orders_2024` VALUES (1,'it\\'s `orders`',\"orders\");"
+        );
+        assert_eq!(remap.apply_to_table("orders"), "orders_2024");
+        assert!(Remap::new(&[], &[("a".into(), "b.c".into())]).is_err());
+    }
+
+    #[test]
+    fn renames_columns_of_matching_tables() {
+        let renames = ColumnRenames::new(&[
+            ("users.e_mail".to_string(), "email".to_string()),
+            ("billing.users.tel".to_string(), "phone".to_string()),
+        ])
+        .unwrap();
+        let columns = ["id".to_string(), "e_mail".to_string(), "tel".to_string()];
+
+        assert_eq!(
+            renames.apply("public.users", &columns),
+            ["id", "email", "tel"]
+        );
+        assert_eq!(
+            renames.apply("billing.users", &columns),
+            ["id", "email", "phone"]
+        );
+        assert_eq!(renames.apply("orders", &columns), columns);
+        assert!(ColumnRenames::new(&[("e_mail".into(), "email".into())]).is_err());
+        assert!(ColumnRenames::new(&[("users.e_mail".into(), "users.email".into())]).is_err());
+    }
+}
@@ -0,0 +1,633 @@
+/// The quoting and comment rules of a dialect, which decide where its
+/// strings, quoted names, comments and statements end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Syntax {
+    /// `\` escapes the next character in strings
+    pub backslash_escapes: bool,
+    /// `#` starts a comment and `--` only does before a space, and `"`
+    /// quotes strings rather than names, as in MySQL
+    pub mysql_quoting: bool,
+    /// `/* */` comments nest, as in PostgreSQL
+    pub nested_comments: bool,
+    /// `$$` and `$name$` quote bodies; elsewhere `$` is part of a name
+    pub dollar_quotes: bool,
+    /// `;` inside the `BEGIN ... END` body of a `CREATE TRIGGER` does not end
+    /// it, as in SQLite
+    pub trigger_bodies: bool,
+}
+
+impl Syntax {
+    /// Standard SQL, with PostgreSQL's `E'...'` strings and dollar quotes
+    pub const STANDARD: Syntax = Syntax {
+        backslash_escapes: false,
+        mysql_quoting: false,
+        nested_comments: false,
+        dollar_quotes: true,
+        trigger_bodies: false,
+    };
+
+    pub const MYSQL: Syntax = Syntax {
+        backslash_escapes: true,
+        mysql_quoting: true,
+        dollar_quotes: false,
+        ..Syntax::STANDARD
+    };
+
+    pub const POSTGRES: Syntax = Syntax {
+        nested_comments: true,
+        ..Syntax::STANDARD
+    };
+
+    pub const SQLITE: Syntax = Syntax {
+        dollar_quotes: false,
+        trigger_bodies: true,
+        ..Syntax::STANDARD
+    };
+}
+
+/// What a token of SQL text is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Space,
+    /// A `--` or `#` comment, up to the end of its line
+    LineComment,
+    /// A `/* ... */` comment; MySQL runs a `/*! ... */` one as code
+    BlockComment,
+    /// A keyword, bare name or number
+    Word,
+    /// A string literal or dollar-quoted body, with its quotes
+    Str,
+    /// A quoted name, with its quotes
+    QuotedName,
+    /// Any other character; `::` is one token
+    Punct,
+}
+
+/// A token of SQL text, as written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+}
+
+/// Split `sql` into tokens by the rules of `syntax`. A string, quoted name
+/// or comment left open runs to the end.
+pub fn tokenize(sql: &str, syntax: Syntax) -> Vec<Token<'_>> {
+    let mut lexer = Lexer::new(syntax);
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < sql.len() {
+        let (kind, end) = lexer.next(sql, i);
+        tokens.push(Token {
+            kind,
+            text: &sql[i..end],
+        });
+        i = end;
+    }
+    tokens
+}
+
+/// Where the lexer is in the SQL text
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum State {
+    Code,
+    /// In a `'...'` string; `backslash` when a backslash escapes the
+    /// character after it, as in MySQL or a PostgreSQL `E'...'` string
+    Single {
+        backslash: bool,
+    },
+    /// In a `"..."` name, or string in MySQL
+    Double,
+    /// In a `` `...` `` name
+    Backtick,
+    /// In a `/* ... */` comment, this many levels deep
+    BlockComment(usize),
+    /// In a `$$` or `$name$` quoted body, holding the tag that closes it
+    Dollar(String),
+}
+
+/// Reads SQL text a token at a time. Text can come in pieces, such as
+/// lines: a string, quoted name or comment a piece leaves open carries on
+/// at the start of the next.
+#[derive(Debug, Clone)]
+pub struct Lexer {
+    syntax: Syntax,
+    state: State,
+}
+
+impl Lexer {
+    pub fn new(syntax: Syntax) -> Self {
+        Lexer {
+            syntax,
+            state: State::Code,
+        }
+    }
+
+    /// Whether the text so far ends between tokens, rather than in a
+    /// string, quoted name or block comment
+    pub fn in_code(&self) -> bool {
+        self.state == State::Code
+    }
+
+    /// Forget a construct left open
+    pub fn reset(&mut self) {
+        self.state = State::Code;
+    }
+
+    /// The kind and end of the token at `i` in `text`: the rest of one left
+    /// open by an earlier piece, or else the one starting there
+    pub fn next(&mut self, text: &str, i: usize) -> (TokenKind, usize) {
+        let bytes = text.as_bytes();
+        if self.state != State::Code {
+            return self.quoted(bytes, i);
+        }
+
+        let b = bytes[i];
+        let run = |f: fn(u8) -> bool| i + bytes[i..].iter().take_while(|b| f(**b)).count();
+        match b {
+            b if b.is_ascii_whitespace() => (TokenKind::Space, run(|b| b.is_ascii_whitespace())),
+            b'-' if bytes.get(i + 1) == Some(&b'-') && self.opens_dash_comment(bytes, i) => {
+                (TokenKind::LineComment, line_end(bytes, i))
+            }
+            b'#' if self.syntax.mysql_quoting => (TokenKind::LineComment, line_end(bytes, i)),
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                self.state = State::BlockComment(1);
+                self.quoted(bytes, i + 2)
+            }
+            b'\'' => {
+                let backslash = self.syntax.backslash_escapes || opens_escape_string(bytes, i);
+                self.state = State::Single { backslash };
+                self.quoted(bytes, i + 1)
+            }
+            b'"' => {
+                self.state = State::Double;
+                self.quoted(bytes, i + 1)
+            }
+            b'`' => {
+                self.state = State::Backtick;
+                self.quoted(bytes, i + 1)
+            }
+            b'$' if self.syntax.dollar_quotes && dollar_tag(bytes, i).is_some() => {
+                let tag = dollar_tag(bytes, i).unwrap_or_default();
+                let start = i + tag.len();
+                self.state = State::Dollar(tag);
+                self.quoted(bytes, start)
+            }
+            b if is_word(b) => (TokenKind::Word, run(is_word)),
+            b':' if bytes.get(i + 1) == Some(&b':') => (TokenKind::Punct, i + 2),
+            _ => (TokenKind::Punct, i + 1),
+        }
+    }
+
+    /// Carry on through the construct the lexer is in from `i`, to just
+    /// past its end or to the end of the text
+    fn quoted(&mut self, bytes: &[u8], mut i: usize) -> (TokenKind, usize) {
+        let kind = match self.state {
+            State::Single { .. } | State::Dollar(_) => TokenKind::Str,
+            State::Double if self.syntax.mysql_quoting => TokenKind::Str,
+            State::Double | State::Backtick => TokenKind::QuotedName,
+            State::BlockComment(_) => TokenKind::BlockComment,
+            State::Code => unreachable!("quoted() is only called inside a construct"),
+        };
+        while i < bytes.len() {
+            let b = bytes[i];
+            match &mut self.state {
+                State::Single { backslash } => match b {
+                    b'\\' if *backslash => i += 1,
+                    // A doubled quote stands for one inside
+                    b'\'' if bytes.get(i + 1) == Some(&b'\'') => i += 1,
+                    b'\'' => {
+                        self.state = State::Code;
+                        return (kind, i + 1);
+                    }
+                    _ => {}
+                },
+                State::Double | State::Backtick => {
+                    let close = if self.state == State::Double {
+                        b'"'
+                    } else {
+                        b'`'
+                    };
+                    let escape = b == b'\\' && close == b'"' && self.syntax.backslash_escapes;
+                    // A doubled quote stands for one inside
+                    if escape || (b == close && bytes.get(i + 1) == Some(&close)) {
+                        i += 1;
+                    } else if b == close {
+                        self.state = State::Code;
+                        return (kind, i + 1);
+                    }
+                }
+                State::BlockComment(depth) => {
+                    if b == b'*' && bytes.get(i + 1) == Some(&b'/') {
+                        *depth -= 1;
+                        i += 1;
+                        if *depth == 0 {
+                            self.state = State::Code;
+                            return (kind, i + 1);
+                        }
+                    } else if b == b'/'
+                        && bytes.get(i + 1) == Some(&b'*')
+                        && self.syntax.nested_comments
+                    {
+                        *depth += 1;
+                        i += 1;
+                    }
+                }
+                State::Dollar(tag) => {
+                    if bytes[i..].starts_with(tag.as_bytes()) {
+                        let end = i + tag.len();
+                        self.state = State::Code;
+                        return (kind, end);
+                    }
+                }
+                State::Code => unreachable!(),
+            }
+            i += 1;
+        }
+        (kind, bytes.len())
+    }
+
+    /// Whether the `--` at `i` starts a comment: anywhere in standard SQL,
+    /// but only before a space or the line's end in MySQL
+    fn opens_dash_comment(&self, bytes: &[u8], i: usize) -> bool {
+        !self.syntax.mysql_quoting || bytes.get(i + 2).is_none_or(|b| b.is_ascii_whitespace())
+    }
+}
+
+/// How far a statement has shown itself to be a `CREATE TRIGGER`, whose
+/// body runs to an `END` just after a `;`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Trigger {
+    /// No word read yet
+    Start,
+    /// After `CREATE`, and `TEMP` if given
+    Create,
+    /// Some other statement
+    Not,
+    Body,
+    /// In the body, just after a `;`
+    Semicolon,
+    /// After an `END` that followed a `;`, where the next `;` ends it
+    End,
+}
+
+/// Splits SQL text, fed to it line by line, into statements. It follows
+/// string literals, quoted identifiers, dollar-quoted bodies and comments
+/// with a [`Lexer`], so a delimiter or `--` inside any of them is taken as
+/// text, and a line can end, continue or hold several statements. Line
+/// comments are dropped; lines are joined with a space, except within
+/// quotes, where the line breaks are part of the text, and under a
+/// `DELIMITER` other than `;`, so routine and trigger bodies reach the
+/// server as they were written.
+#[derive(Debug)]
+pub struct StatementSplitter {
+    lexer: Lexer,
+    syntax: Syntax,
+    current: String,
+    /// What ends a statement, as set by `DELIMITER` lines
+    delimiter: String,
+    trigger: Trigger,
+}
+
+impl StatementSplitter {
+    pub fn new(syntax: Syntax) -> Self {
+        StatementSplitter {
+            lexer: Lexer::new(syntax),
+            syntax,
+            current: String::new(),
+            delimiter: ";".to_string(),
+            trigger: Trigger::Start,
+        }
+    }
+
+    /// Whether the next line starts afresh, outside any statement
+    pub fn is_idle(&self) -> bool {
+        self.lexer.in_code() && self.current.trim().is_empty()
+    }
+
+    /// End statements with `delimiter` from now on, rather than `;`
+    pub fn set_delimiter(&mut self, delimiter: &str) {
+        self.delimiter = delimiter.to_string();
+    }
+
+    /// Take one line, with its line break if it has one, returning the
+    /// statements it completes. Statements keep a closing `;`, but not
+    /// another delimiter.
+    pub fn push_line(&mut self, line: &str) -> Vec<String> {
+        let end = line.trim_end_matches(['\n', '\r']).len();
+        let text = &line[..end];
+        let mut statements = Vec::new();
+        // Start of the text not yet added to the statement
+        let mut start = 0;
+        let mut i = 0;
+
+        while i < end {
+            let in_code = self.lexer.in_code();
+            if in_code && text[i..].starts_with(&self.delimiter) {
+                let semicolon = self.delimiter == ";";
+                if semicolon && matches!(self.trigger, Trigger::Body | Trigger::Semicolon) {
+                    self.trigger = Trigger::Semicolon;
+                    i += 1;
+                    continue;
+                }
+                self.current
+                    .push_str(&text[start..i + if semicolon { 1 } else { 0 }]);
+                i += self.delimiter.len();
+                start = i;
+                let sql = self.current.trim();
+                if !sql.is_empty() && sql != ";" {
+                    statements.push(sql.to_string());
+                }
+                self.current.clear();
+                self.trigger = Trigger::Start;
+                continue;
+            }
+
+            let (kind, mut next) = self.lexer.next(text, i);
+            if !in_code {
+                i = next;
+                continue;
+            }
+            match kind {
+                TokenKind::LineComment => {
+                    // A body under another delimiter is kept as written
+                    let begun =
+                        !self.current.trim().is_empty() || !text[start..i].trim().is_empty();
+                    if self.delimiter == ";" || !begun {
+                        self.current.push_str(&text[start..i]);
+                        start = end;
+                    }
+                    break;
+                }
+                TokenKind::Word => {
+                    // A delimiter such as `$$` can follow a word directly
+                    if let Some(at) = text[i + 1..next].find(&self.delimiter) {
+                        next = i + 1 + at;
+                    }
+                    self.trigger_word(&text[i..next]);
+                }
+                TokenKind::Space | TokenKind::BlockComment => {}
+                _ => self.trigger_token(),
+            }
+            i = next;
+        }
+
+        if start < end {
+            self.current.push_str(&text[start..]);
+        }
+        if !self.current.is_empty() {
+            if self.lexer.in_code() && self.delimiter == ";" {
+                self.current.push(' ');
+            } else {
+                self.current.push_str(&line[end..]);
+            }
+        }
+        statements
+    }
+
+    /// The statement left unfinished at the end of the input, if any
+    pub fn finish(&mut self) -> Option<String> {
+        self.lexer.reset();
+        self.trigger = Trigger::Start;
+        let sql = std::mem::take(&mut self.current);
+        let sql = sql.trim();
+        (!sql.is_empty()).then(|| sql.to_string())
+    }
+
+    /// Follow a word towards or through a trigger body
+    fn trigger_word(&mut self, word: &str) {
+        if !self.syntax.trigger_bodies {
+            return;
+        }
+        let is = |keyword: &str| word.eq_ignore_ascii_case(keyword);
+        self.trigger = match self.trigger {
+            Trigger::Start if is("CREATE") => Trigger::Create,
+            Trigger::Create if is("TEMP") || is("TEMPORARY") => Trigger::Create,
+            Trigger::Create if is("TRIGGER") => Trigger::Body,
+            Trigger::Start | Trigger::Create => Trigger::Not,
+            Trigger::Semicolon if is("END") => Trigger::End,
+            Trigger::Semicolon | Trigger::End => Trigger::Body,
+            other => other,
+        };
+    }
+
+    /// Follow any other token through a trigger body
+    fn trigger_token(&mut self) {
+        self.trigger = match self.trigger {
+            Trigger::Start | Trigger::Create => Trigger::Not,
+            Trigger::Semicolon | Trigger::End => Trigger::Body,
+            other => other,
+        };
+    }
+}
+
+/// The end of the line holding `i`, before its line break
+fn line_end(bytes: &[u8], i: usize) -> usize {
+    bytes[i..]
+        .iter()
+        .position(|&b| b == b'\n' || b == b'\r')
+        .map_or(bytes.len(), |at| i + at)
+}
+
+/// Whether a byte continues an identifier; bytes of non-ASCII characters
+/// count as letters
+pub fn is_word(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'$' || b >= 0x80
+}
+
+/// Whether the quote at `i` opens a PostgreSQL `E'...'` string
+fn opens_escape_string(bytes: &[u8], i: usize) -> bool {
+    i > 0 && bytes[i - 1].eq_ignore_ascii_case(&b'e') && (i < 2 || !is_word(bytes[i - 2]))
+}
+
+/// The tag of a dollar quote opening at `i`: `$$` or `$name$` outside an
+/// identifier (`$1` parameters are not tags)
+fn dollar_tag(bytes: &[u8], i: usize) -> Option<String> {
+    if i > 0 && is_word(bytes[i - 1]) {
+        return None;
+    }
+    let name = &bytes[i + 1..];
+    let len = name
+        .iter()
+        .position(|&b| !(b.is_ascii_alphanumeric() || b == b'_'))
+        .unwrap_or(name.len());
+    if name.first().is_some_and(u8::is_ascii_digit) || name.get(len) != Some(&b'$') {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes[i..i + len + 2]).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(input: &str, syntax: Syntax) -> Vec<String> {
+        let mut splitter = StatementSplitter::new(syntax);
+        let mut statements: Vec<String> = input
+            .split_inclusive('\n')
+            .flat_map(|line| splitter.push_line(line))
+            .collect();
+        statements.extend(splitter.finish());
+        statements
+    }
+
+    #[test]
+    fn splits_outside_quotes_and_comments() {
+        let input = "INSERT INTO t VALUES ('a;b', 'it''s -- not a comment');\nINSERT INTO t VALUES ('two\nlines;'); SELECT 1; -- done;\nSELECT \"x;y\" /* ; */ FROM t;";
+        assert_eq!(
+            split(input, Syntax::STANDARD),
+            [
+                "INSERT INTO t VALUES ('a;b', 'it''s -- not a comment');",
+                "INSERT INTO t VALUES ('two\nlines;');",
+                "SELECT 1;",
+                "SELECT \"x;y\" /* ; */ FROM t;",
+            ]
+        );
+    }
+
+    #[test]
+    fn follows_backslash_escapes() {
+        // MySQL escapes quotes with a backslash, and needs a space after --
+        let input = "INSERT INTO t VALUES ('a\\';b', \"c\\\";\", 1--1);\nSELECT `a;b`;";
+        assert_eq!(
+            split(input, Syntax::MYSQL),
+            [
+                "INSERT INTO t VALUES ('a\\';b', \"c\\\";\", 1--1);",
+                "SELECT `a;b`;",
+            ]
+        );
+        // In PostgreSQL only E'' strings do, so 'C:\' ends at its quote
+        let input = "SELECT 'C:\\'; SELECT E'it\\'s;';";
+        assert_eq!(
+            split(input, Syntax::STANDARD),
+            ["SELECT 'C:\\';", "SELECT E'it\\'s;';"]
+        );
+    }
+
+    #[test]
+    fn ends_statements_at_the_delimiter() {
+        let mut splitter = StatementSplitter::new(Syntax::MYSQL);
+        splitter.set_delimiter("$$");
+        assert!(splitter
+            .push_line("CREATE PROCEDURE p() BEGIN\n")
+            .is_empty());
+        assert!(splitter.push_line("  SELECT ';'; # note\n").is_empty());
+        assert!(!splitter.is_idle());
+        assert_eq!(
+            splitter.push_line("END$$ SELECT 2$$\n"),
+            [
+                "CREATE PROCEDURE p() BEGIN\n  SELECT ';'; # note\nEND",
+                "SELECT 2"
+            ]
+        );
+        assert!(splitter.is_idle());
+    }
+
+    #[test]
+    fn keeps_crlf_line_breaks_inside_strings() {
+        let input = "INSERT INTO t VALUES ('a\r\nb');\r\nSELECT\r\n1;\r\n";
+        assert_eq!(
+            split(input, Syntax::STANDARD),
+            ["INSERT INTO t VALUES ('a\r\nb');", "SELECT 1;"]
+        );
+    }
+
+    #[test]
+    fn drops_hash_comments_in_mysql() {
+        let input = "# don't do this\nINSERT INTO t VALUES (1);\nINSERT INTO t VALUES (2);\nSELECT 1; # a; b\nSELECT 2;";
+        assert_eq!(
+            split(input, Syntax::MYSQL),
+            [
+                "INSERT INTO t VALUES (1);",
+                "INSERT INTO t VALUES (2);",
+                "SELECT 1;",
+                "SELECT 2;",
+            ]
+        );
+        // Elsewhere `#` is an operator
+        assert_eq!(
+            split("SELECT 1 # 2; SELECT 3;", Syntax::POSTGRES),
+            ["SELECT 1 # 2;", "SELECT 3;"]
+        );
+    }
+
+    #[test]
+    fn nests_block_comments_in_postgres() {
+        let input = "SELECT 1 /* a /* b */ ; */; SELECT 2;";
+        assert_eq!(
+            split(input, Syntax::POSTGRES),
+            ["SELECT 1 /* a /* b */ ; */;", "SELECT 2;"]
+        );
+        // MySQL ends a comment at the first `*/`
+        assert_eq!(
+            split("SELECT 1 /* a /* b */; SELECT 2;", Syntax::MYSQL),
+            ["SELECT 1 /* a /* b */;", "SELECT 2;"]
+        );
+    }
+
+    #[test]
+    fn takes_dollars_as_name_characters_in_mysql() {
+        let input = "SELECT $a$ FROM t;\nSELECT 1 AS $b$;";
+        assert_eq!(
+            split(input, Syntax::MYSQL),
+            ["SELECT $a$ FROM t;", "SELECT 1 AS $b$;"]
+        );
+        assert_eq!(
+            split("SELECT $a$;x$a$; SELECT 2;", Syntax::POSTGRES),
+            ["SELECT $a$;x$a$;", "SELECT 2;"]
+        );
+    }
+
+    #[test]
+    fn keeps_sqlite_trigger_bodies_whole() {
+        let input = "CREATE TRIGGER t_ai AFTER INSERT ON t\nBEGIN\n  UPDATE c SET n = n + 1;\n  INSERT INTO log VALUES (NEW.id);\nEND;\nINSERT INTO t VALUES (1);";
+        assert_eq!(
+            split(input, Syntax::SQLITE),
+            [
+                "CREATE TRIGGER t_ai AFTER INSERT ON t BEGIN   UPDATE c SET n = n + 1;   INSERT INTO log VALUES (NEW.id); END;",
+                "INSERT INTO t VALUES (1);",
+            ]
+        );
+        assert_eq!(
+            split("CREATE TEMP TRIGGER x BEFORE DELETE ON t BEGIN SELECT CASE WHEN 1 THEN 2 END; END; SELECT 3;", Syntax::SQLITE),
+            [
+                "CREATE TEMP TRIGGER x BEFORE DELETE ON t BEGIN SELECT CASE WHEN 1 THEN 2 END; END;",
+                "SELECT 3;",
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenizes_by_dialect() {
+        let kinds = |sql, syntax| -> Vec<(TokenKind, &str)> {
+            tokenize(sql, syntax)
+                .into_iter()
+                .filter(|t| t.kind != TokenKind::Space)
+                .map(|t| (t.kind, t.text))
+                .collect()
+        };
+        assert_eq!(
+            kinds("SELECT E'a\\'b', $f$x'y$f$::text -- c", Syntax::POSTGRES),
+            [
+                (TokenKind::Word, "SELECT"),
+                (TokenKind::Word, "E"),
+                (TokenKind::Str, "'a\\'b'"),
+                (TokenKind::Punct, ","),
+                (TokenKind::Str, "$f$x'y$f$"),
+                (TokenKind::Punct, "::"),
+                (TokenKind::Word, "text"),
+                (TokenKind::LineComment, "-- c"),
+            ]
+        );
+        assert_eq!(
+            kinds("SELECT \"a\"\"b\", `c` # d", Syntax::MYSQL),
+            [
+                (TokenKind::Word, "SELECT"),
+                (TokenKind::Str, "\"a\"\"b\""),
+                (TokenKind::Punct, ","),
+                (TokenKind::QuotedName, "`c`"),
+                (TokenKind::LineComment, "# d"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,718 @@
+use crate::engine::value::SqlValue;
+use crate::engine::ReplicationPosition;
+use crate::util::binary;
+use crate::util::compress::Codec;
+use crate::util::manifest::{replication_from_json, replication_to_json, DataSection};
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// File in a directory dump noting which tables are done, while it is being
+/// written; removed once the manifest is in place
+pub const STATE_FILE: &str = ".migrasquiel-state.json";
+
+/// Layout version written to the state file
+const STATE_VERSION: u64 = 1;
+
+/// How far a directory dump has got, for `dump --resume`
+#[derive(Debug, Default, PartialEq)]
+pub struct DumpState {
+    /// Codec of the data files, which a resumed dump has to keep
+    pub compress: Codec,
+    /// Tables whose files are complete, with what was dumped of their data
+    pub done: BTreeMap<String, Option<DataSection>>,
+    /// Tables part way through a chunked read of their data
+    pub partial: BTreeMap<String, Checkpoint>,
+}
+
+/// Where a chunked read of a table's data had got to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    /// Rows in the data file so far
+    pub rows: u64,
+    /// Bytes of the data file, before compression, holding everything up to
+    /// and including those rows
+    pub bytes: u64,
+    /// Primary key of the last of those rows
+    pub after: Vec<SqlValue>,
+}
+
+impl DumpState {
+    pub fn to_json(&self) -> Value {
+        let done: Map<String, Value> = self
+            .done
+            .iter()
+            .map(|(table, section)| {
+                let section = match section {
+                    Some(s) => json!({ "rows": s.rows, "bytes": s.bytes, "sha256": s.sha256 }),
+                    None => json!({}),
+                };
+                (table.clone(), section)
+            })
+            .collect();
+        let partial: Map<String, Value> = self
+            .partial
+            .iter()
+            .map(|(table, c)| {
+                let after = hex::encode(binary::encode_values(&c.after));
+                let checkpoint = json!({ "rows": c.rows, "bytes": c.bytes, "after": after });
+                (table.clone(), checkpoint)
+            })
+            .collect();
+        json!({
+            "version": STATE_VERSION,
+            "compress": self.compress.to_string(),
+            "done": done,
+            "partial": partial,
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let version = value["version"].as_u64().unwrap_or(0);
+        if version == 0 || version > STATE_VERSION {
+            bail!("Unsupported state version {}", value["version"]);
+        }
+        let compress = value["compress"]
+            .as_str()
+            .ok_or_else(|| anyhow!("State is missing 'compress'"))?
+            .parse()
+            .map_err(anyhow::Error::msg)?;
+        let tables = |field: &str| {
+            value[field]
+                .as_object()
+                .ok_or_else(|| anyhow!("State is missing '{}'", field))
+        };
+
+        let mut done = BTreeMap::new();
+        for (table, section) in tables("done")? {
+            let section = match (
+                section["rows"].as_u64(),
+                section["bytes"].as_u64(),
+                section["sha256"].as_str(),
+            ) {
+                (Some(rows), Some(bytes), Some(sha256)) => Some(DataSection {
+                    rows,
+                    bytes,
+                    sha256: sha256.to_string(),
+                }),
+                (None, None, None) => None,
+                _ => bail!("Invalid row count or checksum for table '{}'", table),
+            };
+            done.insert(table.clone(), section);
+        }
+
+        let mut partial = BTreeMap::new();
+        for (table, checkpoint) in tables("partial")? {
+            let invalid = || anyhow!("Invalid checkpoint for table '{}'", table);
+            let after = checkpoint["after"].as_str().ok_or_else(invalid)?;
+            let after = hex::decode(after).map_err(|_| invalid())?;
+            let checkpoint = Checkpoint {
+                rows: checkpoint["rows"].as_u64().ok_or_else(invalid)?,
+                bytes: checkpoint["bytes"].as_u64().ok_or_else(invalid)?,
+                after: binary::decode_values(&after).with_context(invalid)?,
+            };
+            partial.insert(table.clone(), checkpoint);
+        }
+
+        Ok(DumpState {
+            compress,
+            done,
+            partial,
+        })
+    }
+}
+
+/// The state file of a directory dump, rewritten as its tables progress
+pub struct StateFile {
+    path: PathBuf,
+    state: Mutex<DumpState>,
+}
+
+impl StateFile {
+    /// Start a fresh state in the dump directory `dir`
+    pub fn create(dir: &Path, compress: Codec) -> Result<Self> {
+        let file = StateFile {
+            path: dir.join(STATE_FILE),
+            state: Mutex::new(DumpState {
+                compress,
+                ..DumpState::default()
+            }),
+        };
+        file.update(|_| ())?;
+        Ok(file)
+    }
+
+    /// The state an interrupted dump left in `dir`, if it left one
+    pub fn open(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(STATE_FILE);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read '{}'", path.display()))
+            }
+        };
+        let value: Value = serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        let state = DumpState::from_json(&value)
+            .with_context(|| format!("Invalid dump state '{}'", path.display()))?;
+        Ok(Some(StateFile {
+            path,
+            state: Mutex::new(state),
+        }))
+    }
+
+    /// Codec the dump's data files were started with
+    pub fn compress(&self) -> Codec {
+        self.lock().compress
+    }
+
+    /// What was dumped of `table`'s data, if its files are complete
+    pub fn done(&self, table: &str) -> Option<Option<DataSection>> {
+        self.lock().done.get(table).cloned()
+    }
+
+    /// Where a chunked read of `table` had got to, if it was under way
+    pub fn checkpoint(&self, table: &str) -> Option<Checkpoint> {
+        self.lock().partial.get(table).cloned()
+    }
+
+    /// Note how far `table`'s data has been read
+    pub fn record_checkpoint(&self, table: &str, checkpoint: Checkpoint) -> Result<()> {
+        self.update(|state| {
+            state.partial.insert(table.to_string(), checkpoint);
+        })
+    }
+
+    /// Note that `table`'s files are complete
+    pub fn record_done(&self, table: &str, section: Option<DataSection>) -> Result<()> {
+        self.update(|state| {
+            state.partial.remove(table);
+            state.done.insert(table.to_string(), section);
+        })
+    }
+
+    /// Remove the file once the dump is complete
+    pub fn remove(self) -> Result<()> {
+        std::fs::remove_file(&self.path)
+            .with_context(|| format!("Failed to remove '{}'", self.path.display()))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, DumpState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Change the state and write it out
+    fn update(&self, change: impl FnOnce(&mut DumpState)) -> Result<()> {
+        let mut state = self.lock();
+        change(&mut state);
+        write_json(&self.path, &state.to_json())
+    }
+}
+
+/// How far a sequential restore has got, for `restore --resume`
+#[derive(Debug, Default, PartialEq)]
+pub struct RestoreState {
+    /// Input being restored, as it was given
+    pub input: String,
+    /// Statements, pieces of COPY data and row batches read from the input,
+    /// all committed
+    pub items: u64,
+}
+
+impl RestoreState {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "version": STATE_VERSION,
+            "input": self.input,
+            "items": self.items,
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let version = value["version"].as_u64().unwrap_or(0);
+        if version == 0 || version > STATE_VERSION {
+            bail!("Unsupported state version {}", value["version"]);
+        }
+        Ok(RestoreState {
+            input: value["input"]
+                .as_str()
+                .ok_or_else(|| anyhow!("State is missing 'input'"))?
+                .to_string(),
+            items: value["items"]
+                .as_u64()
+                .ok_or_else(|| anyhow!("State is missing 'items'"))?,
+        })
+    }
+}
+
+/// The state file of a restore, rewritten after each commit
+pub struct RestoreStateFile {
+    path: PathBuf,
+    state: RestoreState,
+}
+
+impl RestoreStateFile {
+    /// Start the state of restoring `input`, with its first `items` done
+    pub fn create(path: &Path, input: &str, items: u64) -> Result<Self> {
+        let file = RestoreStateFile {
+            path: path.to_path_buf(),
+            state: RestoreState {
+                input: input.to_string(),
+                items,
+            },
+        };
+        write_json(&file.path, &file.state.to_json())?;
+        Ok(file)
+    }
+
+    /// The state a failed restore left at `path`, if it left one
+    pub fn open(path: &Path) -> Result<Option<Self>> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read '{}'", path.display()))
+            }
+        };
+        let value: Value = serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        let state = RestoreState::from_json(&value)
+            .with_context(|| format!("Invalid restore state '{}'", path.display()))?;
+        Ok(Some(RestoreStateFile {
+            path: path.to_path_buf(),
+            state,
+        }))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn state(&self) -> &RestoreState {
+        &self.state
+    }
+
+    /// Note that the first `items` read from the input are committed
+    pub fn record(&mut self, items: u64) -> Result<()> {
+        self.state.items = items;
+        write_json(&self.path, &self.state.to_json())
+    }
+
+    /// Remove the file once the restore is complete
+    pub fn remove(self) -> Result<()> {
+        std::fs::remove_file(&self.path)
+            .with_context(|| format!("Failed to remove '{}'", self.path.display()))
+    }
+}
+
+/// Where `migrate --incremental` has copied each table up to, for the next run
+#[derive(Debug, Default, PartialEq)]
+pub struct WatermarkState {
+    /// Column the watermarks are values of
+    pub column: String,
+    /// Largest value of the column among the rows copied from each table
+    pub tables: BTreeMap<String, SqlValue>,
+}
+
+impl WatermarkState {
+    pub fn to_json(&self) -> Value {
+        let tables: Map<String, Value> = self
+            .tables
+            .iter()
+            .map(|(table, value)| {
+                let value = hex::encode(binary::encode_values(std::slice::from_ref(value)));
+                (table.clone(), Value::String(value))
+            })
+            .collect();
+        json!({
+            "version": STATE_VERSION,
+            "column": self.column,
+            "tables": tables,
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let version = value["version"].as_u64().unwrap_or(0);
+        if version == 0 || version > STATE_VERSION {
+            bail!("Unsupported state version {}", value["version"]);
+        }
+        let column = value["column"]
+            .as_str()
+            .ok_or_else(|| anyhow!("State is missing 'column'"))?
+            .to_string();
+        let mut tables = BTreeMap::new();
+        for (table, watermark) in value["tables"]
+            .as_object()
+            .ok_or_else(|| anyhow!("State is missing 'tables'"))?
+        {
+            let invalid = || anyhow!("Invalid watermark for table '{}'", table);
+            let bytes =
+                hex::decode(watermark.as_str().ok_or_else(invalid)?).map_err(|_| invalid())?;
+            let [watermark] =
+                <[SqlValue; 1]>::try_from(binary::decode_values(&bytes).with_context(invalid)?)
+                    .map_err(|_| invalid())?;
+            tables.insert(table.clone(), watermark);
+        }
+        Ok(WatermarkState { column, tables })
+    }
+}
+
+/// The state file of `migrate --incremental`, rewritten as each table is copied
+pub struct WatermarkFile {
+    path: PathBuf,
+    state: Mutex<WatermarkState>,
+}
+
+impl WatermarkFile {
+    /// The watermarks left at `path` by earlier runs on `column`, or none
+    /// when there is no file yet
+    pub fn open(path: &Path, column: &str) -> Result<Self> {
+        let state = match std::fs::read_to_string(path) {
+            Ok(text) => {
+                let value: Value = serde_json::from_str(&text)
+                    .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+                let state = WatermarkState::from_json(&value)
+                    .with_context(|| format!("Invalid watermark state '{}'", path.display()))?;
+                if state.column != column {
+                    bail!(
+                        "'{}' holds watermarks of column '{}', not '{}'; give another --incremental-state",
+                        path.display(),
+                        state.column,
+                        column
+                    );
+                }
+                state
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => WatermarkState {
+                column: column.to_string(),
+                tables: BTreeMap::new(),
+            },
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read '{}'", path.display()))
+            }
+        };
+        Ok(WatermarkFile {
+            path: path.to_path_buf(),
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn column(&self) -> String {
+        self.lock().column.clone()
+    }
+
+    /// Largest value of the column copied from `table` so far, if any
+    pub fn watermark(&self, table: &str) -> Option<SqlValue> {
+        self.lock().tables.get(table).cloned()
+    }
+
+    /// Note that `table`'s rows up to `watermark` are copied
+    pub fn record(&self, table: &str, watermark: SqlValue) -> Result<()> {
+        let mut state = self.lock();
+        state.tables.insert(table.to_string(), watermark);
+        write_json(&self.path, &state.to_json())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, WatermarkState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Tables a `migrate --continue-on-error` run could not migrate, with the
+/// error each failed with, for `--retry-failed`
+#[derive(Debug, Default, PartialEq)]
+pub struct FailedTables {
+    pub tables: BTreeMap<String, String>,
+}
+
+impl FailedTables {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "version": STATE_VERSION,
+            "tables": self.tables,
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let version = value["version"].as_u64().unwrap_or(0);
+        if version == 0 || version > STATE_VERSION {
+            bail!("Unsupported state version {}", value["version"]);
+        }
+        let mut tables = BTreeMap::new();
+        for (table, error) in value["tables"]
+            .as_object()
+            .ok_or_else(|| anyhow!("State is missing 'tables'"))?
+        {
+            let error = error
+                .as_str()
+                .ok_or_else(|| anyhow!("Invalid error for table '{}'", table))?;
+            tables.insert(table.clone(), error.to_string());
+        }
+        Ok(FailedTables { tables })
+    }
+
+    /// The failures an earlier run left at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| {
+            format!(
+                "Failed to read '{}'; migrate with --continue-on-error writes it",
+                path.display()
+            )
+        })?;
+        let value: Value = serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        FailedTables::from_json(&value)
+            .with_context(|| format!("Invalid failed tables file '{}'", path.display()))
+    }
+}
+
+/// The failed tables file of a `migrate --continue-on-error` run, rewritten
+/// as tables fail
+pub struct FailedTablesFile {
+    path: PathBuf,
+    failed: Mutex<FailedTables>,
+}
+
+impl FailedTablesFile {
+    /// Start noting the failures of a run; the file is left alone until one
+    /// is noted
+    pub fn new(path: &Path) -> Self {
+        FailedTablesFile {
+            path: path.to_path_buf(),
+            failed: Mutex::new(FailedTables::default()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Note that `table` failed with `error`
+    pub fn record(&self, table: &str, error: &str) -> Result<()> {
+        let mut failed = self.lock();
+        failed.tables.insert(table.to_string(), error.to_string());
+        write_json(&self.path, &failed.to_json())
+    }
+
+    /// The tables that failed, with their errors, by name
+    pub fn failures(&self) -> Vec<(String, String)> {
+        self.lock()
+            .tables
+            .iter()
+            .map(|(table, error)| (table.clone(), error.clone()))
+            .collect()
+    }
+
+    /// Remove the file an earlier run left, once no table has failed
+    pub fn remove_if_clean(&self) -> Result<()> {
+        if !self.lock().tables.is_empty() {
+            return Ok(());
+        }
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Failed to remove '{}'", self.path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, FailedTables> {
+        self.failed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Where `replicate` is to carry on from: the position `migrate
+/// --position-file` took its snapshot at, moved on as each transaction is
+/// applied
+pub struct PositionFile {
+    path: PathBuf,
+    position: ReplicationPosition,
+}
+
+impl PositionFile {
+    pub fn create(path: &Path, position: ReplicationPosition) -> Result<Self> {
+        let file = PositionFile {
+            path: path.to_path_buf(),
+            position,
+        };
+        file.write()?;
+        Ok(file)
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| {
+            format!(
+                "Failed to read '{}'; migrate with --position-file writes it",
+                path.display()
+            )
+        })?;
+        let value: Value = serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        let version = value["version"].as_u64().unwrap_or(0);
+        if version == 0 || version > STATE_VERSION {
+            bail!("Unsupported state version {}", value["version"]);
+        }
+        let position = replication_from_json(&value["replication"])
+            .with_context(|| format!("Invalid position file '{}'", path.display()))?;
+        Ok(PositionFile {
+            path: path.to_path_buf(),
+            position,
+        })
+    }
+
+    pub fn position(&self) -> &ReplicationPosition {
+        &self.position
+    }
+
+    /// Note that changes up to `position` are applied
+    pub fn record(&mut self, position: ReplicationPosition) -> Result<()> {
+        self.position = position;
+        self.write()
+    }
+
+    fn write(&self) -> Result<()> {
+        let value = json!({
+            "version": STATE_VERSION,
+            "replication": replication_to_json(&self.position),
+        });
+        write_json(&self.path, &value)
+    }
+}
+
+/// Write `value` to `path`. The file is replaced by renaming over it, so an
+/// interruption leaves the old state or the new one.
+fn write_json(path: &Path, value: &Value) -> Result<()> {
+    let json = serde_json::to_string_pretty(value)? + "\n";
+    let temp = path.with_extension("json.tmp");
+    std::fs::write(&temp, json)
+        .and_then(|()| std::fs::rename(&temp, path))
+        .with_context(|| format!("Failed to write '{}'", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_state() {
+        let mut state = DumpState {
+            compress: Codec::Zstd,
+            ..DumpState::default()
+        };
+        state.done.insert(
+            "public.users".into(),
+            Some(DataSection {
+                rows: 3,
+                bytes: 120,
+                sha256: "ab".repeat(32),
+            }),
+        );
+        state.done.insert("public.audit".into(), None);
+        state.partial.insert(
+            "public.orders".into(),
+            Checkpoint {
+                rows: 5000,
+                bytes: 812_345,
+                after: vec![SqlValue::Int(5000), SqlValue::String("eu".into())],
+            },
+        );
+
+        let json = serde_json::to_string(&state.to_json()).unwrap();
+        let read = DumpState::from_json(&serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(read, state);
+
+        let mut value = state.to_json();
+        value["partial"]["public.orders"]["after"] = json!("zz");
+        assert!(DumpState::from_json(&value).is_err());
+    }
+
+    #[test]
+    fn round_trips_restore_state() {
+        let state = RestoreState {
+            input: "s3://backups/db.sql.zst".into(),
+            items: 1234,
+        };
+        let read = RestoreState::from_json(&state.to_json()).unwrap();
+        assert_eq!(read, state);
+        assert!(RestoreState::from_json(&json!({ "version": 1, "input": "db.sql" })).is_err());
+    }
+
+    #[test]
+    fn round_trips_watermarks() {
+        let mut state = WatermarkState {
+            column: "updated_at".into(),
+            ..WatermarkState::default()
+        };
+        state.tables.insert(
+            "public.orders".into(),
+            SqlValue::Timestamp {
+                y: 2024,
+                m: 5,
+                d: 1,
+                hh: 10,
+                mm: 0,
+                ss: 0,
+                us: 123_456,
+            },
+        );
+        state
+            .tables
+            .insert("public.users".into(), SqlValue::Int(42));
+
+        let read = WatermarkState::from_json(&state.to_json()).unwrap();
+        assert_eq!(read, state);
+
+        let mut value = state.to_json();
+        value["tables"]["public.users"] = json!("zz");
+        assert!(WatermarkState::from_json(&value).is_err());
+    }
+
+    #[test]
+    fn records_failed_tables() {
+        let path =
+            std::env::temp_dir().join(format!("migrasquiel-failed-{}.json", std::process::id()));
+        let file = FailedTablesFile::new(&path);
+        file.record("public.orders", "insert error on source row 3")
+            .unwrap();
+        file.record("public.audit", "relation exists").unwrap();
+
+        let read = FailedTables::load(&path).unwrap();
+        assert_eq!(
+            read.tables.keys().collect::<Vec<_>>(),
+            ["public.audit", "public.orders"]
+        );
+        assert_eq!(file.failures(), read.tables.into_iter().collect::<Vec<_>>());
+
+        // A later run with no failures clears the file
+        let file = FailedTablesFile::new(&path);
+        file.remove_if_clean().unwrap();
+        assert!(!path.exists());
+        file.remove_if_clean().unwrap();
+    }
+
+    #[test]
+    fn records_replication_positions() {
+        let path =
+            std::env::temp_dir().join(format!("migrasquiel-position-{}.json", std::process::id()));
+        let start = ReplicationPosition::Binlog {
+            file: "binlog.000003".into(),
+            position: 157,
+            gtid_set: None,
+        };
+        PositionFile::create(&path, start.clone()).unwrap();
+
+        let mut file = PositionFile::open(&path).unwrap();
+        assert_eq!(file.position(), &start);
+        let next = ReplicationPosition::Binlog {
+            file: "binlog.000004".into(),
+            position: 4,
+            gtid_set: None,
+        };
+        file.record(next.clone()).unwrap();
+        assert_eq!(PositionFile::open(&path).unwrap().position(), &next);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,242 @@
+use futures::io::{AsyncRead, AsyncReadExt};
+use futures::TryStreamExt;
+use object_store::aws::AmazonS3Builder;
+use object_store::azure::MicrosoftAzureBuilder;
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, PutPayload, WriteMultipart};
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::pin::Pin;
+use tokio::runtime::Handle;
+use url::Url;
+
+/// URL schemes of the object stores dumps can be written to and read from
+const SCHEMES: &[&str] = &["s3", "gs", "az"];
+
+/// Size of each part of a multipart upload. Stores allow about 10,000 parts,
+/// so this caps a single dump at roughly 320 GB.
+const PART_BYTES: usize = 32 * 1024 * 1024;
+
+/// Parts uploaded at once; writers wait when this many are in flight
+const UPLOAD_CONCURRENCY: usize = 4;
+
+/// Whether `path` is an object storage URL (`s3://`, `gs://` or `az://`)
+/// rather than a local path
+pub fn is_remote(path: &str) -> bool {
+    path.split_once("://")
+        .is_some_and(|(scheme, _)| SCHEMES.contains(&scheme))
+}
+
+/// Whether `path` is an `http://` or `https://` URL, which dumps can be
+/// read from but not written to
+pub fn is_http(path: &str) -> bool {
+    path.split_once("://")
+        .is_some_and(|(scheme, _)| matches!(scheme, "http" | "https"))
+}
+
+/// `location` fit to show: an HTTP URL without its query, which holds the
+/// signature of a presigned URL
+pub fn display_location(location: &str) -> &str {
+    match location.split_once('?') {
+        Some((url, _)) if is_http(location) => url,
+        _ => location,
+    }
+}
+
+/// The store holding `location` and the object's path in it, with
+/// credentials from the environment (`AWS_*`, `GOOGLE_*` or `AZURE_*`)
+fn open_store(location: &str) -> io::Result<(Box<dyn ObjectStore>, ObjectPath)> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+    let url = Url::parse(location).map_err(|e| invalid(format!("{}: {}", location, e)))?;
+    let store: Box<dyn ObjectStore> = match url.scheme() {
+        "s3" => Box::new(
+            AmazonS3Builder::from_env()
+                .with_url(location)
+                .build()
+                .map_err(io::Error::other)?,
+        ),
+        "gs" => Box::new(
+            GoogleCloudStorageBuilder::from_env()
+                .with_url(location)
+                .build()
+                .map_err(io::Error::other)?,
+        ),
+        "az" => Box::new(
+            MicrosoftAzureBuilder::from_env()
+                .with_url(location)
+                .build()
+                .map_err(io::Error::other)?,
+        ),
+        scheme => return Err(invalid(format!("unsupported object store '{}'", scheme))),
+    };
+    let path = ObjectPath::from_url_path(url.path()).map_err(|e| invalid(e.to_string()))?;
+    if path.as_ref().is_empty() {
+        return Err(invalid(format!(
+            "'{}' names a bucket but no object",
+            location
+        )));
+    }
+    Ok((store, path))
+}
+
+/// Run `future` to completion from synchronous code, whether on a thread of
+/// the runtime (through `block_in_place`, as the DuckDB engine does) or on a
+/// writer thread of its own
+fn block_on<F: Future>(runtime: &Handle, future: F) -> F::Output {
+    if Handle::try_current().is_ok() {
+        tokio::task::block_in_place(|| runtime.block_on(future))
+    } else {
+        runtime.block_on(future)
+    }
+}
+
+/// Store `bytes` as the object at `location` in one request
+pub fn put(location: &str, bytes: Vec<u8>) -> io::Result<()> {
+    let runtime = Handle::current();
+    let (store, path) = open_store(location)?;
+    block_on(&runtime, store.put(&path, PutPayload::from(bytes))).map_err(io::Error::other)?;
+    Ok(())
+}
+
+/// A multipart upload, written to like a file. The object only appears once
+/// `finish` completes the upload; dropped before that, the upload is
+/// aborted and no partial dump is left behind.
+pub struct Upload {
+    runtime: Handle,
+    upload: Option<WriteMultipart>,
+}
+
+impl Upload {
+    /// Start uploading to `location`; must be called within the runtime
+    pub fn create(location: &str) -> io::Result<Self> {
+        let runtime = Handle::current();
+        let (store, path) = open_store(location)?;
+        let upload = block_on(&runtime, store.put_multipart(&path)).map_err(io::Error::other)?;
+        Ok(Upload {
+            runtime,
+            upload: Some(WriteMultipart::new_with_chunk_size(upload, PART_BYTES)),
+        })
+    }
+
+    /// Upload the last part and complete the upload
+    pub fn finish(mut self) -> io::Result<()> {
+        let upload = self.upload.take().expect("upload finished twice");
+        block_on(&self.runtime, upload.finish()).map_err(io::Error::other)?;
+        Ok(())
+    }
+}
+
+impl Write for Upload {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let upload = self.upload.as_mut().expect("upload already finished");
+        block_on(&self.runtime, upload.wait_for_capacity(UPLOAD_CONCURRENCY))
+            .map_err(io::Error::other)?;
+        // Full parts are uploaded by tasks spawned on the runtime
+        let _runtime = self.runtime.enter();
+        upload.write(buf);
+        Ok(buf.len())
+    }
+
+    /// Parts are uploaded as they fill; what is left goes up with `finish`
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for Upload {
+    fn drop(&mut self) {
+        if let Some(upload) = self.upload.take() {
+            let _ = block_on(&self.runtime, upload.abort());
+        }
+    }
+}
+
+/// An object, or the body of an HTTP response, streamed down as it is read
+pub struct Download {
+    runtime: Handle,
+    reader: Pin<Box<dyn AsyncRead + Send>>,
+}
+
+impl Download {
+    /// Start downloading `location`, an object storage or HTTP URL; must be
+    /// called within the runtime
+    pub fn open(location: &str) -> io::Result<Self> {
+        let runtime = Handle::current();
+        if is_http(location) {
+            // Errors leave out the URL, which may be presigned
+            let response = block_on(&runtime, reqwest::get(location))
+                .map_err(|e| io::Error::other(e.without_url()))?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "HTTP 404 Not Found",
+                ));
+            }
+            let response = response
+                .error_for_status()
+                .map_err(|e| io::Error::other(e.without_url()))?;
+            let reader = response
+                .bytes_stream()
+                .map_err(|e| io::Error::other(e.without_url()))
+                .into_async_read();
+            return Ok(Download {
+                runtime,
+                reader: Box::pin(reader),
+            });
+        }
+
+        let (store, path) = open_store(location)?;
+        let object = block_on(&runtime, store.get(&path)).map_err(|e| match e {
+            object_store::Error::NotFound { .. } => {
+                io::Error::new(io::ErrorKind::NotFound, e.to_string())
+            }
+            e => io::Error::other(e),
+        })?;
+        let reader = object
+            .into_stream()
+            .map_err(io::Error::other)
+            .into_async_read();
+        Ok(Download {
+            runtime,
+            reader: Box::pin(reader),
+        })
+    }
+}
+
+impl Read for Download {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        block_on(&self.runtime, self.reader.read(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_object_store_urls() {
+        assert!(is_remote("s3://backups/nightly/db.sql.zst"));
+        assert!(is_remote("gs://backups/db.sql"));
+        assert!(is_remote("az://container/db.sql.gz"));
+        assert!(!is_remote("backup.sql"));
+        assert!(!is_remote("-"));
+        assert!(!is_remote("/var/backups/s3://odd.sql"));
+        assert!(!is_remote("https://example.com/db.sql"));
+        assert!(is_http("https://example.com/db.sql"));
+        assert!(!is_http("s3://backups/db.sql"));
+        assert_eq!(
+            display_location("https://example.com/db.sql.gz?X-Amz-Signature=abc"),
+            "https://example.com/db.sql.gz"
+        );
+        assert_eq!(display_location("backup?.sql"), "backup?.sql");
+    }
+
+    #[test]
+    fn needs_an_object_name() {
+        let err = open_store("s3://backups/").err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = open_store("s3://backups").err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::engine::value::SqlValue;
+use anyhow::Result;
+use futures::stream::{BoxStream, StreamExt};
+use std::fmt;
+
+/// `--limit-rows` / `--sample`: copy only part of each table, for a small
+/// development copy of a large database. The sample is systematic (every
+/// Nth row in read order), so the same rows are picked on every run; with
+/// `--chunk-rows` that order is the primary key's.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RowSubset {
+    /// Rows kept per table, after sampling
+    pub limit: Option<u64>,
+    /// Fraction of rows kept, above 0 and at most 1
+    pub sample: Option<f64>,
+}
+
+impl RowSubset {
+    pub fn is_set(&self) -> bool {
+        self.limit.is_some() || self.sample.is_some()
+    }
+
+    /// Rows per keyset query for a table read with `chunk_rows`. A limit
+    /// alone caps the query, so a keyed table is read with a single
+    /// `LIMIT n` instead of in full.
+    pub fn chunk_rows(&self, chunk_rows: Option<usize>) -> Option<usize> {
+        match (self.limit, self.sample) {
+            (Some(limit), None) => {
+                let limit = usize::try_from(limit).unwrap_or(usize::MAX).max(1);
+                Some(chunk_rows.map_or(limit, |rows| rows.min(limit)))
+            }
+            _ => chunk_rows,
+        }
+    }
+
+    /// About how many of `total` rows are kept, for progress bars
+    pub fn expected_rows(&self, total: u64) -> u64 {
+        let sampled = match self.sample {
+            Some(fraction) => (total as f64 * fraction).floor() as u64,
+            None => total,
+        };
+        self.limit.map_or(sampled, |limit| sampled.min(limit))
+    }
+
+    /// Keep the rows of the subset from `rows`; reading stops once the limit
+    /// is reached
+    pub fn apply<'a>(
+        &self,
+        rows: BoxStream<'a, Result<Vec<SqlValue>>>,
+    ) -> BoxStream<'a, Result<Vec<SqlValue>>> {
+        let rows = match self.sample {
+            Some(fraction) => {
+                let mut seen = 0u64;
+                rows.filter(move |row| {
+                    // Keep a row each time the running share passes a whole
+                    // row; errors always pass
+                    let before = (seen as f64 * fraction).floor();
+                    seen += 1;
+                    let keep = row.is_err() || (seen as f64 * fraction).floor() > before;
+                    std::future::ready(keep)
+                })
+                .boxed()
+            }
+            None => rows,
+        };
+        match self.limit {
+            Some(limit) => rows
+                .take(usize::try_from(limit).unwrap_or(usize::MAX))
+                .boxed(),
+            None => rows,
+        }
+    }
+}
+
+impl fmt::Display for RowSubset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(fraction) = self.sample {
+            parts.push(format!("a {}% sample", fraction * 100.0));
+        }
+        if let Some(limit) = self.limit {
+            parts.push(format!("at most {} rows", limit));
+        }
+        write!(f, "{} of each table", parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream::{self, TryStreamExt};
+
+    fn ids(count: i64) -> BoxStream<'static, Result<Vec<SqlValue>>> {
+        stream::iter((1..=count).map(|id| Ok(vec![SqlValue::Int(id)]))).boxed()
+    }
+
+    async fn kept(subset: RowSubset, count: i64) -> Vec<i64> {
+        let rows: Vec<Vec<SqlValue>> = subset.apply(ids(count)).try_collect().await.unwrap();
+        rows.into_iter()
+            .map(|row| match row[0] {
+                SqlValue::Int(id) => id,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn samples_then_limits() {
+        let sample = RowSubset {
+            limit: None,
+            sample: Some(0.25),
+        };
+        assert_eq!(kept(sample, 10).await, [4, 8]);
+        assert_eq!(sample.expected_rows(10), 2);
+
+        let both = RowSubset {
+            limit: Some(3),
+            sample: Some(0.5),
+        };
+        assert_eq!(kept(both, 100).await, [2, 4, 6]);
+        assert_eq!(
+            both.to_string(),
+            "a 50% sample, at most 3 rows of each table"
+        );
+
+        // A limit alone is read as one keyset query
+        let limit = RowSubset {
+            limit: Some(500),
+            sample: None,
+        };
+        assert_eq!(limit.chunk_rows(None), Some(500));
+        assert_eq!(limit.chunk_rows(Some(100)), Some(100));
+        assert_eq!(both.chunk_rows(None), None);
+        assert_eq!(kept(RowSubset::default(), 3).await, [1, 2, 3]);
+    }
+}